@@ -137,6 +137,8 @@ impl Execution {
                 allocated_bytes,
                 allocated_message_bytes,
                 instance_stats,
+                canister_log,
+                wasm_profile,
             },
             deltas,
             instance_or_system_api,
@@ -202,6 +204,8 @@ impl Execution {
                     allocated_message_bytes,
                     num_instructions_left,
                     instance_stats,
+                    canister_log,
+                    wasm_profile,
                 };
                 self.sandbox_manager.controller.execution_finished(
                     protocol::ctlsvc::ExecutionFinishedRequest {
@@ -227,6 +231,8 @@ impl Execution {
                     allocated_bytes,
                     allocated_message_bytes,
                     instance_stats,
+                    canister_log,
+                    wasm_profile,
                 };
 
                 self.sandbox_manager.controller.execution_finished(
@@ -164,7 +164,8 @@ mod tests {
         messages::CallContextId,
         methods::{FuncRef, WasmMethod},
         time::Time,
-        CanisterTimer, ComputeAllocation, Cycles, MemoryAllocation, NumBytes, NumInstructions,
+        CanisterTimer, CanisterTimers, ComputeAllocation, Cycles, MemoryAllocation, NumBytes,
+        NumInstructions,
     };
     use mockall::*;
     use std::collections::{BTreeMap, BTreeSet};
@@ -216,8 +217,13 @@ mod tests {
             SMALL_APP_SUBNET_MAX_SIZE,
             SchedulerConfig::application_subnet().dirty_page_overhead,
             CanisterTimer::Inactive,
+            CanisterTimers::default(),
             0,
             BTreeSet::from([controller]),
+            None,
+            BTreeMap::new(),
+            false,
+            BTreeMap::new(),
         )
     }
 
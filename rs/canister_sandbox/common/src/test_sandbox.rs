@@ -37,6 +37,8 @@ impl sandbox_service::SandboxService for DummySandboxService {
                 wasm_metadata: WasmMetadata::default(),
                 compilation_cost: NumInstructions::from(0),
                 imports_details: WasmImportsDetails::default(),
+                is_wasm64: false,
+                wasm_memory_max_pages: None,
             },
         )))))
     }
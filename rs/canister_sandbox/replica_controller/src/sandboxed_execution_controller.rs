@@ -959,6 +959,8 @@ impl WasmExecutor for SandboxedExecutionController {
             stable_memory,
             exported_globals,
             serialized_module.wasm_metadata.clone(),
+            serialized_module.is_wasm64,
+            serialized_module.wasm_memory_max_pages,
         );
         Ok((
             execution_state,
@@ -37,6 +37,10 @@ pub enum CertificationVersion {
     V14 = 14,
     /// Added subnet metrics in `subnet` subtree.
     V15 = 15,
+    /// Define optional `Request::deadline` and `Response::deadline` fields.
+    V16 = 16,
+    /// Define optional `RequestOrResponse::fragment` field.
+    V17 = 17,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -75,7 +79,7 @@ pub const CURRENT_CERTIFICATION_VERSION: CertificationVersion = CertificationVer
 ///
 /// The replica will panic if requested to certify using a version higher than
 /// this.
-pub const MAX_SUPPORTED_CERTIFICATION_VERSION: CertificationVersion = CertificationVersion::V15;
+pub const MAX_SUPPORTED_CERTIFICATION_VERSION: CertificationVersion = CertificationVersion::V17;
 
 /// Returns a list of all certification versions up to [MAX_SUPPORTED_CERTIFICATION_VERSION].
 pub fn all_supported_versions() -> impl std::iter::Iterator<Item = CertificationVersion> {
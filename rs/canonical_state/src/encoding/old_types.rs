@@ -13,9 +13,243 @@ use crate::CertificationVersion;
 use super::types;
 use crate::encoding::types::{Bytes, Cycles, Funds, Response};
 use ic_protobuf::proxy::ProxyDecodeError;
-use ic_types::xnet::StreamHeader;
+use ic_types::{messages::NO_DEADLINE, xnet::StreamHeader};
 use serde::{Deserialize, Serialize};
 
+// Copy of `types::RequestOrResponse` at canonical version 16 (before the
+// addition of `fragment`).
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RequestOrResponseV16 {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request: Option<types::Request>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<Response>,
+}
+
+impl From<(&ic_types::messages::RequestOrResponse, CertificationVersion)> for RequestOrResponseV16 {
+    fn from(
+        (message, certification_version): (
+            &ic_types::messages::RequestOrResponse,
+            CertificationVersion,
+        ),
+    ) -> Self {
+        use ic_types::messages::RequestOrResponse::*;
+        match message {
+            Request(request) => Self {
+                request: Some((request.as_ref(), certification_version).into()),
+                response: None,
+            },
+            Response(response) => Self {
+                request: None,
+                response: Some((response.as_ref(), certification_version).into()),
+            },
+            Fragment(_) => {
+                unreachable!("Message fragments are only supported from certification version 17")
+            }
+        }
+    }
+}
+
+impl TryFrom<RequestOrResponseV16> for ic_types::messages::RequestOrResponse {
+    type Error = ProxyDecodeError;
+
+    fn try_from(message: RequestOrResponseV16) -> Result<Self, Self::Error> {
+        match message {
+          RequestOrResponseV16 {
+              request: Some(request),
+              response: None,
+          } => Ok(Self::Request(Arc::new(request.try_into()?))),
+          RequestOrResponseV16 {
+              request: None,
+              response: Some(response),
+          } => Ok(Self::Response(Arc::new(response.try_into()?))),
+          other => Err(ProxyDecodeError::Other(format!(
+              "RequestOrResponse: expected exactly one of `request` or `response` to be `Some(_)`, got `{:?}`",
+              other
+          )))
+      }
+    }
+}
+
+// Copy of `types::RequestOrResponse` at canonical version 15 (before the
+// addition of `deadline` to `types::Request` and `types::Response`).
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RequestOrResponseV15 {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request: Option<RequestV15>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<ResponseV15>,
+}
+
+// Copy of `types::Request` at canonical version 15 (before the addition of `deadline`).
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RequestV15 {
+    #[serde(with = "serde_bytes")]
+    pub receiver: Bytes,
+    #[serde(with = "serde_bytes")]
+    pub sender: Bytes,
+    pub sender_reply_callback: u64,
+    pub payment: Funds,
+    pub method_name: String,
+    #[serde(with = "serde_bytes")]
+    pub method_payload: Bytes,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cycles_payment: Option<Cycles>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<types::RequestMetadata>,
+}
+
+// Copy of `types::Response` at canonical version 15 (before the addition of `deadline`).
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResponseV15 {
+    #[serde(with = "serde_bytes")]
+    pub originator: Bytes,
+    #[serde(with = "serde_bytes")]
+    pub respondent: Bytes,
+    pub originator_reply_callback: u64,
+    pub refund: Funds,
+    pub response_payload: types::Payload,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cycles_refund: Option<Cycles>,
+}
+
+impl From<(&ic_types::messages::RequestOrResponse, CertificationVersion)> for RequestOrResponseV15 {
+    fn from(
+        (message, certification_version): (
+            &ic_types::messages::RequestOrResponse,
+            CertificationVersion,
+        ),
+    ) -> Self {
+        use ic_types::messages::RequestOrResponse::*;
+        match message {
+            Request(request) => Self {
+                request: Some((request.as_ref(), certification_version).into()),
+                response: None,
+            },
+            Response(response) => Self {
+                request: None,
+                response: Some((response.as_ref(), certification_version).into()),
+            },
+            Fragment(_) => {
+                unreachable!("Message fragments are only supported from certification version 17")
+            }
+        }
+    }
+}
+
+impl TryFrom<RequestOrResponseV15> for ic_types::messages::RequestOrResponse {
+    type Error = ProxyDecodeError;
+
+    fn try_from(message: RequestOrResponseV15) -> Result<Self, Self::Error> {
+        match message {
+          RequestOrResponseV15 {
+              request: Some(request),
+              response: None,
+          } => Ok(Self::Request(Arc::new(request.try_into()?))),
+          RequestOrResponseV15 {
+              request: None,
+              response: Some(response),
+          } => Ok(Self::Response(Arc::new(response.try_into()?))),
+          other => Err(ProxyDecodeError::Other(format!(
+              "RequestOrResponse: expected exactly one of `request` or `response` to be `Some(_)`, got `{:?}`",
+              other
+          )))
+      }
+    }
+}
+
+impl From<(&ic_types::messages::Request, CertificationVersion)> for RequestV15 {
+    fn from(
+        (request, certification_version): (&ic_types::messages::Request, CertificationVersion),
+    ) -> Self {
+        let types::Request {
+            receiver,
+            sender,
+            sender_reply_callback,
+            payment,
+            method_name,
+            method_payload,
+            cycles_payment,
+            metadata,
+            deadline: _,
+        } = (request, certification_version).into();
+        Self {
+            receiver,
+            sender,
+            sender_reply_callback,
+            payment,
+            method_name,
+            method_payload,
+            cycles_payment,
+            metadata,
+        }
+    }
+}
+
+impl TryFrom<RequestV15> for ic_types::messages::Request {
+    type Error = ProxyDecodeError;
+
+    fn try_from(request: RequestV15) -> Result<Self, Self::Error> {
+        types::Request {
+            receiver: request.receiver,
+            sender: request.sender,
+            sender_reply_callback: request.sender_reply_callback,
+            payment: request.payment,
+            method_name: request.method_name,
+            method_payload: request.method_payload,
+            cycles_payment: request.cycles_payment,
+            metadata: request.metadata,
+            deadline: 0,
+        }
+        .try_into()
+    }
+}
+
+impl From<(&ic_types::messages::Response, CertificationVersion)> for ResponseV15 {
+    fn from(
+        (response, certification_version): (&ic_types::messages::Response, CertificationVersion),
+    ) -> Self {
+        let Response {
+            originator,
+            respondent,
+            originator_reply_callback,
+            refund,
+            response_payload,
+            cycles_refund,
+            deadline: _,
+        } = (response, certification_version).into();
+        Self {
+            originator,
+            respondent,
+            originator_reply_callback,
+            refund,
+            response_payload,
+            cycles_refund,
+        }
+    }
+}
+
+impl TryFrom<ResponseV15> for ic_types::messages::Response {
+    type Error = ProxyDecodeError;
+
+    fn try_from(response: ResponseV15) -> Result<Self, Self::Error> {
+        Response {
+            originator: response.originator,
+            respondent: response.respondent,
+            originator_reply_callback: response.originator_reply_callback,
+            refund: response.refund,
+            response_payload: response.response_payload,
+            cycles_refund: response.cycles_refund,
+            deadline: 0,
+        }
+        .try_into()
+    }
+}
+
 // Copy of `types::RequestOrResponse` at canonical version 13 (before the
 // addition of `metadata` to `types::Request`).
 #[derive(Debug, Serialize, Deserialize)]
@@ -61,6 +295,9 @@ impl From<(&ic_types::messages::RequestOrResponse, CertificationVersion)> for Re
                 request: None,
                 response: Some((response.as_ref(), certification_version).into()),
             },
+            Fragment(_) => {
+                unreachable!("Message fragments are only supported from certification version 17")
+            }
         }
     }
 }
@@ -128,6 +365,7 @@ impl TryFrom<RequestV13> for ic_types::messages::Request {
             method_name: request.method_name,
             method_payload: request.method_payload,
             metadata: None,
+            deadline: NO_DEADLINE,
         })
     }
 }
@@ -182,6 +420,7 @@ impl TryFrom<RequestV3> for ic_types::messages::Request {
             method_name: request.method_name,
             method_payload: request.method_payload,
             metadata: None,
+            deadline: NO_DEADLINE,
         })
     }
 }
@@ -231,6 +470,7 @@ impl TryFrom<ResponseV3> for ic_types::messages::Response {
             originator_reply_callback: response.originator_reply_callback.into(),
             refund: response.refund.cycles.try_into()?,
             response_payload: response.response_payload.try_into()?,
+            deadline: NO_DEADLINE,
         })
     }
 }
@@ -263,6 +503,9 @@ impl From<(&ic_types::messages::RequestOrResponse, CertificationVersion)> for Re
                 request: None,
                 response: Some(ResponseV3::from((resp.as_ref(), certification_version))),
             },
+            Fragment(_) => {
+                unreachable!("Message fragments are only supported from certification version 17")
+            }
         }
     }
 }
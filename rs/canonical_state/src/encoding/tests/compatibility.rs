@@ -25,11 +25,12 @@ use ic_test_utilities::types::{
 use ic_types::{
     crypto::CryptoHash,
     messages::{
-        CallbackId, Payload, RejectContext, Request, RequestMetadata, RequestOrResponse, Response,
+        CallbackId, MessageFragment, Payload, RejectContext, Request, RequestMetadata,
+        RequestOrResponse, Response,
     },
     nominal_cycles::NominalCycles,
     xnet::StreamHeader,
-    CryptoHashOfPartialState, Cycles, Funds, NumBytes, Time,
+    CoarseTime, CryptoHashOfPartialState, Cycles, Funds, NumBytes, Time,
 };
 use serde_cbor::value::Value;
 use std::collections::{BTreeMap, VecDeque};
@@ -340,6 +341,165 @@ fn canonical_encoding_request_v14_plus() {
     }
 }
 
+/// Canonical CBOR encoding of:
+///
+/// ```no_run
+/// RequestOrResponse::Request(
+///     Request {
+///         receiver: canister_test_id(1),
+///         sender: canister_test_id(2),
+///         sender_reply_callback: CallbackId::from(3),
+///         payment: Cycles::new(4),
+///         method_name: "test".to_string(),
+///         method_payload: vec![6],
+///         metadata: None,
+///         deadline: CoarseTime::from_secs_since_unix_epoch(101),
+///     }
+/// )
+/// ```
+///
+/// Expected:
+///
+/// ```text
+/// A1                            # map(1)
+///    00                         # field_index(RequestOrResponse::request)
+///    A7                         # map(7)
+///       00                      # field_index(Request::receiver)
+///       4A                      # bytes(10)
+///          00000000000000010101 # "\x00\x00\x00\x00\x00\x00\x00\x01\x01\x01"
+///       01                      # field_index(Request::sender)
+///       4A                      # bytes(10)
+///          00000000000000020101 # "\x00\x00\x00\x00\x00\x00\x00\x02\x01\x01"
+///       02                      # field_index(Request::sender_reply_callback)
+///       03                      # unsigned(3)
+///       03                      # field_index(Request::payment)
+///       A1                      # map(1)
+///          00                   # field_index(Funds::cycles)
+///          A1                   # map(1)
+///             00                # field_index(Cycles::raw)
+///             04                # unsigned(4)
+///       04                      # field_index(Request::method_name)
+///       64                      # text(4)
+///          74657374             # "test"
+///       05                      # field_index(Request::method_payload)
+///       41                      # bytes(1)
+///          06                   # "\x06"
+///       08                      # field_index(Request::deadline)
+///       18 65                   # unsigned(101)
+/// ```
+/// Used http://cbor.me/ for printing the human friendly output.
+#[test]
+fn canonical_encoding_request_v16_plus() {
+    for certification_version in
+        all_supported_versions().filter(|v| v >= &CertificationVersion::V16)
+    {
+        let request: RequestOrResponse = RequestBuilder::new()
+            .receiver(canister_test_id(1))
+            .sender(canister_test_id(2))
+            .sender_reply_callback(CallbackId::from(3))
+            .payment(Cycles::new(4))
+            .method_name("test".to_string())
+            .method_payload(vec![6])
+            .deadline(CoarseTime::from_secs_since_unix_epoch(101))
+            .build()
+            .into();
+
+        assert_eq!(
+            "A1 00 A7 00 4A 00 00 00 00 00 00 00 01 01 01 01 4A 00 00 00 00 00 00 00 02 01 01 02 03 03 A1 00 A1 00 04 04 64 74 65 73 74 05 41 06 08 18 65",
+            as_hex(&encode_message(&request, certification_version))
+        );
+    }
+}
+
+/// Canonical CBOR encoding of:
+///
+/// ```no_run
+/// RequestOrResponse::Fragment(
+///     MessageFragment {
+///         header: RequestOrResponse::Request(
+///             Request {
+///                 receiver: canister_test_id(1),
+///                 sender: canister_test_id(2),
+///                 sender_reply_callback: CallbackId::from(3),
+///                 payment: Cycles::new(4),
+///                 method_name: "test".to_string(),
+///                 method_payload: vec![],
+///             }
+///         ),
+///         index: 1,
+///         count: 3,
+///         payload: vec![6],
+///     }
+/// )
+/// ```
+///
+/// Expected:
+///
+/// ```text
+/// A1                            # map(1)
+///    02                         # field_index(RequestOrResponse::fragment)
+///    A4                         # map(4)
+///       00                      # field_index(MessageFragment::request)
+///       A6                      # map(6)
+///          00                   # field_index(Request::receiver)
+///          4A                   # bytes(10)
+///             00000000000000010101 # "\x00\x00\x00\x00\x00\x00\x00\x01\x01\x01"
+///          01                   # field_index(Request::sender)
+///          4A                   # bytes(10)
+///             00000000000000020101 # "\x00\x00\x00\x00\x00\x00\x00\x02\x01\x01"
+///          02                   # field_index(Request::sender_reply_callback)
+///          03                   # unsigned(3)
+///          03                   # field_index(Request::payment)
+///          A1                   # map(1)
+///             00                # field_index(Funds::cycles)
+///             A1                # map(1)
+///                00             # field_index(Cycles::raw)
+///                04             # unsigned(4)
+///          04                   # field_index(Request::method_name)
+///          64                   # text(4)
+///             74657374          # "test"
+///          05                   # field_index(Request::method_payload)
+///          40                   # bytes(0)
+///       02                      # field_index(MessageFragment::index)
+///       01                      # unsigned(1)
+///       03                      # field_index(MessageFragment::count)
+///       03                      # unsigned(3)
+///       04                      # field_index(MessageFragment::payload)
+///       41                      # bytes(1)
+///          06                   # "\x06"
+/// ```
+/// Used http://cbor.me/ for printing the human friendly output.
+#[test]
+fn canonical_encoding_request_fragment_v17_plus() {
+    for certification_version in
+        all_supported_versions().filter(|v| v >= &CertificationVersion::V17)
+    {
+        let header: RequestOrResponse = RequestBuilder::new()
+            .receiver(canister_test_id(1))
+            .sender(canister_test_id(2))
+            .sender_reply_callback(CallbackId::from(3))
+            .payment(Cycles::new(4))
+            .method_name("test".to_string())
+            .method_payload(vec![])
+            .build()
+            .into();
+        let fragment = RequestOrResponse::Fragment(
+            MessageFragment {
+                header,
+                index: 1,
+                count: 3,
+                payload: vec![6],
+            }
+            .into(),
+        );
+
+        assert_eq!(
+            "A1 02 A4 00 A6 00 4A 00 00 00 00 00 00 00 01 01 01 01 4A 00 00 00 00 00 00 00 02 01 01 02 03 03 A1 00 A1 00 04 04 64 74 65 73 74 05 40 02 01 03 03 04 41 06",
+            as_hex(&encode_message(&fragment, certification_version))
+        );
+    }
+}
+
 /// Canonical CBOR encoding of:
 ///
 /// ```no_run
@@ -461,6 +621,72 @@ fn canonical_encoding_response() {
     );
 }
 
+/// Canonical CBOR encoding of:
+///
+/// ```no_run
+/// RequestOrResponse::Response(
+///     Response {
+///         originator: canister_test_id(5),
+///         respondent: canister_test_id(4),
+///         originator_reply_callback: CallbackId::from(3),
+///         refund: Cycles::new(2),
+///         response_payload: Payload::Data(vec![1]),
+///         deadline: CoarseTime::from_secs_since_unix_epoch(101),
+///     }
+/// )
+/// ```
+///
+/// Expected:
+///
+/// ```text
+/// A1                            # map(1)
+///    01                         # field_index(RequestOrResponse::response)
+///    A6                         # map(6)
+///       00                      # field_index(Response::originator)
+///       4A                      # bytes(10)
+///          00000000000000050101 # "\x00\x00\x00\x00\x00\x00\x00\x05\x01\x01"
+///       01                      # field_index(Response::respondent)
+///       4A                      # bytes(10)
+///          00000000000000040101 # "\x00\x00\x00\x00\x00\x00\x00\x04\x01\x01"
+///       02                      # field_index(Response::originator_reply_callback)
+///       03                      # unsigned(3)
+///       03                      # field_index(Response::refund)
+///       A1                      # map(1)
+///          00                   # field_index(Funds::cycles)
+///          A1                   # map(1)
+///             00                # field_index(Cycles::raw)
+///             02                # unsigned(2)
+///       04                      # field_index(Response::response_payload)
+///       A1                      # map(1)
+///          00                   # field_index(Payload::data)
+///          41                   # bytes(1)
+///             01                # "\x01"
+///       06                      # field_index(Response::deadline)
+///       18 65                   # unsigned(101)
+/// ```
+/// Used http://cbor.me/ for printing the human friendly output.
+#[test]
+fn canonical_encoding_response_v16_plus() {
+    for certification_version in
+        all_supported_versions().filter(|v| v >= &CertificationVersion::V16)
+    {
+        let response: RequestOrResponse = ResponseBuilder::new()
+            .originator(canister_test_id(5))
+            .respondent(canister_test_id(4))
+            .originator_reply_callback(CallbackId::from(3))
+            .refund(Cycles::new(2))
+            .response_payload(Payload::Data(vec![1]))
+            .deadline(CoarseTime::from_secs_since_unix_epoch(101))
+            .build()
+            .into();
+
+        assert_eq!(
+            "A1 01 A6 00 4A 00 00 00 00 00 00 00 05 01 01 01 4A 00 00 00 00 00 00 00 04 01 01 02 03 03 A1 00 A1 00 02 04 A1 00 41 01 06 18 65",
+            as_hex(&encode_message(&response, certification_version))
+        );
+    }
+}
+
 ///
 /// Canonical CBOR encoding of:
 ///
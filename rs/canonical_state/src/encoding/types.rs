@@ -13,7 +13,7 @@
 use crate::CertificationVersion;
 use ic_error_types::TryFromError;
 use ic_protobuf::proxy::ProxyDecodeError;
-use ic_types::{xnet::StreamIndex, Time};
+use ic_types::{xnet::StreamIndex, CoarseTime, Time};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::VecDeque,
@@ -48,6 +48,25 @@ pub struct RequestOrResponse {
     pub request: Option<Request>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub response: Option<Response>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fragment: Option<MessageFragment>,
+}
+
+/// Canonical representation of `ic_types::messages::MessageFragment`.
+///
+/// Exactly one of `request` or `response` (the payload-less header of the
+/// fragmented message) is `Some(_)`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MessageFragment {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request: Option<Request>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<Response>,
+    pub index: u32,
+    pub count: u32,
+    #[serde(with = "serde_bytes")]
+    pub payload: Bytes,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -77,6 +96,8 @@ pub struct Request {
     pub cycles_payment: Option<Cycles>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub metadata: Option<RequestMetadata>,
+    #[serde(default, skip_serializing_if = "is_zero_u32")]
+    pub deadline: u32,
 }
 
 /// Canonical representation of `ic_types::messages::Response`.
@@ -92,6 +113,8 @@ pub struct Response {
     pub response_payload: Payload,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cycles_refund: Option<Cycles>,
+    #[serde(default, skip_serializing_if = "is_zero_u32")]
+    pub deadline: u32,
 }
 
 /// Canonical representation of `ic_types::funds::Cycles`.
@@ -117,6 +140,10 @@ pub fn is_zero(v: &u64) -> bool {
     *v == 0
 }
 
+pub fn is_zero_u32(v: &u32) -> bool {
+    *v == 0
+}
+
 /// Canonical representation of `ic_types::messages::Payload`.
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -227,11 +254,25 @@ impl From<(&ic_types::messages::RequestOrResponse, CertificationVersion)> for Re
             Request(request) => Self {
                 request: Some((request.as_ref(), certification_version).into()),
                 response: None,
+                fragment: None,
             },
             Response(response) => Self {
                 request: None,
                 response: Some((response.as_ref(), certification_version).into()),
+                fragment: None,
             },
+            Fragment(fragment) => {
+                assert!(
+                    certification_version >= CertificationVersion::V17,
+                    "Message fragments are not supported at certification version {:?}",
+                    certification_version
+                );
+                Self {
+                    request: None,
+                    response: None,
+                    fragment: Some((fragment.as_ref(), certification_version).into()),
+                }
+            }
         }
     }
 }
@@ -244,19 +285,85 @@ impl TryFrom<RequestOrResponse> for ic_types::messages::RequestOrResponse {
             RequestOrResponse {
                 request: Some(request),
                 response: None,
+                fragment: None,
             } => Ok(Self::Request(Arc::new(request.try_into()?))),
             RequestOrResponse {
                 request: None,
                 response: Some(response),
+                fragment: None,
             } => Ok(Self::Response(Arc::new(response.try_into()?))),
+            RequestOrResponse {
+                request: None,
+                response: None,
+                fragment: Some(fragment),
+            } => Ok(Self::Fragment(Arc::new(fragment.try_into()?))),
             other => Err(ProxyDecodeError::Other(format!(
-                "RequestOrResponse: expected exactly one of `request` or `response` to be `Some(_)`, got `{:?}`",
+                "RequestOrResponse: expected exactly one of `request`, `response` or `fragment` to be `Some(_)`, got `{:?}`",
                 other
             )))
         }
     }
 }
 
+impl From<(&ic_types::messages::MessageFragment, CertificationVersion)> for MessageFragment {
+    fn from(
+        (fragment, certification_version): (
+            &ic_types::messages::MessageFragment,
+            CertificationVersion,
+        ),
+    ) -> Self {
+        use ic_types::messages::RequestOrResponse::*;
+        let (request, response) = match &fragment.header {
+            Request(request) => (Some((request.as_ref(), certification_version).into()), None),
+            Response(response) => (
+                None,
+                Some((response.as_ref(), certification_version).into()),
+            ),
+            Fragment(_) => unreachable!("Fragment of a message fragment"),
+        };
+        Self {
+            request,
+            response,
+            index: fragment.index,
+            count: fragment.count,
+            payload: fragment.payload.clone(),
+        }
+    }
+}
+
+impl TryFrom<MessageFragment> for ic_types::messages::MessageFragment {
+    type Error = ProxyDecodeError;
+
+    fn try_from(fragment: MessageFragment) -> Result<Self, Self::Error> {
+        let header = match (fragment.request, fragment.response) {
+            (Some(request), None) => {
+                ic_types::messages::RequestOrResponse::Request(Arc::new(request.try_into()?))
+            }
+            (None, Some(response)) => {
+                ic_types::messages::RequestOrResponse::Response(Arc::new(response.try_into()?))
+            }
+            (request, response) => {
+                return Err(ProxyDecodeError::Other(format!(
+                    "MessageFragment: expected exactly one of `request` or `response` to be `Some(_)`, got `{:?}` and `{:?}`",
+                    request, response
+                )))
+            }
+        };
+        if fragment.index >= fragment.count {
+            return Err(ProxyDecodeError::Other(format!(
+                "MessageFragment: index {} out of range 0..{}",
+                fragment.index, fragment.count
+            )));
+        }
+        Ok(Self {
+            header,
+            index: fragment.index,
+            count: fragment.count,
+            payload: fragment.payload,
+        })
+    }
+}
+
 impl From<&ic_types::messages::RequestMetadata> for RequestMetadata {
     fn from(metadata: &ic_types::messages::RequestMetadata) -> Self {
         RequestMetadata {
@@ -296,6 +403,11 @@ impl From<(&ic_types::messages::Request, CertificationVersion)> for Request {
             method_payload: request.method_payload.clone(),
             cycles_payment: None,
             metadata,
+            deadline: if certification_version >= CertificationVersion::V16 {
+                request.deadline.as_secs_since_unix_epoch()
+            } else {
+                0
+            },
         }
     }
 }
@@ -332,6 +444,7 @@ impl TryFrom<Request> for ic_types::messages::Request {
             method_name: request.method_name,
             method_payload: request.method_payload,
             metadata: request.metadata.map(From::from),
+            deadline: CoarseTime::from_secs_since_unix_epoch(request.deadline),
         })
     }
 }
@@ -351,6 +464,11 @@ impl From<(&ic_types::messages::Response, CertificationVersion)> for Response {
             refund: funds,
             response_payload: (&response.response_payload, certification_version).into(),
             cycles_refund: None,
+            deadline: if certification_version >= CertificationVersion::V16 {
+                response.deadline.as_secs_since_unix_epoch()
+            } else {
+                0
+            },
         }
     }
 }
@@ -375,6 +493,7 @@ impl TryFrom<Response> for ic_types::messages::Response {
             originator_reply_callback: response.originator_reply_callback.into(),
             refund,
             response_payload: response.response_payload.try_into()?,
+            deadline: CoarseTime::from_secs_since_unix_epoch(response.deadline),
         })
     }
 }
@@ -242,6 +242,8 @@ mod tests {
             metadata: WasmMetadata::new(metadata),
             last_executed_round: ExecutionRound::from(0),
             next_scheduled_method: NextScheduledMethod::default(),
+            is_wasm64: false,
+            wasm_memory_max_pages: None,
         };
 
         canister_state.execution_state = Some(execution_state);
@@ -611,6 +613,7 @@ mod tests {
                 subnet_type: SubnetType::Application,
                 subnet_features: SubnetFeatures::default(),
                 ecdsa_keys_held: BTreeSet::new(),
+                max_inter_canister_payload_in_bytes: None,
             },
             subnet_test_id(1) => SubnetTopology {
                 public_key: vec![5, 6, 7, 8],
@@ -618,6 +621,7 @@ mod tests {
                 subnet_type: SubnetType::Application,
                 subnet_features: SubnetFeatures::default(),
                 ecdsa_keys_held: BTreeSet::new(),
+                max_inter_canister_payload_in_bytes: None,
             }
         };
         fn id_range(from: u64, to: u64) -> CanisterIdRange {
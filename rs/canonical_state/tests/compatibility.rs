@@ -1,9 +1,12 @@
 use ic_base_types::PrincipalId;
 use ic_canonical_state::{
     encoding::{
-        old_types::{RequestOrResponseV13, RequestOrResponseV3, StreamHeaderV6, SystemMetadataV9},
+        old_types::{
+            RequestOrResponseV13, RequestOrResponseV15, RequestOrResponseV16, RequestOrResponseV3,
+            StreamHeaderV6, SystemMetadataV9,
+        },
         types::{
-            RequestOrResponse as RequestOrResponseV14, StreamHeader as StreamHeaderV8,
+            RequestOrResponse as RequestOrResponseV17, StreamHeader as StreamHeaderV8,
             SubnetMetrics as SubnetMetricsV15, SystemMetadata as SystemMetadataV10,
         },
         CborProxyDecoder, CborProxyEncoder,
@@ -202,10 +205,19 @@ pub(crate) fn arb_valid_versioned_message(
         (
             arbitrary::valid_request_or_response_for_certification_version(
                 // From version 14 and on, pairwise comparisons must support the case of `metadata.is_some()`.
-                MAX_SUPPORTED_CERTIFICATION_VERSION
+                // Version 16 introduces a new field `deadline` for `Request` and `Response`. For
+                // version 15 and below, this field is always zero.
+                CertificationVersion::V15
             ),
             Just(CertificationVersion::V14..=MAX_SUPPORTED_CERTIFICATION_VERSION)
         ),
+        (
+            arbitrary::valid_request_or_response_for_certification_version(
+                // From version 16 and on, pairwise comparisons must support non-zero deadlines.
+                MAX_SUPPORTED_CERTIFICATION_VERSION
+            ),
+            Just(CertificationVersion::V16..=MAX_SUPPORTED_CERTIFICATION_VERSION)
+        ),
     ]
 }
 
@@ -228,11 +240,25 @@ lazy_static! {
             |v| RequestOrResponseV13::proxy_decode(v),
         ),
         #[allow(clippy::redundant_closure)]
+        VersionedEncoding::new(
+            CertificationVersion::V0..=CertificationVersion::V15,
+            "RequestOrResponseV15",
+            |v| RequestOrResponseV15::proxy_encode(v),
+            |v| RequestOrResponseV15::proxy_decode(v),
+        ),
+        #[allow(clippy::redundant_closure)]
+        VersionedEncoding::new(
+            CertificationVersion::V0..=CertificationVersion::V16,
+            "RequestOrResponseV16",
+            |v| RequestOrResponseV16::proxy_encode(v),
+            |v| RequestOrResponseV16::proxy_decode(v),
+        ),
+        #[allow(clippy::redundant_closure)]
         VersionedEncoding::new(
             CertificationVersion::V0..=MAX_SUPPORTED_CERTIFICATION_VERSION,
             "RequestOrResponse",
-            |v| RequestOrResponseV14::proxy_encode(v),
-            |v| RequestOrResponseV14::proxy_decode(v),
+            |v| RequestOrResponseV17::proxy_encode(v),
+            |v| RequestOrResponseV17::proxy_decode(v),
         ),
     ];
 }
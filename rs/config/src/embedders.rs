@@ -61,6 +61,9 @@ pub const STABLE_MEMORY_DIRTY_PAGE_LIMIT: u64 = 8 * GiB / (PAGE_SIZE as u64);
 // Maximum number of stable memory pages that a single message execution
 // is allowed to access.
 pub const STABLE_MEMORY_ACCESSED_PAGE_LIMIT: u64 = 8 * GiB / (PAGE_SIZE as u64);
+/// The maximum size of a 64-bit Wasm memory. The limit is checked during
+/// validation and determines the size of the write barrier bytemap.
+pub(crate) const MAX_WASM64_MEMORY_SIZE: NumBytes = NumBytes::new(6 * GiB);
 
 #[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
 pub struct FeatureFlags {
@@ -70,6 +73,21 @@ pub struct FeatureFlags {
     /// Track dirty pages with a write barrier instead of the signal handler.
     pub write_barrier: FlagStatus,
     pub wasm_native_stable_memory: FlagStatus,
+    /// Record the output of `debug_print` and trap messages in the canister
+    /// log, which can be fetched with `fetch_canister_logs`.
+    pub canister_logging: FlagStatus,
+    /// Accept Wasm modules that declare a 64-bit memory (the `memory64`
+    /// proposal).
+    pub wasm64: FlagStatus,
+    /// Capture a Wasm backtrace when a canister traps and report it in the
+    /// error message and the canister log. Function names are taken from the
+    /// `name` custom section of the module.
+    pub canister_backtrace: FlagStatus,
+    /// Instrument canister code to record the instructions executed by each
+    /// Wasm function together with its call stack. This is a tool for local
+    /// optimisation of canister code in `drun` and `StateMachine` and must
+    /// never be enabled on a replica.
+    pub wasm_profiler: FlagStatus,
 }
 
 impl FeatureFlags {
@@ -78,6 +96,10 @@ impl FeatureFlags {
             rate_limiting_of_debug_prints: FlagStatus::Enabled,
             write_barrier: FlagStatus::Disabled,
             wasm_native_stable_memory: FlagStatus::Enabled,
+            canister_logging: FlagStatus::Disabled,
+            wasm64: FlagStatus::Disabled,
+            canister_backtrace: FlagStatus::Disabled,
+            wasm_profiler: FlagStatus::Disabled,
         }
     }
 }
@@ -144,6 +166,11 @@ pub struct Config {
     // can access.
     pub stable_memory_accessed_page_limit: NumPages,
 
+    /// Maximum size of a 64-bit Wasm memory in bytes. Only relevant if the
+    /// `wasm64` feature flag is enabled. Capped at the maximum stable memory
+    /// size when the hypervisor is created.
+    pub max_wasm64_memory_size: NumBytes,
+
     /// Sandbox process eviction does not activate if the number of sandbox
     /// processes is below this threshold.
     pub min_sandbox_count: usize,
@@ -188,6 +215,7 @@ impl Config {
             metering_type: MeteringType::New,
             stable_memory_dirty_page_limit: NumPages::new(STABLE_MEMORY_DIRTY_PAGE_LIMIT),
             stable_memory_accessed_page_limit: NumPages::new(STABLE_MEMORY_ACCESSED_PAGE_LIMIT),
+            max_wasm64_memory_size: MAX_WASM64_MEMORY_SIZE,
             min_sandbox_count: DEFAULT_MIN_SANDBOX_COUNT,
             max_sandbox_count: DEFAULT_MAX_SANDBOX_COUNT,
             max_sandbox_idle_time: DEFAULT_MAX_SANDBOX_IDLE_TIME,
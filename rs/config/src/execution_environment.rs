@@ -5,7 +5,7 @@ use ic_types::{
     Cycles, NumBytes, NumInstructions, MAX_STABLE_MEMORY_IN_BYTES, MAX_WASM_MEMORY_IN_BYTES,
 };
 use serde::{Deserialize, Serialize};
-use std::{str::FromStr, time::Duration};
+use std::{path::PathBuf, str::FromStr, time::Duration};
 
 const MIB: u64 = 1024 * 1024;
 const GIB: u64 = MIB * 1024;
@@ -72,6 +72,10 @@ pub(crate) const MAX_INSTRUCTIONS_PER_COMPOSITE_QUERY_CALL: u64 = 5_000_000_000;
 /// The maximum time in seconds a query call is allowed to run.
 pub(crate) const MAX_TIME_PER_COMPOSITE_QUERY_CALL: Duration = Duration::from_secs(10);
 
+/// The total number of instructions a non-replicated query may execute across
+/// all its slices if deterministic time slicing of queries is enabled.
+const MAX_INSTRUCTIONS_PER_QUERY_WITH_DTS: NumInstructions = NumInstructions::new(20_000_000_000);
+
 /// This would allow 100 calls with the current MAX_INSTRUCTIONS_PER_COMPOSITE_QUERY_CALL
 pub const INSTRUCTION_OVERHEAD_PER_QUERY_CALL: u64 = 50_000_000;
 
@@ -116,9 +120,24 @@ const BITCOIN_MAINNET_SOFT_LAUNCH_CANISTER_ID: &str = "gsvzx-syaaa-aaaan-aaabq-c
 /// The capacity of the Wasm compilation cache.
 pub const MAX_COMPILATION_CACHE_SIZE: NumBytes = NumBytes::new(10 * GIB);
 
+/// The disk space that the persistent tier of the Wasm compilation cache may use.
+pub const MAX_COMPILATION_CACHE_DISK_SIZE: NumBytes = NumBytes::new(20 * GIB);
+
 /// Maximum number of controllers allowed in a request (specified in the interface spec).
 pub const MAX_ALLOWED_CONTROLLERS_COUNT: usize = 10;
 
+/// Maximum number of environment variables a canister can have.
+pub const MAX_ENVIRONMENT_VARIABLES: usize = 20;
+
+/// Maximum length in bytes of the name of an environment variable.
+pub const MAX_ENVIRONMENT_VARIABLE_NAME_LENGTH: usize = 128;
+
+/// Maximum length in bytes of the value of an environment variable.
+pub const MAX_ENVIRONMENT_VARIABLE_VALUE_LENGTH: usize = 128;
+
+/// Maximum number of named timers a canister can have active at the same time.
+pub const MAX_CANISTER_TIMERS: usize = 100;
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
 #[serde(default)]
 pub struct Config {
@@ -169,6 +188,15 @@ pub struct Config {
     /// Maximum number of controllers a canister can have.
     pub max_controllers: usize,
 
+    /// Maximum number of environment variables a canister can have.
+    pub max_environment_variables: usize,
+
+    /// Maximum length in bytes of the name of an environment variable.
+    pub max_environment_variable_name_length: usize,
+
+    /// Maximum length in bytes of the value of an environment variable.
+    pub max_environment_variable_value_length: usize,
+
     /// Indicates whether canisters sandboxing is enabled or not.
     pub canister_sandboxing_flag: FlagStatus,
 
@@ -212,12 +240,29 @@ pub struct Config {
     /// Indicates whether deterministic time slicing is enabled or not.
     pub deterministic_time_slicing: FlagStatus,
 
+    /// Indicates whether non-replicated queries may run across multiple
+    /// execution slices, with queries of other canisters executing between
+    /// the slices. Has no effect unless `deterministic_time_slicing` is
+    /// enabled.
+    pub query_deterministic_time_slicing: FlagStatus,
+
+    /// The total number of instructions a non-replicated query may execute
+    /// across all its slices if `query_deterministic_time_slicing` is enabled.
+    /// Each slice is limited by `max_instructions_per_message_without_dts`.
+    /// The instruction limit of the query call graph is raised to this value
+    /// if it is lower.
+    pub max_instructions_per_query_with_dts: NumInstructions,
+
     /// Bitcoin configuration.
     pub bitcoin: BitcoinConfig,
 
     /// Indicates whether composite queries are available or not.
     pub composite_queries: FlagStatus,
 
+    /// Indicates whether composite queries can call canisters on other
+    /// subnets. If disabled, such calls are rejected as before.
+    pub cross_subnet_composite_queries: FlagStatus,
+
     /// Indicates whether replica side query caching is enabled.
     pub query_caching: FlagStatus,
 
@@ -227,12 +272,22 @@ pub struct Config {
     /// The capacity of the Wasm compilation cache.
     pub max_compilation_cache_size: NumBytes,
 
+    /// The directory in which compiled Wasm modules are persisted across
+    /// replica restarts. If `None`, the compilation cache is in-memory only.
+    pub compilation_cache_dir: Option<PathBuf>,
+
+    /// The disk space that the persisted compiled Wasm modules may use.
+    pub max_compilation_cache_disk_size: NumBytes,
+
     /// Indicate whether query stats should be collected or not.
     pub query_stats_aggregation: FlagStatus,
 
     /// Indicate whether the Wasm chunk store feature has been enabled or not.
     pub wasm_chunk_store: FlagStatus,
 
+    /// Indicate whether canister snapshots feature has been enabled or not.
+    pub canister_snapshots: FlagStatus,
+
     /// The duration a stop_canister has to stop the canister before timing out.
     pub stop_canister_timeout_duration: Duration,
 }
@@ -268,6 +323,9 @@ impl Default for Config {
             // The default freeze threshold is 30 days.
             default_freeze_threshold: NumSeconds::from(30 * 24 * 60 * 60),
             max_controllers: MAX_ALLOWED_CONTROLLERS_COUNT,
+            max_environment_variables: MAX_ENVIRONMENT_VARIABLES,
+            max_environment_variable_name_length: MAX_ENVIRONMENT_VARIABLE_NAME_LENGTH,
+            max_environment_variable_value_length: MAX_ENVIRONMENT_VARIABLE_VALUE_LENGTH,
             canister_sandboxing_flag: FlagStatus::Enabled,
             query_execution_threads_total: QUERY_EXECUTION_THREADS_TOTAL,
             query_scheduling_time_slice_per_canister: QUERY_SCHEDULING_TIME_SLICE_PER_CANISTER,
@@ -285,6 +343,8 @@ impl Default for Config {
             // best-effort canisters have sufficient compute to make progress.
             allocatable_compute_capacity_in_percent: 50,
             deterministic_time_slicing: FlagStatus::Enabled,
+            query_deterministic_time_slicing: FlagStatus::Disabled,
+            max_instructions_per_query_with_dts: MAX_INSTRUCTIONS_PER_QUERY_WITH_DTS,
             bitcoin: BitcoinConfig {
                 privileged_access: vec![
                     bitcoin_testnet_canister_id,
@@ -295,11 +355,15 @@ impl Default for Config {
                 mainnet_canister_id: Some(bitcoin_mainnet_canister_id),
             },
             composite_queries: FlagStatus::Enabled,
+            cross_subnet_composite_queries: FlagStatus::Disabled,
             query_caching: FlagStatus::Enabled,
             query_cache_capacity: QUERY_CACHE_CAPACITY,
             max_compilation_cache_size: MAX_COMPILATION_CACHE_SIZE,
+            compilation_cache_dir: None,
+            max_compilation_cache_disk_size: MAX_COMPILATION_CACHE_DISK_SIZE,
             query_stats_aggregation: FlagStatus::Disabled,
             wasm_chunk_store: FlagStatus::Disabled,
+            canister_snapshots: FlagStatus::Disabled,
             stop_canister_timeout_duration: STOP_CANISTER_TIMEOUT_DURATION,
         }
     }
@@ -78,6 +78,7 @@ pub(super) fn make_reshare_dealings_response(
                             }
                             .encode(),
                         ),
+                        deadline: ic_types::messages::NO_DEADLINE,
                     });
                 }
             }
@@ -69,6 +69,7 @@ pub(crate) fn update_signature_agreements(
                 }
                 .encode(),
             ),
+            deadline: ic_types::messages::NO_DEADLINE,
         };
         completed.insert(*request_id, ecdsa::CompletedSignature::Unreported(response));
     }
@@ -1025,6 +1025,7 @@ impl EcdsaSubnetConfig {
                 subnet_type: SubnetType::Application.into(),
                 is_halted: false,
                 halt_at_cup_height: false,
+                max_inter_canister_payload_in_bytes: None,
                 max_instructions_per_message: 5_000_000_000,
                 max_instructions_per_round: 7_000_000_000,
                 max_instructions_per_install_code: 200_000_000_000,
@@ -25,7 +25,10 @@ use ic_replicated_state::{
 };
 use ic_types::{
     canister_http::MAX_CANISTER_HTTP_RESPONSE_BYTES,
-    messages::{Request, Response, SignedIngressContent, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES},
+    messages::{
+        Request, Response, SignedIngressContent, MAX_FRAGMENTED_INTER_CANISTER_PAYLOAD_IN_BYTES,
+        MAX_INTER_CANISTER_PAYLOAD_IN_BYTES,
+    },
     CanisterId, ComputeAllocation, Cycles, MemoryAllocation, NumBytes, NumInstructions, SubnetId,
 };
 use prometheus::IntCounter;
@@ -730,11 +733,9 @@ impl CyclesAccountManager {
         //   - the fee to send the request (by size)
         //   - the fee for the largest possible response
         //   - the fee for executing the largest allowed response when it eventually arrives.
-        let transmission_fee = self.scale_cost(
-            self.config.xnet_call_fee
-                + self.config.xnet_byte_transmission_fee * request.payload_size_bytes().get(),
-            subnet_size,
-        ) + prepayment_for_response_transmission;
+        let transmission_fee = self
+            .xnet_call_request_transmission_fee(request.payload_size_bytes(), subnet_size)
+            + prepayment_for_response_transmission;
 
         let fee = transmission_fee + prepayment_for_response_execution;
 
@@ -765,6 +766,29 @@ impl CyclesAccountManager {
         ]))
     }
 
+    /// Returns the fee for performing an xnet call and transmitting a request
+    /// with the given payload size (method name plus argument).
+    fn xnet_call_request_transmission_fee(
+        &self,
+        payload_size: NumBytes,
+        subnet_size: usize,
+    ) -> Cycles {
+        self.scale_cost(
+            self.config.xnet_call_fee + self.config.xnet_byte_transmission_fee * payload_size.get(),
+            subnet_size,
+        )
+    }
+
+    /// Returns the total amount of cycles withdrawn by `withdraw_request_cycles`
+    /// when sending a request with the given payload size (method name plus
+    /// argument): the request transmission fee plus the prepayments for
+    /// transmitting and executing the largest possible response.
+    pub fn xnet_call_total_fee(&self, payload_size: NumBytes, subnet_size: usize) -> Cycles {
+        self.xnet_call_request_transmission_fee(payload_size, subnet_size)
+            + self.prepayment_for_response_transmission(subnet_size)
+            + self.prepayment_for_response_execution(subnet_size)
+    }
+
     /// Returns the amount of cycles required for executing the longest-running
     /// response callback.
     pub fn prepayment_for_response_execution(&self, subnet_size: usize) -> Cycles {
@@ -780,8 +804,34 @@ impl CyclesAccountManager {
         )
     }
 
+    /// Returns the fee for transmitting the bytes of a response payload of the
+    /// given size beyond `MAX_INTER_CANISTER_PAYLOAD_IN_BYTES`.
+    ///
+    /// Responses routed as XNet message fragments may be larger than the
+    /// `MAX_INTER_CANISTER_PAYLOAD_IN_BYTES` covered by the caller's prepayment
+    /// for response transmission; the responding canister pays for the extra
+    /// bytes when it replies.
+    pub fn response_transmission_fee_beyond_prepayment(
+        &self,
+        payload_size: NumBytes,
+        subnet_size: usize,
+    ) -> Cycles {
+        let extra_bytes = payload_size
+            .get()
+            .saturating_sub(MAX_INTER_CANISTER_PAYLOAD_IN_BYTES.get());
+        self.scale_cost(
+            self.config.xnet_byte_transmission_fee * extra_bytes,
+            subnet_size,
+        )
+    }
+
     /// Returns the refund cycles for the response transmission bytes reserved at
     /// the initial call time.
+    ///
+    /// Responses routed as XNet message fragments may be larger than the
+    /// `MAX_INTER_CANISTER_PAYLOAD_IN_BYTES` covered by the prepayment, in
+    /// which case no cycles are refunded. The extra bytes are paid for by the
+    /// responding canister (see `response_transmission_fee_beyond_prepayment`).
     pub fn refund_for_response_transmission(
         &self,
         log: &ReplicaLogger,
@@ -790,7 +840,7 @@ impl CyclesAccountManager {
         prepayment_for_response_transmission: Cycles,
         subnet_size: usize,
     ) -> Cycles {
-        let max_expected_bytes = MAX_INTER_CANISTER_PAYLOAD_IN_BYTES.get();
+        let max_expected_bytes = MAX_FRAGMENTED_INTER_CANISTER_PAYLOAD_IN_BYTES.get();
         let transmitted_bytes = response.payload_size_bytes().get();
         debug_assert!(transmitted_bytes <= max_expected_bytes);
         if max_expected_bytes < transmitted_bytes {
@@ -1154,6 +1204,10 @@ mod tests {
                 None,
                 Some(default_freezing_limit),
                 None,
+                None,
+                None,
+                None,
+                None,
             ),
             sender_canister_version: None, // ingress messages are not supposed to set this field
         };
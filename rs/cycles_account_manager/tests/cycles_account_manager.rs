@@ -14,12 +14,14 @@ use ic_test_utilities::{
     state::{new_canister_state, SystemStateBuilder},
     types::{
         ids::{canister_test_id, subnet_test_id, user_test_id},
-        messages::SignedIngressBuilder,
+        messages::{RequestBuilder, SignedIngressBuilder},
     },
 };
 use ic_test_utilities_logger::with_test_replica_logger;
 use ic_types::{
-    messages::{extract_effective_canister_id, SignedIngressContent},
+    messages::{
+        extract_effective_canister_id, SignedIngressContent, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES,
+    },
     nominal_cycles::NominalCycles,
     CanisterId, ComputeAllocation, Cycles, MemoryAllocation, NumBytes, NumInstructions,
 };
@@ -677,6 +679,69 @@ fn withdraw_for_transfer_does_not_consume_cycles() {
     assert_eq!(consumed_cycles_before, consumed_cycles_after);
 }
 
+#[test]
+fn xnet_call_total_fee_matches_withdrawn_request_cycles() {
+    let system_state = SystemStateBuilder::new().build();
+    let cycles_account_manager = CyclesAccountManagerBuilder::new()
+        .with_subnet_type(SubnetType::Application)
+        .build();
+    let request = RequestBuilder::new()
+        .method_name("test")
+        .method_payload(vec![0; 1000])
+        .build();
+    let initial_balance = Cycles::new(5_000_000_000_000);
+    let mut balance = initial_balance;
+    cycles_account_manager
+        .withdraw_request_cycles(
+            system_state.canister_id,
+            &mut balance,
+            system_state.freeze_threshold,
+            system_state.memory_allocation,
+            NumBytes::from(0),
+            NumBytes::from(0),
+            ComputeAllocation::default(),
+            &request,
+            cycles_account_manager.prepayment_for_response_execution(SMALL_APP_SUBNET_MAX_SIZE),
+            cycles_account_manager.prepayment_for_response_transmission(SMALL_APP_SUBNET_MAX_SIZE),
+            SMALL_APP_SUBNET_MAX_SIZE,
+            system_state.reserved_balance(),
+        )
+        .unwrap();
+
+    assert_eq!(
+        initial_balance - balance,
+        cycles_account_manager
+            .xnet_call_total_fee(request.payload_size_bytes(), SMALL_APP_SUBNET_MAX_SIZE)
+    );
+}
+
+#[test]
+fn response_transmission_fee_beyond_prepayment_charges_only_extra_bytes() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new()
+        .with_subnet_type(SubnetType::Application)
+        .build();
+    let config = CyclesAccountManagerConfig::application_subnet();
+
+    for payload_size in [0, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES.get()] {
+        assert_eq!(
+            cycles_account_manager.response_transmission_fee_beyond_prepayment(
+                NumBytes::from(payload_size),
+                SMALL_APP_SUBNET_MAX_SIZE,
+            ),
+            Cycles::zero()
+        );
+    }
+
+    let extra_bytes = 1024 * 1024;
+    assert_eq!(
+        cycles_account_manager.response_transmission_fee_beyond_prepayment(
+            MAX_INTER_CANISTER_PAYLOAD_IN_BYTES + NumBytes::from(extra_bytes),
+            SMALL_APP_SUBNET_MAX_SIZE,
+        ),
+        config.xnet_byte_transmission_fee * extra_bytes
+    );
+}
+
 #[test]
 fn consume_cycles_updates_consumed_cycles() {
     let mut system_state = SystemStateBuilder::new().build();
@@ -2,7 +2,7 @@
 
 use crate::message::{msg_stream_from_file, Message};
 use hex::encode;
-use ic_config::{subnet_config::SubnetConfig, Config};
+use ic_config::{flag_status::FlagStatus, subnet_config::SubnetConfig, Config};
 use ic_cycles_account_manager::CyclesAccountManager;
 use ic_error_types::{ErrorCode, UserError};
 use ic_execution_environment::ExecutionServices;
@@ -42,7 +42,8 @@ use ic_types::{
 use rand::distributions::{Distribution, Uniform};
 use slog::{Drain, Logger};
 use std::collections::BTreeMap;
-use std::fs::OpenOptions;
+use std::fs::{File, OpenOptions};
+use std::io::BufWriter;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::{thread::sleep, time::Duration};
@@ -62,6 +63,9 @@ pub struct DrunOptions {
     pub log_file: Option<PathBuf>,
     pub instruction_limit: Option<u64>,
     pub subnet_type: SubnetType,
+    /// If set, canister executions are profiled and the profile is written to
+    /// this file as folded stacks that `inferno-flamegraph` can render.
+    pub profile_file: Option<PathBuf>,
 }
 
 /// Deliver a single message to the Message Routing layer
@@ -161,6 +165,7 @@ pub fn run_drun(uo: DrunOptions) -> Result<(), String> {
         log_file,
         instruction_limit,
         subnet_type,
+        profile_file,
     } = uo;
     // Hardcoded magic values to create a ReplicaConfig that parses.
     let mut subnet_config = SubnetConfig::new(subnet_type);
@@ -175,6 +180,10 @@ pub fn run_drun(uo: DrunOptions) -> Result<(), String> {
         cfg.hypervisor.max_query_call_graph_instructions = NumInstructions::new(instruction_limit);
     }
 
+    if profile_file.is_some() {
+        cfg.hypervisor.embedders_config.feature_flags.wasm_profiler = FlagStatus::Enabled;
+    }
+
     let subnet_id = SubnetId::from(PrincipalId::new_subnet_test_id(0));
     let root_subnet_id = SubnetId::from(PrincipalId::new_subnet_test_id(1));
     let replica_config = ReplicaConfig {
@@ -214,19 +223,21 @@ pub fn run_drun(uo: DrunOptions) -> Result<(), String> {
         None,
         ic_types::malicious_flags::MaliciousFlags::default(),
     ));
+    let execution_services = ExecutionServices::setup_execution(
+        log.clone().into(),
+        &metrics_registry,
+        replica_config.subnet_id,
+        subnet_type,
+        subnet_config.scheduler_config,
+        cfg.hypervisor.clone(),
+        Arc::clone(&cycles_account_manager),
+        Arc::clone(&state_manager) as Arc<_>,
+        state_manager.get_fd_factory(),
+        None,
+    );
+    let wasm_profiler = execution_services.wasm_profiler.clone();
     let (_, ingress_history_writer, ingress_hist_reader, query_handler, _, _, scheduler) =
-        ExecutionServices::setup_execution(
-            log.clone().into(),
-            &metrics_registry,
-            replica_config.subnet_id,
-            subnet_type,
-            subnet_config.scheduler_config,
-            cfg.hypervisor.clone(),
-            Arc::clone(&cycles_account_manager),
-            Arc::clone(&state_manager) as Arc<_>,
-            state_manager.get_fd_factory(),
-        )
-        .into_parts();
+        execution_services.into_parts();
 
     let _metrics_endpoint = MetricsHttpEndpoint::new_insecure(
         tokio::runtime::Handle::current(),
@@ -249,7 +260,7 @@ pub fn run_drun(uo: DrunOptions) -> Result<(), String> {
         MaliciousFlags::default(),
     );
 
-    msg_stream.try_for_each(|parse_result| {
+    let result = msg_stream.try_for_each(|parse_result| {
         parse_result.map(|msg| match msg {
             Message::Install(msg) => {
                 deliver_message(
@@ -288,7 +299,27 @@ pub fn run_drun(uo: DrunOptions) -> Result<(), String> {
                 );
             }
         })
-    })
+    });
+
+    if let (Some(profile_file), Some(wasm_profiler)) = (profile_file, wasm_profiler) {
+        let file = File::create(&profile_file).map_err(|e| {
+            format!(
+                "Failed to create the profile file {}: {}",
+                profile_file.display(),
+                e
+            )
+        })?;
+        wasm_profiler
+            .write_folded_stacks(BufWriter::new(file))
+            .map_err(|e| {
+                format!(
+                    "Failed to write the profile file {}: {}",
+                    profile_file.display(),
+                    e
+                )
+            })?;
+    }
+    result
 }
 
 fn print_query_result(res: Result<WasmResult, UserError>) {
@@ -17,6 +17,7 @@ const ARG_EXTRA_BATCHES: &str = "extra-batches";
 const ARG_INSTRUCTION_LIMIT: &str = "instruction-limit";
 const ARG_SUBNET_TYPE: &str = "subnet-type";
 const USE_OLD_METERING: &str = "use-old-metering";
+const ARG_PROFILE: &str = "profile";
 
 fn main() -> Result<(), String> {
     // Check if `drun` is running in the canister sandbox mode where it waits
@@ -100,6 +101,7 @@ async fn drun_main() -> Result<(), String> {
             log_file,
             instruction_limit,
             subnet_type,
+            profile_file: matches.value_of(ARG_PROFILE).map(PathBuf::from),
         };
         run_drun(uo)
     })
@@ -168,5 +170,16 @@ fn get_arg_matches() -> ArgMatches {
                 .help("Enable the old metering in the local canister execution environment.")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new(ARG_PROFILE)
+                .long(ARG_PROFILE)
+                .value_name("profile_file")
+                .help(
+                    "Profile the executed canister code and write the instructions per Wasm \
+                    call stack to this file in the folded stacks format, e.g. for \
+                    `inferno-flamegraph` (default: None).",
+                )
+                .takes_value(true),
+        )
         .get_matches()
 }
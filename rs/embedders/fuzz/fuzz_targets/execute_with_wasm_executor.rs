@@ -64,6 +64,10 @@ fuzz_target!(|module: ConfiguredModule<ICWasmConfig>| {
             rate_limiting_of_debug_prints: FlagStatus::Enabled,
             wasm_native_stable_memory: FlagStatus::Enabled,
             write_barrier: FlagStatus::Enabled,
+            canister_logging: FlagStatus::Disabled,
+            wasm64: FlagStatus::Disabled,
+            canister_backtrace: FlagStatus::Disabled,
+            wasm_profiler: FlagStatus::Disabled,
         },
         ..Default::default()
     };
@@ -168,5 +172,7 @@ fn setup_execution_state(
         Memory::new_for_testing(),
         persisted_globals,
         WasmMetadata::default(),
+        false,
+        None,
     )
 }
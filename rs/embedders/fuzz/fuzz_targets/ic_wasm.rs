@@ -99,6 +99,11 @@ impl Config for ICWasmConfig {
         true
     }
 
+    // Tail call proposal is enabled for IC
+    fn tail_call_enabled(&self) -> bool {
+        true
+    }
+
     // Disable 64bit memory
     fn memory64_enabled(&self) -> bool {
         false
@@ -154,6 +159,7 @@ impl Config for ICWasmConfig {
             "canister_post_upgrade".to_string(),
             "canister_heartbeat".to_string(),
             "canister_global_timer".to_string(),
+            "canister_on_low_wasm_memory".to_string(),
         ]))
     }
 
@@ -1,48 +1,314 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    convert::TryFrom,
+    fs,
+    hash::Hash,
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
 
-use crate::SerializedModule;
+use crate::{SerializedModule, WasmtimeEmbedder};
+use ic_config::embedders::Config as EmbeddersConfig;
+use ic_crypto_sha2::Sha256;
 use ic_interfaces::execution_environment::HypervisorResult;
-use ic_types::NumBytes;
+use ic_types::{replica_version::REPLICA_BINARY_HASH, CountBytes, NumBytes, ReplicaVersion};
 use ic_utils_lru_cache::LruCache;
 use ic_wasm_types::{CanisterModule, WasmHash};
 
+/// Extension of the files holding serialized modules in the disk cache.
+const MODULE_FILE_EXTENSION: &str = "bin";
+
+/// Extension of the files that are being written and haven't been renamed
+/// into place yet.
+const TMP_FILE_EXTENSION: &str = "tmp";
+
+/// Length of the SHA-256 checksum that precedes the serialized module in each
+/// cache file.
+const CHECKSUM_LEN: usize = 32;
+
 /// Stores the serialized modules of wasm code that has already been compiled so
 /// that it can be used again without recompiling.
+///
+/// The cache consists of an in-memory tier and an optional disk tier. The disk
+/// tier outlives the replica process, so that canisters don't have to be
+/// recompiled after a restart. Only successful compilations are persisted.
 pub struct CompilationCache {
     cache: Mutex<LruCache<WasmHash, HypervisorResult<Arc<SerializedModule>>>>,
+    disk_cache: Option<DiskCache>,
 }
 
 impl CompilationCache {
     pub fn new(capacity: NumBytes) -> Self {
         Self {
             cache: Mutex::new(LruCache::new(capacity)),
+            disk_cache: None,
         }
     }
 
+    /// Creates a compilation cache that in addition to the in-memory tier of
+    /// size `capacity` persists compiled modules in `dir`, using at most
+    /// `disk_capacity` bytes of disk space.
+    ///
+    /// Modules are stored in a subdirectory that is specific to the embedder
+    /// configuration, the Wasmtime version and the replica version, so
+    /// modules compiled by a different replica are never used. Subdirectories
+    /// of other versions are removed.
+    pub fn new_persistent(
+        capacity: NumBytes,
+        dir: &Path,
+        disk_capacity: NumBytes,
+        embedder_config: &EmbeddersConfig,
+    ) -> io::Result<Self> {
+        let version = cache_version(embedder_config)?;
+        Ok(Self {
+            cache: Mutex::new(LruCache::new(capacity)),
+            disk_cache: Some(DiskCache::open(dir, &version, disk_capacity)?),
+        })
+    }
+
     pub fn insert(
         &self,
         canister_module: &CanisterModule,
         serialized_module: HypervisorResult<Arc<SerializedModule>>,
     ) {
+        let wasm_hash = WasmHash::from(canister_module);
+        if let (Some(disk_cache), Ok(serialized_module)) = (&self.disk_cache, &serialized_module) {
+            disk_cache.insert(&wasm_hash, serialized_module);
+        }
         self.cache
             .lock()
             .unwrap()
-            .push(WasmHash::from(canister_module), serialized_module);
+            .push(wasm_hash, serialized_module);
     }
 
     pub fn get(
         &self,
         canister_module: &CanisterModule,
     ) -> Option<HypervisorResult<Arc<SerializedModule>>> {
+        let wasm_hash = WasmHash::from(canister_module);
+        let cached = self
+            .cache
+            .lock()
+            .unwrap()
+            .get(&wasm_hash)
+            .map(|o| o.as_ref().map(Arc::clone).map_err(|e| e.clone()));
+        if cached.is_some() {
+            return cached;
+        }
+        let serialized_module = Arc::new(self.disk_cache.as_ref()?.get(&wasm_hash)?);
         self.cache
             .lock()
             .unwrap()
-            .get(&WasmHash::from(canister_module))
-            .map(|o| o.as_ref().map(Arc::clone).map_err(|e| e.clone()))
+            .push(wasm_hash, Ok(Arc::clone(&serialized_module)));
+        Some(Ok(serialized_module))
     }
 
     #[doc(hidden)]
     pub fn clear_for_testing(&self) {
-        self.cache.lock().unwrap().clear()
+        self.cache.lock().unwrap().clear();
+        if let Some(disk_cache) = &self.disk_cache {
+            disk_cache.clear();
+        }
+    }
+}
+
+/// Computes a digest of everything that affects the compiled code, so that
+/// modules persisted by an incompatible replica are not loaded.
+fn cache_version(embedder_config: &EmbeddersConfig) -> io::Result<[u8; 32]> {
+    let engine = wasmtime::Engine::new(&WasmtimeEmbedder::wasmtime_execution_config(
+        embedder_config,
+    ))
+    .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+    let config_bytes = bincode::serialize(embedder_config)
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+
+    let mut hasher = Sha256::new();
+    engine.precompile_compatibility_hash().hash(&mut hasher);
+    Sha256::write(&mut hasher, &config_bytes);
+    Sha256::write(&mut hasher, ReplicaVersion::default().as_ref().as_bytes());
+    if let Some(binary_hash) = REPLICA_BINARY_HASH.get() {
+        Sha256::write(&mut hasher, binary_hash.as_bytes());
     }
+    Ok(hasher.finish())
+}
+
+/// The size of a module file on disk.
+struct DiskEntry(usize);
+
+impl CountBytes for DiskEntry {
+    fn count_bytes(&self) -> usize {
+        self.0
+    }
+}
+
+/// The disk tier of the `CompilationCache`.
+///
+/// Each module is stored in its own file named after the hash of the Wasm
+/// binary. The file contains the SHA-256 checksum of the serialized module
+/// followed by the serialized module itself. Files are written to a temporary
+/// location first and then renamed, so a crash never leaves a partially written
+/// module behind. Files that fail the checksum check are deleted.
+struct DiskCache {
+    dir: PathBuf,
+    /// Tracks the files in `dir` in LRU order, so that the least recently used
+    /// ones can be deleted once the total size exceeds the capacity.
+    index: Mutex<LruCache<WasmHash, DiskEntry>>,
+    /// Used to give concurrently written temporary files unique names.
+    next_tmp_id: AtomicU64,
+}
+
+impl DiskCache {
+    fn open(root: &Path, version: &[u8; 32], capacity: NumBytes) -> io::Result<Self> {
+        let version = hex::encode(version);
+        fs::create_dir_all(root)?;
+        for entry in fs::read_dir(root)? {
+            let entry = entry?;
+            if entry.file_name() != version.as_str() {
+                remove_path(&entry.path());
+            }
+        }
+        let dir = root.join(version);
+        fs::create_dir_all(&dir)?;
+
+        // Load the existing files in the order they were written, so that the
+        // oldest ones get evicted first.
+        let mut files = vec![];
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let wasm_hash = path
+                .extension()
+                .filter(|ext| *ext == MODULE_FILE_EXTENSION)
+                .and_then(|_| path.file_stem())
+                .and_then(|stem| hex::decode(stem.to_str()?).ok())
+                .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok());
+            let metadata = fs::metadata(&path)?;
+            match wasm_hash {
+                Some(wasm_hash) if metadata.is_file() => files.push((
+                    metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                    WasmHash::from(wasm_hash),
+                    metadata.len() as usize,
+                )),
+                _ => remove_path(&path),
+            }
+        }
+        files.sort_by_key(|(modified, _, _)| *modified);
+
+        let disk_cache = Self {
+            dir,
+            index: Mutex::new(LruCache::new(capacity)),
+            next_tmp_id: AtomicU64::new(0),
+        };
+        for (_, wasm_hash, size) in files {
+            disk_cache.add_to_index(wasm_hash, size);
+        }
+        Ok(disk_cache)
+    }
+
+    fn path(&self, wasm_hash: &WasmHash) -> PathBuf {
+        self.dir
+            .join(hex::encode(wasm_hash.to_slice()))
+            .with_extension(MODULE_FILE_EXTENSION)
+    }
+
+    fn get(&self, wasm_hash: &WasmHash) -> Option<SerializedModule> {
+        self.index.lock().unwrap().get(wasm_hash)?;
+        let path = self.path(wasm_hash);
+        let serialized_module = fs::read(&path)
+            .ok()
+            .and_then(|contents| decode_module_file(&contents));
+        if serialized_module.is_none() {
+            // The file is missing or corrupted.
+            self.index.lock().unwrap().pop(wasm_hash);
+            remove_path(&path);
+        }
+        serialized_module
+    }
+
+    fn insert(&self, wasm_hash: &WasmHash, serialized_module: &SerializedModule) {
+        if self.index.lock().unwrap().get(wasm_hash).is_some() {
+            return;
+        }
+        let contents = match encode_module_file(serialized_module) {
+            Some(contents) => contents,
+            None => return,
+        };
+        let tmp_path = self.dir.join(format!(
+            "{}.{}",
+            self.next_tmp_id.fetch_add(1, Ordering::Relaxed),
+            TMP_FILE_EXTENSION
+        ));
+        let path = self.path(wasm_hash);
+        if fs::write(&tmp_path, &contents)
+            .and_then(|()| fs::rename(&tmp_path, &path))
+            .is_err()
+        {
+            // Persisting modules is best effort, the module just has to be
+            // compiled again next time.
+            remove_path(&tmp_path);
+            return;
+        }
+        self.add_to_index(wasm_hash.clone(), contents.len());
+    }
+
+    /// Adds the file of the given module to the index and deletes the files
+    /// evicted as a result.
+    fn add_to_index(&self, wasm_hash: WasmHash, size: usize) {
+        let evicted = self
+            .index
+            .lock()
+            .unwrap()
+            .push(wasm_hash.clone(), DiskEntry(size));
+        for (evicted_hash, _) in evicted {
+            // `push` also returns the previous entry for the same key, whose
+            // file has just been replaced and must be kept.
+            if evicted_hash != wasm_hash {
+                remove_path(&self.path(&evicted_hash));
+            }
+        }
+        // A module larger than the capacity is evicted right away.
+        if self.index.lock().unwrap().get(&wasm_hash).is_none() {
+            remove_path(&self.path(&wasm_hash));
+        }
+    }
+
+    fn clear(&self) {
+        let mut index = self.index.lock().unwrap();
+        index.clear();
+        if let Ok(entries) = fs::read_dir(&self.dir) {
+            for entry in entries.flatten() {
+                remove_path(&entry.path());
+            }
+        }
+    }
+}
+
+fn encode_module_file(serialized_module: &SerializedModule) -> Option<Vec<u8>> {
+    let payload = bincode::serialize(serialized_module).ok()?;
+    let mut contents = Vec::with_capacity(CHECKSUM_LEN + payload.len());
+    contents.extend_from_slice(&Sha256::hash(&payload));
+    contents.extend_from_slice(&payload);
+    Some(contents)
+}
+
+fn decode_module_file(contents: &[u8]) -> Option<SerializedModule> {
+    if contents.len() < CHECKSUM_LEN {
+        return None;
+    }
+    let (checksum, payload) = contents.split_at(CHECKSUM_LEN);
+    if checksum != Sha256::hash(payload) {
+        return None;
+    }
+    bincode::deserialize(payload).ok()
+}
+
+fn remove_path(path: &Path) {
+    let _ = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
 }
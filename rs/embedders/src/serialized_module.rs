@@ -7,8 +7,9 @@ use ic_wasm_types::WasmEngineError;
 use serde::{Deserialize, Serialize};
 use wasmtime::Module;
 
-use crate::wasm_utils::{
-    InstrumentationOutput, Segments, WasmImportsDetails, WasmValidationDetails,
+use crate::{
+    wasm_utils::{InstrumentationOutput, Segments, WasmImportsDetails, WasmValidationDetails},
+    wasmtime_embedder::{is_wasm64, wasm_memory_max_pages},
 };
 
 /// A `wasmtime::Module` that has been serialized.
@@ -69,6 +70,10 @@ pub struct SerializedModule {
     pub compilation_cost: NumInstructions,
     /// Imported System API functions that are deprecated, should become deprecated, or should only be used by NNS canisters.
     pub imports_details: WasmImportsDetails,
+    /// Whether the module declares a 64-bit memory.
+    pub is_wasm64: bool,
+    /// The maximum size of the Wasm memory in Wasm pages, if declared.
+    pub wasm_memory_max_pages: Option<u64>,
 }
 
 impl CountBytes for SerializedModule {
@@ -91,6 +96,8 @@ impl SerializedModule {
             wasm_metadata: validation_details.wasm_metadata,
             compilation_cost: instrumentation_output.compilation_cost,
             imports_details: validation_details.imports_details,
+            is_wasm64: is_wasm64(module),
+            wasm_memory_max_pages: wasm_memory_max_pages(module),
         })
     }
 
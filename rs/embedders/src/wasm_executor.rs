@@ -309,6 +309,8 @@ impl WasmExecutor for WasmExecutorImpl {
             ),
             globals,
             wasm_metadata,
+            serialized_module.is_wasm64,
+            serialized_module.wasm_memory_max_pages,
         );
         Ok((
             execution_state,
@@ -454,6 +456,8 @@ pub fn wasm_execution_error(
             allocated_bytes: NumBytes::from(0),
             allocated_message_bytes: NumBytes::from(0),
             instance_stats: InstanceStats::default(),
+            canister_log: Default::default(),
+            wasm_profile: Default::default(),
         },
         None,
     )
@@ -617,6 +621,8 @@ pub fn process(
                     allocated_bytes: NumBytes::from(0),
                     allocated_message_bytes: NumBytes::from(0),
                     instance_stats: InstanceStats::default(),
+                    canister_log: Default::default(),
+                    wasm_profile: Default::default(),
                 },
                 None,
                 Err(system_api.unwrap()), // should be safe because we've passed Some(api) to new_instance
@@ -654,6 +660,18 @@ pub fn process(
     // Has the side effect of deallocating memory if message failed and
     // returning cycles from a request that wasn't sent.
     let mut wasm_result = system_api.take_execution_result(run_result.as_ref().err());
+    if let Err(HypervisorError::Trapped {
+        trap_code,
+        backtrace: Some(backtrace),
+    }) = &wasm_result
+    {
+        if embedder.config().feature_flags.canister_logging == FlagStatus::Enabled {
+            system_api.save_trap_backtrace(trap_code, backtrace);
+        }
+    }
+    // Log records are kept even if the execution failed.
+    let canister_log = system_api.take_canister_log();
+    let wasm_profile = instance.take_wasm_profile();
 
     let wasm_heap_size_after = instance.heap_size(CanisterMemoryType::Heap);
     let wasm_heap_limit =
@@ -725,6 +743,8 @@ pub fn process(
             allocated_bytes,
             allocated_message_bytes,
             instance_stats,
+            canister_log,
+            wasm_profile,
         },
         wasm_state_changes,
         Ok(instance),
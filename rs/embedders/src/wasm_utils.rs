@@ -209,6 +209,9 @@ fn validate_and_instrument(
         config.metering_type,
         config.subnet_type,
         config.dirty_page_overhead,
+        config.max_wasm64_memory_size,
+        config.feature_flags.canister_backtrace,
+        config.feature_flags.wasm_profiler,
     )?;
     Ok((wasm_validation_details, instrumentation_output))
 }
@@ -109,17 +109,50 @@
 //! (memory (export "stable_memory_bytemap") i32 (i64.const STABLE_BYTEMAP_SIZE) (i64.const STABLE_BYTEMAP_SIZE))
 //! ```
 //!
+//! # 64-bit Wasm memories
+//!
+//! If the module declares a 64-bit memory, its maximum size is capped at the
+//! configured limit and the injected instrumentation works with i64 addresses:
+//! the write barrier shifts the i64 address before wrapping it to an index
+//! into the (32-bit) heap bytemap, `memory.grow` arguments and results are
+//! converted around the call to `update_available_memory`, and the i64 size of
+//! `memory.fill` and `memory.copy` is passed directly to the instruction
+//! counter decrementation.
+//!
+//! # Wasm profiler
+//!
+//! If the Wasm profiler is enabled, two more functions are imported after all
+//! other injected imports:
+//! ```wasm
+//! (import "__" "profiler_enter" (func (param i32)))
+//! (import "__" "profiler_exit" (func))
+//! ```
+//! The body of every function is wrapped in a block that has the results of
+//! the function, so that branches to the function label leave the block:
+//! ```wasm
+//! i32.const <index of the function in the original module>
+//! call profiler_enter
+//! block (result ...)
+//!   ...      ;; original body, with `call profiler_exit` before every
+//!            ;; `return`, `return_call` and `return_call_indirect`
+//! end
+//! call profiler_exit
+//! ```
+//! The profiler calls are injected after the metering, so they do not change
+//! the number of instructions charged for the execution.
+//!
 
 use super::system_api_replacements::replacement_functions;
 use super::validation::API_VERSION_IC0;
 use super::{InstrumentationOutput, Segments, SystemApiFunc};
 use ic_config::embedders::MeteringType;
 use ic_config::flag_status::FlagStatus;
+use ic_interfaces::execution_environment::GrowableMemory;
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::NumWasmPages;
 use ic_sys::PAGE_SIZE;
 use ic_types::{methods::WasmMethod, MAX_WASM_MEMORY_IN_BYTES};
-use ic_types::{NumInstructions, MAX_STABLE_MEMORY_IN_BYTES};
+use ic_types::{NumBytes, NumInstructions, MAX_STABLE_MEMORY_IN_BYTES};
 use ic_wasm_types::{BinaryEncodedWasm, WasmError, WasmInstrumentationError};
 use wasmtime_environ::WASM_PAGE_SIZE;
 
@@ -146,13 +179,28 @@ pub(crate) enum InjectedImports {
 }
 
 impl InjectedImports {
-    fn count(wasm_native_stable_memory: FlagStatus) -> usize {
-        if wasm_native_stable_memory == FlagStatus::Enabled {
+    pub(crate) fn count(wasm_native_stable_memory: FlagStatus, wasm_profiler: FlagStatus) -> usize {
+        let count = if wasm_native_stable_memory == FlagStatus::Enabled {
             5
         } else {
             2
+        };
+        if wasm_profiler == FlagStatus::Enabled {
+            count + 2
+        } else {
+            count
         }
     }
+
+    // The profiler imports follow all other injected imports, so their
+    // indices depend on whether Wasm-native stable memory is enabled.
+    fn profiler_enter(wasm_native_stable_memory: FlagStatus) -> u32 {
+        Self::count(wasm_native_stable_memory, FlagStatus::Disabled) as u32
+    }
+
+    fn profiler_exit(wasm_native_stable_memory: FlagStatus) -> u32 {
+        Self::profiler_enter(wasm_native_stable_memory) + 1
+    }
 }
 
 // Gets the cost of an instruction.
@@ -173,7 +221,7 @@ pub fn instruction_to_cost(i: &Operator) -> u64 {
 // Gets the cost of an instruction.
 pub fn instruction_to_cost_new(i: &Operator) -> u64 {
     // This aims to be a complete list of all instructions that can be executed, with certain exceptions.
-    // The exceptions are: SIMD instructions, atomic instructions, and the dynamic cost of
+    // The exceptions are: atomic instructions, relaxed SIMD instructions, and the dynamic cost of
     // of operations such as table/memory fill, copy, init. This
     // dynamic cost is treated separately. Here we only assign a static cost to these instructions.
     match i {
@@ -424,6 +472,10 @@ pub fn instruction_to_cost_new(i: &Operator) -> u64 {
         Operator::Call { .. } => 5,
         Operator::CallIndirect { .. } => 10,
 
+        // Tail calls are of the same cost as the regular calls.
+        Operator::ReturnCall { .. } => 5,
+        Operator::ReturnCallIndirect { .. } => 10,
+
         // Return, drop, unreachable and nop instructions are of cost 1.
         Operator::Return { .. } | Operator::Drop | Operator::Unreachable | Operator::Nop => 1,
 
@@ -441,6 +493,93 @@ pub fn instruction_to_cost_new(i: &Operator) -> u64 {
         | Operator::I64Clz { .. }
         | Operator::I64Ctz { .. } => 1,
 
+        // SIMD loads and stores are of the same cost as the scalar ones.
+        Operator::V128Load { .. }
+        | Operator::V128Load8x8S { .. }
+        | Operator::V128Load8x8U { .. }
+        | Operator::V128Load16x4S { .. }
+        | Operator::V128Load16x4U { .. }
+        | Operator::V128Load32x2S { .. }
+        | Operator::V128Load32x2U { .. }
+        | Operator::V128Load8Splat { .. }
+        | Operator::V128Load16Splat { .. }
+        | Operator::V128Load32Splat { .. }
+        | Operator::V128Load64Splat { .. }
+        | Operator::V128Load32Zero { .. }
+        | Operator::V128Load64Zero { .. }
+        | Operator::V128Load8Lane { .. }
+        | Operator::V128Load16Lane { .. }
+        | Operator::V128Load32Lane { .. }
+        | Operator::V128Load64Lane { .. }
+        | Operator::V128Store { .. }
+        | Operator::V128Store8Lane { .. }
+        | Operator::V128Store16Lane { .. }
+        | Operator::V128Store32Lane { .. }
+        | Operator::V128Store64Lane { .. } => 1,
+
+        // SIMD integer lane-wise operations are mostly single CPU instructions
+        // and are of cost 1 by default. The 64-bit lane multiplication has no
+        // native x86 instruction and is emulated with several instructions.
+        Operator::I64x2Mul { .. } => 4,
+
+        // SIMD floating point operations are priced like their scalar
+        // counterparts to stay on the safe side.
+        Operator::F32x4Add { .. }
+        | Operator::F32x4Sub { .. }
+        | Operator::F32x4Mul { .. }
+        | Operator::F32x4Div { .. }
+        | Operator::F32x4Min { .. }
+        | Operator::F32x4Max { .. }
+        | Operator::F32x4PMin { .. }
+        | Operator::F32x4PMax { .. }
+        | Operator::F32x4Ceil { .. }
+        | Operator::F32x4Floor { .. }
+        | Operator::F32x4Trunc { .. }
+        | Operator::F32x4Nearest { .. }
+        | Operator::F32x4Sqrt { .. }
+        | Operator::F64x2Add { .. }
+        | Operator::F64x2Sub { .. }
+        | Operator::F64x2Mul { .. }
+        | Operator::F64x2Div { .. }
+        | Operator::F64x2Min { .. }
+        | Operator::F64x2Max { .. }
+        | Operator::F64x2PMin { .. }
+        | Operator::F64x2PMax { .. }
+        | Operator::F64x2Ceil { .. }
+        | Operator::F64x2Floor { .. }
+        | Operator::F64x2Trunc { .. }
+        | Operator::F64x2Nearest { .. }
+        | Operator::F64x2Sqrt { .. } => 20,
+
+        Operator::F32x4Abs { .. }
+        | Operator::F32x4Neg { .. }
+        | Operator::F64x2Abs { .. }
+        | Operator::F64x2Neg { .. } => 2,
+
+        Operator::F32x4Eq { .. }
+        | Operator::F32x4Ne { .. }
+        | Operator::F32x4Lt { .. }
+        | Operator::F32x4Gt { .. }
+        | Operator::F32x4Le { .. }
+        | Operator::F32x4Ge { .. }
+        | Operator::F64x2Eq { .. }
+        | Operator::F64x2Ne { .. }
+        | Operator::F64x2Lt { .. }
+        | Operator::F64x2Gt { .. }
+        | Operator::F64x2Le { .. }
+        | Operator::F64x2Ge { .. } => 3,
+
+        Operator::F32x4ConvertI32x4S { .. } | Operator::F64x2ConvertLowI32x4S { .. } => 3,
+
+        Operator::F32x4ConvertI32x4U { .. } | Operator::F64x2ConvertLowI32x4U { .. } => 16,
+
+        Operator::I32x4TruncSatF32x4S { .. }
+        | Operator::I32x4TruncSatF32x4U { .. }
+        | Operator::I32x4TruncSatF64x2SZero { .. }
+        | Operator::I32x4TruncSatF64x2UZero { .. } => 20,
+
+        Operator::F32x4DemoteF64x2Zero { .. } | Operator::F64x2PromoteLowF32x4 { .. } => 1,
+
         // Null references are cheap, validated in benchmarks.
         Operator::RefNull { .. } => 1,
         // Checking for null references is the same as branching
@@ -461,16 +600,25 @@ const UPDATE_MEMORY_FUN_NAME: &str = "update_available_memory";
 const TRY_GROW_STABLE_MEMORY_FUN_NAME: &str = "try_grow_stable_memory";
 const INTERNAL_TRAP_FUN_NAME: &str = "internal_trap";
 const STABLE_READ_FIRST_ACCESS_NAME: &str = "stable_read_first_access";
+const PROFILER_ENTER_FUN_NAME: &str = "profiler_enter";
+const PROFILER_EXIT_FUN_NAME: &str = "profiler_exit";
 const TABLE_STR: &str = "table";
 pub(crate) const INSTRUCTIONS_COUNTER_GLOBAL_NAME: &str = "canister counter_instructions";
 pub(crate) const DIRTY_PAGES_COUNTER_GLOBAL_NAME: &str = "canister counter_dirty_pages";
 pub(crate) const ACCESSED_PAGES_COUNTER_GLOBAL_NAME: &str = "canister counter_accessed_pages";
 const CANISTER_START_STR: &str = "canister_start";
+const NAME_SECTION_NAME: &str = "name";
 
 /// There is one byte for each OS page in the wasm heap.
 const BYTEMAP_SIZE_IN_WASM_PAGES: u64 =
     MAX_WASM_MEMORY_IN_BYTES / (PAGE_SIZE as u64) / (WASM_PAGE_SIZE as u64);
 
+/// There is one byte for each OS page in a 64-bit wasm heap of the given
+/// maximum size.
+fn wasm64_bytemap_size_in_wasm_pages(max_wasm64_memory_size: NumBytes) -> u64 {
+    (max_wasm64_memory_size.get() / PAGE_SIZE as u64).div_ceil(WASM_PAGE_SIZE as u64)
+}
+
 const MAX_STABLE_MEMORY_IN_WASM_PAGES: u64 = MAX_STABLE_MEMORY_IN_BYTES / (WASM_PAGE_SIZE as u64);
 /// There is one byte for each OS page in the stable memory.
 const STABLE_BYTEMAP_SIZE_IN_WASM_PAGES: u64 = MAX_STABLE_MEMORY_IN_WASM_PAGES / (PAGE_SIZE as u64);
@@ -540,11 +688,7 @@ fn mutate_function_indices(module: &mut Module, f: impl Fn(u32) -> u32) {
             ic_wasm_transform::DataSegmentKind::Active {
                 memory_index: _,
                 offset_expr,
-            } => {
-                let mut temp = [offset_expr.clone()];
-                mutate_instructions(&f, &mut temp);
-                *offset_expr = temp.into_iter().next().unwrap();
-            }
+            } => mutate_instructions(&f, offset_expr),
         }
     }
 
@@ -560,7 +704,11 @@ fn mutate_function_indices(module: &mut Module, f: impl Fn(u32) -> u32) {
 /// added as the last imports, we'd need to increment only non imported
 /// functions, since imported functions precede all others in the function index
 /// space, but this would be error-prone).
-fn inject_helper_functions(mut module: Module, wasm_native_stable_memory: FlagStatus) -> Module {
+fn inject_helper_functions(
+    mut module: Module,
+    wasm_native_stable_memory: FlagStatus,
+    wasm_profiler: FlagStatus,
+) -> Module {
     // insert types
     let ooi_type = FuncType::new([], []);
     let uam_type = FuncType::new([ValType::I32, ValType::I32, ValType::I32], [ValType::I32]);
@@ -582,8 +730,9 @@ fn inject_helper_functions(mut module: Module, wasm_native_stable_memory: FlagSt
     };
 
     let mut old_imports = module.imports;
-    module.imports =
-        Vec::with_capacity(old_imports.len() + InjectedImports::count(wasm_native_stable_memory));
+    module.imports = Vec::with_capacity(
+        old_imports.len() + InjectedImports::count(wasm_native_stable_memory, wasm_profiler),
+    );
     module.imports.push(ooi_imp);
     module.imports.push(uam_imp);
 
@@ -616,10 +765,28 @@ fn inject_helper_functions(mut module: Module, wasm_native_stable_memory: FlagSt
         module.imports.push(fr_imp);
     }
 
+    if wasm_profiler == FlagStatus::Enabled {
+        let pe_type = FuncType::new([ValType::I32], []);
+        let pe_type_idx = add_func_type(&mut module, pe_type);
+        module.imports.push(Import {
+            module: INSTRUMENTED_FUN_MODULE,
+            name: PROFILER_ENTER_FUN_NAME,
+            ty: TypeRef::Func(pe_type_idx),
+        });
+
+        let px_type = FuncType::new([], []);
+        let px_type_idx = add_func_type(&mut module, px_type);
+        module.imports.push(Import {
+            module: INSTRUMENTED_FUN_MODULE,
+            name: PROFILER_EXIT_FUN_NAME,
+            ty: TypeRef::Func(px_type_idx),
+        });
+    }
+
     module.imports.append(&mut old_imports);
 
     // now increment all function references by InjectedImports::Count
-    let cnt = InjectedImports::count(wasm_native_stable_memory) as u32;
+    let cnt = InjectedImports::count(wasm_native_stable_memory, wasm_profiler) as u32;
     mutate_function_indices(&mut module, |i| i + cnt);
 
     debug_assert!(
@@ -642,10 +809,84 @@ fn inject_helper_functions(mut module: Module, wasm_native_stable_memory: FlagSt
                 == "stable_read_first_access"
         );
     }
+    if wasm_profiler == FlagStatus::Enabled {
+        debug_assert!(
+            module.imports[InjectedImports::profiler_enter(wasm_native_stable_memory) as usize]
+                .name
+                == PROFILER_ENTER_FUN_NAME
+        );
+        debug_assert!(
+            module.imports[InjectedImports::profiler_exit(wasm_native_stable_memory) as usize].name
+                == PROFILER_EXIT_FUN_NAME
+        );
+    }
 
     module
 }
 
+/// Shifts the function indices in the `name` custom section by the number of
+/// injected imports, so that Wasmtime can still use the section to symbolize
+/// backtraces of the instrumented module. Only the module name and the function
+/// names are kept. A malformed section is dropped.
+fn update_name_section<'a>(
+    mut module: Module<'a>,
+    name_section: &'a mut Vec<u8>,
+    injected_imports: u32,
+) -> Module<'a> {
+    let Some(position) = module
+        .custom_sections
+        .iter()
+        .position(|(name, _)| *name == NAME_SECTION_NAME)
+    else {
+        return module;
+    };
+    let (_, data) = module.custom_sections.remove(position);
+    if let Ok(encoded) = shift_function_names(data, injected_imports) {
+        *name_section = encoded;
+        let name_section: &'a Vec<u8> = name_section;
+        module
+            .custom_sections
+            .insert(position, (NAME_SECTION_NAME, name_section.as_slice()));
+    }
+    module
+}
+
+/// Re-encodes the module and function names subsections of the given `name`
+/// section with all function indices shifted by `shift`.
+fn shift_function_names(data: &[u8], shift: u32) -> wasmparser::Result<Vec<u8>> {
+    use wasm_encoder::Encode;
+
+    const MODULE_NAME_SUBSECTION_ID: u8 = 0;
+    const FUNCTION_NAMES_SUBSECTION_ID: u8 = 1;
+
+    let mut result = vec![];
+    for subsection in wasmparser::NameSectionReader::new(data, 0) {
+        match subsection? {
+            wasmparser::Name::Module { name, .. } => {
+                let mut content = vec![];
+                name.encode(&mut content);
+                result.push(MODULE_NAME_SUBSECTION_ID);
+                content.as_slice().encode(&mut result);
+            }
+            wasmparser::Name::Function(names) => {
+                let names = names
+                    .into_iter()
+                    .collect::<wasmparser::Result<Vec<wasmparser::Naming>>>()?;
+                let mut content = vec![];
+                (names.len() as u32).encode(&mut content);
+                for naming in names {
+                    naming.index.saturating_add(shift).encode(&mut content);
+                    naming.name.encode(&mut content);
+                }
+                result.push(FUNCTION_NAMES_SUBSECTION_ID);
+                content.as_slice().encode(&mut result);
+            }
+            _ => {}
+        }
+    }
+    Ok(result)
+}
+
 /// Indices of functions, globals, etc that will be need in the later parts of
 /// instrumentation.
 #[derive(Default)]
@@ -672,12 +913,31 @@ pub(super) fn instrument(
     metering_type: MeteringType,
     subnet_type: SubnetType,
     dirty_page_overhead: NumInstructions,
+    max_wasm64_memory_size: NumBytes,
+    canister_backtrace: FlagStatus,
+    wasm_profiler: FlagStatus,
 ) -> Result<InstrumentationOutput, WasmInstrumentationError> {
     let stable_memory_index;
-    let mut module = inject_helper_functions(module, wasm_native_stable_memory);
+    let is_wasm64 = module
+        .memories
+        .first()
+        .map_or(false, |memory| memory.memory64);
+    let mut module = inject_helper_functions(module, wasm_native_stable_memory, wasm_profiler);
+    let mut name_section: Vec<u8> = Vec::new();
+    if canister_backtrace == FlagStatus::Enabled {
+        module = update_name_section(
+            module,
+            &mut name_section,
+            InjectedImports::count(wasm_native_stable_memory, wasm_profiler) as u32,
+        );
+    }
     module = export_table(module);
-    (module, stable_memory_index) =
-        update_memories(module, write_barrier, wasm_native_stable_memory);
+    (module, stable_memory_index) = update_memories(
+        module,
+        write_barrier,
+        wasm_native_stable_memory,
+        max_wasm64_memory_size,
+    );
 
     let mut extra_strs: Vec<String> = Vec::new();
     module = export_mutable_globals(module, &mut extra_strs);
@@ -731,7 +991,12 @@ pub(super) fn instrument(
 
     // inject instructions counter decrementation
     for func_body in &mut module.code_sections {
-        inject_metering(&mut func_body.instructions, &special_indices, metering_type);
+        inject_metering(
+            &mut func_body.instructions,
+            &special_indices,
+            metering_type,
+            is_wasm64,
+        );
     }
 
     // Collect all the function types of the locally defined functions inside the
@@ -758,13 +1023,21 @@ pub(super) fn instrument(
     if !func_types.is_empty() {
         let func_bodies = &mut module.code_sections;
         for (func_ix, func_type) in func_types.into_iter() {
-            inject_update_available_memory(&mut func_bodies[func_ix], &func_type);
+            inject_update_available_memory(&mut func_bodies[func_ix], &func_type, is_wasm64);
             if write_barrier == FlagStatus::Enabled {
-                inject_mem_barrier(&mut func_bodies[func_ix], &func_type);
+                inject_mem_barrier(&mut func_bodies[func_ix], &func_type, is_wasm64);
             }
         }
     }
 
+    if wasm_profiler == FlagStatus::Enabled {
+        inject_profiler(
+            &mut module,
+            wasm_native_stable_memory,
+            num_imported_functions as u32,
+        )?;
+    }
+
     module = export_additional_symbols(module, &special_indices, wasm_native_stable_memory);
 
     if wasm_native_stable_memory == FlagStatus::Enabled {
@@ -774,6 +1047,7 @@ pub(super) fn instrument(
             subnet_type,
             dirty_page_overhead,
             metering_type,
+            is_wasm64,
         )
     }
 
@@ -829,6 +1103,37 @@ pub(super) fn instrument(
     })
 }
 
+/// Returns the names of the functions of the given uninstrumented module by
+/// their index, i.e. the index used in the call stacks of a Wasm profile.
+/// Names are taken from the `name` custom section. Functions without a name
+/// there are named after their export, if they are exported.
+pub fn function_names(wasm: &BinaryEncodedWasm) -> BTreeMap<u32, String> {
+    let mut names = BTreeMap::new();
+    let Ok(module) = Module::parse(wasm.as_slice(), false) else {
+        return names;
+    };
+    for (name, data) in &module.custom_sections {
+        if *name != NAME_SECTION_NAME {
+            continue;
+        }
+        for subsection in wasmparser::NameSectionReader::new(data, 0) {
+            if let Ok(wasmparser::Name::Function(map)) = subsection {
+                for naming in map.into_iter().flatten() {
+                    names.insert(naming.index, naming.name.to_string());
+                }
+            }
+        }
+    }
+    for export in &module.exports {
+        if export.kind == ExternalKind::Func {
+            names
+                .entry(export.index)
+                .or_insert_with(|| export.name.to_string());
+        }
+    }
+    names
+}
+
 fn calculate_api_indexes(module: &Module<'_>) -> BTreeMap<SystemApiFunc, u32> {
     module
         .imports
@@ -853,6 +1158,7 @@ fn replace_system_api_functions(
     subnet_type: SubnetType,
     dirty_page_overhead: NumInstructions,
     metering_type: MeteringType,
+    is_wasm64: bool,
 ) {
     let api_indexes = calculate_api_indexes(module);
     let number_of_func_imports = module
@@ -869,6 +1175,7 @@ fn replace_system_api_functions(
         subnet_type,
         dirty_page_overhead,
         metering_type,
+        is_wasm64,
     ) {
         if let Some(old_index) = api_indexes.get(&api) {
             let type_idx = add_func_type(module, ty);
@@ -1171,11 +1478,13 @@ impl InjectionPoint {
 //   of every reentrant block (a loop or a function call).
 // - we insert a function call before each dynamic cost instruction which
 //   performs an overflow check and then decrements the counter by the value at
-//   the top of the stack.
+//   the top of the stack. The size argument of `memory.fill` and `memory.copy`
+//   is an i64 if the module uses a 64-bit memory.
 fn inject_metering(
     code: &mut Vec<Operator>,
     export_data_module: &SpecialIndices,
     metering_type: MeteringType,
+    is_wasm64: bool,
 ) {
     let points = match metering_type {
         MeteringType::Old => injections_old(code),
@@ -1227,6 +1536,17 @@ fn inject_metering(
                     ]);
                 }
             }
+            InjectionPointCostDetail::DynamicCost
+                if is_wasm64
+                    && matches!(
+                        orig_elems[point.position],
+                        MemoryFill { .. } | MemoryCopy { .. }
+                    ) =>
+            {
+                elems.push(Call {
+                    function_index: export_data_module.decr_instruction_counter_fn,
+                });
+            }
             InjectionPointCostDetail::DynamicCost => {
                 elems.extend_from_slice(&[
                     I64ExtendI32U,
@@ -1246,87 +1566,162 @@ fn inject_metering(
     *orig_elems = elems;
 }
 
+// Wraps the body of every function in a block and injects the calls to
+// `profiler_enter` and `profiler_exit`, so that the profiler is notified
+// about every call and every return of a function of the original module.
+// Must run before the helper functions are added to the module.
+fn inject_profiler(
+    module: &mut Module,
+    wasm_native_stable_memory: FlagStatus,
+    num_imported_functions: u32,
+) -> Result<(), WasmInstrumentationError> {
+    use Operator::*;
+
+    let enter_fn = InjectedImports::profiler_enter(wasm_native_stable_memory);
+    let exit_fn = InjectedImports::profiler_exit(wasm_native_stable_memory);
+    // Functions are reported with their indices in the original module, which
+    // did not have the injected imports.
+    let first_function_index = num_imported_functions
+        - InjectedImports::count(wasm_native_stable_memory, FlagStatus::Enabled) as u32;
+
+    for i in 0..module.code_sections.len() {
+        let results = match &module.types[module.functions[i] as usize].structural_type {
+            StructuralType::Func(t) => t.results().to_vec(),
+            other => {
+                return Err(WasmInstrumentationError::InvalidFunctionType(format!(
+                    "Function has type which is not a function type. Found type: {:?}",
+                    other
+                )))
+            }
+        };
+        let blockty = match results.as_slice() {
+            [] => BlockType::Empty,
+            [ty] => BlockType::Type(*ty),
+            _ => BlockType::FuncType(add_func_type(module, FuncType::new([], results))),
+        };
+
+        let body = &mut module.code_sections[i].instructions;
+        let mut instructions = Vec::with_capacity(body.len() + 5);
+        instructions.extend_from_slice(&[
+            I32Const {
+                value: (first_function_index + i as u32) as i32,
+            },
+            Call {
+                function_index: enter_fn,
+            },
+            Block { blockty },
+        ]);
+        for op in body.drain(..) {
+            if matches!(op, Return | ReturnCall { .. } | ReturnCallIndirect { .. }) {
+                instructions.push(Call {
+                    function_index: exit_fn,
+                });
+            }
+            instructions.push(op);
+        }
+        // The `end` of the original body now closes the injected block.
+        instructions.extend_from_slice(&[
+            Call {
+                function_index: exit_fn,
+            },
+            End,
+        ]);
+        *body = instructions;
+    }
+    Ok(())
+}
+
 // This function adds mem barrier writes, assuming that arguments
 // of the original store operation are on the stack
 fn write_barrier_instructions<'a>(
     offset: u64,
     val_arg_idx: u32,
     addr_arg_idx: u32,
+    is_wasm64: bool,
 ) -> Vec<Operator<'a>> {
     use Operator::*;
     let page_size_shift = PAGE_SIZE.trailing_zeros() as i32;
     let tracking_mem_idx = 1;
-    if offset % PAGE_SIZE as u64 == 0 {
-        vec![
-            LocalSet {
-                local_index: val_arg_idx,
-            }, // value
-            LocalTee {
-                local_index: addr_arg_idx,
-            }, // address
-            I32Const {
-                value: page_size_shift,
-            },
-            I32ShrU,
-            I32Const { value: 1 },
-            I32Store8 {
-                memarg: wasmparser::MemArg {
-                    align: 0,
-                    max_align: 0,
-                    offset: offset >> page_size_shift,
-                    memory: tracking_mem_idx,
+    let mut instructions = vec![
+        LocalSet {
+            local_index: val_arg_idx,
+        }, // value
+        LocalTee {
+            local_index: addr_arg_idx,
+        }, // address
+    ];
+    // If the offset is page aligned, then it can be folded into the offset of
+    // the bytemap store. Otherwise, it has to be added to the address first.
+    let bytemap_offset = if offset % PAGE_SIZE as u64 == 0 {
+        offset >> page_size_shift
+    } else {
+        if is_wasm64 {
+            instructions.extend_from_slice(&[
+                I64Const {
+                    value: offset as i64,
                 },
+                I64Add,
+            ]);
+        } else {
+            instructions.extend_from_slice(&[
+                I32Const {
+                    value: offset as i32,
+                },
+                I32Add,
+            ]);
+        }
+        0
+    };
+    if is_wasm64 {
+        // The bytemap is a 32-bit memory, so the page index is wrapped after
+        // the shift. It fits because the size of 64-bit memories is limited.
+        instructions.extend_from_slice(&[
+            I64Const {
+                value: page_size_shift as i64,
             },
-            // Put original params on the stack
-            LocalGet {
-                local_index: addr_arg_idx,
-            },
-            LocalGet {
-                local_index: val_arg_idx,
-            },
-        ]
+            I64ShrU,
+            I32WrapI64,
+        ]);
     } else {
-        vec![
-            LocalSet {
-                local_index: val_arg_idx,
-            }, // value
-            LocalTee {
-                local_index: addr_arg_idx,
-            }, // address
-            I32Const {
-                value: offset as i32,
-            },
-            I32Add,
+        instructions.extend_from_slice(&[
             I32Const {
                 value: page_size_shift,
             },
             I32ShrU,
-            I32Const { value: 1 },
-            I32Store8 {
-                memarg: wasmparser::MemArg {
-                    align: 0,
-                    max_align: 0,
-                    offset: 0,
-                    memory: tracking_mem_idx,
-                },
-            },
-            // Put original params on the stack
-            LocalGet {
-                local_index: addr_arg_idx,
-            },
-            LocalGet {
-                local_index: val_arg_idx,
-            },
-        ]
+        ]);
     }
+    instructions.extend_from_slice(&[
+        I32Const { value: 1 },
+        I32Store8 {
+            memarg: wasmparser::MemArg {
+                align: 0,
+                max_align: 0,
+                offset: bytemap_offset,
+                memory: tracking_mem_idx,
+            },
+        },
+        // Put original params on the stack
+        LocalGet {
+            local_index: addr_arg_idx,
+        },
+        LocalGet {
+            local_index: val_arg_idx,
+        },
+    ]);
+    instructions
 }
 
-fn inject_mem_barrier(func_body: &mut ic_wasm_transform::Body, func_type: &FuncType) {
+fn inject_mem_barrier(
+    func_body: &mut ic_wasm_transform::Body,
+    func_type: &FuncType,
+    is_wasm64: bool,
+) {
     use Operator::*;
     let mut val_i32_needed = false;
     let mut val_i64_needed = false;
     let mut val_f32_needed = false;
     let mut val_f64_needed = false;
+    let mut val_v128_needed = false;
 
     let mut injection_points: Vec<usize> = Vec::new();
     {
@@ -1348,6 +1743,14 @@ fn inject_mem_barrier(func_body: &mut ic_wasm_transform::Body, func_type: &FuncT
                     val_f64_needed = true;
                     injection_points.push(idx)
                 }
+                V128Store { .. }
+                | V128Store8Lane { .. }
+                | V128Store16Lane { .. }
+                | V128Store32Lane { .. }
+                | V128Store64Lane { .. } => {
+                    val_v128_needed = true;
+                    injection_points.push(idx)
+                }
                 _ => (),
             }
         }
@@ -1360,22 +1763,28 @@ fn inject_mem_barrier(func_body: &mut ic_wasm_transform::Body, func_type: &FuncT
         // the total number of locals.
         let n_locals: u32 = func_body.locals.iter().map(|x| x.0).sum();
         let mut next_local = func_type.params().len() as u32 + n_locals;
-        let arg_i32_addr_idx = next_local;
+        let arg_addr_idx = next_local;
         next_local += 1;
+        let addr_type = if is_wasm64 {
+            ValType::I64
+        } else {
+            ValType::I32
+        };
+        func_body.locals.push((1, addr_type));
 
         // conditionally add following locals
         let arg_i32_val_idx;
         let arg_i64_val_idx;
         let arg_f32_val_idx;
         let arg_f64_val_idx;
+        let arg_v128_val_idx;
 
         if val_i32_needed {
             arg_i32_val_idx = next_local;
             next_local += 1;
-            func_body.locals.push((2, ValType::I32)); // addr and val locals
+            func_body.locals.push((1, ValType::I32));
         } else {
             arg_i32_val_idx = u32::MAX; // not used
-            func_body.locals.push((1, ValType::I32)); // only addr local
         }
 
         if val_i64_needed {
@@ -1396,12 +1805,20 @@ fn inject_mem_barrier(func_body: &mut ic_wasm_transform::Body, func_type: &FuncT
 
         if val_f64_needed {
             arg_f64_val_idx = next_local;
-            // next_local += 1;
+            next_local += 1;
             func_body.locals.push((1, ValType::F64));
         } else {
             arg_f64_val_idx = u32::MAX;
         }
 
+        if val_v128_needed {
+            arg_v128_val_idx = next_local;
+            // next_local += 1;
+            func_body.locals.push((1, ValType::V128));
+        } else {
+            arg_v128_val_idx = u32::MAX;
+        }
+
         let orig_elems = &func_body.instructions;
         let mut elems: Vec<Operator> = Vec::new();
         let mut last_injection_position = 0;
@@ -1414,7 +1831,8 @@ fn inject_mem_barrier(func_body: &mut ic_wasm_transform::Body, func_type: &FuncT
                     elems.extend_from_slice(&write_barrier_instructions(
                         memarg.offset,
                         arg_i32_val_idx,
-                        arg_i32_addr_idx,
+                        arg_addr_idx,
+                        is_wasm64,
                     ));
                 }
                 I64Store { memarg }
@@ -1424,21 +1842,36 @@ fn inject_mem_barrier(func_body: &mut ic_wasm_transform::Body, func_type: &FuncT
                     elems.extend_from_slice(&write_barrier_instructions(
                         memarg.offset,
                         arg_i64_val_idx,
-                        arg_i32_addr_idx,
+                        arg_addr_idx,
+                        is_wasm64,
                     ));
                 }
                 F32Store { memarg } => {
                     elems.extend_from_slice(&write_barrier_instructions(
                         memarg.offset,
                         arg_f32_val_idx,
-                        arg_i32_addr_idx,
+                        arg_addr_idx,
+                        is_wasm64,
                     ));
                 }
                 F64Store { memarg } => {
                     elems.extend_from_slice(&write_barrier_instructions(
                         memarg.offset,
                         arg_f64_val_idx,
-                        arg_i32_addr_idx,
+                        arg_addr_idx,
+                        is_wasm64,
+                    ));
+                }
+                V128Store { memarg }
+                | V128Store8Lane { memarg, .. }
+                | V128Store16Lane { memarg, .. }
+                | V128Store32Lane { memarg, .. }
+                | V128Store64Lane { memarg, .. } => {
+                    elems.extend_from_slice(&write_barrier_instructions(
+                        memarg.offset,
+                        arg_v128_val_idx,
+                        arg_addr_idx,
+                        is_wasm64,
                     ));
                 }
                 _ => {}
@@ -1457,19 +1890,25 @@ fn inject_mem_barrier(func_body: &mut ic_wasm_transform::Body, func_type: &FuncT
 // `table.grow` instruction to make sure that there's enough available memory
 // left to support the requested extra memory. If no `memory.grow` or
 // `table.grow` instructions are present then the code remains unchanged.
-fn inject_update_available_memory(func_body: &mut ic_wasm_transform::Body, func_type: &FuncType) {
-    // This is an overestimation of table element size computed based on the
-    // existing canister limits.
-    const TABLE_ELEMENT_SIZE: u32 = 1024;
+//
+// For 64-bit memories, the argument and the result of `memory.grow` are
+// wrapped to i32 before calling `update_available_memory`, and the result is
+// extended back to i64 afterwards. This is safe because the size of 64-bit
+// memories is limited to a number of Wasm pages that fits into an i32.
+fn inject_update_available_memory(
+    func_body: &mut ic_wasm_transform::Body,
+    func_type: &FuncType,
+    is_wasm64: bool,
+) {
     use Operator::*;
-    let mut injection_points: Vec<(usize, u32)> = Vec::new();
+    let mut injection_points: Vec<(usize, GrowableMemory, bool)> = Vec::new();
     {
         for (idx, instr) in func_body.instructions.iter().enumerate() {
             if let MemoryGrow { .. } = instr {
-                injection_points.push((idx, WASM_PAGE_SIZE));
+                injection_points.push((idx, GrowableMemory::WasmMemory, is_wasm64));
             }
             if let TableGrow { .. } = instr {
-                injection_points.push((idx, TABLE_ELEMENT_SIZE));
+                injection_points.push((idx, GrowableMemory::Table, false));
             }
         }
     }
@@ -1482,13 +1921,41 @@ fn inject_update_available_memory(func_body: &mut ic_wasm_transform::Body, func_
         let n_locals: u32 = func_body.locals.iter().map(|x| x.0).sum();
         let memory_local_ix = func_type.params().len() as u32 + n_locals;
         func_body.locals.push((1, ValType::I32));
+        // A second local is needed for the i64 argument of `memory.grow` on
+        // 64-bit memories.
+        let memory64_local_ix = memory_local_ix + 1;
+        if is_wasm64 {
+            func_body.locals.push((1, ValType::I64));
+        }
 
         let orig_elems = &func_body.instructions;
         let mut elems: Vec<Operator> = Vec::new();
         let mut last_injection_position = 0;
-        for (point, element_size) in injection_points {
+        for (point, memory, is_memory64_grow) in injection_points {
             let update_available_memory_instr = orig_elems[point].clone();
             elems.extend_from_slice(&orig_elems[last_injection_position..point]);
+            if is_memory64_grow {
+                elems.extend_from_slice(&[
+                    LocalTee {
+                        local_index: memory64_local_ix,
+                    },
+                    update_available_memory_instr,
+                    I32WrapI64,
+                    LocalGet {
+                        local_index: memory64_local_ix,
+                    },
+                    I32WrapI64,
+                    I32Const {
+                        value: memory as i32,
+                    },
+                    Call {
+                        function_index: InjectedImports::UpdateAvailableMemory as u32,
+                    },
+                    I64ExtendI32S,
+                ]);
+                last_injection_position = point + 1;
+                continue;
+            }
             // At this point we have a memory.grow so the argument to it will be on top of
             // the stack, which we just assign to `memory_local_ix` with a local.tee
             // instruction.
@@ -1501,7 +1968,7 @@ fn inject_update_available_memory(func_body: &mut ic_wasm_transform::Body, func_
                     local_index: memory_local_ix,
                 },
                 I32Const {
-                    value: element_size as i32,
+                    value: memory as i32,
                 },
                 Call {
                     function_index: InjectedImports::UpdateAvailableMemory as u32,
@@ -1633,6 +2100,45 @@ fn injections_new(code: &[Operator]) -> Vec<InjectionPoint> {
     res
 }
 
+// Evaluates the offset expression of a data segment. Besides a single constant,
+// the offset can be computed by the integer arithmetic of the extended-const
+// proposal. Returns `None` for any other expression.
+fn evaluate_offset_expr(offset_expr: &[Operator]) -> Option<usize> {
+    let mut stack: Vec<i64> = vec![];
+    for op in offset_expr {
+        match op {
+            Operator::I32Const { value } => stack.push(*value as i64),
+            Operator::I64Const { value } => stack.push(*value),
+            Operator::I32Add | Operator::I32Sub | Operator::I32Mul => {
+                let rhs = stack.pop()? as i32;
+                let lhs = stack.pop()? as i32;
+                let result = match op {
+                    Operator::I32Add => lhs.wrapping_add(rhs),
+                    Operator::I32Sub => lhs.wrapping_sub(rhs),
+                    _ => lhs.wrapping_mul(rhs),
+                };
+                stack.push(result as i64);
+            }
+            Operator::I64Add | Operator::I64Sub | Operator::I64Mul => {
+                let rhs = stack.pop()?;
+                let lhs = stack.pop()?;
+                let result = match op {
+                    Operator::I64Add => lhs.wrapping_add(rhs),
+                    Operator::I64Sub => lhs.wrapping_sub(rhs),
+                    _ => lhs.wrapping_mul(rhs),
+                };
+                stack.push(result);
+            }
+            Operator::End => break,
+            _ => return None,
+        }
+    }
+    match stack.as_slice() {
+        [offset] => Some(*offset as usize),
+        _ => None,
+    }
+}
+
 // Looks for the data section and if it is present, converts it to a vector of
 // tuples (heap offset, bytes) and then deletes the section.
 fn get_data(
@@ -1645,9 +2151,9 @@ fn get_data(
                 ic_wasm_transform::DataSegmentKind::Active {
                     memory_index: _,
                     offset_expr,
-                } => match offset_expr {
-                    Operator::I32Const { value } => *value as usize,
-                    _ => return Err(WasmInstrumentationError::WasmDeserializeError(WasmError::new(
+                } => match evaluate_offset_expr(offset_expr) {
+                    Some(offset) => offset,
+                    None => return Err(WasmInstrumentationError::WasmDeserializeError(WasmError::new(
                         "complex initialization expressions for data segments are not supported!".into()
                     ))),
                 },
@@ -1693,9 +2199,23 @@ fn update_memories(
     mut module: Module,
     write_barrier: FlagStatus,
     wasm_native_stable_memory: FlagStatus,
+    max_wasm64_memory_size: NumBytes,
 ) -> (Module, u32) {
     let mut stable_index = 0;
 
+    // Cap the maximum of a 64-bit memory at the configured limit so that the
+    // bytemap covers the whole memory.
+    let max_wasm64_memory_pages = max_wasm64_memory_size.get() / WASM_PAGE_SIZE as u64;
+    let mut bytemap_size_in_wasm_pages = BYTEMAP_SIZE_IN_WASM_PAGES;
+    if let Some(memory) = module.memories.first_mut() {
+        if memory.memory64 {
+            memory.maximum = Some(memory.maximum.map_or(max_wasm64_memory_pages, |max| {
+                max.min(max_wasm64_memory_pages)
+            }));
+            bytemap_size_in_wasm_pages = wasm64_bytemap_size_in_wasm_pages(max_wasm64_memory_size);
+        }
+    }
+
     let mut memory_already_exported = false;
     for export in &mut module.exports {
         if let ExternalKind::Memory = export.kind {
@@ -1717,8 +2237,8 @@ fn update_memories(
         module.memories.push(MemoryType {
             memory64: false,
             shared: false,
-            initial: BYTEMAP_SIZE_IN_WASM_PAGES,
-            maximum: Some(BYTEMAP_SIZE_IN_WASM_PAGES),
+            initial: bytemap_size_in_wasm_pages,
+            maximum: Some(bytemap_size_in_wasm_pages),
         });
 
         module.exports.push(Export {
@@ -35,6 +35,7 @@ pub(super) fn replacement_functions(
     subnet_type: SubnetType,
     dirty_page_overhead: NumInstructions,
     metering_type: MeteringType,
+    is_wasm64: bool,
 ) -> Vec<(SystemApiFunc, (FuncType, Body<'static>))> {
     let count_clean_pages_fn_index = special_indices.count_clean_pages_fn.unwrap();
     let dirty_pages_counter_index = special_indices.dirty_pages_counter_ix.unwrap();
@@ -45,6 +46,11 @@ pub(super) fn replacement_functions(
     use Operator::*;
     let page_size_shift = PAGE_SIZE.trailing_zeros() as i32;
     let stable_memory_bytemap_index = stable_memory_index + 1;
+    // Heap addresses and lengths passed to `memory.copy` need to match the
+    // index type of the Wasm heap, which is i64 for 64-bit memories.
+    let extend_to_heap_index = if is_wasm64 { I64ExtendI32U } else { Nop };
+    let wrap_to_heap_index = if is_wasm64 { Nop } else { I32WrapI64 };
+    let max_heap_address = if is_wasm64 { i64::MAX } else { u32::MAX as i64 };
     vec![
         (
             SystemApiFunc::StableSize,
@@ -454,9 +460,11 @@ pub(super) fn replacement_functions(
                             },
                             Else,
                             LocalGet { local_index: DST },
+                            extend_to_heap_index.clone(),
                             LocalGet { local_index: SRC },
                             I64ExtendI32U,
                             LocalGet { local_index: LEN },
+                            extend_to_heap_index.clone(),
                             MemoryCopy {
                                 dst_mem: 0,
                                 src_mem: stable_memory_index,
@@ -567,11 +575,11 @@ pub(super) fn replacement_functions(
                                 function_index: InjectedImports::InternalTrap as u32,
                             },
                             End,
-                            // check if these i64 hold valid i32 heap addresses
+                            // check if these i64 hold valid heap addresses
                             // check dst
                             LocalGet { local_index: DST },
                             I64Const {
-                                value: u32::MAX as i64,
+                                value: max_heap_address,
                             },
                             I64GtU,
                             If {
@@ -587,7 +595,7 @@ pub(super) fn replacement_functions(
                             // check len
                             LocalGet { local_index: LEN },
                             I64Const {
-                                value: u32::MAX as i64,
+                                value: max_heap_address,
                             },
                             I64GtU,
                             If {
@@ -743,10 +751,10 @@ pub(super) fn replacement_functions(
                             },
                             Else,
                             LocalGet { local_index: DST },
-                            I32WrapI64,
+                            wrap_to_heap_index.clone(),
                             LocalGet { local_index: SRC },
                             LocalGet { local_index: LEN },
-                            I32WrapI64,
+                            wrap_to_heap_index.clone(),
                             MemoryCopy {
                                 dst_mem: 0,
                                 src_mem: stable_memory_index,
@@ -968,7 +976,9 @@ pub(super) fn replacement_functions(
                             LocalGet { local_index: DST },
                             I64ExtendI32U,
                             LocalGet { local_index: SRC },
+                            extend_to_heap_index.clone(),
                             LocalGet { local_index: LEN },
+                            extend_to_heap_index.clone(),
                             MemoryCopy {
                                 dst_mem: stable_memory_index,
                                 src_mem: 0,
@@ -1088,11 +1098,11 @@ pub(super) fn replacement_functions(
                                 function_index: InjectedImports::InternalTrap as u32,
                             },
                             End,
-                            // check if these i64 hold valid i32 heap addresses
+                            // check if these i64 hold valid heap addresses
                             // check src
                             LocalGet { local_index: SRC },
                             I64Const {
-                                value: u32::MAX as i64,
+                                value: max_heap_address,
                             },
                             I64GtU,
                             If {
@@ -1108,7 +1118,7 @@ pub(super) fn replacement_functions(
                             // check len
                             LocalGet { local_index: LEN },
                             I64Const {
-                                value: u32::MAX as i64,
+                                value: max_heap_address,
                             },
                             I64GtU,
                             If {
@@ -1225,9 +1235,9 @@ pub(super) fn replacement_functions(
                             // copy memory contents
                             LocalGet { local_index: DST },
                             LocalGet { local_index: SRC },
-                            I32WrapI64,
+                            wrap_to_heap_index.clone(),
                             LocalGet { local_index: LEN },
-                            I32WrapI64,
+                            wrap_to_heap_index.clone(),
                             MemoryCopy {
                                 dst_mem: stable_memory_index,
                                 src_mem: 0,
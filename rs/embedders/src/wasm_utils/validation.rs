@@ -3,7 +3,7 @@
 
 use super::{Complexity, WasmImportsDetails, WasmValidationDetails};
 
-use ic_config::embedders::Config as EmbeddersConfig;
+use ic_config::{embedders::Config as EmbeddersConfig, flag_status::FlagStatus};
 use ic_replicated_state::canister_state::execution_state::{
     CustomSection, CustomSectionType, WasmMetadata,
 };
@@ -22,6 +22,7 @@ use crate::wasmtime_embedder::{
     STABLE_BYTEMAP_MEMORY_NAME, STABLE_MEMORY_NAME, WASM_HEAP_MEMORY_NAME,
 };
 use wasmparser::{ExternalKind, FuncType, Operator, StructuralType, TypeRef, ValType};
+use wasmtime_environ::WASM_PAGE_SIZE;
 
 /// Symbols that are reserved and cannot be exported by canisters.
 #[doc(hidden)] // pub for usage in tests
@@ -56,7 +57,13 @@ pub(super) const API_VERSION_IC0: &str = "ic0";
 // user tries to import a function that doesn't exist in any of the expected
 // modules vs the case where the function exists but is imported from the wrong
 // module.
-fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>> {
+fn get_valid_system_apis(is_wasm64: bool) -> HashMap<String, HashMap<String, FunctionSignature>> {
+    // Heap addresses and sizes are i64 for modules with a 64-bit memory.
+    let ptr_type = if is_wasm64 {
+        ValType::I64
+    } else {
+        ValType::I32
+    };
     let valid_system_apis = vec![
         (
             // Public methods
@@ -65,7 +72,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
                 API_VERSION_IC0,
                 FunctionSignature {
                     param_types: vec![],
-                    return_type: vec![ValType::I32],
+                    return_type: vec![ptr_type],
                 },
             )],
         ),
@@ -74,7 +81,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I32, ValType::I32, ValType::I32],
+                    param_types: vec![ptr_type, ptr_type, ptr_type],
                     return_type: vec![],
                 },
             )],
@@ -85,7 +92,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
                 API_VERSION_IC0,
                 FunctionSignature {
                     param_types: vec![],
-                    return_type: vec![ValType::I32],
+                    return_type: vec![ptr_type],
                 },
             )],
         ),
@@ -94,7 +101,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I32, ValType::I32, ValType::I32],
+                    param_types: vec![ptr_type, ptr_type, ptr_type],
                     return_type: vec![],
                 },
             )],
@@ -105,7 +112,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
                 API_VERSION_IC0,
                 FunctionSignature {
                     param_types: vec![],
-                    return_type: vec![ValType::I32],
+                    return_type: vec![ptr_type],
                 },
             )],
         ),
@@ -114,7 +121,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I32, ValType::I32, ValType::I32],
+                    param_types: vec![ptr_type, ptr_type, ptr_type],
                     return_type: vec![],
                 },
             )],
@@ -145,7 +152,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
                 API_VERSION_IC0,
                 FunctionSignature {
                     param_types: vec![],
-                    return_type: vec![ValType::I32],
+                    return_type: vec![ptr_type],
                 },
             )],
         ),
@@ -154,7 +161,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I32, ValType::I32, ValType::I32],
+                    param_types: vec![ptr_type, ptr_type, ptr_type],
                     return_type: vec![],
                 },
             )],
@@ -164,7 +171,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I32, ValType::I32],
+                    param_types: vec![ptr_type, ptr_type],
                     return_type: vec![],
                 },
             )],
@@ -184,7 +191,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I32, ValType::I32],
+                    param_types: vec![ptr_type, ptr_type],
                     return_type: vec![],
                 },
             )],
@@ -195,7 +202,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
                 API_VERSION_IC0,
                 FunctionSignature {
                     param_types: vec![],
-                    return_type: vec![ValType::I32],
+                    return_type: vec![ptr_type],
                 },
             )],
         ),
@@ -204,7 +211,97 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I32, ValType::I32, ValType::I32],
+                    param_types: vec![ptr_type, ptr_type, ptr_type],
+                    return_type: vec![],
+                },
+            )],
+        ),
+        (
+            "subnet_self_size",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![],
+                    return_type: vec![ptr_type],
+                },
+            )],
+        ),
+        (
+            "subnet_self_copy",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![ptr_type, ptr_type, ptr_type],
+                    return_type: vec![],
+                },
+            )],
+        ),
+        (
+            "in_replicated_execution",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![],
+                    return_type: vec![ValType::I32],
+                },
+            )],
+        ),
+        (
+            "env_var_count",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![],
+                    return_type: vec![ptr_type],
+                },
+            )],
+        ),
+        (
+            "env_var_name_size",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![ptr_type],
+                    return_type: vec![ptr_type],
+                },
+            )],
+        ),
+        (
+            "env_var_name_copy",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![ptr_type, ptr_type, ptr_type, ptr_type],
+                    return_type: vec![],
+                },
+            )],
+        ),
+        (
+            "env_var_name_exists",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![ptr_type, ptr_type],
+                    return_type: vec![ValType::I32],
+                },
+            )],
+        ),
+        (
+            "env_var_value_size",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![ptr_type, ptr_type],
+                    return_type: vec![ptr_type],
+                },
+            )],
+        ),
+        (
+            "env_var_value_copy",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![ptr_type, ptr_type, ptr_type, ptr_type, ptr_type],
                     return_type: vec![],
                 },
             )],
@@ -226,14 +323,8 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
                 API_VERSION_IC0,
                 FunctionSignature {
                     param_types: vec![
-                        ValType::I32,
-                        ValType::I32,
-                        ValType::I32,
-                        ValType::I32,
-                        ValType::I32,
-                        ValType::I32,
-                        ValType::I32,
-                        ValType::I32,
+                        ptr_type, ptr_type, ptr_type, ptr_type, ptr_type, ptr_type, ptr_type,
+                        ptr_type,
                     ],
                     return_type: vec![],
                 },
@@ -244,7 +335,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I32, ValType::I32],
+                    param_types: vec![ptr_type, ptr_type],
                     return_type: vec![],
                 },
             )],
@@ -254,7 +345,17 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I32, ValType::I32],
+                    param_types: vec![ptr_type, ptr_type],
+                    return_type: vec![],
+                },
+            )],
+        ),
+        (
+            "call_with_best_effort_response",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![ValType::I32],
                     return_type: vec![],
                 },
             )],
@@ -285,7 +386,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I32, ValType::I32],
+                    param_types: vec![ptr_type, ptr_type],
                     return_type: vec![],
                 },
             )],
@@ -390,6 +491,16 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
                 },
             )],
         ),
+        (
+            "timer_set",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![ValType::I64, ValType::I64],
+                    return_type: vec![ValType::I64],
+                },
+            )],
+        ),
         (
             "performance_counter",
             vec![(
@@ -415,7 +526,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I32, ValType::I32],
+                    param_types: vec![ptr_type, ptr_type],
                     return_type: vec![],
                 },
             )],
@@ -465,7 +576,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I32, ValType::I32],
+                    param_types: vec![ptr_type, ptr_type],
                     return_type: vec![],
                 },
             )],
@@ -486,7 +597,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
                 API_VERSION_IC0,
                 FunctionSignature {
                     param_types: vec![],
-                    return_type: vec![ValType::I32],
+                    return_type: vec![ptr_type],
                 },
             )],
         ),
@@ -495,7 +606,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I32, ValType::I32, ValType::I32],
+                    param_types: vec![ptr_type, ptr_type, ptr_type],
                     return_type: vec![],
                 },
             )],
@@ -535,7 +646,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I32],
+                    param_types: vec![ptr_type],
                     return_type: vec![],
                 },
             )],
@@ -545,7 +656,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I32],
+                    param_types: vec![ptr_type],
                     return_type: vec![],
                 },
             )],
@@ -555,7 +666,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I32],
+                    param_types: vec![ptr_type],
                     return_type: vec![],
                 },
             )],
@@ -565,7 +676,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I64, ValType::I64, ValType::I32],
+                    param_types: vec![ValType::I64, ValType::I64, ptr_type],
                     return_type: vec![],
                 },
             )],
@@ -575,7 +686,7 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I32, ValType::I32],
+                    param_types: vec![ptr_type, ptr_type],
                     return_type: vec![ValType::I32],
                 },
             )],
@@ -585,11 +696,51 @@ fn get_valid_system_apis() -> HashMap<String, HashMap<String, FunctionSignature>
             vec![(
                 API_VERSION_IC0,
                 FunctionSignature {
-                    param_types: vec![ValType::I64, ValType::I64, ValType::I32],
+                    param_types: vec![ValType::I64, ValType::I64, ptr_type],
+                    return_type: vec![],
+                },
+            )],
+        ),
+        (
+            "cost_call",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![ValType::I64, ValType::I64, ptr_type],
+                    return_type: vec![],
+                },
+            )],
+        ),
+        (
+            "cost_create_canister",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![ptr_type],
                     return_type: vec![],
                 },
             )],
         ),
+        (
+            "cost_http_request",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![ValType::I64, ValType::I64, ptr_type],
+                    return_type: vec![],
+                },
+            )],
+        ),
+        (
+            "cost_sign_with_ecdsa",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![ptr_type, ptr_type, ValType::I32, ptr_type],
+                    return_type: vec![ValType::I32],
+                },
+            )],
+        ),
     ];
 
     valid_system_apis
@@ -673,6 +824,13 @@ fn get_valid_exported_functions() -> HashMap<String, FunctionSignature> {
                 return_type: vec![],
             },
         ),
+        (
+            "canister_on_low_wasm_memory",
+            FunctionSignature {
+                param_types: vec![],
+                return_type: vec![],
+            },
+        ),
     ];
 
     valid_exported_functions
@@ -734,7 +892,7 @@ fn validate_import_section(module: &Module) -> Result<WasmImportsDetails, WasmVa
     let mut imports_details = WasmImportsDetails::default();
 
     if !module.imports.is_empty() {
-        let valid_system_apis = get_valid_system_apis();
+        let valid_system_apis = get_valid_system_apis(is_wasm64(module));
         for entry in &module.imports {
             let import_module = entry.module;
             let field = entry.name;
@@ -783,12 +941,17 @@ fn validate_import_section(module: &Module) -> Result<WasmImportsDetails, WasmVa
                         ));
                     }
                 }
-                TypeRef::Memory(_) => {
+                TypeRef::Memory(memory_type) => {
                     if field == WASM_HEAP_MEMORY_NAME && import_module != "env" {
                         return Err(WasmValidationError::InvalidImportSection(
                             "Only memory imported from env.memory is allowed.".to_string(),
                         ));
                     };
+                    if memory_type.memory64 {
+                        return Err(WasmValidationError::InvalidImportSection(
+                            "Importing a 64-bit memory is not allowed.".to_string(),
+                        ));
+                    }
                 }
                 TypeRef::Global(_) => {
                     return Err(WasmValidationError::InvalidImportSection(
@@ -835,6 +998,7 @@ fn validate_export_section(
             "canister_inspect_message",
             "canister_heartbeat",
             "canister_global_timer",
+            "canister_on_low_wasm_memory",
         ];
         let mut number_exported_functions = 0;
         let mut sum_exported_function_name_lengths = 0;
@@ -919,11 +1083,12 @@ fn validate_export_section(
     Ok(())
 }
 
-// Checks that offset-expressions in data sections consist of only one constant
-// expression. Required because of OP. See also:
-// instrumentation.rs
+// Checks that offset-expressions in data sections consist of only constants
+// and the integer arithmetic of the extended-const proposal, of the index type
+// of the memory (i64 for 64-bit memories, i32 otherwise). Required because of
+// OP. See also: instrumentation.rs
 fn validate_data_section(module: &Module) -> Result<(), WasmValidationError> {
-    fn validate_segment(s: &DataSegment) -> Result<(), WasmValidationError> {
+    fn validate_segment(s: &DataSegment, is_wasm64: bool) -> Result<(), WasmValidationError> {
         match &s.kind {
             DataSegmentKind::Passive => Err(WasmValidationError::InvalidDataSection(
                 "Empty offset in data segment.".to_string(),
@@ -931,18 +1096,72 @@ fn validate_data_section(module: &Module) -> Result<(), WasmValidationError> {
             DataSegmentKind::Active {
                 memory_index: _,
                 offset_expr,
-            } => match offset_expr {
-                Operator::I32Const { .. } => Ok(()),
-                _ => Err(WasmValidationError::InvalidDataSection(format!(
-                    "Invalid offset expression in data segment: {:?}",
-                    offset_expr
-                ))),
-            },
+            } => {
+                for op in offset_expr {
+                    match op {
+                        Operator::I32Const { .. }
+                        | Operator::I32Add
+                        | Operator::I32Sub
+                        | Operator::I32Mul
+                            if !is_wasm64 => {}
+                        Operator::I64Const { .. }
+                        | Operator::I64Add
+                        | Operator::I64Sub
+                        | Operator::I64Mul
+                            if is_wasm64 => {}
+                        Operator::End => {}
+                        _ => {
+                            return Err(WasmValidationError::InvalidDataSection(format!(
+                                "Invalid offset expression in data segment: {:?}",
+                                op
+                            )))
+                        }
+                    }
+                }
+                Ok(())
+            }
         }
     }
 
+    let is_wasm64 = is_wasm64(module);
     for d in &module.data {
-        validate_segment(d)?;
+        validate_segment(d, is_wasm64)?;
+    }
+    Ok(())
+}
+
+// Returns true if the module defines a 64-bit memory.
+fn is_wasm64(module: &Module) -> bool {
+    module
+        .memories
+        .first()
+        .map_or(false, |memory| memory.memory64)
+}
+
+// Checks that the initial and maximum size of a 64-bit memory do not exceed the
+// configured limit. The limits of 32-bit memories are checked by Wasmtime.
+fn validate_memory_section(
+    module: &Module,
+    max_wasm64_memory_size: NumBytes,
+) -> Result<(), WasmValidationError> {
+    let max_pages = max_wasm64_memory_size.get() / WASM_PAGE_SIZE as u64;
+    for memory in module.memories.iter().filter(|memory| memory.memory64) {
+        if memory.initial > max_pages {
+            return Err(WasmValidationError::InvalidMemorySection(format!(
+                "The initial size of the 64-bit memory is {} Wasm pages, \
+                 which exceeds the maximum of {} pages.",
+                memory.initial, max_pages
+            )));
+        }
+        if let Some(maximum) = memory.maximum {
+            if maximum > max_pages {
+                return Err(WasmValidationError::InvalidMemorySection(format!(
+                    "The maximum size of the 64-bit memory is {} Wasm pages, \
+                     which exceeds the maximum of {} pages.",
+                    maximum, max_pages
+                )));
+            }
+        }
     }
     Ok(())
 }
@@ -1333,18 +1552,26 @@ pub fn wasmtime_validation_config(embedder_config: &EmbeddersConfig) -> wasmtime
     config.wasm_backtrace(false);
     config.wasm_backtrace_details(wasmtime::WasmBacktraceDetails::Disable);
     config.wasm_bulk_memory(true);
+    // Extended constant expressions only add integer arithmetic, which is
+    // deterministic.
+    config.wasm_extended_const(true);
     config.wasm_function_references(false);
-    // Wasm memory64 and multi-memory features are disabled during validation,
-    // but enabled during execution for the Wasm-native stable memory
+    // Wasm memory64 is only allowed for the canister heap if the `wasm64`
+    // feature flag is enabled. Multi-memory is disabled during validation.
+    // Both are enabled during execution for the Wasm-native stable memory
     // implementation.
-    config.wasm_memory64(false);
+    config.wasm_memory64(embedder_config.feature_flags.wasm64 == FlagStatus::Enabled);
     config.wasm_multi_memory(false);
     config.wasm_reference_types(true);
-    // The SIMD instructions are disable for determinism.
+    // Relaxed SIMD instructions have implementation-defined results and are
+    // disabled for determinism.
     config.wasm_relaxed_simd(false);
-    config.wasm_simd(false);
-    // Tail calls may be enabled in the future.
-    config.wasm_tail_call(false);
+    // Fixed-width SIMD instructions are deterministic except for NaN bit
+    // patterns, which are handled by the NaN canonicalization above.
+    config.wasm_simd(true);
+    // Tail calls are needed by compilers of functional languages. Every call
+    // still goes through the instruction counting at the function entry.
+    config.wasm_tail_call(true);
     // Threads are disabled for determinism.
     config.wasm_threads(false);
 
@@ -1413,6 +1640,7 @@ fn check_code_section_size(wasm: &BinaryEncodedWasm) -> Result<(), WasmValidatio
 /// * Export
 /// * Code
 /// * Data
+/// * Memory
 /// * Global
 /// * Function
 /// * CustomSections
@@ -1433,6 +1661,7 @@ pub(super) fn validate_wasm_binary<'a>(
         config.max_sum_exported_function_name_lengths,
     )?;
     validate_data_section(&module)?;
+    validate_memory_section(&module, config.max_wasm64_memory_size)?;
     validate_global_section(&module, config.max_globals)?;
     validate_function_section(&module, config.max_functions)?;
     let (largest_function_instruction_count, max_complexity) = validate_code_section(&module)?;
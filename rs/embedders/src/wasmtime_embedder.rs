@@ -1,4 +1,5 @@
 pub mod host_memory;
+mod profiler;
 mod signal_stack;
 mod system_api;
 pub mod system_api_complexity;
@@ -20,7 +21,8 @@ use wasmtime::{
 pub use host_memory::WasmtimeMemoryCreator;
 use ic_config::{embedders::Config as EmbeddersConfig, flag_status::FlagStatus};
 use ic_interfaces::execution_environment::{
-    HypervisorError, HypervisorResult, InstanceStats, SystemApi, TrapCode,
+    BacktraceFrame, CanisterBacktrace, HypervisorError, HypervisorResult, InstanceStats, SystemApi,
+    TrapCode, WasmProfile,
 };
 use ic_logger::{debug, error, fatal, ReplicaLogger};
 use ic_replicated_state::{
@@ -34,10 +36,11 @@ use ic_types::{
 };
 use ic_wasm_types::{BinaryEncodedWasm, WasmEngineError};
 use memory_tracker::{DirtyPageTracking, PageBitmap, SigsegvMemoryTracker};
+use profiler::Profiler;
 use signal_stack::WasmtimeSignalStack;
 
 use crate::wasm_utils::instrumentation::{
-    ACCESSED_PAGES_COUNTER_GLOBAL_NAME, DIRTY_PAGES_COUNTER_GLOBAL_NAME,
+    InjectedImports, ACCESSED_PAGES_COUNTER_GLOBAL_NAME, DIRTY_PAGES_COUNTER_GLOBAL_NAME,
     INSTRUCTIONS_COUNTER_GLOBAL_NAME,
 };
 use crate::{
@@ -57,6 +60,50 @@ pub(crate) const WASM_HEAP_BYTEMAP_MEMORY_NAME: &str = "bytemap_memory";
 pub(crate) const STABLE_MEMORY_NAME: &str = "stable_memory";
 pub(crate) const STABLE_BYTEMAP_MEMORY_NAME: &str = "stable_bytemap_memory";
 
+/// The maximum number of frames reported in a canister backtrace. Deeper
+/// backtraces, e.g. of a stack overflow, are truncated.
+const MAX_CANISTER_BACKTRACE_FRAMES: usize = 50;
+
+/// Symbolizes the Wasm backtrace attached to the given error, if any. Frames
+/// of the instrumented module are mapped back to the function indices of the
+/// original module by subtracting the number of injected imports.
+fn canister_backtrace(err: &anyhow::Error, injected_imports: u32) -> Option<CanisterBacktrace> {
+    let backtrace = err.downcast_ref::<wasmtime::WasmBacktrace>()?;
+    let frames = backtrace.frames();
+    Some(CanisterBacktrace {
+        frames: frames
+            .iter()
+            .take(MAX_CANISTER_BACKTRACE_FRAMES)
+            .map(|frame| BacktraceFrame {
+                func_index: frame.func_index().saturating_sub(injected_imports),
+                func_name: frame.func_name().map(|name| name.to_string()),
+            })
+            .collect(),
+        omitted_frames: frames.len().saturating_sub(MAX_CANISTER_BACKTRACE_FRAMES),
+    })
+}
+
+/// Attaches the backtrace of `source` to `err` if both of them are traps and
+/// `err` doesn't have a backtrace yet.
+fn with_backtrace_of(err: HypervisorError, source: &HypervisorError) -> HypervisorError {
+    match (err, source) {
+        (
+            HypervisorError::Trapped {
+                trap_code,
+                backtrace: None,
+            },
+            HypervisorError::Trapped {
+                backtrace: Some(backtrace),
+                ..
+            },
+        ) => HypervisorError::Trapped {
+            trap_code,
+            backtrace: Some(backtrace.clone()),
+        },
+        (err, _) => err,
+    }
+}
+
 fn wasmtime_error_to_hypervisor_error(err: anyhow::Error) -> HypervisorError {
     match err.downcast::<wasmtime::Trap>() {
         Ok(trap) => trap_code_to_hypervisor_error(trap),
@@ -85,31 +132,67 @@ fn wasmtime_error_to_hypervisor_error(err: anyhow::Error) -> HypervisorError {
             if message.contains("argument type mismatch") || arguments_or_results_mismatch {
                 return HypervisorError::ContractViolation(BAD_SIGNATURE_MESSAGE.to_string());
             }
-            HypervisorError::Trapped(TrapCode::Other)
+            HypervisorError::Trapped {
+                trap_code: TrapCode::Other,
+                backtrace: None,
+            }
         }
     }
 }
 
 fn trap_code_to_hypervisor_error(trap: wasmtime::Trap) -> HypervisorError {
     match trap {
-        wasmtime::Trap::StackOverflow => HypervisorError::Trapped(TrapCode::StackOverflow),
-        wasmtime::Trap::MemoryOutOfBounds => HypervisorError::Trapped(TrapCode::HeapOutOfBounds),
-        wasmtime::Trap::TableOutOfBounds => HypervisorError::Trapped(TrapCode::TableOutOfBounds),
+        wasmtime::Trap::StackOverflow => HypervisorError::Trapped {
+            trap_code: TrapCode::StackOverflow,
+            backtrace: None,
+        },
+        wasmtime::Trap::MemoryOutOfBounds => HypervisorError::Trapped {
+            trap_code: TrapCode::HeapOutOfBounds,
+            backtrace: None,
+        },
+        wasmtime::Trap::TableOutOfBounds => HypervisorError::Trapped {
+            trap_code: TrapCode::TableOutOfBounds,
+            backtrace: None,
+        },
         wasmtime::Trap::BadSignature => {
             HypervisorError::ContractViolation(BAD_SIGNATURE_MESSAGE.to_string())
         }
-        wasmtime::Trap::IntegerDivisionByZero => {
-            HypervisorError::Trapped(TrapCode::IntegerDivByZero)
-        }
-        wasmtime::Trap::UnreachableCodeReached => HypervisorError::Trapped(TrapCode::Unreachable),
+        wasmtime::Trap::IntegerDivisionByZero => HypervisorError::Trapped {
+            trap_code: TrapCode::IntegerDivByZero,
+            backtrace: None,
+        },
+        wasmtime::Trap::UnreachableCodeReached => HypervisorError::Trapped {
+            trap_code: TrapCode::Unreachable,
+            backtrace: None,
+        },
         _ => {
             // The `wasmtime::TrapCode` enum is marked as #[non_exhaustive]
             // so we have to use the wildcard matching here.
-            HypervisorError::Trapped(TrapCode::Other)
+            HypervisorError::Trapped {
+                trap_code: TrapCode::Other,
+                backtrace: None,
+            }
         }
     }
 }
 
+/// Returns true if the heap memory exported by the instrumented `module` is a
+/// 64-bit memory.
+pub(crate) fn is_wasm64(module: &Module) -> bool {
+    module
+        .get_export(WASM_HEAP_MEMORY_NAME)
+        .and_then(|export| export.memory().map(|memory| memory.is_64()))
+        .unwrap_or(false)
+}
+
+/// Returns the maximum size of the exported Wasm memory in Wasm pages, if the
+/// module declares one.
+pub(crate) fn wasm_memory_max_pages(module: &Module) -> Option<u64> {
+    module
+        .get_export(WASM_HEAP_MEMORY_NAME)
+        .and_then(|export| export.memory().and_then(|memory| memory.maximum()))
+}
+
 fn get_exported_globals<T>(
     wasm_native_stable_memory: FlagStatus,
     instance: &Instance,
@@ -199,6 +282,11 @@ impl WasmtimeEmbedder {
         if embedder_config.feature_flags.wasm_native_stable_memory == FlagStatus::Enabled {
             config.wasm_memory64(true);
         }
+        // Capturing backtraces makes traps more expensive, so it is only done
+        // if canister backtraces are enabled.
+        if embedder_config.feature_flags.canister_backtrace == FlagStatus::Enabled {
+            config.wasm_backtrace(true);
+        }
         config
     }
 
@@ -226,13 +314,23 @@ impl WasmtimeEmbedder {
 
     pub fn pre_instantiate(&self, module: &Module) -> HypervisorResult<InstancePre<StoreData>> {
         let mut linker: wasmtime::Linker<StoreData> = Linker::new(module.engine());
-        system_api::syscalls(
-            &mut linker,
-            self.config.feature_flags,
-            self.config.stable_memory_dirty_page_limit,
-            self.config.stable_memory_accessed_page_limit,
-            self.config.metering_type,
-        );
+        if is_wasm64(module) {
+            system_api::syscalls::<u64>(
+                &mut linker,
+                self.config.feature_flags,
+                self.config.stable_memory_dirty_page_limit,
+                self.config.stable_memory_accessed_page_limit,
+                self.config.metering_type,
+            );
+        } else {
+            system_api::syscalls::<u32>(
+                &mut linker,
+                self.config.feature_flags,
+                self.config.stable_memory_dirty_page_limit,
+                self.config.stable_memory_accessed_page_limit,
+                self.config.metering_type,
+            );
+        }
 
         let instance_pre = linker.instantiate_pre(module).map_err(|e| {
             HypervisorError::WasmEngineError(WasmEngineError::FailedToInstantiateModule(format!(
@@ -342,6 +440,10 @@ impl WasmtimeEmbedder {
                 num_instructions_global: None,
                 log: self.log.clone(),
                 num_stable_dirty_pages_from_non_native_writes: NumPages::from(0),
+                profiler: match self.config.feature_flags.wasm_profiler {
+                    FlagStatus::Enabled => Some(Profiler::default()),
+                    FlagStatus::Disabled => None,
+                },
             },
         );
 
@@ -453,6 +555,8 @@ impl WasmtimeEmbedder {
             store,
             write_barrier: self.config.feature_flags.write_barrier,
             wasm_native_stable_memory: self.config.feature_flags.wasm_native_stable_memory,
+            canister_backtrace: self.config.feature_flags.canister_backtrace,
+            wasm_profiler: self.config.feature_flags.wasm_profiler,
             modification_tracking,
             dirty_page_overhead: self.config.dirty_page_overhead,
             #[cfg(debug_assertions)]
@@ -646,6 +750,8 @@ pub struct StoreData {
     pub log: ReplicaLogger,
     /// Tracks the number of dirty pages in stable memory in non-native stable mode
     pub num_stable_dirty_pages_from_non_native_writes: NumPages,
+    /// Collects the Wasm profile if the Wasm profiler is enabled.
+    pub(crate) profiler: Option<Profiler>,
 }
 
 impl StoreData {
@@ -697,6 +803,8 @@ pub struct WasmtimeInstance {
     store: wasmtime::Store<StoreData>,
     write_barrier: FlagStatus,
     wasm_native_stable_memory: FlagStatus,
+    canister_backtrace: FlagStatus,
+    wasm_profiler: FlagStatus,
     modification_tracking: ModificationTracking,
     dirty_page_overhead: NumInstructions,
     #[cfg(debug_assertions)]
@@ -704,6 +812,27 @@ pub struct WasmtimeInstance {
 }
 
 impl WasmtimeInstance {
+    /// Converts an error returned by Wasmtime and attaches the symbolized
+    /// backtrace to traps if canister backtraces are enabled.
+    fn convert_wasmtime_error(&self, err: anyhow::Error) -> HypervisorError {
+        let backtrace = match self.canister_backtrace {
+            FlagStatus::Enabled => canister_backtrace(
+                &err,
+                InjectedImports::count(self.wasm_native_stable_memory, self.wasm_profiler) as u32,
+            ),
+            FlagStatus::Disabled => None,
+        };
+        match (wasmtime_error_to_hypervisor_error(err), backtrace) {
+            (HypervisorError::Trapped { trap_code, .. }, Some(backtrace)) => {
+                HypervisorError::Trapped {
+                    trap_code,
+                    backtrace: Some(backtrace),
+                }
+            }
+            (err, _) => err,
+        }
+    }
+
     pub fn into_store_data(self) -> StoreData {
         self.store.into_data()
     }
@@ -727,7 +856,7 @@ impl WasmtimeInstance {
                 HypervisorError::ContractViolation("export is not a function".to_string())
             })?
             .call(&mut self.store, args, &mut [])
-            .map_err(wasmtime_error_to_hypervisor_error)
+            .map_err(|err| self.convert_wasmtime_error(err))
     }
 
     fn page_accesses(&mut self) -> HypervisorResult<PageAccessResults> {
@@ -841,7 +970,7 @@ impl WasmtimeInstance {
                     )
                 })?
                 .call(&mut self.store, &[Val::I32(closure.env as i32)], &mut [])
-                .map_err(wasmtime_error_to_hypervisor_error),
+                .map_err(|err| self.convert_wasmtime_error(err)),
         }
         .map_err(|e| {
             let exec_err = self
@@ -856,7 +985,7 @@ impl WasmtimeInstance {
                     error!(self.log, "[EXC-BUG] Canister {}: {}", cid, err);
                     HypervisorError::WasmEngineError(WasmEngineError::Unexpected(err))
                 }
-                Ok(Some(err)) => err,
+                Ok(Some(err)) => with_backtrace_of(err, &e),
                 Ok(None) => e,
                 Err(_) => e,
             }
@@ -1073,8 +1202,22 @@ impl WasmtimeInstance {
         }
     }
 
+    /// Returns the Wasm profile collected so far and stops profiling. The
+    /// profile is empty if the Wasm profiler is disabled.
+    pub fn take_wasm_profile(&mut self) -> WasmProfile {
+        let instruction_counter = self.instruction_counter();
+        let store_data = self.store.data_mut();
+        match (store_data.profiler.take(), store_data.system_api.as_ref()) {
+            (Some(profiler), Some(system_api)) => profiler.finish(
+                system_api
+                    .message_instructions_executed(instruction_counter)
+                    .get(),
+            ),
+            _ => WasmProfile::default(),
+        }
+    }
+
     /// Returns the heap size.
-    /// Result is guaranteed to fit in a `u32`.
     pub fn heap_size(&mut self, canister_memory_type: CanisterMemoryType) -> NumWasmPages {
         let name = match canister_memory_type {
             CanisterMemoryType::Heap => WASM_HEAP_MEMORY_NAME,
@@ -0,0 +1,86 @@
+//! Builds the [`WasmProfile`] of an execution from the calls to the
+//! `profiler_enter` and `profiler_exit` functions that the instrumentation
+//! injects if the Wasm profiler is enabled.
+
+use ic_interfaces::execution_environment::WasmProfile;
+
+/// Tracks the Wasm call stack and attributes the instructions executed between
+/// two consecutive profiler calls to the function on top of the stack.
+#[derive(Default)]
+pub(crate) struct Profiler {
+    stack: Vec<u32>,
+    /// The number of instructions executed by the message at the time of the
+    /// last profiler call.
+    instructions_executed: u64,
+    profile: WasmProfile,
+}
+
+impl Profiler {
+    /// Called when the function with the given index in the original module is
+    /// entered, after the message has executed `instructions_executed`.
+    pub(crate) fn enter(&mut self, function_index: u32, instructions_executed: u64) {
+        self.record(instructions_executed);
+        self.stack.push(function_index);
+    }
+
+    /// Called when the innermost function returns, after the message has
+    /// executed `instructions_executed`.
+    pub(crate) fn exit(&mut self, instructions_executed: u64) {
+        self.record(instructions_executed);
+        self.stack.pop();
+    }
+
+    /// Returns the profile. Instructions executed since the last profiler call,
+    /// e.g. before a trap, are attributed to the current call stack.
+    pub(crate) fn finish(mut self, instructions_executed: u64) -> WasmProfile {
+        self.record(instructions_executed);
+        self.profile
+    }
+
+    fn record(&mut self, instructions_executed: u64) {
+        let instructions = instructions_executed.saturating_sub(self.instructions_executed);
+        self.instructions_executed = self.instructions_executed.max(instructions_executed);
+        if !self.stack.is_empty() {
+            self.profile.add(&self.stack, instructions);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn attributes_instructions_to_innermost_function() {
+        let mut profiler = Profiler::default();
+        profiler.enter(0, 0);
+        profiler.enter(1, 10);
+        profiler.exit(15);
+        profiler.enter(1, 20);
+        profiler.enter(2, 22);
+        profiler.exit(30);
+        profiler.exit(31);
+        let profile = profiler.finish(40);
+        assert_eq!(
+            profile.stacks,
+            BTreeMap::from([
+                (vec![0], 10 + 5 + 9),
+                (vec![0, 1], 5 + 2 + 1),
+                (vec![0, 1, 2], 8),
+            ])
+        );
+    }
+
+    #[test]
+    fn attributes_instructions_after_trap_to_current_stack() {
+        let mut profiler = Profiler::default();
+        profiler.enter(3, 0);
+        profiler.enter(4, 1);
+        let profile = profiler.finish(11);
+        assert_eq!(
+            profile.stacks,
+            BTreeMap::from([(vec![3], 1), (vec![3, 4], 10)])
+        );
+    }
+}
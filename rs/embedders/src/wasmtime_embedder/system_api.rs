@@ -29,6 +29,40 @@ use ic_system_api::SystemApiImpl;
 /// from wasm sandbox to the replica execution environment.
 const INSTRUCTIONS_PER_BYTE_CONVERSION_FACTOR: u32 = 50;
 
+/// The type of heap addresses and sizes passed to and from the system API:
+/// `u32` for modules with a 32-bit memory and `u64` for modules with a
+/// 64-bit memory.
+pub(crate) trait WasmMemoryPtr:
+    wasmtime::WasmTy
+    + Copy
+    + Into<u64>
+    + TryFrom<usize, Error = std::num::TryFromIntError>
+    + Send
+    + Sync
+    + 'static
+{
+    /// Converts the value to a heap index. Values that don't fit are
+    /// saturated, so that the subsequent bounds check against the heap fails.
+    fn to_usize(self) -> usize {
+        let value: u64 = self.into();
+        usize::try_from(value).unwrap_or(usize::MAX)
+    }
+
+    /// Converts a callback function index or environment to `u32`.
+    fn to_u32(self) -> HypervisorResult<u32> {
+        let value: u64 = self.into();
+        u32::try_from(value).map_err(|_| {
+            HypervisorError::ContractViolation(format!(
+                "Callback argument {} does not fit into 32 bits",
+                value
+            ))
+        })
+    }
+}
+
+impl WasmMemoryPtr for u32 {}
+impl WasmMemoryPtr for u64 {}
+
 fn unexpected_err(s: String) -> HypervisorError {
     HypervisorError::WasmEngineError(WasmEngineError::Unexpected(s))
 }
@@ -287,6 +321,18 @@ fn charge_direct_fee(
     Ok(())
 }
 
+/// Returns the number of instructions executed by the message so far, across
+/// all slices.
+fn message_instructions_executed(caller: &mut Caller<'_, StoreData>) -> HypervisorResult<u64> {
+    let num_instructions_global = get_num_instructions_global(caller)?;
+    let instruction_counter = load_value(&num_instructions_global, caller)?;
+    Ok(caller
+        .data()
+        .system_api()?
+        .message_instructions_executed(instruction_counter)
+        .get())
+}
+
 /// A helper to pass wasmtime counters to the System API
 fn ic0_performance_counter_helper(
     caller: &mut Caller<'_, StoreData>,
@@ -309,7 +355,7 @@ fn ic0_performance_counter_helper(
     }
 }
 
-pub(crate) fn syscalls(
+pub(crate) fn syscalls<I: WasmMemoryPtr>(
     linker: &mut Linker<StoreData>,
     feature_flags: FeatureFlags,
     stable_memory_dirty_page_limit: NumPages,
@@ -361,7 +407,8 @@ pub(crate) fn syscalls(
 
     linker
         .func_wrap("ic0", "msg_caller_copy", {
-            move |mut caller: Caller<'_, StoreData>, dst: u32, offset: u32, size: u32| {
+            move |mut caller: Caller<'_, StoreData>, dst: I, offset: I, size: I| {
+                let (dst, offset, size) = (dst.to_usize(), offset.to_usize(), size.to_usize());
                 charge_for_cpu_and_mem(
                     &mut caller,
                     overhead!(MSG_CALLER_COPY, metering_type),
@@ -371,7 +418,7 @@ pub(crate) fn syscalls(
                     system_api.ic0_msg_caller_copy(dst, offset, size, memory)
                 })?;
                 if feature_flags.write_barrier == FlagStatus::Enabled {
-                    mark_writes_on_bytemap(&mut caller, dst as usize, size as usize)
+                    mark_writes_on_bytemap(&mut caller, dst, size)
                 } else {
                     Ok(())
                 }
@@ -384,7 +431,7 @@ pub(crate) fn syscalls(
             move |mut caller: Caller<'_, StoreData>| {
                 charge_for_cpu(&mut caller, overhead!(MSG_CALLER_SIZE, metering_type))?;
                 with_system_api(&mut caller, |s| s.ic0_msg_caller_size()).and_then(|s| {
-                    i32::try_from(s).map_err(|e| {
+                    I::try_from(s as usize).map_err(|e| {
                         anyhow::Error::msg(format!("ic0::msg_caller_size failed: {}", e))
                     })
                 })
@@ -397,7 +444,7 @@ pub(crate) fn syscalls(
             move |mut caller: Caller<'_, StoreData>| {
                 charge_for_cpu(&mut caller, overhead!(MSG_ARG_DATA_SIZE, metering_type))?;
                 with_system_api(&mut caller, |s| s.ic0_msg_arg_data_size()).and_then(|s| {
-                    i32::try_from(s).map_err(|e| {
+                    I::try_from(s as usize).map_err(|e| {
                         anyhow::Error::msg(format!("ic0::msg_arg_data_size failed: {}", e))
                     })
                 })
@@ -407,7 +454,8 @@ pub(crate) fn syscalls(
 
     linker
         .func_wrap("ic0", "msg_arg_data_copy", {
-            move |mut caller: Caller<'_, StoreData>, dst: u32, offset: u32, size: u32| {
+            move |mut caller: Caller<'_, StoreData>, dst: I, offset: I, size: I| {
+                let (dst, offset, size) = (dst.to_usize(), offset.to_usize(), size.to_usize());
                 charge_for_cpu_and_mem(
                     &mut caller,
                     overhead!(MSG_ARG_DATA_COPY, metering_type),
@@ -417,7 +465,7 @@ pub(crate) fn syscalls(
                     system_api.ic0_msg_arg_data_copy(dst, offset, size, mem)
                 })?;
                 if feature_flags.write_barrier == FlagStatus::Enabled {
-                    mark_writes_on_bytemap(&mut caller, dst as usize, size as usize)
+                    mark_writes_on_bytemap(&mut caller, dst, size)
                 } else {
                     Ok(())
                 }
@@ -430,7 +478,7 @@ pub(crate) fn syscalls(
             move |mut caller: Caller<'_, StoreData>| {
                 charge_for_cpu(&mut caller, overhead!(MSG_METHOD_NAME_SIZE, metering_type))?;
                 with_system_api(&mut caller, |s| s.ic0_msg_method_name_size()).and_then(|s| {
-                    i32::try_from(s).map_err(|e| {
+                    I::try_from(s as usize).map_err(|e| {
                         anyhow::Error::msg(format!("ic0::msg_metohd_name_size failed: {}", e))
                     })
                 })
@@ -440,7 +488,8 @@ pub(crate) fn syscalls(
 
     linker
         .func_wrap("ic0", "msg_method_name_copy", {
-            move |mut caller: Caller<'_, StoreData>, dst: u32, offset: u32, size: u32| {
+            move |mut caller: Caller<'_, StoreData>, dst: I, offset: I, size: I| {
+                let (dst, offset, size) = (dst.to_usize(), offset.to_usize(), size.to_usize());
                 charge_for_cpu_and_mem(
                     &mut caller,
                     overhead!(MSG_METHOD_NAME_COPY, metering_type),
@@ -450,7 +499,7 @@ pub(crate) fn syscalls(
                     system_api.ic0_msg_method_name_copy(dst, offset, size, memory)
                 })?;
                 if feature_flags.write_barrier == FlagStatus::Enabled {
-                    mark_writes_on_bytemap(&mut caller, dst as usize, size as usize)
+                    mark_writes_on_bytemap(&mut caller, dst, size)
                 } else {
                     Ok(())
                 }
@@ -469,11 +518,12 @@ pub(crate) fn syscalls(
 
     linker
         .func_wrap("ic0", "msg_reply_data_append", {
-            move |mut caller: Caller<'_, StoreData>, src: u32, size: u32| {
+            move |mut caller: Caller<'_, StoreData>, src: I, size: I| {
+                let (src, size) = (src.to_usize(), size.to_usize());
                 charge_for_cpu_and_mem(
                     &mut caller,
                     overhead!(MSG_REPLY_DATA_APPEND, metering_type),
-                    (INSTRUCTIONS_PER_BYTE_CONVERSION_FACTOR * size) as u64,
+                    (INSTRUCTIONS_PER_BYTE_CONVERSION_FACTOR as u64).saturating_mul(size as u64),
                 )?;
                 with_memory_and_system_api(&mut caller, |system_api, memory| {
                     system_api.ic0_msg_reply_data_append(src, size, memory)
@@ -502,11 +552,12 @@ pub(crate) fn syscalls(
 
     linker
         .func_wrap("ic0", "msg_reject", {
-            move |mut caller: Caller<'_, StoreData>, src: u32, size: u32| {
+            move |mut caller: Caller<'_, StoreData>, src: I, size: I| {
+                let (src, size) = (src.to_usize(), size.to_usize());
                 charge_for_cpu_and_mem(
                     &mut caller,
                     overhead!(MSG_REJECT, metering_type),
-                    (INSTRUCTIONS_PER_BYTE_CONVERSION_FACTOR * size) as u64,
+                    (INSTRUCTIONS_PER_BYTE_CONVERSION_FACTOR as u64).saturating_mul(size as u64),
                 )?;
                 with_memory_and_system_api(&mut caller, |system_api, memory| {
                     system_api.ic0_msg_reject(src, size, memory)
@@ -520,7 +571,7 @@ pub(crate) fn syscalls(
             move |mut caller: Caller<'_, StoreData>| {
                 charge_for_cpu(&mut caller, overhead!(MSG_REJECT_MSG_SIZE, metering_type))?;
                 with_system_api(&mut caller, |s| s.ic0_msg_reject_msg_size()).and_then(|s| {
-                    i32::try_from(s).map_err(|e| {
+                    I::try_from(s as usize).map_err(|e| {
                         anyhow::Error::msg(format!("ic0_msg_reject_msg_size failed: {}", e))
                     })
                 })
@@ -530,7 +581,8 @@ pub(crate) fn syscalls(
 
     linker
         .func_wrap("ic0", "msg_reject_msg_copy", {
-            move |mut caller: Caller<'_, StoreData>, dst: u32, offset: u32, size: u32| {
+            move |mut caller: Caller<'_, StoreData>, dst: I, offset: I, size: I| {
+                let (dst, offset, size) = (dst.to_usize(), offset.to_usize(), size.to_usize());
                 charge_for_cpu_and_mem(
                     &mut caller,
                     overhead!(MSG_REJECT_MSG_COPY, metering_type),
@@ -540,7 +592,7 @@ pub(crate) fn syscalls(
                     system_api.ic0_msg_reject_msg_copy(dst, offset, size, memory)
                 })?;
                 if feature_flags.write_barrier == FlagStatus::Enabled {
-                    mark_writes_on_bytemap(&mut caller, dst as usize, size as usize)
+                    mark_writes_on_bytemap(&mut caller, dst, size)
                 } else {
                     Ok(())
                 }
@@ -553,7 +605,7 @@ pub(crate) fn syscalls(
             move |mut caller: Caller<'_, StoreData>| {
                 charge_for_cpu(&mut caller, overhead!(CANISTER_SELF_SIZE, metering_type))?;
                 with_system_api(&mut caller, |s| s.ic0_canister_self_size()).and_then(|s| {
-                    i32::try_from(s).map_err(|e| {
+                    I::try_from(s).map_err(|e| {
                         anyhow::Error::msg(format!("ic0_canister_self_size failed: {}", e))
                     })
                 })
@@ -563,7 +615,8 @@ pub(crate) fn syscalls(
 
     linker
         .func_wrap("ic0", "canister_self_copy", {
-            move |mut caller: Caller<'_, StoreData>, dst: u32, offset: u32, size: u32| {
+            move |mut caller: Caller<'_, StoreData>, dst: I, offset: I, size: I| {
+                let (dst, offset, size) = (dst.to_usize(), offset.to_usize(), size.to_usize());
                 charge_for_cpu_and_mem(
                     &mut caller,
                     overhead!(CANISTER_SELF_COPY, metering_type),
@@ -573,7 +626,175 @@ pub(crate) fn syscalls(
                     system_api.ic0_canister_self_copy(dst, offset, size, memory)
                 })?;
                 if feature_flags.write_barrier == FlagStatus::Enabled {
-                    mark_writes_on_bytemap(&mut caller, dst as usize, size as usize)
+                    mark_writes_on_bytemap(&mut caller, dst, size)
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .unwrap();
+
+    linker
+        .func_wrap("ic0", "subnet_self_size", {
+            move |mut caller: Caller<'_, StoreData>| {
+                charge_for_cpu(&mut caller, overhead!(SUBNET_SELF_SIZE, metering_type))?;
+                with_system_api(&mut caller, |s| s.ic0_subnet_self_size()).and_then(|s| {
+                    I::try_from(s).map_err(|e| {
+                        anyhow::Error::msg(format!("ic0_subnet_self_size failed: {}", e))
+                    })
+                })
+            }
+        })
+        .unwrap();
+
+    linker
+        .func_wrap("ic0", "subnet_self_copy", {
+            move |mut caller: Caller<'_, StoreData>, dst: I, offset: I, size: I| {
+                let (dst, offset, size) = (dst.to_usize(), offset.to_usize(), size.to_usize());
+                charge_for_cpu_and_mem(
+                    &mut caller,
+                    overhead!(SUBNET_SELF_COPY, metering_type),
+                    size as u64,
+                )?;
+                with_memory_and_system_api(&mut caller, |system_api, memory| {
+                    system_api.ic0_subnet_self_copy(dst, offset, size, memory)
+                })?;
+                if feature_flags.write_barrier == FlagStatus::Enabled {
+                    mark_writes_on_bytemap(&mut caller, dst, size)
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .unwrap();
+
+    linker
+        .func_wrap("ic0", "in_replicated_execution", {
+            move |mut caller: Caller<'_, StoreData>| {
+                charge_for_cpu(
+                    &mut caller,
+                    overhead!(IN_REPLICATED_EXECUTION, metering_type),
+                )?;
+                with_system_api(&mut caller, |s| s.ic0_in_replicated_execution())
+            }
+        })
+        .unwrap();
+
+    linker
+        .func_wrap("ic0", "env_var_count", {
+            move |mut caller: Caller<'_, StoreData>| {
+                charge_for_cpu(&mut caller, overhead!(ENV_VAR_COUNT, metering_type))?;
+                with_system_api(&mut caller, |s| s.ic0_env_var_count()).and_then(|s| {
+                    I::try_from(s)
+                        .map_err(|e| anyhow::Error::msg(format!("ic0_env_var_count failed: {}", e)))
+                })
+            }
+        })
+        .unwrap();
+
+    linker
+        .func_wrap("ic0", "env_var_name_size", {
+            move |mut caller: Caller<'_, StoreData>, index: I| {
+                charge_for_cpu(&mut caller, overhead!(ENV_VAR_NAME_SIZE, metering_type))?;
+                with_system_api(&mut caller, |s| s.ic0_env_var_name_size(index.to_usize()))
+                    .and_then(|s| {
+                        I::try_from(s).map_err(|e| {
+                            anyhow::Error::msg(format!("ic0_env_var_name_size failed: {}", e))
+                        })
+                    })
+            }
+        })
+        .unwrap();
+
+    linker
+        .func_wrap("ic0", "env_var_name_copy", {
+            move |mut caller: Caller<'_, StoreData>, index: I, dst: I, offset: I, size: I| {
+                let (index, dst, offset, size) = (
+                    index.to_usize(),
+                    dst.to_usize(),
+                    offset.to_usize(),
+                    size.to_usize(),
+                );
+                charge_for_cpu_and_mem(
+                    &mut caller,
+                    overhead!(ENV_VAR_NAME_COPY, metering_type),
+                    size as u64,
+                )?;
+                with_memory_and_system_api(&mut caller, |system_api, memory| {
+                    system_api.ic0_env_var_name_copy(index, dst, offset, size, memory)
+                })?;
+                if feature_flags.write_barrier == FlagStatus::Enabled {
+                    mark_writes_on_bytemap(&mut caller, dst, size)
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .unwrap();
+
+    linker
+        .func_wrap("ic0", "env_var_name_exists", {
+            move |mut caller: Caller<'_, StoreData>, name_src: I, name_size: I| {
+                let (name_src, name_size) = (name_src.to_usize(), name_size.to_usize());
+                charge_for_cpu_and_mem(
+                    &mut caller,
+                    overhead!(ENV_VAR_NAME_EXISTS, metering_type),
+                    name_size as u64,
+                )?;
+                with_memory_and_system_api(&mut caller, |system_api, memory| {
+                    system_api.ic0_env_var_name_exists(name_src, name_size, memory)
+                })
+            }
+        })
+        .unwrap();
+
+    linker
+        .func_wrap("ic0", "env_var_value_size", {
+            move |mut caller: Caller<'_, StoreData>, name_src: I, name_size: I| {
+                let (name_src, name_size) = (name_src.to_usize(), name_size.to_usize());
+                charge_for_cpu_and_mem(
+                    &mut caller,
+                    overhead!(ENV_VAR_VALUE_SIZE, metering_type),
+                    name_size as u64,
+                )?;
+                with_memory_and_system_api(&mut caller, |system_api, memory| {
+                    system_api.ic0_env_var_value_size(name_src, name_size, memory)
+                })
+                .and_then(|s| {
+                    I::try_from(s).map_err(|e| {
+                        anyhow::Error::msg(format!("ic0_env_var_value_size failed: {}", e))
+                    })
+                })
+            }
+        })
+        .unwrap();
+
+    linker
+        .func_wrap("ic0", "env_var_value_copy", {
+            move |mut caller: Caller<'_, StoreData>,
+                  name_src: I,
+                  name_size: I,
+                  dst: I,
+                  offset: I,
+                  size: I| {
+                let (name_src, name_size, dst, offset, size) = (
+                    name_src.to_usize(),
+                    name_size.to_usize(),
+                    dst.to_usize(),
+                    offset.to_usize(),
+                    size.to_usize(),
+                );
+                charge_for_cpu_and_mem(
+                    &mut caller,
+                    overhead!(ENV_VAR_VALUE_COPY, metering_type),
+                    name_size as u64 + size as u64,
+                )?;
+                with_memory_and_system_api(&mut caller, |system_api, memory| {
+                    system_api
+                        .ic0_env_var_value_copy(name_src, name_size, dst, offset, size, memory)
+                })?;
+                if feature_flags.write_barrier == FlagStatus::Enabled {
+                    mark_writes_on_bytemap(&mut caller, dst, size)
                 } else {
                     Ok(())
                 }
@@ -583,12 +804,19 @@ pub(crate) fn syscalls(
 
     linker
         .func_wrap("ic0", "debug_print", {
-            move |mut caller: Caller<'_, StoreData>, offset: u32, length: u32| {
+            move |mut caller: Caller<'_, StoreData>, offset: I, length: I| {
+                let (offset, length) = (offset.to_usize(), length.to_usize());
                 charge_for_cpu_and_mem(
                     &mut caller,
                     overhead!(DEBUG_PRINT, metering_type),
                     length as u64,
                 )?;
+                if feature_flags.canister_logging == FlagStatus::Enabled {
+                    with_memory_and_system_api(&mut caller, |system_api, memory| {
+                        system_api.save_log_message(false, offset, length, memory);
+                        Ok(())
+                    })?;
+                }
                 match (
                     caller.data().system_api.as_ref().unwrap().subnet_type(),
                     feature_flags.rate_limiting_of_debug_prints,
@@ -610,9 +838,13 @@ pub(crate) fn syscalls(
 
     linker
         .func_wrap("ic0", "trap", {
-            move |mut caller: Caller<'_, StoreData>, offset: u32, length: u32| -> Result<(), _> {
+            move |mut caller: Caller<'_, StoreData>, offset: I, length: I| -> Result<(), _> {
+                let (offset, length) = (offset.to_usize(), length.to_usize());
                 charge_for_cpu_and_mem(&mut caller, overhead!(TRAP, metering_type), length as u64)?;
                 with_memory_and_system_api(&mut caller, |system_api, memory| {
+                    if feature_flags.canister_logging == FlagStatus::Enabled {
+                        system_api.save_log_message(true, offset, length, memory);
+                    }
                     system_api.ic0_trap(offset, length, memory)
                 })
             }
@@ -622,18 +854,24 @@ pub(crate) fn syscalls(
     linker
         .func_wrap("ic0", "call_new", {
             move |mut caller: Caller<'_, StoreData>,
-                  callee_src: u32,
-                  callee_size: u32,
-                  name_src: u32,
-                  name_len: u32,
-                  reply_fun: u32,
-                  reply_env: u32,
-                  reject_fun: u32,
-                  reject_env: u32| {
+                  callee_src: I,
+                  callee_size: I,
+                  name_src: I,
+                  name_len: I,
+                  reply_fun: I,
+                  reply_env: I,
+                  reject_fun: I,
+                  reject_env: I| {
+                let (callee_src, callee_size, name_src, name_len) = (
+                    callee_src.to_usize(),
+                    callee_size.to_usize(),
+                    name_src.to_usize(),
+                    name_len.to_usize(),
+                );
                 charge_for_cpu_and_mem(
                     &mut caller,
                     overhead!(CALL_NEW, metering_type),
-                    (callee_size as u64) + (name_len as u64),
+                    (callee_size as u64).saturating_add(name_len as u64),
                 )?;
                 with_memory_and_system_api(&mut caller, |system_api, memory| {
                     system_api.ic0_call_new(
@@ -641,10 +879,10 @@ pub(crate) fn syscalls(
                         callee_size,
                         name_src,
                         name_len,
-                        reply_fun,
-                        reply_env,
-                        reject_fun,
-                        reject_env,
+                        reply_fun.to_u32()?,
+                        reply_env.to_u32()?,
+                        reject_fun.to_u32()?,
+                        reject_env.to_u32()?,
                         memory,
                     )
                 })
@@ -654,11 +892,12 @@ pub(crate) fn syscalls(
 
     linker
         .func_wrap("ic0", "call_data_append", {
-            move |mut caller: Caller<'_, StoreData>, src: u32, size: u32| {
+            move |mut caller: Caller<'_, StoreData>, src: I, size: I| {
+                let (src, size) = (src.to_usize(), size.to_usize());
                 charge_for_cpu_and_mem(
                     &mut caller,
                     overhead!(CALL_DATA_APPEND, metering_type),
-                    (INSTRUCTIONS_PER_BYTE_CONVERSION_FACTOR * size) as u64,
+                    (INSTRUCTIONS_PER_BYTE_CONVERSION_FACTOR as u64).saturating_mul(size as u64),
                 )?;
                 with_memory_and_system_api(&mut caller, |system_api, memory| {
                     system_api.ic0_call_data_append(src, size, memory)
@@ -669,9 +908,25 @@ pub(crate) fn syscalls(
 
     linker
         .func_wrap("ic0", "call_on_cleanup", {
-            move |mut caller: Caller<'_, StoreData>, fun: u32, env: u32| {
+            move |mut caller: Caller<'_, StoreData>, fun: I, env: I| {
                 charge_for_cpu(&mut caller, overhead!(CALL_ON_CLEANUP, metering_type))?;
-                with_system_api(&mut caller, |s| s.ic0_call_on_cleanup(fun, env))
+                with_system_api(&mut caller, |s| {
+                    s.ic0_call_on_cleanup(fun.to_u32()?, env.to_u32()?)
+                })
+            }
+        })
+        .unwrap();
+
+    linker
+        .func_wrap("ic0", "call_with_best_effort_response", {
+            move |mut caller: Caller<'_, StoreData>, timeout_seconds: u32| {
+                charge_for_cpu(
+                    &mut caller,
+                    overhead!(CALL_WITH_BEST_EFFORT_RESPONSE, metering_type),
+                )?;
+                with_system_api(&mut caller, |s| {
+                    s.ic0_call_with_best_effort_response(timeout_seconds)
+                })
             }
         })
         .unwrap();
@@ -856,6 +1111,18 @@ pub(crate) fn syscalls(
         })
         .unwrap();
 
+    linker
+        .func_wrap("ic0", "timer_set", {
+            move |mut caller: Caller<'_, StoreData>, timer_id: u64, time: u64| {
+                charge_for_cpu(&mut caller, overhead!(TIMER_SET, metering_type))?;
+                with_system_api(&mut caller, |s| {
+                    s.ic0_timer_set(timer_id, Time::from_nanos_since_unix_epoch(time))
+                })
+                .map(|s| s.as_nanos_since_unix_epoch())
+            }
+        })
+        .unwrap();
+
     linker
         .func_wrap("ic0", "performance_counter", {
             move |mut caller: Caller<'_, StoreData>, counter_type: u32| {
@@ -893,7 +1160,8 @@ pub(crate) fn syscalls(
 
     linker
         .func_wrap("ic0", "canister_cycle_balance128", {
-            move |mut caller: Caller<'_, StoreData>, dst: u32| {
+            move |mut caller: Caller<'_, StoreData>, dst: I| {
+                let dst = dst.to_usize();
                 charge_for_cpu(
                     &mut caller,
                     overhead!(CANISTER_CYCLE_BALANCE128, metering_type),
@@ -902,7 +1170,7 @@ pub(crate) fn syscalls(
                     system_api.ic0_canister_cycle_balance128(dst, memory)
                 })?;
                 if feature_flags.write_barrier == FlagStatus::Enabled {
-                    mark_writes_on_bytemap(&mut caller, dst as usize, 16)
+                    mark_writes_on_bytemap(&mut caller, dst, 16)
                 } else {
                     Ok(())
                 }
@@ -925,7 +1193,8 @@ pub(crate) fn syscalls(
 
     linker
         .func_wrap("ic0", "msg_cycles_available128", {
-            move |mut caller: Caller<'_, StoreData>, dst: u32| {
+            move |mut caller: Caller<'_, StoreData>, dst: I| {
+                let dst = dst.to_usize();
                 charge_for_cpu(
                     &mut caller,
                     overhead!(MSG_CYCLES_AVAILABLE128, metering_type),
@@ -934,7 +1203,7 @@ pub(crate) fn syscalls(
                     system_api.ic0_msg_cycles_available128(dst, memory)
                 })?;
                 if feature_flags.write_barrier == FlagStatus::Enabled {
-                    mark_writes_on_bytemap(&mut caller, dst as usize, 16)
+                    mark_writes_on_bytemap(&mut caller, dst, 16)
                 } else {
                     Ok(())
                 }
@@ -957,7 +1226,8 @@ pub(crate) fn syscalls(
 
     linker
         .func_wrap("ic0", "msg_cycles_refunded128", {
-            move |mut caller: Caller<'_, StoreData>, dst: u32| {
+            move |mut caller: Caller<'_, StoreData>, dst: I| {
+                let dst = dst.to_usize();
                 charge_for_cpu(
                     &mut caller,
                     overhead!(MSG_CYCLES_REFUNDED128, metering_type),
@@ -966,7 +1236,7 @@ pub(crate) fn syscalls(
                     system_api.ic0_msg_cycles_refunded128(dst, memory)
                 })?;
                 if feature_flags.write_barrier == FlagStatus::Enabled {
-                    mark_writes_on_bytemap(&mut caller, dst as usize, 16)
+                    mark_writes_on_bytemap(&mut caller, dst, 16)
                 } else {
                     Ok(())
                 }
@@ -985,7 +1255,8 @@ pub(crate) fn syscalls(
 
     linker
         .func_wrap("ic0", "msg_cycles_accept128", {
-            move |mut caller: Caller<'_, StoreData>, amount_high: u64, amount_low: u64, dst: u32| {
+            move |mut caller: Caller<'_, StoreData>, amount_high: u64, amount_low: u64, dst: I| {
+                let dst = dst.to_usize();
                 charge_for_cpu(&mut caller, overhead!(MSG_CYCLES_ACCEPT128, metering_type))?;
                 with_memory_and_system_api(&mut caller, |system_api, memory| {
                     system_api.ic0_msg_cycles_accept128(
@@ -995,7 +1266,7 @@ pub(crate) fn syscalls(
                     )
                 })?;
                 if feature_flags.write_barrier == FlagStatus::Enabled {
-                    mark_writes_on_bytemap(&mut caller, dst as usize, 16)
+                    mark_writes_on_bytemap(&mut caller, dst, 16)
                 } else {
                     Ok(())
                 }
@@ -1024,12 +1295,15 @@ pub(crate) fn syscalls(
             move |mut caller: Caller<'_, StoreData>,
                   native_memory_grow_res: i32,
                   additional_elements: u32,
-                  element_size: u32| {
+                  memory: i32| {
                 with_system_api(&mut caller, |s| {
                     s.update_available_memory(
                         native_memory_grow_res as i64,
                         additional_elements as u64,
-                        element_size as u64,
+                        memory.try_into().map_err(|()| HypervisorError::Trapped {
+                            trap_code: TrapCode::Other,
+                            backtrace: None,
+                        })?,
                     )
                 })
                 .map(|()| native_memory_grow_res)
@@ -1056,7 +1330,10 @@ pub(crate) fn syscalls(
                         additional_pages as u64,
                         stable_memory_api
                             .try_into()
-                            .map_err(|()| HypervisorError::Trapped(TrapCode::Other))?,
+                            .map_err(|()| HypervisorError::Trapped {
+                                trap_code: TrapCode::Other,
+                                backtrace: None,
+                            })?,
                     )? {
                         StableGrowOutcome::Success => Ok(current_size),
                         StableGrowOutcome::Failure => Ok(-1),
@@ -1077,7 +1354,8 @@ pub(crate) fn syscalls(
 
     linker
         .func_wrap("ic0", "certified_data_set", {
-            move |mut caller: Caller<'_, StoreData>, src: u32, size: u32| {
+            move |mut caller: Caller<'_, StoreData>, src: I, size: I| {
+                let (src, size) = (src.to_usize(), size.to_usize());
                 charge_for_cpu_and_mem(
                     &mut caller,
                     overhead!(CERTIFIED_DATA_SET, metering_type),
@@ -1106,14 +1384,19 @@ pub(crate) fn syscalls(
         .func_wrap("ic0", "data_certificate_size", {
             move |mut caller: Caller<'_, StoreData>| {
                 charge_for_cpu(&mut caller, overhead!(DATA_CERTIFICATE_SIZE, metering_type))?;
-                with_system_api(&mut caller, |s| s.ic0_data_certificate_size())
+                with_system_api(&mut caller, |s| s.ic0_data_certificate_size()).and_then(|s| {
+                    I::try_from(s as usize).map_err(|e| {
+                        anyhow::Error::msg(format!("ic0_data_certificate_size failed: {}", e))
+                    })
+                })
             }
         })
         .unwrap();
 
     linker
         .func_wrap("ic0", "is_controller", {
-            move |mut caller: Caller<'_, StoreData>, src: u32, size: u32| {
+            move |mut caller: Caller<'_, StoreData>, src: I, size: I| {
+                let (src, size) = (src.to_usize(), size.to_usize());
                 charge_for_cpu_and_mem(
                     &mut caller,
                     overhead!(IS_CONTROLLER, metering_type),
@@ -1128,7 +1411,8 @@ pub(crate) fn syscalls(
 
     linker
         .func_wrap("ic0", "data_certificate_copy", {
-            move |mut caller: Caller<'_, StoreData>, dst: u32, offset: u32, size: u32| {
+            move |mut caller: Caller<'_, StoreData>, dst: I, offset: I, size: I| {
+                let (dst, offset, size) = (dst.to_usize(), offset.to_usize(), size.to_usize());
                 charge_for_cpu_and_mem(
                     &mut caller,
                     overhead!(DATA_CERTIFICATE_COPY, metering_type),
@@ -1138,7 +1422,7 @@ pub(crate) fn syscalls(
                     system_api.ic0_data_certificate_copy(dst, offset, size, memory)
                 })?;
                 if feature_flags.write_barrier == FlagStatus::Enabled {
-                    mark_writes_on_bytemap(&mut caller, dst as usize, size as usize)
+                    mark_writes_on_bytemap(&mut caller, dst, size)
                 } else {
                     Ok(())
                 }
@@ -1159,7 +1443,8 @@ pub(crate) fn syscalls(
 
     linker
         .func_wrap("ic0", "cycles_burn128", {
-            move |mut caller: Caller<'_, StoreData>, amount_high: u64, amount_low: u64, dst: u32| {
+            move |mut caller: Caller<'_, StoreData>, amount_high: u64, amount_low: u64, dst: I| {
+                let dst = dst.to_usize();
                 with_memory_and_system_api(&mut caller, |s, memory| {
                     s.ic0_cycles_burn128(Cycles::from_parts(amount_high, amount_low), dst, memory)
                 })
@@ -1168,18 +1453,134 @@ pub(crate) fn syscalls(
         })
         .unwrap();
 
+    linker
+        .func_wrap("ic0", "cost_call", {
+            move |mut caller: Caller<'_, StoreData>,
+                  method_name_size: u64,
+                  payload_size: u64,
+                  dst: I| {
+                let dst = dst.to_usize();
+                charge_for_cpu(&mut caller, overhead!(COST_CALL, metering_type))?;
+                with_memory_and_system_api(&mut caller, |system_api, memory| {
+                    system_api.ic0_cost_call(method_name_size, payload_size, dst, memory)
+                })?;
+                if feature_flags.write_barrier == FlagStatus::Enabled {
+                    mark_writes_on_bytemap(&mut caller, dst, 16)
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .unwrap();
+
+    linker
+        .func_wrap("ic0", "cost_create_canister", {
+            move |mut caller: Caller<'_, StoreData>, dst: I| {
+                let dst = dst.to_usize();
+                charge_for_cpu(&mut caller, overhead!(COST_CREATE_CANISTER, metering_type))?;
+                with_memory_and_system_api(&mut caller, |system_api, memory| {
+                    system_api.ic0_cost_create_canister(dst, memory)
+                })?;
+                if feature_flags.write_barrier == FlagStatus::Enabled {
+                    mark_writes_on_bytemap(&mut caller, dst, 16)
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .unwrap();
+
+    linker
+        .func_wrap("ic0", "cost_http_request", {
+            move |mut caller: Caller<'_, StoreData>,
+                  request_size: u64,
+                  max_res_bytes: u64,
+                  dst: I| {
+                let dst = dst.to_usize();
+                charge_for_cpu(&mut caller, overhead!(COST_HTTP_REQUEST, metering_type))?;
+                with_memory_and_system_api(&mut caller, |system_api, memory| {
+                    system_api.ic0_cost_http_request(request_size, max_res_bytes, dst, memory)
+                })?;
+                if feature_flags.write_barrier == FlagStatus::Enabled {
+                    mark_writes_on_bytemap(&mut caller, dst, 16)
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .unwrap();
+
+    linker
+        .func_wrap("ic0", "cost_sign_with_ecdsa", {
+            move |mut caller: Caller<'_, StoreData>, src: I, size: I, curve: u32, dst: I| {
+                let (src, size, dst) = (src.to_usize(), size.to_usize(), dst.to_usize());
+                charge_for_cpu_and_mem(
+                    &mut caller,
+                    overhead!(COST_SIGN_WITH_ECDSA, metering_type),
+                    size as u64,
+                )?;
+                let result = with_memory_and_system_api(&mut caller, |system_api, memory| {
+                    system_api.ic0_cost_sign_with_ecdsa(src, size, curve, dst, memory)
+                })?;
+                if result == 0 && feature_flags.write_barrier == FlagStatus::Enabled {
+                    mark_writes_on_bytemap(&mut caller, dst, 16)?;
+                }
+                Ok(result)
+            }
+        })
+        .unwrap();
+
+    if feature_flags.wasm_profiler == FlagStatus::Enabled {
+        linker
+            .func_wrap("__", "profiler_enter", {
+                move |mut caller: Caller<'_, StoreData>, function_index: i32| -> Result<(), _> {
+                    with_error_handling(&mut caller, |c| {
+                        let instructions_executed = message_instructions_executed(c)?;
+                        if let Some(profiler) = c.data_mut().profiler.as_mut() {
+                            profiler.enter(function_index as u32, instructions_executed);
+                        }
+                        Ok(())
+                    })
+                }
+            })
+            .unwrap();
+
+        linker
+            .func_wrap("__", "profiler_exit", {
+                move |mut caller: Caller<'_, StoreData>| -> Result<(), _> {
+                    with_error_handling(&mut caller, |c| {
+                        let instructions_executed = message_instructions_executed(c)?;
+                        if let Some(profiler) = c.data_mut().profiler.as_mut() {
+                            profiler.exit(instructions_executed);
+                        }
+                        Ok(())
+                    })
+                }
+            })
+            .unwrap();
+    }
+
     linker
         .func_wrap("__", "internal_trap", {
             move |mut caller: Caller<'_, StoreData>, err_code: i32| -> Result<(), _> {
                 let err = match InternalErrorCode::from_i32(err_code) {
                     InternalErrorCode::HeapOutOfBounds => {
-                        HypervisorError::Trapped(TrapCode::HeapOutOfBounds)
+                        HypervisorError::Trapped {
+                            trap_code: TrapCode::HeapOutOfBounds,
+                            backtrace: None,
+                        }
                     }
                     InternalErrorCode::StableMemoryOutOfBounds => {
-                        HypervisorError::Trapped(TrapCode::StableMemoryOutOfBounds)
+                        HypervisorError::Trapped {
+                            trap_code: TrapCode::StableMemoryOutOfBounds,
+                            backtrace: None,
+                        }
                     }
                     InternalErrorCode::StableMemoryTooBigFor32Bit => {
-                        HypervisorError::Trapped(TrapCode::StableMemoryTooBigFor32Bit)
+                        HypervisorError::Trapped {
+                            trap_code: TrapCode::StableMemoryTooBigFor32Bit,
+                            backtrace: None,
+                        }
                     }
                     InternalErrorCode::MemoryWriteLimitExceeded => {
                         HypervisorError::MemoryAccessLimitExceeded(
@@ -27,6 +27,7 @@ pub mod overhead {
         pub const CALL_NEW: NumInstructions = NumInstructions::new(0);
         pub const CALL_ON_CLEANUP: NumInstructions = NumInstructions::new(0);
         pub const CALL_PERFORM: NumInstructions = NumInstructions::new(0);
+        pub const CALL_WITH_BEST_EFFORT_RESPONSE: NumInstructions = NumInstructions::new(0);
         pub const CANISTER_CYCLE_BALANCE: NumInstructions = NumInstructions::new(0);
         pub const CANISTER_CYCLE_BALANCE128: NumInstructions = NumInstructions::new(0);
         pub const CANISTER_SELF_COPY: NumInstructions = NumInstructions::new(0);
@@ -34,12 +35,23 @@ pub mod overhead {
         pub const CANISTER_STATUS: NumInstructions = NumInstructions::new(0);
         pub const CANISTER_VERSION: NumInstructions = NumInstructions::new(0);
         pub const CERTIFIED_DATA_SET: NumInstructions = NumInstructions::new(0);
+        pub const COST_CALL: NumInstructions = NumInstructions::new(0);
+        pub const COST_CREATE_CANISTER: NumInstructions = NumInstructions::new(0);
+        pub const COST_HTTP_REQUEST: NumInstructions = NumInstructions::new(0);
+        pub const COST_SIGN_WITH_ECDSA: NumInstructions = NumInstructions::new(0);
         pub const CYCLES_BURN: NumInstructions = NumInstructions::new(100);
         pub const DATA_CERTIFICATE_COPY: NumInstructions = NumInstructions::new(0);
         pub const DATA_CERTIFICATE_PRESENT: NumInstructions = NumInstructions::new(0);
         pub const DATA_CERTIFICATE_SIZE: NumInstructions = NumInstructions::new(0);
         pub const DEBUG_PRINT: NumInstructions = NumInstructions::new(100);
+        pub const ENV_VAR_COUNT: NumInstructions = NumInstructions::new(0);
+        pub const ENV_VAR_NAME_COPY: NumInstructions = NumInstructions::new(0);
+        pub const ENV_VAR_NAME_EXISTS: NumInstructions = NumInstructions::new(0);
+        pub const ENV_VAR_NAME_SIZE: NumInstructions = NumInstructions::new(0);
+        pub const ENV_VAR_VALUE_COPY: NumInstructions = NumInstructions::new(0);
+        pub const ENV_VAR_VALUE_SIZE: NumInstructions = NumInstructions::new(0);
         pub const GLOBAL_TIMER_SET: NumInstructions = NumInstructions::new(0);
+        pub const IN_REPLICATED_EXECUTION: NumInstructions = NumInstructions::new(0);
         pub const IS_CONTROLLER: NumInstructions = NumInstructions::new(1_000);
         pub const MSG_ARG_DATA_COPY: NumInstructions = NumInstructions::new(20);
         pub const MSG_ARG_DATA_SIZE: NumInstructions = NumInstructions::new(0);
@@ -68,7 +80,10 @@ pub mod overhead {
         pub const STABLE64_READ: NumInstructions = NumInstructions::new(20);
         pub const STABLE64_SIZE: NumInstructions = NumInstructions::new(0);
         pub const STABLE64_WRITE: NumInstructions = NumInstructions::new(20);
+        pub const SUBNET_SELF_COPY: NumInstructions = NumInstructions::new(0);
+        pub const SUBNET_SELF_SIZE: NumInstructions = NumInstructions::new(0);
         pub const TIME: NumInstructions = NumInstructions::new(0);
+        pub const TIMER_SET: NumInstructions = NumInstructions::new(0);
         pub const TRAP: NumInstructions = NumInstructions::new(20);
     }
 
@@ -81,6 +96,7 @@ pub mod overhead {
         pub const CALL_NEW: NumInstructions = NumInstructions::new(1_500);
         pub const CALL_ON_CLEANUP: NumInstructions = NumInstructions::new(500);
         pub const CALL_PERFORM: NumInstructions = NumInstructions::new(5_000);
+        pub const CALL_WITH_BEST_EFFORT_RESPONSE: NumInstructions = NumInstructions::new(500);
         pub const CANISTER_CYCLE_BALANCE: NumInstructions = NumInstructions::new(500);
         pub const CANISTER_CYCLE_BALANCE128: NumInstructions = NumInstructions::new(500);
         pub const CANISTER_SELF_COPY: NumInstructions = NumInstructions::new(500);
@@ -90,11 +106,22 @@ pub mod overhead {
         pub const CERTIFIED_DATA_SET: NumInstructions = NumInstructions::new(500);
         pub const CONTROLLER_COPY: NumInstructions = NumInstructions::new(500);
         pub const CONTROLLER_SIZE: NumInstructions = NumInstructions::new(500);
+        pub const COST_CALL: NumInstructions = NumInstructions::new(500);
+        pub const COST_CREATE_CANISTER: NumInstructions = NumInstructions::new(500);
+        pub const COST_HTTP_REQUEST: NumInstructions = NumInstructions::new(500);
+        pub const COST_SIGN_WITH_ECDSA: NumInstructions = NumInstructions::new(500);
         pub const DATA_CERTIFICATE_COPY: NumInstructions = NumInstructions::new(500);
         pub const DATA_CERTIFICATE_PRESENT: NumInstructions = NumInstructions::new(500);
         pub const DATA_CERTIFICATE_SIZE: NumInstructions = NumInstructions::new(500);
         pub const DEBUG_PRINT: NumInstructions = NumInstructions::new(100);
+        pub const ENV_VAR_COUNT: NumInstructions = NumInstructions::new(500);
+        pub const ENV_VAR_NAME_COPY: NumInstructions = NumInstructions::new(500);
+        pub const ENV_VAR_NAME_EXISTS: NumInstructions = NumInstructions::new(500);
+        pub const ENV_VAR_NAME_SIZE: NumInstructions = NumInstructions::new(500);
+        pub const ENV_VAR_VALUE_COPY: NumInstructions = NumInstructions::new(500);
+        pub const ENV_VAR_VALUE_SIZE: NumInstructions = NumInstructions::new(500);
         pub const GLOBAL_TIMER_SET: NumInstructions = NumInstructions::new(500);
+        pub const IN_REPLICATED_EXECUTION: NumInstructions = NumInstructions::new(500);
         pub const IS_CONTROLLER: NumInstructions = NumInstructions::new(1_000);
         pub const MSG_ARG_DATA_COPY: NumInstructions = NumInstructions::new(500);
         pub const MSG_ARG_DATA_SIZE: NumInstructions = NumInstructions::new(500);
@@ -123,7 +150,10 @@ pub mod overhead {
         pub const STABLE64_READ: NumInstructions = NumInstructions::new(20);
         pub const STABLE64_SIZE: NumInstructions = NumInstructions::new(20);
         pub const STABLE64_WRITE: NumInstructions = NumInstructions::new(20);
+        pub const SUBNET_SELF_COPY: NumInstructions = NumInstructions::new(500);
+        pub const SUBNET_SELF_SIZE: NumInstructions = NumInstructions::new(500);
         pub const TIME: NumInstructions = NumInstructions::new(500);
+        pub const TIMER_SET: NumInstructions = NumInstructions::new(500);
         pub const TRAP: NumInstructions = NumInstructions::new(500);
     }
 }
@@ -1,7 +1,7 @@
 use std::rc::Rc;
 use std::sync::Arc;
 
-use super::{system_api, StoreData, INSTRUCTIONS_COUNTER_GLOBAL_NAME};
+use super::{is_wasm64, system_api, StoreData, INSTRUCTIONS_COUNTER_GLOBAL_NAME};
 use crate::{wasm_utils::validate_and_instrument_for_testing, WasmtimeEmbedder};
 use ic_config::flag_status::FlagStatus;
 use ic_config::{embedders::Config as EmbeddersConfig, subnet_config::SchedulerConfig};
@@ -88,6 +88,7 @@ fn test_wasmtime_system_api() {
             num_instructions_global: None,
             log: no_op_logger(),
             num_stable_dirty_pages_from_non_native_writes: ic_types::NumPages::from(0),
+            profiler: None,
         },
     );
 
@@ -117,13 +118,23 @@ fn test_wasmtime_system_api() {
 
     let mut linker: wasmtime::Linker<StoreData> = wasmtime::Linker::new(&engine);
 
-    system_api::syscalls(
-        &mut linker,
-        config.feature_flags,
-        config.stable_memory_dirty_page_limit,
-        config.stable_memory_accessed_page_limit,
-        config.metering_type,
-    );
+    if is_wasm64(&module) {
+        system_api::syscalls::<u64>(
+            &mut linker,
+            config.feature_flags,
+            config.stable_memory_dirty_page_limit,
+            config.stable_memory_accessed_page_limit,
+            config.metering_type,
+        );
+    } else {
+        system_api::syscalls::<u32>(
+            &mut linker,
+            config.feature_flags,
+            config.stable_memory_dirty_page_limit,
+            config.stable_memory_accessed_page_limit,
+            config.metering_type,
+        );
+    }
     let instance = linker
         .instantiate(&mut store, &module)
         .expect("failed to instantiate instance");
@@ -147,21 +158,15 @@ fn test_wasmtime_system_api() {
 
 #[test]
 fn test_initial_wasmtime_config() {
-    // The following proposals should be disabled: tail_call, simd, relaxed_simd,
-    // threads, multi_memory, exceptions, memory64, extended_const, component_model,
-    // function_references, memory_control, gc
+    // The following proposals should be disabled: relaxed_simd, threads,
+    // multi_memory, exceptions, memory64, component_model, function_references,
+    // memory_control, gc
     for (proposal, _url, wat, expected_err_msg) in [
         (
-            "tail_call",
-            "https://github.com/WebAssembly/tail-call/",
-            "(module (func $f1 return_call $f2) (func $f2))",
-            "tail calls support is not enabled",
-        ),
-        (
-            "simd",
+            "relaxed_simd",
             "https://github.com/WebAssembly/relaxed-simd/",
-            "(module (func $f (drop (v128.const i64x2 0 0))))",
-            "SIMD support is not enabled",
+            "(module (func $f (drop (i32x4.relaxed_trunc_f32x4_s (v128.const i64x2 0 0)))))",
+            "relaxed SIMD support is not enabled",
         ),
         (
             "threads",
@@ -182,12 +187,6 @@ fn test_initial_wasmtime_config() {
             "(module (memory $m i64 1 1))",
             "memory64 must be enabled",
         ),
-        (
-            "extended_const",
-            "https://github.com/WebAssembly/extended-const/",
-            "(module (global i32 (i32.add (i32.const 0) (i32.const 0))))",
-            "constant expression required",
-        ),
         (
             "component_model",
             "https://github.com/WebAssembly/component-model/",
@@ -224,3 +223,31 @@ fn test_initial_wasmtime_config() {
         );
     }
 }
+
+#[test]
+fn test_enabled_wasmtime_proposals() {
+    for (proposal, _url, wat) in [
+        (
+            "tail_call",
+            "https://github.com/WebAssembly/tail-call/",
+            "(module (func $f1 return_call $f2) (func $f2) (memory 0))",
+        ),
+        (
+            "extended_const",
+            "https://github.com/WebAssembly/extended-const/",
+            "(module (global i32 (i32.add (i32.const 0) (i32.const 0))) (memory 0))",
+        ),
+    ] {
+        let wasm_binary = BinaryEncodedWasm::new(
+            wat::parse_str(wat)
+                .unwrap_or_else(|_| panic!("Error parsing proposal `{proposal}` code snippet")),
+        );
+        validate_and_instrument_for_testing(
+            &WasmtimeEmbedder::new(EmbeddersConfig::default(), no_op_logger()),
+            &wasm_binary,
+        )
+        .unwrap_or_else(|err| {
+            panic!("Error having `{proposal}` proposal disabled in the `wasmtime` config: {err}")
+        });
+    }
+}
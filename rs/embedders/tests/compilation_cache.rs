@@ -0,0 +1,185 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use ic_config::embedders::Config as EmbeddersConfig;
+use ic_embedders::{wasm_utils::compile, CompilationCache, SerializedModule, WasmtimeEmbedder};
+use ic_interfaces::execution_environment::HypervisorError;
+use ic_logger::replica_logger::no_op_logger;
+use ic_types::NumBytes;
+use ic_wasm_types::{BinaryEncodedWasm, CanisterModule};
+
+const MEMORY_CAPACITY: NumBytes = NumBytes::new(1 << 30);
+const DISK_CAPACITY: NumBytes = NumBytes::new(1 << 30);
+
+fn canister_module(id: i32) -> CanisterModule {
+    let wat = format!(
+        r#"
+        (module
+            (func (export "canister_update run")
+                (drop (i32.const {id}))
+            )
+            (memory 1)
+        )"#
+    );
+    CanisterModule::new(wat::parse_str(wat).unwrap())
+}
+
+fn compile_module(canister_module: &CanisterModule) -> Arc<SerializedModule> {
+    let embedder = WasmtimeEmbedder::new(EmbeddersConfig::default(), no_op_logger());
+    let wasm = BinaryEncodedWasm::new(canister_module.as_slice().to_vec());
+    let (_, result) = compile(&embedder, &wasm);
+    Arc::new(result.unwrap().1)
+}
+
+fn persistent_cache(dir: &Path, disk_capacity: NumBytes) -> CompilationCache {
+    CompilationCache::new_persistent(
+        MEMORY_CAPACITY,
+        dir,
+        disk_capacity,
+        &EmbeddersConfig::default(),
+    )
+    .unwrap()
+}
+
+/// Returns the module files of all versions stored in the cache directory.
+fn module_files(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .unwrap()
+        .flat_map(|version_dir| fs::read_dir(version_dir.unwrap().path()).unwrap())
+        .map(|entry| entry.unwrap().path())
+        .collect()
+}
+
+#[test]
+fn persisted_module_is_used_after_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let module = canister_module(1);
+    let serialized_module = compile_module(&module);
+
+    let cache = persistent_cache(dir.path(), DISK_CAPACITY);
+    cache.insert(&module, Ok(Arc::clone(&serialized_module)));
+    drop(cache);
+
+    let cache = persistent_cache(dir.path(), DISK_CAPACITY);
+    let cached = cache.get(&module).unwrap().unwrap();
+    assert_eq!(cached.compilation_cost, serialized_module.compilation_cost);
+    assert_eq!(
+        cached.exported_functions,
+        serialized_module.exported_functions
+    );
+    assert_eq!(cached.bytes.as_slice(), serialized_module.bytes.as_slice());
+    assert!(cache.get(&canister_module(2)).is_none());
+
+    // The persisted module can be loaded by the embedder.
+    let embedder = WasmtimeEmbedder::new(EmbeddersConfig::default(), no_op_logger());
+    embedder
+        .deserialize_module_and_pre_instantiate(&cached.bytes)
+        .unwrap();
+}
+
+#[test]
+fn memory_only_cache_does_not_survive_restart() {
+    let module = canister_module(1);
+    let cache = CompilationCache::new(MEMORY_CAPACITY);
+    cache.insert(&module, Ok(compile_module(&module)));
+    assert!(cache.get(&module).is_some());
+
+    let cache = CompilationCache::new(MEMORY_CAPACITY);
+    assert!(cache.get(&module).is_none());
+}
+
+#[test]
+fn compilation_errors_are_not_persisted() {
+    let dir = tempfile::tempdir().unwrap();
+    let module = canister_module(1);
+
+    let cache = persistent_cache(dir.path(), DISK_CAPACITY);
+    cache.insert(
+        &module,
+        Err(HypervisorError::ContractViolation("error".to_string())),
+    );
+    assert!(cache.get(&module).unwrap().is_err());
+    assert!(module_files(dir.path()).is_empty());
+    drop(cache);
+
+    let cache = persistent_cache(dir.path(), DISK_CAPACITY);
+    assert!(cache.get(&module).is_none());
+}
+
+#[test]
+fn corrupted_module_is_discarded() {
+    let dir = tempfile::tempdir().unwrap();
+    let module = canister_module(1);
+
+    let cache = persistent_cache(dir.path(), DISK_CAPACITY);
+    cache.insert(&module, Ok(compile_module(&module)));
+    drop(cache);
+
+    let files = module_files(dir.path());
+    assert_eq!(files.len(), 1);
+    let mut contents = fs::read(&files[0]).unwrap();
+    *contents.last_mut().unwrap() ^= 1;
+    fs::write(&files[0], contents).unwrap();
+
+    let cache = persistent_cache(dir.path(), DISK_CAPACITY);
+    assert!(cache.get(&module).is_none());
+    assert!(module_files(dir.path()).is_empty());
+}
+
+#[test]
+fn least_recently_used_modules_are_evicted_from_disk() {
+    let dir = tempfile::tempdir().unwrap();
+    let modules: Vec<_> = (1..=3).map(canister_module).collect();
+
+    // Measure the size of a module file to size the cache for two modules.
+    let cache = persistent_cache(dir.path(), DISK_CAPACITY);
+    cache.insert(&modules[0], Ok(compile_module(&modules[0])));
+    let file_size = fs::metadata(&module_files(dir.path())[0]).unwrap().len();
+    cache.clear_for_testing();
+    drop(cache);
+
+    let disk_capacity = NumBytes::new(file_size * 5 / 2);
+    let cache = persistent_cache(dir.path(), disk_capacity);
+    for module in modules.iter() {
+        cache.insert(module, Ok(compile_module(module)));
+    }
+    assert_eq!(module_files(dir.path()).len(), 2);
+    drop(cache);
+
+    let cache = persistent_cache(dir.path(), disk_capacity);
+    assert!(cache.get(&modules[0]).is_none());
+    assert!(cache.get(&modules[1]).is_some());
+    assert!(cache.get(&modules[2]).is_some());
+}
+
+#[test]
+fn modules_of_other_versions_are_removed() {
+    let dir = tempfile::tempdir().unwrap();
+    let stale_dir = dir.path().join("stale_version");
+    fs::create_dir_all(&stale_dir).unwrap();
+    fs::write(stale_dir.join("module.bin"), b"stale").unwrap();
+
+    let _cache = persistent_cache(dir.path(), DISK_CAPACITY);
+    assert!(!stale_dir.exists());
+}
+
+#[test]
+fn embedder_config_change_invalidates_persisted_modules() {
+    let dir = tempfile::tempdir().unwrap();
+    let module = canister_module(1);
+
+    let cache = persistent_cache(dir.path(), DISK_CAPACITY);
+    cache.insert(&module, Ok(compile_module(&module)));
+    drop(cache);
+
+    let mut config = EmbeddersConfig::default();
+    config.max_globals += 1;
+    let cache =
+        CompilationCache::new_persistent(MEMORY_CAPACITY, dir.path(), DISK_CAPACITY, &config)
+            .unwrap();
+    assert!(cache.get(&module).is_none());
+    assert!(module_files(dir.path()).is_empty());
+}
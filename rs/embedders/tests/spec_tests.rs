@@ -66,7 +66,11 @@ mod convert {
     /// Comparison of a Wasmtime f32 result with the expected Wast value. Copied
     /// from
     /// https://github.com/bytecodealliance/wasmtime/blob/main/crates/wast/src/core.rs#L106.
-    fn f32_equal(actual: u32, expected: &NanPattern<Float32>) -> bool {
+    ///
+    /// If `canonical_nans_only` is set, then an expected arithmetic NaN only
+    /// matches a canonical NaN, which is what the NaN canonicalization in the
+    /// production config must produce.
+    fn f32_equal(actual: u32, expected: &NanPattern<Float32>, canonical_nans_only: bool) -> bool {
         match expected {
             // Check if an f32 (as u32 bits to avoid possible quieting when moving values in registers, e.g.
             // https://developer.arm.com/documentation/ddi0344/i/neon-and-vfp-programmers-model/modes-of-operation/default-nan-mode?lang=en)
@@ -85,6 +89,9 @@ mod convert {
             // set to 1, but one or more of the remaining payload bits MAY BE set to
             // 1 (a canonical NaN specifies all 0s). See
             // https://webassembly.github.io/spec/core/syntax/values.html#floating-point.
+            NanPattern::ArithmeticNan if canonical_nans_only => {
+                f32_equal(actual, &NanPattern::CanonicalNan, false)
+            }
             NanPattern::ArithmeticNan => {
                 const AF32_NAN: u32 = 0x7f80_0000;
                 let is_nan = actual & AF32_NAN == AF32_NAN;
@@ -99,7 +106,13 @@ mod convert {
     /// Comparison of a Wasmtime f64 result with the expected Wast value. Copied
     /// from
     /// https://github.com/bytecodealliance/wasmtime/blob/main/crates/wast/src/core.rs#L171.
-    pub fn f64_equal(actual: u64, expected: &NanPattern<Float64>) -> bool {
+    ///
+    /// See `f32_equal` for the meaning of `canonical_nans_only`.
+    pub fn f64_equal(
+        actual: u64,
+        expected: &NanPattern<Float64>,
+        canonical_nans_only: bool,
+    ) -> bool {
         match expected {
             // Check if an f64 (as u64 bits to avoid possible quieting when moving values in registers, e.g.
             // https://developer.arm.com/documentation/ddi0344/i/neon-and-vfp-programmers-model/modes-of-operation/default-nan-mode?lang=en)
@@ -117,6 +130,9 @@ mod convert {
             // canonical NaN including that the payload MSB is set to 1, but one or more of the remaining
             // payload bits MAY BE set to 1 (a canonical NaN specifies all 0s). See
             // https://webassembly.github.io/spec/core/syntax/values.html#floating-point.
+            NanPattern::ArithmeticNan if canonical_nans_only => {
+                f64_equal(actual, &NanPattern::CanonicalNan, false)
+            }
             NanPattern::ArithmeticNan => {
                 const AF64_NAN: u64 = 0x7ff0_0000_0000_0000;
                 let is_nan = actual & AF64_NAN == AF64_NAN;
@@ -128,7 +144,7 @@ mod convert {
         }
     }
 
-    fn v128_equal(left: u128, right: &V128Pattern) -> bool {
+    fn v128_equal(left: u128, right: &V128Pattern, canonical_nans_only: bool) -> bool {
         match right {
             V128Pattern::I8x16(parts) => {
                 left == u128::from_le_bytes(
@@ -176,18 +192,21 @@ mod convert {
                 let l2 = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
                 let l3 = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
                 let l4 = u32::from_le_bytes(bytes[12..16].try_into().unwrap());
-                f32_equal(l1, r1) && f32_equal(l2, r2) && f32_equal(l3, r3) && f32_equal(l4, r4)
+                f32_equal(l1, r1, canonical_nans_only)
+                    && f32_equal(l2, r2, canonical_nans_only)
+                    && f32_equal(l3, r3, canonical_nans_only)
+                    && f32_equal(l4, r4, canonical_nans_only)
             }
             V128Pattern::F64x2([r1, r2]) => {
                 let bytes = left.to_le_bytes();
                 let l1 = u64::from_le_bytes(bytes[0..8].try_into().unwrap());
                 let l2 = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
-                f64_equal(l1, r1) && f64_equal(l2, r2)
+                f64_equal(l1, r1, canonical_nans_only) && f64_equal(l2, r2, canonical_nans_only)
             }
         }
     }
 
-    fn val_equal(left: &wasmtime::Val, right: &WastRet) -> bool {
+    fn val_equal(left: &wasmtime::Val, right: &WastRet, canonical_nans_only: bool) -> bool {
         use wasmtime::Val as V;
         use wast::core::WastRetCore as R;
         use WastRet::Core as C;
@@ -195,9 +214,9 @@ mod convert {
         match (left, right) {
             (V::I32(l), C(R::I32(r))) => l == r,
             (V::I64(l), C(R::I64(r))) => l == r,
-            (V::F32(l), C(R::F32(r))) => f32_equal(*l, r),
-            (V::F64(l), C(R::F64(r))) => f64_equal(*l, r),
-            (V::V128(l), C(R::V128(r))) => v128_equal(l.as_u128(), r),
+            (V::F32(l), C(R::F32(r))) => f32_equal(*l, r, canonical_nans_only),
+            (V::F64(l), C(R::F64(r))) => f64_equal(*l, r, canonical_nans_only),
+            (V::V128(l), C(R::V128(r))) => v128_equal(l.as_u128(), r, canonical_nans_only),
             (V::ExternRef(None), C(R::RefExtern(_))) => false,
             // `WastArgCore::RefExtern` always stores a `u32`.
             (V::ExternRef(Some(l)), C(R::RefExtern(r))) => {
@@ -216,9 +235,15 @@ mod convert {
         }
     }
 
-    pub(super) fn vals_equal(left: &[wasmtime::Val], right: &[WastRet]) -> bool {
+    pub(super) fn vals_equal(
+        left: &[wasmtime::Val],
+        right: &[WastRet],
+        canonical_nans_only: bool,
+    ) -> bool {
         if left.len() == right.len() {
-            left.iter().zip(right.iter()).all(|(l, r)| val_equal(l, r))
+            left.iter()
+                .zip(right.iter())
+                .all(|(l, r)| val_equal(l, r, canonical_nans_only))
         } else {
             false
         }
@@ -319,10 +344,12 @@ struct TestState<'a> {
     store: Store<()>,
     linker: Linker<()>,
     engine: Engine,
+    /// Whether expected arithmetic NaN results must be canonical NaNs.
+    canonical_nans_only: bool,
 }
 
 impl<'a> TestState<'a> {
-    fn new(config: &Config) -> Self {
+    fn new(config: &Config, canonical_nans_only: bool) -> Self {
         let engine = Engine::new(config).unwrap();
         let mut store = Store::new(&engine, ());
         let mut linker = Linker::new(&engine);
@@ -333,6 +360,7 @@ impl<'a> TestState<'a> {
             store,
             linker,
             engine,
+            canonical_nans_only,
         }
     }
 
@@ -542,7 +570,8 @@ fn run_directive<'a>(
             match exec {
                 wast::WastExecute::Invoke(invoke) => {
                     let run_results = test_state.run(invoke.name, invoke.args, invoke.module)?;
-                    if !convert::vals_equal(&run_results, &results) {
+                    if !convert::vals_equal(&run_results, &results, test_state.canonical_nans_only)
+                    {
                         return Err(format!(
                             "Incorrect result running wasm at {}: Expected {:?} but got {:?}",
                             span_location(span, text, path),
@@ -687,13 +716,14 @@ fn test_spec_file(
     path: &PathBuf,
     config: &Config,
     parsing_multi_memory_enabled: bool,
+    canonical_nans_only: bool,
 ) -> Result<(), String> {
     let contents = fs::read_to_string(path).unwrap();
     let buf = ParseBuffer::new(&contents).unwrap();
 
     let wast = wast::parser::parse::<Wast>(&buf).unwrap();
     let mut error_string = String::new();
-    let mut test_state = TestState::new(config);
+    let mut test_state = TestState::new(config, canonical_nans_only);
     for directive in wast.directives {
         if let Err(e) = run_directive(
             directive,
@@ -712,25 +742,51 @@ fn test_spec_file(
     }
 }
 
-fn run_testsuite(subdirectory: &str, config: &Config, parsing_multi_memory_enabled: bool) {
+/// Returns the `wast` files in the given subdirectory of the testsuite whose
+/// name starts with `prefix`.
+fn testsuite_files(subdirectory: &str, prefix: &str) -> Vec<PathBuf> {
     let dir_path = format!("./external/wasm_spec_testsuite/{}", subdirectory);
     let directory = std::fs::read_dir(dir_path).unwrap();
     let mut test_files = vec![];
     for entry in directory {
         let entry = entry.unwrap();
         let path = entry.path();
+        let file_name = path.file_name().unwrap().to_str().unwrap();
         if path.extension() == Some(&OsString::from("wast"))
-            && !FILES_TO_SKIP.contains(&path.file_name().unwrap().to_str().unwrap())
+            && file_name.starts_with(prefix)
+            && !FILES_TO_SKIP.contains(&file_name)
         {
             test_files.push(path);
         }
     }
+    test_files
+}
 
+fn run_testsuite(subdirectory: &str, config: &Config, parsing_multi_memory_enabled: bool) {
+    run_testsuite_files(
+        testsuite_files(subdirectory, ""),
+        config,
+        parsing_multi_memory_enabled,
+        false,
+    )
+}
+
+fn run_testsuite_files(
+    test_files: Vec<PathBuf>,
+    config: &Config,
+    parsing_multi_memory_enabled: bool,
+    canonical_nans_only: bool,
+) {
     println!("Running spec tests on {} files", test_files.len());
     let mut errors = vec![];
     for path in test_files {
         println!("Running tests on file {:?}", path);
-        if let Err(e) = test_spec_file(&path, config, parsing_multi_memory_enabled) {
+        if let Err(e) = test_spec_file(
+            &path,
+            config,
+            parsing_multi_memory_enabled,
+            canonical_nans_only,
+        ) {
             errors.push(e);
         }
     }
@@ -744,8 +800,6 @@ fn run_testsuite(subdirectory: &str, config: &Config, parsing_multi_memory_enabl
 /// production for validation.
 fn default_config() -> Config {
     let mut config = wasmtime_validation_config(&ic_config::embedders::Config::default());
-    // Some tests require SIMD instructions to run.
-    config.wasm_simd(true);
     // This is needed to avoid stack overflows in some tests.
     config.max_wasm_stack(512 * 1024);
     config
@@ -769,6 +823,17 @@ fn spec_testsuite() {
     run_testsuite("", &default_config(), false)
 }
 
+/// Runs the SIMD part of the spec testsuite with the production config and
+/// additionally requires every NaN result to be a canonical NaN. The spec
+/// allows arbitrary arithmetic NaNs, whose bit patterns may differ between
+/// hardware platforms, so this checks that SIMD execution is deterministic.
+#[test]
+fn simd_determinism_testsuite() {
+    let test_files = testsuite_files("", "simd_");
+    assert!(!test_files.is_empty(), "No SIMD spec tests found");
+    run_testsuite_files(test_files, &default_config(), false, true)
+}
+
 #[test]
 fn multi_memory_testsuite() {
     run_testsuite(
@@ -788,3 +853,13 @@ fn memory64_testsuite() {
         false,
     )
 }
+
+#[test]
+fn tail_call_testsuite() {
+    run_testsuite("proposals/tail-call", &default_config(), false)
+}
+
+#[test]
+fn extended_const_testsuite() {
+    run_testsuite("proposals/extended-const", &default_config(), false)
+}
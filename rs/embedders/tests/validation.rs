@@ -1,7 +1,7 @@
 use std::borrow::Cow;
 
 use assert_matches::assert_matches;
-use ic_config::embedders::Config as EmbeddersConfig;
+use ic_config::{embedders::Config as EmbeddersConfig, flag_status::FlagStatus};
 use ic_embedders::{
     wasm_utils::{
         validate_and_instrument_for_testing,
@@ -93,6 +93,7 @@ fn can_validate_valid_export_section() {
                   (export "canister_global_timer" (func $x))
                   (export "canister_pre_upgrade" (func $x))
                   (export "canister_post_upgrade" (func $x))
+                  (export "canister_on_low_wasm_memory" (func $x))
                   (export "canister_query read" (func $x))
                   (export "canister_composite_query query" (func $x)))"#,
     )
@@ -505,6 +506,24 @@ fn can_validate_valid_data_section() {
     );
 }
 
+#[test]
+fn can_validate_data_section_with_extended_const_offset() {
+    let wasm = wat2wasm(
+        r#"
+                (module
+                    (memory (;0;) 1)
+                    (data (i32.add (i32.mul (i32.const 4) (i32.const 4)) (i32.const 2)) "abcd")
+                )
+            "#,
+    )
+    .unwrap();
+    let embedder = WasmtimeEmbedder::new(EmbeddersConfig::default(), no_op_logger());
+    let (validation_details, output) =
+        validate_and_instrument_for_testing(&embedder, &wasm).unwrap();
+    assert_eq!(validation_details, WasmValidationDetails::default());
+    assert_eq!(output.data.into_slice(), vec![(18, b"abcd".to_vec())]);
+}
+
 #[test]
 // this test passes currently not because of a correct validation that we're not
 // using a global in data offset expression, but because we terminate the
@@ -1130,3 +1149,85 @@ fn wasm_with_multiple_code_sections_is_invalid() {
         ))
     )
 }
+
+fn wasm64_config() -> EmbeddersConfig {
+    let mut config = EmbeddersConfig::default();
+    config.feature_flags.wasm64 = FlagStatus::Enabled;
+    config
+}
+
+#[test]
+fn can_validate_wasm64_memory_only_with_feature_flag() {
+    let wasm = wat2wasm(
+        r#"(module
+                (import "ic0" "msg_reply_data_append" (func $msg_reply_data_append (param i64 i64)))
+                (memory i64 1))"#,
+    )
+    .unwrap();
+    assert_matches!(
+        validate_wasm_binary(&wasm, &EmbeddersConfig::default()),
+        Err(WasmValidationError::WasmtimeValidation(_))
+    );
+    assert_matches!(validate_wasm_binary(&wasm, &wasm64_config()), Ok(_));
+}
+
+#[test]
+fn can_validate_wasm64_import_with_32_bit_pointers() {
+    let wasm = wat2wasm(
+        r#"(module
+                (import "ic0" "msg_reply_data_append" (func $msg_reply_data_append (param i32 i32)))
+                (memory i64 1))"#,
+    )
+    .unwrap();
+    assert_matches!(
+        validate_wasm_binary(&wasm, &wasm64_config()),
+        Err(WasmValidationError::InvalidImportSection(_))
+    );
+}
+
+#[test]
+fn can_validate_wasm64_memory_import() {
+    let wasm = wat2wasm(r#"(module (import "env" "memory" (memory i64 1)))"#).unwrap();
+    assert_matches!(
+        validate_wasm_binary(&wasm, &wasm64_config()),
+        Err(WasmValidationError::InvalidImportSection(_))
+    );
+}
+
+#[test]
+fn can_validate_wasm64_memory_exceeding_max_size() {
+    let config = wasm64_config();
+    let max_pages = config.max_wasm64_memory_size.get() / WASM_PAGE_SIZE as u64;
+    let wasm = wat2wasm(&format!("(module (memory i64 {}))", max_pages)).unwrap();
+    assert_matches!(validate_wasm_binary(&wasm, &config), Ok(_));
+    let wasm = wat2wasm(&format!("(module (memory i64 {}))", max_pages + 1)).unwrap();
+    assert_matches!(
+        validate_wasm_binary(&wasm, &config),
+        Err(WasmValidationError::InvalidMemorySection(_))
+    );
+}
+
+#[test]
+fn can_validate_wasm64_memory_maximum_exceeding_max_size() {
+    let config = wasm64_config();
+    let max_pages = config.max_wasm64_memory_size.get() / WASM_PAGE_SIZE as u64;
+    let wasm = wat2wasm(&format!("(module (memory i64 1 {}))", max_pages)).unwrap();
+    assert_matches!(validate_wasm_binary(&wasm, &config), Ok(_));
+    let wasm = wat2wasm(&format!("(module (memory i64 1 {}))", max_pages + 1)).unwrap();
+    assert_matches!(
+        validate_wasm_binary(&wasm, &config),
+        Err(WasmValidationError::InvalidMemorySection(_))
+    );
+}
+
+#[test]
+fn can_validate_wasm64_data_segment_with_i64_offset() {
+    let wasm = wat2wasm(
+        r#"(module
+                (memory i64 1)
+                (data (i64.const 16) "abc")
+                (data (i64.add (i64.const 16) (i64.mul (i64.const 2) (i64.const 8))) "def"))"#,
+    )
+    .unwrap();
+    assert_matches!(validate_wasm_binary(&wasm, &wasm64_config()), Ok(_));
+}
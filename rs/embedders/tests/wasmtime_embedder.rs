@@ -295,9 +295,11 @@ mod test {
                 assert_eq!(
                     result.err(),
                     Some(
-                        ic_interfaces::execution_environment::HypervisorError::Trapped(
-                            ic_interfaces::execution_environment::TrapCode::StackOverflow
-                        )
+                        ic_interfaces::execution_environment::HypervisorError::Trapped {
+                            trap_code:
+                                ic_interfaces::execution_environment::TrapCode::StackOverflow,
+                            backtrace: None,
+                        }
                     )
                 );
             })
@@ -306,6 +308,90 @@ mod test {
         handler.join().unwrap();
     }
 
+    #[test]
+    fn deep_tail_recursion_does_not_overflow_stack() {
+        use std::thread;
+        const ITERATIONS: i64 = 1_000_000;
+        let builder = thread::Builder::new();
+        let handler = builder
+            // Same stack size as in `stack_overflow_traps`, which overflows
+            // with a regular recursive call.
+            .stack_size(8192000)
+            .spawn(|| {
+                let mut instance = WasmtimeInstanceBuilder::new()
+                    .with_wat(&format!(
+                        r#"
+                        (module
+                            (func $f (param $n i64)
+                            ;; Define many local variables to quickly overflow the stack
+                            (local i64) (local i64) (local i64) (local i64) (local i64)
+                            (local i64) (local i64) (local i64) (local i64) (local i64)
+                            (local i64) (local i64) (local i64) (local i64) (local i64)
+                            (local i64) (local i64) (local i64) (local i64) (local i64)
+                            (if (i64.eqz (local.get $n)) (then (return)))
+                            ;; call "f" recursively in a tail position
+                            (return_call $f (i64.sub (local.get $n) (i64.const 1)))
+                            )
+                            (func (export "canister_update f")
+                                (call $f (i64.const {ITERATIONS}))
+                            )
+                            (memory 0)
+                        )
+                        "#,
+                    ))
+                    .build();
+
+                let result = instance.run(ic_types::methods::FuncRef::Method(
+                    ic_types::methods::WasmMethod::Update("f".to_string()),
+                ));
+                assert_eq!(result.err(), None);
+
+                // Every tail call is charged like a regular call.
+                let return_call_cost = instruction_to_cost_new(&wasmparser::Operator::ReturnCall {
+                    function_index: 0,
+                });
+                let call_cost =
+                    instruction_to_cost_new(&wasmparser::Operator::Call { function_index: 0 });
+                assert_eq!(return_call_cost, call_cost);
+                let instruction_counter = instance.instruction_counter();
+                let instructions_used = instance
+                    .store_data()
+                    .system_api()
+                    .unwrap()
+                    .slice_instructions_executed(instruction_counter);
+                assert!(instructions_used.get() > ITERATIONS as u64 * return_call_cost);
+            })
+            .unwrap();
+
+        handler.join().unwrap();
+    }
+
+    #[test]
+    fn deep_tail_recursion_runs_out_of_instructions() {
+        let mut instance = WasmtimeInstanceBuilder::new()
+            .with_wat(
+                r#"
+                (module
+                    (func $f (export "canister_update f")
+                        ;; infinite tail recursion
+                        (return_call $f)
+                    )
+                    (memory 0)
+                )
+                "#,
+            )
+            .with_num_instructions(1_000_000.into())
+            .build();
+
+        let result = instance.run(ic_types::methods::FuncRef::Method(
+            ic_types::methods::WasmMethod::Update("f".to_string()),
+        ));
+        assert_eq!(
+            result.err(),
+            Some(HypervisorError::InstructionLimitExceeded)
+        );
+    }
+
     #[test]
     // Takes a Wasm with two mutable globals and checks whether we can set and get
     // their values.
@@ -1036,23 +1122,53 @@ mod test {
         // Host stable memory
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_src")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_dst")).unwrap_err();
-        assert_eq!(err, Trapped(HeapOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: HeapOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_len_heap")).unwrap_err();
-        assert_eq!(err, Trapped(HeapOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: HeapOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_len_stable")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_len_both")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         // native stable memory
         let mut config = ic_config::embedders::Config::default();
@@ -1064,35 +1180,65 @@ mod test {
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_src")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new()
             .with_config(config.clone())
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_dst")).unwrap_err();
-        assert_eq!(err, Trapped(HeapOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: HeapOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new()
             .with_config(config.clone())
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_len_heap")).unwrap_err();
-        assert_eq!(err, Trapped(HeapOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: HeapOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new()
             .with_config(config.clone())
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_len_stable")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new()
             .with_config(config)
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_len_both")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
     }
 
     #[test]
@@ -1152,27 +1298,63 @@ mod test {
         // Host stable memory
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_src")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_dst")).unwrap_err();
-        assert_eq!(err, Trapped(HeapOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: HeapOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_len")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_len_heap")).unwrap_err();
-        assert_eq!(err, Trapped(HeapOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: HeapOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_len_stable")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_len_both")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         // Native stable memory
         let mut config = ic_config::embedders::Config::default();
@@ -1184,42 +1366,78 @@ mod test {
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_src")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new()
             .with_config(config.clone())
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_dst")).unwrap_err();
-        assert_eq!(err, Trapped(HeapOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: HeapOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new()
             .with_config(config.clone())
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_len")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new()
             .with_config(config.clone())
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_len_heap")).unwrap_err();
-        assert_eq!(err, Trapped(HeapOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: HeapOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new()
             .with_config(config.clone())
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_len_stable")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new()
             .with_config(config)
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_len_both")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
     }
 
     #[test]
@@ -1272,23 +1490,53 @@ mod test {
         // Host stable memory
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_src")).unwrap_err();
-        assert_eq!(err, Trapped(HeapOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: HeapOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_dst")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_len_heap")).unwrap_err();
-        assert_eq!(err, Trapped(HeapOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: HeapOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_len_stable")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_len_both")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         // native stable memory
         let mut config = ic_config::embedders::Config::default();
@@ -1300,35 +1548,65 @@ mod test {
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_src")).unwrap_err();
-        assert_eq!(err, Trapped(HeapOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: HeapOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new()
             .with_config(config.clone())
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_dst")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new()
             .with_config(config.clone())
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_len_heap")).unwrap_err();
-        assert_eq!(err, Trapped(HeapOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: HeapOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new()
             .with_config(config.clone())
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_len_stable")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new()
             .with_config(config)
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_len_both")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
     }
 
     #[test]
@@ -1388,27 +1666,63 @@ mod test {
         // Host stable memory
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_src")).unwrap_err();
-        assert_eq!(err, Trapped(HeapOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: HeapOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_dst")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_len")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_len_heap")).unwrap_err();
-        assert_eq!(err, Trapped(HeapOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: HeapOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_len_stable")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new().with_wat(wat).build();
         let err = instance.run(func_ref("test_len_both")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         // native stable memory
         let mut config = ic_config::embedders::Config::default();
@@ -1419,42 +1733,78 @@ mod test {
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_src")).unwrap_err();
-        assert_eq!(err, Trapped(HeapOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: HeapOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new()
             .with_config(config.clone())
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_dst")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new()
             .with_config(config.clone())
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_len")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new()
             .with_config(config.clone())
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_len_heap")).unwrap_err();
-        assert_eq!(err, Trapped(HeapOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: HeapOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new()
             .with_config(config.clone())
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_len_stable")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
 
         let mut instance = WasmtimeInstanceBuilder::new()
             .with_config(config)
             .with_wat(wat)
             .build();
         let err = instance.run(func_ref("test_len_both")).unwrap_err();
-        assert_eq!(err, Trapped(StableMemoryOutOfBounds));
+        assert_eq!(
+            err,
+            Trapped {
+                trap_code: StableMemoryOutOfBounds,
+                backtrace: None
+            }
+        );
     }
 
     /// Test that stable memory access past the normal 32-bit range (including
@@ -1323,6 +1323,28 @@ mod tests {
         apply_writes_and_check_heap(&writes, ModificationTracking::Track, &wat, true);
     }
 
+    #[test]
+    fn wasmtime_random_memory_writes_v128store() {
+        // The seed value will always be the same for a particular version of
+        // Proptest and algorithm, but may change across releases.
+        let mut runner = proptest::test_runner::TestRunner::deterministic();
+        let wat = make_backward_store_module_wat(TEST_NUM_PAGES, 16, "v128.store", "v128.load");
+        // Random, *non-empty* writes
+        let writes: Vec<Write> = random_writes(TEST_HEAP_SIZE_BYTES, TEST_NUM_WRITES, 16)
+            .new_tree(&mut runner)
+            .unwrap()
+            .current()
+            .iter()
+            .filter(|w| !w.bytes.is_empty())
+            .cloned()
+            .collect();
+        let corner_writes = corner_case_writes(TEST_HEAP_SIZE_BYTES, 16);
+        for writes in corner_writes {
+            apply_writes_and_check_heap(&writes, ModificationTracking::Track, &wat, true)
+        }
+        apply_writes_and_check_heap(&writes, ModificationTracking::Track, &wat, true);
+    }
+
     #[test]
     fn touch_heap_with_api_calls() {
         with_test_replica_logger(|log| {
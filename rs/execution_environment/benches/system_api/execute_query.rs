@@ -67,6 +67,7 @@ pub fn execute_query_bench(c: &mut Criterion) {
                 exec_env.hypervisor_for_testing(),
                 &mut round_limits,
                 exec_env.state_changes_error(),
+                &|| {},
             )
             .2;
             let executed_instructions =
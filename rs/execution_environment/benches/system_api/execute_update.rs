@@ -238,6 +238,11 @@ pub fn execute_update_bench(c: &mut Criterion) {
             Module::Test.from_ic0("global_timer_set", Param1(0_i64), Result::I64),
             518000006,
         ),
+        common::Benchmark(
+            "ic0_timer_set()".into(),
+            Module::Test.from_ic0("timer_set", Params2(0_i64, 0_i64), Result::I64),
+            519000006,
+        ),
         common::Benchmark(
             "ic0_performance_counter()".into(),
             Module::Test.from_ic0("performance_counter", Param1(0), Result::I64),
@@ -1,6 +1,6 @@
 use crate::as_round_instructions;
 use crate::canister_settings::{validate_canister_settings, ValidatedCanisterSettings};
-use crate::execution::install_code::{validate_controller, OriginalContext};
+use crate::execution::install_code::{canister_layout, validate_controller, OriginalContext};
 use crate::execution::{install::execute_install, upgrade::execute_upgrade};
 use crate::execution_environment::{
     CompilationCostHandling, RoundContext, RoundCounters, RoundLimits,
@@ -16,9 +16,11 @@ use ic_config::flag_status::FlagStatus;
 use ic_cycles_account_manager::{CyclesAccountManager, ResourceSaturation};
 use ic_error_types::{ErrorCode, RejectCode, UserError};
 use ic_ic00_types::{
-    CanisterChangeDetails, CanisterChangeOrigin, CanisterInstallModeV2, CanisterStatusResultV2,
-    CanisterStatusType, InstallChunkedCodeArgs, InstallCodeArgsV2, Method as Ic00Method,
-    StoredChunksReply, UploadChunkReply,
+    CanisterChangeDetails, CanisterChangeOrigin, CanisterInstallModeV2, CanisterMethodKind,
+    CanisterMethodStats, CanisterSnapshotResponse, CanisterStatusResultV2, CanisterStatusType,
+    EnvironmentVariable, FetchCanisterLogsResponse, InstallChunkedCodeArgs, InstallCodeArgsV2,
+    ListCanisterSnapshotsReply, LogVisibility, Method as Ic00Method, StoredChunksReply,
+    UploadChunkReply,
 };
 use ic_interfaces::execution_environment::{
     CanisterOutOfCyclesError, HypervisorError, IngressHistoryWriter, SubnetAvailableMemory,
@@ -34,8 +36,8 @@ use ic_replicated_state::{
     },
     metadata_state::subnet_call_context_manager::InstallCodeCallId,
     page_map::PageAllocatorFileDescriptor,
-    CallOrigin, CanisterState, CanisterStatus, NetworkTopology, ReplicatedState, SchedulerState,
-    SystemState,
+    CallOrigin, CanisterSnapshot, CanisterState, CanisterStatus, NetworkTopology, ReplicatedState,
+    SchedulerState, SnapshotId, SystemState,
 };
 use ic_system_api::ExecutionParameters;
 use ic_types::{
@@ -54,7 +56,12 @@ use num_traits::cast::ToPrimitive;
 use prometheus::IntCounter;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
-use std::{collections::BTreeSet, convert::TryFrom, str::FromStr, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+    str::FromStr,
+    sync::Arc,
+};
 
 #[derive(Debug, PartialEq, Eq)]
 pub(crate) struct InstallCodeResult {
@@ -111,8 +118,13 @@ pub(crate) struct CanisterMgrConfig {
     pub(crate) own_subnet_id: SubnetId,
     pub(crate) own_subnet_type: SubnetType,
     pub(crate) max_controllers: usize,
+    pub(crate) max_environment_variables: usize,
+    pub(crate) max_environment_variable_name_length: usize,
+    pub(crate) max_environment_variable_value_length: usize,
     pub(crate) rate_limiting_of_instructions: FlagStatus,
     pub(crate) wasm_chunk_store: FlagStatus,
+    pub(crate) canister_snapshots: FlagStatus,
+    pub(crate) canister_logging: FlagStatus,
     rate_limiting_of_heap_delta: FlagStatus,
     heap_delta_rate_limit: NumBytes,
     upload_wasm_chunk_instructions: NumInstructions,
@@ -127,6 +139,9 @@ impl CanisterMgrConfig {
         own_subnet_id: SubnetId,
         own_subnet_type: SubnetType,
         max_controllers: usize,
+        max_environment_variables: usize,
+        max_environment_variable_name_length: usize,
+        max_environment_variable_value_length: usize,
         compute_capacity: usize,
         rate_limiting_of_instructions: FlagStatus,
         allocatable_capacity_in_percent: usize,
@@ -134,6 +149,8 @@ impl CanisterMgrConfig {
         rate_limiting_of_heap_delta: FlagStatus,
         heap_delta_rate_limit: NumBytes,
         upload_wasm_chunk_instructions: NumInstructions,
+        canister_snapshots: FlagStatus,
+        canister_logging: FlagStatus,
     ) -> Self {
         Self {
             subnet_memory_capacity,
@@ -142,6 +159,9 @@ impl CanisterMgrConfig {
             own_subnet_id,
             own_subnet_type,
             max_controllers,
+            max_environment_variables,
+            max_environment_variable_name_length,
+            max_environment_variable_value_length,
             compute_capacity: (compute_capacity * allocatable_capacity_in_percent.min(100) / 100)
                 as u64,
             rate_limiting_of_instructions,
@@ -149,6 +169,8 @@ impl CanisterMgrConfig {
             rate_limiting_of_heap_delta,
             heap_delta_rate_limit,
             upload_wasm_chunk_instructions,
+            canister_snapshots,
+            canister_logging,
         }
     }
 }
@@ -329,6 +351,14 @@ pub(crate) struct UploadChunkResult {
     pub(crate) heap_delta_increase: NumBytes,
 }
 
+pub(crate) struct TakeCanisterSnapshotResult {
+    pub(crate) reply: CanisterSnapshotResponse,
+    pub(crate) heap_delta_increase: NumBytes,
+}
+
+/// The maximum number of snapshots a single canister can have at any time.
+pub(crate) const MAX_SNAPSHOTS_PER_CANISTER: usize = 1;
+
 impl CanisterManager {
     pub(crate) fn new(
         hypervisor: Arc<Hypervisor>,
@@ -405,7 +435,11 @@ impl CanisterManager {
             | Ok(Ic00Method::UploadChunk)
             | Ok(Ic00Method::StoredChunks)
             | Ok(Ic00Method::DeleteChunks)
-            | Ok(Ic00Method::ClearChunkStore) => {
+            | Ok(Ic00Method::ClearChunkStore)
+            | Ok(Ic00Method::TakeCanisterSnapshot)
+            | Ok(Ic00Method::LoadCanisterSnapshot)
+            | Ok(Ic00Method::ListCanisterSnapshots)
+            | Ok(Ic00Method::DeleteCanisterSnapshot) => {
                 // Reject large install methods if the flag is not enabled, or
                 // they are not implemented.
                 match method {
@@ -421,6 +455,13 @@ impl CanisterManager {
                         ErrorCode::CanisterRejectedMessage,
                         "Chunked upload API is not yet implemented"
                     )),
+                    Ok(Ic00Method::TakeCanisterSnapshot)
+                    | Ok(Ic00Method::LoadCanisterSnapshot)
+                    | Ok(Ic00Method::ListCanisterSnapshots)
+                    | Ok(Ic00Method::DeleteCanisterSnapshot) if self.config.canister_snapshots == FlagStatus::Disabled => return Err(UserError::new(
+                        ErrorCode::CanisterRejectedMessage,
+                        "Canister snapshotting API is not yet implemented"
+                    )),
                     _ => {}
                 };
                 match effective_canister_id {
@@ -447,6 +488,27 @@ impl CanisterManager {
                 }
             },
 
+            // The logs of a canister can be read by anyone if its log
+            // visibility is public and only by its controllers otherwise.
+            Ok(Ic00Method::FetchCanisterLogs) => {
+                if self.config.canister_logging == FlagStatus::Disabled {
+                    return Err(CanisterManagerError::CanisterLoggingNotEnabled.into());
+                }
+                match effective_canister_id {
+                    Some(canister_id) => {
+                        let canister = state.canister_state(&canister_id).ok_or_else(|| UserError::new(
+                            ErrorCode::CanisterNotFound,
+                            format!("Canister {} not found", canister_id),
+                        ))?;
+                        validate_log_visibility(canister, &sender.get()).map_err(|err| err.into())
+                    },
+                    None => Err(UserError::new(
+                        ErrorCode::InvalidManagementPayload,
+                        format!("Failed to decode payload for ic00 method: {}", method_name),
+                    )),
+                }
+            },
+
             Ok(Ic00Method::ProvisionalCreateCanisterWithCycles)
             | Ok(Ic00Method::BitcoinGetSuccessors)
             | Ok(Ic00Method::ProvisionalTopUpCanister) => {
@@ -482,6 +544,9 @@ impl CanisterManager {
             subnet_compute_allocation_usage,
             self.config.compute_capacity,
             self.config.max_controllers,
+            self.config.max_environment_variables,
+            self.config.max_environment_variable_name_length,
+            self.config.max_environment_variable_value_length,
             self.config.default_freeze_threshold,
             canister_cycles_balance,
             &self.cycles_account_manager,
@@ -544,6 +609,18 @@ impl CanisterManager {
         if let Some(freezing_threshold) = settings.freezing_threshold() {
             canister.system_state.freeze_threshold = freezing_threshold;
         }
+        if let Some(log_visibility) = settings.log_visibility() {
+            canister.system_state.log_visibility = log_visibility;
+        }
+        if let Some(wasm_memory_limit) = settings.wasm_memory_limit() {
+            canister.system_state.wasm_memory_limit = Some(wasm_memory_limit);
+        }
+        if let Some(wasm_memory_threshold) = settings.wasm_memory_threshold() {
+            canister.system_state.wasm_memory_threshold = Some(wasm_memory_threshold);
+        }
+        if let Some(environment_variables) = settings.environment_variables() {
+            canister.system_state.environment_variables = environment_variables.clone();
+        }
     }
 
     /// Tries to apply the requested settings on the canister identified by
@@ -573,6 +650,9 @@ impl CanisterManager {
             round_limits.compute_allocation_used,
             self.config.compute_capacity,
             self.config.max_controllers,
+            self.config.max_environment_variables,
+            self.config.max_environment_variable_name_length,
+            self.config.max_environment_variable_value_length,
             canister.system_state.freeze_threshold,
             canister.system_state.balance(),
             &self.cycles_account_manager,
@@ -583,6 +663,12 @@ impl CanisterManager {
 
         let is_controllers_change =
             validated_settings.controller().is_some() || validated_settings.controllers().is_some();
+        let is_environment_variables_change =
+            validated_settings
+                .environment_variables()
+                .map_or(false, |environment_variables| {
+                    *environment_variables != canister.system_state.environment_variables
+                });
 
         let old_usage = canister.memory_usage();
         let old_mem = canister.memory_allocation().allocated_bytes(old_usage);
@@ -622,10 +708,19 @@ impl CanisterManager {
             let new_controllers = canister.system_state.controllers.iter().copied().collect();
             canister.system_state.add_canister_change(
                 timestamp_nanos,
-                origin,
+                origin.clone(),
                 CanisterChangeDetails::controllers_change(new_controllers),
             );
         }
+        if is_environment_variables_change {
+            canister.system_state.add_canister_change(
+                timestamp_nanos,
+                origin,
+                CanisterChangeDetails::environment_variables_change(hash_environment_variables(
+                    &canister.system_state.environment_variables,
+                )),
+            );
+        }
 
         Ok(())
     }
@@ -933,6 +1028,9 @@ impl CanisterManager {
             AddCanisterChangeToHistory::Yes(origin),
             Arc::clone(&self.fd_factory),
         );
+        // Uninstalling the code also deletes all snapshots of the canister.
+        canister.system_state.snapshots_memory_usage = NumBytes::from(0);
+        state.canister_snapshots.delete_snapshots(canister_id);
         crate::util::process_responses(
             rejects,
             state,
@@ -1095,6 +1193,15 @@ impl CanisterManager {
             Some(memory_allocation.bytes().get()),
             freeze_threshold.get(),
             reserved_cycles_limit.map(|x| x.get()),
+            canister.system_state.log_visibility,
+            canister.system_state.wasm_memory_limit.map(|x| x.get()),
+            canister.system_state.wasm_memory_threshold.map(|x| x.get()),
+            canister
+                .system_state
+                .environment_variables
+                .iter()
+                .map(|(name, value)| EnvironmentVariable::new(name.clone(), value.clone()))
+                .collect(),
             self.cycles_account_manager
                 .idle_cycles_burned_rate(
                     memory_allocation,
@@ -1115,6 +1222,7 @@ impl CanisterManager {
                 .scheduler_state
                 .total_query_stats
                 .egress_payload_size,
+            canister_method_stats(&canister.scheduler_state),
         ))
     }
 
@@ -1159,8 +1267,11 @@ impl CanisterManager {
         // - its state is permanently deleted, and
         // - its cycles are discarded.
 
-        // Take out the canister from `ReplicatedState`.
+        // Take out the canister and its snapshots from `ReplicatedState`.
         let canister_to_delete = state.take_canister_state(&canister_id_to_delete).unwrap();
+        state
+            .canister_snapshots
+            .delete_snapshots(canister_id_to_delete);
         // Leftover cycles in the balance are considered `consumed`.
         let leftover_cycles = NominalCycles::from(canister_to_delete.system_state.balance());
         let consumed_cycles_by_canister_to_delete = leftover_cycles
@@ -1685,6 +1796,427 @@ impl CanisterManager {
             .collect();
         Ok(StoredChunksReply(keys))
     }
+
+    /// Returns the ID of the snapshot `snapshot_id` of the given canister, or
+    /// an error if no such snapshot exists.
+    fn validate_snapshot_exists(
+        &self,
+        state: &ReplicatedState,
+        canister_id: CanisterId,
+        snapshot_id: &[u8],
+    ) -> Result<SnapshotId, CanisterManagerError> {
+        SnapshotId::try_from(snapshot_id)
+            .ok()
+            .filter(|id| {
+                id.canister_id() == canister_id && state.canister_snapshots.get(id).is_some()
+            })
+            .ok_or_else(|| CanisterManagerError::CanisterSnapshotNotFound {
+                canister_id,
+                snapshot_id: snapshot_id.to_vec(),
+            })
+    }
+
+    /// Checks that the canister can afford `bytes` of additional memory,
+    /// bringing its memory usage to `new_memory_usage`, and reserves the
+    /// corresponding cycles and subnet memory.
+    fn reserve_snapshot_memory(
+        &self,
+        canister: &mut CanisterState,
+        bytes: NumBytes,
+        new_memory_usage: NumBytes,
+        round_limits: &mut RoundLimits,
+        subnet_size: usize,
+        resource_saturation: &ResourceSaturation,
+    ) -> Result<(), CanisterManagerError> {
+        match canister.memory_allocation() {
+            MemoryAllocation::Reserved(allocated_bytes) => {
+                if allocated_bytes < new_memory_usage {
+                    return Err(CanisterManagerError::NotEnoughMemoryAllocationGiven {
+                        memory_allocation_given: canister.memory_allocation(),
+                        memory_usage_needed: new_memory_usage,
+                    });
+                }
+            }
+            MemoryAllocation::BestEffort => {
+                let reservation_cycles = self.cycles_account_manager.storage_reservation_cycles(
+                    bytes,
+                    resource_saturation,
+                    subnet_size,
+                );
+
+                // The snapshot must not bump the canister over its freezing
+                // threshold.
+                let threshold = self.cycles_account_manager.freeze_threshold_cycles(
+                    canister.system_state.freeze_threshold,
+                    canister.memory_allocation(),
+                    new_memory_usage,
+                    canister.message_memory_usage(),
+                    canister.compute_allocation(),
+                    subnet_size,
+                    canister.system_state.reserved_balance() + reservation_cycles,
+                );
+                if threshold > canister.system_state.balance() - reservation_cycles {
+                    return Err(CanisterManagerError::InsufficientCyclesInMemoryGrow {
+                        bytes,
+                        available: canister.system_state.balance(),
+                        threshold,
+                    });
+                }
+                round_limits
+                    .subnet_available_memory
+                    .check_available_memory(bytes, NumBytes::from(0), NumBytes::from(0))
+                    .map_err(
+                        |_| CanisterManagerError::SubnetMemoryCapacityOverSubscribed {
+                            requested: bytes,
+                            available: NumBytes::from(
+                                round_limits
+                                    .subnet_available_memory
+                                    .get_execution_memory()
+                                    .max(0) as u64,
+                            ),
+                        },
+                    )?;
+                canister
+                    .system_state
+                    .reserve_cycles(reservation_cycles)
+                    .map_err(|err| match err {
+                        ReservationError::InsufficientCycles {
+                            requested,
+                            available,
+                        } => CanisterManagerError::InsufficientCyclesInMemoryGrow {
+                            bytes,
+                            available,
+                            threshold: requested,
+                        },
+                        ReservationError::ReservedLimitExceed { requested, limit } => {
+                            CanisterManagerError::ReservedCyclesLimitExceededInMemoryGrow {
+                                bytes,
+                                requested,
+                                limit,
+                            }
+                        }
+                    })?;
+                // It's safe to unwrap here because we already checked the
+                // available memory above.
+                round_limits
+                    .subnet_available_memory
+                    .try_decrement(bytes, NumBytes::from(0), NumBytes::from(0))
+                    .expect("Error: Cannot fail to decrement SubnetAvailableMemory after checking for availability");
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes a snapshot of the canister's Wasm module, memories, globals,
+    /// chunk store, certified data and global timer.
+    ///
+    /// If `replace_snapshot` is provided, the given snapshot is deleted once
+    /// the new one has been taken. The snapshot is charged to the memory usage
+    /// of the canister.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn take_canister_snapshot(
+        &self,
+        sender: PrincipalId,
+        canister_id: CanisterId,
+        replace_snapshot: Option<Vec<u8>>,
+        state: &mut ReplicatedState,
+        round_limits: &mut RoundLimits,
+        subnet_size: usize,
+        resource_saturation: &ResourceSaturation,
+    ) -> Result<TakeCanisterSnapshotResult, CanisterManagerError> {
+        if self.config.canister_snapshots == FlagStatus::Disabled {
+            return Err(CanisterManagerError::CanisterSnapshotsNotEnabled);
+        }
+
+        let time = state.time();
+        let canister = self.validate_canister_exists(state, canister_id)?;
+        validate_controller(canister, &sender)?;
+
+        let replace_snapshot = match replace_snapshot {
+            Some(snapshot_id) => {
+                Some(self.validate_snapshot_exists(state, canister_id, &snapshot_id)?)
+            }
+            None => None,
+        };
+        if replace_snapshot.is_none()
+            && state.canister_snapshots.list_snapshots(canister_id).count()
+                >= MAX_SNAPSHOTS_PER_CANISTER
+        {
+            return Err(CanisterManagerError::CanisterSnapshotLimitExceeded {
+                canister_id,
+                limit: MAX_SNAPSHOTS_PER_CANISTER,
+            });
+        }
+
+        if self.config.rate_limiting_of_heap_delta == FlagStatus::Enabled
+            && canister.scheduler_state.heap_delta_debit >= self.config.heap_delta_rate_limit
+        {
+            return Err(CanisterManagerError::CanisterHeapDeltaRateLimited {
+                canister_id,
+                value: canister.scheduler_state.heap_delta_debit,
+                limit: self.config.heap_delta_rate_limit,
+            });
+        }
+
+        let snapshot =
+            CanisterSnapshot::from_canister(canister, time, Arc::clone(&self.fd_factory)).ok_or(
+                CanisterManagerError::CanisterSnapshotExecutionStateNotFound { canister_id },
+            )?;
+        let snapshot_size = snapshot.size();
+        let replaced_snapshot_size = replace_snapshot
+            .and_then(|snapshot_id| state.canister_snapshots.get(&snapshot_id))
+            .map_or(NumBytes::from(0), |snapshot| snapshot.size());
+
+        // The canister was validated to exist above.
+        let canister = state.canister_state_mut(&canister_id).unwrap();
+        if snapshot_size > replaced_snapshot_size {
+            let bytes = snapshot_size - replaced_snapshot_size;
+            let new_memory_usage = canister.memory_usage() + bytes;
+            self.reserve_snapshot_memory(
+                canister,
+                bytes,
+                new_memory_usage,
+                round_limits,
+                subnet_size,
+                resource_saturation,
+            )?;
+        }
+
+        if self.config.rate_limiting_of_heap_delta == FlagStatus::Enabled {
+            canister.scheduler_state.heap_delta_debit += snapshot_size;
+        }
+        canister.system_state.snapshots_memory_usage =
+            canister.system_state.snapshots_memory_usage - replaced_snapshot_size + snapshot_size;
+        let snapshot_id = SnapshotId::new(canister_id, canister.system_state.next_snapshot_id);
+        canister.system_state.next_snapshot_id += 1;
+
+        if let Some(replace_snapshot) = replace_snapshot {
+            state.canister_snapshots.remove(&replace_snapshot);
+        }
+        state.canister_snapshots.push(snapshot_id, snapshot);
+
+        Ok(TakeCanisterSnapshotResult {
+            reply: CanisterSnapshotResponse::new(
+                snapshot_id.to_vec(),
+                time.as_nanos_since_unix_epoch(),
+                snapshot_size,
+            ),
+            heap_delta_increase: snapshot_size,
+        })
+    }
+
+    /// Restores the canister to the state captured by the given snapshot.
+    ///
+    /// The Wasm module of the snapshot is re-instantiated and its memories,
+    /// globals and chunk store replace the current ones of the canister.
+    ///
+    /// Returns the heap delta produced by the restored state.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn load_canister_snapshot(
+        &self,
+        origin: CanisterChangeOrigin,
+        canister_id: CanisterId,
+        snapshot_id: &[u8],
+        state: &mut ReplicatedState,
+        canister_layout_path: PathBuf,
+        round_limits: &mut RoundLimits,
+        subnet_size: usize,
+        resource_saturation: &ResourceSaturation,
+    ) -> Result<NumBytes, CanisterManagerError> {
+        if self.config.canister_snapshots == FlagStatus::Disabled {
+            return Err(CanisterManagerError::CanisterSnapshotsNotEnabled);
+        }
+
+        let sender = origin.origin();
+        let time = state.time();
+        let canister = self.validate_canister_exists(state, canister_id)?;
+        validate_controller(canister, &sender)?;
+        let snapshot_id = self.validate_snapshot_exists(state, canister_id, snapshot_id)?;
+        // The snapshot was validated to exist above.
+        let snapshot = state.canister_snapshots.get(&snapshot_id).unwrap();
+        let execution_snapshot = snapshot.execution_snapshot();
+
+        let (_instructions, result) = self.hypervisor.create_execution_state(
+            execution_snapshot.wasm_binary.clone(),
+            canister_layout(&canister_layout_path, &canister_id).raw_path(),
+            canister_id,
+            round_limits,
+            CompilationCostHandling::CountFullAmount,
+        );
+        let mut execution_state =
+            result.map_err(|err| CanisterManagerError::Hypervisor(canister_id, err))?;
+        let (wasm_memory, stable_memory) = snapshot.restore_memories(Arc::clone(&self.fd_factory));
+        execution_state.wasm_memory = wasm_memory;
+        execution_state.stable_memory = stable_memory;
+        execution_state.exported_globals = execution_snapshot.exported_globals.clone();
+
+        let mut new_canister = canister.clone();
+        new_canister.execution_state = Some(execution_state);
+        new_canister.system_state.wasm_chunk_store =
+            snapshot.restore_chunk_store(Arc::clone(&self.fd_factory));
+        new_canister.system_state.certified_data = snapshot.certified_data().clone();
+        new_canister.system_state.global_timer = snapshot.global_timer();
+        // Named timers are not part of a snapshot.
+        new_canister.system_state.timers.clear();
+        new_canister.system_state.canister_version += 1;
+        new_canister.system_state.add_canister_change(
+            time,
+            origin,
+            CanisterChangeDetails::load_snapshot(
+                snapshot.canister_version(),
+                snapshot_id.to_vec(),
+                snapshot.taken_at_timestamp().as_nanos_since_unix_epoch(),
+            ),
+        );
+        let heap_delta = snapshot.size();
+
+        let old_memory_usage = canister.memory_usage();
+        let new_memory_usage = new_canister.memory_usage();
+        if new_memory_usage > old_memory_usage {
+            self.reserve_snapshot_memory(
+                &mut new_canister,
+                new_memory_usage - old_memory_usage,
+                new_memory_usage,
+                round_limits,
+                subnet_size,
+                resource_saturation,
+            )?;
+        }
+
+        state.put_canister_state(new_canister);
+        Ok(heap_delta)
+    }
+
+    /// Returns the snapshots of the given canister.
+    pub(crate) fn list_canister_snapshots(
+        &self,
+        sender: PrincipalId,
+        canister_id: CanisterId,
+        state: &ReplicatedState,
+    ) -> Result<ListCanisterSnapshotsReply, CanisterManagerError> {
+        if self.config.canister_snapshots == FlagStatus::Disabled {
+            return Err(CanisterManagerError::CanisterSnapshotsNotEnabled);
+        }
+
+        let canister = self.validate_canister_exists(state, canister_id)?;
+        validate_controller(canister, &sender)?;
+
+        let snapshots = state
+            .canister_snapshots
+            .list_snapshots(canister_id)
+            .map(|(snapshot_id, snapshot)| {
+                CanisterSnapshotResponse::new(
+                    snapshot_id.to_vec(),
+                    snapshot.taken_at_timestamp().as_nanos_since_unix_epoch(),
+                    snapshot.size(),
+                )
+            })
+            .collect();
+        Ok(ListCanisterSnapshotsReply(snapshots))
+    }
+
+    /// Deletes the given snapshot and releases the memory charged for it.
+    pub(crate) fn delete_canister_snapshot(
+        &self,
+        sender: PrincipalId,
+        canister_id: CanisterId,
+        snapshot_id: &[u8],
+        state: &mut ReplicatedState,
+    ) -> Result<(), CanisterManagerError> {
+        if self.config.canister_snapshots == FlagStatus::Disabled {
+            return Err(CanisterManagerError::CanisterSnapshotsNotEnabled);
+        }
+
+        let canister = self.validate_canister_exists(state, canister_id)?;
+        validate_controller(canister, &sender)?;
+        let snapshot_id = self.validate_snapshot_exists(state, canister_id, snapshot_id)?;
+
+        // The snapshot and the canister were validated to exist above.
+        let snapshot = state.canister_snapshots.remove(&snapshot_id).unwrap();
+        let canister = state.canister_state_mut(&canister_id).unwrap();
+        canister.system_state.snapshots_memory_usage -= snapshot.size();
+        Ok(())
+    }
+
+    /// Returns the log records of the given canister.
+    ///
+    /// The logs are only visible to the controllers of the canister, unless
+    /// its log visibility is public.
+    pub(crate) fn fetch_canister_logs(
+        &self,
+        sender: PrincipalId,
+        canister_id: CanisterId,
+        state: &ReplicatedState,
+    ) -> Result<FetchCanisterLogsResponse, CanisterManagerError> {
+        if self.config.canister_logging == FlagStatus::Disabled {
+            return Err(CanisterManagerError::CanisterLoggingNotEnabled);
+        }
+
+        let canister = self.validate_canister_exists(state, canister_id)?;
+        validate_log_visibility(canister, &sender)?;
+
+        Ok(FetchCanisterLogsResponse {
+            canister_log_records: canister
+                .system_state
+                .canister_log
+                .records()
+                .iter()
+                .cloned()
+                .collect(),
+        })
+    }
+}
+
+/// Checks that `sender` is allowed to read the logs of `canister`.
+fn validate_log_visibility(
+    canister: &CanisterState,
+    sender: &PrincipalId,
+) -> Result<(), CanisterManagerError> {
+    match canister.system_state.log_visibility {
+        LogVisibility::Public => Ok(()),
+        LogVisibility::Controllers => validate_controller(canister, sender),
+    }
+}
+
+/// Computes the hash of the environment variables recorded in the canister
+/// history: the SHA-256 of the concatenated hashes of each name and value, in
+/// the order of the names.
+fn hash_environment_variables(environment_variables: &BTreeMap<String, String>) -> [u8; 32] {
+    let mut hasher = ic_crypto_sha2::Sha256::new();
+    for (name, value) in environment_variables {
+        hasher.write(&ic_crypto_sha2::Sha256::hash(name.as_bytes()));
+        hasher.write(&ic_crypto_sha2::Sha256::hash(value.as_bytes()));
+    }
+    hasher.finish()
+}
+
+/// Returns the per-method stats of the canister as reported by
+/// `canister_status`: update methods first, then query methods, each ordered by
+/// method name.
+fn canister_method_stats(scheduler_state: &SchedulerState) -> Vec<CanisterMethodStats> {
+    let update_stats = scheduler_state
+        .total_update_method_stats
+        .iter()
+        .map(|stats| (CanisterMethodKind::Update, stats));
+    let query_stats = scheduler_state
+        .total_query_stats
+        .method_stats
+        .iter()
+        .map(|stats| (CanisterMethodKind::Query, stats));
+    update_stats
+        .chain(query_stats)
+        .map(|(kind, (method_name, stats))| {
+            CanisterMethodStats::new(
+                method_name.clone(),
+                kind,
+                stats.num_calls,
+                stats.num_errors,
+                stats.num_instructions,
+                stats.ingress_payload_size,
+                stats.egress_payload_size,
+            )
+        })
+        .collect()
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -1766,6 +2298,27 @@ pub(crate) enum CanisterManagerError {
     WasmChunkStoreError {
         message: String,
     },
+    InvalidUpgradeOption {
+        message: String,
+    },
+    CanisterSnapshotsNotEnabled,
+    CanisterLoggingNotEnabled,
+    CanisterSnapshotNotFound {
+        canister_id: CanisterId,
+        snapshot_id: Vec<u8>,
+    },
+    CanisterSnapshotLimitExceeded {
+        canister_id: CanisterId,
+        limit: usize,
+    },
+    CanisterSnapshotExecutionStateNotFound {
+        canister_id: CanisterId,
+    },
+    CanisterHeapDeltaRateLimited {
+        canister_id: CanisterId,
+        value: NumBytes,
+        limit: NumBytes,
+    },
 }
 
 impl From<CanisterManagerError> for UserError {
@@ -2002,6 +2555,63 @@ impl From<CanisterManagerError> for UserError {
                     )
                 )
             }
+            InvalidUpgradeOption { message } => {
+                Self::new(
+                    ErrorCode::CanisterContractViolation,
+                    format!(
+                        "Invalid upgrade option: {}", message
+                    )
+                )
+            }
+            CanisterSnapshotsNotEnabled => {
+                Self::new(
+                    ErrorCode::CanisterContractViolation,
+                    "Canister snapshotting API is not yet implemented.",
+                )
+            }
+            CanisterLoggingNotEnabled => {
+                Self::new(
+                    ErrorCode::CanisterContractViolation,
+                    "fetch_canister_logs API is not enabled on this subnet.",
+                )
+            }
+            CanisterSnapshotNotFound { canister_id, snapshot_id } => {
+                Self::new(
+                    ErrorCode::CanisterSnapshotNotFound,
+                    format!(
+                        "Could not find the snapshot ID {} for canister {}.",
+                        hex::encode(snapshot_id), canister_id,
+                    )
+                )
+            }
+            CanisterSnapshotLimitExceeded { canister_id, limit } => {
+                Self::new(
+                    ErrorCode::CanisterRejectedMessage,
+                    format!(
+                        "Canister {} has reached the maximum number of snapshots allowed: {}. \
+                         Please provide a snapshot to be replaced.",
+                        canister_id, limit,
+                    )
+                )
+            }
+            CanisterSnapshotExecutionStateNotFound { canister_id } => {
+                Self::new(
+                    ErrorCode::CanisterWasmModuleNotFound,
+                    format!(
+                        "Failed to take a snapshot of canister {}: the canister has no Wasm module installed.",
+                        canister_id,
+                    )
+                )
+            }
+            CanisterHeapDeltaRateLimited { canister_id, value, limit } => {
+                Self::new(
+                    ErrorCode::CanisterHeapDeltaRateLimited,
+                    format!(
+                        "Canister {} is heap delta rate limited: current delta debit is {}, but limit is {}.",
+                        canister_id, value, limit,
+                    )
+                )
+            }
         }
     }
 }
@@ -2041,8 +2651,9 @@ pub fn uninstall_canister(
     // Drop its certified data.
     canister.system_state.certified_data = Vec::new();
 
-    // Deactivate global timer.
+    // Deactivate global timer and all named timers.
     canister.system_state.global_timer = CanisterTimer::Inactive;
+    canister.system_state.timers.clear();
     // Increment canister version.
     canister.system_state.canister_version += 1;
     match add_canister_change {
@@ -2087,7 +2698,7 @@ pub fn uninstall_canister(
                         },
                     }));
                 }
-                CallOrigin::CanisterUpdate(caller_canister_id, callback_id) => {
+                CallOrigin::CanisterUpdate(caller_canister_id, callback_id, deadline) => {
                     rejects.push(Response::Canister(CanisterResponse {
                         originator: *caller_canister_id,
                         respondent: canister_id,
@@ -2097,6 +2708,7 @@ pub fn uninstall_canister(
                             RejectCode::CanisterReject,
                             "Canister has been uninstalled.",
                         )),
+                        deadline: *deadline,
                     }));
                 }
                 CallOrigin::CanisterQuery(_, _) | CallOrigin::Query(_) => fatal!(
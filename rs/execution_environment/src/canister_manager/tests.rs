@@ -1,8 +1,9 @@
 use crate::{
     as_num_instructions,
     canister_manager::{
-        uninstall_canister, AddCanisterChangeToHistory, CanisterManager, CanisterManagerError,
-        CanisterMgrConfig, InstallCodeContext, StopCanisterResult,
+        hash_environment_variables, uninstall_canister, AddCanisterChangeToHistory,
+        CanisterManager, CanisterManagerError, CanisterMgrConfig, InstallCodeContext,
+        StopCanisterResult,
     },
     canister_settings::{CanisterSettings, CanisterSettingsBuilder},
     execution_environment::{as_round_instructions, RoundCounters},
@@ -22,9 +23,12 @@ use ic_embedders::wasm_utils::instrumentation::instruction_to_cost_new;
 use ic_error_types::{ErrorCode, UserError};
 use ic_ic00_types::{
     CanisterChange, CanisterChangeDetails, CanisterChangeOrigin, CanisterIdRecord,
-    CanisterInstallMode, CanisterInstallModeV2, CanisterSettingsArgsBuilder,
-    CanisterStatusResultV2, CanisterStatusType, ClearChunkStoreArgs, CreateCanisterArgs, EmptyBlob,
-    InstallCodeArgsV2, Method, Payload, SkipPreUpgrade, StoredChunksArgs, StoredChunksReply,
+    CanisterInstallMode, CanisterInstallModeV2, CanisterMethodKind, CanisterSettingsArgsBuilder,
+    CanisterSnapshotResponse, CanisterStatusResultV2, CanisterStatusType, CanisterUpgradeOptions,
+    ClearChunkStoreArgs, CreateCanisterArgs, DeleteCanisterSnapshotArgs, EmptyBlob,
+    EnvironmentVariable, FetchCanisterLogsRequest, FetchCanisterLogsResponse, InstallCodeArgsV2,
+    ListCanisterSnapshotArgs, ListCanisterSnapshotsReply, LoadCanisterSnapshotArgs, LogVisibility,
+    Method, Payload, StoredChunksArgs, StoredChunksReply, TakeCanisterSnapshotArgs,
     UpdateSettingsArgs, UploadChunkArgs, UploadChunkReply,
 };
 use ic_interfaces::execution_environment::{ExecutionMode, HypervisorError, SubnetAvailableMemory};
@@ -63,8 +67,9 @@ use ic_test_utilities_execution_environment::{
     wasm_compilation_cost, wat_compilation_cost, ExecutionTest, ExecutionTestBuilder,
 };
 use ic_types::{
+    batch::TotalMethodStats,
     ingress::{IngressState, IngressStatus, WasmResult},
-    messages::{CallbackId, CanisterCall, StopCanisterCallId, StopCanisterContext},
+    messages::{CallbackId, CanisterCall, StopCanisterCallId, StopCanisterContext, NO_DEADLINE},
     nominal_cycles::NominalCycles,
     CanisterId, CanisterTimer, ComputeAllocation, Cycles, MemoryAllocation, NumBytes,
     NumInstructions, SubnetId, Time, UserId,
@@ -82,6 +87,9 @@ const MAX_NUM_INSTRUCTIONS: NumInstructions = NumInstructions::new(5_000_000_000
 const DEFAULT_PROVISIONAL_BALANCE: Cycles = Cycles::new(100_000_000_000_000);
 const MEMORY_CAPACITY: NumBytes = NumBytes::new(8 * 1024 * 1024 * 1024); // 8GiB
 const MAX_CONTROLLERS: usize = 10;
+const MAX_ENVIRONMENT_VARIABLES: usize = 20;
+const MAX_ENVIRONMENT_VARIABLE_NAME_LENGTH: usize = 128;
+const MAX_ENVIRONMENT_VARIABLE_VALUE_LENGTH: usize = 128;
 const WASM_PAGE_SIZE_IN_BYTES: u64 = 64 * 1024; // 64KiB
 const MAX_NUMBER_OF_CANISTERS: u64 = 0;
 // The simplest valid WASM binary: "(module)"
@@ -275,6 +283,9 @@ fn canister_manager_config(
         subnet_id,
         subnet_type,
         MAX_CONTROLLERS,
+        MAX_ENVIRONMENT_VARIABLES,
+        MAX_ENVIRONMENT_VARIABLE_NAME_LENGTH,
+        MAX_ENVIRONMENT_VARIABLE_VALUE_LENGTH,
         // Compute capacity for 2-core scheduler is 100%
         // TODO(RUN-319): the capacity should be defined based on actual `scheduler_cores`
         100,
@@ -285,6 +296,8 @@ fn canister_manager_config(
         // 10 MiB should be enough for all the tests.
         NumBytes::from(10 * 1024 * 1024),
         SchedulerConfig::application_subnet().upload_wasm_chunk_instructions,
+        FlagStatus::Enabled,
+        FlagStatus::Enabled,
     )
 }
 
@@ -1712,6 +1725,7 @@ fn stop_a_running_canister() {
             reply_callback: CallbackId::new(0),
             call_id: Some(StopCanisterCallId::new(0)),
             cycles: Cycles::zero(),
+            deadline: NO_DEADLINE,
         };
         assert_eq!(
             canister_manager.stop_canister(canister_id, stop_context.clone(), &mut state),
@@ -1794,6 +1808,7 @@ fn stop_a_stopped_canister_from_another_canister() {
             reply_callback: CallbackId::from(0),
             call_id: Some(StopCanisterCallId::new(0)),
             cycles: Cycles::from(cycles),
+            deadline: NO_DEADLINE,
         };
         assert_eq!(
             canister_manager.stop_canister(canister_id, stop_context, &mut state),
@@ -6218,7 +6233,10 @@ fn test_upgrade_with_skip_pre_upgrade_preserves_stable_memory() {
     test.upgrade_canister_v2(
         canister_id,
         UNIVERSAL_CANISTER_WASM.to_vec(),
-        Some(SkipPreUpgrade(Some(true))),
+        Some(CanisterUpgradeOptions {
+            skip_pre_upgrade: Some(true),
+            wasm_memory_persistence: None,
+        }),
     )
     .unwrap();
 
@@ -6235,7 +6253,10 @@ fn test_upgrade_with_skip_pre_upgrade_preserves_stable_memory() {
         .upgrade_canister_v2(
             canister_id,
             UNIVERSAL_CANISTER_WASM.to_vec(),
-            Some(SkipPreUpgrade(None)),
+            Some(CanisterUpgradeOptions {
+                skip_pre_upgrade: None,
+                wasm_memory_persistence: None,
+            }),
         )
         .unwrap_err();
     assert_eq!(ErrorCode::CanisterCalledTrap, err.code());
@@ -6739,6 +6760,187 @@ fn canister_status_contains_reserved_cycles_limit() {
     );
 }
 
+#[test]
+fn canister_status_contains_wasm_memory_limit() {
+    const CYCLES: Cycles = Cycles::new(1_000_000_000_000_000);
+
+    let mut test = ExecutionTestBuilder::new().build();
+
+    let canister_id = test.create_canister(CYCLES);
+    let result = test.canister_status(canister_id);
+    let reply = get_reply(result);
+    let status = CanisterStatusResultV2::decode(&reply).unwrap();
+    assert_eq!(status.wasm_memory_limit(), None);
+
+    test.canister_update_wasm_memory_limit(canister_id, NumBytes::new(42))
+        .unwrap();
+
+    let result = test.canister_status(canister_id);
+    let reply = get_reply(result);
+    let status = CanisterStatusResultV2::decode(&reply).unwrap();
+    assert_eq!(status.wasm_memory_limit(), Some(42));
+    assert_eq!(
+        test.canister_state(canister_id)
+            .system_state
+            .wasm_memory_limit,
+        Some(NumBytes::new(42))
+    );
+}
+
+#[test]
+fn canister_status_contains_method_stats() {
+    let mut test = ExecutionTestBuilder::new().build();
+
+    let canister_id = test.universal_canister().unwrap();
+    test.ingress(canister_id, "update", wasm().reply().build())
+        .unwrap();
+    // Query stats only get updated through consensus, so set them directly.
+    test.canister_state_mut(canister_id)
+        .scheduler_state
+        .total_query_stats
+        .method_stats = btreemap! {
+        "query".to_string() => TotalMethodStats {
+            num_calls: 3,
+            num_errors: 1,
+            num_instructions: 300,
+            ingress_payload_size: 30,
+            egress_payload_size: 60,
+        },
+    };
+
+    let result = test.canister_status(canister_id);
+    let reply = get_reply(result);
+    let status = CanisterStatusResultV2::decode(&reply).unwrap();
+    let method_stats = status.method_stats();
+    assert_eq!(method_stats.len(), 2);
+
+    assert_eq!(method_stats[0].method_name(), "update");
+    assert_eq!(method_stats[0].kind(), CanisterMethodKind::Update);
+    assert_eq!(method_stats[0].num_calls(), 1);
+    assert_eq!(method_stats[0].num_errors(), 0);
+    assert!(method_stats[0].num_instructions() > 0);
+
+    assert_eq!(method_stats[1].method_name(), "query");
+    assert_eq!(method_stats[1].kind(), CanisterMethodKind::Query);
+    assert_eq!(method_stats[1].num_calls(), 3);
+    assert_eq!(method_stats[1].num_errors(), 1);
+    assert_eq!(method_stats[1].num_instructions(), 300);
+    assert_eq!(method_stats[1].request_payload_bytes(), 30);
+    assert_eq!(method_stats[1].response_payload_bytes(), 60);
+}
+
+#[test]
+fn update_settings_sets_environment_variables_and_records_history() {
+    const CYCLES: Cycles = Cycles::new(1_000_000_000_000_000);
+
+    let mut test = ExecutionTestBuilder::new().build();
+
+    let canister_id = test.create_canister(CYCLES);
+    let environment_variables = vec![
+        EnvironmentVariable::new(
+            "PEER".to_string(),
+            "rwlgt-iiaaa-aaaaa-aaaaa-cai".to_string(),
+        ),
+        EnvironmentVariable::new("FEATURE_FLAG".to_string(), "enabled".to_string()),
+    ];
+    test.canister_update_environment_variables(canister_id, environment_variables)
+        .unwrap();
+
+    let result = test.canister_status(canister_id);
+    let reply = get_reply(result);
+    let status = CanisterStatusResultV2::decode(&reply).unwrap();
+    // The variables are returned sorted by name.
+    assert_eq!(
+        status.environment_variables(),
+        &[
+            EnvironmentVariable::new("FEATURE_FLAG".to_string(), "enabled".to_string()),
+            EnvironmentVariable::new(
+                "PEER".to_string(),
+                "rwlgt-iiaaa-aaaaa-aaaaa-cai".to_string()
+            ),
+        ]
+    );
+
+    let system_state = &test.canister_state(canister_id).system_state;
+    let expected_hash = hash_environment_variables(&system_state.environment_variables);
+    let history = system_state.get_canister_history();
+    let num_changes = history.get_total_num_changes();
+    assert_eq!(
+        history.get_changes(1).next().unwrap().details(),
+        &CanisterChangeDetails::environment_variables_change(expected_hash)
+    );
+
+    // Setting the same variables again does not add a history entry.
+    test.canister_update_environment_variables(
+        canister_id,
+        vec![
+            EnvironmentVariable::new("FEATURE_FLAG".to_string(), "enabled".to_string()),
+            EnvironmentVariable::new(
+                "PEER".to_string(),
+                "rwlgt-iiaaa-aaaaa-aaaaa-cai".to_string(),
+            ),
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        test.canister_state(canister_id)
+            .system_state
+            .get_canister_history()
+            .get_total_num_changes(),
+        num_changes
+    );
+}
+
+#[test]
+fn update_settings_rejects_invalid_environment_variables() {
+    const CYCLES: Cycles = Cycles::new(1_000_000_000_000_000);
+
+    let mut test = ExecutionTestBuilder::new().build();
+
+    let canister_id = test.create_canister(CYCLES);
+
+    let duplicate = vec![
+        EnvironmentVariable::new("NAME".to_string(), "a".to_string()),
+        EnvironmentVariable::new("NAME".to_string(), "b".to_string()),
+    ];
+    let err = test
+        .canister_update_environment_variables(canister_id, duplicate)
+        .unwrap_err();
+    assert_eq!(err.code(), ErrorCode::CanisterContractViolation);
+
+    let too_many = (0..=MAX_ENVIRONMENT_VARIABLES)
+        .map(|i| EnvironmentVariable::new(format!("NAME_{}", i), String::new()))
+        .collect();
+    let err = test
+        .canister_update_environment_variables(canister_id, too_many)
+        .unwrap_err();
+    assert_eq!(err.code(), ErrorCode::CanisterContractViolation);
+
+    let long_name = vec![EnvironmentVariable::new(
+        "N".repeat(MAX_ENVIRONMENT_VARIABLE_NAME_LENGTH + 1),
+        String::new(),
+    )];
+    let err = test
+        .canister_update_environment_variables(canister_id, long_name)
+        .unwrap_err();
+    assert_eq!(err.code(), ErrorCode::CanisterContractViolation);
+
+    let long_value = vec![EnvironmentVariable::new(
+        "NAME".to_string(),
+        "v".repeat(MAX_ENVIRONMENT_VARIABLE_VALUE_LENGTH + 1),
+    )];
+    let err = test
+        .canister_update_environment_variables(canister_id, long_value)
+        .unwrap_err();
+    assert_eq!(err.code(), ErrorCode::CanisterContractViolation);
+
+    assert!(test
+        .canister_state(canister_id)
+        .system_state
+        .environment_variables
+        .is_empty());
+}
+
 #[test]
 fn upload_chunk_works_from_white_list() {
     const CYCLES: Cycles = Cycles::new(1_000_000_000_000_000);
@@ -7581,3 +7783,559 @@ fn chunk_store_methods_succeed_from_canister_itself() {
         let _result = get_reply(test.ingress(uc, "update", wasm));
     }
 }
+
+fn take_canister_snapshot(
+    test: &mut ExecutionTest,
+    canister_id: CanisterId,
+    replace_snapshot: Option<Vec<u8>>,
+) -> Result<CanisterSnapshotResponse, UserError> {
+    let args = TakeCanisterSnapshotArgs::new(canister_id, replace_snapshot);
+    test.subnet_message(Method::TakeCanisterSnapshot, args.encode())
+        .map(|result| CanisterSnapshotResponse::decode(&get_reply(Ok(result))).unwrap())
+}
+
+fn list_canister_snapshots(
+    test: &mut ExecutionTest,
+    canister_id: CanisterId,
+) -> Vec<CanisterSnapshotResponse> {
+    let args = ListCanisterSnapshotArgs::new(canister_id);
+    let result = test.subnet_message(Method::ListCanisterSnapshots, args.encode());
+    ListCanisterSnapshotsReply::decode(&get_reply(result))
+        .unwrap()
+        .0
+}
+
+#[test]
+fn take_canister_snapshot_works() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_snapshots(FlagStatus::Enabled)
+        .build();
+    let canister_id = test.universal_canister().unwrap();
+    let memory_usage_before = test.canister_state(canister_id).memory_usage();
+
+    let snapshot = take_canister_snapshot(&mut test, canister_id, None).unwrap();
+
+    assert_eq!(
+        snapshot.taken_at_timestamp(),
+        test.state().time().as_nanos_since_unix_epoch()
+    );
+    assert_eq!(
+        list_canister_snapshots(&mut test, canister_id),
+        vec![snapshot.clone()]
+    );
+    assert_eq!(
+        test.canister_state(canister_id)
+            .system_state
+            .snapshots_memory_usage,
+        NumBytes::from(snapshot.total_size())
+    );
+    assert_eq!(
+        test.canister_state(canister_id).memory_usage(),
+        memory_usage_before + NumBytes::from(snapshot.total_size())
+    );
+}
+
+#[test]
+fn take_canister_snapshot_fails_when_limit_is_reached() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_snapshots(FlagStatus::Enabled)
+        .build();
+    let canister_id = test.universal_canister().unwrap();
+
+    let snapshot = take_canister_snapshot(&mut test, canister_id, None).unwrap();
+    let err = take_canister_snapshot(&mut test, canister_id, None).unwrap_err();
+    assert_eq!(err.code(), ErrorCode::CanisterRejectedMessage);
+
+    // Replacing the existing snapshot is allowed.
+    let new_snapshot = take_canister_snapshot(
+        &mut test,
+        canister_id,
+        Some(snapshot.snapshot_id().to_vec()),
+    )
+    .unwrap();
+    assert_ne!(new_snapshot.snapshot_id(), snapshot.snapshot_id());
+    assert_eq!(
+        list_canister_snapshots(&mut test, canister_id),
+        vec![new_snapshot]
+    );
+}
+
+#[test]
+fn take_canister_snapshot_fails_without_wasm_module() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_snapshots(FlagStatus::Enabled)
+        .build();
+    let canister_id = test.create_canister(Cycles::new(1_000_000_000_000));
+
+    let err = take_canister_snapshot(&mut test, canister_id, None).unwrap_err();
+    assert_eq!(err.code(), ErrorCode::CanisterWasmModuleNotFound);
+    assert!(test.state().canister_snapshots.is_empty());
+}
+
+#[test]
+fn load_canister_snapshot_restores_canister_state() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_snapshots(FlagStatus::Enabled)
+        .build();
+    let canister_id = test.universal_canister().unwrap();
+
+    let result = test.ingress(
+        canister_id,
+        "update",
+        wasm().set_global_data(b"before").reply().build(),
+    );
+    get_reply(result);
+    let snapshot = take_canister_snapshot(&mut test, canister_id, None).unwrap();
+    let snapshot_canister_version = test
+        .canister_state(canister_id)
+        .system_state
+        .canister_version;
+
+    let result = test.ingress(
+        canister_id,
+        "update",
+        wasm().set_global_data(b"after").reply().build(),
+    );
+    get_reply(result);
+    let canister_version = test
+        .canister_state(canister_id)
+        .system_state
+        .canister_version;
+
+    let args = LoadCanisterSnapshotArgs::new(canister_id, snapshot.snapshot_id().to_vec(), None);
+    let result = test.subnet_message(Method::LoadCanisterSnapshot, args.encode());
+    assert_eq!(result, Ok(WasmResult::Reply(EmptyBlob.encode())));
+
+    // Loading the snapshot bumps the canister version and is recorded in the
+    // canister history.
+    let system_state = &test.canister_state(canister_id).system_state;
+    assert_eq!(system_state.canister_version, canister_version + 1);
+    assert_eq!(
+        system_state
+            .get_canister_history()
+            .get_changes(1)
+            .next()
+            .unwrap()
+            .as_ref(),
+        &CanisterChange::new(
+            test.state().time().as_nanos_since_unix_epoch(),
+            canister_version + 1,
+            CanisterChangeOrigin::from_user(test.user_id().get()),
+            CanisterChangeDetails::load_snapshot(
+                snapshot_canister_version,
+                snapshot.snapshot_id().to_vec(),
+                snapshot.taken_at_timestamp(),
+            ),
+        )
+    );
+
+    let result = test.ingress(
+        canister_id,
+        "update",
+        wasm().get_global_data().append_and_reply().build(),
+    );
+    assert_eq!(get_reply(result), b"before".to_vec());
+}
+
+#[test]
+fn load_canister_snapshot_reserves_cycles_for_memory_growth() {
+    const CYCLES: Cycles = Cycles::new(1_000_000_000_000_000);
+    const CAPACITY: u64 = 20_000_000_000;
+    const THRESHOLD: u64 = 0;
+
+    let mut test = ExecutionTestBuilder::new()
+        .with_snapshots(FlagStatus::Enabled)
+        .with_subnet_execution_memory(CAPACITY as i64)
+        .with_subnet_memory_reservation(0)
+        .with_subnet_memory_threshold(THRESHOLD as i64)
+        .build();
+    let canister_id = test
+        .create_canister_with_settings(
+            CYCLES,
+            CanisterSettingsArgsBuilder::new()
+                .with_reserved_cycles_limit(CYCLES.get())
+                .build(),
+        )
+        .unwrap();
+    test.install_canister(canister_id, UNIVERSAL_CANISTER_WASM.to_vec())
+        .unwrap();
+
+    // Grow the stable memory, snapshot the canister and reinstall it, dropping
+    // the stable memory.
+    let result = test.ingress(
+        canister_id,
+        "update",
+        wasm().stable_grow(100).reply().build(),
+    );
+    get_reply(result);
+    let snapshot = take_canister_snapshot(&mut test, canister_id, None).unwrap();
+    test.reinstall_canister(canister_id, UNIVERSAL_CANISTER_WASM.to_vec())
+        .unwrap();
+
+    let memory_usage_before = test.canister_state(canister_id).memory_usage();
+    let reserved_balance_before = test
+        .canister_state(canister_id)
+        .system_state
+        .reserved_balance();
+    let subnet_memory_usage =
+        CAPACITY - test.subnet_available_memory().get_execution_memory() as u64;
+
+    let args = LoadCanisterSnapshotArgs::new(canister_id, snapshot.snapshot_id().to_vec(), None);
+    let result = test.subnet_message(Method::LoadCanisterSnapshot, args.encode());
+    assert_eq!(result, Ok(WasmResult::Reply(EmptyBlob.encode())));
+
+    let memory_usage_after = test.canister_state(canister_id).memory_usage();
+    assert!(memory_usage_after > memory_usage_before);
+    let reserved_cycles = test
+        .canister_state(canister_id)
+        .system_state
+        .reserved_balance()
+        - reserved_balance_before;
+    assert!(reserved_cycles > Cycles::zero());
+    assert_eq!(
+        reserved_cycles,
+        test.cycles_account_manager().storage_reservation_cycles(
+            memory_usage_after - memory_usage_before,
+            &ResourceSaturation::new(subnet_memory_usage, THRESHOLD, CAPACITY),
+            test.subnet_size(),
+        )
+    );
+}
+
+#[test]
+fn delete_canister_snapshot_releases_memory() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_snapshots(FlagStatus::Enabled)
+        .build();
+    let canister_id = test.universal_canister().unwrap();
+    let memory_usage_before = test.canister_state(canister_id).memory_usage();
+
+    let snapshot = take_canister_snapshot(&mut test, canister_id, None).unwrap();
+    let args = DeleteCanisterSnapshotArgs::new(canister_id, snapshot.snapshot_id().to_vec());
+    let result = test.subnet_message(Method::DeleteCanisterSnapshot, args.encode());
+    assert_eq!(result, Ok(WasmResult::Reply(EmptyBlob.encode())));
+
+    assert!(list_canister_snapshots(&mut test, canister_id).is_empty());
+    assert_eq!(
+        test.canister_state(canister_id).memory_usage(),
+        memory_usage_before
+    );
+
+    // Deleting the snapshot again fails.
+    let result = test.subnet_message(Method::DeleteCanisterSnapshot, args.encode());
+    assert_eq!(
+        result.unwrap_err().code(),
+        ErrorCode::CanisterSnapshotNotFound
+    );
+}
+
+#[test]
+fn uninstall_code_deletes_canister_snapshots() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_snapshots(FlagStatus::Enabled)
+        .build();
+    let canister_id = test.universal_canister().unwrap();
+    let other_canister_id = test.universal_canister().unwrap();
+    take_canister_snapshot(&mut test, canister_id, None).unwrap();
+    let other_snapshot = take_canister_snapshot(&mut test, other_canister_id, None).unwrap();
+
+    test.uninstall_code(canister_id).unwrap();
+
+    assert!(list_canister_snapshots(&mut test, canister_id).is_empty());
+    assert_eq!(
+        test.canister_state(canister_id)
+            .system_state
+            .snapshots_memory_usage,
+        NumBytes::from(0)
+    );
+    // The snapshots of other canisters are kept.
+    assert_eq!(
+        list_canister_snapshots(&mut test, other_canister_id),
+        vec![other_snapshot]
+    );
+}
+
+#[test]
+fn delete_canister_deletes_canister_snapshots() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_snapshots(FlagStatus::Enabled)
+        .build();
+    let canister_id = test.universal_canister().unwrap();
+    take_canister_snapshot(&mut test, canister_id, None).unwrap();
+
+    test.stop_canister(canister_id);
+    test.process_stopping_canisters();
+    let payload = CanisterIdRecord::from(canister_id).encode();
+    let result = test.subnet_message(Method::DeleteCanister, payload);
+    assert_eq!(result, Ok(WasmResult::Reply(EmptyBlob.encode())));
+
+    assert!(test.state().canister_state(&canister_id).is_none());
+    assert_eq!(
+        test.state()
+            .canister_snapshots
+            .list_snapshots(canister_id)
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn canister_snapshot_methods_fail_from_non_controller() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_snapshots(FlagStatus::Enabled)
+        .build();
+    let canister_id = test.universal_canister().unwrap();
+    let snapshot = take_canister_snapshot(&mut test, canister_id, None).unwrap();
+    test.set_user_id(user_test_id(42));
+
+    let methods = [
+        (
+            Method::TakeCanisterSnapshot,
+            TakeCanisterSnapshotArgs::new(canister_id, None).encode(),
+        ),
+        (
+            Method::LoadCanisterSnapshot,
+            LoadCanisterSnapshotArgs::new(canister_id, snapshot.snapshot_id().to_vec(), None)
+                .encode(),
+        ),
+        (
+            Method::ListCanisterSnapshots,
+            ListCanisterSnapshotArgs::new(canister_id).encode(),
+        ),
+        (
+            Method::DeleteCanisterSnapshot,
+            DeleteCanisterSnapshotArgs::new(canister_id, snapshot.snapshot_id().to_vec()).encode(),
+        ),
+    ];
+
+    for (method, args) in methods {
+        let result = test.subnet_message(method, args);
+        assert_eq!(
+            result.unwrap_err().code(),
+            ErrorCode::CanisterInvalidController
+        );
+    }
+    assert_eq!(test.state().canister_snapshots.len(), 1);
+}
+
+#[test]
+fn delete_canister_deletes_its_snapshots() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_snapshots(FlagStatus::Enabled)
+        .build();
+    let canister_id = test.universal_canister().unwrap();
+    take_canister_snapshot(&mut test, canister_id, None).unwrap();
+
+    test.stop_canister(canister_id);
+    test.process_stopping_canisters();
+    let result = test.subnet_message(
+        Method::DeleteCanister,
+        CanisterIdRecord::from(canister_id).encode(),
+    );
+    assert_eq!(result, Ok(WasmResult::Reply(EmptyBlob.encode())));
+
+    assert!(test.state().canister_snapshots.is_empty());
+}
+
+fn fetch_canister_logs(
+    test: &mut ExecutionTest,
+    canister_id: CanisterId,
+) -> Result<Vec<Vec<u8>>, UserError> {
+    let args = FetchCanisterLogsRequest::new(canister_id);
+    test.subnet_message(Method::FetchCanisterLogs, args.encode())
+        .map(|result| {
+            FetchCanisterLogsResponse::decode(&get_reply(Ok(result)))
+                .unwrap()
+                .canister_log_records
+                .into_iter()
+                .map(|record| record.content)
+                .collect()
+        })
+}
+
+#[test]
+fn fetch_canister_logs_returns_debug_prints_and_traps() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_canister_logging(FlagStatus::Enabled)
+        .build();
+    let canister_id = test.universal_canister().unwrap();
+
+    let payload = wasm().debug_print(b"hello").reply().build();
+    test.ingress(canister_id, "update", payload).unwrap();
+    let payload = wasm()
+        .debug_print(b"before trap")
+        .trap_with_blob(b"boom")
+        .build();
+    test.ingress(canister_id, "update", payload).unwrap_err();
+
+    assert_eq!(
+        fetch_canister_logs(&mut test, canister_id).unwrap(),
+        vec![
+            b"hello".to_vec(),
+            b"before trap".to_vec(),
+            b"[TRAP]: boom".to_vec()
+        ]
+    );
+}
+
+#[test]
+fn fetch_canister_logs_returns_records_of_install_and_upgrade() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_canister_logging(FlagStatus::Enabled)
+        .build();
+    let canister_id = test.create_canister(Cycles::new(1_000_000_000_000));
+
+    let trapping_init = wat::parse_str(
+        r#"(module
+            (import "ic0" "debug_print" (func $debug_print (param i32 i32)))
+            (import "ic0" "trap" (func $trap (param i32 i32)))
+            (func (export "canister_init")
+                (call $debug_print (i32.const 0) (i32.const 4))
+                (call $trap (i32.const 4) (i32.const 4)))
+            (memory 1)
+            (data (i32.const 0) "initboom"))"#,
+    )
+    .unwrap();
+    test.install_canister(canister_id, trapping_init)
+        .unwrap_err();
+    assert_eq!(
+        fetch_canister_logs(&mut test, canister_id).unwrap(),
+        vec![b"init".to_vec(), b"[TRAP]: boom".to_vec()]
+    );
+
+    let wasm = wat::parse_str(
+        r#"(module
+            (import "ic0" "debug_print" (func $debug_print (param i32 i32)))
+            (func (export "canister_init")
+                (call $debug_print (i32.const 0) (i32.const 4)))
+            (func (export "canister_pre_upgrade")
+                (call $debug_print (i32.const 4) (i32.const 3)))
+            (func (export "canister_post_upgrade")
+                (call $debug_print (i32.const 7) (i32.const 4)))
+            (memory 1)
+            (data (i32.const 0) "initprepost"))"#,
+    )
+    .unwrap();
+    test.install_canister(canister_id, wasm.clone()).unwrap();
+    test.upgrade_canister(canister_id, wasm).unwrap();
+    assert_eq!(
+        fetch_canister_logs(&mut test, canister_id).unwrap(),
+        vec![
+            b"init".to_vec(),
+            b"[TRAP]: boom".to_vec(),
+            b"init".to_vec(),
+            b"pre".to_vec(),
+            b"post".to_vec()
+        ]
+    );
+}
+
+#[test]
+fn fetch_canister_logs_returns_records_of_dts_upgrade() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_canister_logging(FlagStatus::Enabled)
+        .with_deterministic_time_slicing()
+        .with_install_code_slice_instruction_limit(10_000)
+        .with_install_code_instruction_limit(1_000_000)
+        .with_cost_to_compile_wasm_instruction(0)
+        .build();
+
+    // `canister_post_upgrade` runs for multiple slices, so the record of
+    // `canister_pre_upgrade` is kept across pausing and resuming the upgrade.
+    let wasm = wat::parse_str(
+        r#"(module
+            (import "ic0" "debug_print" (func $debug_print (param i32 i32)))
+            (import "ic0" "performance_counter"
+                (func $performance_counter (param i32) (result i64)))
+            (func (export "canister_pre_upgrade")
+                (call $debug_print (i32.const 0) (i32.const 3)))
+            (func (export "canister_post_upgrade")
+                (local $limit i64)
+                (local.set $limit
+                    (i64.add (call $performance_counter (i32.const 0)) (i64.const 30000)))
+                (loop $loop
+                    (if (i64.lt_s
+                            (call $performance_counter (i32.const 0))
+                            (local.get $limit))
+                        (then
+                            (memory.fill (i32.const 0) (i32.const 0) (i32.const 100))
+                            (br $loop))))
+                (call $debug_print (i32.const 3) (i32.const 4)))
+            (memory 1)
+            (data (i32.const 0) "prepost"))"#,
+    )
+    .unwrap();
+    let canister_id = test.canister_from_binary(wasm.clone()).unwrap();
+
+    let message_id = test.dts_upgrade_canister(canister_id, wasm);
+    assert_eq!(test.ingress_state(&message_id), IngressState::Processing);
+    while test.ingress_state(&message_id) == IngressState::Processing {
+        test.execute_slice(canister_id);
+    }
+    assert_eq!(
+        test.ingress_state(&message_id),
+        IngressState::Completed(WasmResult::Reply(EmptyBlob.encode()))
+    );
+    assert_eq!(
+        fetch_canister_logs(&mut test, canister_id).unwrap(),
+        vec![b"pre".to_vec(), b"post".to_vec()]
+    );
+}
+
+#[test]
+fn fetch_canister_logs_respects_log_visibility() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_canister_logging(FlagStatus::Enabled)
+        .build();
+    let canister_id = test.universal_canister().unwrap();
+    let controller = test.user_id();
+
+    test.set_user_id(user_test_id(42));
+    assert_eq!(
+        fetch_canister_logs(&mut test, canister_id)
+            .unwrap_err()
+            .code(),
+        ErrorCode::CanisterInvalidController
+    );
+
+    test.set_user_id(controller);
+    let payload = UpdateSettingsArgs {
+        canister_id: canister_id.into(),
+        settings: CanisterSettingsArgsBuilder::new()
+            .with_log_visibility(LogVisibility::Public)
+            .build(),
+        sender_canister_version: None,
+    };
+    test.subnet_message(Method::UpdateSettings, payload.encode())
+        .unwrap();
+    assert_eq!(
+        test.canister_state(canister_id).system_state.log_visibility,
+        LogVisibility::Public
+    );
+
+    test.set_user_id(user_test_id(42));
+    assert_eq!(fetch_canister_logs(&mut test, canister_id), Ok(vec![]));
+}
+
+#[test]
+fn fetch_canister_logs_fails_when_logging_is_disabled() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_canister_logging(FlagStatus::Disabled)
+        .build();
+    let canister_id = test.universal_canister().unwrap();
+
+    let payload = wasm().debug_print(b"hello").reply().build();
+    test.ingress(canister_id, "update", payload).unwrap();
+
+    assert_eq!(
+        fetch_canister_logs(&mut test, canister_id)
+            .unwrap_err()
+            .code(),
+        ErrorCode::CanisterContractViolation
+    );
+    assert!(test
+        .canister_state(canister_id)
+        .system_state
+        .canister_log
+        .is_empty());
+}
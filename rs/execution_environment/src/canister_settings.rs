@@ -1,13 +1,14 @@
 use ic_base_types::{NumBytes, NumSeconds};
 use ic_cycles_account_manager::{CyclesAccountManager, ResourceSaturation};
 use ic_error_types::{ErrorCode, UserError};
-use ic_ic00_types::CanisterSettingsArgs;
+use ic_ic00_types::{CanisterSettingsArgs, EnvironmentVariable, LogVisibility};
 use ic_interfaces::execution_environment::SubnetAvailableMemory;
 use ic_types::{
     ComputeAllocation, Cycles, InvalidComputeAllocationError, InvalidMemoryAllocationError,
     MemoryAllocation, PrincipalId,
 };
 use num_traits::cast::ToPrimitive;
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 
 use crate::canister_manager::CanisterManagerError;
@@ -21,9 +22,14 @@ pub(crate) struct CanisterSettings {
     pub(crate) memory_allocation: Option<MemoryAllocation>,
     pub(crate) freezing_threshold: Option<NumSeconds>,
     pub(crate) reserved_cycles_limit: Option<Cycles>,
+    pub(crate) log_visibility: Option<LogVisibility>,
+    pub(crate) wasm_memory_limit: Option<NumBytes>,
+    pub(crate) wasm_memory_threshold: Option<NumBytes>,
+    pub(crate) environment_variables: Option<BTreeMap<String, String>>,
 }
 
 impl CanisterSettings {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         controller: Option<PrincipalId>,
         controllers: Option<Vec<PrincipalId>>,
@@ -31,6 +37,10 @@ impl CanisterSettings {
         memory_allocation: Option<MemoryAllocation>,
         freezing_threshold: Option<NumSeconds>,
         reserved_cycles_limit: Option<Cycles>,
+        log_visibility: Option<LogVisibility>,
+        wasm_memory_limit: Option<NumBytes>,
+        wasm_memory_threshold: Option<NumBytes>,
+        environment_variables: Option<BTreeMap<String, String>>,
     ) -> Self {
         Self {
             controller,
@@ -39,6 +49,10 @@ impl CanisterSettings {
             memory_allocation,
             freezing_threshold,
             reserved_cycles_limit,
+            log_visibility,
+            wasm_memory_limit,
+            wasm_memory_threshold,
+            environment_variables,
         }
     }
 
@@ -65,6 +79,22 @@ impl CanisterSettings {
     pub fn reserved_cycles_limit(&self) -> Option<Cycles> {
         self.reserved_cycles_limit
     }
+
+    pub fn log_visibility(&self) -> Option<LogVisibility> {
+        self.log_visibility
+    }
+
+    pub fn wasm_memory_limit(&self) -> Option<NumBytes> {
+        self.wasm_memory_limit
+    }
+
+    pub fn wasm_memory_threshold(&self) -> Option<NumBytes> {
+        self.wasm_memory_threshold
+    }
+
+    pub fn environment_variables(&self) -> Option<&BTreeMap<String, String>> {
+        self.environment_variables.as_ref()
+    }
 }
 
 impl TryFrom<CanisterSettingsArgs> for CanisterSettings {
@@ -102,6 +132,36 @@ impl TryFrom<CanisterSettingsArgs> for CanisterSettings {
             None => None,
         };
 
+        let wasm_memory_limit = match input.wasm_memory_limit {
+            Some(limit) => Some(NumBytes::from(limit.0.to_u64().ok_or(
+                UpdateSettingsError::WasmMemoryLimitOutOfRange { provided: limit },
+            )?)),
+            None => None,
+        };
+
+        let wasm_memory_threshold = match input.wasm_memory_threshold {
+            Some(threshold) => Some(NumBytes::from(threshold.0.to_u64().ok_or(
+                UpdateSettingsError::WasmMemoryThresholdOutOfRange {
+                    provided: threshold,
+                },
+            )?)),
+            None => None,
+        };
+
+        let environment_variables = match input.environment_variables {
+            Some(variables) => {
+                let mut environment_variables = BTreeMap::new();
+                for EnvironmentVariable { name, value } in variables {
+                    if environment_variables.contains_key(&name) {
+                        return Err(UpdateSettingsError::DuplicateEnvironmentVariable { name });
+                    }
+                    environment_variables.insert(name, value);
+                }
+                Some(environment_variables)
+            }
+            None => None,
+        };
+
         Ok(CanisterSettings::new(
             controller,
             input
@@ -111,6 +171,10 @@ impl TryFrom<CanisterSettingsArgs> for CanisterSettings {
             memory_allocation,
             freezing_threshold,
             reserved_cycles_limit,
+            input.log_visibility,
+            wasm_memory_limit,
+            wasm_memory_threshold,
+            environment_variables,
         ))
     }
 }
@@ -133,6 +197,10 @@ pub(crate) struct CanisterSettingsBuilder {
     memory_allocation: Option<MemoryAllocation>,
     freezing_threshold: Option<NumSeconds>,
     reserved_cycles_limit: Option<Cycles>,
+    log_visibility: Option<LogVisibility>,
+    wasm_memory_limit: Option<NumBytes>,
+    wasm_memory_threshold: Option<NumBytes>,
+    environment_variables: Option<BTreeMap<String, String>>,
 }
 
 #[allow(dead_code)]
@@ -145,6 +213,10 @@ impl CanisterSettingsBuilder {
             memory_allocation: None,
             freezing_threshold: None,
             reserved_cycles_limit: None,
+            log_visibility: None,
+            wasm_memory_limit: None,
+            wasm_memory_threshold: None,
+            environment_variables: None,
         }
     }
 
@@ -156,6 +228,10 @@ impl CanisterSettingsBuilder {
             memory_allocation: self.memory_allocation,
             freezing_threshold: self.freezing_threshold,
             reserved_cycles_limit: self.reserved_cycles_limit,
+            log_visibility: self.log_visibility,
+            wasm_memory_limit: self.wasm_memory_limit,
+            wasm_memory_threshold: self.wasm_memory_threshold,
+            environment_variables: self.environment_variables,
         }
     }
 
@@ -200,6 +276,37 @@ impl CanisterSettingsBuilder {
             ..self
         }
     }
+
+    pub fn with_log_visibility(self, log_visibility: LogVisibility) -> Self {
+        Self {
+            log_visibility: Some(log_visibility),
+            ..self
+        }
+    }
+
+    pub fn with_wasm_memory_limit(self, wasm_memory_limit: NumBytes) -> Self {
+        Self {
+            wasm_memory_limit: Some(wasm_memory_limit),
+            ..self
+        }
+    }
+
+    pub fn with_wasm_memory_threshold(self, wasm_memory_threshold: NumBytes) -> Self {
+        Self {
+            wasm_memory_threshold: Some(wasm_memory_threshold),
+            ..self
+        }
+    }
+
+    pub fn with_environment_variables(
+        self,
+        environment_variables: BTreeMap<String, String>,
+    ) -> Self {
+        Self {
+            environment_variables: Some(environment_variables),
+            ..self
+        }
+    }
 }
 
 pub enum UpdateSettingsError {
@@ -207,6 +314,9 @@ pub enum UpdateSettingsError {
     MemoryAllocation(InvalidMemoryAllocationError),
     FreezingThresholdOutOfRange { provided: candid::Nat },
     ReservedCyclesLimitOutOfRange { provided: candid::Nat },
+    WasmMemoryLimitOutOfRange { provided: candid::Nat },
+    WasmMemoryThresholdOutOfRange { provided: candid::Nat },
+    DuplicateEnvironmentVariable { name: String },
 }
 
 impl From<UpdateSettingsError> for UserError {
@@ -242,6 +352,24 @@ impl From<UpdateSettingsError> for UserError {
                     provided
                 ),
             ),
+            UpdateSettingsError::WasmMemoryLimitOutOfRange { provided } => UserError::new(
+                ErrorCode::CanisterContractViolation,
+                format!(
+                    "Wasm memory limit expected to be in the range of [0..2^64-1], got {}",
+                    provided
+                ),
+            ),
+            UpdateSettingsError::WasmMemoryThresholdOutOfRange { provided } => UserError::new(
+                ErrorCode::CanisterContractViolation,
+                format!(
+                    "Wasm memory threshold expected to be in the range of [0..2^64-1], got {}",
+                    provided
+                ),
+            ),
+            UpdateSettingsError::DuplicateEnvironmentVariable { name } => UserError::new(
+                ErrorCode::CanisterContractViolation,
+                format!("Environment variable {} is specified more than once", name),
+            ),
         }
     }
 }
@@ -266,6 +394,10 @@ pub(crate) struct ValidatedCanisterSettings {
     freezing_threshold: Option<NumSeconds>,
     reserved_cycles_limit: Option<Cycles>,
     reservation_cycles: Cycles,
+    log_visibility: Option<LogVisibility>,
+    wasm_memory_limit: Option<NumBytes>,
+    wasm_memory_threshold: Option<NumBytes>,
+    environment_variables: Option<BTreeMap<String, String>>,
 }
 
 impl ValidatedCanisterSettings {
@@ -296,6 +428,22 @@ impl ValidatedCanisterSettings {
     pub fn reservation_cycles(&self) -> Cycles {
         self.reservation_cycles
     }
+
+    pub fn log_visibility(&self) -> Option<LogVisibility> {
+        self.log_visibility
+    }
+
+    pub fn wasm_memory_limit(&self) -> Option<NumBytes> {
+        self.wasm_memory_limit
+    }
+
+    pub fn wasm_memory_threshold(&self) -> Option<NumBytes> {
+        self.wasm_memory_threshold
+    }
+
+    pub fn environment_variables(&self) -> Option<&BTreeMap<String, String>> {
+        self.environment_variables.as_ref()
+    }
 }
 
 /// Validates the new canisters settings:
@@ -309,6 +457,9 @@ impl ValidatedCanisterSettings {
 ///     - there must be enough cycles to avoid freezing the canister.
 /// - controllers:
 ///     - the number of controllers cannot exceed the given maximum.
+/// - environment variables:
+///     - the number of variables and the lengths of their names and values
+///       cannot exceed the given maximums.
 /// Keep this function in sync with `do_update_settings()`.
 pub(crate) fn validate_canister_settings(
     settings: CanisterSettings,
@@ -321,6 +472,9 @@ pub(crate) fn validate_canister_settings(
     subnet_compute_allocation_usage: u64,
     subnet_compute_allocation_capacity: u64,
     max_controllers: usize,
+    max_environment_variables: usize,
+    max_environment_variable_name_length: usize,
+    max_environment_variable_value_length: usize,
     canister_freezing_threshold: NumSeconds,
     canister_cycles_balance: Cycles,
     cycles_account_manager: &CyclesAccountManager,
@@ -399,6 +553,36 @@ pub(crate) fn validate_canister_settings(
         None => {}
     }
 
+    if let Some(environment_variables) = settings.environment_variables() {
+        if environment_variables.len() > max_environment_variables {
+            return Err(CanisterManagerError::InvalidSettings {
+                message: format!(
+                    "Invalid settings: at most {} environment variables are allowed, got {}",
+                    max_environment_variables,
+                    environment_variables.len()
+                ),
+            });
+        }
+        for (name, value) in environment_variables {
+            if name.len() > max_environment_variable_name_length {
+                return Err(CanisterManagerError::InvalidSettings {
+                    message: format!(
+                        "Invalid settings: environment variable name {} exceeds the maximum length of {} bytes",
+                        name, max_environment_variable_name_length
+                    ),
+                });
+            }
+            if value.len() > max_environment_variable_value_length {
+                return Err(CanisterManagerError::InvalidSettings {
+                    message: format!(
+                        "Invalid settings: value of environment variable {} exceeds the maximum length of {} bytes",
+                        name, max_environment_variable_value_length
+                    ),
+                });
+            }
+        }
+    }
+
     let new_memory_allocation = settings
         .memory_allocation
         .unwrap_or(canister_memory_allocation);
@@ -491,5 +675,9 @@ pub(crate) fn validate_canister_settings(
         freezing_threshold: settings.freezing_threshold(),
         reserved_cycles_limit: settings.reserved_cycles_limit(),
         reservation_cycles,
+        log_visibility: settings.log_visibility(),
+        wasm_memory_limit: settings.wasm_memory_limit(),
+        wasm_memory_threshold: settings.wasm_memory_threshold(),
+        environment_variables: settings.environment_variables,
     })
 }
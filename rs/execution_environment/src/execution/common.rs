@@ -25,7 +25,7 @@ use ic_types::messages::{
     Response,
 };
 use ic_types::methods::{Callback, WasmMethod};
-use ic_types::{Cycles, NumInstructions, Time, UserId};
+use ic_types::{CoarseTime, Cycles, NumInstructions, Time, UserId};
 
 use crate::execution_environment::ExecutionResponse;
 use crate::{as_round_instructions, ExecuteMessageResult, RoundLimits};
@@ -73,15 +73,16 @@ pub(crate) fn action_to_response(
             log,
             ingress_with_cycles_error,
         ),
-        CallOrigin::CanisterUpdate(caller_canister_id, callback_id) => {
-            action_to_request_response(canister, action, caller_canister_id, callback_id)
+        CallOrigin::CanisterUpdate(caller_canister_id, callback_id, deadline) => {
+            action_to_request_response(canister, action, caller_canister_id, callback_id, deadline)
         }
         CallOrigin::CanisterQuery(_, _) | CallOrigin::Query(_) => fatal!(
             log,
             "The update path should not have created a callback with a query origin",
         ),
         CallOrigin::SystemTask => {
-            // System task is either a Heartbeat or a GlobalTimer.
+            // System task is either a Heartbeat, a GlobalTimer or an
+            // OnLowWasmMemory hook.
             // Since system tasks are invoked by the system as opposed
             // to a principal, they cannot respond since there's no one to
             // respond to. Do nothing.
@@ -95,6 +96,7 @@ pub(crate) fn action_to_request_response(
     action: CallContextAction,
     originator: CanisterId,
     reply_callback_id: CallbackId,
+    deadline: CoarseTime,
 ) -> ExecutionResponse {
     let response_payload_and_refund = match action {
         CallContextAction::NotYetResponded | CallContextAction::AlreadyResponded => None,
@@ -126,6 +128,7 @@ pub(crate) fn action_to_request_response(
             originator_reply_callback: reply_callback_id,
             refund,
             response_payload,
+            deadline,
         })
     } else {
         ExecutionResponse::Empty
@@ -239,13 +242,14 @@ pub(crate) fn wasm_result_to_query_response(
         CallOrigin::Ingress(user_id, message_id) => {
             wasm_result_to_ingress_response(result, canister, user_id, message_id, time)
         }
-        CallOrigin::CanisterUpdate(caller_canister_id, callback_id) => {
+        CallOrigin::CanisterUpdate(caller_canister_id, callback_id, deadline) => {
             let response = Response {
                 originator: caller_canister_id,
                 respondent: canister.canister_id(),
                 originator_reply_callback: callback_id,
                 refund,
                 response_payload: Payload::from(result),
+                deadline,
             };
             ExecutionResponse::Request(response)
         }
@@ -253,7 +257,8 @@ pub(crate) fn wasm_result_to_query_response(
             fatal!(log, "The update path should not have a query origin",)
         }
         CallOrigin::SystemTask => {
-            // System task is either a Heartbeat or a GlobalTimer.
+            // System task is either a Heartbeat, a GlobalTimer or an
+            // OnLowWasmMemory hook.
             // Since system tasks are invoked by the system as opposed
             // to a principal, they cannot respond since there's no one to
             // respond to. Do nothing.
@@ -505,6 +510,10 @@ pub fn apply_canister_state_changes(
             }
         }
     }
+    // The log records are kept regardless of whether the execution succeeded.
+    system_state
+        .canister_log
+        .append_delta_log(&mut output.canister_log);
 }
 
 pub(crate) fn finish_call_with_error(
@@ -524,6 +533,7 @@ pub(crate) fn finish_call_with_error(
                 originator_reply_callback: request.sender_reply_callback,
                 refund: request.payment,
                 response_payload: Payload::from(Err(user_error)),
+                deadline: request.deadline,
             };
             ExecutionResponse::Request(response)
         }
@@ -575,7 +585,7 @@ mod test {
     use ic_logger::LoggerImpl;
     use ic_logger::ReplicaLogger;
     use ic_replicated_state::{CanisterState, SchedulerState, SystemState};
-    use ic_types::messages::CallbackId;
+    use ic_types::messages::{CallbackId, NO_DEADLINE};
     use ic_types::Cycles;
     use ic_types::Time;
 
@@ -602,6 +612,7 @@ mod test {
             ic_replicated_state::CallOrigin::CanisterUpdate(
                 CanisterId::from(123u64),
                 CallbackId::new(2),
+                NO_DEADLINE,
             ),
             &log,
             Cycles::from(1000u128),
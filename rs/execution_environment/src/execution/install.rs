@@ -9,8 +9,8 @@ use crate::canister_manager::{
 };
 use crate::execution::common::{ingress_status_with_processing_state, update_round_limits};
 use crate::execution::install_code::{
-    canister_layout, finish_err, InstallCodeHelper, OriginalContext, PausedInstallCodeHelper,
-    StableMemoryHandling,
+    canister_layout, finish_err, InstallCodeHelper, MainMemoryHandling, OriginalContext,
+    PausedInstallCodeHelper, StableMemoryHandling,
 };
 use crate::execution_environment::{RoundContext, RoundLimits};
 use ic_base_types::PrincipalId;
@@ -85,7 +85,14 @@ pub(crate) fn execute_install(
     // Stage 0: validate input.
     if let Err(err) = helper.validate_input(&original, &round, round_limits) {
         let instructions_left = helper.instructions_left();
-        return finish_err(clean_canister, instructions_left, original, round, err);
+        return finish_err(
+            clean_canister,
+            instructions_left,
+            original,
+            round,
+            err,
+            helper.take_canister_log(),
+        );
     }
 
     // Stage 1: create a new execution state based on the new Wasm binary, clear certified data, deactivate global timer, and bump canister version.
@@ -104,10 +111,18 @@ pub(crate) fn execute_install(
         instructions_from_compilation,
         result,
         StableMemoryHandling::Replace,
+        MainMemoryHandling::Replace,
         &original,
     ) {
         let instructions_left = helper.instructions_left();
-        return finish_err(clean_canister, instructions_left, original, round, err);
+        return finish_err(
+            clean_canister,
+            instructions_left,
+            original,
+            round,
+            err,
+            helper.take_canister_log(),
+        );
     }
     helper.clear_certified_data();
     helper.deactivate_global_timer();
@@ -211,7 +226,14 @@ fn install_stage_2a_process_start_result(
 
     if let Err(err) = result {
         let instructions_left = helper.instructions_left();
-        return finish_err(clean_canister, instructions_left, original, round, err);
+        return finish_err(
+            clean_canister,
+            instructions_left,
+            original,
+            round,
+            err,
+            helper.take_canister_log(),
+        );
     }
 
     install_stage_2b_continue_install_after_start(
@@ -314,7 +336,14 @@ fn install_stage_3_process_init_result(
     );
     if let Err(err) = result {
         let instructions_left = helper.instructions_left();
-        return finish_err(clean_canister, instructions_left, original, round, err);
+        return finish_err(
+            clean_canister,
+            instructions_left,
+            original,
+            round,
+            err,
+            helper.take_canister_log(),
+        );
     }
     helper.finish(clean_canister, original, round, round_limits)
 }
@@ -348,7 +377,7 @@ impl PausedInstallCodeExecution for PausedInitExecution {
             round_limits,
         ) {
             Ok(helper) => helper,
-            Err((err, instructions_left)) => {
+            Err((err, instructions_left, canister_log)) => {
                 warn!(
                     round.log,
                     "[DTS] Canister {} failed to resume paused (canister_init) execution: {:?}.",
@@ -356,7 +385,14 @@ impl PausedInstallCodeExecution for PausedInitExecution {
                     err
                 );
                 self.paused_wasm_execution.abort();
-                return finish_err(clean_canister, instructions_left, self.original, round, err);
+                return finish_err(
+                    clean_canister,
+                    instructions_left,
+                    self.original,
+                    round,
+                    err,
+                    canister_log,
+                );
             }
         };
 
@@ -444,7 +480,7 @@ impl PausedInstallCodeExecution for PausedStartExecutionDuringInstall {
             round_limits,
         ) {
             Ok(helper) => helper,
-            Err((err, instructions_left)) => {
+            Err((err, instructions_left, canister_log)) => {
                 warn!(
                     round.log,
                     "[DTS] Canister {} failed to resume paused (start) execution: {:?}",
@@ -452,7 +488,14 @@ impl PausedInstallCodeExecution for PausedStartExecutionDuringInstall {
                     err
                 );
                 self.paused_wasm_execution.abort();
-                return finish_err(clean_canister, instructions_left, self.original, round, err);
+                return finish_err(
+                    clean_canister,
+                    instructions_left,
+                    self.original,
+                    round,
+                    err,
+                    canister_log,
+                );
             }
         };
         let execution_state = helper.canister().execution_state.as_ref().unwrap();
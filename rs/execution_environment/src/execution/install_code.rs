@@ -13,13 +13,13 @@ use ic_interfaces::execution_environment::{
 use ic_logger::{error, fatal, info, warn};
 use ic_replicated_state::canister_state::system_state::ReservationError;
 use ic_replicated_state::metadata_state::subnet_call_context_manager::InstallCodeCallId;
-use ic_replicated_state::{CanisterState, ExecutionState};
+use ic_replicated_state::{CanisterState, ExecutionState, Memory};
 use ic_state_layout::{CanisterLayout, CheckpointLayout, ReadOnly};
 use ic_sys::PAGE_SIZE;
 use ic_system_api::ExecutionParameters;
 use ic_types::{
-    funds::Cycles, messages::CanisterCall, CanisterTimer, ComputeAllocation, Height,
-    MemoryAllocation, NumInstructions, Time,
+    canister_log::CanisterLog, funds::Cycles, messages::CanisterCall, CanisterTimer,
+    ComputeAllocation, Height, MemoryAllocation, NumInstructions, Time,
 };
 use ic_wasm_types::WasmHash;
 
@@ -43,6 +43,14 @@ pub(crate) enum StableMemoryHandling {
     Replace,
 }
 
+/// Indicates whether to keep the old Wasm memory or replace it with the new
+/// (initial) Wasm memory of the module.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum MainMemoryHandling {
+    Keep,
+    Replace,
+}
+
 /// The main steps of `install_code` execution that may fail with an error or
 /// change the canister state.
 #[derive(Clone, Debug)]
@@ -53,6 +61,7 @@ pub(crate) enum InstallCodeStep {
         instructions_from_compilation: NumInstructions,
         maybe_execution_state: HypervisorResult<ExecutionState>,
         stable_memory_handling: StableMemoryHandling,
+        main_memory_handling: MainMemoryHandling,
     },
     ClearCertifiedData,
     DeactivateGlobalTimer,
@@ -75,6 +84,7 @@ pub(crate) enum InstallCodeStep {
 pub(crate) struct PausedInstallCodeHelper {
     steps: Vec<InstallCodeStep>,
     instructions_left: NumInstructions,
+    canister_log: CanisterLog,
 }
 
 /// A helper that implements and keeps track of `install_code` steps.
@@ -96,6 +106,8 @@ pub(crate) struct InstallCodeHelper {
     deallocated_wasm_custom_sections_bytes: NumBytes,
     // The total heap delta of all steps.
     total_heap_delta: NumBytes,
+    // The log records produced by the Wasm executions of all steps.
+    canister_log: CanisterLog,
 }
 
 impl InstallCodeHelper {
@@ -111,6 +123,7 @@ impl InstallCodeHelper {
             deallocated_bytes: NumBytes::from(0),
             deallocated_wasm_custom_sections_bytes: NumBytes::from(0),
             total_heap_delta: NumBytes::from(0),
+            canister_log: CanisterLog::default(),
         }
     }
 
@@ -126,6 +139,7 @@ impl InstallCodeHelper {
     pub fn deactivate_global_timer(&mut self) {
         self.steps.push(InstallCodeStep::DeactivateGlobalTimer);
         self.canister.system_state.global_timer = CanisterTimer::Inactive;
+        self.canister.system_state.timers.clear();
     }
 
     pub fn bump_canister_version(&mut self) {
@@ -178,27 +192,31 @@ impl InstallCodeHelper {
         PausedInstallCodeHelper {
             instructions_left: self.instructions_left(),
             steps: self.steps,
+            canister_log: self.canister_log,
         }
     }
 
     /// Replays the previous `install_code` steps on the given clean canister.
-    /// Returns an error if any step fails. Otherwise, it returns an instance of
-    /// the helper that can be used to continue the `install_code` execution.
+    /// Returns an error (along with the log records produced so far) if any
+    /// step fails. Otherwise, it returns an instance of the helper that can be
+    /// used to continue the `install_code` execution.
     pub fn resume(
         clean_canister: &CanisterState,
         paused: PausedInstallCodeHelper,
         original: &OriginalContext,
         round: &RoundContext,
         round_limits: &RoundLimits,
-    ) -> Result<Self, (CanisterManagerError, NumInstructions)> {
+    ) -> Result<Self, (CanisterManagerError, NumInstructions, CanisterLog)> {
         let mut helper = Self::new(clean_canister, original);
         let paused_instructions_left = paused.instructions_left;
+        let canister_log = paused.canister_log;
         for state_change in paused.steps.into_iter() {
-            helper
-                .replay_step(state_change, original, round, round_limits)
-                .map_err(|err| (err, paused_instructions_left))?;
+            if let Err(err) = helper.replay_step(state_change, original, round, round_limits) {
+                return Err((err, paused_instructions_left, canister_log));
+            }
         }
         assert_eq!(paused_instructions_left, helper.instructions_left());
+        helper.canister_log = canister_log;
         Ok(helper)
     }
 
@@ -281,6 +299,7 @@ impl InstallCodeHelper {
                         original,
                         round,
                         err,
+                        self.take_canister_log(),
                     );
                 }
             }
@@ -306,6 +325,7 @@ impl InstallCodeHelper {
                     original,
                     round,
                     err,
+                    self.take_canister_log(),
                 );
             }
         }
@@ -349,6 +369,7 @@ impl InstallCodeHelper {
                         original,
                         round,
                         err,
+                        self.take_canister_log(),
                     );
                 }
             }
@@ -371,6 +392,7 @@ impl InstallCodeHelper {
                         requested: new_compute_allocation,
                         available: available.max(old_compute_allocation.as_percent()),
                     },
+                    self.take_canister_log(),
                 );
             }
             round_limits.compute_allocation_used = others + new_compute_allocation.as_percent();
@@ -396,6 +418,11 @@ impl InstallCodeHelper {
                 .saturating_sub(instructions_left.get()),
         );
 
+        self.canister
+            .system_state
+            .canister_log
+            .append_delta_log(&mut self.canister_log);
+
         let old_wasm_hash = get_wasm_hash(&clean_canister);
         let new_wasm_hash = get_wasm_hash(&self.canister);
         DtsInstallCodeResult::Finished {
@@ -433,6 +460,9 @@ impl InstallCodeHelper {
                 memory_allocation: original.requested_memory_allocation,
                 freezing_threshold: None,
                 reserved_cycles_limit: None,
+                log_visibility: None,
+                wasm_memory_limit: None,
+                environment_variables: None,
             },
             self.canister.memory_usage(),
             self.canister.message_memory_usage(),
@@ -443,6 +473,9 @@ impl InstallCodeHelper {
             round_limits.compute_allocation_used,
             original.config.compute_capacity,
             original.config.max_controllers,
+            original.config.max_environment_variables,
+            original.config.max_environment_variable_name_length,
+            original.config.max_environment_variable_value_length,
             self.canister.system_state.freeze_threshold,
             self.canister.system_state.balance(),
             round.cycles_account_manager,
@@ -480,6 +513,7 @@ impl InstallCodeHelper {
         instructions_from_compilation: NumInstructions,
         maybe_execution_state: HypervisorResult<ExecutionState>,
         stable_memory_handling: StableMemoryHandling,
+        main_memory_handling: MainMemoryHandling,
         original: &OriginalContext,
     ) -> Result<(), CanisterManagerError> {
         self.steps
@@ -487,6 +521,7 @@ impl InstallCodeHelper {
                 instructions_from_compilation,
                 maybe_execution_state: maybe_execution_state.clone(),
                 stable_memory_handling,
+                main_memory_handling,
             });
 
         self.execution_parameters
@@ -502,19 +537,23 @@ impl InstallCodeHelper {
             .as_ref()
             .map_or(NumBytes::from(0), |es| es.metadata.memory_usage());
 
-        // Replace the execution state and maybe the stable memory.
+        // Replace the execution state and maybe the stable and the Wasm memory.
         let mut execution_state =
             maybe_execution_state.map_err(|err| (self.canister.canister_id(), err))?;
 
         let new_wasm_custom_sections_memory_used = execution_state.metadata.memory_usage();
 
-        execution_state.stable_memory =
-            match (stable_memory_handling, self.canister.execution_state.take()) {
-                (StableMemoryHandling::Keep, Some(old)) => old.stable_memory,
-                (StableMemoryHandling::Keep, None) | (StableMemoryHandling::Replace, _) => {
-                    execution_state.stable_memory
-                }
-            };
+        if let Some(old) = self.canister.execution_state.take() {
+            if stable_memory_handling == StableMemoryHandling::Keep {
+                execution_state.stable_memory = old.stable_memory;
+            }
+            if main_memory_handling == MainMemoryHandling::Keep {
+                // The new module may declare a larger initial memory than the
+                // old module has grown to.
+                let size = old.wasm_memory.size.max(execution_state.wasm_memory.size);
+                execution_state.wasm_memory = Memory::new(old.wasm_memory.page_map, size);
+            }
+        }
         self.canister.execution_state = Some(execution_state);
 
         // Update the compute allocation.
@@ -588,6 +627,12 @@ impl InstallCodeHelper {
         }
     }
 
+    /// Returns the log records produced by the Wasm executions so far,
+    /// leaving an empty log in their place.
+    pub fn take_canister_log(&mut self) -> CanisterLog {
+        std::mem::take(&mut self.canister_log)
+    }
+
     /// Checks the result of Wasm execution and applies the state changes.
     ///
     /// Returns the amount of instructions consumed along with the result of
@@ -595,7 +640,7 @@ impl InstallCodeHelper {
     pub fn handle_wasm_execution(
         &mut self,
         canister_state_changes: Option<CanisterStateChanges>,
-        output: WasmExecutionOutput,
+        mut output: WasmExecutionOutput,
         original: &OriginalContext,
         round: &RoundContext,
     ) -> (NumInstructions, Result<(), CanisterManagerError>) {
@@ -604,6 +649,9 @@ impl InstallCodeHelper {
             output: output.clone(),
         });
 
+        // The log records are kept regardless of whether the execution succeeded.
+        self.canister_log.append_delta_log(&mut output.canister_log);
+
         let instructions_consumed = NumInstructions::from(
             self.execution_parameters
                 .instruction_limits
@@ -721,10 +769,12 @@ impl InstallCodeHelper {
                 instructions_from_compilation,
                 maybe_execution_state,
                 stable_memory_handling,
+                main_memory_handling,
             } => self.replace_execution_state_and_allocations(
                 instructions_from_compilation,
                 maybe_execution_state,
                 stable_memory_handling,
+                main_memory_handling,
                 original,
             ),
             InstallCodeStep::ClearCertifiedData => {
@@ -822,9 +872,16 @@ pub(crate) fn finish_err(
     original: OriginalContext,
     round: RoundContext,
     err: CanisterManagerError,
+    mut canister_log: CanisterLog,
 ) -> DtsInstallCodeResult {
     let mut new_canister = clean_canister;
 
+    // The log records are kept even though the execution failed.
+    new_canister
+        .system_state
+        .canister_log
+        .append_delta_log(&mut canister_log);
+
     new_canister
         .system_state
         .apply_ingress_induction_cycles_debit(
@@ -18,6 +18,9 @@ use ic_types::{Cycles, NumInstructions, Time};
 use prometheus::IntCounter;
 
 // Execute non replicated query.
+//
+// If the instruction limits allow for multiple slices, then `between_slices` is
+// called between two slices of the execution.
 #[allow(clippy::too_many_arguments)]
 pub fn execute_non_replicated_query(
     query_kind: NonReplicatedQueryKind,
@@ -31,6 +34,7 @@ pub fn execute_non_replicated_query(
     hypervisor: &Hypervisor,
     round_limits: &mut RoundLimits,
     state_changes_error: &IntCounter,
+    between_slices: &dyn Fn(),
 ) -> (
     CanisterState,
     NumInstructions,
@@ -100,7 +104,7 @@ pub fn execute_non_replicated_query(
     // As we are executing the query in non-replicated mode, we can
     // modify the canister as the caller is not going to be able to
     // commit modifications to the canister anyway.
-    let (output, output_execution_state, output_system_state) = hypervisor.execute(
+    let (output, output_execution_state, output_system_state) = hypervisor.execute_in_slices(
         api_type,
         time,
         canister.system_state,
@@ -112,6 +116,7 @@ pub fn execute_non_replicated_query(
         network_topology,
         round_limits,
         state_changes_error,
+        between_slices,
     );
     canister.system_state = output_system_state;
     if preserve_changes {
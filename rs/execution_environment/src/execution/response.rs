@@ -900,9 +900,9 @@ pub fn execute_response(
     };
 
     let func_ref = match original.call_origin {
-        CallOrigin::Ingress(_, _) | CallOrigin::CanisterUpdate(_, _) | CallOrigin::SystemTask => {
-            FuncRef::UpdateClosure(closure)
-        }
+        CallOrigin::Ingress(_, _)
+        | CallOrigin::CanisterUpdate(_, _, _)
+        | CallOrigin::SystemTask => FuncRef::UpdateClosure(closure),
         CallOrigin::CanisterQuery(_, _) | CallOrigin::Query(_) => FuncRef::QueryClosure(closure),
     };
 
@@ -928,6 +928,18 @@ pub fn execute_response(
             call_context.instructions_executed(),
         ),
     };
+    // Replies to canister requests may be routed as XNet message fragments.
+    let api_type = match original.call_origin {
+        CallOrigin::CanisterUpdate(_, _, _) => api_type.with_max_reply_size(
+            round
+                .network_topology
+                .max_inter_canister_payload_in_bytes(&round.hypervisor.subnet_id()),
+        ),
+        CallOrigin::Ingress(_, _)
+        | CallOrigin::CanisterQuery(_, _)
+        | CallOrigin::Query(_)
+        | CallOrigin::SystemTask => api_type,
+    };
 
     let (execution_parameters, reserved_cleanup_instructions) =
         reserve_cleanup_instructions(execution_parameters);
@@ -987,9 +999,9 @@ fn execute_response_cleanup(
         .instruction_limits
         .update(instructions_left);
     let func_ref = match original.call_origin {
-        CallOrigin::Ingress(_, _) | CallOrigin::CanisterUpdate(_, _) | CallOrigin::SystemTask => {
-            FuncRef::UpdateClosure(cleanup_closure)
-        }
+        CallOrigin::Ingress(_, _)
+        | CallOrigin::CanisterUpdate(_, _, _)
+        | CallOrigin::SystemTask => FuncRef::UpdateClosure(cleanup_closure),
         CallOrigin::CanisterQuery(_, _) | CallOrigin::Query(_) => {
             FuncRef::QueryClosure(cleanup_closure)
         }
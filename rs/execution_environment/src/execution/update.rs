@@ -16,7 +16,8 @@ use ic_interfaces::execution_environment::{
     CanisterOutOfCyclesError, HypervisorError, WasmExecutionOutput,
 };
 use ic_logger::{info, ReplicaLogger};
-use ic_replicated_state::{CallOrigin, CanisterState};
+use ic_replicated_state::{CallOrigin, CanisterState, OnLowWasmMemoryHookStatus};
+use ic_types::ingress::WasmResult;
 use ic_types::messages::{
     CallContextId, CanisterCall, CanisterCallOrTask, CanisterMessage, CanisterMessageOrTask,
     CanisterTask,
@@ -112,23 +113,43 @@ pub fn execute_update(
     };
 
     let api_type = match &original.call_or_task {
-        CanisterCallOrTask::Call(msg) => ApiType::update(
-            time,
-            msg.method_payload().to_vec(),
-            msg.cycles(),
-            *msg.sender(),
-            helper.call_context_id(),
-        ),
+        CanisterCallOrTask::Call(msg) => {
+            let api_type = ApiType::update(
+                time,
+                msg.method_payload().to_vec(),
+                msg.cycles(),
+                *msg.sender(),
+                helper.call_context_id(),
+            );
+            match msg {
+                // Replies to canister requests may be routed as XNet message fragments.
+                CanisterCall::Request(_) => api_type.with_max_reply_size(
+                    round
+                        .network_topology
+                        .max_inter_canister_payload_in_bytes(&round.hypervisor.subnet_id()),
+                ),
+                CanisterCall::Ingress(_) => api_type,
+            }
+        }
         CanisterCallOrTask::Task(CanisterTask::Heartbeat) => ApiType::system_task(
             IC_00.get(),
             SystemMethod::CanisterHeartbeat,
             time,
+            helper.system_task_payload().to_vec(),
             helper.call_context_id(),
         ),
         CanisterCallOrTask::Task(CanisterTask::GlobalTimer) => ApiType::system_task(
             IC_00.get(),
             SystemMethod::CanisterGlobalTimer,
             time,
+            helper.system_task_payload().to_vec(),
+            helper.call_context_id(),
+        ),
+        CanisterCallOrTask::Task(CanisterTask::OnLowWasmMemory) => ApiType::system_task(
+            IC_00.get(),
+            SystemMethod::CanisterOnLowWasmMemory,
+            time,
+            helper.system_task_payload().to_vec(),
             helper.call_context_id(),
         ),
     };
@@ -225,6 +246,7 @@ fn finish_err(
         round.log,
     );
     let instructions_used = instruction_limit - instructions_left;
+    record_update_method_stats(&mut canister, &original, instructions_used, 0, true);
     finish_call_with_error(
         err,
         canister,
@@ -236,6 +258,35 @@ fn finish_err(
     )
 }
 
+/// Accounts for a finished update call in the per-method stats of the canister.
+/// Canister tasks and calls to methods the canister does not export are not
+/// accounted for.
+fn record_update_method_stats(
+    canister: &mut CanisterState,
+    original: &OriginalContext,
+    instructions_used: NumInstructions,
+    egress_payload_size: usize,
+    is_error: bool,
+) {
+    let CanisterCallOrTask::Call(call) = &original.call_or_task else {
+        return;
+    };
+    if !canister.exports_method(&original.method) {
+        return;
+    }
+    canister
+        .scheduler_state
+        .total_update_method_stats
+        .entry(original.method.name())
+        .or_default()
+        .record_call(
+            instructions_used.get(),
+            call.method_payload().len() as u64,
+            egress_payload_size as u64,
+            is_error,
+        );
+}
+
 /// Context variables that remain the same throughout the entire deterministic
 /// time slicing execution of an update call execution.
 #[derive(Debug)]
@@ -265,6 +316,9 @@ struct UpdateHelper {
     canister: CanisterState,
     call_context_id: CallContextId,
     initial_cycles_balance: Cycles,
+    /// The argument passed to a system task: the little-endian ID of the
+    /// named timer that fired for `canister_global_timer`, empty otherwise.
+    system_task_payload: Vec<u8>,
 }
 
 impl UpdateHelper {
@@ -285,12 +339,32 @@ impl UpdateHelper {
             );
 
         let initial_cycles_balance = canister.system_state.balance();
+        let mut system_task_payload = vec![];
 
         match original.call_or_task {
             CanisterCallOrTask::Call(_) | CanisterCallOrTask::Task(CanisterTask::Heartbeat) => {}
             CanisterCallOrTask::Task(CanisterTask::GlobalTimer) => {
-                // The global timer is one-off.
-                canister.system_state.global_timer = CanisterTimer::Inactive;
+                // All timers are one-off. The legacy global timer takes
+                // precedence, any other reached named timer fires in the next
+                // rounds, one per execution and in the order of deadlines.
+                if canister
+                    .system_state
+                    .global_timer
+                    .has_reached_deadline(original.time)
+                {
+                    canister.system_state.global_timer = CanisterTimer::Inactive;
+                } else if let Some(timer_id) = canister
+                    .system_state
+                    .timers
+                    .pop_reached_deadline(original.time)
+                {
+                    system_task_payload = timer_id.to_le_bytes().to_vec();
+                }
+            }
+            CanisterCallOrTask::Task(CanisterTask::OnLowWasmMemory) => {
+                // The hook runs once until the condition is reset.
+                canister.system_state.on_low_wasm_memory_hook_status =
+                    OnLowWasmMemoryHookStatus::Executed;
             }
         }
 
@@ -298,6 +372,7 @@ impl UpdateHelper {
             canister,
             call_context_id,
             initial_cycles_balance,
+            system_task_payload,
         })
     }
 
@@ -408,6 +483,18 @@ impl UpdateHelper {
                 .get()
                 .saturating_sub(output.num_instructions_left.get()),
         );
+        let (egress_payload_size, is_error) = match &output.wasm_result {
+            Ok(Some(WasmResult::Reply(data))) => (data.len(), false),
+            Ok(Some(WasmResult::Reject(_))) | Err(_) => (0, true),
+            Ok(None) => (0, false),
+        };
+        record_update_method_stats(
+            &mut self.canister,
+            &original,
+            instructions_used,
+            egress_payload_size,
+            is_error,
+        );
         let action = self
             .canister
             .system_state
@@ -449,6 +536,10 @@ impl UpdateHelper {
         &self.canister
     }
 
+    fn system_task_payload(&self) -> &[u8] {
+        &self.system_task_payload
+    }
+
     fn call_context_id(&self) -> CallContextId {
         self.call_context_id
     }
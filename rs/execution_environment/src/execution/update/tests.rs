@@ -872,3 +872,36 @@ fn test_call_context_instructions_executed_is_updated_on_err_update() {
         .unwrap();
     assert!(call_context_manager.call_contexts().is_empty());
 }
+
+#[test]
+fn update_method_stats_are_collected() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let canister_id = test.universal_canister().unwrap();
+
+    let reply_payload = wasm().reply_data(b"hello").build();
+    let reply_payload_size = reply_payload.len() as u128;
+    let result = test.ingress(canister_id, "update", reply_payload).unwrap();
+    assert_eq!(result, WasmResult::Reply(b"hello".to_vec()));
+
+    let trap_payload = wasm().trap().build();
+    let trap_payload_size = trap_payload.len() as u128;
+    let err = test
+        .ingress(canister_id, "update", trap_payload)
+        .unwrap_err();
+    assert_eq!(err.code(), ErrorCode::CanisterCalledTrap);
+
+    let method_stats = &test
+        .canister_state(canister_id)
+        .scheduler_state
+        .total_update_method_stats;
+    assert_eq!(method_stats.keys().collect::<Vec<_>>(), vec!["update"]);
+    let stats = &method_stats["update"];
+    assert_eq!(stats.num_calls, 2);
+    assert_eq!(stats.num_errors, 1);
+    assert!(stats.num_instructions > 0);
+    assert_eq!(
+        stats.ingress_payload_size,
+        reply_payload_size + trap_payload_size
+    );
+    assert_eq!(stats.egress_payload_size, 5);
+}
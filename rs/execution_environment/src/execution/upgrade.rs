@@ -6,22 +6,23 @@
 use std::sync::Arc;
 
 use crate::canister_manager::{
-    DtsInstallCodeResult, InstallCodeContext, PausedInstallCodeExecution,
+    CanisterManagerError, DtsInstallCodeResult, InstallCodeContext, PausedInstallCodeExecution,
 };
 use crate::execution::common::{ingress_status_with_processing_state, update_round_limits};
 use crate::execution::install_code::{
-    canister_layout, finish_err, InstallCodeHelper, OriginalContext, PausedInstallCodeHelper,
-    StableMemoryHandling,
+    canister_layout, finish_err, InstallCodeHelper, MainMemoryHandling, OriginalContext,
+    PausedInstallCodeHelper, StableMemoryHandling,
 };
 use crate::execution_environment::{RoundContext, RoundLimits};
 use ic_base_types::PrincipalId;
 use ic_embedders::wasm_executor::{CanisterStateChanges, PausedWasmExecution, WasmExecutionResult};
-use ic_ic00_types::{CanisterInstallModeV2, SkipPreUpgrade};
+use ic_ic00_types::{CanisterInstallModeV2, CanisterUpgradeOptions};
 use ic_interfaces::execution_environment::{HypervisorError, WasmExecutionOutput};
 use ic_logger::{info, warn, ReplicaLogger};
 use ic_replicated_state::page_map::PageAllocatorFileDescriptor;
 use ic_replicated_state::{
-    metadata_state::subnet_call_context_manager::InstallCodeCallId, CanisterState, SystemState,
+    metadata_state::subnet_call_context_manager::InstallCodeCallId, CanisterState, ExecutionState,
+    SystemState,
 };
 use ic_system_api::ApiType;
 use ic_types::methods::{FuncRef, SystemMethod, WasmMethod};
@@ -30,10 +31,17 @@ use ic_types::{funds::Cycles, messages::CanisterCall};
 #[cfg(test)]
 mod tests;
 
+/// The name of the custom section with which a Wasm module declares that it
+/// can take over the Wasm memory of the previous module. It is required for
+/// the `wasm_memory_persistence: keep` upgrade option.
+const ENHANCED_ORTHOGONAL_PERSISTENCE_SECTION: &str = "enhanced-orthogonal-persistence";
+
 /// Performs a canister upgrade. The algorithm consists of six stages:
 /// - Stage 0: validate input.
 /// - Stage 1: invoke `canister_pre_upgrade()` (if present) using the old code.
 /// - Stage 2: create a new execution state based on the new Wasm code, deactivate global timer, and bump canister version.
+///   The Wasm memory of the old code is carried over if the `wasm_memory_persistence: keep`
+///   upgrade option is given.
 /// - Stage 3: invoke the `start()` method (if present).
 /// - Stage 4: invoke the `canister_post_upgrade()` method (if present).
 /// - Stage 5: finalize execution and refund execution cycles.
@@ -104,6 +112,7 @@ pub(crate) fn execute_upgrade(
             original,
             round,
             err,
+            helper.take_canister_log(),
         );
     }
 
@@ -120,14 +129,13 @@ pub(crate) fn execute_upgrade(
                 original,
                 round,
                 (canister_id, HypervisorError::WasmModuleNotFound).into(),
+                helper.take_canister_log(),
             );
         }
     };
 
     let method = WasmMethod::System(SystemMethod::CanisterPreUpgrade);
-    if context.mode == CanisterInstallModeV2::Upgrade(Some(SkipPreUpgrade(Some(true))))
-        || !execution_state.exports_method(&method)
-    {
+    if upgrade_options(&context).skips_pre_upgrade() || !execution_state.exports_method(&method) {
         // If the Wasm module does not export the method, or skip_pre_upgrade
         // is enabled then this execution succeeds as a no-op.
         upgrade_stage_2_and_3a_create_execution_state_and_call_start(
@@ -194,6 +202,62 @@ pub(crate) fn execute_upgrade(
     }
 }
 
+/// Returns the upgrade options given in the install code context.
+fn upgrade_options(context: &InstallCodeContext) -> CanisterUpgradeOptions {
+    match context.mode {
+        CanisterInstallModeV2::Upgrade(Some(upgrade_options)) => upgrade_options,
+        CanisterInstallModeV2::Install
+        | CanisterInstallModeV2::Reinstall
+        | CanisterInstallModeV2::Upgrade(None) => CanisterUpgradeOptions::default(),
+    }
+}
+
+/// Checks that the new Wasm module declares that it is compatible with the
+/// Wasm memory of the old module, which is required for keeping the memory.
+///
+/// The given execution state must already contain the kept Wasm memory.
+fn validate_wasm_memory_persistence(
+    new_execution_state: &ExecutionState,
+    old_is_wasm64: bool,
+) -> Result<(), CanisterManagerError> {
+    if new_execution_state
+        .metadata
+        .get_custom_section(ENHANCED_ORTHOGONAL_PERSISTENCE_SECTION)
+        .is_none()
+    {
+        return Err(CanisterManagerError::InvalidUpgradeOption {
+            message: format!(
+                "The `wasm_memory_persistence: keep` option requires that the new \
+                 Wasm module declares the `{}` custom section.",
+                ENHANCED_ORTHOGONAL_PERSISTENCE_SECTION
+            ),
+        });
+    }
+    if new_execution_state.is_wasm64 != old_is_wasm64 {
+        return Err(CanisterManagerError::InvalidUpgradeOption {
+            message: format!(
+                "The `wasm_memory_persistence: keep` option requires that the new \
+                 Wasm module declares a {}-bit memory like the old module.",
+                if old_is_wasm64 { 64 } else { 32 }
+            ),
+        });
+    }
+    let wasm_memory_size = new_execution_state.wasm_memory.size.get() as u64;
+    if let Some(max_pages) = new_execution_state.wasm_memory_max_pages {
+        if max_pages < wasm_memory_size {
+            return Err(CanisterManagerError::InvalidUpgradeOption {
+                message: format!(
+                    "The `wasm_memory_persistence: keep` option requires that the new \
+                     Wasm module declares a maximum memory size of at least {} Wasm \
+                     pages, but it declares {} Wasm pages.",
+                    wasm_memory_size, max_pages
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
 fn upgrade_stage_1_process_pre_upgrade_result(
     canister_state_changes: Option<CanisterStateChanges>,
@@ -219,7 +283,14 @@ fn upgrade_stage_1_process_pre_upgrade_result(
 
     if let Err(err) = result {
         let instructions_left = helper.instructions_left();
-        return finish_err(clean_canister, instructions_left, original, round, err);
+        return finish_err(
+            clean_canister,
+            instructions_left,
+            original,
+            round,
+            err,
+            helper.take_canister_log(),
+        );
     }
 
     upgrade_stage_2_and_3a_create_execution_state_and_call_start(
@@ -246,9 +317,21 @@ fn upgrade_stage_2_and_3a_create_execution_state_and_call_start(
     let canister_id = helper.canister().canister_id();
     let context_sender = context.sender();
     let module_hash = context.wasm_module.module_hash();
+    let main_memory_handling = if upgrade_options(&context).keeps_wasm_memory() {
+        MainMemoryHandling::Keep
+    } else {
+        MainMemoryHandling::Replace
+    };
     // Stage 2: create a new execution state based on the new Wasm code, deactivate global timer, and bump canister version.
     // Replace the execution state of the canister with a new execution state, but
-    // persist the stable memory (if it exists).
+    // persist the stable memory (if it exists) and, if requested, the Wasm memory.
+    // The memory type of the old module is needed for validating that the new
+    // module can keep the Wasm memory.
+    let old_is_wasm64 = helper
+        .canister()
+        .execution_state
+        .as_ref()
+        .map_or(false, |es| es.is_wasm64);
     let layout = canister_layout(&original.canister_layout_path, &canister_id);
     let (instructions_from_compilation, result) = round.hypervisor.create_execution_state(
         context.wasm_module,
@@ -262,10 +345,34 @@ fn upgrade_stage_2_and_3a_create_execution_state_and_call_start(
         instructions_from_compilation,
         result,
         StableMemoryHandling::Keep,
+        main_memory_handling,
         &original,
     ) {
         let instructions_left = helper.instructions_left();
-        return finish_err(clean_canister, instructions_left, original, round, err);
+        return finish_err(
+            clean_canister,
+            instructions_left,
+            original,
+            round,
+            err,
+            helper.take_canister_log(),
+        );
+    }
+
+    if main_memory_handling == MainMemoryHandling::Keep {
+        // The execution state is present because we just put it there.
+        let execution_state = helper.canister().execution_state.as_ref().unwrap();
+        if let Err(err) = validate_wasm_memory_persistence(execution_state, old_is_wasm64) {
+            let instructions_left = helper.instructions_left();
+            return finish_err(
+                clean_canister,
+                instructions_left,
+                original,
+                round,
+                err,
+                helper.take_canister_log(),
+            );
+        }
     }
 
     helper.deactivate_global_timer();
@@ -369,7 +476,14 @@ fn upgrade_stage_3b_process_start_result(
 
     if let Err(err) = result {
         let instructions_left = helper.instructions_left();
-        return finish_err(clean_canister, instructions_left, original, round, err);
+        return finish_err(
+            clean_canister,
+            instructions_left,
+            original,
+            round,
+            err,
+            helper.take_canister_log(),
+        );
     }
 
     upgrade_stage_4a_call_post_upgrade(
@@ -475,7 +589,14 @@ fn upgrade_stage_4b_process_post_upgrade_result(
     );
     if let Err(err) = result {
         let instructions_left = helper.instructions_left();
-        return finish_err(clean_canister, instructions_left, original, round, err);
+        return finish_err(
+            clean_canister,
+            instructions_left,
+            original,
+            round,
+            err,
+            helper.take_canister_log(),
+        );
     }
     helper.finish(clean_canister, original, round, round_limits)
 }
@@ -512,7 +633,7 @@ impl PausedInstallCodeExecution for PausedPreUpgradeExecution {
             round_limits,
         ) {
             Ok(helper) => helper,
-            Err((err, instructions_left)) => {
+            Err((err, instructions_left, canister_log)) => {
                 warn!(
                     round.log,
                     "[DTS] Canister {} failed to resume paused (canister_pre_upgrade) execution: {:?}.",
@@ -520,7 +641,14 @@ impl PausedInstallCodeExecution for PausedPreUpgradeExecution {
                     err
                 );
                 self.paused_wasm_execution.abort();
-                return finish_err(clean_canister, instructions_left, self.original, round, err);
+                return finish_err(
+                    clean_canister,
+                    instructions_left,
+                    self.original,
+                    round,
+                    err,
+                    canister_log,
+                );
             }
         };
         let execution_state = helper.canister().execution_state.as_ref().unwrap();
@@ -611,7 +739,7 @@ impl PausedInstallCodeExecution for PausedStartExecutionDuringUpgrade {
             round_limits,
         ) {
             Ok(helper) => helper,
-            Err((err, instructions_left)) => {
+            Err((err, instructions_left, canister_log)) => {
                 warn!(
                     round.log,
                     "[DTS] Canister {} failed to resume paused (start) execution: {:?}.",
@@ -619,7 +747,14 @@ impl PausedInstallCodeExecution for PausedStartExecutionDuringUpgrade {
                     err
                 );
                 self.paused_wasm_execution.abort();
-                return finish_err(clean_canister, instructions_left, self.original, round, err);
+                return finish_err(
+                    clean_canister,
+                    instructions_left,
+                    self.original,
+                    round,
+                    err,
+                    canister_log,
+                );
             }
         };
         let execution_state = helper.canister().execution_state.as_ref().unwrap();
@@ -708,7 +843,7 @@ impl PausedInstallCodeExecution for PausedPostUpgradeExecution {
             round_limits,
         ) {
             Ok(helper) => helper,
-            Err((err, instructions_left)) => {
+            Err((err, instructions_left, canister_log)) => {
                 warn!(
                     round.log,
                     "[DTS] Canister {} failed to resume paused (canister_post_upgrade) execution: {:?}.",
@@ -716,7 +851,14 @@ impl PausedInstallCodeExecution for PausedPostUpgradeExecution {
                     err
                 );
                 self.paused_wasm_execution.abort();
-                return finish_err(clean_canister, instructions_left, self.original, round, err);
+                return finish_err(
+                    clean_canister,
+                    instructions_left,
+                    self.original,
+                    round,
+                    err,
+                    canister_log,
+                );
             }
         };
         let execution_state = helper.canister().execution_state.as_ref().unwrap();
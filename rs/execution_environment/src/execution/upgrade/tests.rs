@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use ic_error_types::ErrorCode;
-use ic_ic00_types::{EmptyBlob, Payload, SkipPreUpgrade};
+use ic_ic00_types::{CanisterUpgradeOptions, EmptyBlob, Payload};
 use ic_logger::replica_logger::LogEntryLogger;
 use ic_replicated_state::{canister_state::NextExecution, CanisterState};
 use ic_state_machine_tests::{IngressState, WasmResult};
@@ -354,9 +354,18 @@ fn test_pre_upgrade_execution_with_canister_install_mode_v2() {
 
     for skip_pre_upgrade in [
         None,
-        Some(SkipPreUpgrade(None)),
-        Some(SkipPreUpgrade(Some(false))),
-        Some(SkipPreUpgrade(Some(true))),
+        Some(CanisterUpgradeOptions {
+            skip_pre_upgrade: None,
+            wasm_memory_persistence: None,
+        }),
+        Some(CanisterUpgradeOptions {
+            skip_pre_upgrade: Some(false),
+            wasm_memory_persistence: None,
+        }),
+        Some(CanisterUpgradeOptions {
+            skip_pre_upgrade: Some(true),
+            wasm_memory_persistence: None,
+        }),
     ] {
         let old_binary = binary(&[(Function::PreUpgrade, Execution::ShortTrap)]);
         let canister_id = test.create_canister(Cycles::from(1_000_000_000_000u128));
@@ -365,7 +374,12 @@ fn test_pre_upgrade_execution_with_canister_install_mode_v2() {
 
         let result = test.upgrade_canister_v2(canister_id, new_empty_binary(), skip_pre_upgrade);
 
-        if skip_pre_upgrade == Some(SkipPreUpgrade(Some(true))) {
+        if skip_pre_upgrade
+            == Some(CanisterUpgradeOptions {
+                skip_pre_upgrade: Some(true),
+                wasm_memory_persistence: None,
+            })
+        {
             assert_eq!(result, Ok(()));
             assert_canister_state_after_ok(
                 &canister_state_before,
@@ -387,9 +401,18 @@ fn test_upgrade_execution_with_canister_install_mode_v2() {
 
     for skip_pre_upgrade in [
         None,
-        Some(SkipPreUpgrade(None)),
-        Some(SkipPreUpgrade(Some(false))),
-        Some(SkipPreUpgrade(Some(true))),
+        Some(CanisterUpgradeOptions {
+            skip_pre_upgrade: None,
+            wasm_memory_persistence: None,
+        }),
+        Some(CanisterUpgradeOptions {
+            skip_pre_upgrade: Some(false),
+            wasm_memory_persistence: None,
+        }),
+        Some(CanisterUpgradeOptions {
+            skip_pre_upgrade: Some(true),
+            wasm_memory_persistence: None,
+        }),
     ] {
         let old_binary = binary(&[(Function::PreUpgrade, Execution::Short)]);
         let canister_id = test.create_canister(Cycles::from(1_000_000_000_000u128));
@@ -995,7 +1018,10 @@ fn upgrade_with_skip_pre_upgrade_fails_on_no_execution_state() {
     let result = test.upgrade_canister_v2(
         canister_id,
         new_empty_binary(),
-        Some(SkipPreUpgrade(Some(true))),
+        Some(CanisterUpgradeOptions {
+            skip_pre_upgrade: Some(true),
+            wasm_memory_persistence: None,
+        }),
     );
     assert_eq!(
         result.unwrap_err().code(),
@@ -1013,7 +1039,10 @@ fn upgrade_with_skip_pre_upgrade_ok_with_no_pre_upgrade() {
     let result = test.upgrade_canister_v2(
         canister_id,
         new_empty_binary(),
-        Some(SkipPreUpgrade(Some(true))),
+        Some(CanisterUpgradeOptions {
+            skip_pre_upgrade: Some(true),
+            wasm_memory_persistence: None,
+        }),
     );
     assert_eq!(result, Ok(()));
     assert_canister_state_after_ok(&canister_state_before, test.canister_state(canister_id));
@@ -1,7 +1,8 @@
 use crate::{
     canister_manager::{
         CanisterManager, CanisterManagerError, CanisterMgrConfig, DtsInstallCodeResult,
-        InstallCodeContext, PausedInstallCodeExecution, StopCanisterResult, UploadChunkResult,
+        InstallCodeContext, PausedInstallCodeExecution, StopCanisterResult,
+        TakeCanisterSnapshotResult, UploadChunkResult,
     },
     canister_settings::CanisterSettings,
     execution::{
@@ -32,12 +33,13 @@ use ic_error_types::{ErrorCode, RejectCode, UserError};
 use ic_ic00_types::{
     CanisterChangeOrigin, CanisterHttpRequestArgs, CanisterIdRecord, CanisterInfoRequest,
     CanisterInfoResponse, CanisterSettingsArgs, CanisterStatusType, ClearChunkStoreArgs,
-    ComputeInitialEcdsaDealingsArgs, CreateCanisterArgs, ECDSAPublicKeyArgs,
-    ECDSAPublicKeyResponse, EcdsaKeyId, EmptyBlob, InstallChunkedCodeArgs, InstallCodeArgsV2,
+    ComputeInitialEcdsaDealingsArgs, CreateCanisterArgs, DeleteCanisterSnapshotArgs,
+    ECDSAPublicKeyArgs, ECDSAPublicKeyResponse, EcdsaKeyId, EmptyBlob, FetchCanisterLogsRequest,
+    InstallChunkedCodeArgs, InstallCodeArgsV2, ListCanisterSnapshotArgs, LoadCanisterSnapshotArgs,
     Method as Ic00Method, NodeMetricsHistoryArgs, Payload as Ic00Payload,
     ProvisionalCreateCanisterWithCyclesArgs, ProvisionalTopUpCanisterArgs, SetupInitialDKGArgs,
-    SignWithECDSAArgs, StoredChunksArgs, UninstallCodeArgs, UpdateSettingsArgs, UploadChunkArgs,
-    IC_00,
+    SignWithECDSAArgs, StoredChunksArgs, TakeCanisterSnapshotArgs, UninstallCodeArgs,
+    UpdateSettingsArgs, UploadChunkArgs, IC_00,
 };
 use ic_interfaces::execution_environment::{
     ExecutionMode, IngressHistoryWriter, RegistryExecutionSettings, SubnetAvailableMemory,
@@ -345,6 +347,9 @@ impl ExecutionEnvironment {
             own_subnet_id,
             own_subnet_type,
             config.max_controllers,
+            config.max_environment_variables,
+            config.max_environment_variable_name_length,
+            config.max_environment_variable_value_length,
             compute_capacity,
             config.rate_limiting_of_instructions,
             config.allocatable_compute_capacity_in_percent,
@@ -352,6 +357,8 @@ impl ExecutionEnvironment {
             config.rate_limiting_of_heap_delta,
             heap_delta_rate_limit,
             upload_wasm_chunk_instructions,
+            config.canister_snapshots,
+            config.embedders_config.feature_flags.canister_logging,
         );
         let metrics = ExecutionEnvironmentMetrics::new(metrics_registry);
         let canister_manager = CanisterManager::new(
@@ -452,6 +459,7 @@ impl ExecutionEnvironment {
                                 originator_reply_callback: request.sender_reply_callback,
                                 refund: request.payment,
                                 response_payload: response.response_payload.clone(),
+                                deadline: request.deadline,
                             }
                             .into(),
                         );
@@ -526,6 +534,7 @@ impl ExecutionEnvironment {
                                         "An empty message cannot be signed",
                                     ),
                                 ),
+                                deadline: request.deadline,
                             }
                             .into(),
                         );
@@ -1080,6 +1089,64 @@ impl ExecutionEnvironment {
                 Some((res, msg.take_cycles()))
             }
 
+            Ok(Ic00Method::TakeCanisterSnapshot) => {
+                let resource_saturation =
+                    self.subnet_memory_saturation(&round_limits.subnet_available_memory);
+                let res = match TakeCanisterSnapshotArgs::decode(payload) {
+                    Err(err) => Err(err),
+                    Ok(args) => self.take_canister_snapshot(
+                        *msg.sender(),
+                        &mut state,
+                        args,
+                        round_limits,
+                        registry_settings.subnet_size,
+                        &resource_saturation,
+                    ),
+                };
+                Some((res, msg.take_cycles()))
+            }
+
+            Ok(Ic00Method::LoadCanisterSnapshot) => {
+                let resource_saturation =
+                    self.subnet_memory_saturation(&round_limits.subnet_available_memory);
+                let res = match LoadCanisterSnapshotArgs::decode(payload) {
+                    Err(err) => Err(err),
+                    Ok(args) => self.load_canister_snapshot(
+                        msg.canister_change_origin(args.get_sender_canister_version()),
+                        &mut state,
+                        args,
+                        round_limits,
+                        registry_settings.subnet_size,
+                        &resource_saturation,
+                    ),
+                };
+                Some((res, msg.take_cycles()))
+            }
+
+            Ok(Ic00Method::ListCanisterSnapshots) => {
+                let res = match ListCanisterSnapshotArgs::decode(payload) {
+                    Err(err) => Err(err),
+                    Ok(args) => self.list_canister_snapshots(*msg.sender(), &state, args),
+                };
+                Some((res, msg.take_cycles()))
+            }
+
+            Ok(Ic00Method::DeleteCanisterSnapshot) => {
+                let res = match DeleteCanisterSnapshotArgs::decode(payload) {
+                    Err(err) => Err(err),
+                    Ok(args) => self.delete_canister_snapshot(*msg.sender(), &mut state, args),
+                };
+                Some((res, msg.take_cycles()))
+            }
+
+            Ok(Ic00Method::FetchCanisterLogs) => {
+                let res = match FetchCanisterLogsRequest::decode(payload) {
+                    Err(err) => Err(err),
+                    Ok(args) => self.fetch_canister_logs(*msg.sender(), &state, args),
+                };
+                Some((res, msg.take_cycles()))
+            }
+
             Ok(Ic00Method::DeleteChunks) | Ok(Ic00Method::InstallChunkedCode) => Some((
                 Err(UserError::new(
                     ErrorCode::CanisterRejectedMessage,
@@ -1340,6 +1407,11 @@ impl ExecutionEnvironment {
         self.config.subnet_memory_capacity
     }
 
+    /// Returns the maximum size of a 64-bit Wasm memory.
+    pub fn max_wasm64_memory_size(&self) -> NumBytes {
+        self.hypervisor.max_wasm64_memory_size()
+    }
+
     /// Builds execution parameters for the given canister with the given
     /// instruction limit and available subnet memory counter.
     fn execution_parameters(
@@ -1616,6 +1688,100 @@ impl ExecutionEnvironment {
             .map_err(|err| err.into())
     }
 
+    fn take_canister_snapshot(
+        &self,
+        sender: PrincipalId,
+        state: &mut ReplicatedState,
+        args: TakeCanisterSnapshotArgs,
+        round_limits: &mut RoundLimits,
+        subnet_size: usize,
+        resource_saturation: &ResourceSaturation,
+    ) -> Result<Vec<u8>, UserError> {
+        self.canister_manager
+            .take_canister_snapshot(
+                sender,
+                args.get_canister_id(),
+                args.replace_snapshot(),
+                state,
+                round_limits,
+                subnet_size,
+                resource_saturation,
+            )
+            .map(
+                |TakeCanisterSnapshotResult {
+                     reply,
+                     heap_delta_increase,
+                 }| {
+                    state.metadata.heap_delta_estimate += heap_delta_increase;
+                    reply.encode()
+                },
+            )
+            .map_err(|err| err.into())
+    }
+
+    fn load_canister_snapshot(
+        &self,
+        origin: CanisterChangeOrigin,
+        state: &mut ReplicatedState,
+        args: LoadCanisterSnapshotArgs,
+        round_limits: &mut RoundLimits,
+        subnet_size: usize,
+        resource_saturation: &ResourceSaturation,
+    ) -> Result<Vec<u8>, UserError> {
+        self.canister_manager
+            .load_canister_snapshot(
+                origin,
+                args.get_canister_id(),
+                args.snapshot_id(),
+                state,
+                "NOT_USED".into(),
+                round_limits,
+                subnet_size,
+                resource_saturation,
+            )
+            .map(|heap_delta_increase| {
+                state.metadata.heap_delta_estimate += heap_delta_increase;
+                EmptyBlob.encode()
+            })
+            .map_err(|err| err.into())
+    }
+
+    fn list_canister_snapshots(
+        &self,
+        sender: PrincipalId,
+        state: &ReplicatedState,
+        args: ListCanisterSnapshotArgs,
+    ) -> Result<Vec<u8>, UserError> {
+        self.canister_manager
+            .list_canister_snapshots(sender, args.get_canister_id(), state)
+            .map(|reply| reply.encode())
+            .map_err(|err| err.into())
+    }
+
+    fn fetch_canister_logs(
+        &self,
+        sender: PrincipalId,
+        state: &ReplicatedState,
+        args: FetchCanisterLogsRequest,
+    ) -> Result<Vec<u8>, UserError> {
+        self.canister_manager
+            .fetch_canister_logs(sender, args.get_canister_id(), state)
+            .map(|response| response.encode())
+            .map_err(|err| err.into())
+    }
+
+    fn delete_canister_snapshot(
+        &self,
+        sender: PrincipalId,
+        state: &mut ReplicatedState,
+        args: DeleteCanisterSnapshotArgs,
+    ) -> Result<Vec<u8>, UserError> {
+        self.canister_manager
+            .delete_canister_snapshot(sender, args.get_canister_id(), args.snapshot_id(), state)
+            .map(|()| EmptyBlob.encode())
+            .map_err(|err| err.into())
+    }
+
     fn node_metrics_history(
         &self,
         state: &ReplicatedState,
@@ -1872,6 +2038,7 @@ impl ExecutionEnvironment {
             &self.hypervisor,
             &mut round_limits,
             &self.metrics.state_changes_error,
+            &|| {},
         )
         .2;
 
@@ -1913,6 +2080,7 @@ impl ExecutionEnvironment {
                     originator_reply_callback: req.sender_reply_callback,
                     refund,
                     response_payload: payload,
+                    deadline: req.deadline,
                 };
 
                 state.push_subnet_output_response(response.into());
@@ -1990,6 +2158,7 @@ impl ExecutionEnvironment {
                     reply_callback,
                     call_id,
                     cycles,
+                    deadline,
                 } => {
                     // Rejecting a stop_canister request from a canister.
                     let subnet_id_as_canister_id = CanisterId::from(self.own_subnet_id);
@@ -2004,6 +2173,7 @@ impl ExecutionEnvironment {
                             RejectCode::CanisterError,
                             format!("Canister {}'s stop request cancelled", canister_id),
                         )),
+                        deadline,
                     };
                     state.push_subnet_output_response(response.into());
                 }
@@ -2495,6 +2665,7 @@ impl ExecutionEnvironment {
         match task {
             ExecutionTask::Heartbeat
             | ExecutionTask::GlobalTimer
+            | ExecutionTask::OnLowWasmMemory
             | ExecutionTask::PausedExecution(_)
             | ExecutionTask::AbortedExecution { .. } => {
                 panic!(
@@ -2591,7 +2762,8 @@ impl ExecutionEnvironment {
                     ExecutionTask::AbortedExecution { .. }
                     | ExecutionTask::AbortedInstallCode { .. }
                     | ExecutionTask::Heartbeat
-                    | ExecutionTask::GlobalTimer => task,
+                    | ExecutionTask::GlobalTimer
+                    | ExecutionTask::OnLowWasmMemory => task,
                     ExecutionTask::PausedExecution(id) => {
                         let paused = self.take_paused_execution(id).unwrap();
                         let (input, prepaid_execution_cycles) = paused.abort(log);
@@ -2745,6 +2917,7 @@ impl ExecutionEnvironment {
                 sender,
                 reply_callback,
                 cycles,
+                deadline,
                 ..
             } => {
                 // Responding to stop_canister request from a canister.
@@ -2762,6 +2935,7 @@ impl ExecutionEnvironment {
                     originator_reply_callback: *reply_callback,
                     refund: *cycles,
                     response_payload,
+                    deadline: *deadline,
                 };
                 state.push_subnet_output_response(response.into());
             }
@@ -3135,6 +3309,10 @@ pub fn execute_canister(
                 let task = CanisterMessageOrTask::Task(CanisterTask::GlobalTimer);
                 (task, None)
             }
+            ExecutionTask::OnLowWasmMemory => {
+                let task = CanisterMessageOrTask::Task(CanisterTask::OnLowWasmMemory);
+                (task, None)
+            }
             ExecutionTask::AbortedExecution {
                 input,
                 prepaid_execution_cycles,
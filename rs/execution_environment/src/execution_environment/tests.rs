@@ -32,6 +32,7 @@ use ic_types::{
     ingress::{IngressState, IngressStatus, WasmResult},
     messages::{
         CallbackId, Payload, RejectContext, RequestOrResponse, Response, MAX_RESPONSE_COUNT_BYTES,
+        NO_DEADLINE,
     },
     CanisterId, Cycles, PrincipalId, RegistryVersion,
 };
@@ -1473,7 +1474,8 @@ fn setup_initial_dkg_sender_not_on_nns() {
                     ic00::Method::SetupInitialDKG,
                     other_canister,
                 )
-            ))
+            )),
+            deadline: NO_DEADLINE,
         }
         .into()
     );
@@ -2019,7 +2021,9 @@ fn execute_canister_http_request_disabled() {
 
 fn get_reject_message(response: RequestOrResponse) -> String {
     match response {
-        RequestOrResponse::Request(_) => panic!("Expected Response"),
+        RequestOrResponse::Request(_) | RequestOrResponse::Fragment(_) => {
+            panic!("Expected Response")
+        }
         RequestOrResponse::Response(resp) => match &resp.response_payload {
             Payload::Data(_) => panic!("Expected Reject"),
             Payload::Reject(reject) => reject.message().clone(),
@@ -1,7 +1,7 @@
 use assert_matches::assert_matches;
 use ic_registry_subnet_type::SubnetType;
-use ic_replicated_state::NumWasmPages;
 use ic_replicated_state::{page_map::PAGE_SIZE, CanisterStatus};
+use ic_replicated_state::{NumWasmPages, OnLowWasmMemoryHookStatus};
 use ic_state_machine_tests::{Cycles, StateMachine};
 use ic_state_machine_tests::{StateMachineBuilder, WasmResult};
 use ic_test_utilities_execution_environment::{wat_compilation_cost, ExecutionTestBuilder};
@@ -46,6 +46,32 @@ fn global_timer_is_executed() {
     );
 }
 
+#[test]
+fn on_low_wasm_memory_hook_is_executed() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let wat = r#"(module
+            (func (export "canister_on_low_wasm_memory")
+                (drop (memory.grow (i32.const 10)))
+            )
+            (memory 1 20)
+        )"#;
+    let canister_id = test.canister_from_wat(wat).unwrap();
+    test.canister_state_mut(canister_id)
+        .system_state
+        .on_low_wasm_memory_hook_status = OnLowWasmMemoryHookStatus::Ready;
+    test.canister_task(canister_id, CanisterTask::OnLowWasmMemory);
+    assert_eq!(
+        test.execution_state(canister_id).wasm_memory.size,
+        NumWasmPages::new(11)
+    );
+    assert_eq!(
+        test.canister_state(canister_id)
+            .system_state
+            .on_low_wasm_memory_hook_status,
+        OnLowWasmMemoryHookStatus::Executed
+    );
+}
+
 #[test]
 fn ic0_global_timer_set_is_supported_in_pre_upgrade() {
     let env = StateMachine::new();
@@ -65,6 +91,64 @@ fn ic0_global_timer_set_is_supported_in_pre_upgrade() {
     assert_eq!(result, Ok(()));
 }
 
+#[test]
+fn named_timers_fire_in_deadline_order_with_timer_id_as_argument() {
+    let env = StateMachine::new();
+    let wat = r#"
+        (module
+            (import "ic0" "timer_set"
+                (func $timer_set (param i64 i64) (result i64))
+            )
+            (import "ic0" "msg_arg_data_copy"
+                (func $msg_arg_data_copy (param i32 i32 i32))
+            )
+            (import "ic0" "msg_reply" (func $msg_reply))
+            (import "ic0" "msg_reply_data_append"
+                (func $msg_reply_data_append (param i32 i32))
+            )
+            (func (export "canister_update set_timers")
+                (drop (call $timer_set (i64.const 2) (i64.const 1)))
+                (drop (call $timer_set (i64.const 1) (i64.const 2)))
+                (call $msg_reply)
+            )
+            ;; Appends the ID of the fired timer to the list at address 8,
+            ;; the number of entries is kept at address 0.
+            (func (export "canister_global_timer")
+                (call $msg_arg_data_copy
+                    (i32.add (i32.const 8) (i32.mul (i32.load (i32.const 0)) (i32.const 8)))
+                    (i32.const 0)
+                    (i32.const 8)
+                )
+                (i32.store (i32.const 0) (i32.add (i32.load (i32.const 0)) (i32.const 1)))
+            )
+            (func (export "canister_query fired_timers")
+                (call $msg_reply_data_append
+                    (i32.const 8)
+                    (i32.mul (i32.load (i32.const 0)) (i32.const 8))
+                )
+                (call $msg_reply)
+            )
+            (memory 1)
+        )"#;
+    let canister_id = env.install_canister_wat(wat, vec![], None);
+
+    let result = env.execute_ingress(canister_id, "set_timers", vec![]);
+    assert_eq!(result, Ok(WasmResult::Reply(vec![])));
+    env.tick();
+    env.tick();
+
+    let result = env.query(canister_id, "fired_timers", vec![]);
+    let expected = [2u64.to_le_bytes(), 1u64.to_le_bytes()].concat();
+    assert_eq!(result, Ok(WasmResult::Reply(expected)));
+    assert!(env
+        .get_latest_state()
+        .canister_state(&canister_id)
+        .unwrap()
+        .system_state
+        .timers
+        .is_empty());
+}
+
 #[test]
 fn heartbeat_produces_heap_delta() {
     let mut test = ExecutionTestBuilder::new().build();
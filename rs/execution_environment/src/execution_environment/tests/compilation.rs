@@ -311,6 +311,8 @@ mod execution_tests {
             Memory::new_for_testing(),
             Vec::new(),
             WasmMetadata::default(),
+            false,
+            None,
         ));
 
         // Call the same method on the canister twice.
@@ -365,6 +367,8 @@ mod execution_tests {
             Memory::new_for_testing(),
             Vec::new(),
             WasmMetadata::default(),
+            false,
+            None,
         ));
         let canister_id2 = test.create_canister(Cycles::new(1_000_000_000_000));
         let canister_state = test.canister_state_mut(canister_id2);
@@ -381,6 +385,8 @@ mod execution_tests {
             Memory::new_for_testing(),
             Vec::new(),
             WasmMetadata::default(),
+            false,
+            None,
         ));
 
         // Execute an update on each canister.
@@ -432,6 +438,8 @@ mod execution_tests {
             Memory::new_for_testing(),
             Vec::new(),
             WasmMetadata::default(),
+            false,
+            None,
         ));
         // Install a canister with the same invalid wasm.
         assert_eq!(
@@ -490,6 +498,8 @@ mod execution_tests {
             Memory::new_for_testing(),
             Vec::new(),
             WasmMetadata::default(),
+            false,
+            None,
         ));
         // Install a canister with the same invalid wasm.
         assert_eq!(
@@ -241,6 +241,7 @@ fn dashboard_label_value_from(code: ErrorCode) -> &'static str {
         CanisterFunctionNotFound => "Canister Function Not Found",
         CanisterAlreadyInstalled => "Canister Already Installed",
         CanisterWasmModuleNotFound => "Canister WASM Module Not Found",
+        CanisterSnapshotNotFound => "Canister Snapshot Not Found",
         CanisterNonEmpty => "Canister Non-Empty",
         CanisterOutOfCycles => "Canister Out Of Cycles",
         CanisterTrapped => "Canister Trapped",
@@ -288,5 +289,7 @@ fn dashboard_label_value_from(code: ErrorCode) -> &'static str {
         ReservedCyclesLimitExceededInMemoryGrow => "Canister cannot grow memory due to its reserved cycles limit",
         InsufficientCyclesInMessageMemoryGrow => "Canister does not have enough cycles to grow message memory",
         StopCanisterRequestTimeout => "Stop canister request timed out",
+        CanisterHeapDeltaRateLimited => "Canister is heap delta rate limited",
+        CanisterWasmMemoryLimitExceeded => "Canister exceeded its Wasm memory limit",
     }
 }
@@ -1,4 +1,5 @@
 use ic_canister_sandbox_replica_controller::sandboxed_execution_controller::SandboxedExecutionController;
+use ic_config::embedders::Config as EmbeddersConfig;
 use ic_config::execution_environment::{Config, MAX_COMPILATION_CACHE_SIZE};
 use ic_config::flag_status::FlagStatus;
 use ic_cycles_account_manager::CyclesAccountManager;
@@ -7,7 +8,7 @@ use ic_embedders::wasm_utils::decoding::decoded_wasm_size;
 use ic_embedders::{wasm_executor::WasmExecutorImpl, WasmExecutionInput, WasmtimeEmbedder};
 use ic_embedders::{CompilationCache, CompilationResult};
 use ic_interfaces::execution_environment::{HypervisorResult, WasmExecutionOutput};
-use ic_logger::ReplicaLogger;
+use ic_logger::{warn, ReplicaLogger};
 use ic_metrics::buckets::decimal_buckets_with_zero;
 use ic_metrics::{buckets::exponential_buckets, MetricsRegistry};
 use ic_registry_subnet_type::SubnetType;
@@ -15,13 +16,17 @@ use ic_replicated_state::NetworkTopology;
 use ic_replicated_state::{page_map::allocated_pages_count, ExecutionState, SystemState};
 use ic_system_api::ExecutionParameters;
 use ic_system_api::{sandbox_safe_system_state::SandboxSafeSystemState, ApiType};
-use ic_types::{methods::FuncRef, CanisterId, NumBytes, NumInstructions, SubnetId, Time};
+use ic_types::{
+    methods::FuncRef, CanisterId, NumBytes, NumInstructions, SubnetId, Time,
+    MAX_STABLE_MEMORY_IN_BYTES,
+};
 use ic_wasm_types::CanisterModule;
 use prometheus::{Histogram, IntCounter, IntGauge};
 use std::{path::PathBuf, sync::Arc};
 
 use crate::execution::common::{apply_canister_state_changes, update_round_limits};
 use crate::execution_environment::{as_round_instructions, CompilationCostHandling, RoundLimits};
+use crate::wasm_profiler::{profile_execution, WasmProfiler};
 use ic_replicated_state::page_map::PageAllocatorFileDescriptor;
 
 #[cfg(test)]
@@ -157,6 +162,8 @@ pub struct Hypervisor {
     deterministic_time_slicing: FlagStatus,
     cost_to_compile_wasm_instruction: NumInstructions,
     dirty_page_overhead: NumInstructions,
+    wasm_profiler: Option<Arc<WasmProfiler>>,
+    max_wasm64_memory_size: NumBytes,
 }
 
 impl Hypervisor {
@@ -168,6 +175,17 @@ impl Hypervisor {
         self.own_subnet_type
     }
 
+    /// Returns the maximum size of a 64-bit Wasm memory.
+    pub fn max_wasm64_memory_size(&self) -> NumBytes {
+        self.max_wasm64_memory_size
+    }
+
+    /// Returns the profiler that collects the Wasm profiles of all executions
+    /// if the Wasm profiler is enabled.
+    pub fn wasm_profiler(&self) -> Option<Arc<WasmProfiler>> {
+        self.wasm_profiler.clone()
+    }
+
     pub fn create_execution_state(
         &self,
         canister_module: CanisterModule,
@@ -230,6 +248,42 @@ impl Hypervisor {
         let mut embedder_config = config.embedders_config.clone();
         embedder_config.subnet_type = own_subnet_type;
         embedder_config.dirty_page_overhead = dirty_page_overhead;
+        // 64-bit memories are backed by an address space reservation of the
+        // maximum stable memory size, so a larger limit cannot be honored.
+        if embedder_config.max_wasm64_memory_size.get() > MAX_STABLE_MEMORY_IN_BYTES {
+            warn!(
+                log,
+                "Configured max_wasm64_memory_size {} exceeds the maximum of {} bytes, capping it",
+                embedder_config.max_wasm64_memory_size,
+                MAX_STABLE_MEMORY_IN_BYTES
+            );
+            embedder_config.max_wasm64_memory_size = NumBytes::new(MAX_STABLE_MEMORY_IN_BYTES);
+        }
+
+        let compilation_cache = match &config.compilation_cache_dir {
+            Some(dir) => CompilationCache::new_persistent(
+                config.max_compilation_cache_size,
+                dir,
+                config.max_compilation_cache_disk_size,
+                &embedder_config,
+            )
+            .unwrap_or_else(|err| {
+                warn!(
+                    log,
+                    "Failed to open the compilation cache at {}, falling back to an in-memory cache: {}",
+                    dir.display(),
+                    err
+                );
+                CompilationCache::new(config.max_compilation_cache_size)
+            }),
+            None => CompilationCache::new(config.max_compilation_cache_size),
+        };
+
+        let max_wasm64_memory_size = embedder_config.max_wasm64_memory_size;
+        let wasm_profiler = match embedder_config.feature_flags.wasm_profiler {
+            FlagStatus::Enabled => Some(Arc::new(WasmProfiler::default())),
+            FlagStatus::Disabled => None,
+        };
 
         let wasm_executor: Arc<dyn WasmExecutor> = match config.canister_sandboxing_flag {
             FlagStatus::Enabled => {
@@ -260,12 +314,14 @@ impl Hypervisor {
             own_subnet_type,
             log,
             cycles_account_manager,
-            compilation_cache: Arc::new(CompilationCache::new(config.max_compilation_cache_size)),
+            compilation_cache: Arc::new(compilation_cache),
             deterministic_time_slicing: config.deterministic_time_slicing,
             cost_to_compile_wasm_instruction: config
                 .embedders_config
                 .cost_to_compile_wasm_instruction,
             dirty_page_overhead,
+            wasm_profiler,
+            max_wasm64_memory_size,
         }
     }
 
@@ -292,6 +348,8 @@ impl Hypervisor {
             deterministic_time_slicing,
             cost_to_compile_wasm_instruction,
             dirty_page_overhead,
+            wasm_profiler: None,
+            max_wasm64_memory_size: EmbeddersConfig::default().max_wasm64_memory_size,
         }
     }
 
@@ -308,12 +366,12 @@ impl Hypervisor {
         &self,
         api_type: ApiType,
         time: Time,
-        mut system_state: SystemState,
+        system_state: SystemState,
         canister_current_memory_usage: NumBytes,
         canister_current_message_memory_usage: NumBytes,
         execution_parameters: ExecutionParameters,
         func_ref: FuncRef,
-        mut execution_state: ExecutionState,
+        execution_state: ExecutionState,
         network_topology: &NetworkTopology,
         round_limits: &mut RoundLimits,
         state_changes_error: &IntCounter,
@@ -322,7 +380,45 @@ impl Hypervisor {
             execution_parameters.instruction_limits.message(),
             execution_parameters.instruction_limits.slice()
         );
-        let execution_result = self.execute_dts(
+        self.execute_in_slices(
+            api_type,
+            time,
+            system_state,
+            canister_current_memory_usage,
+            canister_current_message_memory_usage,
+            execution_parameters,
+            func_ref,
+            execution_state,
+            network_topology,
+            round_limits,
+            state_changes_error,
+            &|| {},
+        )
+    }
+
+    /// Executes the given WebAssembly function to completion, resuming it
+    /// right away whenever it pauses at the end of a slice. `between_slices`
+    /// is called after each paused slice, before the execution is resumed.
+    ///
+    /// This is only suitable for non-replicated execution, where the paused
+    /// execution does not need to survive the end of a round.
+    #[allow(clippy::too_many_arguments)]
+    pub fn execute_in_slices(
+        &self,
+        api_type: ApiType,
+        time: Time,
+        mut system_state: SystemState,
+        canister_current_memory_usage: NumBytes,
+        canister_current_message_memory_usage: NumBytes,
+        execution_parameters: ExecutionParameters,
+        func_ref: FuncRef,
+        mut execution_state: ExecutionState,
+        network_topology: &NetworkTopology,
+        round_limits: &mut RoundLimits,
+        state_changes_error: &IntCounter,
+        between_slices: &dyn Fn(),
+    ) -> (WasmExecutionOutput, ExecutionState, SystemState) {
+        let mut execution_result = self.execute_dts(
             api_type,
             &execution_state,
             &system_state,
@@ -333,12 +429,16 @@ impl Hypervisor {
             round_limits,
             network_topology,
         );
-        let (slice, mut output, canister_state_changes) = match execution_result {
-            WasmExecutionResult::Finished(slice, output, system_state_changes) => {
-                (slice, output, system_state_changes)
-            }
-            WasmExecutionResult::Paused(_, _) => {
-                unreachable!("DTS is not supported");
+        let (slice, mut output, canister_state_changes) = loop {
+            match execution_result {
+                WasmExecutionResult::Finished(slice, output, system_state_changes) => {
+                    break (slice, output, system_state_changes);
+                }
+                WasmExecutionResult::Paused(slice, paused_wasm_execution) => {
+                    update_round_limits(round_limits, &slice);
+                    between_slices();
+                    execution_result = paused_wasm_execution.resume(&execution_state);
+                }
             }
         };
         update_round_limits(round_limits, &slice);
@@ -407,7 +507,15 @@ impl Hypervisor {
                 .observe_compilation_metrics(&compilation_result);
         }
         self.metrics.observe(&execution_result);
-        execution_result
+        match &self.wasm_profiler {
+            Some(wasm_profiler) => profile_execution(
+                wasm_profiler,
+                system_state.canister_id(),
+                execution_state,
+                execution_result,
+            ),
+            None => execution_result,
+        }
     }
 
     #[doc(hidden)]
@@ -1,10 +1,13 @@
 use assert_matches::assert_matches;
 use candid::{Decode, Encode};
 use ic_base_types::{NumSeconds, PrincipalId};
+use ic_config::flag_status::FlagStatus;
 use ic_cycles_account_manager::ResourceSaturation;
 use ic_embedders::wasm_utils::instrumentation::instruction_to_cost_new;
 use ic_error_types::{ErrorCode, RejectCode};
-use ic_ic00_types::{CanisterChange, CanisterHttpResponsePayload, SkipPreUpgrade};
+use ic_ic00_types::{
+    CanisterChange, CanisterHttpResponsePayload, CanisterUpgradeOptions, WasmMemoryPersistence,
+};
 use ic_interfaces::execution_environment::{HypervisorError, SubnetAvailableMemory};
 use ic_nns_constants::CYCLES_MINTING_CANISTER_ID;
 use ic_registry_subnet_type::SubnetType;
@@ -32,7 +35,7 @@ use ic_types::{
 use ic_universal_canister::{call_args, wasm, UNIVERSAL_CANISTER_WASM};
 use proptest::prelude::*;
 use proptest::test_runner::{TestRng, TestRunner};
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::mem::size_of;
 use std::time::Duration;
 
@@ -308,6 +311,56 @@ fn ic0_grow_can_reach_max_number_of_pages() {
     assert_empty_reply(result);
 }
 
+#[test]
+fn wasm64_data_segment_is_installed() {
+    let mut test = ExecutionTestBuilder::new().with_wasm64().build();
+    let wat = r#"
+        (module
+            (import "ic0" "msg_reply" (func $msg_reply))
+            (import "ic0" "msg_reply_data_append"
+                (func $msg_reply_data_append (param i64 i64)))
+            (memory i64 1)
+            (data (i64.add (i64.const 8) (i64.const 8)) "abc")
+            (func (export "canister_query read")
+                (call $msg_reply_data_append (i64.const 16) (i64.const 3))
+                (call $msg_reply)
+            )
+        )"#;
+    let canister_id = test.canister_from_wat(wat).unwrap();
+    let result = test.ingress(canister_id, "read", vec![]).unwrap();
+    assert_eq!(result, WasmResult::Reply(b"abc".to_vec()));
+}
+
+#[test]
+fn wasm64_memory_grow_beyond_4gb_works() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_wasm64()
+        .with_initial_canister_cycles(3_000_000_000_000)
+        .build();
+    let wat = r#"
+        (module
+            (memory i64 1)
+            (func (export "canister_update test")
+                ;; Grow the memory to 4 GiB + 1 page.
+                (if (i64.ne (memory.grow (i64.const 65536)) (i64.const 1))
+                    (then (unreachable))
+                )
+                ;; Write and read back a value above the 4 GiB boundary.
+                (i64.store (i64.const 4294967296) (i64.const 42))
+                (if (i64.ne (i64.load (i64.const 4294967296)) (i64.const 42))
+                    (then (unreachable))
+                )
+            )
+        )"#;
+    let canister_id = test.canister_from_wat(wat).unwrap();
+    let result = test.ingress(canister_id, "test", vec![]);
+    assert_empty_reply(result);
+    assert_eq!(
+        test.execution_state(canister_id).wasm_memory.size,
+        NumWasmPages::new(65537)
+    );
+}
+
 #[test]
 fn ic0_stable64_grow_works() {
     let mut test = ExecutionTestBuilder::new().build();
@@ -2264,6 +2317,118 @@ fn ic0_trap_works() {
     );
 }
 
+#[test]
+fn wasm_trap_has_no_backtrace_by_default() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let wat = r#"
+        (module
+            (func $inner (unreachable))
+            (func $test (export "canister_update test") (call $inner))
+        )"#;
+    let canister_id = test.canister_from_wat(wat).unwrap();
+    let err = test.ingress(canister_id, "test", vec![]).unwrap_err();
+    assert_eq!(ErrorCode::CanisterTrapped, err.code());
+    assert_eq!(
+        format!("Canister {} trapped: unreachable", canister_id),
+        err.description()
+    );
+}
+
+#[test]
+fn wasm_trap_reports_symbolized_backtrace() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_canister_backtrace(FlagStatus::Enabled)
+        .with_canister_logging(FlagStatus::Enabled)
+        .build();
+    let wat = r#"
+        (module
+            (import "ic0" "msg_reply" (func $msg_reply))
+            (func $inner (unreachable))
+            (func (call $inner))
+            (func $test (export "canister_update test") (call 2))
+        )"#;
+    let canister_id = test.canister_from_wat(wat).unwrap();
+    let err = test.ingress(canister_id, "test", vec![]).unwrap_err();
+    assert_eq!(ErrorCode::CanisterTrapped, err.code());
+    let backtrace = "Canister Backtrace:\ninner\nunknown function at index 2\ntest";
+    assert_eq!(
+        format!(
+            "Canister {} trapped: unreachable\n{}",
+            canister_id, backtrace
+        ),
+        err.description()
+    );
+    let log: Vec<_> = test
+        .canister_state(canister_id)
+        .system_state
+        .canister_log
+        .records()
+        .iter()
+        .map(|record| String::from_utf8(record.content.clone()).unwrap())
+        .collect();
+    assert_eq!(log, vec![format!("[TRAP]: unreachable\n{}", backtrace)]);
+}
+
+const WASM_PROFILER_TEST_WAT: &str = r#"
+    (module
+        (import "ic0" "msg_reply" (func $msg_reply))
+        (func $leaf (drop (i32.add (i32.const 1) (i32.const 2))))
+        (func $middle (call $leaf) (call $leaf))
+        (func $test (export "canister_update test")
+            (call $middle)
+            (call $leaf)
+            (call $msg_reply)
+        )
+    )"#;
+
+#[test]
+fn wasm_profiler_records_call_stacks() {
+    let mut test = ExecutionTestBuilder::new().with_wasm_profiler().build();
+    let canister_id = test.canister_from_wat(WASM_PROFILER_TEST_WAT).unwrap();
+    let profiler = test.hypervisor_deprecated().wasm_profiler().unwrap();
+    profiler.clear();
+    let result = test.ingress(canister_id, "test", vec![]).unwrap();
+    assert_eq!(result, WasmResult::Reply(vec![]));
+
+    let folded_stacks = profiler.folded_stacks();
+    let stacks: BTreeMap<&str, u64> = folded_stacks
+        .lines()
+        .map(|line| {
+            let (stack, instructions) = line.rsplit_once(' ').unwrap();
+            (stack, instructions.parse().unwrap())
+        })
+        .collect();
+    let expected: Vec<String> = ["test", "test;middle", "test;middle;leaf", "test;leaf"]
+        .iter()
+        .map(|stack| format!("{};{}", canister_id, stack))
+        .collect();
+    let mut expected: Vec<&str> = expected.iter().map(|stack| stack.as_str()).collect();
+    expected.sort();
+    assert_eq!(stacks.keys().copied().collect::<Vec<_>>(), expected);
+    assert!(stacks.values().all(|instructions| *instructions > 0));
+    // `leaf` is called twice from `middle`.
+    assert_eq!(
+        stacks[format!("{};test;middle;leaf", canister_id).as_str()],
+        2 * stacks[format!("{};test;leaf", canister_id).as_str()]
+    );
+}
+
+#[test]
+fn wasm_profiler_does_not_change_instructions_executed() {
+    let mut executed_instructions = vec![];
+    for builder in [
+        ExecutionTestBuilder::new(),
+        ExecutionTestBuilder::new().with_wasm_profiler(),
+    ] {
+        let mut test = builder.build();
+        let canister_id = test.canister_from_wat(WASM_PROFILER_TEST_WAT).unwrap();
+        let before = test.canister_executed_instructions(canister_id);
+        test.ingress(canister_id, "test", vec![]).unwrap();
+        executed_instructions.push(test.canister_executed_instructions(canister_id) - before);
+    }
+    assert_eq!(executed_instructions[0], executed_instructions[1]);
+}
+
 #[test]
 fn globals_are_updated() {
     let mut test = ExecutionTestBuilder::new().build();
@@ -6306,7 +6471,10 @@ fn upgrade_with_skip_pre_upgrade_preserves_stable_memory() {
     test.upgrade_canister_v2(
         canister_id,
         wat::parse_str(wat.clone()).unwrap(),
-        Some(SkipPreUpgrade(Some(true))),
+        Some(CanisterUpgradeOptions {
+            skip_pre_upgrade: Some(true),
+            wasm_memory_persistence: None,
+        }),
     )
     .unwrap();
 
@@ -6315,7 +6483,10 @@ fn upgrade_with_skip_pre_upgrade_preserves_stable_memory() {
         .upgrade_canister_v2(
             canister_id,
             wat::parse_str(wat).unwrap(),
-            Some(SkipPreUpgrade(Some(false))),
+            Some(CanisterUpgradeOptions {
+                skip_pre_upgrade: Some(false),
+                wasm_memory_persistence: None,
+            }),
         )
         .unwrap_err();
     assert_eq!(ErrorCode::CanisterTrapped, err.code());
@@ -6324,6 +6495,167 @@ fn upgrade_with_skip_pre_upgrade_preserves_stable_memory() {
     assert_eq!(result, Ok(WasmResult::Reply("abcd".as_bytes().to_vec())));
 }
 
+const WASM_MEMORY_WAT: &str = r#"
+    (module
+        (import "ic0" "msg_reply" (func $msg_reply))
+        (import "ic0" "msg_reply_data_append"
+            (func $msg_reply_data_append (param i32) (param i32)))
+        (func (export "canister_update write")
+            (i32.store (i32.const 0) (i32.const 0x64636261)) ;; "abcd"
+            (call $msg_reply)
+        )
+        (func (export "canister_update read")
+            (call $msg_reply_data_append (i32.const 0) (i32.const 4))
+            (call $msg_reply)
+        )
+        (memory 1)
+    )"#;
+
+/// Returns the given module with an appended private custom section that
+/// declares support for keeping the Wasm memory on upgrade.
+fn wasm_with_enhanced_orthogonal_persistence(wat: &str) -> Vec<u8> {
+    let name = b"icp:private enhanced-orthogonal-persistence";
+    let mut wasm = wat::parse_str(wat).unwrap();
+    // Section id, section size, name length, name.
+    wasm.extend_from_slice(&[0, name.len() as u8 + 1, name.len() as u8]);
+    wasm.extend_from_slice(name);
+    wasm
+}
+
+fn keep_wasm_memory() -> Option<CanisterUpgradeOptions> {
+    Some(CanisterUpgradeOptions {
+        skip_pre_upgrade: None,
+        wasm_memory_persistence: Some(WasmMemoryPersistence::Keep),
+    })
+}
+
+#[test]
+fn upgrade_with_wasm_memory_persistence_keep_preserves_wasm_memory() {
+    let mut test: ExecutionTest = ExecutionTestBuilder::new().build();
+    let canister_id = test.canister_from_wat(WASM_MEMORY_WAT).unwrap();
+    let result = test.ingress(canister_id, "write", vec![]);
+    assert_empty_reply(result);
+
+    test.upgrade_canister_v2(
+        canister_id,
+        wasm_with_enhanced_orthogonal_persistence(WASM_MEMORY_WAT),
+        keep_wasm_memory(),
+    )
+    .unwrap();
+    let result = test.ingress(canister_id, "read", vec![]);
+    assert_eq!(result, Ok(WasmResult::Reply("abcd".as_bytes().to_vec())));
+
+    // Without the option the Wasm memory is replaced.
+    test.upgrade_canister_v2(
+        canister_id,
+        wasm_with_enhanced_orthogonal_persistence(WASM_MEMORY_WAT),
+        Some(CanisterUpgradeOptions {
+            skip_pre_upgrade: None,
+            wasm_memory_persistence: Some(WasmMemoryPersistence::Replace),
+        }),
+    )
+    .unwrap();
+    let result = test.ingress(canister_id, "read", vec![]);
+    assert_eq!(result, Ok(WasmResult::Reply(vec![0; 4])));
+}
+
+#[test]
+fn upgrade_with_wasm_memory_persistence_keep_preserves_grown_wasm_memory() {
+    let mut test: ExecutionTest = ExecutionTestBuilder::new().build();
+    let canister_id = test.canister_from_wat(WASM_MEMORY_WAT).unwrap();
+    let mut execution_state = test.execution_state(canister_id).clone();
+    execution_state.wasm_memory.size = NumWasmPages::new(5);
+    test.canister_state_mut(canister_id).execution_state = Some(execution_state);
+
+    test.upgrade_canister_v2(
+        canister_id,
+        wasm_with_enhanced_orthogonal_persistence(WASM_MEMORY_WAT),
+        keep_wasm_memory(),
+    )
+    .unwrap();
+    assert_eq!(
+        test.execution_state(canister_id).wasm_memory.size,
+        NumWasmPages::new(5)
+    );
+}
+
+#[test]
+fn upgrade_with_wasm_memory_persistence_keep_fails_without_declaration() {
+    let mut test: ExecutionTest = ExecutionTestBuilder::new().build();
+    let canister_id = test.canister_from_wat(WASM_MEMORY_WAT).unwrap();
+    let result = test.ingress(canister_id, "write", vec![]);
+    assert_empty_reply(result);
+    let execution_state_before = test.execution_state(canister_id).clone();
+
+    let err = test
+        .upgrade_canister_v2(
+            canister_id,
+            wat::parse_str(WASM_MEMORY_WAT).unwrap(),
+            keep_wasm_memory(),
+        )
+        .unwrap_err();
+    assert_eq!(ErrorCode::CanisterContractViolation, err.code());
+    assert!(err
+        .description()
+        .contains("Invalid upgrade option: The `wasm_memory_persistence: keep` option"));
+    assert_eq!(&execution_state_before, test.execution_state(canister_id));
+}
+
+#[test]
+fn upgrade_with_wasm_memory_persistence_keep_fails_on_memory64_mismatch() {
+    let mut test: ExecutionTest = ExecutionTestBuilder::new().with_wasm64().build();
+    let canister_id = test.canister_from_wat(WASM_MEMORY_WAT).unwrap();
+    let execution_state_before = test.execution_state(canister_id).clone();
+
+    let err = test
+        .upgrade_canister_v2(
+            canister_id,
+            wasm_with_enhanced_orthogonal_persistence("(module (memory i64 1))"),
+            keep_wasm_memory(),
+        )
+        .unwrap_err();
+    assert_eq!(ErrorCode::CanisterContractViolation, err.code());
+    assert!(err
+        .description()
+        .contains("declares a 32-bit memory like the old module"));
+    assert_eq!(&execution_state_before, test.execution_state(canister_id));
+}
+
+#[test]
+fn upgrade_with_wasm_memory_persistence_keep_fails_if_maximum_is_below_memory_size() {
+    let mut test: ExecutionTest = ExecutionTestBuilder::new().build();
+    let canister_id = test.canister_from_wat(WASM_MEMORY_WAT).unwrap();
+    let mut execution_state = test.execution_state(canister_id).clone();
+    execution_state.wasm_memory.size = NumWasmPages::new(5);
+    test.canister_state_mut(canister_id).execution_state = Some(execution_state);
+    let execution_state_before = test.execution_state(canister_id).clone();
+
+    let err = test
+        .upgrade_canister_v2(
+            canister_id,
+            wasm_with_enhanced_orthogonal_persistence("(module (memory 1 2))"),
+            keep_wasm_memory(),
+        )
+        .unwrap_err();
+    assert_eq!(ErrorCode::CanisterContractViolation, err.code());
+    assert!(err.description().contains(
+        "declares a maximum memory size of at least 5 Wasm pages, but it declares 2 Wasm pages"
+    ));
+    assert_eq!(&execution_state_before, test.execution_state(canister_id));
+
+    // A maximum that covers the kept memory is accepted.
+    test.upgrade_canister_v2(
+        canister_id,
+        wasm_with_enhanced_orthogonal_persistence("(module (memory 1 5))"),
+        keep_wasm_memory(),
+    )
+    .unwrap();
+    assert_eq!(
+        test.execution_state(canister_id).wasm_memory.size,
+        NumWasmPages::new(5)
+    );
+}
+
 #[test]
 fn resource_saturation_scaling_works_in_regular_execution() {
     const CYCLES: Cycles = Cycles::new(20_000_000_000_000);
@@ -6534,3 +6866,86 @@ fn stable_memory_grow_does_not_reserve_cycles_on_out_of_memory() {
         .reserved_balance();
     assert_eq!(reserved_cycles_before, reserved_cycles_after);
 }
+
+const WASM_MEMORY_GROW_WAT: &str = r#"
+    (module
+        (import "ic0" "msg_reply" (func $msg_reply))
+        (func $grow
+            (if (i32.eq (memory.grow (i32.const 10)) (i32.const -1))
+              (then (unreachable))
+            )
+        )
+        (func $update
+            (call $grow)
+            (call $msg_reply)
+        )
+        (memory $memory 1)
+        (export "canister_update update" (func $update))
+        (export "canister_post_upgrade" (func $grow))
+    )"#;
+
+#[test]
+fn memory_grow_respects_wasm_memory_limit() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let canister_id = test.canister_from_wat(WASM_MEMORY_GROW_WAT).unwrap();
+
+    // The update grows the memory from 1 to 11 Wasm pages.
+    test.canister_update_wasm_memory_limit(
+        canister_id,
+        NumBytes::new(10 * WASM_PAGE_SIZE_IN_BYTES as u64),
+    )
+    .unwrap();
+    let err = test.ingress(canister_id, "update", vec![]).unwrap_err();
+    assert_eq!(err.code(), ErrorCode::CanisterWasmMemoryLimitExceeded);
+    assert!(err.description().contains("exceeds its Wasm memory limit"));
+
+    test.canister_update_wasm_memory_limit(
+        canister_id,
+        NumBytes::new(11 * WASM_PAGE_SIZE_IN_BYTES as u64),
+    )
+    .unwrap();
+    let result = test.ingress(canister_id, "update", vec![]).unwrap();
+    assert_eq!(result, WasmResult::Reply(vec![]));
+}
+
+#[test]
+fn wasm_memory_limit_is_not_enforced_during_upgrade() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let canister_id = test.canister_from_wat(WASM_MEMORY_GROW_WAT).unwrap();
+    test.canister_update_wasm_memory_limit(
+        canister_id,
+        NumBytes::new(WASM_PAGE_SIZE_IN_BYTES as u64),
+    )
+    .unwrap();
+    test.upgrade_canister(canister_id, wat::parse_str(WASM_MEMORY_GROW_WAT).unwrap())
+        .unwrap();
+    assert_eq!(
+        test.execution_state(canister_id).wasm_memory.size,
+        NumWasmPages::new(11)
+    );
+}
+
+#[test]
+fn table_grow_is_not_subject_to_wasm_memory_limit() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let wat = r#"
+        (module
+            (import "ic0" "msg_reply" (func $msg_reply))
+            (table $table 1 funcref)
+            (func (export "canister_update update")
+                (if (i32.eq (table.grow $table (ref.null func) (i32.const 100)) (i32.const -1))
+                  (then (unreachable))
+                )
+                (call $msg_reply)
+            )
+            (memory $memory 1)
+        )"#;
+    let canister_id = test.canister_from_wat(wat).unwrap();
+    test.canister_update_wasm_memory_limit(
+        canister_id,
+        NumBytes::new(WASM_PAGE_SIZE_IN_BYTES as u64),
+    )
+    .unwrap();
+    let result = test.ingress(canister_id, "update", vec![]).unwrap();
+    assert_eq!(result, WasmResult::Reply(vec![]));
+}
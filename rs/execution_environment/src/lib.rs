@@ -14,6 +14,7 @@ mod query_handler;
 mod scheduler;
 mod types;
 pub mod util;
+mod wasm_profiler;
 
 use query_handler::query_stats::QueryStatsPayloadBuilderParams;
 // We need to expose this for testing purposes
@@ -32,8 +33,8 @@ use ic_config::{execution_environment::Config, subnet_config::SchedulerConfig};
 use ic_cycles_account_manager::CyclesAccountManager;
 use ic_interfaces::execution_environment::AnonymousQueryService;
 use ic_interfaces::execution_environment::{
-    IngressFilter, IngressFilterService, IngressHistoryReader, IngressHistoryWriter,
-    QueryExecutionService, QueryHandler, Scheduler,
+    CrossSubnetQueryClient, CrossSubnetQueryService, IngressFilter, IngressFilterService,
+    IngressHistoryReader, IngressHistoryWriter, QueryExecutionService, QueryHandler, Scheduler,
 };
 use ic_interfaces_state_manager::StateReader;
 use ic_logger::ReplicaLogger;
@@ -45,10 +46,13 @@ use ic_types::{messages::CallContextId, SubnetId};
 use ingress_filter::IngressFilterImpl;
 pub use metrics::IngressFilterMetrics;
 pub use query_handler::InternalHttpQueryHandler;
-use query_handler::{HttpQueryHandler, QueryScheduler, QuerySchedulerFlag};
+use query_handler::{
+    CrossSubnetQueryHandler, HttpQueryHandler, QueryScheduler, QuerySchedulerFlag,
+};
 pub use scheduler::RoundSchedule;
 use scheduler::SchedulerImpl;
 use std::sync::Arc;
+pub use wasm_profiler::WasmProfiler;
 
 /// When executing a wasm method of query type, this enum indicates if we are
 /// running in an replicated or non-replicated context. This information is
@@ -90,8 +94,12 @@ pub struct ExecutionServices {
     pub sync_query_handler: Arc<dyn QueryHandler<State = ReplicatedState>>,
     pub async_query_handler: QueryExecutionService,
     pub anonymous_query_handler: AnonymousQueryService,
+    pub cross_subnet_query_handler: CrossSubnetQueryService,
     pub scheduler: Box<dyn Scheduler<State = ReplicatedState>>,
     pub query_stats_payload_builder: QueryStatsPayloadBuilderParams,
+    /// Collects the Wasm profiles of all executions if the Wasm profiler is
+    /// enabled in the embedders config.
+    pub wasm_profiler: Option<Arc<WasmProfiler>>,
 }
 
 impl ExecutionServices {
@@ -108,6 +116,7 @@ impl ExecutionServices {
         cycles_account_manager: Arc<CyclesAccountManager>,
         state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
         fd_factory: Arc<dyn PageAllocatorFileDescriptor>,
+        cross_subnet_query_client: Option<Arc<dyn CrossSubnetQueryClient>>,
     ) -> ExecutionServices {
         let hypervisor = Arc::new(Hypervisor::new(
             config.clone(),
@@ -145,6 +154,7 @@ impl ExecutionServices {
             scheduler_config.heap_delta_rate_limit,
             scheduler_config.upload_wasm_chunk_instructions,
         ));
+        let wasm_profiler = hypervisor.wasm_profiler();
         let sync_query_handler = Arc::new(InternalHttpQueryHandler::new(
             logger.clone(),
             hypervisor,
@@ -154,6 +164,7 @@ impl ExecutionServices {
             scheduler_config.max_instructions_per_message_without_dts,
             Arc::clone(&cycles_account_manager),
             query_stats_collector,
+            cross_subnet_query_client,
         ));
 
         let query_scheduler = QueryScheduler::new(
@@ -181,6 +192,11 @@ impl ExecutionServices {
             Arc::clone(&exec_env),
             ingress_filter_metrics.clone(),
         );
+        let cross_subnet_query_handler = CrossSubnetQueryHandler::new_service(
+            Arc::clone(&sync_query_handler),
+            query_scheduler.clone(),
+            Arc::clone(&state_reader),
+        );
         let anonymous_query_handler = AnonymousQueryHandler::new_service(
             query_scheduler,
             Arc::clone(&state_reader),
@@ -210,8 +226,10 @@ impl ExecutionServices {
             sync_query_handler,
             async_query_handler,
             anonymous_query_handler,
+            cross_subnet_query_handler,
             scheduler,
             query_stats_payload_builder,
+            wasm_profiler,
         }
     }
 
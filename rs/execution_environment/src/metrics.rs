@@ -43,6 +43,7 @@ pub(crate) struct QueryHandlerMetrics {
     pub query_initial_call: ScopedMetrics,
     pub query_retry_call: ScopedMetrics,
     pub query_spawned_calls: ScopedMetrics,
+    pub cross_subnet_query: ScopedMetrics,
     pub query_critical_error: IntCounter,
 }
 
@@ -147,6 +148,32 @@ impl QueryHandlerMetrics {
                     metrics_registry,
                 ),
             },
+            cross_subnet_query: ScopedMetrics {
+                duration: duration_histogram(
+                    "execution_cross_subnet_query_duration_seconds",
+                    "The duration of handling query calls from composite \
+                    queries on other subnets",
+                    metrics_registry,
+                ),
+                instructions: instructions_histogram(
+                    "execution_cross_subnet_query_instructions",
+                    "The number of instructions executed in handling query \
+                    calls from composite queries on other subnets",
+                    metrics_registry,
+                ),
+                slices: slices_histogram(
+                    "execution_cross_subnet_query_slices",
+                    "The number of slices executed in handling query calls \
+                    from composite queries on other subnets",
+                    metrics_registry,
+                ),
+                messages: messages_histogram(
+                    "execution_cross_subnet_query_messages",
+                    "The number of messages executed in handling query calls \
+                    from composite queries on other subnets",
+                    metrics_registry,
+                ),
+            },
             query_critical_error: metrics_registry.error_counter(QUERY_HANDLER_CRITICAL_ERROR),
         }
     }
@@ -21,7 +21,8 @@ use ic_crypto_tree_hash::{flatmap, Label, LabeledTree, LabeledTree::SubTree};
 use ic_cycles_account_manager::CyclesAccountManager;
 use ic_error_types::{ErrorCode, RejectCode, UserError};
 use ic_interfaces::execution_environment::{
-    QueryExecutionError, QueryExecutionResponse, QueryExecutionService, QueryHandler,
+    CrossSubnetQueryClient, CrossSubnetQueryService, QueryExecutionError, QueryExecutionResponse,
+    QueryExecutionService, QueryHandler,
 };
 use ic_interfaces_state_manager::{Labeled, StateReader};
 use ic_logger::ReplicaLogger;
@@ -33,8 +34,8 @@ use ic_types::QueryStatsEpoch;
 use ic_types::{
     ingress::WasmResult,
     messages::{
-        Blob, Certificate, CertificateDelegation, HttpQueryResponse, HttpQueryResponseReply,
-        UserQuery,
+        Blob, Certificate, CertificateDelegation, CrossSubnetQuery, CrossSubnetQueryResponse,
+        HttpQueryResponse, HttpQueryResponseReply, RejectContext, UserQuery,
     },
     CanisterId, NumInstructions,
 };
@@ -46,6 +47,7 @@ use std::{
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
+    time::Duration,
 };
 use tokio::sync::oneshot;
 use tower::{util::BoxCloneService, Service};
@@ -108,6 +110,7 @@ pub struct InternalHttpQueryHandler {
     cycles_account_manager: Arc<CyclesAccountManager>,
     local_query_execution_stats: QueryStatsCollector,
     query_cache: query_cache::QueryCache,
+    cross_subnet_query_client: Option<Arc<dyn CrossSubnetQueryClient>>,
 }
 
 #[derive(Clone)]
@@ -128,6 +131,7 @@ impl InternalHttpQueryHandler {
         max_instructions_per_query: NumInstructions,
         cycles_account_manager: Arc<CyclesAccountManager>,
         local_query_execution_stats: QueryStatsCollector,
+        cross_subnet_query_client: Option<Arc<dyn CrossSubnetQueryClient>>,
     ) -> Self {
         let query_cache_capacity = config.query_cache_capacity;
         Self {
@@ -140,9 +144,101 @@ impl InternalHttpQueryHandler {
             cycles_account_manager,
             local_query_execution_stats,
             query_cache: query_cache::QueryCache::new(metrics_registry, query_cache_capacity),
+            cross_subnet_query_client,
         }
     }
 
+    /// Returns the client for forwarding query calls to canisters on other
+    /// subnets, if cross-subnet composite queries are enabled.
+    fn cross_subnet_query_client(&self) -> Option<&dyn CrossSubnetQueryClient> {
+        match self.config.cross_subnet_composite_queries {
+            FlagStatus::Enabled => self.cross_subnet_query_client.as_deref(),
+            FlagStatus::Disabled => None,
+        }
+    }
+
+    /// Returns the total instruction limit of a query that runs across multiple
+    /// slices, if deterministic time slicing of queries is enabled.
+    fn max_instructions_per_query_with_dts(&self) -> Option<NumInstructions> {
+        match (
+            self.config.deterministic_time_slicing,
+            self.config.query_deterministic_time_slicing,
+        ) {
+            (FlagStatus::Enabled, FlagStatus::Enabled) => {
+                Some(self.config.max_instructions_per_query_with_dts)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the instruction limit of a query call graph, which is at least
+    /// the total instruction limit of a single query.
+    fn max_query_call_graph_instructions(&self) -> NumInstructions {
+        match self.max_instructions_per_query_with_dts() {
+            Some(max_instructions_per_query_with_dts) => self
+                .config
+                .max_query_call_graph_instructions
+                .max(max_instructions_per_query_with_dts),
+            None => self.config.max_query_call_graph_instructions,
+        }
+    }
+
+    /// Executes a query call that a composite query on another subnet made to
+    /// a canister on this subnet, within the budget that the caller's subnet
+    /// has left for the query call graph.
+    pub fn cross_subnet_query(
+        &self,
+        query: CrossSubnetQuery,
+        state: Labeled<Arc<ReplicatedState>>,
+    ) -> CrossSubnetQueryResponse {
+        if self.config.cross_subnet_composite_queries == FlagStatus::Disabled {
+            let error = UserError::new(
+                ErrorCode::CanisterContractViolation,
+                "Cross-subnet composite queries are not enabled",
+            );
+            return CrossSubnetQueryResponse {
+                result: Ok(ic_types::messages::Payload::Reject(RejectContext::from(
+                    error,
+                ))),
+                instructions_executed: 0,
+            };
+        }
+
+        let measurement_scope = MeasurementScope::root(&self.metrics.cross_subnet_query);
+        let receiver = query.receiver;
+        let mut context = query_context::QueryContext::new(
+            &self.log,
+            self.hypervisor.as_ref(),
+            self.own_subnet_type,
+            state,
+            // Only the root canister of a query call graph gets a certificate.
+            None,
+            subnet_memory_capacity(&self.config),
+            self.config.max_canister_memory_size,
+            self.max_instructions_per_query,
+            self.max_instructions_per_query_with_dts(),
+            self.config
+                .max_query_call_graph_depth
+                .min(query.max_call_graph_depth as usize),
+            self.max_query_call_graph_instructions()
+                .min(NumInstructions::from(query.max_instructions)),
+            self.config
+                .max_query_call_walltime
+                .min(Duration::from_millis(query.max_walltime_millis)),
+            self.config.instruction_overhead_per_query_call,
+            self.config.composite_queries,
+            receiver,
+            &self.metrics.query_critical_error,
+            if self.config.query_stats_aggregation == FlagStatus::Enabled {
+                Some(&self.local_query_execution_stats)
+            } else {
+                None
+            },
+            self.cross_subnet_query_client(),
+        );
+        context.run_cross_subnet_query(query, &measurement_scope)
+    }
+
     /// Get query stas for given canister from query stats collector.
     ///
     /// This is used in testing.
@@ -250,12 +346,13 @@ impl QueryHandler for InternalHttpQueryHandler {
             self.hypervisor.as_ref(),
             self.own_subnet_type,
             state,
-            data_certificate,
+            Some(data_certificate),
             subnet_available_memory,
             max_canister_memory_size,
             self.max_instructions_per_query,
+            self.max_instructions_per_query_with_dts(),
             self.config.max_query_call_graph_depth,
-            self.config.max_query_call_graph_instructions,
+            self.max_query_call_graph_instructions(),
             self.config.max_query_call_walltime,
             self.config.instruction_overhead_per_query_call,
             self.config.composite_queries,
@@ -266,6 +363,7 @@ impl QueryHandler for InternalHttpQueryHandler {
             } else {
                 None
             },
+            self.cross_subnet_query_client(),
         );
 
         let result = context.run(
@@ -384,3 +482,56 @@ impl Service<(UserQuery, Option<CertificateDelegation>)> for HttpQueryHandler {
         })
     }
 }
+
+#[derive(Clone)]
+/// Struct that is responsible for handling query calls that composite queries
+/// on other subnets make to canisters on this subnet.
+pub(crate) struct CrossSubnetQueryHandler {
+    internal: Arc<InternalHttpQueryHandler>,
+    state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
+    query_scheduler: QueryScheduler,
+}
+
+impl CrossSubnetQueryHandler {
+    pub(crate) fn new_service(
+        internal: Arc<InternalHttpQueryHandler>,
+        query_scheduler: QueryScheduler,
+        state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
+    ) -> CrossSubnetQueryService {
+        BoxCloneService::new(Self {
+            internal,
+            state_reader,
+            query_scheduler,
+        })
+    }
+}
+
+impl Service<CrossSubnetQuery> for CrossSubnetQueryHandler {
+    type Response = CrossSubnetQueryResponse;
+    type Error = Infallible;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, query: CrossSubnetQuery) -> Self::Future {
+        let internal = Arc::clone(&self.internal);
+        let state_reader = Arc::clone(&self.state_reader);
+        let (tx, rx) = oneshot::channel();
+        let canister_id = query.receiver;
+        self.query_scheduler.push(canister_id, move || {
+            let start = std::time::Instant::now();
+            if !tx.is_closed() {
+                let state = state_reader.get_latest_state();
+                let _ = tx.send(Ok(internal.cross_subnet_query(query, state)));
+            }
+            start.elapsed()
+        });
+        Box::pin(async move {
+            rx.await
+                .expect("The sender was dropped before sending the message.")
+        })
+    }
+}
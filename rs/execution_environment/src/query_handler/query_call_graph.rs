@@ -43,6 +43,10 @@ struct PendingCall(CanisterState, CallOrigin, VecDeque<Arc<Request>>);
 /// - the limit on the total number of executed instructions by all queries and
 ///   response callbacks.
 ///
+/// Query calls to canisters on other subnets are forwarded together with what
+/// is left of these limits and of the walltime, so that they bound the whole
+/// query call graph across subnets.
+///
 /// A note on re-entrancy: currently re-entrant query calls are not allowed.
 /// In other words, if a canister is in the call stack, then an attempt to make a
 /// new query call to that canister will result in an error. This restriction
@@ -122,7 +126,12 @@ pub(super) fn evaluate_query_call_graph(
                     // properly handle the response of the callee.
                     call_stack.push(PendingCall(canister, call_origin, requests));
 
-                    match query_context.handle_request(request, measurement_scope) {
+                    // The callee and its sub-graph may nest the remaining
+                    // number of query calls.
+                    let max_callee_depth =
+                        max_query_call_graph_depth.saturating_sub(call_stack.len());
+                    match query_context.handle_request(request, max_callee_depth, measurement_scope)
+                    {
                         ExecutionResult::Calls(canister, call_origin, requests) => {
                             call_stack.push(PendingCall(canister, call_origin, requests));
                         }
@@ -11,7 +11,9 @@ use ic_config::flag_status::FlagStatus;
 use ic_constants::SMALL_APP_SUBNET_MAX_SIZE;
 use ic_cycles_account_manager::{CyclesAccountManager, ResourceSaturation};
 use ic_error_types::{ErrorCode, RejectCode, UserError};
-use ic_interfaces::execution_environment::{ExecutionMode, HypervisorError, SubnetAvailableMemory};
+use ic_interfaces::execution_environment::{
+    CrossSubnetQueryClient, ExecutionMode, HypervisorError, SubnetAvailableMemory,
+};
 use ic_interfaces_state_manager::Labeled;
 use ic_logger::{error, ReplicaLogger};
 use ic_registry_subnet_type::SubnetType;
@@ -23,20 +25,23 @@ use ic_types::{
     epoch_from_height,
     ingress::WasmResult,
     messages::{
-        CallContextId, CallbackId, Payload, RejectContext, Request, RequestOrResponse, Response,
-        UserQuery,
+        CallContextId, CallbackId, CrossSubnetQuery, CrossSubnetQueryResponse, Payload,
+        RejectContext, Request, RequestOrResponse, Response, UserQuery, NO_DEADLINE,
     },
     methods::WasmMethod,
-    CanisterId, Cycles, NumInstructions, NumMessages, Time,
+    CanisterId, Cycles, NumInstructions, NumMessages, SubnetId, Time,
 };
 use ic_types::{
     methods::{FuncRef, WasmClosure},
     NumSlices,
 };
 use prometheus::IntCounter;
-use std::{collections::VecDeque, sync::Arc, time::Duration, time::Instant};
+use std::{cell::Cell, collections::VecDeque, sync::Arc, time::Duration, time::Instant};
 
-use super::{query_call_graph::evaluate_query_call_graph, query_stats::QueryStatsCollector};
+use super::{
+    query_call_graph::evaluate_query_call_graph, query_scheduler::run_queries_of_other_canisters,
+    query_stats::QueryStatsCollector,
+};
 
 /// The response of a query. If the query originated from a user, then it
 /// contains either `UserResponse` or `UserError`. If the query originated from
@@ -56,6 +61,13 @@ pub(super) enum ExecutionResult {
     SystemError(UserError),
 }
 
+/// Runs queries of other canisters between two slices of a query and adds
+/// the walltime spent on them to `other_queries_walltime`.
+fn run_other_queries(other_queries_walltime: &Cell<Duration>) {
+    let duration = run_queries_of_other_canisters();
+    other_queries_walltime.set(other_queries_walltime.get() + duration);
+}
+
 /// Returns either `WasmMethod::CompositeQuery` or `WasmMethod::Query` depending
 /// on whether the given method name is exported as a composite query or not.
 fn wasm_query_method(
@@ -86,9 +98,13 @@ pub(super) struct QueryContext<'a> {
     state: Labeled<Arc<ReplicatedState>>,
     network_topology: Arc<NetworkTopology>,
     // Certificate for certified queries + canister ID of the root query of this context
-    data_certificate: (Vec<u8>, CanisterId),
+    data_certificate: Option<(Vec<u8>, CanisterId)>,
     max_canister_memory_size: NumBytes,
     max_instructions_per_query: NumInstructions,
+    // The total instruction limit of a query that runs across multiple slices
+    // of `max_instructions_per_query` instructions each. If `None`, queries
+    // run in a single slice.
+    max_instructions_per_query_with_dts: Option<NumInstructions>,
     max_query_call_graph_depth: usize,
     instruction_overhead_per_query_call: RoundInstructions,
     round_limits: RoundLimits,
@@ -96,8 +112,14 @@ pub(super) struct QueryContext<'a> {
     // Walltime at which the query has started to execute.
     query_context_time_start: Instant,
     query_context_time_limit: Duration,
+    // Walltime spent executing queries of other canisters between the slices
+    // of queries in this context. It does not count against the time limit.
+    other_queries_walltime: Cell<Duration>,
     query_critical_error: &'a IntCounter,
     local_query_execution_stats: Option<&'a QueryStatsCollector>,
+    // Forwards query calls to canisters on other subnets. If `None`, such
+    // calls are rejected because the callee is not found.
+    cross_subnet_query_client: Option<&'a dyn CrossSubnetQueryClient>,
 }
 
 impl<'a> QueryContext<'a> {
@@ -107,10 +129,11 @@ impl<'a> QueryContext<'a> {
         hypervisor: &'a Hypervisor,
         own_subnet_type: SubnetType,
         state: Labeled<Arc<ReplicatedState>>,
-        data_certificate: Vec<u8>,
+        data_certificate: Option<Vec<u8>>,
         subnet_available_memory: SubnetAvailableMemory,
         max_canister_memory_size: NumBytes,
         max_instructions_per_query: NumInstructions,
+        max_instructions_per_query_with_dts: Option<NumInstructions>,
         max_query_call_graph_depth: usize,
         max_query_call_graph_instructions: NumInstructions,
         max_query_call_walltime: Duration,
@@ -119,6 +142,7 @@ impl<'a> QueryContext<'a> {
         canister_id: CanisterId,
         query_critical_error: &'a IntCounter,
         local_query_execution_stats: Option<&'a QueryStatsCollector>,
+        cross_subnet_query_client: Option<&'a dyn CrossSubnetQueryClient>,
     ) -> Self {
         let network_topology = Arc::new(state.get_ref().metadata.network_topology.clone());
         let round_limits = RoundLimits {
@@ -133,9 +157,10 @@ impl<'a> QueryContext<'a> {
             own_subnet_type,
             state,
             network_topology,
-            data_certificate: (data_certificate, canister_id),
+            data_certificate: data_certificate.map(|certificate| (certificate, canister_id)),
             max_canister_memory_size,
             max_instructions_per_query,
+            max_instructions_per_query_with_dts,
             max_query_call_graph_depth,
             instruction_overhead_per_query_call: as_round_instructions(
                 instruction_overhead_per_query_call,
@@ -144,8 +169,10 @@ impl<'a> QueryContext<'a> {
             composite_queries,
             query_context_time_start: Instant::now(),
             query_context_time_limit: max_query_call_walltime,
+            other_queries_walltime: Cell::new(Duration::ZERO),
             query_critical_error,
             local_query_execution_stats,
+            cross_subnet_query_client,
         }
     }
 
@@ -351,7 +378,7 @@ impl<'a> QueryContext<'a> {
                 // Messages of these types are not produced by this
                 // module so must have existed on the canister's output
                 // queue from before.
-                RequestOrResponse::Response(_) => {}
+                RequestOrResponse::Response(_) | RequestOrResponse::Fragment(_) => {}
             }
         }
         Ok(())
@@ -377,14 +404,16 @@ impl<'a> QueryContext<'a> {
                 );
             }
         }
-        let instruction_limit = self.max_instructions_per_query.min(NumInstructions::new(
-            self.round_limits.instructions.get().max(0) as u64,
-        ));
-        let instruction_limits =
-            InstructionLimits::new(FlagStatus::Disabled, instruction_limit, instruction_limit);
+        let instruction_limits = self.instruction_limits();
+        let instruction_limit = instruction_limits.message();
         let execution_parameters = self.execution_parameters(&canister, instruction_limits);
 
         let data_certificate = self.get_data_certificate(&canister.canister_id());
+        // Only exported methods are tracked individually, so that calls to
+        // arbitrary method names cannot blow up the size of the stats.
+        let stats_method_name = canister
+            .exports_method(&method_name)
+            .then(|| method_name.name());
         let (mut canister, instructions_left, result, call_context_id) =
             execute_non_replicated_query(
                 query_kind,
@@ -398,6 +427,7 @@ impl<'a> QueryContext<'a> {
                 self.hypervisor,
                 &mut self.round_limits,
                 self.query_critical_error,
+                &|| run_other_queries(&self.other_queries_walltime),
             );
         let instructions_executed = instruction_limit - instructions_left;
 
@@ -410,6 +440,7 @@ impl<'a> QueryContext<'a> {
             },
             Err(_) => 0,
         };
+        let is_error = matches!(result, Ok(Some(WasmResult::Reject(_))) | Err(_));
 
         // Add query statistics to the query aggregator.
         if let Some(query_stats) = self.local_query_execution_stats {
@@ -417,9 +448,11 @@ impl<'a> QueryContext<'a> {
 
             query_stats.register_query_statistics(
                 canister.canister_id(),
+                stats_method_name.as_deref(),
                 instructions_executed,
                 ingress_payload_size as u64,
                 egress_payload_size as u64,
+                is_error,
             );
         }
 
@@ -441,6 +474,27 @@ impl<'a> QueryContext<'a> {
         (canister, result)
     }
 
+    /// Returns the instruction limits for executing a message, given the
+    /// instructions left in the query call graph.
+    fn instruction_limits(&self) -> InstructionLimits {
+        let instructions_left =
+            NumInstructions::new(self.round_limits.instructions.get().max(0) as u64);
+        match self.max_instructions_per_query_with_dts {
+            Some(max_instructions_per_query_with_dts) => {
+                let message_limit = max_instructions_per_query_with_dts.min(instructions_left);
+                InstructionLimits::new(
+                    FlagStatus::Enabled,
+                    message_limit,
+                    self.max_instructions_per_query.min(message_limit),
+                )
+            }
+            None => {
+                let instruction_limit = self.max_instructions_per_query.min(instructions_left);
+                InstructionLimits::new(FlagStatus::Disabled, instruction_limit, instruction_limit)
+            }
+        }
+    }
+
     fn finish(
         &self,
         canister: &mut CanisterState,
@@ -507,7 +561,7 @@ impl<'a> QueryContext<'a> {
         };
         let func_ref = match call_origin {
             CallOrigin::Ingress(_, _)
-            | CallOrigin::CanisterUpdate(_, _)
+            | CallOrigin::CanisterUpdate(_, _, _)
             | CallOrigin::SystemTask => unreachable!("Unreachable in the QueryContext."),
             CallOrigin::CanisterQuery(_, _) | CallOrigin::Query(_) => {
                 FuncRef::QueryClosure(closure)
@@ -518,11 +572,8 @@ impl<'a> QueryContext<'a> {
         // No cycles are refunded in a response to a query call.
         let incoming_cycles = Cycles::zero();
 
-        let instruction_limit = self.max_instructions_per_query.min(NumInstructions::new(
-            self.round_limits.instructions.get().max(0) as u64,
-        ));
-        let instruction_limits =
-            InstructionLimits::new(FlagStatus::Disabled, instruction_limit, instruction_limit);
+        let instruction_limits = self.instruction_limits();
+        let instruction_limit = instruction_limits.message();
         let mut execution_parameters = self.execution_parameters(&canister, instruction_limits);
         let api_type = match response.response_payload {
             Payload::Data(payload) => ApiType::reply_callback(
@@ -547,19 +598,21 @@ impl<'a> QueryContext<'a> {
             ),
         };
 
-        let (output, output_execution_state, output_system_state) = self.hypervisor.execute(
-            api_type,
-            time,
-            canister.system_state.clone(),
-            canister.memory_usage(),
-            canister.message_memory_usage(),
-            execution_parameters.clone(),
-            func_ref,
-            canister.execution_state.take().unwrap(),
-            &self.network_topology,
-            &mut self.round_limits,
-            self.query_critical_error,
-        );
+        let (output, output_execution_state, output_system_state) =
+            self.hypervisor.execute_in_slices(
+                api_type,
+                time,
+                canister.system_state.clone(),
+                canister.memory_usage(),
+                canister.message_memory_usage(),
+                execution_parameters.clone(),
+                func_ref,
+                canister.execution_state.take().unwrap(),
+                &self.network_topology,
+                &mut self.round_limits,
+                self.query_critical_error,
+                &|| run_other_queries(&self.other_queries_walltime),
+            );
 
         let canister_current_memory_usage = canister.memory_usage();
         let canister_current_message_memory_usage = canister.message_memory_usage();
@@ -634,14 +687,14 @@ impl<'a> QueryContext<'a> {
     ) -> (NumInstructions, Result<Option<WasmResult>, HypervisorError>) {
         let func_ref = match call_origin {
             CallOrigin::Ingress(_, _)
-            | CallOrigin::CanisterUpdate(_, _)
+            | CallOrigin::CanisterUpdate(_, _, _)
             | CallOrigin::SystemTask => unreachable!("Unreachable in the QueryContext."),
             CallOrigin::CanisterQuery(_, _) | CallOrigin::Query(_) => {
                 FuncRef::QueryClosure(cleanup_closure)
             }
         };
         let (cleanup_output, output_execution_state, output_system_state) =
-            self.hypervisor.execute(
+            self.hypervisor.execute_in_slices(
                 ApiType::Cleanup {
                     caller: call_origin.get_principal(),
                     time,
@@ -657,6 +710,7 @@ impl<'a> QueryContext<'a> {
                 &self.network_topology,
                 &mut self.round_limits,
                 self.query_critical_error,
+                &|| run_other_queries(&self.other_queries_walltime),
             );
 
         canister.execution_state = Some(output_execution_state);
@@ -688,9 +742,14 @@ impl<'a> QueryContext<'a> {
     /// of outgoing query calls (requests).
     /// If the execution produces a response, then the function returns it and
     /// discards the call context and outgoing requests.
+    ///
+    /// If the callee is hosted on another subnet, then the query call is
+    /// forwarded there, allowing its sub-graph a depth of at most
+    /// `max_call_graph_depth`, and the function returns the response.
     pub fn handle_request(
         &mut self,
         request: Arc<Request>,
+        max_call_graph_depth: usize,
         measurement_scope: &MeasurementScope,
     ) -> ExecutionResult {
         // A handy function to create a `Response` using parameters from the `Request`
@@ -701,11 +760,16 @@ impl<'a> QueryContext<'a> {
                 originator_reply_callback: request.sender_reply_callback,
                 response_payload: payload,
                 refund: Cycles::zero(),
+                deadline: NO_DEADLINE,
             })
         };
 
         let canister_id = request.receiver;
 
+        if let Some((subnet_id, client)) = self.remote_subnet(&canister_id) {
+            return self.forward_request(client, subnet_id, &request, max_call_graph_depth);
+        }
+
         let canister = match self.state.get_ref().get_active_canister(&canister_id) {
             Ok(canister) => canister,
             Err(err) => {
@@ -772,6 +836,142 @@ impl<'a> QueryContext<'a> {
         }
     }
 
+    /// Returns the subnet hosting the given canister, if it is not this subnet
+    /// and query calls can be forwarded to other subnets.
+    fn remote_subnet(
+        &self,
+        canister_id: &CanisterId,
+    ) -> Option<(SubnetId, &'a dyn CrossSubnetQueryClient)> {
+        let client = self.cross_subnet_query_client?;
+        let own_subnet_id = self.state.get_ref().metadata.own_subnet_id;
+        self.network_topology
+            .routing_table
+            .route(canister_id.get())
+            .filter(|subnet_id| *subnet_id != own_subnet_id)
+            .map(|subnet_id| (subnet_id, client))
+    }
+
+    /// Forwards the given query call to a replica of `subnet_id` together with
+    /// the budget that is left for the query call graph, and charges the
+    /// instructions executed there against this query call graph.
+    fn forward_request(
+        &mut self,
+        client: &dyn CrossSubnetQueryClient,
+        subnet_id: SubnetId,
+        request: &Request,
+        max_call_graph_depth: usize,
+    ) -> ExecutionResult {
+        let remaining_walltime = self
+            .query_context_time_limit
+            .saturating_sub(self.elapsed_walltime());
+        let query = CrossSubnetQuery {
+            sender: request.sender,
+            receiver: request.receiver,
+            method_name: request.method_name.clone(),
+            method_payload: request.method_payload.clone(),
+            max_call_graph_depth: max_call_graph_depth as u64,
+            max_instructions: self.round_limits.instructions.get().max(0) as u64,
+            max_walltime_millis: remaining_walltime.as_millis() as u64,
+        };
+
+        self.round_limits.instructions -= self.instruction_overhead_per_query_call;
+        let payload = match client.query(subnet_id, query) {
+            Ok(CrossSubnetQueryResponse {
+                result,
+                instructions_executed,
+            }) => {
+                self.round_limits.instructions -=
+                    as_round_instructions(NumInstructions::from(instructions_executed));
+                match result {
+                    Ok(payload) => payload,
+                    // The sub-graph exceeded a limit of the query call graph
+                    // or failed, which aborts the whole query call graph.
+                    Err(err) => return ExecutionResult::SystemError(err),
+                }
+            }
+            // The query call could not be delivered, which the caller observes
+            // as a reject.
+            Err(err) => Payload::Reject(RejectContext::from(err)),
+        };
+
+        ExecutionResult::Response(QueryResponse::CanisterResponse(Response {
+            originator: request.sender,
+            respondent: request.receiver,
+            originator_reply_callback: request.sender_reply_callback,
+            response_payload: payload,
+            refund: Cycles::zero(),
+            deadline: NO_DEADLINE,
+        }))
+    }
+
+    /// Executes a query call that a composite query on another subnet made to
+    /// a canister on this subnet, along with its outgoing query calls.
+    ///
+    /// The limits of this context are expected to be the minimum of the local
+    /// limits and the budget that the caller's subnet has left.
+    pub(super) fn run_cross_subnet_query(
+        &mut self,
+        query: CrossSubnetQuery,
+        measurement_scope: &MeasurementScope,
+    ) -> CrossSubnetQueryResponse {
+        // The caller's subnet may have used up the depth of the query call
+        // graph, e.g. if the query call graph cycles between subnets.
+        if self.max_query_call_graph_depth == 0 {
+            return CrossSubnetQueryResponse {
+                result: Err(UserError::new(
+                    ErrorCode::QueryCallGraphTooDeep,
+                    "Composite query calls exceeded the maximum call depth.",
+                )),
+                instructions_executed: 0,
+            };
+        }
+
+        let instructions_before = self.round_limits.instructions;
+        let request = Arc::new(Request {
+            receiver: query.receiver,
+            sender: query.sender,
+            // The callback is tracked by the caller's subnet, which matches the
+            // response to the query call it forwarded.
+            sender_reply_callback: CallbackId::from(0),
+            payment: Cycles::zero(),
+            method_name: query.method_name,
+            method_payload: query.method_payload,
+            metadata: None,
+            deadline: NO_DEADLINE,
+        });
+
+        let response = match self.handle_request(
+            request,
+            self.max_query_call_graph_depth,
+            measurement_scope,
+        ) {
+            ExecutionResult::Response(response) => response,
+            ExecutionResult::Calls(canister, call_origin, requests) => evaluate_query_call_graph(
+                self,
+                canister,
+                call_origin,
+                requests,
+                self.max_query_call_graph_depth,
+                measurement_scope,
+            ),
+            ExecutionResult::SystemError(err) => QueryResponse::UserError(err),
+        };
+        let result = match response {
+            QueryResponse::CanisterResponse(response) => Ok(response.response_payload),
+            QueryResponse::UserError(err) => Err(err),
+            QueryResponse::UserResponse(_) => {
+                unreachable!("A canister query cannot produce a user response.");
+            }
+        };
+
+        CrossSubnetQueryResponse {
+            result,
+            instructions_executed: (instructions_before - self.round_limits.instructions)
+                .get()
+                .max(0) as u64,
+        }
+    }
+
     /// Extracts the query result from the call context action.
     fn action_to_result(
         &self,
@@ -853,7 +1053,7 @@ impl<'a> QueryContext<'a> {
             };
 
         match call_origin {
-            CallOrigin::CanisterUpdate(_, _)
+            CallOrigin::CanisterUpdate(_, _, _)
             | CallOrigin::Ingress(_, _)
             | CallOrigin::SystemTask => {
                 error!(
@@ -888,6 +1088,7 @@ impl<'a> QueryContext<'a> {
                         originator_reply_callback: callback_id,
                         refund: Cycles::zero(),
                         response_payload: payload,
+                        deadline: NO_DEADLINE,
                     };
                     QueryResponse::CanisterResponse(response)
                 };
@@ -922,7 +1123,15 @@ impl<'a> QueryContext<'a> {
 
     /// Return whether the time limit for this query context has been reached.
     pub fn time_limit_reached(&self) -> bool {
-        self.query_context_time_start.elapsed() >= self.query_context_time_limit
+        self.elapsed_walltime() >= self.query_context_time_limit
+    }
+
+    // Returns the walltime spent on this query context, excluding the time
+    // spent executing queries of other canisters between slices.
+    fn elapsed_walltime(&self) -> Duration {
+        self.query_context_time_start
+            .elapsed()
+            .saturating_sub(self.other_queries_walltime.get())
     }
 
     /// Returns a synthetic reject response for the case when a query call
@@ -938,7 +1147,7 @@ impl<'a> QueryContext<'a> {
         );
         match call_origin {
             CallOrigin::Ingress(_, _)
-            | CallOrigin::CanisterUpdate(_, _)
+            | CallOrigin::CanisterUpdate(_, _, _)
             | CallOrigin::SystemTask => {
                 unreachable!("Expected a query call context");
             }
@@ -950,6 +1159,7 @@ impl<'a> QueryContext<'a> {
                     originator_reply_callback: callback_id,
                     refund: Cycles::zero(),
                     response_payload: Payload::Reject(RejectContext::from(error)),
+                    deadline: NO_DEADLINE,
                 };
                 QueryResponse::CanisterResponse(response)
             }
@@ -974,10 +1184,11 @@ impl<'a> QueryContext<'a> {
     }
 
     fn get_data_certificate(&self, canister_id: &CanisterId) -> Option<Vec<u8>> {
-        if canister_id != &self.data_certificate.1 {
-            None
-        } else {
-            Some(self.data_certificate.0.clone())
+        match &self.data_certificate {
+            Some((certificate, root_canister_id)) if canister_id == root_canister_id => {
+                Some(certificate.clone())
+            }
+            _ => None,
         }
     }
 }
@@ -11,6 +11,8 @@ use self::{
     thread_pool::QueryThreadPool,
 };
 
+pub(crate) use self::thread_pool::run_queries_of_other_canisters;
+
 mod internal;
 mod thread_pool;
 
@@ -33,6 +35,11 @@ pub(crate) enum QuerySchedulerFlag {
 /// The algorithm also ensures that each canister executes on at most
 /// `max_threads_per_canister` threads, which is necessary to avoid performance
 /// regression due to the memory bottleneck in the sandbox process.
+///
+/// A query that runs across multiple execution slices hands its thread over
+/// to a batch of queries of another canister between two slices (see
+/// `run_queries_of_other_canisters()`), so that long-running queries are
+/// interleaved with the queries of other canisters.
 #[derive(Clone)]
 pub(crate) enum QueryScheduler {
     NewScheduler {
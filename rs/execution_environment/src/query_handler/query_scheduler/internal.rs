@@ -175,7 +175,23 @@ impl QuerySchedulerCore {
 
     /// Returns a batch of queries to execute if there are any.
     fn pop(&mut self) -> Option<(CanisterId, Vec<Query>)> {
-        let canister_id = self.scheduled.pop_front()?;
+        self.pop_first_matching(|_| true)
+    }
+
+    /// Returns a batch of queries of a canister other than the given one to
+    /// execute if there are any.
+    fn pop_other(&mut self, canister_id: CanisterId) -> Option<(CanisterId, Vec<Query>)> {
+        self.pop_first_matching(|other| *other != canister_id)
+    }
+
+    /// Returns a batch of queries of the first canister in the round-robin
+    /// queue that satisfies the given predicate.
+    fn pop_first_matching<P>(&mut self, predicate: P) -> Option<(CanisterId, Vec<Query>)>
+    where
+        P: Fn(&CanisterId) -> bool,
+    {
+        let index = self.scheduled.iter().position(predicate)?;
+        let canister_id = self.scheduled.remove(index)?;
         // It is safe to unwrap here because of the invariants in
         // `validate_invariants()`: each canister in the round-robin list must
         // be present in the canister table.
@@ -324,6 +340,13 @@ impl QuerySchedulerInternal {
         core.pop()
     }
 
+    /// Returns a batch of queries of a canister other than the given one if
+    /// there are any. Unlike `pop()`, this function never blocks.
+    pub fn try_pop_other(&self, canister_id: CanisterId) -> Option<(CanisterId, Vec<Query>)> {
+        let mut core = self.core.lock().unwrap();
+        core.pop_other(canister_id)
+    }
+
     // This is called by the query execution thread after it finished executing
     // a batch of queries.
     pub fn notify_finished_execution(
@@ -249,3 +249,83 @@ fn query_scheduler_properly_reads_leftover_queries() {
 
     assert_eq!(queries.len(), 1);
 }
+
+#[test]
+fn query_scheduler_try_pop_other_skips_given_canister() {
+    let metrics_registry = MetricsRegistry::new();
+    let scheduler = QuerySchedulerInternal::new(2, Duration::from_millis(1), &metrics_registry);
+
+    for c in 0..2 {
+        scheduler.push(
+            canister_test_id(c),
+            Query(Box::new(move || std::time::Duration::from_millis(1))),
+        );
+    }
+
+    let (canister_id, queries) = scheduler.try_pop_other(canister_test_id(0)).unwrap();
+    assert_eq!(canister_test_id(1), canister_id);
+    assert_eq!(queries.len(), 1);
+
+    assert!(scheduler.try_pop_other(canister_test_id(0)).is_none());
+
+    let (canister_id, _) = scheduler.pop().unwrap();
+    assert_eq!(canister_test_id(0), canister_id);
+}
+
+#[test]
+fn query_scheduler_runs_other_canisters_between_slices() {
+    let metrics_registry = MetricsRegistry::new();
+    let scheduler = QueryScheduler::new(
+        1,
+        1,
+        Duration::from_millis(1),
+        &metrics_registry,
+        QuerySchedulerFlag::UseNewSchedulingAlgorithm,
+    );
+    let ready = Arc::new(AtomicU32::default());
+    let execution_count = Arc::new(AtomicU32::default());
+    let schedule = Arc::new(Mutex::new(vec![]));
+
+    {
+        let ready = Arc::clone(&ready);
+        let execution_count = Arc::clone(&execution_count);
+        let schedule = Arc::clone(&schedule);
+        scheduler.push(canister_test_id(0), move || {
+            // Wait until the queries of the other canister have been pushed.
+            while ready.load(Ordering::SeqCst) == 0 {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+            for slice in 0..3 {
+                schedule.lock().unwrap().push((0, slice));
+                super::run_queries_of_other_canisters();
+            }
+            execution_count.fetch_add(1, Ordering::SeqCst);
+            std::time::Duration::from_millis(3)
+        });
+    }
+
+    for i in 0..3 {
+        let execution_count = Arc::clone(&execution_count);
+        let schedule = Arc::clone(&schedule);
+        scheduler.push(canister_test_id(1), move || {
+            let duration = std::time::Duration::from_millis(2);
+            std::thread::sleep(duration);
+            schedule.lock().unwrap().push((1, i));
+            // Nested calls must not run any queries.
+            super::run_queries_of_other_canisters();
+            execution_count.fetch_add(1, Ordering::SeqCst);
+            duration
+        });
+    }
+    ready.store(1, Ordering::SeqCst);
+
+    while execution_count.load(Ordering::SeqCst) < 4 {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    let schedule = schedule.lock().unwrap().clone();
+    assert_eq!(
+        schedule,
+        vec![(0, 0), (1, 0), (0, 1), (1, 1), (0, 2), (1, 2)]
+    );
+}
@@ -1,6 +1,30 @@
-use std::time::Duration;
+use std::{
+    cell::{Cell, RefCell},
+    time::{Duration, Instant},
+};
 
-use super::internal::QuerySchedulerInternal;
+use ic_base_types::CanisterId;
+
+use super::internal::{Query, QuerySchedulerInternal};
+
+/// The batch of queries that a query execution thread is currently executing.
+struct CurrentBatch {
+    scheduler: QuerySchedulerInternal,
+    canister_id: CanisterId,
+    time_slice_per_canister: Duration,
+}
+
+thread_local! {
+    // Set while a thread of the thread-pool executes a batch of queries. It is
+    // taken while the thread executes queries of other canisters on behalf of
+    // a query that runs across multiple slices, so that these queries cannot
+    // hand over the thread again.
+    static CURRENT_BATCH: RefCell<Option<CurrentBatch>> = RefCell::new(None);
+
+    // The walltime that the thread has spent executing queries of other
+    // canisters on behalf of the query that it is currently executing.
+    static NESTED_DURATION: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+}
 
 /// Manages a thread-pool where each thread polls queries from `scheduler` and
 /// executes them. The threads stop when the thread-pool object is dropped.
@@ -50,21 +74,76 @@ fn query_execution_thread(time_slice_per_canister: Duration, scheduler: QuerySch
         match scheduler.pop() {
             None => break,
             Some((canister_id, queries)) => {
-                let mut iter = queries.into_iter();
-                let mut query_duration_sum = Duration::ZERO;
-                let mut query_duration_cnt = 0;
-                for query in iter.by_ref() {
-                    let query_duration = query.execute();
-                    query_duration_sum += query_duration;
-                    query_duration_cnt += 1;
-                    if query_duration_sum >= time_slice_per_canister {
-                        break;
-                    }
-                }
-                let average_query_duration = query_duration_sum / query_duration_cnt.max(1);
-                let leftover = iter.collect();
-                scheduler.notify_finished_execution(canister_id, average_query_duration, leftover)
+                CURRENT_BATCH.with(|current| {
+                    *current.borrow_mut() = Some(CurrentBatch {
+                        scheduler: scheduler.clone(),
+                        canister_id,
+                        time_slice_per_canister,
+                    })
+                });
+                execute_batch(&scheduler, time_slice_per_canister, canister_id, queries);
+                CURRENT_BATCH.with(|current| *current.borrow_mut() = None);
             }
         }
     }
 }
+
+// Executes the given queries one by one until the total execution duration
+// exceeds `time_slice_per_canister` and returns the rest to the scheduler.
+fn execute_batch(
+    scheduler: &QuerySchedulerInternal,
+    time_slice_per_canister: Duration,
+    canister_id: CanisterId,
+    queries: Vec<Query>,
+) {
+    let mut iter = queries.into_iter();
+    let mut query_duration_sum = Duration::ZERO;
+    let mut query_duration_cnt = 0;
+    for query in iter.by_ref() {
+        NESTED_DURATION.with(|nested| nested.set(Duration::ZERO));
+        // The queries of other canisters executed between the slices of this
+        // query are accounted to their own canisters.
+        let query_duration = query
+            .execute()
+            .saturating_sub(NESTED_DURATION.with(|nested| nested.take()));
+        query_duration_sum += query_duration;
+        query_duration_cnt += 1;
+        if query_duration_sum >= time_slice_per_canister {
+            break;
+        }
+    }
+    let average_query_duration = query_duration_sum / query_duration_cnt.max(1);
+    let leftover = iter.collect();
+    scheduler.notify_finished_execution(canister_id, average_query_duration, leftover)
+}
+
+/// Executes one batch of queries of another canister on the current thread,
+/// if there are any.
+///
+/// A query that runs across multiple slices calls this between two slices, so
+/// that it does not hold on to its thread while queries of other canisters are
+/// waiting. Does nothing if the current thread is not executing a batch of
+/// queries or is already executing queries on behalf of another query.
+///
+/// Returns the walltime spent executing the queries of other canisters, which
+/// the caller must not count against its own walltime.
+pub(crate) fn run_queries_of_other_canisters() -> Duration {
+    let Some(batch) = CURRENT_BATCH.with(|current| current.borrow_mut().take()) else {
+        return Duration::ZERO;
+    };
+    let mut duration = Duration::ZERO;
+    if let Some((canister_id, queries)) = batch.scheduler.try_pop_other(batch.canister_id) {
+        let outer_nested_duration = NESTED_DURATION.with(|nested| nested.take());
+        let start = Instant::now();
+        execute_batch(
+            &batch.scheduler,
+            batch.time_slice_per_canister,
+            canister_id,
+            queries,
+        );
+        duration = start.elapsed();
+        NESTED_DURATION.with(|nested| nested.set(outer_nested_duration + duration));
+    }
+    CURRENT_BATCH.with(|current| *current.borrow_mut() = Some(batch));
+    duration
+}
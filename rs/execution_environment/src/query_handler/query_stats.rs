@@ -86,12 +86,20 @@ impl QueryStatsCollector {
         *current_epoch = Some(new_epoch);
     }
 
+    /// Accounts for a single query call to `canister_id`.
+    ///
+    /// If `method_name` is set, the call is also accounted for in the
+    /// per-method stats of the canister. Callers should only set it for
+    /// methods that are exported by the canister, so that the number of
+    /// tracked methods stays bounded.
     pub fn register_query_statistics(
         &self,
         canister_id: CanisterId,
+        method_name: Option<&str>,
         num_instructions: NumInstructions,
         ingress_payload_size: u64,
         egress_payload_size: u64,
+        is_error: bool,
     ) {
         let current_epoch = *self.current_epoch.read().unwrap();
         if current_epoch.is_none() {
@@ -117,5 +125,24 @@ impl QueryStatsCollector {
         stats_for_canister.egress_payload_size = stats_for_canister
             .egress_payload_size
             .saturating_add(egress_payload_size);
+
+        if let Some(method_name) = method_name {
+            let stats_for_method = stats_for_canister
+                .method_stats
+                .entry(method_name.to_string())
+                .or_default();
+            stats_for_method.num_calls = stats_for_method.num_calls.saturating_add(1);
+            stats_for_method.num_errors =
+                stats_for_method.num_errors.saturating_add(is_error as u32);
+            stats_for_method.num_instructions = stats_for_method
+                .num_instructions
+                .saturating_add(num_instructions.get());
+            stats_for_method.ingress_payload_size = stats_for_method
+                .ingress_payload_size
+                .saturating_add(ingress_payload_size);
+            stats_for_method.egress_payload_size = stats_for_method
+                .egress_payload_size
+                .saturating_add(egress_payload_size);
+        }
     }
 }
@@ -459,6 +459,7 @@ mod tests {
                         num_instructions: 1000,
                         ingress_payload_size: 1000,
                         egress_payload_size: 1000,
+                        method_stats: Default::default(),
                     },
                 })
                 .collect(),
@@ -4,19 +4,23 @@ use ic_btc_interface::NetworkInRequest as BitcoinNetwork;
 use ic_config::execution_environment::INSTRUCTION_OVERHEAD_PER_QUERY_CALL;
 use ic_error_types::{ErrorCode, UserError};
 use ic_ic00_types::{BitcoinGetBalanceArgs, BitcoinGetUtxosArgs, Payload};
+use ic_interfaces::execution_environment::CrossSubnetQueryClient;
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::canister_state::system_state::CyclesUseCase;
 use ic_test_utilities::{
-    types::ids::user_test_id,
+    types::ids::{subnet_test_id, user_test_id},
     universal_canister::{call_args, wasm},
 };
 use ic_test_utilities_execution_environment::{ExecutionTest, ExecutionTestBuilder};
 use ic_types::{
     ingress::WasmResult,
-    messages::{CanisterTask, UserQuery},
-    time, CountBytes, Cycles, NumInstructions,
+    messages::{CanisterTask, CrossSubnetQuery, CrossSubnetQueryResponse, UserQuery},
+    time, CountBytes, Cycles, NumInstructions, SubnetId,
+};
+use std::{
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
 };
-use std::{sync::Arc, time::Duration};
 
 const CYCLES_BALANCE: Cycles = Cycles::new(100_000_000_000_000);
 
@@ -2268,6 +2272,24 @@ fn query_stats_are_collected() {
             assert_eq!(canister_query_stats.ingress_payload_size, 13);
             assert_eq!(canister_query_stats.egress_payload_size, 6);
         }
+
+        // All calls went to a single method, so its stats match the totals.
+        let method_stats: Vec<_> = canister_query_stats.method_stats.values().collect();
+        assert_eq!(method_stats.len(), 1);
+        assert_eq!(method_stats[0].num_calls, 1);
+        assert_eq!(method_stats[0].num_errors, 0);
+        assert_eq!(
+            method_stats[0].num_instructions,
+            canister_query_stats.num_instructions
+        );
+        assert_eq!(
+            method_stats[0].ingress_payload_size,
+            canister_query_stats.ingress_payload_size
+        );
+        assert_eq!(
+            method_stats[0].egress_payload_size,
+            canister_query_stats.egress_payload_size
+        );
     }
 }
 
@@ -2599,3 +2621,318 @@ fn test_call_context_performance_counter_correctly_reported_on_composite_query()
     assert!(counters[1] < counters[2]);
     assert!(counters[2] < counters[3]);
 }
+
+/// A `CrossSubnetQueryClient` that records forwarded queries and returns a
+/// fixed response.
+struct MockCrossSubnetQueryClient {
+    queries: Mutex<Vec<(SubnetId, CrossSubnetQuery)>>,
+    response: CrossSubnetQueryResponse,
+}
+
+impl MockCrossSubnetQueryClient {
+    fn new(response: CrossSubnetQueryResponse) -> Arc<Self> {
+        Arc::new(Self {
+            queries: Mutex::new(vec![]),
+            response,
+        })
+    }
+}
+
+impl CrossSubnetQueryClient for MockCrossSubnetQueryClient {
+    fn query(
+        &self,
+        subnet_id: SubnetId,
+        query: CrossSubnetQuery,
+    ) -> Result<CrossSubnetQueryResponse, UserError> {
+        self.queries.lock().unwrap().push((subnet_id, query));
+        Ok(self.response.clone())
+    }
+}
+
+#[test]
+fn composite_query_forwards_calls_to_canisters_on_other_subnets() {
+    let remote_subnet = subnet_test_id(3);
+    let remote_canister = CanisterId::from(7);
+    let client = MockCrossSubnetQueryClient::new(CrossSubnetQueryResponse {
+        result: Ok(ic_types::messages::Payload::Data(vec![42])),
+        instructions_executed: 1_000,
+    });
+    let mut test = ExecutionTestBuilder::new()
+        .with_composite_queries()
+        .with_caller(remote_subnet, remote_canister)
+        .with_cross_subnet_query_client(Arc::clone(&client) as Arc<_>)
+        .build();
+    let canister = test.universal_canister_with_cycles(CYCLES_BALANCE).unwrap();
+
+    let other_side = wasm().reply_data(&[1, 2, 3]).build();
+    let payload = wasm()
+        .composite_query(remote_canister, call_args().other_side(other_side.clone()))
+        .build();
+    let result = test.query(
+        UserQuery {
+            source: user_test_id(2),
+            receiver: canister,
+            method_name: "composite_query".to_string(),
+            method_payload: payload,
+            ingress_expiry: 0,
+            nonce: None,
+        },
+        Arc::new(test.state().clone()),
+        vec![],
+    );
+    assert_eq!(result, Ok(WasmResult::Reply(vec![42])));
+
+    let queries = client.queries.lock().unwrap();
+    assert_eq!(queries.len(), 1);
+    let (subnet_id, query) = &queries[0];
+    assert_eq!(*subnet_id, remote_subnet);
+    assert_eq!(query.sender, canister);
+    assert_eq!(query.receiver, remote_canister);
+    assert_eq!(query.method_name, "composite_query");
+    assert_eq!(query.method_payload, other_side);
+    let config = ic_config::execution_environment::Config::default();
+    // The root canister is part of the call graph, the remote sub-graph
+    // gets the remaining depth.
+    assert_eq!(
+        query.max_call_graph_depth,
+        config.max_query_call_graph_depth as u64 - 1
+    );
+    assert!(query.max_instructions < config.max_query_call_graph_instructions.get());
+    assert!(query.max_walltime_millis <= config.max_query_call_walltime.as_millis() as u64);
+}
+
+#[test]
+fn cross_subnet_query_error_aborts_query_call_graph() {
+    let remote_subnet = subnet_test_id(3);
+    let remote_canister = CanisterId::from(7);
+    let client = MockCrossSubnetQueryClient::new(CrossSubnetQueryResponse {
+        result: Err(UserError::new(
+            ErrorCode::QueryCallGraphTotalInstructionLimitExceeded,
+            "Query call graph contains too many instructions",
+        )),
+        instructions_executed: 1_000,
+    });
+    let mut test = ExecutionTestBuilder::new()
+        .with_composite_queries()
+        .with_caller(remote_subnet, remote_canister)
+        .with_cross_subnet_query_client(client)
+        .build();
+    let canister = test.universal_canister_with_cycles(CYCLES_BALANCE).unwrap();
+
+    let payload = wasm().composite_query(remote_canister, call_args()).build();
+    let result = test.query(
+        UserQuery {
+            source: user_test_id(2),
+            receiver: canister,
+            method_name: "composite_query".to_string(),
+            method_payload: payload,
+            ingress_expiry: 0,
+            nonce: None,
+        },
+        Arc::new(test.state().clone()),
+        vec![],
+    );
+    assert_eq!(
+        result.unwrap_err().code(),
+        ErrorCode::QueryCallGraphTotalInstructionLimitExceeded
+    );
+}
+
+/// A `CrossSubnetQueryClient` that simulates a remote canister which forwards
+/// every query call it receives back to a canister on the calling subnet,
+/// i.e. a query call graph that cycles between two subnets.
+struct LoopbackCrossSubnetQueryClient {
+    /// The canister on the calling subnet that query calls are forwarded to.
+    callee: CanisterId,
+    /// If set, the payload of the forwarded query calls. Otherwise, the
+    /// payload of the received query call is forwarded.
+    payload: Option<Vec<u8>>,
+    /// Executes the forwarded query calls on the calling subnet.
+    executor: OnceLock<Box<dyn Fn(CrossSubnetQuery) -> CrossSubnetQueryResponse + Send + Sync>>,
+    queries: Mutex<Vec<CrossSubnetQuery>>,
+}
+
+impl LoopbackCrossSubnetQueryClient {
+    fn new(callee: CanisterId, payload: Option<Vec<u8>>) -> Arc<Self> {
+        Arc::new(Self {
+            callee,
+            payload,
+            executor: Default::default(),
+            queries: Mutex::new(vec![]),
+        })
+    }
+}
+
+impl CrossSubnetQueryClient for LoopbackCrossSubnetQueryClient {
+    fn query(
+        &self,
+        _subnet_id: SubnetId,
+        query: CrossSubnetQuery,
+    ) -> Result<CrossSubnetQueryResponse, UserError> {
+        self.queries.lock().unwrap().push(query.clone());
+        // The remote canister is part of the query call graph, so its query
+        // call gets one level less.
+        let forwarded = CrossSubnetQuery {
+            sender: query.receiver,
+            receiver: self.callee,
+            method_name: "composite_query".to_string(),
+            method_payload: self.payload.clone().unwrap_or(query.method_payload),
+            max_call_graph_depth: query.max_call_graph_depth.saturating_sub(1),
+            max_instructions: query.max_instructions,
+            max_walltime_millis: query.max_walltime_millis,
+        };
+        let executor = self.executor.get().expect("The executor is not set");
+        Ok(executor(forwarded))
+    }
+}
+
+#[test]
+fn composite_query_call_graph_can_cycle_between_subnets() {
+    // Canister A on this subnet calls canister B on another subnet, which
+    // calls canister A again.
+    let remote_subnet = subnet_test_id(3);
+    let canister_b = CanisterId::from(7);
+    let canister_a = CanisterId::from(0);
+    let client = LoopbackCrossSubnetQueryClient::new(canister_a, None);
+    let mut test = ExecutionTestBuilder::new()
+        .with_composite_queries()
+        .with_caller(remote_subnet, canister_b)
+        .with_cross_subnet_query_client(Arc::clone(&client) as Arc<_>)
+        .build();
+    assert_eq!(
+        test.universal_canister_with_cycles(CYCLES_BALANCE).unwrap(),
+        canister_a
+    );
+    let _ = client.executor.set(test.cross_subnet_query_executor());
+
+    let other_side = wasm().reply_data(&[7]).build();
+    let payload = wasm()
+        .composite_query(canister_b, call_args().other_side(other_side))
+        .build();
+    let result = test.query(
+        UserQuery {
+            source: user_test_id(2),
+            receiver: canister_a,
+            method_name: "composite_query".to_string(),
+            method_payload: payload,
+            ingress_expiry: 0,
+            nonce: None,
+        },
+        Arc::new(test.state().clone()),
+        vec![],
+    );
+    assert_eq!(result, Ok(WasmResult::Reply(vec![7])));
+    assert_eq!(client.queries.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn composite_query_call_graph_cycling_between_subnets_is_bounded() {
+    // Canister A on this subnet calls canister B on another subnet, which
+    // calls canister A again with the same payload, and so on.
+    let remote_subnet = subnet_test_id(3);
+    let canister_b = CanisterId::from(7);
+    let canister_a = CanisterId::from(0);
+    let payload = wasm()
+        .composite_query(canister_b, call_args().other_side(wasm().build()))
+        .build();
+    let client = LoopbackCrossSubnetQueryClient::new(canister_a, Some(payload.clone()));
+    let mut test = ExecutionTestBuilder::new()
+        .with_composite_queries()
+        .with_caller(remote_subnet, canister_b)
+        .with_cross_subnet_query_client(Arc::clone(&client) as Arc<_>)
+        .build();
+    assert_eq!(
+        test.universal_canister_with_cycles(CYCLES_BALANCE).unwrap(),
+        canister_a
+    );
+    let _ = client.executor.set(test.cross_subnet_query_executor());
+
+    let result = test.query(
+        UserQuery {
+            source: user_test_id(2),
+            receiver: canister_a,
+            method_name: "composite_query".to_string(),
+            method_payload: payload,
+            ingress_expiry: 0,
+            nonce: None,
+        },
+        Arc::new(test.state().clone()),
+        vec![],
+    );
+    assert_eq!(result.unwrap_err().code(), ErrorCode::QueryCallGraphTooDeep);
+
+    // Every hop between the subnets uses up the depth and the instructions of
+    // the query call graph.
+    let queries = client.queries.lock().unwrap();
+    assert!(queries.len() > 1);
+    for hops in queries.windows(2) {
+        assert!(hops[1].max_call_graph_depth < hops[0].max_call_graph_depth);
+        assert!(hops[1].max_instructions < hops[0].max_instructions);
+    }
+}
+
+#[test]
+fn query_above_instruction_limit_without_dts_succeeds_with_query_dts() {
+    const SLICE_INSTRUCTION_LIMIT: u64 = 1_000_000;
+    let mut test = ExecutionTestBuilder::new()
+        .with_deterministic_time_slicing()
+        .with_query_deterministic_time_slicing()
+        .with_instruction_limit_without_dts(SLICE_INSTRUCTION_LIMIT)
+        .with_query_caching()
+        .build();
+    let canister_id = test.universal_canister_with_cycles(CYCLES_BALANCE).unwrap();
+    test.state_mut().metadata.batch_time = time::GENESIS;
+
+    let query = UserQuery {
+        source: user_test_id(1),
+        receiver: canister_id,
+        method_name: "query".into(),
+        method_payload: wasm()
+            .instruction_counter_is_at_least(3 * SLICE_INSTRUCTION_LIMIT)
+            .reply_data(b"done")
+            .build(),
+        ingress_expiry: 0,
+        nonce: None,
+    };
+    let output_1 = test.query(query.clone(), Arc::new(test.state().clone()), vec![]);
+    assert_eq!(output_1, Ok(WasmResult::Reply(b"done".to_vec())));
+
+    // The result of the query across all slices is cached.
+    let output_2 = test.query(query, Arc::new(test.state().clone()), vec![]);
+    assert_eq!(output_1, output_2);
+    let metrics = &downcast_query_handler(test.query_handler())
+        .query_cache
+        .metrics;
+    assert_eq!(1, metrics.misses.get());
+    assert_eq!(1, metrics.hits.get());
+}
+
+#[test]
+fn query_above_instruction_limit_without_dts_fails_without_query_dts() {
+    const SLICE_INSTRUCTION_LIMIT: u64 = 1_000_000;
+    let mut test = ExecutionTestBuilder::new()
+        .with_deterministic_time_slicing()
+        .with_instruction_limit_without_dts(SLICE_INSTRUCTION_LIMIT)
+        .build();
+    let canister_id = test.universal_canister_with_cycles(CYCLES_BALANCE).unwrap();
+
+    let output = test.query(
+        UserQuery {
+            source: user_test_id(1),
+            receiver: canister_id,
+            method_name: "query".into(),
+            method_payload: wasm()
+                .instruction_counter_is_at_least(3 * SLICE_INSTRUCTION_LIMIT)
+                .reply_data(b"done")
+                .build(),
+            ingress_expiry: 0,
+            nonce: None,
+        },
+        Arc::new(test.state().clone()),
+        vec![],
+    );
+    assert_eq!(
+        output.unwrap_err().code(),
+        ErrorCode::CanisterInstructionLimitExceeded
+    );
+}
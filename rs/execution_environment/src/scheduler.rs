@@ -31,7 +31,7 @@ use ic_system_api::InstructionLimits;
 use ic_types::{
     crypto::canister_threshold_sig::MasterEcdsaPublicKey,
     ingress::{IngressState, IngressStatus},
-    messages::{CanisterMessage, Ingress, MessageId, StopCanisterContext},
+    messages::{CanisterMessage, Ingress, MessageId, RequestOrResponse, StopCanisterContext},
     AccumulatedPriority, CanisterId, ComputeAllocation, Cycles, ExecutionRound, LongExecutionMode,
     MemoryAllocation, NumBytes, NumInstructions, NumSlices, Randomness, SubnetId, Time,
 };
@@ -560,9 +560,9 @@ impl SchedulerImpl {
         (new_state, message_instructions)
     }
 
-    /// Invoked in the first iteration of the inner round to add the `Heartbeat`
-    /// and `GlobalTimer` tasks that are carried out prior to processing
-    /// any input messages.
+    /// Invoked in the first iteration of the inner round to add the `Heartbeat`,
+    /// `GlobalTimer` and `OnLowWasmMemory` tasks that are carried out prior to
+    /// processing any input messages.
     /// It also returns the list of canisters that have non-zero priority credit.
     fn initialize_inner_round(
         &self,
@@ -577,13 +577,17 @@ impl SchedulerImpl {
         let mut non_zero_priority_credit_canister_ids = BTreeSet::new();
 
         let now = state.time();
+        let max_wasm64_memory_size = self.exec_env.max_wasm64_memory_size();
         for canister in state.canisters_iter_mut() {
             // Remember all non-zero priority_credit canisters to apply it after the round.
             if canister.scheduler_state.priority_credit != AccumulatedPriority::default() {
                 non_zero_priority_credit_canister_ids.insert(canister.system_state.canister_id);
             }
 
-            // Add `Heartbeat` or `GlobalTimer` for running canisters only.
+            canister.update_on_low_wasm_memory_hook_status(max_wasm64_memory_size);
+
+            // Add `Heartbeat`, `GlobalTimer` or `OnLowWasmMemory` for running
+            // canisters only.
             match canister.system_state.status {
                 CanisterStatus::Running { .. } => {}
                 CanisterStatus::Stopping { .. } | CanisterStatus::Stopped => {
@@ -592,7 +596,8 @@ impl SchedulerImpl {
             }
 
             let global_timer_has_reached_deadline =
-                canister.system_state.global_timer.has_reached_deadline(now);
+                canister.system_state.global_timer.has_reached_deadline(now)
+                    || canister.system_state.timers.has_reached_deadline(now);
             match canister.next_execution() {
                 NextExecution::ContinueLong | NextExecution::ContinueInstallCode => {
                     // Do not add a heartbeat task if a long execution
@@ -612,6 +617,23 @@ impl SchedulerImpl {
                             break;
                         }
                     }
+
+                    // The low Wasm memory hook goes first, so that the
+                    // canister gets a chance to free memory before it
+                    // executes anything else.
+                    if canister
+                        .system_state
+                        .on_low_wasm_memory_hook_status
+                        .is_ready()
+                        && canister.exports_on_low_wasm_memory_method()
+                    {
+                        canister
+                            .system_state
+                            .task_queue
+                            .push_front(ExecutionTask::OnLowWasmMemory);
+                        heartbeat_and_timer_canister_ids.insert(canister.canister_id());
+                        self.metrics.on_low_wasm_memory_hooks_scheduled.inc();
+                    }
                 }
             }
         }
@@ -800,12 +822,15 @@ impl SchedulerImpl {
                 .metrics
                 .round_inner_heartbeat_overhead_duration
                 .start_timer();
-            // Remove all remaining `Heartbeat` and `GlobalTimer` tasks
-            // because they will be added again in the next round.
+            // Remove all remaining `Heartbeat`, `GlobalTimer` and
+            // `OnLowWasmMemory` tasks because they will be added again in the
+            // next round.
             for canister_id in &heartbeat_and_timer_canister_ids {
                 let canister = state.canister_state_mut(canister_id).unwrap();
                 canister.system_state.task_queue.retain(|task| match task {
-                    ExecutionTask::Heartbeat | ExecutionTask::GlobalTimer => false,
+                    ExecutionTask::Heartbeat
+                    | ExecutionTask::GlobalTimer
+                    | ExecutionTask::OnLowWasmMemory => false,
                     ExecutionTask::PausedExecution(..)
                     | ExecutionTask::PausedInstallCode(..)
                     | ExecutionTask::AbortedExecution { .. }
@@ -1073,6 +1098,7 @@ impl SchedulerImpl {
     ) {
         let state_time = state.time();
         let mut all_rejects = Vec::new();
+        let mut uninstalled_canisters = Vec::new();
         for canister in state.canisters_iter_mut() {
             // Postpone charging for resources when a canister has a paused execution
             // to avoid modifying the balance of a canister during an unfinished operation.
@@ -1114,6 +1140,8 @@ impl SchedulerImpl {
                     canister.scheduler_state.compute_allocation = ComputeAllocation::zero();
                     canister.system_state.memory_allocation = MemoryAllocation::BestEffort;
                     canister.system_state.clear_canister_history();
+                    canister.system_state.snapshots_memory_usage = NumBytes::from(0);
+                    uninstalled_canisters.push(canister.canister_id());
                     // Burn the remaining balance of the canister.
                     canister.system_state.burn_remaining_balance_for_uninstall();
 
@@ -1127,6 +1155,11 @@ impl SchedulerImpl {
             }
         }
 
+        // Uninstalled canisters lose all their snapshots.
+        for canister_id in uninstalled_canisters {
+            state.canister_snapshots.delete_snapshots(canister_id);
+        }
+
         // Send rejects to any requests that were forcibly closed while uninstalling.
         for rejects in all_rejects.into_iter() {
             process_responses(
@@ -1208,11 +1241,21 @@ impl SchedulerImpl {
                             state.metadata.own_subnet_type,
                             InputQueueType::LocalSubnet,
                         )
-                        .map_err(|(err, msg)| {
-                            error!(
-                                self.log,
-                                "Inducting {:?} on same subnet failed with error '{}'.", &msg, &err
-                            );
+                        .or_else(|(err, msg)| match &msg {
+                            // Best-effort responses may fail induction because the
+                            // callback has already expired. Drop them.
+                            RequestOrResponse::Response(response) if response.is_best_effort() => {
+                                Ok(())
+                            }
+                            _ => {
+                                error!(
+                                    self.log,
+                                    "Inducting {:?} on same subnet failed with error '{}'.",
+                                    &msg,
+                                    &err
+                                );
+                                Err(())
+                            }
                         }),
                     None => Err(()),
                 });
@@ -1338,8 +1381,8 @@ impl SchedulerImpl {
             .iter()
             .filter(|(_, canister)| !canister.system_state.task_queue.is_empty());
 
-        // 1. Heartbeat and GlobalTimer tasks exist only during the round
-        //    and must not exist after the round.
+        // 1. Heartbeat, GlobalTimer and OnLowWasmMemory tasks exist only
+        //    during the round and must not exist after the round.
         // 2. Paused executions can exist only in ordinary rounds (not checkpoint rounds).
         // 3. If deterministic time slicing is disabled, then there are no paused tasks.
         //    Aborted tasks may still exist if DTS was disabled in recent checkpoints.
@@ -1360,6 +1403,12 @@ impl SchedulerImpl {
                             id
                         );
                     }
+                    ExecutionTask::OnLowWasmMemory => {
+                        panic!(
+                            "Unexpected on low wasm memory task after a round in canister {:?}",
+                            id
+                        );
+                    }
                     ExecutionTask::PausedExecution(_) | ExecutionTask::PausedInstallCode(_) => {
                         assert_eq!(
                             self.deterministic_time_slicing,
@@ -2067,7 +2116,10 @@ fn observe_replicated_state_metrics(
             Some(&ExecutionTask::AbortedInstallCode { .. }) => {
                 num_aborted_install += 1;
             }
-            Some(&ExecutionTask::Heartbeat) | Some(&ExecutionTask::GlobalTimer) | None => {}
+            Some(&ExecutionTask::Heartbeat)
+            | Some(&ExecutionTask::GlobalTimer)
+            | Some(&ExecutionTask::OnLowWasmMemory)
+            | None => {}
         }
         consumed_cycles_total += canister
             .system_state
@@ -2307,7 +2359,12 @@ fn get_instructions_limits_for_subnet_message(
             | UploadChunk
             | StoredChunks
             | DeleteChunks
-            | ClearChunkStore => default_limits,
+            | ClearChunkStore
+            | TakeCanisterSnapshot
+            | LoadCanisterSnapshot
+            | ListCanisterSnapshots
+            | DeleteCanisterSnapshot
+            | FetchCanisterLogs => default_limits,
             InstallCode | InstallChunkedCode => InstructionLimits::new(
                 dts,
                 config.max_instructions_per_install_code,
@@ -2382,7 +2439,8 @@ fn should_add_task(
         ExecutionTask::GlobalTimer => {
             global_timer_has_reached_deadline && canister.exports_global_timer_method()
         }
-        ExecutionTask::AbortedExecution { .. }
+        ExecutionTask::OnLowWasmMemory
+        | ExecutionTask::AbortedExecution { .. }
         | ExecutionTask::AbortedInstallCode { .. }
         | ExecutionTask::PausedExecution(..)
         | ExecutionTask::PausedInstallCode(..) => unreachable!("Unexpected ExecutionTask variant."),
@@ -2393,7 +2451,8 @@ fn get_other_task(task: ExecutionTask) -> ExecutionTask {
     match task {
         ExecutionTask::Heartbeat => ExecutionTask::GlobalTimer,
         ExecutionTask::GlobalTimer => ExecutionTask::Heartbeat,
-        ExecutionTask::AbortedExecution { .. }
+        ExecutionTask::OnLowWasmMemory
+        | ExecutionTask::AbortedExecution { .. }
         | ExecutionTask::AbortedInstallCode { .. }
         | ExecutionTask::PausedExecution(..)
         | ExecutionTask::PausedInstallCode(..) => unreachable!("Unexpected ExecutionTask variant."),
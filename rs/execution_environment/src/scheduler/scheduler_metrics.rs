@@ -62,6 +62,7 @@ pub(super) struct SchedulerMetrics {
     pub(super) inner_loop_consumed_non_zero_instructions_count: IntCounter,
     pub(super) inner_round_loop_consumed_max_instructions: IntCounter,
     pub(super) num_canisters_uninstalled_out_of_cycles: IntCounter,
+    pub(super) on_low_wasm_memory_hooks_scheduled: IntCounter,
     pub(super) round: ScopedMetrics,
     pub(super) round_preparation_duration: Histogram,
     pub(super) round_preparation_ingress: Histogram,
@@ -276,6 +277,11 @@ impl SchedulerMetrics {
                 "The number of canisters that were uninstalled because \
                       they ran out of cycles.",
             ),
+            on_low_wasm_memory_hooks_scheduled: metrics_registry.int_counter(
+                "scheduler_on_low_wasm_memory_hooks_scheduled",
+                "The number of times the `canister_on_low_wasm_memory` hook \
+                      was scheduled because a canister ran low on Wasm memory.",
+            ),
             round: ScopedMetrics {
                 duration: duration_histogram(
                     "execution_round_duration_seconds",
@@ -416,7 +422,7 @@ impl SchedulerMetrics {
             },
             round_inner_heartbeat_overhead_duration: duration_histogram(
                 "execution_round_inner_heartbeat_overhead_duration_seconds",
-                "The duration of iterating canisters to prepare/remove heartbeat and global timer tasks",
+                "The duration of iterating canisters to prepare/remove heartbeat, global timer and on low wasm memory tasks",
                 metrics_registry,
             ),
             round_inner_iteration: ScopedMetrics {
@@ -34,7 +34,8 @@ use ic_replicated_state::{
     canister_state::execution_state::{self, WasmMetadata},
     page_map::TestPageAllocatorFileDescriptorImpl,
     testing::{CanisterQueuesTesting, ReplicatedStateTesting},
-    CanisterState, ExecutionState, ExportedFunctions, InputQueueType, Memory, ReplicatedState,
+    CanisterState, ExecutionState, ExportedFunctions, InputQueueType, Memory, NumWasmPages,
+    ReplicatedState,
 };
 use ic_system_api::{
     sandbox_safe_system_state::{SandboxSafeSystemState, SystemStateChanges},
@@ -51,7 +52,9 @@ use ic_test_utilities_execution_environment::{generate_subnets, test_registry_se
 use ic_types::{
     crypto::{canister_threshold_sig::MasterEcdsaPublicKey, AlgorithmId},
     ingress::{IngressState, IngressStatus},
-    messages::{CallContextId, Ingress, MessageId, Request, RequestOrResponse, Response},
+    messages::{
+        CallContextId, Ingress, MessageId, Request, RequestOrResponse, Response, NO_DEADLINE,
+    },
     methods::{Callback, FuncRef, SystemMethod, WasmClosure, WasmMethod},
     CanisterTimer, ComputeAllocation, Cycles, ExecutionRound, MemoryAllocation, NumInstructions,
     Randomness, Time, UserId,
@@ -430,6 +433,12 @@ impl SchedulerTest {
                 RequestOrResponse::Response(response) => {
                     output.push((*response).clone());
                 }
+                RequestOrResponse::Fragment(fragment) => {
+                    panic!(
+                        "Expected the xnet message to be a Response, but got a Fragment: {:?}",
+                        fragment
+                    )
+                }
             }
         }
         output
@@ -464,6 +473,20 @@ impl SchedulerTest {
         wasm_executor.push_system_task(canister_id, system_task);
     }
 
+    pub fn expect_on_low_wasm_memory(&mut self, canister_id: CanisterId, system_task: TestMessage) {
+        assert!(
+            self.canister_state(canister_id)
+                .execution_state
+                .as_ref()
+                .unwrap()
+                .exports_method(&WasmMethod::System(SystemMethod::CanisterOnLowWasmMemory)),
+            "The canister should be created with \
+             `create_canister_with(.., Some(SystemMethod::CanisterOnLowWasmMemory))`"
+        );
+        let mut wasm_executor = self.wasm_executor.core.lock().unwrap();
+        wasm_executor.push_system_task(canister_id, system_task);
+    }
+
     pub fn execute_round(&mut self, round_type: ExecutionRoundType) {
         let state = self.state.take().unwrap();
         let state = self.scheduler.execute_round(
@@ -557,6 +580,20 @@ impl SchedulerTest {
         canister_state.system_state.global_timer = CanisterTimer::Active(time);
     }
 
+    /// Sets the Wasm memory limit, threshold and heap size of the canister.
+    pub(crate) fn set_canister_wasm_memory(
+        &mut self,
+        canister: CanisterId,
+        wasm_memory_limit: NumBytes,
+        wasm_memory_threshold: NumBytes,
+        wasm_memory_size: NumWasmPages,
+    ) {
+        let canister = self.canister_state_mut(canister);
+        canister.system_state.wasm_memory_limit = Some(wasm_memory_limit);
+        canister.system_state.wasm_memory_threshold = Some(wasm_memory_threshold);
+        canister.execution_state.as_mut().unwrap().wasm_memory.size = wasm_memory_size;
+    }
+
     pub(crate) fn set_time(&mut self, time: Time) {
         self.state_mut().metadata.batch_time = time;
     }
@@ -1071,6 +1108,8 @@ impl TestWasmExecutorCore {
                 allocated_bytes: NumBytes::from(0),
                 allocated_message_bytes: NumBytes::from(0),
                 instance_stats: InstanceStats::default(),
+                canister_log: Default::default(),
+                wasm_profile: Default::default(),
             };
             self.schedule
                 .push((self.round, canister_id, instructions_to_execute));
@@ -1115,6 +1154,8 @@ impl TestWasmExecutorCore {
             allocated_message_bytes: NumBytes::from(0),
             num_instructions_left: instructions_left,
             instance_stats,
+            canister_log: Default::default(),
+            wasm_profile: Default::default(),
         };
         self.schedule
             .push((self.round, canister_id, instructions_to_execute));
@@ -1146,6 +1187,8 @@ impl TestWasmExecutorCore {
             Memory::new_for_testing(),
             vec![],
             WasmMetadata::default(),
+            false,
+            None,
         );
         let compilation_result = CompilationResult::empty_for_testing();
         Ok((
@@ -1221,6 +1264,7 @@ impl TestWasmExecutorCore {
             method_name: "update".into(),
             method_payload: encode_message_id_as_payload(call_message_id),
             metadata: None,
+            deadline: NO_DEADLINE,
         };
         if let Err(req) = system_state.push_output_request(
             canister_current_memory_usage,
@@ -25,7 +25,7 @@ use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::canister_state::system_state::PausedExecutionId;
 use ic_replicated_state::testing::CanisterQueuesTesting;
 use ic_replicated_state::testing::SystemStateTesting;
-use ic_replicated_state::ExportedFunctions;
+use ic_replicated_state::{ExportedFunctions, NumWasmPages, OnLowWasmMemoryHookStatus};
 use ic_state_machine_tests::{PayloadBuilder, StateMachineBuilder};
 use ic_test_utilities::types::ids::message_test_id;
 use ic_test_utilities::{
@@ -42,6 +42,7 @@ use ic_test_utilities_metrics::{
 };
 use ic_types::messages::{
     CallbackId, Payload, RejectContext, Response, StopCanisterCallId, MAX_RESPONSE_COUNT_BYTES,
+    NO_DEADLINE,
 };
 use ic_types::methods::SystemMethod;
 use ic_types::methods::WasmMethod;
@@ -1633,6 +1634,201 @@ fn execute_global_timer_before_messages() {
     assert_eq!(test.ingress_queue_size(canister), 3);
 }
 
+/// Makes the canister have 384 KiB of free Wasm heap space below its 1 MiB
+/// limit, which is below the threshold of 512 KiB.
+fn set_low_wasm_memory(test: &mut SchedulerTest, canister: CanisterId) {
+    test.set_canister_wasm_memory(
+        canister,
+        NumBytes::new(1 << 20),
+        NumBytes::new(1 << 19),
+        NumWasmPages::new(10),
+    );
+}
+
+#[test]
+fn execute_on_low_wasm_memory_hook_once() {
+    let mut test = SchedulerTestBuilder::new().build();
+    let canister = test.create_canister_with(
+        Cycles::new(1_000_000_000_000),
+        ComputeAllocation::zero(),
+        MemoryAllocation::BestEffort,
+        Some(SystemMethod::CanisterOnLowWasmMemory),
+        None,
+        None,
+    );
+    set_low_wasm_memory(&mut test, canister);
+
+    test.send_ingress(canister, ingress(1));
+    test.expect_on_low_wasm_memory(canister, instructions(1));
+    test.execute_round(ExecutionRoundType::OrdinaryRound);
+    let metrics = &test.scheduler().metrics;
+    assert_eq!(metrics.round_inner.messages.get_sample_sum(), 2.0);
+    assert_eq!(metrics.on_low_wasm_memory_hooks_scheduled.get(), 1);
+    assert_eq!(
+        test.canister_state(canister)
+            .system_state
+            .on_low_wasm_memory_hook_status,
+        OnLowWasmMemoryHookStatus::Executed
+    );
+
+    // The memory is still low, but the hook has already been executed.
+    test.send_ingress(canister, ingress(1));
+    test.execute_round(ExecutionRoundType::OrdinaryRound);
+    let metrics = &test.scheduler().metrics;
+    assert_eq!(metrics.round_inner.messages.get_sample_sum(), 3.0);
+    assert_eq!(metrics.on_low_wasm_memory_hooks_scheduled.get(), 1);
+}
+
+#[test]
+fn execute_on_low_wasm_memory_hook_again_after_memory_is_freed() {
+    let mut test = SchedulerTestBuilder::new().build();
+    let canister = test.create_canister_with(
+        Cycles::new(1_000_000_000_000),
+        ComputeAllocation::zero(),
+        MemoryAllocation::BestEffort,
+        Some(SystemMethod::CanisterOnLowWasmMemory),
+        None,
+        None,
+    );
+    set_low_wasm_memory(&mut test, canister);
+    test.expect_on_low_wasm_memory(canister, instructions(1));
+    test.execute_round(ExecutionRoundType::OrdinaryRound);
+    assert_eq!(
+        test.scheduler()
+            .metrics
+            .on_low_wasm_memory_hooks_scheduled
+            .get(),
+        1
+    );
+
+    // Free the memory, which resets the hook.
+    test.canister_state_mut(canister)
+        .execution_state
+        .as_mut()
+        .unwrap()
+        .wasm_memory
+        .size = NumWasmPages::new(0);
+    test.execute_round(ExecutionRoundType::OrdinaryRound);
+    assert_eq!(
+        test.canister_state(canister)
+            .system_state
+            .on_low_wasm_memory_hook_status,
+        OnLowWasmMemoryHookStatus::ConditionNotSatisfied
+    );
+
+    set_low_wasm_memory(&mut test, canister);
+    test.expect_on_low_wasm_memory(canister, instructions(1));
+    test.execute_round(ExecutionRoundType::OrdinaryRound);
+    let metrics = &test.scheduler().metrics;
+    assert_eq!(metrics.round_inner.messages.get_sample_sum(), 2.0);
+    assert_eq!(metrics.on_low_wasm_memory_hooks_scheduled.get(), 2);
+}
+
+#[test]
+fn on_low_wasm_memory_hook_is_not_scheduled_if_memory_is_not_low() {
+    let mut test = SchedulerTestBuilder::new().build();
+    let canister = test.create_canister_with(
+        Cycles::new(1_000_000_000_000),
+        ComputeAllocation::zero(),
+        MemoryAllocation::BestEffort,
+        Some(SystemMethod::CanisterOnLowWasmMemory),
+        None,
+        None,
+    );
+    test.set_canister_wasm_memory(
+        canister,
+        NumBytes::new(1 << 20),
+        NumBytes::new(1 << 19),
+        NumWasmPages::new(1),
+    );
+
+    test.send_ingress(canister, ingress(1));
+    test.execute_round(ExecutionRoundType::OrdinaryRound);
+    let metrics = &test.scheduler().metrics;
+    assert_eq!(metrics.round_inner.messages.get_sample_sum(), 1.0);
+    assert_eq!(metrics.on_low_wasm_memory_hooks_scheduled.get(), 0);
+}
+
+#[test]
+fn on_low_wasm_memory_hook_is_not_scheduled_if_the_method_is_not_exported() {
+    let mut test = SchedulerTestBuilder::new().build();
+    let canister = test.create_canister_with(
+        Cycles::new(1_000_000_000_000),
+        ComputeAllocation::zero(),
+        MemoryAllocation::BestEffort,
+        None,
+        None,
+        None,
+    );
+    set_low_wasm_memory(&mut test, canister);
+
+    test.send_ingress(canister, ingress(1));
+    test.execute_round(ExecutionRoundType::OrdinaryRound);
+    let metrics = &test.scheduler().metrics;
+    assert_eq!(metrics.round_inner.messages.get_sample_sum(), 1.0);
+    assert_eq!(metrics.on_low_wasm_memory_hooks_scheduled.get(), 0);
+    assert_eq!(
+        test.canister_state(canister)
+            .system_state
+            .on_low_wasm_memory_hook_status,
+        OnLowWasmMemoryHookStatus::Ready
+    );
+}
+
+#[test]
+fn on_low_wasm_memory_hook_is_not_scheduled_if_the_canister_is_stopped() {
+    let mut test = SchedulerTestBuilder::new().build();
+    let canister = test.create_canister_with(
+        Cycles::new(1_000_000_000_000),
+        ComputeAllocation::zero(),
+        MemoryAllocation::BestEffort,
+        Some(SystemMethod::CanisterOnLowWasmMemory),
+        None,
+        Some(CanisterStatusType::Stopped),
+    );
+    set_low_wasm_memory(&mut test, canister);
+
+    test.send_ingress(canister, ingress(1));
+    test.execute_round(ExecutionRoundType::OrdinaryRound);
+    let metrics = &test.scheduler().metrics;
+    assert_eq!(metrics.round_inner.messages.get_sample_sum(), 1.0);
+    assert_eq!(metrics.on_low_wasm_memory_hooks_scheduled.get(), 0);
+}
+
+#[test]
+fn execute_on_low_wasm_memory_hook_before_messages() {
+    let mut test = SchedulerTestBuilder::new()
+        .with_scheduler_config(SchedulerConfig {
+            scheduler_cores: 2,
+            max_instructions_per_round: NumInstructions::new(1),
+            max_instructions_per_message: NumInstructions::new(1),
+            max_instructions_per_message_without_dts: NumInstructions::new(1),
+            max_instructions_per_slice: NumInstructions::new(1),
+            instruction_overhead_per_message: NumInstructions::from(0),
+            instruction_overhead_per_canister: NumInstructions::from(0),
+            ..SchedulerConfig::system_subnet()
+        })
+        .build();
+    let canister = test.create_canister_with(
+        Cycles::new(1_000_000_000_000),
+        ComputeAllocation::zero(),
+        MemoryAllocation::BestEffort,
+        Some(SystemMethod::CanisterOnLowWasmMemory),
+        None,
+        None,
+    );
+    set_low_wasm_memory(&mut test, canister);
+
+    test.send_ingress(canister, ingress(1));
+    test.send_ingress(canister, ingress(1));
+    test.send_ingress(canister, ingress(1));
+    test.expect_on_low_wasm_memory(canister, instructions(1));
+    test.execute_round(ExecutionRoundType::OrdinaryRound);
+    let metrics = &test.scheduler().metrics;
+    assert_eq!(metrics.round_inner.messages.get_sample_sum(), 1.0);
+    assert_eq!(test.ingress_queue_size(canister), 3);
+}
+
 #[test]
 fn test_drain_subnet_messages_with_some_long_running_canisters() {
     let mut test = SchedulerTestBuilder::new()
@@ -3033,6 +3229,7 @@ fn ecdsa_signature_agreements_metric_is_updated() {
         originator_reply_callback: *callback_id,
         refund: context.request.payment,
         response_payload: Payload::Reject(RejectContext::new(RejectCode::SysFatal, "")),
+        deadline: NO_DEADLINE,
     };
 
     test.state_mut().consensus_queue.push(response);
@@ -3081,6 +3278,7 @@ fn ecdsa_signature_agreements_metric_is_updated() {
             }
             .encode(),
         ),
+        deadline: NO_DEADLINE,
     };
 
     test.state_mut().consensus_queue.push(response);
@@ -0,0 +1,145 @@
+//! Aggregates the Wasm profiles of all executions into folded stacks, the
+//! input format of flamegraph tools like `inferno-flamegraph` and
+//! `flamegraph.pl`. Only used by local tools like `drun` and `StateMachine`.
+
+use ic_embedders::{
+    wasm_executor::{PausedWasmExecution, WasmExecutionResult},
+    wasm_utils::{decoding::decode_wasm, instrumentation::function_names},
+};
+use ic_interfaces::execution_environment::WasmProfile;
+use ic_replicated_state::{canister_state::execution_state::WasmBinary, ExecutionState};
+use ic_types::CanisterId;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+/// Collects the instructions executed by the canisters, attributed to their
+/// Wasm call stacks. Each folded stack starts with the canister ID, followed by
+/// the names of the functions from the outermost to the innermost call.
+#[derive(Default)]
+pub struct WasmProfiler {
+    state: Mutex<WasmProfilerState>,
+}
+
+impl std::fmt::Debug for WasmProfiler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmProfiler").finish_non_exhaustive()
+    }
+}
+
+#[derive(Default)]
+struct WasmProfilerState {
+    /// Function names of the modules seen so far, by module hash.
+    function_names: HashMap<[u8; 32], BTreeMap<u32, String>>,
+    /// Instructions executed by the innermost function of a folded stack.
+    stacks: BTreeMap<String, u64>,
+}
+
+impl WasmProfiler {
+    /// Adds the profile of an execution of the given canister module.
+    fn record(&self, canister_id: CanisterId, wasm_binary: &WasmBinary, profile: WasmProfile) {
+        if profile.is_empty() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        let WasmProfilerState {
+            function_names: names_by_module,
+            stacks,
+        } = &mut *state;
+        let names = names_by_module
+            .entry(wasm_binary.binary.module_hash())
+            .or_insert_with(|| match decode_wasm(wasm_binary.binary.to_shared_vec()) {
+                Ok(wasm) => function_names(&wasm),
+                Err(_) => BTreeMap::new(),
+            });
+        for (stack, instructions) in profile.stacks {
+            let mut folded = canister_id.to_string();
+            for function_index in stack {
+                folded.push(';');
+                match names.get(&function_index) {
+                    // `;` separates the frames of a folded stack.
+                    Some(name) => folded.push_str(&name.replace(';', ":")),
+                    None => folded.push_str(&format!("func[{}]", function_index)),
+                }
+            }
+            let total = stacks.entry(folded).or_default();
+            *total = total.saturating_add(instructions);
+        }
+    }
+
+    /// Writes the folded stacks, one `<stack> <instructions>` line per stack.
+    pub fn write_folded_stacks<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let state = self.state.lock().unwrap();
+        for (stack, instructions) in state.stacks.iter() {
+            writeln!(writer, "{} {}", stack, instructions)?;
+        }
+        writer.flush()
+    }
+
+    /// Returns the folded stacks collected so far.
+    pub fn folded_stacks(&self) -> String {
+        let mut result = vec![];
+        self.write_folded_stacks(&mut result)
+            .expect("Writing to a vector cannot fail");
+        String::from_utf8(result).expect("Folded stacks are valid UTF-8")
+    }
+
+    /// Discards all profiles collected so far.
+    pub fn clear(&self) {
+        self.state.lock().unwrap().stacks.clear();
+    }
+}
+
+/// Records the profile of the given execution result. If the execution is
+/// paused, then the profile is recorded once the execution finishes.
+pub(crate) fn profile_execution(
+    profiler: &Arc<WasmProfiler>,
+    canister_id: CanisterId,
+    execution_state: &ExecutionState,
+    execution_result: WasmExecutionResult,
+) -> WasmExecutionResult {
+    match execution_result {
+        WasmExecutionResult::Finished(slice, mut output, canister_state_changes) => {
+            profiler.record(
+                canister_id,
+                &execution_state.wasm_binary,
+                std::mem::take(&mut output.wasm_profile),
+            );
+            WasmExecutionResult::Finished(slice, output, canister_state_changes)
+        }
+        WasmExecutionResult::Paused(slice, paused_wasm_execution) => WasmExecutionResult::Paused(
+            slice,
+            Box::new(ProfiledPausedWasmExecution {
+                paused_wasm_execution,
+                profiler: Arc::clone(profiler),
+                canister_id,
+            }),
+        ),
+    }
+}
+
+/// A paused execution that records its profile when it finishes.
+#[derive(Debug)]
+struct ProfiledPausedWasmExecution {
+    paused_wasm_execution: Box<dyn PausedWasmExecution>,
+    profiler: Arc<WasmProfiler>,
+    canister_id: CanisterId,
+}
+
+impl PausedWasmExecution for ProfiledPausedWasmExecution {
+    fn resume(self: Box<Self>, execution_state: &ExecutionState) -> WasmExecutionResult {
+        let execution_result = self.paused_wasm_execution.resume(execution_state);
+        profile_execution(
+            &self.profiler,
+            self.canister_id,
+            execution_state,
+            execution_result,
+        )
+    }
+
+    fn abort(self: Box<Self>) {
+        self.paused_wasm_execution.abort()
+    }
+}
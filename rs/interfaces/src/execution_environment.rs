@@ -1,7 +1,9 @@
 //! The execution environment public interface.
 mod errors;
 
-pub use errors::{CanisterOutOfCyclesError, HypervisorError, TrapCode};
+pub use errors::{
+    BacktraceFrame, CanisterBacktrace, CanisterOutOfCyclesError, HypervisorError, TrapCode,
+};
 use ic_base_types::NumBytes;
 use ic_error_types::UserError;
 use ic_ic00_types::EcdsaKeyId;
@@ -10,13 +12,14 @@ use ic_registry_provisional_whitelist::ProvisionalWhitelist;
 use ic_registry_subnet_type::SubnetType;
 use ic_sys::{PageBytes, PageIndex};
 use ic_types::{
+    canister_log::CanisterLog,
     crypto::canister_threshold_sig::MasterEcdsaPublicKey,
     ingress::{IngressStatus, WasmResult},
     messages::{
-        AnonymousQuery, AnonymousQueryResponse, CertificateDelegation, HttpQueryResponse,
-        MessageId, SignedIngressContent, UserQuery,
+        AnonymousQuery, AnonymousQueryResponse, CertificateDelegation, CrossSubnetQuery,
+        CrossSubnetQueryResponse, HttpQueryResponse, MessageId, SignedIngressContent, UserQuery,
     },
-    Cycles, ExecutionRound, Height, NumInstructions, NumPages, Randomness, Time,
+    Cycles, ExecutionRound, Height, NumInstructions, NumPages, Randomness, SubnetId, Time,
 };
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
@@ -60,6 +63,36 @@ pub struct InstanceStats {
     pub copy_page_count: usize,
 }
 
+/// The instructions executed by a message, attributed to the Wasm call stacks
+/// that executed them. Only collected if the Wasm profiler is enabled.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct WasmProfile {
+    /// Maps a call stack to the number of instructions executed by its
+    /// innermost function. A call stack lists the indices of the functions in
+    /// the original (uninstrumented) module from the outermost to the
+    /// innermost call.
+    pub stacks: BTreeMap<Vec<u32>, u64>,
+}
+
+impl WasmProfile {
+    pub fn is_empty(&self) -> bool {
+        self.stacks.is_empty()
+    }
+
+    /// Adds the instructions of the given call stack to the profile.
+    pub fn add(&mut self, stack: &[u32], instructions: u64) {
+        if instructions == 0 {
+            return;
+        }
+        match self.stacks.get_mut(stack) {
+            Some(total) => *total = total.saturating_add(instructions),
+            None => {
+                self.stacks.insert(stack.to_vec(), instructions);
+            }
+        }
+    }
+}
+
 /// Errors that can be returned when fetching the available memory on a subnet.
 #[derive(Debug)]
 pub enum SubnetAvailableMemoryError {
@@ -274,6 +307,28 @@ pub type HypervisorResult<T> = Result<T, HypervisorError>;
 pub type AnonymousQueryService =
     BoxCloneService<AnonymousQuery, AnonymousQueryResponse, Infallible>;
 
+/// Interface for the component to execute the query calls that composite
+/// queries on other subnets make to canisters on this subnet.
+pub type CrossSubnetQueryService =
+    BoxCloneService<CrossSubnetQuery, CrossSubnetQueryResponse, Infallible>;
+
+/// Interface for forwarding the query calls that composite queries make to
+/// canisters on other subnets.
+pub trait CrossSubnetQueryClient: Send + Sync {
+    /// Executes the given query call on replicas of `subnet_id` and blocks
+    /// until the response arrives or `query.max_walltime_millis` elapse. Called
+    /// from query execution threads, never from within an async runtime.
+    ///
+    /// Returns an error if the query call could not be delivered, if the
+    /// replicas disagree on the response, or if too many query calls to other
+    /// subnets are in flight.
+    fn query(
+        &self,
+        subnet_id: SubnetId,
+        query: CrossSubnetQuery,
+    ) -> Result<CrossSubnetQueryResponse, UserError>;
+}
+
 /// Interface for the component to filter out ingress messages that
 /// the canister is not willing to accept.
 pub type IngressFilterService = BoxCloneService<
@@ -391,6 +446,34 @@ fn stable_memory_api_round_trip() {
     }
 }
 
+/// Indicates whether a native grow instruction grew the Wasm heap or a table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GrowableMemory {
+    WasmMemory = 0,
+    Table = 1,
+}
+
+impl TryFrom<i32> for GrowableMemory {
+    type Error = ();
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::WasmMemory),
+            1 => Ok(Self::Table),
+            _ => Err(()),
+        }
+    }
+}
+
+#[test]
+fn growable_memory_round_trip() {
+    for i in 0..10 {
+        if let Ok(memory) = GrowableMemory::try_from(i) {
+            assert_eq!(i, memory as i32)
+        }
+    }
+}
+
 /// Indicates whether an attempt to grow stable memory succeeded or failed.
 pub enum StableGrowOutcome {
     Success,
@@ -438,14 +521,18 @@ pub trait SystemApi {
     /// Canister id of the executing canister.
     fn canister_id(&self) -> ic_types::CanisterId;
 
+    /// Records the specified bytes on the heap as a new entry of the canister
+    /// log. Trap messages are prefixed to tell them apart from debug prints.
+    fn save_log_message(&mut self, is_trap: bool, src: usize, size: usize, heap: &[u8]);
+
     /// Copies `size` bytes starting from `offset` inside the opaque caller blob
     /// and copies them to heap[dst..dst+size]. The caller is the canister
     /// id in case of requests or the user id in case of an ingress message.
     fn ic0_msg_caller_copy(
         &self,
-        dst: u32,
-        offset: u32,
-        size: u32,
+        dst: usize,
+        offset: usize,
+        size: usize,
         heap: &mut [u8],
     ) -> HypervisorResult<()>;
 
@@ -459,9 +546,9 @@ pub trait SystemApi {
     /// memory[dst..dst+size].
     fn ic0_msg_arg_data_copy(
         &self,
-        dst: u32,
-        offset: u32,
-        size: u32,
+        dst: usize,
+        offset: usize,
+        size: usize,
         heap: &mut [u8],
     ) -> HypervisorResult<()>;
 
@@ -473,9 +560,9 @@ pub trait SystemApi {
     /// only be called in the context of inspecting messages.
     fn ic0_msg_method_name_copy(
         &self,
-        dst: u32,
-        offset: u32,
-        size: u32,
+        dst: usize,
+        offset: usize,
+        size: usize,
         heap: &mut [u8],
     ) -> HypervisorResult<()>;
 
@@ -488,8 +575,8 @@ pub trait SystemApi {
     /// it to the (initially empty) data reply.
     fn ic0_msg_reply_data_append(
         &mut self,
-        src: u32,
-        size: u32,
+        src: usize,
+        size: usize,
         heap: &[u8],
     ) -> HypervisorResult<()>;
 
@@ -505,7 +592,7 @@ pub trait SystemApi {
     fn ic0_msg_reject_code(&self) -> HypervisorResult<i32>;
 
     /// Replies to sender with an error message
-    fn ic0_msg_reject(&mut self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<()>;
+    fn ic0_msg_reject(&mut self, src: usize, size: usize, heap: &[u8]) -> HypervisorResult<()>;
 
     /// Returns the length of the reject message in bytes.
     ///
@@ -524,9 +611,9 @@ pub trait SystemApi {
     /// called from inside a reject callback.
     fn ic0_msg_reject_msg_copy(
         &self,
-        dst: u32,
-        offset: u32,
-        size: u32,
+        dst: usize,
+        offset: usize,
+        size: usize,
         heap: &mut [u8],
     ) -> HypervisorResult<()>;
 
@@ -537,17 +624,92 @@ pub trait SystemApi {
     /// canister to heap[dst..dst+size].
     fn ic0_canister_self_copy(
         &mut self,
-        dst: u32,
-        offset: u32,
-        size: u32,
+        dst: usize,
+        offset: usize,
+        size: usize,
+        heap: &mut [u8],
+    ) -> HypervisorResult<()>;
+
+    /// Returns the size of the blob corresponding to the id of the subnet
+    /// hosting the canister.
+    fn ic0_subnet_self_size(&self) -> HypervisorResult<usize>;
+
+    /// Copies `size` bytes starting from `offset` in the id blob of the
+    /// subnet hosting the canister to heap[dst..dst+size].
+    fn ic0_subnet_self_copy(
+        &mut self,
+        dst: usize,
+        offset: usize,
+        size: usize,
+        heap: &mut [u8],
+    ) -> HypervisorResult<()>;
+
+    /// Returns 1 if the canister is being run in replicated mode (i.e. its
+    /// execution goes through consensus) and 0 otherwise.
+    fn ic0_in_replicated_execution(&self) -> HypervisorResult<i32>;
+
+    /// Returns the number of environment variables of the canister.
+    fn ic0_env_var_count(&self) -> HypervisorResult<usize>;
+
+    /// Returns the size of the name of the environment variable at `index`,
+    /// with variables ordered by name.
+    ///
+    /// Traps if `index` is out of bounds.
+    fn ic0_env_var_name_size(&self, index: usize) -> HypervisorResult<usize>;
+
+    /// Copies `size` bytes starting from `offset` in the name of the
+    /// environment variable at `index` to heap[dst..dst+size].
+    ///
+    /// Traps if `index` is out of bounds.
+    fn ic0_env_var_name_copy(
+        &self,
+        index: usize,
+        dst: usize,
+        offset: usize,
+        size: usize,
+        heap: &mut [u8],
+    ) -> HypervisorResult<()>;
+
+    /// Returns 1 if an environment variable with the name in
+    /// heap[name_src..name_src+name_size] exists and 0 otherwise.
+    fn ic0_env_var_name_exists(
+        &self,
+        name_src: usize,
+        name_size: usize,
+        heap: &[u8],
+    ) -> HypervisorResult<i32>;
+
+    /// Returns the size of the value of the environment variable with the name
+    /// in heap[name_src..name_src+name_size].
+    ///
+    /// Traps if no such variable exists.
+    fn ic0_env_var_value_size(
+        &self,
+        name_src: usize,
+        name_size: usize,
+        heap: &[u8],
+    ) -> HypervisorResult<usize>;
+
+    /// Copies `size` bytes starting from `offset` in the value of the
+    /// environment variable with the name in heap[name_src..name_src+name_size]
+    /// to heap[dst..dst+size].
+    ///
+    /// Traps if no such variable exists.
+    fn ic0_env_var_value_copy(
+        &self,
+        name_src: usize,
+        name_size: usize,
+        dst: usize,
+        offset: usize,
+        size: usize,
         heap: &mut [u8],
     ) -> HypervisorResult<()>;
 
     /// Outputs the specified bytes on the heap as a string on STDOUT.
-    fn ic0_debug_print(&self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<()>;
+    fn ic0_debug_print(&self, src: usize, size: usize, heap: &[u8]) -> HypervisorResult<()>;
 
     /// Traps, with a possibly helpful message
-    fn ic0_trap(&self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<()>;
+    fn ic0_trap(&self, src: usize, size: usize, heap: &[u8]) -> HypervisorResult<()>;
 
     /// Begins assembling a call to the canister specified by
     /// callee_src/callee_size at method name_src/name_size. Two mandatory
@@ -561,10 +723,10 @@ pub trait SystemApi {
     #[allow(clippy::too_many_arguments)]
     fn ic0_call_new(
         &mut self,
-        callee_src: u32,
-        callee_size: u32,
-        name_src: u32,
-        name_len: u32,
+        callee_src: usize,
+        callee_size: usize,
+        name_src: usize,
+        name_len: usize,
         reply_fun: u32,
         reply_env: u32,
         reject_fun: u32,
@@ -575,7 +737,12 @@ pub trait SystemApi {
     /// Appends the specified bytes to the argument of the call. Initially, the
     /// argument is empty. This can be called multiple times between
     /// `ic0.call_new` and `ic0.call_perform`.
-    fn ic0_call_data_append(&mut self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<()>;
+    fn ic0_call_data_append(
+        &mut self,
+        src: usize,
+        size: usize,
+        heap: &[u8],
+    ) -> HypervisorResult<()>;
 
     /// Specifies the closure to be called if the reply/reject closures trap.
     /// Can be called at most once between `ic0.call_new` and
@@ -584,6 +751,12 @@ pub trait SystemApi {
     /// See <https://sdk.dfinity.org/docs/interface-spec/index.html#system-api-call>
     fn ic0_call_on_cleanup(&mut self, fun: u32, env: u32) -> HypervisorResult<()>;
 
+    /// Turns the call under construction into a best-effort call: the caller
+    /// stops waiting for a response after `timeout_seconds` (capped by the
+    /// system) and gets a `SYS_UNKNOWN` reject instead. Can be called at most
+    /// once between `ic0.call_new` and `ic0.call_perform`.
+    fn ic0_call_with_best_effort_response(&mut self, timeout_seconds: u32) -> HypervisorResult<()>;
+
     /// (deprecated) Please use `ic0_call_cycles_add128` instead, as this API
     /// can only add a 64-bit value.
     ///
@@ -727,6 +900,10 @@ pub trait SystemApi {
     /// The canister can set a global one-off timer at the specific time.
     fn ic0_global_timer_set(&mut self, time: Time) -> HypervisorResult<Time>;
 
+    /// The canister can set a named one-off timer at the specific time.
+    /// Setting the time to zero cancels the timer.
+    fn ic0_timer_set(&mut self, timer_id: u64, time: Time) -> HypervisorResult<Time>;
+
     /// The canister can query the IC for its version.
     fn ic0_canister_version(&self) -> HypervisorResult<u64>;
 
@@ -761,12 +938,13 @@ pub trait SystemApi {
 
     /// This system call is not part of the public spec. It's called after a
     /// native `memory.grow` or `table.grow` has been called to check whether
-    /// there's enough available memory left.
+    /// there's enough available memory left. `memory` tells which of the two
+    /// instructions was executed.
     fn update_available_memory(
         &mut self,
         native_memory_grow_res: i64,
         additional_elements: u64,
-        element_size: u64,
+        memory: GrowableMemory,
     ) -> HypervisorResult<()>;
 
     /// Attempts to allocate memory before calling stable grow. Will also check
@@ -796,7 +974,7 @@ pub trait SystemApi {
     /// The amount of cycles is represented by a 128-bit value
     /// and is copied in the canister memory starting
     /// starting at the location `dst`.
-    fn ic0_canister_cycle_balance128(&self, dst: u32, heap: &mut [u8]) -> HypervisorResult<()>;
+    fn ic0_canister_cycle_balance128(&self, dst: usize, heap: &mut [u8]) -> HypervisorResult<()>;
 
     /// (deprecated) Please use `ic0_msg_cycles_available128` instead.
     /// This API supports only 64-bit values.
@@ -812,7 +990,7 @@ pub trait SystemApi {
     /// The amount of cycles is represented by a 128-bit value
     /// and is copied in the canister memory starting
     /// starting at the location `dst`.
-    fn ic0_msg_cycles_available128(&self, dst: u32, heap: &mut [u8]) -> HypervisorResult<()>;
+    fn ic0_msg_cycles_available128(&self, dst: usize, heap: &mut [u8]) -> HypervisorResult<()>;
 
     /// (deprecated) Please use `ic0_msg_cycles_refunded128` instead.
     /// This API supports only 64-bit values.
@@ -828,7 +1006,7 @@ pub trait SystemApi {
     /// The amount of cycles is represented by a 128-bit value
     /// and is copied in the canister memory starting
     /// starting at the location `dst`.
-    fn ic0_msg_cycles_refunded128(&self, dst: u32, heap: &mut [u8]) -> HypervisorResult<()>;
+    fn ic0_msg_cycles_refunded128(&self, dst: usize, heap: &mut [u8]) -> HypervisorResult<()>;
 
     /// (deprecated) Please use `ic0_msg_cycles_accept128` instead.
     /// This API supports only 64-bit values.
@@ -874,13 +1052,18 @@ pub trait SystemApi {
     fn ic0_msg_cycles_accept128(
         &mut self,
         max_amount: Cycles,
-        dst: u32,
+        dst: usize,
         heap: &mut [u8],
     ) -> HypervisorResult<()>;
 
     /// Sets the certified data for the canister.
     /// See: <https://sdk.dfinity.org/docs/interface-spec/index.html#system-api-certified-data>
-    fn ic0_certified_data_set(&mut self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<()>;
+    fn ic0_certified_data_set(
+        &mut self,
+        src: usize,
+        size: usize,
+        heap: &[u8],
+    ) -> HypervisorResult<()>;
 
     /// If run in non-replicated execution (i.e. query),
     /// returns 1 if the data certificate is present, 0 otherwise.
@@ -898,9 +1081,9 @@ pub trait SystemApi {
     /// Traps if data_certificate_present returns 0.
     fn ic0_data_certificate_copy(
         &self,
-        dst: u32,
-        offset: u32,
-        size: u32,
+        dst: usize,
+        offset: usize,
+        size: usize,
         heap: &mut [u8],
     ) -> HypervisorResult<()>;
 
@@ -925,7 +1108,7 @@ pub trait SystemApi {
     /// otherwise a 0 is returned. It can be called multiple times.
     ///
     /// This system call traps if src+size exceeds the size of the WebAssembly memory.
-    fn ic0_is_controller(&self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<u32>;
+    fn ic0_is_controller(&self, src: usize, size: usize, heap: &[u8]) -> HypervisorResult<u32>;
 
     /// Burns the provided `amount` cycles.
     /// Removes cycles from the canister's balance.
@@ -937,9 +1120,53 @@ pub trait SystemApi {
     fn ic0_cycles_burn128(
         &mut self,
         amount: Cycles,
-        dst: u32,
+        dst: usize,
+        heap: &mut [u8],
+    ) -> HypervisorResult<()>;
+
+    /// Copies to `dst` the amount of cycles that would be withdrawn from the
+    /// canister's balance (in addition to the attached cycles) when performing
+    /// a call with a method name and argument of the given sizes.
+    ///
+    /// The amount is written as a 128-bit value.
+    fn ic0_cost_call(
+        &self,
+        method_name_size: u64,
+        payload_size: u64,
+        dst: usize,
         heap: &mut [u8],
     ) -> HypervisorResult<()>;
+
+    /// Copies to `dst` the amount of cycles that need to be attached to a
+    /// `create_canister` call on this subnet.
+    fn ic0_cost_create_canister(&self, dst: usize, heap: &mut [u8]) -> HypervisorResult<()>;
+
+    /// Copies to `dst` the amount of cycles that need to be attached to an
+    /// `http_request` call with the given request size and maximum response
+    /// size.
+    fn ic0_cost_http_request(
+        &self,
+        request_size: u64,
+        max_res_bytes: u64,
+        dst: usize,
+        heap: &mut [u8],
+    ) -> HypervisorResult<()>;
+
+    /// Copies to `dst` the amount of cycles that need to be attached to a
+    /// `sign_with_ecdsa` call using the key whose name is in `src..src+size`
+    /// and whose curve is `curve`.
+    ///
+    /// Returns 0 on success, 1 if `curve` is invalid and 2 if no key with the
+    /// given name and curve is known. Nothing is written to `dst` in the
+    /// latter two cases.
+    fn ic0_cost_sign_with_ecdsa(
+        &self,
+        src: usize,
+        size: usize,
+        curve: u32,
+        dst: usize,
+        heap: &mut [u8],
+    ) -> HypervisorResult<u32>;
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -1041,6 +1268,12 @@ pub struct WasmExecutionOutput {
     pub allocated_bytes: NumBytes,
     pub allocated_message_bytes: NumBytes,
     pub instance_stats: InstanceStats,
+    /// The log records produced by the execution, which are yet to be
+    /// appended to the canister log.
+    pub canister_log: CanisterLog,
+    /// The Wasm profile of the execution. Empty unless the Wasm profiler is
+    /// enabled.
+    pub wasm_profile: WasmProfile,
 }
 
 impl fmt::Display for WasmExecutionOutput {
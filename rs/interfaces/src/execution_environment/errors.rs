@@ -39,6 +39,71 @@ impl std::fmt::Display for TrapCode {
     }
 }
 
+/// A frame of a canister backtrace.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BacktraceFrame {
+    /// The index of the function in the canister's Wasm module.
+    pub func_index: u32,
+    /// The name of the function from the `name` custom section, if any.
+    pub func_name: Option<String>,
+}
+
+/// A symbolized Wasm backtrace of a canister trap. The innermost frame comes
+/// first.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CanisterBacktrace {
+    pub frames: Vec<BacktraceFrame>,
+    /// The number of outermost frames that were dropped to bound the size of
+    /// the backtrace.
+    pub omitted_frames: usize,
+}
+
+/// The maximum number of bytes of a function name in a displayed backtrace.
+/// Longer names, e.g. of deeply nested generic functions, are truncated.
+const MAX_BACKTRACE_FUNC_NAME_BYTES: usize = 256;
+
+/// The maximum number of bytes of the frames of a displayed backtrace. Frames
+/// beyond this limit are counted as omitted.
+const MAX_BACKTRACE_BYTES: usize = 4 * 1024;
+
+/// Truncates `name` to at most `MAX_BACKTRACE_FUNC_NAME_BYTES` bytes, on a
+/// character boundary.
+fn truncate_func_name(name: &str) -> String {
+    if name.len() <= MAX_BACKTRACE_FUNC_NAME_BYTES {
+        return name.to_string();
+    }
+    let mut end = MAX_BACKTRACE_FUNC_NAME_BYTES;
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...", &name[..end])
+}
+
+impl std::fmt::Display for CanisterBacktrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Canister Backtrace:")?;
+        let mut total_bytes = 0;
+        let mut displayed_frames = 0;
+        for frame in &self.frames {
+            let line = match &frame.func_name {
+                Some(name) => truncate_func_name(name),
+                None => format!("unknown function at index {}", frame.func_index),
+            };
+            total_bytes += line.len() + 1;
+            if total_bytes > MAX_BACKTRACE_BYTES {
+                break;
+            }
+            write!(f, "\n{}", line)?;
+            displayed_frames += 1;
+        }
+        let omitted_frames = self.omitted_frames + self.frames.len() - displayed_frames;
+        if omitted_frames > 0 {
+            write!(f, "\n... {} more frames", omitted_frames)?;
+        }
+        Ok(())
+    }
+}
+
 /// Error when a canister's balance is too low compared to its freezing
 /// threshold and cannot perform the requested action.
 ///
@@ -85,8 +150,12 @@ pub enum HypervisorError {
     /// We could not instrument the wasm module
     InstrumentationFailed(WasmInstrumentationError),
     /// Canister Wasm trapped (e.g. by executing the `unreachable`
-    /// instruction or dividing by zero).
-    Trapped(TrapCode),
+    /// instruction or dividing by zero). The backtrace is only captured if
+    /// the `canister_backtrace` feature is enabled.
+    Trapped {
+        trap_code: TrapCode,
+        backtrace: Option<CanisterBacktrace>,
+    },
     /// Canister explicitly called `ic.trap`.
     CalledTrap(String),
     /// An attempt was made to execute a message on a canister that does not
@@ -148,6 +217,12 @@ pub enum HypervisorError {
         available: Cycles,
         threshold: Cycles,
     },
+    /// An attempt was made to grow the Wasm heap above the canister's
+    /// `wasm_memory_limit` setting.
+    WasmMemoryLimitExceeded {
+        bytes: NumBytes,
+        limit: NumBytes,
+    },
 }
 
 impl From<WasmInstrumentationError> for HypervisorError {
@@ -246,9 +321,17 @@ impl HypervisorError {
                     canister_id, err
                 ),
             ),
-            Self::Trapped(code) => UserError::new(
+            Self::Trapped {
+                trap_code,
+                backtrace,
+            } => UserError::new(
                 E::CanisterTrapped,
-                format!("Canister {} trapped: {}", canister_id, code),
+                match backtrace {
+                    Some(backtrace) => {
+                        format!("Canister {} trapped: {}\n{}", canister_id, trap_code, backtrace)
+                    }
+                    None => format!("Canister {} trapped: {}", canister_id, trap_code),
+                },
             ),
             Self::CalledTrap(msg) => UserError::new(
                 E::CanisterCalledTrap,
@@ -353,6 +436,14 @@ impl HypervisorError {
                      bytes,
                      threshold - available)
             ),
+            Self::WasmMemoryLimitExceeded { bytes, limit } => UserError::new(
+                E::CanisterWasmMemoryLimitExceeded,
+                format!(
+                    "Canister {} attempted to grow its Wasm memory to {}, which exceeds \
+                     its Wasm memory limit of {}.",
+                    canister_id, bytes, limit
+                ),
+            ),
         }
     }
 
@@ -366,7 +457,7 @@ impl HypervisorError {
             HypervisorError::InstructionLimitExceeded => "InstructionLimitExceeded",
             HypervisorError::InvalidWasm(_) => "InvalidWasm",
             HypervisorError::InstrumentationFailed(_) => "InstrumentationFailed",
-            HypervisorError::Trapped(_) => "Trapped",
+            HypervisorError::Trapped { .. } => "Trapped",
             HypervisorError::CalledTrap(_) => "CalledTrap",
             HypervisorError::WasmModuleNotFound => "WasmModuleNotFound",
             HypervisorError::OutOfMemory => "OutOfMemory",
@@ -391,6 +482,48 @@ impl HypervisorError {
             HypervisorError::InsufficientCyclesInMessageMemoryGrow { .. } => {
                 "InsufficientCyclesInMessageMemoryGrow"
             }
+            HypervisorError::WasmMemoryLimitExceeded { .. } => "WasmMemoryLimitExceeded",
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canister_backtrace_truncates_long_function_names() {
+        let backtrace = CanisterBacktrace {
+            frames: vec![BacktraceFrame {
+                func_index: 0,
+                func_name: Some("é".repeat(MAX_BACKTRACE_FUNC_NAME_BYTES)),
+            }],
+            omitted_frames: 0,
+        };
+        let expected = format!(
+            "Canister Backtrace:\n{}...",
+            "é".repeat(MAX_BACKTRACE_FUNC_NAME_BYTES / 2)
+        );
+        assert_eq!(backtrace.to_string(), expected);
+    }
+
+    #[test]
+    fn canister_backtrace_caps_total_length() {
+        let backtrace = CanisterBacktrace {
+            frames: vec![
+                BacktraceFrame {
+                    func_index: 0,
+                    func_name: Some("f".repeat(MAX_BACKTRACE_FUNC_NAME_BYTES)),
+                };
+                100
+            ],
+            omitted_frames: 3,
+        };
+        let displayed = backtrace.to_string();
+        assert!(displayed.len() <= "Canister Backtrace:".len() + MAX_BACKTRACE_BYTES + 64);
+
+        let displayed_frames = MAX_BACKTRACE_BYTES / (MAX_BACKTRACE_FUNC_NAME_BYTES + 1);
+        assert_eq!(displayed.lines().count(), 1 + displayed_frames + 1);
+        assert!(displayed.ends_with(&format!("\n... {} more frames", 100 - displayed_frames + 3)));
+    }
+}
@@ -77,6 +77,7 @@ const PHASE_COMMIT: &str = "commit";
 const METRIC_PROCESS_BATCH_DURATION: &str = "mr_process_batch_duration_seconds";
 const METRIC_PROCESS_BATCH_PHASE_DURATION: &str = "mr_process_batch_phase_duration_seconds";
 const METRIC_TIMED_OUT_REQUESTS_TOTAL: &str = "mr_timed_out_requests_total";
+const METRIC_TIMED_OUT_CALLBACKS_TOTAL: &str = "mr_timed_out_callbacks_total";
 const METRIC_SUBNET_SPLIT_HEIGHT: &str = "mr_subnet_split_height";
 const BLOCKS_PROPOSED_TOTAL: &str = "mr_blocks_proposed_total";
 const BLOCKS_NOT_PROPOSED_TOTAL: &str = "mr_blocks_not_proposed_total";
@@ -260,6 +261,8 @@ pub(crate) struct MessageRoutingMetrics {
     pub process_batch_phase_duration: HistogramVec,
     /// Number of timed out requests.
     pub timed_out_requests_total: IntCounter,
+    /// Number of timed out best-effort callbacks.
+    pub timed_out_callbacks_total: IntCounter,
     /// Height at which the subnet last split (if during the lifetime of this
     /// replica process; otherwise zero).
     pub subnet_split_height: IntGaugeVec,
@@ -338,6 +341,10 @@ impl MessageRoutingMetrics {
                 METRIC_TIMED_OUT_REQUESTS_TOTAL,
                 "Count of timed out requests.",
             ),
+            timed_out_callbacks_total: metrics_registry.int_counter(
+                METRIC_TIMED_OUT_CALLBACKS_TOTAL,
+                "Count of timed out best-effort callbacks.",
+            ),
             subnet_split_height: metrics_registry.int_gauge_vec(
                 METRIC_SUBNET_SPLIT_HEIGHT,
                 "Height at which the subnet last split (if during the lifetime of this replica process).",
@@ -810,6 +817,9 @@ impl BatchProcessorImpl {
                 })
                 .transpose()?
                 .unwrap_or_default();
+            let max_inter_canister_payload_in_bytes = subnet_record
+                .max_inter_canister_payload_in_bytes
+                .map(NumBytes::from);
 
             subnets.insert(
                 *subnet_id,
@@ -819,6 +829,7 @@ impl BatchProcessorImpl {
                     subnet_type,
                     subnet_features,
                     ecdsa_keys_held,
+                    max_inter_canister_payload_in_bytes,
                 },
             );
         }
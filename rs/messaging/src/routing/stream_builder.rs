@@ -1,10 +1,12 @@
 use crate::message_routing::LatencyMetrics;
+use ic_certification_version::CertificationVersion;
 use ic_constants::SYSTEM_SUBNET_STREAM_MSG_LIMIT;
 use ic_error_types::RejectCode;
-use ic_logger::{error, warn, ReplicaLogger};
+use ic_logger::{debug, error, warn, ReplicaLogger};
 use ic_metrics::{buckets::decimal_buckets, MetricsRegistry};
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::{
+    metadata_state::Streams,
     replicated_state::{
         PeekableOutputIterator, ReplicatedStateMessageRouting, MR_SYNTHETIC_REJECT_MESSAGE_MAX_LEN,
     },
@@ -13,10 +15,11 @@ use ic_replicated_state::{
 use ic_types::{
     messages::{
         Payload, RejectContext, Request, RequestOrResponse, Response,
-        MAX_INTER_CANISTER_PAYLOAD_IN_BYTES, MAX_REJECT_MESSAGE_LEN_BYTES,
+        MAX_INTER_CANISTER_PAYLOAD_IN_BYTES, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64,
+        MAX_REJECT_MESSAGE_LEN_BYTES,
     },
     xnet::QueueId,
-    CountBytes, SubnetId,
+    CanisterId, CountBytes, SubnetId, Time,
 };
 #[cfg(test)]
 use mockall::automock;
@@ -44,6 +47,14 @@ struct StreamBuilderMetrics {
     pub critical_error_payload_too_large: IntCounter,
     /// Critical error for responses dropped due to destination not found.
     pub critical_error_response_destination_not_found: IntCounter,
+    /// Messages routed into streams at or above half their limits by the latest
+    /// `build_streams()` call, for the `MAX_CANISTERS_PER_METRIC` source
+    /// canisters with the most such messages.
+    pub near_limit_routed_messages: IntGaugeVec,
+    /// Messages left in output queues by the latest `build_streams()` call due
+    /// to full streams or fair share limits, for the `MAX_CANISTERS_PER_METRIC`
+    /// source canisters with the most such messages.
+    pub backlogged_messages: IntGaugeVec,
 }
 
 /// Desired byte size of an outgoing stream.
@@ -58,21 +69,102 @@ const TARGET_STREAM_SIZE_BYTES: usize = 10 * 1024 * 1024;
 /// `count_bytes()` is greater than or equal to `TARGET_STREAM_SIZE_BYTES`.
 const MAX_STREAM_MESSAGES: usize = 50_000;
 
+/// Maximum number of source canisters exported by the per-canister metrics
+/// (`near_limit_routed_messages` and `backlogged_messages`), to keep their
+/// cardinality bounded.
+const MAX_CANISTERS_PER_METRIC: usize = 10;
+
+/// Message count and byte size of the messages from each source canister in a
+/// stream. Used to enforce fair sharing of streams at or above half their
+/// limits.
+#[derive(Default)]
+struct StreamSenders(BTreeMap<CanisterId, (usize, usize)>);
+
+impl StreamSenders {
+    /// Computes the message count and byte size per sender of all messages in
+    /// `stream`.
+    fn new(stream: &Stream) -> Self {
+        let mut senders = Self::default();
+        for (_, msg) in stream.messages().iter() {
+            senders.observe(msg);
+        }
+        senders
+    }
+
+    /// Accounts for `msg` having been pushed into the stream.
+    fn observe(&mut self, msg: &RequestOrResponse) {
+        let (messages, bytes) = self.0.entry(msg.sender()).or_default();
+        *messages += 1;
+        *bytes += msg.count_bytes();
+    }
+
+    /// Tests whether `sender` has used up its fair share of the stream: an equal
+    /// part of `max_messages` and `max_bytes` among `sender`, all other senders
+    /// with messages in the stream and one extra share, reserved for senders
+    /// without messages in the stream.
+    fn is_over_fair_share(
+        &self,
+        sender: &CanisterId,
+        max_messages: usize,
+        max_bytes: usize,
+    ) -> bool {
+        self.is_over_fair_share_after(sender, (0, 0), max_messages, max_bytes)
+    }
+
+    /// Tests whether `sender` will have used up its fair share of the stream
+    /// (see `is_over_fair_share()`) after `pending_messages` more messages with
+    /// a total byte size of `pending_bytes` from `sender` are pushed into it.
+    fn is_over_fair_share_after(
+        &self,
+        sender: &CanisterId,
+        (pending_messages, pending_bytes): (usize, usize),
+        max_messages: usize,
+        max_bytes: usize,
+    ) -> bool {
+        let (messages, bytes) = self.0.get(sender).copied().unwrap_or_default();
+        let (messages, bytes) = (messages + pending_messages, bytes + pending_bytes);
+        let other_senders = self.0.len() - usize::from(self.0.contains_key(sender));
+        let shares = other_senders + 2;
+        messages >= max_messages.div_ceil(shares) || bytes >= max_bytes.div_ceil(shares)
+    }
+}
+
+/// Resets `gauge` and sets it to the respective count for (at most)
+/// `MAX_CANISTERS_PER_METRIC` canisters with the highest counts, labeled by
+/// canister ID. Ties are broken by canister ID.
+fn set_top_canister_gauges(gauge: &IntGaugeVec, counts: BTreeMap<CanisterId, usize>) {
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|(canister_a, count_a), (canister_b, count_b)| {
+        count_b.cmp(count_a).then(canister_a.cmp(canister_b))
+    });
+
+    gauge.reset();
+    for (canister, count) in counts.into_iter().take(MAX_CANISTERS_PER_METRIC) {
+        gauge
+            .with_label_values(&[&canister.to_string()])
+            .set(count as i64);
+    }
+}
+
 const METRIC_STREAM_MESSAGES: &str = "mr_stream_messages";
 const METRIC_STREAM_BYTES: &str = "mr_stream_bytes";
 const METRIC_STREAM_BEGIN: &str = "mr_stream_begin";
 const METRIC_ROUTED_MESSAGES: &str = "mr_routed_message_count";
 const METRIC_ROUTED_PAYLOAD_SIZES: &str = "mr_routed_payload_size_bytes";
+const METRIC_NEAR_LIMIT_ROUTED_MESSAGES: &str = "mr_near_limit_routed_messages";
+const METRIC_BACKLOGGED_MESSAGES: &str = "mr_backlogged_messages";
 
 const LABEL_TYPE: &str = "type";
 const LABEL_STATUS: &str = "status";
 const LABEL_REMOTE: &str = "remote";
+const LABEL_CANISTER: &str = "canister";
 
 const LABEL_VALUE_TYPE_REQUEST: &str = "request";
 const LABEL_VALUE_TYPE_RESPONSE: &str = "response";
 const LABEL_VALUE_STATUS_SUCCESS: &str = "success";
 const LABEL_VALUE_STATUS_CANISTER_NOT_FOUND: &str = "canister_not_found";
 const LABEL_VALUE_STATUS_PAYLOAD_TOO_LARGE: &str = "payload_too_large";
+const LABEL_VALUE_STATUS_DEADLINE_EXPIRED: &str = "deadline_expired";
 
 const CRITICAL_ERROR_INFINITE_LOOP: &str = "mr_stream_builder_infinite_loop";
 const CRITICAL_ERROR_PAYLOAD_TOO_LARGE: &str = "mr_stream_builder_payload_too_large";
@@ -107,6 +199,16 @@ impl StreamBuilderMetrics {
             // 10 B - 5 MB
             decimal_buckets(1, 6),
         );
+        let near_limit_routed_messages = metrics_registry.int_gauge_vec(
+            METRIC_NEAR_LIMIT_ROUTED_MESSAGES,
+            "Messages routed into streams at or above half their limits, for the top source canisters.",
+            &[LABEL_CANISTER],
+        );
+        let backlogged_messages = metrics_registry.int_gauge_vec(
+            METRIC_BACKLOGGED_MESSAGES,
+            "Messages left in output queues due to full streams or fair share limits, for the top source canisters.",
+            &[LABEL_CANISTER],
+        );
         let critical_error_infinite_loops =
             metrics_registry.error_counter(CRITICAL_ERROR_INFINITE_LOOP);
         let critical_error_payload_too_large =
@@ -139,6 +241,8 @@ impl StreamBuilderMetrics {
             critical_error_infinite_loops,
             critical_error_payload_too_large,
             critical_error_response_destination_not_found,
+            near_limit_routed_messages,
+            backlogged_messages,
         }
     }
 }
@@ -197,6 +301,7 @@ impl StreamBuilderImpl {
                             MR_SYNTHETIC_REJECT_MESSAGE_MAX_LEN,
                         ),
                     ),
+                    deadline: req.deadline,
                 }
                 .into(),
                 // Arbitrary large amount, pushing a response always returns memory.
@@ -209,9 +314,10 @@ impl StreamBuilderImpl {
 
     /// Records the result of routing an XNet message.
     fn observe_message_status(&self, msg: &RequestOrResponse, status: &str) {
-        let msg_type = match msg {
-            RequestOrResponse::Request(_) => LABEL_VALUE_TYPE_REQUEST,
-            RequestOrResponse::Response(_) => LABEL_VALUE_TYPE_RESPONSE,
+        let msg_type = if msg.is_request() {
+            LABEL_VALUE_TYPE_REQUEST
+        } else {
+            LABEL_VALUE_TYPE_RESPONSE
         };
         self.observe_message_type_status(msg_type, status)
     }
@@ -255,35 +361,102 @@ impl StreamBuilderImpl {
             message
         }
 
-        /// Tests whether a stream is over the message count limit, byte limit or (if
-        /// directed at a system subnet) over `2 * SYSTEM_SUBNET_STREAM_MSG_LIMIT`.
-        fn is_at_limit(
-            stream: Option<&Stream>,
+        /// Returns the message count and byte size limits of the stream to
+        /// `destination_subnet_type`: `max_stream_messages` and
+        /// `target_stream_size_bytes`; with the message count limit reduced to
+        /// `2 * SYSTEM_SUBNET_STREAM_MSG_LIMIT` for non-local streams to system
+        /// subnets (i.e., excluding the loopback stream on system subnets).
+        fn stream_limits(
             max_stream_messages: usize,
             target_stream_size_bytes: usize,
             is_local_message: bool,
             destination_subnet_type: SubnetType,
+        ) -> (usize, usize) {
+            if !is_local_message && destination_subnet_type == SubnetType::System {
+                (
+                    max_stream_messages.min(2 * SYSTEM_SUBNET_STREAM_MSG_LIMIT),
+                    target_stream_size_bytes,
+                )
+            } else {
+                (max_stream_messages, target_stream_size_bytes)
+            }
+        }
+
+        /// Tests whether a stream is at or over `1 / divisor` of the given message
+        /// count or byte limit.
+        fn is_over(
+            stream: Option<&Stream>,
+            (max_messages, max_bytes): (usize, usize),
+            divisor: usize,
+        ) -> bool {
+            match stream {
+                Some(stream) => {
+                    stream.messages().len() >= max_messages / divisor
+                        || stream.count_bytes() >= max_bytes / divisor
+                }
+                None => false,
+            }
+        }
+
+        /// Tests whether all `fragments` of a message from `sender` can be pushed
+        /// into `stream`, one after the other, without the stream being at or
+        /// over `limits` before any of them; or, once the stream is at or over
+        /// half its limits, without `sender` being over its fair share (unless
+        /// `fair_share` is `None`, i.e. for the subnet queues).
+        ///
+        /// Fragments of a message are routed consecutively, so either all or none
+        /// of them are pushed. Always `true` for an empty stream, so that every
+        /// message can eventually be routed.
+        fn fragments_fit(
+            stream: Option<&Stream>,
+            fair_share: Option<&StreamSenders>,
+            sender: &CanisterId,
+            fragments: &[RequestOrResponse],
+            (max_messages, max_bytes): (usize, usize),
         ) -> bool {
             let stream = match stream {
-                Some(stream) => stream,
-                None => return false,
+                Some(stream) if !stream.messages().is_empty() => stream,
+                _ => return true,
             };
-            let stream_messages_len = stream.messages().len();
-
-            if stream_messages_len >= max_stream_messages
-                || stream.count_bytes() >= target_stream_size_bytes
-            {
-                // At limit if message count or byte size limits (enforced across all outgoing
-                // streams) are hit.
-                return true;
+
+            let (mut messages, mut bytes) = (stream.messages().len(), stream.count_bytes());
+            let (mut pending_messages, mut pending_bytes) = (0, 0);
+            for fragment in fragments {
+                if messages >= max_messages || bytes >= max_bytes {
+                    return false;
+                }
+                if let Some(senders) = fair_share {
+                    if (messages >= max_messages / 2 || bytes >= max_bytes / 2)
+                        && senders.is_over_fair_share_after(
+                            sender,
+                            (pending_messages, pending_bytes),
+                            max_messages,
+                            max_bytes,
+                        )
+                    {
+                        return false;
+                    }
+                }
+                messages += 1;
+                bytes += fragment.count_bytes();
+                pending_messages += 1;
+                pending_bytes += fragment.count_bytes();
             }
+            true
+        }
 
-            // At limit if system subnet limit is hit. This is only enforced for non-local
-            // streams to system subnets (i.e., excluding the loopback stream on system
-            // subnets).
-            !is_local_message
-                && destination_subnet_type == SubnetType::System
-                && stream_messages_len >= 2 * SYSTEM_SUBNET_STREAM_MSG_LIMIT
+        /// Pushes `msg` into the stream to `destination`, updating the stream's
+        /// senders stats, if tracked.
+        fn push(
+            streams: &mut Streams,
+            stream_senders: &mut BTreeMap<SubnetId, StreamSenders>,
+            destination: SubnetId,
+            msg: RequestOrResponse,
+        ) {
+            if let Some(senders) = stream_senders.get_mut(&destination) {
+                senders.observe(&msg);
+            }
+            streams.push(destination, msg);
         }
 
         let mut streams = state.take_streams();
@@ -298,6 +471,23 @@ impl StreamBuilderImpl {
 
         let mut requests_to_reject = Vec::new();
         let mut oversized_requests = Vec::new();
+        // Per-sender stats of streams at or above half their limits, computed on
+        // demand.
+        let mut stream_senders: BTreeMap<SubnetId, StreamSenders> = BTreeMap::new();
+        let mut near_limit_routed_messages: BTreeMap<CanisterId, usize> = BTreeMap::new();
+        let mut backlogged_messages: BTreeMap<CanisterId, usize> = BTreeMap::new();
+        let subnet_queues_id = CanisterId::from(self.subnet_id);
+        let current_time = state.time();
+        // Payloads above `MAX_INTER_CANISTER_PAYLOAD_IN_BYTES` can only be routed to
+        // remote subnets (as fragments) once all subnets can decode fragments.
+        let fragments_supported = state.metadata.certification_version >= CertificationVersion::V17;
+        // Maximum payload size of an inter-canister message, as configured in the
+        // subnet record. Remote messages with payloads above
+        // `MAX_INTER_CANISTER_PAYLOAD_IN_BYTES` are fragmented.
+        let max_inter_canister_payload_in_bytes = state
+            .metadata
+            .network_topology
+            .max_inter_canister_payload_in_bytes(&self.subnet_id);
 
         let mut output_iter = state.output_into_iter();
         let mut last_output_size = usize::MAX;
@@ -305,6 +495,11 @@ impl StreamBuilderImpl {
         // Route all messages into the appropriate stream or generate reject Responses
         // when unable to (no route to canister). When a stream's byte size reaches or
         // exceeds `target_stream_size_bytes`, any matching queues are skipped.
+        //
+        // Once a stream reaches half its limits, every source canister (other than
+        // the subnet queues) is limited to its fair share of the stream (see
+        // `StreamSenders::is_over_fair_share()`), so a single canister cannot hold
+        // back everyone else's messages to the same subnet.
         while let Some((queue_id, msg)) = output_iter.peek() {
             // Cheap to clone, `RequestOrResponse` wraps `Arcs`.
             let msg = msg.clone();
@@ -327,31 +522,104 @@ impl StreamBuilderImpl {
             match routing_table.route(queue_id.dst_canister.get()) {
                 // Destination subnet found.
                 Some(dst_net_id) => {
-                    if is_at_limit(
-                        streams.get(&dst_net_id),
+                    let limits = stream_limits(
                         max_stream_messages,
                         target_stream_size_bytes,
                         self.subnet_id == dst_net_id,
                         *subnet_types
                             .get(&dst_net_id)
                             .unwrap_or(&SubnetType::Application),
-                    ) {
+                    );
+                    let stream = streams.get(&dst_net_id);
+                    if is_over(stream, limits, 1) {
                         // Stream full, skip all other messages to this destination.
-                        output_iter.exclude_queue();
+                        *backlogged_messages
+                            .entry(queue_id.src_canister)
+                            .or_default() += output_iter.exclude_queue();
                         continue;
                     }
 
+                    let is_remote = dst_net_id != self.subnet_id;
+                    let payload_limit = if is_remote && !fragments_supported {
+                        MAX_INTER_CANISTER_PAYLOAD_IN_BYTES
+                    } else {
+                        max_inter_canister_payload_in_bytes
+                    };
+
+                    // A remote message above `MAX_INTER_CANISTER_PAYLOAD_IN_BYTES` is routed
+                    // as a sequence of consecutive fragments. Each fragment counts against
+                    // the stream limits and the sender's fair share, so only route the
+                    // message once the stream can take all of them.
+                    let payload_size = msg.payload_size_bytes();
+                    if is_remote
+                        && payload_size > MAX_INTER_CANISTER_PAYLOAD_IN_BYTES
+                        && payload_size <= payload_limit
+                    {
+                        let fragments = msg
+                            .clone()
+                            .into_fragments(MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64 as usize);
+                        let fair_share = match stream {
+                            Some(stream) if queue_id.src_canister != subnet_queues_id => Some(
+                                &*stream_senders
+                                    .entry(dst_net_id)
+                                    .or_insert_with(|| StreamSenders::new(stream)),
+                            ),
+                            _ => None,
+                        };
+                        if !fragments_fit(
+                            stream,
+                            fair_share,
+                            &queue_id.src_canister,
+                            &fragments,
+                            limits,
+                        ) {
+                            // The stream cannot take the whole message yet, skip all other
+                            // messages from the queue.
+                            *backlogged_messages
+                                .entry(queue_id.src_canister)
+                                .or_default() += output_iter.exclude_queue();
+                            continue;
+                        }
+                    }
+
+                    if queue_id.src_canister != subnet_queues_id && is_over(stream, limits, 2) {
+                        let senders = stream_senders
+                            .entry(dst_net_id)
+                            .or_insert_with(|| StreamSenders::new(stream.unwrap()));
+                        if senders.is_over_fair_share(&queue_id.src_canister, limits.0, limits.1) {
+                            // Sender has used up its share of the stream, skip all other
+                            // messages from the queue.
+                            *backlogged_messages
+                                .entry(queue_id.src_canister)
+                                .or_default() += output_iter.exclude_queue();
+                            continue;
+                        }
+                        *near_limit_routed_messages
+                            .entry(queue_id.src_canister)
+                            .or_default() += 1;
+                    }
+
                     // We will route (or reject) the message, pop it.
                     let mut msg = validated_next(&mut output_iter, (queue_id, &msg));
 
                     // Reject messages with oversized payloads, as they may
                     // cause streams to permanently stall.
                     match msg {
+                        // Expired best-effort response, the originator has already timed
+                        // out the callback. Drop it.
+                        RequestOrResponse::Response(ref rep)
+                            if rep.is_best_effort() && Time::from(rep.deadline) <= current_time =>
+                        {
+                            debug!(self.log, "Dropping expired best-effort response {:?}", rep);
+                            self.observe_message_type_status(
+                                LABEL_VALUE_TYPE_RESPONSE,
+                                LABEL_VALUE_STATUS_DEADLINE_EXPIRED,
+                            );
+                        }
+
                         // Remote request above the payload size limit.
                         RequestOrResponse::Request(req)
-                            if dst_net_id != self.subnet_id
-                                && req.payload_size_bytes()
-                                    > MAX_INTER_CANISTER_PAYLOAD_IN_BYTES =>
+                            if is_remote && req.payload_size_bytes() > payload_limit =>
                         {
                             warn!(
                                 self.log,
@@ -363,12 +631,12 @@ impl StreamBuilderImpl {
                                 LABEL_VALUE_TYPE_REQUEST,
                                 LABEL_VALUE_STATUS_PAYLOAD_TOO_LARGE,
                             );
-                            oversized_requests.push(req);
+                            oversized_requests.push((req, payload_limit));
                         }
 
                         // Response above the payload size limit.
                         RequestOrResponse::Response(ref mut rep)
-                            if rep.payload_size_bytes() > MAX_INTER_CANISTER_PAYLOAD_IN_BYTES =>
+                            if rep.payload_size_bytes() > payload_limit =>
                         {
                             error!(
                                 self.log,
@@ -391,7 +659,7 @@ impl StreamBuilderImpl {
                                         RejectCode::CanisterError,
                                         format!(
                                             "Canister {} violated contract: attempted to send a message of size {} exceeding the limit {}",
-                                            rep.respondent, rep.payload_size_bytes(), MAX_INTER_CANISTER_PAYLOAD_IN_BYTES
+                                            rep.respondent, rep.payload_size_bytes(), payload_limit
                                         ),
                                     ))
                                 }
@@ -401,14 +669,28 @@ impl StreamBuilderImpl {
                                 }
                             }
 
-                            streams.push(dst_net_id, msg);
+                            push(&mut streams, &mut stream_senders, dst_net_id, msg);
+                        }
+
+                        // Remote message above the single message payload size limit: route
+                        // it as a sequence of consecutive fragments.
+                        _ if is_remote
+                            && msg.payload_size_bytes() > MAX_INTER_CANISTER_PAYLOAD_IN_BYTES =>
+                        {
+                            self.observe_message_status(&msg, LABEL_VALUE_STATUS_SUCCESS);
+                            self.observe_payload_size(&msg);
+                            for fragment in
+                                msg.into_fragments(MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64 as usize)
+                            {
+                                push(&mut streams, &mut stream_senders, dst_net_id, fragment);
+                            }
                         }
 
                         _ => {
                             // Route the message into the stream.
                             self.observe_message_status(&msg, LABEL_VALUE_STATUS_SUCCESS);
                             self.observe_payload_size(&msg);
-                            streams.push(dst_net_id, msg);
+                            push(&mut streams, &mut stream_senders, dst_net_id, msg);
                         }
                     };
                 }
@@ -434,6 +716,9 @@ impl StreamBuilderImpl {
                                 .critical_error_response_destination_not_found
                                 .inc();
                         }
+                        RequestOrResponse::Fragment(_) => {
+                            unreachable!("Message fragments are never enqueued in output queues")
+                        }
                     }
                 }
             };
@@ -450,7 +735,7 @@ impl StreamBuilderImpl {
             );
         }
 
-        for req in oversized_requests {
+        for (req, payload_limit) in oversized_requests {
             let sender = req.sender;
             self.reject_local_request(
                 &mut state,
@@ -460,11 +745,19 @@ impl StreamBuilderImpl {
                     "Canister {} violated contract: attempted to send a message of size {} exceeding the limit {}",
                     sender,
                     req.payload_size_bytes(),
-                    MAX_INTER_CANISTER_PAYLOAD_IN_BYTES
+                    payload_limit
                 ),
             );
         }
 
+        // Export the number of messages routed near stream limits and left in output
+        // queues, for the top source canisters.
+        set_top_canister_gauges(
+            &self.metrics.near_limit_routed_messages,
+            near_limit_routed_messages,
+        );
+        set_top_canister_gauges(&self.metrics.backlogged_messages, backlogged_messages);
+
         // Export the total number of enqueued messages and byte size, per stream.
         streams
             .iter()
@@ -6,7 +6,7 @@ use ic_registry_routing_table::{CanisterIdRange, RoutingTable};
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::{
     testing::{CanisterQueuesTesting, ReplicatedStateTesting, SystemStateTesting},
-    CanisterState, InputQueueType, ReplicatedState, Stream,
+    CanisterState, InputQueueType, ReplicatedState, Stream, SubnetTopology,
 };
 use ic_test_utilities::{
     mock_time,
@@ -24,7 +24,8 @@ use ic_test_utilities_metrics::{
 use ic_types::{
     messages::{
         CallbackId, Payload, RejectContext, Request, RequestOrResponse, Response,
-        MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64,
+        MAX_FRAGMENTED_INTER_CANISTER_PAYLOAD_IN_BYTES, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64,
+        NO_DEADLINE,
     },
     xnet::{StreamIndex, StreamIndexedQueue},
     CanisterId, Cycles, SubnetId, Time,
@@ -122,6 +123,7 @@ fn reject_local_request() {
                     originator_reply_callback: msg.sender_reply_callback,
                     refund: msg.payment,
                     response_payload: Payload::Reject(expected_reject_context),
+                    deadline: NO_DEADLINE,
                 }
                 .into(),
                 &mut (i64::MAX / 2),
@@ -185,6 +187,7 @@ fn reject_local_request_for_subnet() {
                         RejectCode::SysFatal,
                         reject_message,
                     )),
+                    deadline: NO_DEADLINE,
                 }
                 .into(),
                 &mut (i64::MAX / 2),
@@ -552,6 +555,234 @@ fn build_streams_impl_respects_message_limit() {
     build_streams_impl_respects_limits(4, 1_000_000, 4);
 }
 
+/// Tests that once a stream is at or above half its limits, a canister that
+/// already holds more than its fair share of the stream is skipped, while
+/// other canisters' messages are still routed.
+#[test]
+fn build_streams_impl_enforces_fair_share_near_limit() {
+    with_test_replica_logger(|log| {
+        let (stream_builder, mut provided_state, metrics_registry) = new_fixture(&log);
+        provided_state.metadata.network_topology.routing_table = Arc::new(RoutingTable::try_from(
+            btreemap! {
+                CanisterIdRange{ start: CanisterId::from(0), end: CanisterId::from(0xfff) } => REMOTE_SUBNET,
+            },
+        ).unwrap());
+
+        let chatty = canister_test_id(3);
+        let quiet = canister_test_id(4);
+        let receiver = canister_test_id(700);
+        let request = |sender: CanisterId, i: u64| {
+            generate_message_for_test(
+                sender,
+                receiver,
+                CallbackId::from(i),
+                format!("req_{}", i),
+                Cycles::new(0),
+            )
+        };
+
+        // A stream to `REMOTE_SUBNET` holding 6 messages from `chatty`, i.e. above
+        // half of the 10 message limit.
+        let mut messages = StreamIndexedQueue::default();
+        for i in 0..6 {
+            messages.push(request(chatty, 100 + i).into());
+        }
+        provided_state.modify_streams(|streams| {
+            streams.insert(REMOTE_SUBNET, Stream::new(messages, Default::default()));
+        });
+
+        // 3 more messages from `chatty` and 2 from `quiet` in output queues.
+        let chatty_requests: Vec<_> = (1..=3).map(|i| request(chatty, i)).collect();
+        let quiet_requests: Vec<_> = (1..=2).map(|i| request(quiet, i)).collect();
+        provided_state.put_canister_states(canister_states_with_outputs(
+            chatty_requests
+                .iter()
+                .chain(quiet_requests.iter())
+                .cloned()
+                .collect(),
+        ));
+
+        // Act.
+        let result_state = stream_builder.build_streams_impl(provided_state, 10, usize::MAX);
+
+        // Only the messages from `quiet` were routed, `chatty` is over its fair share
+        // (10 messages split 3 ways: `chatty`, `quiet` and one reserved share).
+        let stream = result_state.get_stream(&REMOTE_SUBNET).unwrap();
+        assert_eq!(8, stream.messages().len());
+        let routed: Vec<_> = stream
+            .messages()
+            .iter()
+            .skip(6)
+            .map(|(_, msg)| msg.clone())
+            .collect();
+        let expected: Vec<RequestOrResponse> = quiet_requests.into_iter().map(Into::into).collect();
+        assert_eq!(expected, routed);
+        assert!(!result_state.canister_state(&quiet).unwrap().has_output());
+        assert_eq!(
+            3,
+            result_state
+                .canister_state(&chatty)
+                .unwrap()
+                .system_state
+                .queues()
+                .output_queues_message_count()
+        );
+
+        assert_eq!(
+            metric_vec(&[(&[(LABEL_CANISTER, &quiet.to_string())], 2)]),
+            fetch_int_gauge_vec(&metrics_registry, METRIC_NEAR_LIMIT_ROUTED_MESSAGES)
+        );
+        assert_eq!(
+            metric_vec(&[(&[(LABEL_CANISTER, &chatty.to_string())], 3)]),
+            fetch_int_gauge_vec(&metrics_registry, METRIC_BACKLOGGED_MESSAGES)
+        );
+    });
+}
+
+/// Tests that the fragments of a message count against the stream limits and
+/// the sender's fair share; and that a message is only routed once the stream
+/// can take all of its fragments.
+#[test]
+fn build_streams_impl_routes_fragments_only_if_all_fit() {
+    with_test_replica_logger(|log| {
+        let other = canister_test_id(3);
+        let sender = canister_test_id(4);
+        let receiver = canister_test_id(700);
+
+        // Request with a payload that is split into 3 fragments.
+        let request = Request {
+            sender,
+            receiver,
+            sender_reply_callback: CallbackId::from(1),
+            payment: Cycles::new(1),
+            method_name: "method".to_string(),
+            method_payload: vec![13; 2 * MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64 as usize + 1],
+            metadata: None,
+            deadline: NO_DEADLINE,
+        };
+        let fragments = RequestOrResponse::from(request.clone())
+            .into_fragments(MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64 as usize);
+        assert_eq!(3, fragments.len());
+
+        for (max_stream_messages, target_stream_size_bytes, expect_routed) in [
+            // Room for all fragments.
+            (10, usize::MAX, true),
+            // The stream reaches half its message limit before the last fragment,
+            // by which time `sender` is over its fair share (6 messages split 3 ways).
+            (6, usize::MAX, false),
+            // The stream reaches half its byte limit after the first fragment, by
+            // which time `sender` is over its fair share of bytes.
+            (
+                10,
+                2 * MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64 as usize,
+                false,
+            ),
+        ] {
+            let (stream_builder, mut provided_state, metrics_registry) = new_fixture(&log);
+            provided_state.metadata.certification_version = CertificationVersion::V17;
+            provided_state.metadata.network_topology.routing_table = Arc::new(RoutingTable::try_from(
+                btreemap! {
+                    CanisterIdRange{ start: CanisterId::from(0), end: CanisterId::from(0xfff) } => REMOTE_SUBNET,
+                },
+            ).unwrap());
+
+            // A stream to `REMOTE_SUBNET` holding one message from `other`.
+            let mut messages = StreamIndexedQueue::default();
+            messages.push(
+                generate_message_for_test(
+                    other,
+                    receiver,
+                    CallbackId::from(2),
+                    "req".to_string(),
+                    Cycles::new(0),
+                )
+                .into(),
+            );
+            provided_state.modify_streams(|streams| {
+                streams.insert(REMOTE_SUBNET, Stream::new(messages, Default::default()));
+            });
+            provided_state.put_canister_states(canister_states_with_outputs::<RequestOrResponse>(
+                vec![request.clone().into()],
+            ));
+
+            // Act.
+            let result_state = stream_builder.build_streams_impl(
+                provided_state,
+                max_stream_messages,
+                target_stream_size_bytes,
+            );
+
+            let stream = result_state.get_stream(&REMOTE_SUBNET).unwrap();
+            let output_messages = result_state
+                .canister_state(&sender)
+                .unwrap()
+                .system_state
+                .queues()
+                .output_queues_message_count();
+            if expect_routed {
+                // All fragments were routed, consecutively.
+                let routed: Vec<_> = stream
+                    .messages()
+                    .iter()
+                    .skip(1)
+                    .map(|(_, msg)| msg.clone())
+                    .collect();
+                assert_eq!(fragments, routed);
+                assert_eq!(0, output_messages);
+                assert_eq!(
+                    MetricVec::<u64>::new(),
+                    fetch_int_gauge_vec(&metrics_registry, METRIC_BACKLOGGED_MESSAGES)
+                );
+            } else {
+                // None of the fragments were routed, the request is still enqueued.
+                assert_eq!(1, stream.messages().len());
+                assert_eq!(1, output_messages);
+                assert_eq!(
+                    metric_vec(&[(&[(LABEL_CANISTER, &sender.to_string())], 1)]),
+                    fetch_int_gauge_vec(&metrics_registry, METRIC_BACKLOGGED_MESSAGES)
+                );
+            }
+        }
+    });
+}
+
+/// Tests that the per-canister metrics only export the `MAX_CANISTERS_PER_METRIC`
+/// canisters with the highest counts, and only those of the latest call.
+#[test]
+fn set_top_canister_gauges_exports_top_canisters() {
+    let metrics_registry = MetricsRegistry::new();
+    let gauge = metrics_registry.int_gauge_vec(
+        METRIC_BACKLOGGED_MESSAGES,
+        "Messages left in output queues.",
+        &[LABEL_CANISTER],
+    );
+
+    // One more canister than exported; canister 0 has the lowest count.
+    let counts: BTreeMap<CanisterId, usize> = (0..=MAX_CANISTERS_PER_METRIC as u64)
+        .map(|i| (canister_test_id(i), i as usize + 1))
+        .collect();
+    set_top_canister_gauges(&gauge, counts);
+    let expected: MetricVec<u64> = (1..=MAX_CANISTERS_PER_METRIC as u64)
+        .map(|i| {
+            (
+                btreemap! { LABEL_CANISTER.to_string() => canister_test_id(i).to_string() },
+                i + 1,
+            )
+        })
+        .collect();
+    assert_eq!(
+        expected,
+        fetch_int_gauge_vec(&metrics_registry, METRIC_BACKLOGGED_MESSAGES)
+    );
+
+    // Canisters not present in the latest call are no longer exported.
+    set_top_canister_gauges(&gauge, btreemap! { canister_test_id(0) => 7 });
+    assert_eq!(
+        metric_vec(&[(&[(LABEL_CANISTER, &canister_test_id(0).to_string())], 7)]),
+        fetch_int_gauge_vec(&metrics_registry, METRIC_BACKLOGGED_MESSAGES)
+    );
+}
+
 // Tests that messages addressed to canisters not mapped to a known subnet
 // result in reject Responses.
 #[test]
@@ -681,7 +912,8 @@ fn build_streams_with_messages_targeted_to_other_subnets() {
     });
 }
 
-// Tests that remote requests and all responses with oversized payloads are rejected.
+// Tests that remote requests and all responses with oversized payloads are rejected
+// on a subnet that does not support payloads above `MAX_INTER_CANISTER_PAYLOAD_IN_BYTES`.
 #[test]
 fn build_streams_with_oversized_payloads() {
     with_test_replica_logger(|log| {
@@ -711,6 +943,7 @@ fn build_streams_with_oversized_payloads() {
             method_name: method_name.clone(),
             method_payload: oversized_request_payload.clone(),
             metadata: None,
+            deadline: NO_DEADLINE,
         };
         assert!(local_request.payload_size_bytes() > MAX_INTER_CANISTER_PAYLOAD_IN_BYTES);
 
@@ -723,6 +956,7 @@ fn build_streams_with_oversized_payloads() {
             method_name,
             method_payload: oversized_request_payload,
             metadata: None,
+            deadline: NO_DEADLINE,
         };
         assert!(remote_request.payload_size_bytes() > MAX_INTER_CANISTER_PAYLOAD_IN_BYTES);
         let remote_request_reject = Response {
@@ -739,6 +973,7 @@ fn build_streams_with_oversized_payloads() {
                     MAX_INTER_CANISTER_PAYLOAD_IN_BYTES
                 ),
             )),
+            deadline: NO_DEADLINE,
         };
 
         // Oversized response: will be replaced with a reject response.
@@ -748,6 +983,7 @@ fn build_streams_with_oversized_payloads() {
             originator_reply_callback: CallbackId::from(3),
             refund: Cycles::new(3),
             response_payload: Payload::Data(oversized_response_payload),
+            deadline: NO_DEADLINE,
         };
         assert!(data_response.payload_size_bytes() > MAX_INTER_CANISTER_PAYLOAD_IN_BYTES);
         let data_response_reject = Response {
@@ -764,6 +1000,7 @@ fn build_streams_with_oversized_payloads() {
                     MAX_INTER_CANISTER_PAYLOAD_IN_BYTES
                 ),
             )),
+            deadline: NO_DEADLINE,
         };
 
         // Oversized reject response: will be replaced with a reject response.
@@ -777,6 +1014,7 @@ fn build_streams_with_oversized_payloads() {
                 RejectCode::SysTransient,
                 oversized_error_message,
             )),
+            deadline: NO_DEADLINE,
         };
         assert!(reject_response.payload_size_bytes() > MAX_INTER_CANISTER_PAYLOAD_IN_BYTES);
         let reject_response_reject = Response {
@@ -789,9 +1027,11 @@ fn build_streams_with_oversized_payloads() {
                 // Long enough message to be properly truncated by the constructor.
                 "x".repeat(10 * 1024),
             )),
+            deadline: NO_DEADLINE,
         };
 
-        let (stream_builder, mut provided_state, metrics_registry) = new_fixture(&log);
+        let (stream_builder, mut provided_state, metrics_registry) =
+            new_fixture_with_payload_limit(&log, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES);
 
         // Map local canister to `LOCAL_SUBNET` and remote canister to `REMOTE_SUBNET`.
         provided_state.metadata.network_topology.routing_table = Arc::new(
@@ -881,11 +1121,123 @@ fn build_streams_with_oversized_payloads() {
     });
 }
 
+// Tests that remote messages with payloads above `MAX_INTER_CANISTER_PAYLOAD_IN_BYTES`
+// are routed as fragments; or rejected if fragments are not yet supported.
+#[test]
+fn build_streams_with_fragmented_payloads() {
+    with_test_replica_logger(|log| {
+        let local_canister = canister_test_id(0);
+        let remote_canister = canister_test_id(1);
+
+        // Request with a payload that is split into 3 fragments.
+        let request = Request {
+            sender: local_canister,
+            receiver: remote_canister,
+            sender_reply_callback: CallbackId::from(1),
+            payment: Cycles::new(1),
+            method_name: "method".to_string(),
+            method_payload: vec![13; 2 * MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64 as usize + 1],
+            metadata: None,
+            deadline: NO_DEADLINE,
+        };
+        let fragments = RequestOrResponse::from(request.clone())
+            .into_fragments(MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64 as usize);
+        assert_eq!(3, fragments.len());
+
+        for certification_version in [CertificationVersion::V16, CertificationVersion::V17] {
+            let (stream_builder, mut provided_state, metrics_registry) = new_fixture(&log);
+            provided_state.metadata.certification_version = certification_version;
+
+            // Map local canister to `LOCAL_SUBNET` and remote canister to `REMOTE_SUBNET`.
+            provided_state.metadata.network_topology.routing_table = Arc::new(
+                RoutingTable::try_from(btreemap! {
+                    CanisterIdRange{ start: local_canister, end: local_canister } => LOCAL_SUBNET,
+                    CanisterIdRange{ start: remote_canister, end: remote_canister } => REMOTE_SUBNET,
+                })
+                .unwrap(),
+            );
+            provided_state.put_canister_states(canister_states_with_outputs::<RequestOrResponse>(
+                vec![request.clone().into()],
+            ));
+
+            // Expecting all canister outputs to have been consumed.
+            let mut expected_state = consume_output_queues(&provided_state);
+            let status = if certification_version >= CertificationVersion::V17 {
+                // Expecting a remote stream consisting of the request fragments.
+                let mut expected_stream_messages = StreamIndexedQueue::with_begin(0.into());
+                for fragment in fragments.iter() {
+                    expected_stream_messages.push(fragment.clone());
+                }
+                expected_state.modify_streams(|streams| {
+                    streams.insert(
+                        REMOTE_SUBNET,
+                        Stream::new(expected_stream_messages, Default::default()),
+                    );
+                });
+                LABEL_VALUE_STATUS_SUCCESS
+            } else {
+                // Expecting a reject response for the request.
+                let reject = Response {
+                    originator: local_canister,
+                    respondent: remote_canister,
+                    originator_reply_callback: CallbackId::from(1),
+                    refund: Cycles::new(1),
+                    response_payload: Payload::Reject(RejectContext::new(
+                        RejectCode::CanisterError,
+                        format!(
+                            "Canister {} violated contract: attempted to send a message of size {} exceeding the limit {}",
+                            local_canister,
+                            request.payload_size_bytes(),
+                            MAX_INTER_CANISTER_PAYLOAD_IN_BYTES
+                        ),
+                    )),
+                    deadline: NO_DEADLINE,
+                };
+                let local_canister = expected_state.canister_state_mut(&local_canister).unwrap();
+                push_input(local_canister, reject.into());
+                LABEL_VALUE_STATUS_PAYLOAD_TOO_LARGE
+            };
+
+            // Act
+            let result_state = stream_builder.build_streams(provided_state);
+
+            assert_eq!(expected_state, result_state);
+            assert_routed_messages_eq(
+                metric_vec(&[(
+                    &[
+                        (LABEL_TYPE, LABEL_VALUE_TYPE_REQUEST),
+                        (LABEL_STATUS, status),
+                    ],
+                    1,
+                )]),
+                &metrics_registry,
+            );
+            assert_eq_critical_errors(0, 0, &metrics_registry);
+        }
+    });
+}
+
 /// Sets up the `StreamHandlerImpl`, `ReplicatedState` and `MetricsRegistry` to
 /// be used by a test.
 fn new_fixture(log: &ReplicaLogger) -> (StreamBuilderImpl, ReplicatedState, MetricsRegistry) {
+    new_fixture_with_payload_limit(log, MAX_FRAGMENTED_INTER_CANISTER_PAYLOAD_IN_BYTES)
+}
+
+/// Sets up the `StreamHandlerImpl`, `ReplicatedState` and `MetricsRegistry` to
+/// be used by a test, with the given inter-canister payload size limit.
+fn new_fixture_with_payload_limit(
+    log: &ReplicaLogger,
+    max_inter_canister_payload_in_bytes: NumBytes,
+) -> (StreamBuilderImpl, ReplicatedState, MetricsRegistry) {
     let mut state = ReplicatedState::new(LOCAL_SUBNET, SubnetType::Application);
     state.metadata.batch_time = Time::from_nanos_since_unix_epoch(5);
+    state.metadata.network_topology.subnets.insert(
+        LOCAL_SUBNET,
+        SubnetTopology {
+            max_inter_canister_payload_in_bytes: Some(max_inter_canister_payload_in_bytes),
+            ..SubnetTopology::default()
+        },
+    );
     let metrics_registry = MetricsRegistry::new();
     let stream_handler = StreamBuilderImpl::new(
         LOCAL_SUBNET,
@@ -1046,6 +1398,10 @@ fn canister_states_with_outputs<M: Into<RequestOrResponse>>(
 
                 canister_state.push_output_response(rep);
             }
+
+            RequestOrResponse::Fragment(_) => {
+                unreachable!("Message fragments cannot be enqueued: {:?}", msg)
+            }
         }
     }
 
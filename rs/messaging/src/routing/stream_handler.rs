@@ -22,10 +22,10 @@ use ic_replicated_state::{
 use ic_types::{
     messages::{
         Payload, RejectContext, RequestOrResponse, Response,
-        MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64,
+        MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64, NO_DEADLINE,
     },
     xnet::{StreamIndex, StreamIndexedQueue, StreamSlice},
-    SubnetId,
+    SubnetId, Time,
 };
 use prometheus::{Histogram, IntCounter, IntCounterVec, IntGaugeVec};
 use std::{
@@ -77,6 +77,7 @@ const LABEL_VALUE_SUCCESS: &str = "success";
 const LABEL_VALUE_SENDER_SUBNET_MISMATCH: &str = "SenderSubnetMismatch";
 const LABEL_VALUE_RECEIVER_SUBNET_MISMATCH: &str = "ReceiverSubnetMismatch";
 const LABEL_VALUE_CANISTER_MIGRATED: &str = "CanisterMigrated";
+const LABEL_VALUE_DEADLINE_EXPIRED: &str = "DeadlineExpired";
 const LABEL_TYPE: &str = "type";
 const LABEL_VALUE_TYPE_REQUEST: &str = "request";
 const LABEL_VALUE_TYPE_RESPONSE: &str = "response";
@@ -340,7 +341,7 @@ impl StreamHandlerImpl {
             debug_assert!(loopback_stream
                 .messages()
                 .iter()
-                .all(|(_, msg)| matches!(msg, RequestOrResponse::Response(_))));
+                .all(|(_, msg)| !msg.is_request()));
         }
 
         state
@@ -485,9 +486,10 @@ impl StreamHandlerImpl {
         self.observe_gced_reject_signals(signal_count_before - stream.reject_signals().len());
     }
 
-    /// Reroutes all `Responses` rejected by `remote_subnet` into `streams`,
-    /// based on the provided routing table; drops all `Requests`, incrementing
-    /// a critical error counter.
+    /// Reroutes all `Responses` (and response fragments) rejected by
+    /// `remote_subnet` into `streams`, based on the provided routing table;
+    /// drops all `Requests` (and request fragments), incrementing a critical
+    /// error counter.
     fn reroute_rejected_messages(
         &self,
         rejected_messages: Vec<RequestOrResponse>,
@@ -497,6 +499,27 @@ impl StreamHandlerImpl {
     ) {
         for msg in rejected_messages {
             match msg {
+                RequestOrResponse::Fragment(_) if msg.is_request() => {
+                    error!(
+                        self.log,
+                        "{}: Received unsupported reject signal from {} for request fragment: {:?}",
+                        CRITICAL_ERROR_REJECT_SIGNALS_FOR_REQUEST,
+                        remote_subnet,
+                        msg
+                    );
+                    self.metrics.critical_error_reject_signals_for_request.inc();
+                }
+
+                RequestOrResponse::Fragment(_) => {
+                    // Same as for responses below: reroute the response fragment to the
+                    // subnet now hosting the originator. Fragments are rejected and
+                    // rerouted in order, so they remain consecutive.
+                    let new_destination = routing_table.route(msg.receiver().get()).expect(
+                        "Canister disappeared from registry. Registry in an inconsistent state.",
+                    );
+                    streams.get_mut_or_insert(new_destination).push(msg);
+                }
+
                 RequestOrResponse::Request(request) => {
                     // Critical error, honest subnets do not produce reject signals for requests.
                     // We do not want to re-route requests because this can break the message
@@ -578,6 +601,14 @@ impl StreamHandlerImpl {
     ///    to the reverse stream;
     ///  * `Response` not inducted (canister migrated): reject signal appended
     ///    to loopback stream (canonical versions 9+ only).
+    ///  * `MessageFragment` buffered until the last fragment of its message is
+    ///    inducted, at which point the reassembled message is inducted as
+    ///    above. A request fragment that is not inducted is dropped, unless it
+    ///    is the last one, in which case a reject response is generated for
+    ///    the whole request. Response fragments are handled like responses.
+    ///    Either way (or if the fragment is dropped because its deadline has
+    ///    expired), any previously buffered fragments of the same message are
+    ///    discarded.
     ///  * `Request` or `Response` silently dropped and accept signal appended
     ///    to loopback stream iff:
     ///     * the sender and source subnet do not match (according to the
@@ -597,9 +628,10 @@ impl StreamHandlerImpl {
         stream: &mut StreamHandle,
         subnet_available_memory: &mut i64,
     ) {
-        let msg_type = match msg {
-            RequestOrResponse::Request(_) => LABEL_VALUE_TYPE_REQUEST,
-            RequestOrResponse::Response(_) => LABEL_VALUE_TYPE_RESPONSE,
+        let msg_type = if msg.is_request() {
+            LABEL_VALUE_TYPE_REQUEST
+        } else {
+            LABEL_VALUE_TYPE_RESPONSE
         };
 
         if self.should_accept_message_from(&msg, remote_subnet_id, state) {
@@ -614,6 +646,27 @@ impl StreamHandlerImpl {
 
             let payload_size = msg.payload_size_bytes().get();
             match receiver_host_subnet {
+                // Expired best-effort request, drop it. The caller will eventually time
+                // out the callback and get a `SYS_UNKNOWN` reject response.
+                Some(host_subnet)
+                    if host_subnet == self.subnet_id
+                        && msg.is_request()
+                        && msg.deadline() != NO_DEADLINE
+                        && Time::from(msg.deadline()) <= state.time() =>
+                {
+                    debug!(self.log, "Dropping expired best-effort request {:?}", msg);
+                    self.observe_inducted_message_status(msg_type, LABEL_VALUE_DEADLINE_EXPIRED);
+                    // Any previously inducted fragments of the request can no longer be
+                    // reassembled.
+                    if let RequestOrResponse::Fragment(_) = &msg {
+                        state.discard_input_fragments(
+                            &msg.receiver(),
+                            &msg.sender(),
+                            subnet_available_memory,
+                        );
+                    }
+                }
+
                 // Matching receiver subnet, try inducting message.
                 Some(host_subnet) if host_subnet == self.subnet_id => {
                     match state.push_input(msg, subnet_available_memory) {
@@ -627,30 +680,41 @@ impl StreamHandlerImpl {
                         Err((err, msg)) => {
                             self.observe_inducted_message_status(msg_type, err.to_label_value());
 
+                            // Discard any previously inducted fragments of the message, as
+                            // it can no longer be reassembled.
+                            if let RequestOrResponse::Fragment(_) = &msg {
+                                state.discard_input_fragments(
+                                    &msg.receiver(),
+                                    &msg.sender(),
+                                    subnet_available_memory,
+                                );
+                            }
+
                             match msg {
-                                RequestOrResponse::Request(_) => {
+                                // Not the last fragment, drop it. Its message will be rejected
+                                // or dropped when its last fragment fails induction.
+                                RequestOrResponse::Fragment(fragment) if !fragment.is_last() => {
                                     debug!(
-                                    self.log,
-                                    "Induction failed with error '{}', generating reject Response for {:?}",
-                                    &err,
-                                    &msg
-                                );
-                                    let code = reject_code_for_state_error(&err);
-                                    *subnet_available_memory -= stream
-                                        .push(generate_reject_response(msg, code, err.to_string()))
-                                        as i64;
-                                }
-                                RequestOrResponse::Response(response) => {
-                                    // Critical error, responses should always be inducted successfully.
-                                    error!(
                                         self.log,
-                                        "{}: Inducting response failed: {} {:?}",
-                                        CRITICAL_ERROR_INDUCT_RESPONSE_FAILED,
-                                        err,
-                                        response
+                                        "Induction failed with error '{}', dropping message fragment {:?}",
+                                        &err,
+                                        fragment
                                     );
-                                    self.metrics.critical_error_induct_response_failed.inc()
                                 }
+                                // The last fragment stands in for the whole message.
+                                RequestOrResponse::Fragment(fragment) => self
+                                    .handle_induction_failure(
+                                        err,
+                                        fragment.header.clone(),
+                                        stream,
+                                        subnet_available_memory,
+                                    ),
+                                msg => self.handle_induction_failure(
+                                    err,
+                                    msg,
+                                    stream,
+                                    subnet_available_memory,
+                                ),
                             }
                         }
                     }
@@ -661,6 +725,31 @@ impl StreamHandlerImpl {
                     self.observe_inducted_message_status(msg_type, LABEL_VALUE_CANISTER_MIGRATED);
 
                     match &msg {
+                        RequestOrResponse::Fragment(fragment)
+                            if msg.is_request() && !fragment.is_last() =>
+                        {
+                            debug!(
+                                self.log,
+                                "Canister {} is being migrated, dropping request fragment {:?}",
+                                msg.receiver(),
+                                msg
+                            );
+                        }
+
+                        RequestOrResponse::Fragment(fragment) if msg.is_request() => {
+                            let reject_message = format!(
+                                "Canister {} is being migrated to/from {}",
+                                msg.receiver(),
+                                host_subnet
+                            );
+                            debug!(self.log, "Canister {} is being migrated, generating reject response for {:?}", msg.receiver(), msg);
+                            *subnet_available_memory -= stream.push(generate_reject_response(
+                                fragment.header.clone(),
+                                RejectCode::SysTransient,
+                                reject_message,
+                            )) as i64;
+                        }
+
                         RequestOrResponse::Request(_) => {
                             let reject_message = format!(
                                 "Canister {} is being migrated to/from {}",
@@ -675,7 +764,7 @@ impl StreamHandlerImpl {
                             )) as i64;
                         }
 
-                        RequestOrResponse::Response(_) => {
+                        RequestOrResponse::Response(_) | RequestOrResponse::Fragment(_) => {
                             if state.metadata.certification_version >= CertificationVersion::V9
                                 || self.testing_flag_generate_reject_signals
                             {
@@ -735,6 +824,54 @@ impl StreamHandlerImpl {
         stream.increment_signals_end();
     }
 
+    /// Handles a `Request` or `Response` that failed induction with `err`:
+    /// generates a reject response for a `Request`; drops a best-effort
+    /// `Response`; and raises a critical error for any other `Response`.
+    fn handle_induction_failure(
+        &self,
+        err: StateError,
+        msg: RequestOrResponse,
+        stream: &mut StreamHandle,
+        subnet_available_memory: &mut i64,
+    ) {
+        match msg {
+            RequestOrResponse::Request(_) => {
+                debug!(
+                    self.log,
+                    "Induction failed with error '{}', generating reject Response for {:?}",
+                    &err,
+                    &msg
+                );
+                let code = reject_code_for_state_error(&err);
+                *subnet_available_memory -=
+                    stream.push(generate_reject_response(msg, code, err.to_string())) as i64;
+            }
+            RequestOrResponse::Response(response) if response.is_best_effort() => {
+                // The callback may have already expired, drop the response.
+                debug!(
+                    self.log,
+                    "Induction failed with error '{}', dropping best-effort response {:?}",
+                    &err,
+                    response
+                );
+            }
+            RequestOrResponse::Response(response) => {
+                // Critical error, responses should always be inducted successfully.
+                error!(
+                    self.log,
+                    "{}: Inducting response failed: {} {:?}",
+                    CRITICAL_ERROR_INDUCT_RESPONSE_FAILED,
+                    err,
+                    response
+                );
+                self.metrics.critical_error_induct_response_failed.inc()
+            }
+            RequestOrResponse::Fragment(_) => {
+                unreachable!("Expecting a Request or Response, got {:?}", msg)
+            }
+        }
+    }
+
     /// Checks whether `actual_subnet_id` is a valid host subnet for `msg.sender()`
     /// (i.e. whether it is its current host according to the routing table; or it
     /// and the known host subnet are both on the path of a canister migration
@@ -877,6 +1014,7 @@ fn generate_reject_response(
                 message,
                 MR_SYNTHETIC_REJECT_MESSAGE_MAX_LEN,
             )),
+            deadline: msg.deadline,
         }
         .into()
     } else {
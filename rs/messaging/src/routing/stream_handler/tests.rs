@@ -13,9 +13,11 @@ use ic_registry_routing_table::{
 };
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::{
-    replicated_state::{LABEL_VALUE_CANISTER_NOT_FOUND, LABEL_VALUE_OUT_OF_MEMORY},
+    replicated_state::{
+        LABEL_VALUE_CANISTER_NOT_FOUND, LABEL_VALUE_CANISTER_STOPPED, LABEL_VALUE_OUT_OF_MEMORY,
+    },
     testing::ReplicatedStateTesting,
-    CanisterState, ReplicatedState, Stream,
+    CanisterState, CanisterStatus, ReplicatedState, Stream,
 };
 use ic_test_utilities::{
     mock_time,
@@ -30,7 +32,8 @@ use ic_test_utilities_metrics::{
     fetch_int_gauge_vec, metric_vec, nonzero_values, HistogramStats, MetricVec,
 };
 use ic_types::{
-    messages::{CallbackId, Payload, Request, MAX_RESPONSE_COUNT_BYTES},
+    messages::{CallbackId, Payload, Request, MAX_RESPONSE_COUNT_BYTES, NO_DEADLINE},
+    time::CoarseTime,
     xnet::{testing::StreamSliceTesting, StreamIndex, StreamIndexedQueue},
     CanisterId, CountBytes, Cycles,
 };
@@ -1373,6 +1376,7 @@ fn generate_reject_response_queue_full() {
             originator_reply_callback: msg.sender_reply_callback,
             refund: msg.payment,
             response_payload: Payload::Reject(RejectContext::new(RejectCode::SysTransient, &err)),
+            deadline: NO_DEADLINE,
         }
         .into(),
     );
@@ -1413,6 +1417,7 @@ fn generate_reject_response_canister_not_found() {
                 RejectCode::DestinationInvalid,
                 &err,
             )),
+            deadline: NO_DEADLINE,
         }
         .into(),
     );
@@ -1675,6 +1680,335 @@ fn induct_stream_slices_response_to_missing_canister() {
     });
 }
 
+/// Tests that the fragments of a request are buffered and the reassembled
+/// request is inducted upon receiving the last fragment; and that a single
+/// reject response is generated for a fragmented request addressed to a
+/// missing canister, upon receiving its last fragment.
+#[test]
+fn induct_stream_slices_request_fragments() {
+    with_test_replica_logger(|log| {
+        let (stream_handler, mut initial_state, metrics_registry) = new_fixture(&log);
+
+        // Initial state with one canister and one stream.
+        let initial_canister_state = new_canister_state(
+            *LOCAL_CANISTER,
+            user_test_id(24).get(),
+            *INITIAL_CYCLES,
+            NumSeconds::from(100_000),
+        );
+        initial_state.put_canister_state(initial_canister_state);
+        let initial_stream = generate_outgoing_stream(StreamConfig {
+            messages_begin: 31,
+            message_count: 0,
+            signals_end: 43,
+            reject_signals: None,
+        });
+        initial_state.with_streams(btreemap![REMOTE_SUBNET => initial_stream]);
+
+        let mut expected_state = initial_state.clone();
+
+        // Incoming slice with 3 fragments of a request to `LOCAL_CANISTER`...
+        let mut stream_slice = generate_stream_slice(StreamSliceConfig {
+            header_begin: 43,
+            header_end: None,
+            messages_begin: 43,
+            message_count: 0,
+            signals_end: 31,
+            reject_signals: None,
+        });
+        let mut request = test_request(*REMOTE_CANISTER, *LOCAL_CANISTER);
+        request.method_payload = vec![13; 30];
+        let request: RequestOrResponse = request.into();
+        for fragment in request.clone().into_fragments(10) {
+            stream_slice.push_message(fragment);
+        }
+
+        // ...and 2 fragments of a request to a missing canister.
+        let mut request_to_missing_canister = test_request(*REMOTE_CANISTER, *OTHER_LOCAL_CANISTER);
+        request_to_missing_canister.method_payload = vec![17; 20];
+        let request_to_missing_canister: RequestOrResponse = request_to_missing_canister.into();
+        for fragment in request_to_missing_canister.clone().into_fragments(10) {
+            stream_slice.push_message(fragment);
+        }
+
+        // The expected canister state contains the reassembled request...
+        push_inputs(
+            &mut expected_state,
+            std::iter::once((StreamIndex::from(43), &request)),
+        );
+        // ...and the expected stream 5 signals and a single reject response.
+        let mut expected_stream = generate_outgoing_stream(StreamConfig {
+            messages_begin: 31,
+            message_count: 0,
+            signals_end: 48,
+            reject_signals: None,
+        });
+        expected_stream.push(generate_reject_response(
+            request_to_missing_canister,
+            RejectCode::DestinationInvalid,
+            StateError::CanisterNotFound(*OTHER_LOCAL_CANISTER).to_string(),
+        ));
+        expected_state.with_streams(btreemap![REMOTE_SUBNET => expected_stream]);
+
+        // Act
+        let mut subnet_available_memory = stream_handler.subnet_available_memory(&initial_state);
+        let inducted_state = stream_handler.induct_stream_slices(
+            initial_state,
+            btreemap![REMOTE_SUBNET => stream_slice],
+            &mut subnet_available_memory,
+        );
+
+        // Assert
+        assert_eq!(
+            expected_state.canister_state(&LOCAL_CANISTER),
+            inducted_state.canister_state(&LOCAL_CANISTER),
+        );
+        assert_eq!(expected_state, inducted_state);
+
+        assert_inducted_xnet_messages_eq(
+            metric_vec(&[
+                (
+                    &[
+                        (LABEL_TYPE, LABEL_VALUE_TYPE_REQUEST),
+                        (LABEL_STATUS, LABEL_VALUE_SUCCESS),
+                    ],
+                    3,
+                ),
+                (
+                    &[
+                        (LABEL_TYPE, LABEL_VALUE_TYPE_REQUEST),
+                        (LABEL_STATUS, LABEL_VALUE_CANISTER_NOT_FOUND),
+                    ],
+                    2,
+                ),
+            ]),
+            &metrics_registry,
+        );
+    });
+}
+
+/// Tests that when a request fragment fails induction, the previously buffered
+/// fragments of the same request are discarded and their memory released.
+#[test]
+fn induct_stream_slices_rejected_request_fragment_discards_buffered_fragments() {
+    with_test_replica_logger(|log| {
+        let (stream_handler, mut initial_state, metrics_registry) = new_fixture(&log);
+
+        // Initial state with one canister and one stream.
+        let initial_canister_state = new_canister_state(
+            *LOCAL_CANISTER,
+            user_test_id(24).get(),
+            *INITIAL_CYCLES,
+            NumSeconds::from(100_000),
+        );
+        initial_state.put_canister_state(initial_canister_state);
+        let initial_stream = generate_outgoing_stream(StreamConfig {
+            messages_begin: 31,
+            message_count: 0,
+            signals_end: 43,
+            reject_signals: None,
+        });
+        initial_state.with_streams(btreemap![REMOTE_SUBNET => initial_stream]);
+
+        let mut request = test_request(*REMOTE_CANISTER, *LOCAL_CANISTER);
+        request.method_payload = vec![13; 30];
+        let fragments = RequestOrResponse::from(request).into_fragments(10);
+        assert_eq!(3, fragments.len());
+
+        // Induct the first fragment of the request.
+        let mut stream_slice = generate_stream_slice(StreamSliceConfig {
+            header_begin: 43,
+            header_end: None,
+            messages_begin: 43,
+            message_count: 0,
+            signals_end: 31,
+            reject_signals: None,
+        });
+        stream_slice.push_message(fragments[0].clone());
+        let mut subnet_available_memory = stream_handler.subnet_available_memory(&initial_state);
+        let mut state = stream_handler.induct_stream_slices(
+            initial_state,
+            btreemap![REMOTE_SUBNET => stream_slice],
+            &mut subnet_available_memory,
+        );
+        let queues = state
+            .canister_state(&LOCAL_CANISTER)
+            .unwrap()
+            .system_state
+            .queues();
+        assert!(queues.memory_usage() > 0);
+
+        // Stop the canister, so the second fragment fails induction.
+        state
+            .canister_state_mut(&LOCAL_CANISTER)
+            .unwrap()
+            .system_state
+            .status = CanisterStatus::Stopped;
+        let mut stream_slice = generate_stream_slice(StreamSliceConfig {
+            header_begin: 44,
+            header_end: None,
+            messages_begin: 44,
+            message_count: 0,
+            signals_end: 31,
+            reject_signals: None,
+        });
+        stream_slice.push_message(fragments[1].clone());
+        let inducted_state = stream_handler.induct_stream_slices(
+            state,
+            btreemap![REMOTE_SUBNET => stream_slice],
+            &mut subnet_available_memory,
+        );
+
+        // The buffered first fragment was discarded and its memory released.
+        let queues = inducted_state
+            .canister_state(&LOCAL_CANISTER)
+            .unwrap()
+            .system_state
+            .queues();
+        assert_eq!(0, queues.memory_usage());
+        assert_eq!(
+            stream_handler.subnet_available_memory(&inducted_state),
+            subnet_available_memory
+        );
+
+        assert_inducted_xnet_messages_eq(
+            metric_vec(&[
+                (
+                    &[
+                        (LABEL_TYPE, LABEL_VALUE_TYPE_REQUEST),
+                        (LABEL_STATUS, LABEL_VALUE_SUCCESS),
+                    ],
+                    1,
+                ),
+                (
+                    &[
+                        (LABEL_TYPE, LABEL_VALUE_TYPE_REQUEST),
+                        (LABEL_STATUS, LABEL_VALUE_CANISTER_STOPPED),
+                    ],
+                    1,
+                ),
+            ]),
+            &metrics_registry,
+        );
+    });
+}
+
+/// Tests that when the fragments of a best-effort request are dropped because
+/// its deadline expired, the previously buffered fragments of the request are
+/// discarded and their memory released.
+#[test]
+fn induct_stream_slices_expired_request_fragment_discards_buffered_fragments() {
+    with_test_replica_logger(|log| {
+        let (stream_handler, mut initial_state, metrics_registry) = new_fixture(&log);
+
+        // Initial state with one canister and one stream.
+        let initial_canister_state = new_canister_state(
+            *LOCAL_CANISTER,
+            user_test_id(24).get(),
+            *INITIAL_CYCLES,
+            NumSeconds::from(100_000),
+        );
+        initial_state.put_canister_state(initial_canister_state);
+        let initial_stream = generate_outgoing_stream(StreamConfig {
+            messages_begin: 31,
+            message_count: 0,
+            signals_end: 43,
+            reject_signals: None,
+        });
+        initial_state.with_streams(btreemap![REMOTE_SUBNET => initial_stream]);
+
+        // A best-effort request with a deadline 10 seconds after the initial time.
+        let mut request = test_request(*REMOTE_CANISTER, *LOCAL_CANISTER);
+        request.method_payload = vec![13; 30];
+        request.deadline = CoarseTime::from_secs_since_unix_epoch(10);
+        let fragments = RequestOrResponse::from(request).into_fragments(10);
+        assert_eq!(3, fragments.len());
+
+        // Induct the first fragment of the request.
+        let mut stream_slice = generate_stream_slice(StreamSliceConfig {
+            header_begin: 43,
+            header_end: None,
+            messages_begin: 43,
+            message_count: 0,
+            signals_end: 31,
+            reject_signals: None,
+        });
+        stream_slice.push_message(fragments[0].clone());
+        let mut subnet_available_memory = stream_handler.subnet_available_memory(&initial_state);
+        let mut state = stream_handler.induct_stream_slices(
+            initial_state,
+            btreemap![REMOTE_SUBNET => stream_slice],
+            &mut subnet_available_memory,
+        );
+        let queues = state
+            .canister_state(&LOCAL_CANISTER)
+            .unwrap()
+            .system_state
+            .queues();
+        assert!(queues.memory_usage() > 0);
+
+        // The deadline expires before the remaining fragments are inducted.
+        state.metadata.batch_time = Time::from_secs_since_unix_epoch(20).unwrap();
+        let mut stream_slice = generate_stream_slice(StreamSliceConfig {
+            header_begin: 44,
+            header_end: None,
+            messages_begin: 44,
+            message_count: 0,
+            signals_end: 31,
+            reject_signals: None,
+        });
+        stream_slice.push_message(fragments[1].clone());
+        stream_slice.push_message(fragments[2].clone());
+        let inducted_state = stream_handler.induct_stream_slices(
+            state,
+            btreemap![REMOTE_SUBNET => stream_slice],
+            &mut subnet_available_memory,
+        );
+
+        // The buffered first fragment was discarded and its memory released; and
+        // no reject response was generated.
+        let queues = inducted_state
+            .canister_state(&LOCAL_CANISTER)
+            .unwrap()
+            .system_state
+            .queues();
+        assert_eq!(0, queues.memory_usage());
+        assert!(!queues.has_input());
+        assert_eq!(
+            stream_handler.subnet_available_memory(&inducted_state),
+            subnet_available_memory
+        );
+        assert_eq!(
+            0,
+            inducted_state
+                .get_stream(&REMOTE_SUBNET)
+                .unwrap()
+                .messages()
+                .len()
+        );
+
+        assert_inducted_xnet_messages_eq(
+            metric_vec(&[
+                (
+                    &[
+                        (LABEL_TYPE, LABEL_VALUE_TYPE_REQUEST),
+                        (LABEL_STATUS, LABEL_VALUE_SUCCESS),
+                    ],
+                    1,
+                ),
+                (
+                    &[
+                        (LABEL_TYPE, LABEL_VALUE_TYPE_REQUEST),
+                        (LABEL_STATUS, LABEL_VALUE_DEADLINE_EXPIRED),
+                    ],
+                    2,
+                ),
+            ]),
+            &metrics_registry,
+        );
+    });
+}
+
 /// Tests that a message from a sender that is not currently and has not
 /// recently (according to `canister_migrations`) been hosted by the remote
 /// subnet is dropped, incrementing the respective critical error count.
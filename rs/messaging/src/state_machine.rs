@@ -111,11 +111,15 @@ impl StateMachine for StateMachineImpl {
             )
         }
 
-        // Time out requests.
+        // Time out requests and expired best-effort callbacks.
         let timed_out_requests = state.time_out_requests();
         self.metrics
             .timed_out_requests_total
             .inc_by(timed_out_requests);
+        let timed_out_callbacks = state.time_out_callbacks();
+        self.metrics
+            .timed_out_callbacks_total
+            .inc_by(timed_out_callbacks);
         self.observe_phase_duration(PHASE_TIME_OUT_REQUESTS, &phase_timer);
 
         // Preprocess messages and add messages to the induction pool through the Demux.
@@ -1,10 +1,10 @@
 use ic_base_types::CanisterId;
 use ic_logger::{error, info, ReplicaLogger};
 use ic_replicated_state::ReplicatedState;
-use ic_types::batch::{QueryStats, QueryStatsPayload, RawQueryStats};
+use ic_types::batch::{MethodStats, QueryStats, QueryStatsPayload, RawQueryStats};
 use ic_types::consensus::get_faults_tolerated;
 use ic_types::{epoch_from_height, Height};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 /// Aggregate given query stats
 ///
@@ -16,22 +16,62 @@ use std::collections::BTreeMap;
 ///   the value is close to those of honest nodes.
 ///
 /// This function does not check the first property. This has to be done by the caller.
+///
+/// Per-method stats are aggregated the same way, where a node that did not report a method
+/// is considered to have seen no calls to it.
 fn aggregate_query_stats(stats: Vec<&QueryStats>) -> QueryStats {
-    fn get_median<T: Default + Ord + Copy, F>(stats: &Vec<&QueryStats>, f: F) -> T
+    fn get_median<S, T: Default + Ord + Copy, F>(stats: &Vec<S>, f: F) -> T
     where
-        F: FnMut(&&QueryStats) -> T,
+        F: FnMut(&S) -> T,
     {
         let mut values: Vec<T> = stats.iter().map(f).collect();
         values.sort();
         values.get(stats.len() / 2).cloned().unwrap_or(T::default())
     }
 
+    let method_names: BTreeSet<&String> = stats
+        .iter()
+        .flat_map(|stats| stats.method_stats.keys())
+        .collect();
+    let default_method_stats = MethodStats::default();
+    let method_stats = method_names
+        .into_iter()
+        .map(|method_name| {
+            let individual_stats: Vec<&MethodStats> = stats
+                .iter()
+                .map(|stats| {
+                    stats
+                        .method_stats
+                        .get(method_name)
+                        .unwrap_or(&default_method_stats)
+                })
+                .collect();
+            (
+                method_name.clone(),
+                MethodStats {
+                    num_calls: get_median(&individual_stats, |stats| stats.num_calls),
+                    num_errors: get_median(&individual_stats, |stats| stats.num_errors),
+                    num_instructions: get_median(&individual_stats, |stats| stats.num_instructions),
+                    ingress_payload_size: get_median(&individual_stats, |stats| {
+                        stats.ingress_payload_size
+                    }),
+                    egress_payload_size: get_median(&individual_stats, |stats| {
+                        stats.egress_payload_size
+                    }),
+                },
+            )
+        })
+        // Drop methods that did not see any calls on most nodes.
+        .filter(|(_, stats)| stats.num_calls > 0)
+        .collect();
+
     // Take the median for each of the values in stats
     QueryStats {
         num_calls: get_median(&stats, |stats| stats.num_calls),
         num_instructions: get_median(&stats, |stats| stats.num_instructions),
         ingress_payload_size: get_median(&stats, |stats| stats.ingress_payload_size),
         egress_payload_size: get_median(&stats, |stats| stats.egress_payload_size),
+        method_stats,
     }
 }
 
@@ -55,6 +95,20 @@ fn apply_query_stats_to_canister(
             aggregated_stats.ingress_payload_size as u128 * num_nodes_in_subnet;
         canister_query_stats.egress_payload_size +=
             aggregated_stats.egress_payload_size as u128 * num_nodes_in_subnet;
+        for (method_name, method_stats) in &aggregated_stats.method_stats {
+            let total_method_stats = canister_query_stats
+                .method_stats
+                .entry(method_name.clone())
+                .or_default();
+            total_method_stats.num_calls += method_stats.num_calls as u128 * num_nodes_in_subnet;
+            total_method_stats.num_errors += method_stats.num_errors as u128 * num_nodes_in_subnet;
+            total_method_stats.num_instructions +=
+                method_stats.num_instructions as u128 * num_nodes_in_subnet;
+            total_method_stats.ingress_payload_size +=
+                method_stats.ingress_payload_size as u128 * num_nodes_in_subnet;
+            total_method_stats.egress_payload_size +=
+                method_stats.egress_payload_size as u128 * num_nodes_in_subnet;
+        }
     } else {
         info!(
             logger,
@@ -132,6 +186,7 @@ pub fn deliver_query_stats(
                     num_instructions: message.stats.num_instructions,
                     ingress_payload_size: message.stats.ingress_payload_size,
                     egress_payload_size: message.stats.egress_payload_size,
+                    method_stats: message.stats.method_stats.clone(),
                 },
             );
         if previous_value.is_some() {
@@ -143,3 +198,67 @@ pub fn deliver_query_stats(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::btreemap;
+
+    fn method_stats(num_calls: u32, num_errors: u32) -> MethodStats {
+        MethodStats {
+            num_calls,
+            num_errors,
+            num_instructions: 10 * num_calls as u64,
+            ingress_payload_size: 2 * num_calls as u64,
+            egress_payload_size: 3 * num_calls as u64,
+        }
+    }
+
+    fn query_stats(method_stats: BTreeMap<String, MethodStats>) -> QueryStats {
+        QueryStats {
+            num_calls: method_stats.values().map(|stats| stats.num_calls).sum(),
+            num_instructions: method_stats.values().map(|s| s.num_instructions).sum(),
+            ingress_payload_size: method_stats.values().map(|s| s.ingress_payload_size).sum(),
+            egress_payload_size: method_stats.values().map(|s| s.egress_payload_size).sum(),
+            method_stats,
+        }
+    }
+
+    #[test]
+    fn aggregate_query_stats_takes_median_per_method() {
+        let stats = [
+            query_stats(btreemap! {
+                "a".to_string() => method_stats(4, 1),
+                "b".to_string() => method_stats(1, 0),
+            }),
+            query_stats(btreemap! {
+                "a".to_string() => method_stats(5, 0),
+            }),
+            query_stats(btreemap! {
+                "a".to_string() => method_stats(100, 100),
+                "c".to_string() => method_stats(7, 7),
+            }),
+            query_stats(btreemap! {
+                "a".to_string() => method_stats(6, 2),
+                "c".to_string() => method_stats(8, 0),
+            }),
+        ];
+
+        let aggregated = aggregate_query_stats(stats.iter().collect());
+
+        // Methods that were not called on most nodes are dropped.
+        assert_eq!(
+            aggregated.method_stats,
+            btreemap! {
+                "a".to_string() => method_stats(6, 2),
+                "c".to_string() => MethodStats {
+                    num_calls: 7,
+                    num_errors: 0,
+                    num_instructions: 70,
+                    ingress_payload_size: 14,
+                    egress_payload_size: 21,
+                },
+            }
+        );
+    }
+}
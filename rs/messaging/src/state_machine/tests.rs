@@ -20,7 +20,7 @@ use ic_test_utilities::{
 use ic_test_utilities_execution_environment::test_registry_settings;
 use ic_test_utilities_logger::with_test_replica_logger;
 use ic_test_utilities_metrics::fetch_int_counter_vec;
-use ic_types::batch::{CanisterQueryStats, QueryStats, QueryStatsPayload};
+use ic_types::batch::{CanisterQueryStats, MethodStats, QueryStats, QueryStatsPayload};
 use ic_types::messages::SignedIngress;
 use ic_types::{batch::BatchMessages, crypto::canister_threshold_sig::MasterEcdsaPublicKey};
 use ic_types::{Height, PrincipalId, QueryStatsEpoch, SubnetId, Time};
@@ -114,6 +114,7 @@ fn test_fixture(provided_batch: &Batch) -> StateMachineTestFixture {
             subnet_type: SubnetType::Application,
             subnet_features: SubnetFeatures::default(),
             ecdsa_keys_held: BTreeSet::new(),
+            max_inter_canister_payload_in_bytes: None,
         },
     );
 
@@ -297,6 +298,15 @@ pub fn test_query_stats() {
         num_instructions: 2,
         ingress_payload_size: 3,
         egress_payload_size: 4,
+        method_stats: btreemap! {
+            "query".to_string() => MethodStats {
+                num_calls: 1,
+                num_errors: 1,
+                num_instructions: 2,
+                ingress_payload_size: 3,
+                egress_payload_size: 0,
+            },
+        },
     };
     let uninstalled_canister = canister_test_id(1);
     let proposer = NodeId::from(PrincipalId::new_node_test_id(1));
@@ -8,7 +8,7 @@ use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::ReplicatedState;
 use ic_state_machine_tests::{StateMachine, StateMachineBuilder, UserError, WasmResult};
 use ic_test_utilities::types::ids::subnet_test_id;
-use ic_test_utilities_metrics::fetch_int_counter_vec;
+use ic_test_utilities_metrics::{fetch_int_counter_vec, fetch_int_gauge_vec};
 use ic_types::{
     messages::{CallbackId, Payload, RequestOrResponse},
     xnet::{StreamHeader, StreamIndexedQueue},
@@ -546,6 +546,76 @@ fn test_reservations_do_not_inhibit_xnet_induction_of_requests() {
     assert_eq!(metrics.requests_sent, *requests_inducted.unwrap() as usize);
 }
 
+/// Test that a canister flooding the XNet stream to a remote subnet does not delay the
+/// messages of a well-behaved canister on the same subnet.
+/// The local canister first fills up the stream to the remote subnet, until messages pile up
+/// in its output queue. Then a second, well-behaved canister starts sending one small request
+/// per round to the remote canister. Every round, one message is gc'ed from the stream (freeing
+/// up enough space for another message from the flooding canister); yet, since the flooding
+/// canister is already over its fair share of the stream, the requests of the well-behaved
+/// canister are routed in the same round they are sent, while the flooding canister remains
+/// backlogged.
+#[test]
+fn test_flooding_canister_does_not_delay_well_behaved_canister() {
+    let subnets = SubnetPairProxy::with_new_subnets();
+
+    // Flood the stream to the remote subnet from the local canister.
+    subnets
+        .call_start_on_local_canister(10, 1024 * 1024)
+        .unwrap();
+    subnets.build_local_backpressure_until(1);
+
+    // Install a well-behaved canister on the local subnet and make it send one small
+    // request per round to the remote canister.
+    let wasm = Project::cargo_bin_maybe_from_env("xnet-test-canister", &[]).bytes();
+    let well_behaved_canister_id = subnets
+        .local_env
+        .install_canister_with_cycles(wasm, Vec::new(), None, Cycles::new(u128::MAX / 2))
+        .expect("Installing xnet-test-canister failed");
+    let network_topology = vec![
+        vec![well_behaved_canister_id.get().to_vec()],
+        vec![subnets.remote_canister_id.get().to_vec()],
+    ];
+    call_start_on_xnet_canister(
+        &subnets.local_env,
+        well_behaved_canister_id,
+        Encode!(&network_topology, &1_u64, &1024_u64).unwrap(),
+    )
+    .unwrap();
+
+    let requests_in_output_queue = |canister_id| {
+        get_output_queue_iter(
+            &subnets.local_env.get_latest_state(),
+            canister_id,
+            subnets.remote_canister_id,
+        )
+        .map(|iter| iter.filter(|msg| msg.is_some()).count())
+        .unwrap_or_default()
+    };
+
+    for _ in 0..10 {
+        // Gc one message from the stream by inducting it into the remote subnet and then
+        // inducting the resulting signal; this also executes a round on the local subnet.
+        induct_from_head_of_stream(&subnets.local_env, &subnets.remote_env, Some(1)).unwrap();
+        induct_stream_header(&subnets.remote_env, &subnets.local_env).unwrap();
+
+        // All requests of the well-behaved canister were routed; while the flooding
+        // canister is still backlogged.
+        assert_eq!(0, requests_in_output_queue(well_behaved_canister_id));
+        assert!(requests_in_output_queue(subnets.local_canister_id) > 0);
+    }
+
+    let backlogged_messages = fetch_int_gauge_vec(
+        subnets.local_env.metrics_registry(),
+        "mr_backlogged_messages",
+    );
+    assert!(backlogged_messages
+        .get(&btreemap! {
+            "canister".to_string() => subnets.local_canister_id.to_string()
+        })
+        .map_or(false, |count| *count > 0));
+}
+
 /// Snapshot of a message in a stream or a queue that includes only the message variant and the callback id.
 #[derive(Clone, PartialEq)]
 enum MessageSnapshot {
@@ -576,6 +646,7 @@ impl From<&RequestOrResponse> for MessageSnapshot {
                 Self::Response(response.originator_reply_callback)
             }
             Response(response) => Self::RejectResponse(response.originator_reply_callback),
+            RequestOrResponse::Fragment(_) => unreachable!("Message fragments are never enqueued"),
         }
     }
 }
@@ -686,6 +757,7 @@ impl std::fmt::Debug for SubnetSplittingTestState {
 /// Adds / Removes a message's callback id to/from a tracker according to:
 /// - msg is a request: The callback id is added to `add_callback_id_tracker`.
 /// - msg is a response: The callback id is removed from `remove_callback_id_tracker`.
+/// - msg is a fragment: The trackers are updated for its message upon its last fragment.
 fn update_callback_id_trackers(
     msg: &RequestOrResponse,
     add_callback_id_tracker: &mut BTreeSet<CallbackId>,
@@ -709,6 +781,14 @@ fn update_callback_id_trackers(
                 ));
             }
         }
+        RequestOrResponse::Fragment(fragment) if fragment.is_last() => {
+            return update_callback_id_trackers(
+                &fragment.header,
+                add_callback_id_tracker,
+                remove_callback_id_tracker,
+            );
+        }
+        RequestOrResponse::Fragment(_) => {}
     }
     Ok(())
 }
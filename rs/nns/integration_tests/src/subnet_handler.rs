@@ -56,6 +56,7 @@ fn test_submit_and_accept_update_subnet_proposal() {
                 subnet_type: SubnetType::Application.into(),
                 is_halted: false,
                 halt_at_cup_height: false,
+                max_inter_canister_payload_in_bytes: None,
                 max_instructions_per_message: 5_000_000_000,
                 max_instructions_per_round: 7_000_000_000,
                 max_instructions_per_install_code: 200_000_000_000,
@@ -107,6 +108,7 @@ fn test_submit_and_accept_update_subnet_proposal() {
                 subnet_type: None,
                 is_halted: Some(true),
                 halt_at_cup_height: Some(true),
+                max_inter_canister_payload_in_bytes: None,
                 max_instructions_per_message: None,
                 max_instructions_per_round: Some(8_000_000_000),
                 max_instructions_per_install_code: None,
@@ -179,6 +181,7 @@ fn test_submit_and_accept_update_subnet_proposal() {
                     subnet_type: SubnetType::Application.into(),
                     is_halted: true,
                     halt_at_cup_height: true,
+                    max_inter_canister_payload_in_bytes: None,
                     max_instructions_per_message: 5_000_000_000,
                     max_instructions_per_round: 8_000_000_000,
                     max_instructions_per_install_code: 200_000_000_000,
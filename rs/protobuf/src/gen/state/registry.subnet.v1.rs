@@ -84,6 +84,12 @@ pub struct SubnetRecord {
     /// appropriate proposal which sets `is_halted` to `false` is approved.
     #[prost(bool, tag = "28")]
     pub halt_at_cup_height: bool,
+    /// The maximum payload size in bytes of inter-canister requests and responses
+    /// sent by canisters on this subnet. Payloads above 2 MiB are routed to remote
+    /// subnets as sequences of message fragments. If unset, payloads are limited
+    /// to 2 MiB. May not exceed `MAX_FRAGMENTED_INTER_CANISTER_PAYLOAD_IN_BYTES`.
+    #[prost(uint64, optional, tag = "29")]
+    pub max_inter_canister_payload_in_bytes: ::core::option::Option<u64>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -31,6 +31,10 @@ pub mod call_context {
         pub canister_id: ::core::option::Option<super::super::super::super::types::v1::CanisterId>,
         #[prost(uint64, tag = "2")]
         pub callback_id: u64,
+        /// Deadline of the originating call in seconds since UNIX epoch; zero for
+        /// guaranteed response calls.
+        #[prost(uint32, tag = "3")]
+        pub deadline_seconds: u32,
     }
     /// System task is either a Heartbeat or a GlobalTimer.
     #[allow(clippy::derive_partial_eq_without_eq)]
@@ -89,6 +93,10 @@ pub struct Callback {
     #[prost(message, optional, tag = "9")]
     pub prepayment_for_response_transmission:
         ::core::option::Option<super::super::queues::v1::Cycles>,
+    /// Deadline of the call in seconds since UNIX epoch; zero for guaranteed
+    /// response calls.
+    #[prost(uint32, tag = "10")]
+    pub deadline_seconds: u32,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -109,6 +117,10 @@ pub struct CallContextManager {
     pub call_contexts: ::prost::alloc::vec::Vec<CallContextEntry>,
     #[prost(message, repeated, tag = "4")]
     pub callbacks: ::prost::alloc::vec::Vec<CallbackEntry>,
+    /// IDs of best-effort callbacks that have neither expired nor had a response
+    /// enqueued.
+    #[prost(uint64, repeated, tag = "5")]
+    pub unexpired_callbacks: ::prost::alloc::vec::Vec<u64>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -158,6 +170,7 @@ pub mod wasm_method {
         CanisterHeartbeat = 6,
         Empty = 7,
         CanisterGlobalTimer = 8,
+        CanisterOnLowWasmMemory = 9,
     }
     impl SystemMethod {
         /// String value of the enum field names used in the ProtoBuf definition.
@@ -175,6 +188,9 @@ pub mod wasm_method {
                 SystemMethod::CanisterHeartbeat => "SYSTEM_METHOD_CANISTER_HEARTBEAT",
                 SystemMethod::Empty => "SYSTEM_METHOD_EMPTY",
                 SystemMethod::CanisterGlobalTimer => "SYSTEM_METHOD_CANISTER_GLOBAL_TIMER",
+                SystemMethod::CanisterOnLowWasmMemory => {
+                    "SYSTEM_METHOD_CANISTER_ON_LOW_WASM_MEMORY"
+                }
             }
         }
         /// Creates an enum from field names used in the ProtoBuf definition.
@@ -189,6 +205,7 @@ pub mod wasm_method {
                 "SYSTEM_METHOD_CANISTER_HEARTBEAT" => Some(Self::CanisterHeartbeat),
                 "SYSTEM_METHOD_EMPTY" => Some(Self::Empty),
                 "SYSTEM_METHOD_CANISTER_GLOBAL_TIMER" => Some(Self::CanisterGlobalTimer),
+                "SYSTEM_METHOD_CANISTER_ON_LOW_WASM_MEMORY" => Some(Self::CanisterOnLowWasmMemory),
                 _ => None,
             }
         }
@@ -240,6 +257,10 @@ pub struct ExecutionStateBits {
     pub binary_hash: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
     #[prost(enumeration = "NextScheduledMethod", optional, tag = "7")]
     pub next_scheduled_method: ::core::option::Option<i32>,
+    #[prost(bool, tag = "8")]
+    pub is_wasm64: bool,
+    #[prost(uint64, optional, tag = "9")]
+    pub wasm_memory_max_pages: ::core::option::Option<u64>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -272,6 +293,8 @@ pub mod stop_canister_context {
         pub cycles: ::core::option::Option<super::super::super::queues::v1::Cycles>,
         #[prost(uint64, optional, tag = "5")]
         pub call_id: ::core::option::Option<u64>,
+        #[prost(uint32, tag = "6")]
+        pub deadline_seconds: u32,
     }
     #[allow(clippy::derive_partial_eq_without_eq)]
     #[derive(Clone, PartialEq, ::prost::Oneof)]
@@ -363,6 +386,7 @@ pub mod execution_task {
         Unspecified = 0,
         Heartbeat = 1,
         Timer = 2,
+        OnLowWasmMemory = 3,
     }
     impl CanisterTask {
         /// String value of the enum field names used in the ProtoBuf definition.
@@ -374,6 +398,7 @@ pub mod execution_task {
                 CanisterTask::Unspecified => "CANISTER_TASK_UNSPECIFIED",
                 CanisterTask::Heartbeat => "CANISTER_TASK_HEARTBEAT",
                 CanisterTask::Timer => "CANISTER_TASK_TIMER",
+                CanisterTask::OnLowWasmMemory => "CANISTER_TASK_ON_LOW_WASM_MEMORY",
             }
         }
         /// Creates an enum from field names used in the ProtoBuf definition.
@@ -382,6 +407,7 @@ pub mod execution_task {
                 "CANISTER_TASK_UNSPECIFIED" => Some(Self::Unspecified),
                 "CANISTER_TASK_HEARTBEAT" => Some(Self::Heartbeat),
                 "CANISTER_TASK_TIMER" => Some(Self::Timer),
+                "CANISTER_TASK_ON_LOW_WASM_MEMORY" => Some(Self::OnLowWasmMemory),
                 _ => None,
             }
         }
@@ -445,6 +471,22 @@ pub struct CanisterControllersChange {
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CanisterEnvironmentVariablesChange {
+    #[prost(bytes = "vec", tag = "1")]
+    pub environment_variables_hash: ::prost::alloc::vec::Vec<u8>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CanisterLoadSnapshot {
+    #[prost(uint64, tag = "1")]
+    pub canister_version: u64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub snapshot_id: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint64, tag = "3")]
+    pub taken_at_timestamp: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CanisterChange {
     #[prost(uint64, tag = "1")]
     pub timestamp_nanos: u64,
@@ -452,7 +494,7 @@ pub struct CanisterChange {
     pub canister_version: u64,
     #[prost(oneof = "canister_change::ChangeOrigin", tags = "3, 4")]
     pub change_origin: ::core::option::Option<canister_change::ChangeOrigin>,
-    #[prost(oneof = "canister_change::ChangeDetails", tags = "5, 6, 7, 8")]
+    #[prost(oneof = "canister_change::ChangeDetails", tags = "5, 6, 7, 8, 9, 10")]
     pub change_details: ::core::option::Option<canister_change::ChangeDetails>,
 }
 /// Nested message and enum types in `CanisterChange`.
@@ -476,6 +518,10 @@ pub mod canister_change {
         CanisterCodeDeployment(super::CanisterCodeDeployment),
         #[prost(message, tag = "8")]
         CanisterControllersChange(super::CanisterControllersChange),
+        #[prost(message, tag = "9")]
+        CanisterEnvironmentVariablesChange(super::CanisterEnvironmentVariablesChange),
+        #[prost(message, tag = "10")]
+        CanisterLoadSnapshot(super::CanisterLoadSnapshot),
     }
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -503,6 +549,24 @@ pub struct TotalQueryStats {
     pub ingress_payload_size: ::core::option::Option<Unsigned128>,
     #[prost(message, optional, tag = "4")]
     pub egress_payload_size: ::core::option::Option<Unsigned128>,
+    #[prost(message, repeated, tag = "5")]
+    pub method_stats: ::prost::alloc::vec::Vec<TotalMethodStats>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TotalMethodStats {
+    #[prost(string, tag = "1")]
+    pub method_name: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub num_calls: ::core::option::Option<Unsigned128>,
+    #[prost(message, optional, tag = "3")]
+    pub num_errors: ::core::option::Option<Unsigned128>,
+    #[prost(message, optional, tag = "4")]
+    pub num_instructions: ::core::option::Option<Unsigned128>,
+    #[prost(message, optional, tag = "5")]
+    pub ingress_payload_size: ::core::option::Option<Unsigned128>,
+    #[prost(message, optional, tag = "6")]
+    pub egress_payload_size: ::core::option::Option<Unsigned128>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -524,6 +588,34 @@ pub struct WasmChunkStoreMetadata {
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CanisterLogRecord {
+    #[prost(uint64, tag = "1")]
+    pub idx: u64,
+    #[prost(uint64, tag = "2")]
+    pub timestamp_nanos: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub content: ::prost::alloc::vec::Vec<u8>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EnvironmentVariable {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub value: ::prost::alloc::string::String,
+}
+/// A named canister timer set via `ic0.timer_set`.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CanisterTimerEntry {
+    #[prost(uint64, tag = "1")]
+    pub timer_id: u64,
+    /// Deadline of the timer, in nanoseconds since Unix epoch.
+    #[prost(uint64, tag = "2")]
+    pub deadline_nanos: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CanisterStateBits {
     #[prost(uint64, tag = "2")]
     pub last_full_execution_round: u64,
@@ -601,6 +693,40 @@ pub struct CanisterStateBits {
     /// Statistics on query execution for entire lifetime of canister.
     #[prost(message, optional, tag = "41")]
     pub total_query_stats: ::core::option::Option<TotalQueryStats>,
+    /// Total size of all the snapshots of this canister, in bytes.
+    #[prost(uint64, tag = "42")]
+    pub snapshots_memory_usage: u64,
+    /// The local ID to be used for the next snapshot of this canister.
+    #[prost(uint64, tag = "43")]
+    pub next_snapshot_id: u64,
+    /// Who is allowed to fetch the canister log.
+    #[prost(enumeration = "LogVisibility", tag = "44")]
+    pub log_visibility: i32,
+    /// The records currently kept in the canister log.
+    #[prost(message, repeated, tag = "45")]
+    pub canister_log_records: ::prost::alloc::vec::Vec<CanisterLogRecord>,
+    /// The index to be assigned to the next canister log record.
+    #[prost(uint64, tag = "46")]
+    pub next_canister_log_record_idx: u64,
+    /// The user-specified upper limit on the Wasm heap size, in bytes.
+    #[prost(uint64, optional, tag = "47")]
+    pub wasm_memory_limit: ::core::option::Option<u64>,
+    /// Environment variables readable by the canister through `ic0.env_var_*`.
+    #[prost(message, repeated, tag = "48")]
+    pub environment_variables: ::prost::alloc::vec::Vec<EnvironmentVariable>,
+    /// Statistics of update calls, by method name.
+    #[prost(message, repeated, tag = "49")]
+    pub total_update_method_stats: ::prost::alloc::vec::Vec<TotalMethodStats>,
+    /// The user-specified free Wasm heap space, in bytes, below which the
+    /// `canister_on_low_wasm_memory` hook is executed.
+    #[prost(uint64, optional, tag = "50")]
+    pub wasm_memory_threshold: ::core::option::Option<u64>,
+    /// Whether the `canister_on_low_wasm_memory` hook is due or has already run.
+    #[prost(enumeration = "OnLowWasmMemoryHookStatus", tag = "51")]
+    pub on_low_wasm_memory_hook_status: i32,
+    /// Active named canister timers.
+    #[prost(message, repeated, tag = "52")]
+    pub timers: ::prost::alloc::vec::Vec<CanisterTimerEntry>,
     #[prost(oneof = "canister_state_bits::CanisterStatus", tags = "11, 12, 13")]
     pub canister_status: ::core::option::Option<canister_state_bits::CanisterStatus>,
 }
@@ -617,6 +743,35 @@ pub mod canister_state_bits {
         Stopped(super::CanisterStatusStopped),
     }
 }
+/// Bits of a canister snapshot that are not persisted in separate files.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CanisterSnapshotBits {
+    /// Time at which the snapshot was taken, in nanoseconds since Unix epoch.
+    #[prost(uint64, tag = "1")]
+    pub taken_at_timestamp: u64,
+    /// Canister version at the time the snapshot was taken.
+    #[prost(uint64, tag = "2")]
+    pub canister_version: u64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub certified_data: ::prost::alloc::vec::Vec<u8>,
+    /// Canister global timer, in nanoseconds since Unix epoch.
+    #[prost(uint64, optional, tag = "4")]
+    pub global_timer_nanos: ::core::option::Option<u64>,
+    #[prost(message, repeated, tag = "5")]
+    pub exported_globals: ::prost::alloc::vec::Vec<Global>,
+    /// Size of the Wasm memory, in Wasm pages.
+    #[prost(uint64, tag = "6")]
+    pub wasm_memory_size: u64,
+    /// Size of the stable memory, in Wasm pages.
+    #[prost(uint64, tag = "7")]
+    pub stable_memory_size: u64,
+    /// Maps tracking chunks in the Wasm chunk store.
+    #[prost(message, optional, tag = "8")]
+    pub wasm_chunk_store_metadata: ::core::option::Option<WasmChunkStoreMetadata>,
+    #[prost(bytes = "vec", optional, tag = "9")]
+    pub binary_hash: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+}
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum CustomSectionType {
@@ -741,3 +896,68 @@ impl CyclesUseCase {
         }
     }
 }
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum LogVisibility {
+    Unspecified = 0,
+    Controllers = 1,
+    Public = 2,
+}
+impl LogVisibility {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            LogVisibility::Unspecified => "LOG_VISIBILITY_UNSPECIFIED",
+            LogVisibility::Controllers => "LOG_VISIBILITY_CONTROLLERS",
+            LogVisibility::Public => "LOG_VISIBILITY_PUBLIC",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "LOG_VISIBILITY_UNSPECIFIED" => Some(Self::Unspecified),
+            "LOG_VISIBILITY_CONTROLLERS" => Some(Self::Controllers),
+            "LOG_VISIBILITY_PUBLIC" => Some(Self::Public),
+            _ => None,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum OnLowWasmMemoryHookStatus {
+    Unspecified = 0,
+    ConditionNotSatisfied = 1,
+    Ready = 2,
+    Executed = 3,
+}
+impl OnLowWasmMemoryHookStatus {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            OnLowWasmMemoryHookStatus::Unspecified => "ON_LOW_WASM_MEMORY_HOOK_STATUS_UNSPECIFIED",
+            OnLowWasmMemoryHookStatus::ConditionNotSatisfied => {
+                "ON_LOW_WASM_MEMORY_HOOK_STATUS_CONDITION_NOT_SATISFIED"
+            }
+            OnLowWasmMemoryHookStatus::Ready => "ON_LOW_WASM_MEMORY_HOOK_STATUS_READY",
+            OnLowWasmMemoryHookStatus::Executed => "ON_LOW_WASM_MEMORY_HOOK_STATUS_EXECUTED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "ON_LOW_WASM_MEMORY_HOOK_STATUS_UNSPECIFIED" => Some(Self::Unspecified),
+            "ON_LOW_WASM_MEMORY_HOOK_STATUS_CONDITION_NOT_SATISFIED" => {
+                Some(Self::ConditionNotSatisfied)
+            }
+            "ON_LOW_WASM_MEMORY_HOOK_STATUS_READY" => Some(Self::Ready),
+            "ON_LOW_WASM_MEMORY_HOOK_STATUS_EXECUTED" => Some(Self::Executed),
+            _ => None,
+        }
+    }
+}
@@ -30,6 +30,8 @@ pub struct SubnetTopology {
     #[prost(message, repeated, tag = "5")]
     pub ecdsa_keys_held:
         ::prost::alloc::vec::Vec<super::super::super::registry::crypto::v1::EcdsaKeyId>,
+    #[prost(uint64, optional, tag = "6")]
+    pub max_inter_canister_payload_in_bytes: ::core::option::Option<u64>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
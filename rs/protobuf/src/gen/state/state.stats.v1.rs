@@ -27,4 +27,6 @@ pub struct QueryStatsInner {
     pub ingress_payload_size: u64,
     #[prost(uint64, tag = "6")]
     pub egress_payload_size: u64,
+    #[prost(message, repeated, tag = "7")]
+    pub method_stats: ::prost::alloc::vec::Vec<super::super::super::types::v1::MethodQueryStats>,
 }
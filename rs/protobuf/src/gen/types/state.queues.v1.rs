@@ -67,6 +67,9 @@ pub struct Request {
     pub cycles_payment: ::core::option::Option<Cycles>,
     #[prost(message, optional, tag = "8")]
     pub metadata: ::core::option::Option<RequestMetadata>,
+    /// Deadline in seconds since UNIX epoch. Zero for guaranteed response calls.
+    #[prost(uint32, tag = "9")]
+    pub deadline_seconds: u32,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -91,6 +94,9 @@ pub struct Response {
     pub refund: ::core::option::Option<Funds>,
     #[prost(message, optional, tag = "7")]
     pub cycles_refund: ::core::option::Option<Cycles>,
+    /// Deadline in seconds since UNIX epoch. Zero for guaranteed responses.
+    #[prost(uint32, tag = "8")]
+    pub deadline_seconds: u32,
     #[prost(oneof = "response::ResponsePayload", tags = "5, 6")]
     pub response_payload: ::core::option::Option<response::ResponsePayload>,
 }
@@ -110,7 +116,7 @@ pub mod response {
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct RequestOrResponse {
-    #[prost(oneof = "request_or_response::R", tags = "1, 2")]
+    #[prost(oneof = "request_or_response::R", tags = "1, 2, 3")]
     pub r: ::core::option::Option<request_or_response::R>,
 }
 /// Nested message and enum types in `RequestOrResponse`.
@@ -123,8 +129,26 @@ pub mod request_or_response {
         Request(super::Request),
         #[prost(message, tag = "2")]
         Response(super::Response),
+        #[prost(message, tag = "3")]
+        Fragment(::prost::alloc::boxed::Box<super::MessageFragment>),
     }
 }
+/// A fragment of a `Request` or `Response` whose payload is too large to be
+/// routed to a remote subnet as a single message.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MessageFragment {
+    /// The fragmented `Request` or `Response`, with an empty payload.
+    #[prost(message, optional, boxed, tag = "1")]
+    pub header: ::core::option::Option<::prost::alloc::boxed::Box<RequestOrResponse>>,
+    #[prost(uint32, tag = "2")]
+    pub index: u32,
+    #[prost(uint32, tag = "3")]
+    pub count: u32,
+    #[prost(bytes = "vec", tag = "4")]
+    pub payload: ::prost::alloc::vec::Vec<u8>,
+}
 #[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -188,6 +212,9 @@ pub struct CanisterQueues {
     #[prost(message, repeated, tag = "8")]
     pub remote_subnet_input_schedule:
         ::prost::alloc::vec::Vec<super::super::super::types::v1::CanisterId>,
+    /// Fragments of partially received inbound messages, in order.
+    #[prost(message, repeated, tag = "9")]
+    pub input_fragments: ::prost::alloc::vec::Vec<MessageFragment>,
 }
 /// Nested message and enum types in `CanisterQueues`.
 pub mod canister_queues {
@@ -4,6 +4,8 @@
 pub struct CanisterUpgradeOptions {
     #[prost(bool, optional, tag = "1")]
     pub skip_pre_upgrade: ::core::option::Option<bool>,
+    #[prost(enumeration = "WasmMemoryPersistence", optional, tag = "2")]
+    pub wasm_memory_persistence: ::core::option::Option<i32>,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -72,6 +74,47 @@ impl CanisterInstallMode {
         }
     }
 }
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    ::prost::Enumeration,
+)]
+#[repr(i32)]
+pub enum WasmMemoryPersistence {
+    Unspecified = 0,
+    Keep = 1,
+    Replace = 2,
+}
+impl WasmMemoryPersistence {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            WasmMemoryPersistence::Unspecified => "WASM_MEMORY_PERSISTENCE_UNSPECIFIED",
+            WasmMemoryPersistence::Keep => "WASM_MEMORY_PERSISTENCE_KEEP",
+            WasmMemoryPersistence::Replace => "WASM_MEMORY_PERSISTENCE_REPLACE",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "WASM_MEMORY_PERSISTENCE_UNSPECIFIED" => Some(Self::Unspecified),
+            "WASM_MEMORY_PERSISTENCE_KEEP" => Some(Self::Keep),
+            "WASM_MEMORY_PERSISTENCE_REPLACE" => Some(Self::Replace),
+            _ => None,
+        }
+    }
+}
 #[derive(serde::Serialize, serde::Deserialize, Eq, Hash)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -1152,6 +1195,25 @@ pub struct CanisterQueryStats {
     pub ingress_payload_size: u64,
     #[prost(uint64, tag = "5")]
     pub egress_payload_size: u64,
+    #[prost(message, repeated, tag = "6")]
+    pub method_stats: ::prost::alloc::vec::Vec<MethodQueryStats>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MethodQueryStats {
+    #[prost(string, tag = "1")]
+    pub method_name: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub num_calls: u32,
+    #[prost(uint32, tag = "3")]
+    pub num_errors: u32,
+    #[prost(uint64, tag = "4")]
+    pub num_instructions: u64,
+    #[prost(uint64, tag = "5")]
+    pub ingress_payload_size: u64,
+    #[prost(uint64, tag = "6")]
+    pub egress_payload_size: u64,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
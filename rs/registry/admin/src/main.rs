@@ -1606,6 +1606,13 @@ struct ProposeToUpdateSubnetCmd {
     /// for more details on how to choose values.
     max_instructions_per_install_code: Option<u64>,
 
+    #[clap(long)]
+    /// If set, this updates the maximum payload size of inter-canister
+    /// requests and responses sent by canisters on the subnet. Must be
+    /// between 2 MiB and 10 MiB; payloads above 2 MiB are routed to remote
+    /// subnets as message fragments.
+    max_inter_canister_payload_in_bytes: Option<u64>,
+
     #[clap(long)]
     /// Enable key signing on this subnet for a particular key_id.
     /// Only one key_id is permitted at a time at the moment.
@@ -1823,6 +1830,7 @@ impl ProposalPayload<UpdateSubnetPayload> for ProposeToUpdateSubnetCmd {
             max_instructions_per_message: self.max_instructions_per_message,
             max_instructions_per_round: self.max_instructions_per_round,
             max_instructions_per_install_code: self.max_instructions_per_install_code,
+            max_inter_canister_payload_in_bytes: self.max_inter_canister_payload_in_bytes,
             features: self.features.map(|v| v.into()),
             ecdsa_config,
             ecdsa_key_signing_enable,
@@ -66,6 +66,7 @@ pub(crate) struct SubnetRecord {
     pub ssh_readonly_access: Vec<String>,
     pub ssh_backup_access: Vec<String>,
     pub ecdsa_config: Option<EcdsaConfig>,
+    pub max_inter_canister_payload_in_bytes: Option<u64>,
 }
 
 impl SubnetRecord {
@@ -121,6 +122,7 @@ impl From<&SubnetRecordProto> for SubnetRecord {
                 .ecdsa_config
                 .as_ref()
                 .map(|c| c.clone().try_into().unwrap()),
+            max_inter_canister_payload_in_bytes: value.max_inter_canister_payload_in_bytes,
         }
     }
 }
@@ -344,6 +344,7 @@ impl From<CreateSubnetPayload> for SubnetRecord {
 
             is_halted: val.is_halted,
             halt_at_cup_height: false,
+            max_inter_canister_payload_in_bytes: None,
 
             max_instructions_per_message: val.max_instructions_per_message,
             max_instructions_per_round: val.max_instructions_per_round,
@@ -12,7 +12,12 @@ use ic_registry_keys::{make_ecdsa_signing_subnet_list_key, make_subnet_record_ke
 use ic_registry_subnet_features::{EcdsaConfig, SubnetFeatures};
 use ic_registry_subnet_type::SubnetType;
 use ic_registry_transport::{pb::v1::RegistryMutation, upsert};
-use ic_types::p2p::build_default_gossip_config;
+use ic_types::{
+    messages::{
+        MAX_FRAGMENTED_INTER_CANISTER_PAYLOAD_IN_BYTES, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES,
+    },
+    p2p::build_default_gossip_config,
+};
 
 /// Updates the subnet's configuration in the registry.
 ///
@@ -24,6 +29,7 @@ impl Registry {
 
         self.validate_update_payload_ecdsa_config(&payload);
         self.validate_update_sev_feature(&payload);
+        validate_max_inter_canister_payload_in_bytes(&payload);
 
         let subnet_id = payload.subnet_id;
 
@@ -177,6 +183,28 @@ impl Registry {
     }
 }
 
+/// Validates that the maximum inter-canister payload size, if set, is at least
+/// `MAX_INTER_CANISTER_PAYLOAD_IN_BYTES` and at most
+/// `MAX_FRAGMENTED_INTER_CANISTER_PAYLOAD_IN_BYTES`.
+/// Panics if it is not.
+fn validate_max_inter_canister_payload_in_bytes(payload: &UpdateSubnetPayload) {
+    if let Some(max_payload_bytes) = payload.max_inter_canister_payload_in_bytes {
+        let range = MAX_INTER_CANISTER_PAYLOAD_IN_BYTES.get()
+            ..=MAX_FRAGMENTED_INTER_CANISTER_PAYLOAD_IN_BYTES.get();
+        if !range.contains(&max_payload_bytes) {
+            panic!(
+                "{}Proposal attempts to set max_inter_canister_payload_in_bytes for Subnet '{}' \
+                to {}, but it must be between {} and {}.",
+                LOG_PREFIX,
+                payload.subnet_id,
+                max_payload_bytes,
+                range.start(),
+                range.end()
+            );
+        }
+    }
+}
+
 /// The payload of a proposal to update an existing subnet's configuration.
 ///
 /// See /rs/protobuf/def/registry/subnet/v1/subnet.proto
@@ -222,6 +250,7 @@ pub struct UpdateSubnetPayload {
     pub max_instructions_per_message: Option<u64>,
     pub max_instructions_per_round: Option<u64>,
     pub max_instructions_per_install_code: Option<u64>,
+    pub max_inter_canister_payload_in_bytes: Option<u64>,
     pub features: Option<pbSubnetFeatures>,
 
     /// This defines keys held by the subnet,
@@ -315,6 +344,7 @@ fn merge_subnet_record(
         max_instructions_per_message,
         max_instructions_per_round,
         max_instructions_per_install_code,
+        max_inter_canister_payload_in_bytes,
         features,
         ecdsa_config,
         ecdsa_key_signing_enable: _,
@@ -364,6 +394,7 @@ fn merge_subnet_record(
     maybe_set!(subnet_record, max_instructions_per_message);
     maybe_set!(subnet_record, max_instructions_per_round);
     maybe_set!(subnet_record, max_instructions_per_install_code);
+    maybe_set_option!(subnet_record, max_inter_canister_payload_in_bytes);
 
     maybe_set_option!(subnet_record, features);
     maybe_set_option!(subnet_record, ecdsa_config);
@@ -434,6 +465,7 @@ mod tests {
             subnet_type: None,
             is_halted: Some(true),
             halt_at_cup_height: Some(false),
+            max_inter_canister_payload_in_bytes: None,
             max_instructions_per_message: Some(6_000_000_000),
             max_instructions_per_round: Some(8_000_000_000),
             max_instructions_per_install_code: Some(300_000_000_000),
@@ -483,6 +515,7 @@ mod tests {
             subnet_type: None,
             is_halted: None,
             halt_at_cup_height: None,
+            max_inter_canister_payload_in_bytes: None,
             max_instructions_per_message: None,
             max_instructions_per_round: None,
             max_instructions_per_install_code: None,
@@ -522,6 +555,7 @@ mod tests {
             subnet_type: SubnetType::Application.into(),
             is_halted: false,
             halt_at_cup_height: false,
+            max_inter_canister_payload_in_bytes: None,
             max_instructions_per_message: 5_000_000_000,
             max_instructions_per_round: 7_000_000_000,
             max_instructions_per_install_code: 200_000_000_000,
@@ -559,6 +593,7 @@ mod tests {
             subnet_type: None,
             is_halted: Some(true),
             halt_at_cup_height: Some(false),
+            max_inter_canister_payload_in_bytes: Some(4 * 1024 * 1024),
             max_instructions_per_message: Some(6_000_000_000),
             max_instructions_per_round: Some(8_000_000_000),
             max_instructions_per_install_code: Some(300_000_000_000),
@@ -610,6 +645,7 @@ mod tests {
                 subnet_type: SubnetType::Application.into(),
                 is_halted: true,
                 halt_at_cup_height: false,
+                max_inter_canister_payload_in_bytes: Some(4 * 1024 * 1024),
                 max_instructions_per_message: 6_000_000_000,
                 max_instructions_per_round: 8_000_000_000,
                 max_instructions_per_install_code: 300_000_000_000,
@@ -664,6 +700,7 @@ mod tests {
             subnet_type: SubnetType::Application.into(),
             is_halted: false,
             halt_at_cup_height: false,
+            max_inter_canister_payload_in_bytes: None,
             max_instructions_per_message: 5_000_000_000,
             max_instructions_per_round: 7_000_000_000,
             max_instructions_per_install_code: 200_000_000_000,
@@ -701,6 +738,7 @@ mod tests {
             subnet_type: None,
             is_halted: None,
             halt_at_cup_height: Some(true),
+            max_inter_canister_payload_in_bytes: None,
             max_instructions_per_message: None,
             max_instructions_per_round: Some(8_000_000_000),
             max_instructions_per_install_code: None,
@@ -739,6 +777,7 @@ mod tests {
                 subnet_type: SubnetType::Application.into(),
                 is_halted: false,
                 halt_at_cup_height: true,
+                max_inter_canister_payload_in_bytes: None,
                 max_instructions_per_message: 5_000_000_000,
                 max_instructions_per_round: 8_000_000_000,
                 max_instructions_per_install_code: 200_000_000_000,
@@ -792,6 +831,7 @@ mod tests {
             subnet_type: SubnetType::Application.into(),
             is_halted: false,
             halt_at_cup_height: false,
+            max_inter_canister_payload_in_bytes: None,
             max_instructions_per_message: 5_000_000_000,
             max_instructions_per_round: 7_000_000_000,
             max_instructions_per_install_code: 200_000_000_000,
@@ -829,6 +869,7 @@ mod tests {
             subnet_type: Some(SubnetType::Application),
             is_halted: None,
             halt_at_cup_height: None,
+            max_inter_canister_payload_in_bytes: None,
             max_instructions_per_message: None,
             max_instructions_per_round: None,
             max_instructions_per_install_code: None,
@@ -861,6 +902,7 @@ mod tests {
             subnet_type: SubnetType::Application.into(),
             is_halted: false,
             halt_at_cup_height: false,
+            max_inter_canister_payload_in_bytes: None,
             max_instructions_per_message: 5_000_000_000,
             max_instructions_per_round: 7_000_000_000,
             max_instructions_per_install_code: 200_000_000_000,
@@ -898,6 +940,7 @@ mod tests {
             subnet_type: None,
             is_halted: None,
             halt_at_cup_height: None,
+            max_inter_canister_payload_in_bytes: None,
             max_instructions_per_message: None,
             max_instructions_per_round: None,
             max_instructions_per_install_code: None,
@@ -936,6 +979,7 @@ mod tests {
                 subnet_type: SubnetType::Application.into(),
                 is_halted: false,
                 halt_at_cup_height: false,
+                max_inter_canister_payload_in_bytes: None,
                 max_instructions_per_message: 5_000_000_000,
                 max_instructions_per_round: 7_000_000_000,
                 max_instructions_per_install_code: 200_000_000_000,
@@ -974,6 +1018,7 @@ mod tests {
             subnet_type: SubnetType::Application.into(),
             is_halted: false,
             halt_at_cup_height: false,
+            max_inter_canister_payload_in_bytes: None,
             max_instructions_per_message: 5_000_000_000,
             max_instructions_per_round: 7_000_000_000,
             max_instructions_per_install_code: 200_000_000_000,
@@ -1011,6 +1056,7 @@ mod tests {
             subnet_type: None,
             is_halted: None,
             halt_at_cup_height: None,
+            max_inter_canister_payload_in_bytes: None,
             max_instructions_per_message: None,
             max_instructions_per_round: Some(8_000_000_000),
             max_instructions_per_install_code: None,
@@ -1049,6 +1095,7 @@ mod tests {
                 subnet_type: SubnetType::Application.into(),
                 is_halted: false,
                 halt_at_cup_height: false,
+                max_inter_canister_payload_in_bytes: None,
                 max_instructions_per_message: 5_000_000_000,
                 max_instructions_per_round: 8_000_000_000,
                 max_instructions_per_install_code: 200_000_000_000,
@@ -1407,4 +1454,62 @@ mod tests {
         // Should panic because we are trying to modify the config
         registry.do_update_subnet(payload)
     }
+
+    fn make_registry_with_subnet(subnet_id: SubnetId) -> Registry {
+        let mut registry = invariant_compliant_registry(0);
+
+        let (mutate_request, mut node_ids) = prepare_registry_with_nodes(1, 1);
+        registry.maybe_apply_mutation_internal(mutate_request.mutations);
+
+        let mut subnet_list_record = registry.get_subnet_list_record();
+        let subnet_record = get_invariant_compliant_subnet_record(vec![node_ids.pop().unwrap()]);
+        registry.maybe_apply_mutation_internal(add_fake_subnet(
+            subnet_id,
+            &mut subnet_list_record,
+            subnet_record,
+        ));
+        registry
+    }
+
+    #[test]
+    fn can_set_max_inter_canister_payload_in_bytes() {
+        let subnet_id = subnet_test_id(1000);
+        let mut registry = make_registry_with_subnet(subnet_id);
+
+        let mut payload = make_empty_update_payload(subnet_id);
+        payload.max_inter_canister_payload_in_bytes =
+            Some(MAX_FRAGMENTED_INTER_CANISTER_PAYLOAD_IN_BYTES.get());
+        registry.do_update_subnet(payload);
+
+        assert_eq!(
+            registry
+                .get_subnet_or_panic(subnet_id)
+                .max_inter_canister_payload_in_bytes,
+            Some(MAX_FRAGMENTED_INTER_CANISTER_PAYLOAD_IN_BYTES.get())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "but it must be between 2097152 and 10485760")]
+    fn max_inter_canister_payload_in_bytes_above_fragmented_limit_is_rejected() {
+        let subnet_id = subnet_test_id(1000);
+        let mut registry = make_registry_with_subnet(subnet_id);
+
+        let mut payload = make_empty_update_payload(subnet_id);
+        payload.max_inter_canister_payload_in_bytes =
+            Some(MAX_FRAGMENTED_INTER_CANISTER_PAYLOAD_IN_BYTES.get() + 1);
+        registry.do_update_subnet(payload);
+    }
+
+    #[test]
+    #[should_panic(expected = "but it must be between 2097152 and 10485760")]
+    fn max_inter_canister_payload_in_bytes_below_unfragmented_limit_is_rejected() {
+        let subnet_id = subnet_test_id(1000);
+        let mut registry = make_registry_with_subnet(subnet_id);
+
+        let mut payload = make_empty_update_payload(subnet_id);
+        payload.max_inter_canister_payload_in_bytes =
+            Some(MAX_INTER_CANISTER_PAYLOAD_IN_BYTES.get() - 1);
+        registry.do_update_subnet(payload);
+    }
 }
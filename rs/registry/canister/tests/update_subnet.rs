@@ -86,6 +86,7 @@ fn test_the_anonymous_user_cannot_update_a_subnets_configuration() {
             subnet_type: None,
             is_halted: None,
             halt_at_cup_height: None,
+            max_inter_canister_payload_in_bytes: None,
             max_instructions_per_message: Some(6_000_000_000),
             max_instructions_per_round: Some(8_000_000_000),
             max_instructions_per_install_code: Some(300_000_000_000),
@@ -158,6 +159,7 @@ fn test_a_canister_other_than_the_governance_canister_cannot_update_a_subnets_co
             subnet_type: SubnetType::Application.into(),
             is_halted: false,
             halt_at_cup_height: false,
+            max_inter_canister_payload_in_bytes: None,
             max_instructions_per_message: 5_000_000_000,
             max_instructions_per_round: 7_000_000_000,
             max_instructions_per_install_code: 200_000_000_000,
@@ -215,6 +217,7 @@ fn test_a_canister_other_than_the_governance_canister_cannot_update_a_subnets_co
             subnet_type: None,
             is_halted: None,
             halt_at_cup_height: None,
+            max_inter_canister_payload_in_bytes: None,
             max_instructions_per_message: Some(6_000_000_000),
             max_instructions_per_round: Some(8_000_000_000),
             max_instructions_per_install_code: Some(300_000_000_000),
@@ -286,6 +289,7 @@ fn test_the_governance_canister_can_update_a_subnets_configuration() {
                             subnet_type: SubnetType::Application.into(),
                             is_halted: false,
                             halt_at_cup_height: false,
+                            max_inter_canister_payload_in_bytes: None,
                             max_instructions_per_message: 5_000_000_000,
                             max_instructions_per_round: 7_000_000_000,
                             max_instructions_per_install_code: 200_000_000_000,
@@ -334,6 +338,7 @@ fn test_the_governance_canister_can_update_a_subnets_configuration() {
             subnet_type: Some(SubnetType::Application),
             is_halted: Some(true),
             halt_at_cup_height: Some(true),
+            max_inter_canister_payload_in_bytes: None,
             max_instructions_per_message: Some(6_000_000_000),
             max_instructions_per_round: Some(8_000_000_000),
             max_instructions_per_install_code: Some(300_000_000_000),
@@ -390,6 +395,7 @@ fn test_the_governance_canister_can_update_a_subnets_configuration() {
                 subnet_type: SubnetType::Application.into(),
                 is_halted: true,
                 halt_at_cup_height: true,
+                max_inter_canister_payload_in_bytes: None,
                 max_instructions_per_message: 6_000_000_000,
                 max_instructions_per_round: 8_000_000_000,
                 max_instructions_per_install_code: 300_000_000_000,
@@ -444,6 +450,7 @@ fn test_subnets_configuration_ecdsa_fields_are_updated_correctly() {
             subnet_type: SubnetType::Application.into(),
             is_halted: false,
             halt_at_cup_height: false,
+            max_inter_canister_payload_in_bytes: None,
             max_instructions_per_message: 5_000_000_000,
             max_instructions_per_round: 7_000_000_000,
             max_instructions_per_install_code: 200_000_000_000,
@@ -655,6 +662,7 @@ fn empty_update_subnet_payload(subnet_id: SubnetId) -> UpdateSubnetPayload {
         subnet_type: None,
         is_halted: None,
         halt_at_cup_height: None,
+        max_inter_canister_payload_in_bytes: None,
         max_instructions_per_message: None,
         max_instructions_per_round: None,
         max_instructions_per_install_code: None,
@@ -304,6 +304,7 @@ impl Player {
             Arc::clone(&cycles_account_manager),
             Arc::clone(&state_manager) as Arc<_>,
             state_manager.get_fd_factory(),
+            None,
         );
         let message_routing = Arc::new(MessageRoutingImpl::new(
             state_manager.clone(),
@@ -5,7 +5,9 @@ use ic_artifact_pool::{
 };
 use ic_btc_adapter_client::{setup_bitcoin_adapter_clients, BitcoinAdapterClients};
 use ic_btc_consensus::BitcoinPayloadBuilder;
-use ic_config::{artifact_pool::ArtifactPoolConfig, subnet_config::SubnetConfig, Config};
+use ic_config::{
+    artifact_pool::ArtifactPoolConfig, flag_status::FlagStatus, subnet_config::SubnetConfig, Config,
+};
 use ic_consensus::certification::VerifierImpl;
 use ic_crypto::CryptoComponent;
 use ic_cycles_account_manager::CyclesAccountManager;
@@ -31,7 +33,7 @@ use ic_types::{
     NodeId, SubnetId,
 };
 use ic_xnet_endpoint::{XNetEndpoint, XNetEndpointConfig};
-use ic_xnet_payload_builder::XNetPayloadBuilderImpl;
+use ic_xnet_payload_builder::{XNetPayloadBuilderImpl, XNetQueryClient};
 use std::sync::{Arc, RwLock};
 
 /// Create the consensus pool directory (if none exists)
@@ -173,16 +175,41 @@ pub fn construct_ic_stack(
         subnet_config.cycles_account_manager_config,
     ));
 
+    // Persist compiled Wasm modules next to the state, so that canisters don't
+    // need to be recompiled after a restart.
+    let mut hypervisor_config = config.hypervisor.clone();
+    // The Wasm profiler slows down execution and is only meant for local tools.
+    assert_eq!(
+        hypervisor_config
+            .embedders_config
+            .feature_flags
+            .wasm_profiler,
+        FlagStatus::Disabled,
+        "The Wasm profiler must not be enabled on a replica"
+    );
+    hypervisor_config.compilation_cache_dir = hypervisor_config
+        .compilation_cache_dir
+        .or_else(|| Some(state_manager.state_layout().compilation_cache()));
     let execution_services = ExecutionServices::setup_execution(
         log.clone(),
         metrics_registry,
         subnet_id,
         subnet_type,
         subnet_config.scheduler_config,
-        config.hypervisor.clone(),
+        hypervisor_config,
         cycles_account_manager.clone(),
         state_manager.clone(),
         state_manager.get_fd_factory(),
+        Some(Arc::new(XNetQueryClient::new(
+            registry.clone(),
+            Arc::clone(&crypto) as Arc<_>,
+            rt_handle_xnet.clone(),
+            // Leave at least half of the query execution threads to local
+            // queries and to the query calls of other subnets.
+            (config.hypervisor.query_execution_threads_total / 2).max(1),
+            metrics_registry,
+            log.clone(),
+        ))),
     );
     // ---------- MESSAGE ROUTING DEPS FOLLOW ----------
     let certified_stream_store: Arc<dyn CertifiedStreamStore> =
@@ -219,6 +246,7 @@ pub fn construct_ic_stack(
     let xnet_endpoint = XNetEndpoint::new(
         rt_handle_xnet.clone(),
         Arc::clone(&certified_stream_store),
+        execution_services.cross_subnet_query_handler,
         Arc::clone(&crypto) as Arc<_>,
         registry.clone(),
         xnet_config,
@@ -0,0 +1,421 @@
+use crate::{
+    canister_state::{
+        execution_state::Memory,
+        system_state::wasm_chunk_store::{WasmChunkStore, WasmChunkStoreMetadata},
+    },
+    num_bytes_try_from,
+    page_map::PageAllocatorFileDescriptor,
+    CanisterState, Global, PageMap,
+};
+use ic_sys::{PageBytes, PageIndex};
+use ic_types::{CanisterId, CanisterTimer, NumBytes, PrincipalId, Time};
+use ic_wasm_types::CanisterModule;
+use std::{collections::BTreeMap, convert::TryFrom, fmt, sync::Arc};
+
+/// Size in bytes of the encoded local part of a `SnapshotId`.
+const LOCAL_ID_SIZE: usize = std::mem::size_of::<u64>();
+
+/// Uniquely identifies a canister snapshot on the subnet.
+///
+/// A snapshot ID consists of the ID of the snapshotted canister and a local ID
+/// that is unique among all the snapshots ever taken of that canister. The
+/// binary representation returned to users is the big-endian encoding of the
+/// local ID followed by the bytes of the canister ID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SnapshotId {
+    canister_id: CanisterId,
+    local_id: u64,
+}
+
+impl SnapshotId {
+    pub fn new(canister_id: CanisterId, local_id: u64) -> Self {
+        Self {
+            canister_id,
+            local_id,
+        }
+    }
+
+    /// Returns the ID of the canister that this snapshot belongs to.
+    pub fn canister_id(&self) -> CanisterId {
+        self.canister_id
+    }
+
+    /// Returns the per-canister unique part of the ID.
+    pub fn local_id(&self) -> u64 {
+        self.local_id
+    }
+
+    /// Returns the binary representation of the ID.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut bytes = self.local_id.to_be_bytes().to_vec();
+        bytes.extend_from_slice(self.canister_id.get_ref().as_slice());
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for SnapshotId {
+    type Error = String;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if bytes.len() <= LOCAL_ID_SIZE {
+            return Err(format!(
+                "Snapshot ID must be longer than {} bytes, got {} bytes",
+                LOCAL_ID_SIZE,
+                bytes.len()
+            ));
+        }
+        let (local_id, canister_id) = bytes.split_at(LOCAL_ID_SIZE);
+        let local_id = u64::from_be_bytes(local_id.try_into().unwrap());
+        let canister_id = PrincipalId::try_from(canister_id)
+            .map_err(|err| format!("Invalid canister ID in snapshot ID: {}", err))?;
+        Ok(Self {
+            canister_id: CanisterId::unchecked_from_principal(canister_id),
+            local_id,
+        })
+    }
+}
+
+impl fmt::Display for SnapshotId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.to_vec() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns a copy of `page_map` whose pages are all held in its delta.
+///
+/// Snapshots must not share storage with the `PageMap` they were taken from:
+/// the copy has no base height, so on the next flush its full contents get
+/// written to its own file.
+pub fn copy_page_map(
+    page_map: &PageMap,
+    fd_factory: Arc<dyn PageAllocatorFileDescriptor>,
+) -> PageMap {
+    let zero_page: PageBytes = [0; ic_sys::PAGE_SIZE];
+    let pages: Vec<(PageIndex, &PageBytes)> = page_map
+        .host_pages_iter()
+        .filter(|(_, contents)| **contents != zero_page)
+        .collect();
+    let mut copy = PageMap::new(fd_factory);
+    copy.update(&pages);
+    copy
+}
+
+/// Returns a copy of `memory` backed by a fresh `PageMap`.
+fn copy_memory(memory: &Memory, fd_factory: Arc<dyn PageAllocatorFileDescriptor>) -> Memory {
+    Memory::new(copy_page_map(&memory.page_map, fd_factory), memory.size)
+}
+
+/// The parts of a canister's `ExecutionState` that are captured by a snapshot.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecutionStateSnapshot {
+    /// The raw Wasm module of the canister.
+    pub wasm_binary: CanisterModule,
+    /// The values of the exported globals.
+    pub exported_globals: Vec<Global>,
+    /// Contents of the Wasm heap.
+    pub wasm_memory: Memory,
+    /// Contents of the stable memory.
+    pub stable_memory: Memory,
+}
+
+/// A snapshot of a canister, taken via `take_canister_snapshot`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CanisterSnapshot {
+    canister_id: CanisterId,
+    /// The time at which the snapshot was taken.
+    taken_at_timestamp: Time,
+    /// The canister version at the time the snapshot was taken.
+    canister_version: u64,
+    certified_data: Vec<u8>,
+    global_timer: CanisterTimer,
+    chunk_store: WasmChunkStore,
+    execution_snapshot: ExecutionStateSnapshot,
+}
+
+impl CanisterSnapshot {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        canister_id: CanisterId,
+        taken_at_timestamp: Time,
+        canister_version: u64,
+        certified_data: Vec<u8>,
+        global_timer: CanisterTimer,
+        chunk_store: WasmChunkStore,
+        execution_snapshot: ExecutionStateSnapshot,
+    ) -> Self {
+        Self {
+            canister_id,
+            taken_at_timestamp,
+            canister_version,
+            certified_data,
+            global_timer,
+            chunk_store,
+            execution_snapshot,
+        }
+    }
+
+    /// Creates a snapshot from a checkpoint.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_from_checkpoint(
+        canister_id: CanisterId,
+        taken_at_timestamp: Time,
+        canister_version: u64,
+        certified_data: Vec<u8>,
+        global_timer: CanisterTimer,
+        chunk_store_data: PageMap,
+        chunk_store_metadata: WasmChunkStoreMetadata,
+        execution_snapshot: ExecutionStateSnapshot,
+    ) -> Self {
+        Self::new(
+            canister_id,
+            taken_at_timestamp,
+            canister_version,
+            certified_data,
+            global_timer,
+            WasmChunkStore::from_checkpoint(chunk_store_data, chunk_store_metadata),
+            execution_snapshot,
+        )
+    }
+
+    /// Takes a snapshot of `canister`. All memories are deep copied, so that
+    /// subsequent modifications of the canister do not affect the snapshot.
+    ///
+    /// Returns `None` if the canister has no Wasm module installed.
+    pub fn from_canister(
+        canister: &CanisterState,
+        taken_at_timestamp: Time,
+        fd_factory: Arc<dyn PageAllocatorFileDescriptor>,
+    ) -> Option<Self> {
+        let execution_state = canister.execution_state.as_ref()?;
+        let execution_snapshot = ExecutionStateSnapshot {
+            wasm_binary: execution_state.wasm_binary.binary.clone(),
+            exported_globals: execution_state.exported_globals.clone(),
+            wasm_memory: copy_memory(&execution_state.wasm_memory, Arc::clone(&fd_factory)),
+            stable_memory: copy_memory(&execution_state.stable_memory, Arc::clone(&fd_factory)),
+        };
+        let chunk_store = &canister.system_state.wasm_chunk_store;
+        let chunk_store = WasmChunkStore::from_checkpoint(
+            copy_page_map(chunk_store.page_map(), fd_factory),
+            chunk_store.metadata().clone(),
+        );
+        Some(Self::new(
+            canister.canister_id(),
+            taken_at_timestamp,
+            canister.system_state.canister_version,
+            canister.system_state.certified_data.clone(),
+            canister.system_state.global_timer,
+            chunk_store,
+            execution_snapshot,
+        ))
+    }
+
+    pub fn canister_id(&self) -> CanisterId {
+        self.canister_id
+    }
+
+    pub fn taken_at_timestamp(&self) -> &Time {
+        &self.taken_at_timestamp
+    }
+
+    pub fn canister_version(&self) -> u64 {
+        self.canister_version
+    }
+
+    pub fn certified_data(&self) -> &Vec<u8> {
+        &self.certified_data
+    }
+
+    pub fn global_timer(&self) -> CanisterTimer {
+        self.global_timer
+    }
+
+    pub fn chunk_store(&self) -> &WasmChunkStore {
+        &self.chunk_store
+    }
+
+    pub fn chunk_store_mut(&mut self) -> &mut WasmChunkStore {
+        &mut self.chunk_store
+    }
+
+    pub fn execution_snapshot(&self) -> &ExecutionStateSnapshot {
+        &self.execution_snapshot
+    }
+
+    pub fn execution_snapshot_mut(&mut self) -> &mut ExecutionStateSnapshot {
+        &mut self.execution_snapshot
+    }
+
+    /// Returns a deep copy of the snapshotted Wasm chunk store, suitable for
+    /// being installed into a canister.
+    pub fn restore_chunk_store(
+        &self,
+        fd_factory: Arc<dyn PageAllocatorFileDescriptor>,
+    ) -> WasmChunkStore {
+        WasmChunkStore::from_checkpoint(
+            copy_page_map(self.chunk_store.page_map(), fd_factory),
+            self.chunk_store.metadata().clone(),
+        )
+    }
+
+    /// Returns deep copies of the snapshotted Wasm and stable memories,
+    /// suitable for being installed into a canister.
+    pub fn restore_memories(
+        &self,
+        fd_factory: Arc<dyn PageAllocatorFileDescriptor>,
+    ) -> (Memory, Memory) {
+        (
+            copy_memory(
+                &self.execution_snapshot.wasm_memory,
+                Arc::clone(&fd_factory),
+            ),
+            copy_memory(&self.execution_snapshot.stable_memory, fd_factory),
+        )
+    }
+
+    /// Returns the amount of memory taken by the snapshot, which is charged to
+    /// the canister that owns it.
+    pub fn size(&self) -> NumBytes {
+        // We use 8 bytes per global, same as `ExecutionState::memory_usage()`.
+        let globals_size_bytes = 8 * self.execution_snapshot.exported_globals.len() as u64;
+        num_bytes_try_from(self.execution_snapshot.wasm_memory.size)
+            .expect("could not convert from wasm memory number of pages to bytes")
+            + num_bytes_try_from(self.execution_snapshot.stable_memory.size)
+                .expect("could not convert from stable memory number of pages to bytes")
+            + NumBytes::from(globals_size_bytes)
+            + NumBytes::from(self.execution_snapshot.wasm_binary.len() as u64)
+            + self.chunk_store.memory_usage()
+            + NumBytes::from(self.certified_data.len() as u64)
+    }
+}
+
+/// All the canister snapshots hosted on the subnet, indexed by snapshot ID.
+///
+/// Because snapshot IDs are ordered by canister ID first, the snapshots of
+/// every canister form a contiguous range.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CanisterSnapshots {
+    snapshots: BTreeMap<SnapshotId, CanisterSnapshot>,
+}
+
+impl CanisterSnapshots {
+    pub fn new(snapshots: BTreeMap<SnapshotId, CanisterSnapshot>) -> Self {
+        Self { snapshots }
+    }
+
+    /// Adds a new snapshot, replacing any existing snapshot with the same ID.
+    pub fn push(&mut self, snapshot_id: SnapshotId, snapshot: CanisterSnapshot) {
+        debug_assert_eq!(snapshot_id.canister_id(), snapshot.canister_id());
+        self.snapshots.insert(snapshot_id, snapshot);
+    }
+
+    pub fn get(&self, snapshot_id: &SnapshotId) -> Option<&CanisterSnapshot> {
+        self.snapshots.get(snapshot_id)
+    }
+
+    pub fn get_mut(&mut self, snapshot_id: &SnapshotId) -> Option<&mut CanisterSnapshot> {
+        self.snapshots.get_mut(snapshot_id)
+    }
+
+    /// Removes the snapshot with the given ID, returning it if it existed.
+    pub fn remove(&mut self, snapshot_id: &SnapshotId) -> Option<CanisterSnapshot> {
+        self.snapshots.remove(snapshot_id)
+    }
+
+    /// Iterates over the snapshots of the given canister, in the order in
+    /// which they were taken.
+    pub fn list_snapshots(
+        &self,
+        canister_id: CanisterId,
+    ) -> impl Iterator<Item = (&SnapshotId, &CanisterSnapshot)> {
+        self.snapshots
+            .range(SnapshotId::new(canister_id, 0)..=SnapshotId::new(canister_id, u64::MAX))
+    }
+
+    /// Removes all snapshots of the given canister.
+    pub fn delete_snapshots(&mut self, canister_id: CanisterId) {
+        self.snapshots
+            .retain(|snapshot_id, _| snapshot_id.canister_id() != canister_id);
+    }
+
+    /// Retains only the snapshots whose canister satisfies the predicate.
+    pub fn retain_canisters<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&CanisterId) -> bool,
+    {
+        self.snapshots
+            .retain(|snapshot_id, _| f(&snapshot_id.canister_id()));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&SnapshotId, &CanisterSnapshot)> {
+        self.snapshots.iter()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = &SnapshotId> {
+        self.snapshots.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_test_utilities::types::ids::canister_test_id;
+
+    #[test]
+    fn snapshot_id_roundtrip() {
+        let snapshot_id = SnapshotId::new(canister_test_id(42), 7);
+        let bytes = snapshot_id.to_vec();
+        assert_eq!(&bytes[..LOCAL_ID_SIZE], &7u64.to_be_bytes());
+        assert_eq!(SnapshotId::try_from(bytes.as_slice()), Ok(snapshot_id));
+    }
+
+    #[test]
+    fn snapshot_id_rejects_short_input() {
+        assert!(SnapshotId::try_from(&[0u8; LOCAL_ID_SIZE][..]).is_err());
+    }
+
+    #[test]
+    fn list_snapshots_only_returns_own_snapshots() {
+        let mut snapshots = CanisterSnapshots::default();
+        let time = Time::from_nanos_since_unix_epoch(0);
+        for (canister, local_id) in [(1, 0), (2, 0), (2, 1), (3, 0)] {
+            let canister_id = canister_test_id(canister);
+            snapshots.push(
+                SnapshotId::new(canister_id, local_id),
+                CanisterSnapshot::new(
+                    canister_id,
+                    time,
+                    0,
+                    vec![],
+                    CanisterTimer::Inactive,
+                    WasmChunkStore::new_for_testing(NumBytes::from(0)),
+                    ExecutionStateSnapshot {
+                        wasm_binary: CanisterModule::new(vec![]),
+                        exported_globals: vec![],
+                        wasm_memory: Memory::new_for_testing(),
+                        stable_memory: Memory::new_for_testing(),
+                    },
+                ),
+            );
+        }
+        let listed: Vec<_> = snapshots
+            .list_snapshots(canister_test_id(2))
+            .map(|(id, _)| id.local_id())
+            .collect();
+        assert_eq!(listed, vec![0, 1]);
+
+        snapshots.delete_snapshots(canister_test_id(2));
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots.list_snapshots(canister_test_id(2)).count(), 0);
+    }
+}
@@ -10,7 +10,7 @@ use crate::{InputQueueType, StateError};
 pub use execution_state::{EmbedderCache, ExecutionState, ExportedFunctions, Global};
 use ic_ic00_types::CanisterStatusType;
 use ic_registry_subnet_type::SubnetType;
-use ic_types::batch::TotalQueryStats;
+use ic_types::batch::{TotalMethodStats, TotalQueryStats};
 use ic_types::methods::SystemMethod;
 use ic_types::time::UNIX_EPOCH;
 use ic_types::{
@@ -19,10 +19,10 @@ use ic_types::{
     AccumulatedPriority, CanisterId, ComputeAllocation, ExecutionRound, MemoryAllocation, NumBytes,
     PrincipalId, Time,
 };
-use ic_types::{LongExecutionMode, NumInstructions};
+use ic_types::{LongExecutionMode, NumInstructions, MAX_WASM_MEMORY_IN_BYTES};
 use phantom_newtype::AmountOf;
 pub use queues::{CanisterQueues, DEFAULT_QUEUE_CAPACITY};
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::From;
 use std::sync::Arc;
 use std::time::Duration;
@@ -82,6 +82,12 @@ pub struct SchedulerState {
     /// At the end of an "epoch", each node deterministically aggregates all those partial
     /// query statistics received from consensus blocks and mutates these values.
     pub total_query_stats: TotalQueryStats,
+
+    /// Statistics of update calls, by method name.
+    ///
+    /// Unlike query statistics, these are accounted for directly when the
+    /// update call finishes executing.
+    pub total_update_method_stats: BTreeMap<String, TotalMethodStats>,
 }
 
 impl Default for SchedulerState {
@@ -96,6 +102,7 @@ impl Default for SchedulerState {
             install_code_debit: 0.into(),
             time_of_last_allocation_charge: UNIX_EPOCH,
             total_query_stats: TotalQueryStats::default(),
+            total_update_method_stats: BTreeMap::new(),
         }
     }
 }
@@ -210,6 +217,7 @@ impl CanisterState {
             (None, true) => NextExecution::StartNew,
             (Some(ExecutionTask::Heartbeat), _) => NextExecution::StartNew,
             (Some(ExecutionTask::GlobalTimer), _) => NextExecution::StartNew,
+            (Some(ExecutionTask::OnLowWasmMemory), _) => NextExecution::StartNew,
             (Some(ExecutionTask::AbortedExecution { .. }), _)
             | (Some(ExecutionTask::PausedExecution(..)), _) => NextExecution::ContinueLong,
             (Some(ExecutionTask::AbortedInstallCode { .. }), _)
@@ -229,6 +237,7 @@ impl CanisterState {
             None
             | Some(ExecutionTask::Heartbeat)
             | Some(ExecutionTask::GlobalTimer)
+            | Some(ExecutionTask::OnLowWasmMemory)
             | Some(ExecutionTask::PausedExecution(..))
             | Some(ExecutionTask::PausedInstallCode(..))
             | Some(ExecutionTask::AbortedInstallCode { .. }) => false,
@@ -242,6 +251,7 @@ impl CanisterState {
             None
             | Some(ExecutionTask::Heartbeat)
             | Some(ExecutionTask::GlobalTimer)
+            | Some(ExecutionTask::OnLowWasmMemory)
             | Some(ExecutionTask::PausedInstallCode(..))
             | Some(ExecutionTask::AbortedExecution { .. })
             | Some(ExecutionTask::AbortedInstallCode { .. }) => false,
@@ -255,6 +265,7 @@ impl CanisterState {
             None
             | Some(ExecutionTask::Heartbeat)
             | Some(ExecutionTask::GlobalTimer)
+            | Some(ExecutionTask::OnLowWasmMemory)
             | Some(ExecutionTask::PausedExecution(..))
             | Some(ExecutionTask::AbortedExecution { .. })
             | Some(ExecutionTask::AbortedInstallCode { .. }) => false,
@@ -268,6 +279,7 @@ impl CanisterState {
             None
             | Some(ExecutionTask::Heartbeat)
             | Some(ExecutionTask::GlobalTimer)
+            | Some(ExecutionTask::OnLowWasmMemory)
             | Some(ExecutionTask::PausedExecution(..))
             | Some(ExecutionTask::PausedInstallCode(..))
             | Some(ExecutionTask::AbortedExecution { .. }) => false,
@@ -395,11 +407,12 @@ impl CanisterState {
     /// The amount of memory currently being used by the canister.
     ///
     /// This only includes execution memory (heap, stable, globals, Wasm),
-    /// canister history memory and wasm chunk storage.
+    /// canister history memory, wasm chunk storage and canister snapshots.
     pub fn memory_usage(&self) -> NumBytes {
         self.execution_memory_usage()
             + self.canister_history_memory_usage()
             + self.wasm_chunk_store_memory_usage()
+            + self.snapshots_memory_usage()
     }
 
     /// Returns the amount of execution memory (heap, stable, globals, Wasm)
@@ -433,6 +446,11 @@ impl CanisterState {
         self.system_state.wasm_chunk_store.memory_usage()
     }
 
+    /// Returns the memory taken by the snapshots of this canister in bytes.
+    pub fn snapshots_memory_usage(&self) -> NumBytes {
+        self.system_state.snapshots_memory_usage
+    }
+
     /// Sets the (transient) size in bytes of responses from this canister
     /// routed into streams and not yet garbage collected.
     pub(super) fn set_stream_responses_size_bytes(&mut self, size_bytes: usize) {
@@ -471,6 +489,49 @@ impl CanisterState {
         self.exports_method(&WasmMethod::System(SystemMethod::CanisterGlobalTimer))
     }
 
+    /// Returns true if the canister exports the `canister_on_low_wasm_memory`
+    /// system method.
+    pub fn exports_on_low_wasm_memory_method(&self) -> bool {
+        self.exports_method(&WasmMethod::System(SystemMethod::CanisterOnLowWasmMemory))
+    }
+
+    /// Returns true if the canister has a `wasm_memory_threshold` and the
+    /// free Wasm heap space is at most that threshold. The free space is
+    /// measured against the `wasm_memory_limit` or, if it is not set, the
+    /// maximum size of the Wasm heap: 4 GiB for 32-bit memories and
+    /// `max_wasm64_memory_size` for 64-bit memories.
+    pub fn is_low_on_wasm_memory(&self, max_wasm64_memory_size: NumBytes) -> bool {
+        let threshold = match self.system_state.wasm_memory_threshold {
+            Some(threshold) => threshold,
+            None => return false,
+        };
+        let execution_state = match &self.execution_state {
+            Some(execution_state) => execution_state,
+            None => return false,
+        };
+        let wasm_memory_usage = num_bytes_try_from(execution_state.wasm_memory.size)
+            .unwrap_or_else(|_| NumBytes::new(u64::MAX));
+        let wasm_memory_limit = self.system_state.wasm_memory_limit.unwrap_or_else(|| {
+            if execution_state.is_wasm64 {
+                max_wasm64_memory_size
+            } else {
+                NumBytes::new(MAX_WASM_MEMORY_IN_BYTES)
+            }
+        });
+        wasm_memory_limit
+            .get()
+            .saturating_sub(wasm_memory_usage.get())
+            <= threshold.get()
+    }
+
+    /// Re-evaluates whether the `canister_on_low_wasm_memory` hook is due.
+    pub fn update_on_low_wasm_memory_hook_status(&mut self, max_wasm64_memory_size: NumBytes) {
+        let is_low_on_wasm_memory = self.is_low_on_wasm_memory(max_wasm64_memory_size);
+        self.system_state
+            .on_low_wasm_memory_hook_status
+            .update(is_low_on_wasm_memory);
+    }
+
     /// Returns true if the canister exports the given Wasm method.
     pub fn exports_method(&self, method: &WasmMethod) -> bool {
         match &self.execution_state {
@@ -537,6 +598,7 @@ impl CanisterState {
             ExecutionTask::AbortedInstallCode { .. } => false,
             ExecutionTask::Heartbeat
             | ExecutionTask::GlobalTimer
+            | ExecutionTask::OnLowWasmMemory
             | ExecutionTask::PausedExecution(_)
             | ExecutionTask::PausedInstallCode(_)
             | ExecutionTask::AbortedExecution { .. } => true,
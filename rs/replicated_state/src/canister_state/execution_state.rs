@@ -451,6 +451,13 @@ pub struct ExecutionState {
 
     /// Round-robin across canister method types.
     pub next_scheduled_method: NextScheduledMethod,
+
+    /// Whether the Wasm module declares a 64-bit memory.
+    pub is_wasm64: bool,
+
+    /// The maximum size of the Wasm memory in Wasm pages, if the module
+    /// declares one.
+    pub wasm_memory_max_pages: Option<u64>,
 }
 
 // We have to implement it by hand as embedder_cache can not be compared for
@@ -471,6 +478,8 @@ impl PartialEq for ExecutionState {
             metadata,
             last_executed_round,
             next_scheduled_method,
+            is_wasm64,
+            wasm_memory_max_pages,
         } = rhs;
 
         (
@@ -482,6 +491,8 @@ impl PartialEq for ExecutionState {
             &self.metadata,
             &self.last_executed_round,
             &self.next_scheduled_method,
+            &self.is_wasm64,
+            &self.wasm_memory_max_pages,
         ) == (
             &wasm_binary.binary,
             wasm_memory,
@@ -491,6 +502,8 @@ impl PartialEq for ExecutionState {
             metadata,
             last_executed_round,
             next_scheduled_method,
+            is_wasm64,
+            wasm_memory_max_pages,
         )
     }
 }
@@ -507,6 +520,8 @@ impl ExecutionState {
         stable_memory: Memory,
         exported_globals: Vec<Global>,
         wasm_metadata: WasmMetadata,
+        is_wasm64: bool,
+        wasm_memory_max_pages: Option<u64>,
     ) -> Self {
         Self {
             canister_root,
@@ -519,6 +534,8 @@ impl ExecutionState {
             metadata: wasm_metadata,
             last_executed_round: ExecutionRound::from(0),
             next_scheduled_method: NextScheduledMethod::default(),
+            is_wasm64,
+            wasm_memory_max_pages,
         }
     }
 
@@ -1,9 +1,11 @@
+mod fragments;
 mod queue;
 #[cfg(test)]
 mod tests;
 
 use crate::replicated_state::MR_SYNTHETIC_REJECT_MESSAGE_MAX_LEN;
 use crate::{CanisterState, InputQueueType, NextInputQueue, StateError};
+use fragments::InputFragments;
 use ic_base_types::PrincipalId;
 use ic_error_types::RejectCode;
 use ic_ic00_types::IC_00;
@@ -14,8 +16,8 @@ use ic_protobuf::{
 };
 use ic_types::{
     messages::{
-        CanisterMessage, Ingress, Payload, RejectContext, Request, RequestOrResponse, Response,
-        MAX_RESPONSE_COUNT_BYTES,
+        CanisterMessage, Ingress, MessageFragment, Payload, RejectContext, Request,
+        RequestOrResponse, Response, MAX_RESPONSE_COUNT_BYTES,
     },
     xnet::{QueueId, SessionId},
     CanisterId, CountBytes, Cycles, Time,
@@ -31,6 +33,10 @@ use std::{
 
 pub const DEFAULT_QUEUE_CAPACITY: usize = 500;
 
+/// Message fragments are buffered separately and never enqueued, only the
+/// reassembled messages are.
+const FRAGMENT_IN_QUEUE: &str = "Message fragments are never enqueued";
+
 /// The default lifetime of a request in OutputQueue from which the deadline
 /// is computed as time + REQUEST_LIFETIME.
 pub const REQUEST_LIFETIME: Duration = Duration::from_secs(300);
@@ -115,6 +121,9 @@ pub struct CanisterQueues {
 
     /// Round-robin across ingress and cross-net input queues for pop_input().
     next_input_queue: NextInputQueue,
+
+    /// Fragments of partially received inbound messages.
+    input_fragments: InputFragments,
 }
 
 /// Circular iterator that consumes output queue messages: loops over output
@@ -343,6 +352,9 @@ impl CanisterQueues {
     ) -> Result<(), (StateError, RequestOrResponse)> {
         let sender = msg.sender();
         let input_queue = match msg {
+            RequestOrResponse::Fragment(fragment) => {
+                return self.push_input_fragment(fragment, input_queue_type)
+            }
             RequestOrResponse::Request(_) => {
                 let (input_queue, output_queue) = self.get_or_insert_queues(&sender);
                 if let Err(e) = input_queue.check_has_request_slot() {
@@ -382,6 +394,29 @@ impl CanisterQueues {
         Ok(())
     }
 
+    /// Buffers a `MessageFragment` until the last fragment of its message is
+    /// pushed; then pushes the reassembled message into the input queue.
+    ///
+    /// On failure to push the reassembled message, the latter is returned
+    /// along with the error.
+    fn push_input_fragment(
+        &mut self,
+        fragment: Arc<MessageFragment>,
+        input_queue_type: InputQueueType,
+    ) -> Result<(), (StateError, RequestOrResponse)> {
+        match self.input_fragments.push(fragment) {
+            Ok(None) => Ok(()),
+            Ok(Some(msg)) => self.push_input(msg, input_queue_type),
+            Err((err, fragment)) => Err((err, RequestOrResponse::Fragment(fragment))),
+        }
+    }
+
+    /// Discards any fragments of a partially received message from `sender`,
+    /// releasing the memory reserved for them.
+    pub(super) fn discard_input_fragments(&mut self, sender: &CanisterId) {
+        self.input_fragments.remove(sender);
+    }
+
     /// Pops the next canister-to-canister message from `input_queues`.
     ///
     /// Note: We pop senders from the head of `input_schedule` and insert them
@@ -409,6 +444,7 @@ impl CanisterQueues {
             let msg = match msg {
                 RequestOrResponse::Request(msg) => CanisterMessage::Request(msg),
                 RequestOrResponse::Response(msg) => CanisterMessage::Response(msg),
+                RequestOrResponse::Fragment(_) => unreachable!("{}", FRAGMENT_IN_QUEUE),
             };
 
             return Some(msg);
@@ -429,6 +465,7 @@ impl CanisterQueues {
             let msg = match input_queue.peek().unwrap() {
                 RequestOrResponse::Request(msg) => CanisterMessage::Request(Arc::clone(msg)),
                 RequestOrResponse::Response(msg) => CanisterMessage::Response(Arc::clone(msg)),
+                RequestOrResponse::Fragment(_) => unreachable!("{}", FRAGMENT_IN_QUEUE),
             };
             return Some(msg);
         }
@@ -582,8 +619,14 @@ impl CanisterQueues {
         let oq_stats_delta =
             OutputQueuesStats::stats_delta(&RequestOrResponse::Request(msg.clone()));
 
+        // Best-effort requests should time out no later than their own deadline
+        // (subject to the output queue's deadlines being monotonic).
+        let mut deadline = time + REQUEST_LIFETIME;
+        if msg.is_best_effort() {
+            deadline = deadline.min(Time::from(msg.deadline));
+        }
         output_queue
-            .push_request(msg, time + REQUEST_LIFETIME)
+            .push_request(msg, deadline)
             .expect("cannot fail due to the checks above");
 
         self.input_queues_stats.reserved_slots += 1;
@@ -627,6 +670,7 @@ impl CanisterQueues {
             originator_reply_callback: request.sender_reply_callback,
             refund: request.payment,
             response_payload: Payload::Reject(reject_context),
+            deadline: request.deadline,
         }));
         self.push_input(response, InputQueueType::LocalSubnet)
             .map_err(|(e, _msg)| e)
@@ -762,9 +806,10 @@ impl CanisterQueues {
         &self.input_queues_stats
     }
 
-    /// Returns the memory usage of this `CanisterQueues`.
+    /// Returns the memory usage of this `CanisterQueues`, including buffered
+    /// input message fragments.
     pub fn memory_usage(&self) -> usize {
-        self.memory_usage_stats.memory_usage()
+        self.memory_usage_stats.memory_usage() + self.input_fragments.size_bytes()
     }
 
     /// Returns the total byte size of canister responses across input and
@@ -831,7 +876,10 @@ impl CanisterQueues {
         // Reset all fields to default if we have no messages. This is so that an empty
         // `CanisterQueues` serializes as an empty byte array (and there is no need to
         // persist it explicitly).
-        if self.canister_queues.is_empty() && self.ingress_queue.is_empty() {
+        if self.canister_queues.is_empty()
+            && self.ingress_queue.is_empty()
+            && self.input_fragments.is_empty()
+        {
             // The schedules and stats will already have default (zero) values, only
             // `next_input_queue` must be reset explicitly.
             self.next_input_queue = Default::default();
@@ -935,6 +983,7 @@ impl CanisterQueues {
         let response_count = |msg: &RequestOrResponse| match *msg {
             RequestOrResponse::Request(_) => 0,
             RequestOrResponse::Response(_) => 1,
+            RequestOrResponse::Fragment(_) => unreachable!("{}", FRAGMENT_IN_QUEUE),
         };
         for (q, _) in canister_queues.values() {
             stats.message_count += q.num_messages();
@@ -972,6 +1021,7 @@ impl CanisterQueues {
         let response_size_bytes = |msg: &RequestOrResponse| match *msg {
             RequestOrResponse::Request(_) => 0,
             RequestOrResponse::Response(_) => msg.count_bytes(),
+            RequestOrResponse::Fragment(_) => unreachable!("{}", FRAGMENT_IN_QUEUE),
         };
         // `max(0, msg.count_bytes() - MAX_RESPONSE_COUNT_BYTES)` for requests, 0 for
         // responses.
@@ -980,6 +1030,7 @@ impl CanisterQueues {
                 msg.count_bytes().saturating_sub(MAX_RESPONSE_COUNT_BYTES)
             }
             RequestOrResponse::Response(_) => 0,
+            RequestOrResponse::Fragment(_) => unreachable!("{}", FRAGMENT_IN_QUEUE),
         };
 
         let mut stats = MemoryUsageStats::default();
@@ -1004,7 +1055,8 @@ impl CanisterQueues {
     }
 
     /// Times out requests in `OutputQueues` given a current time, enqueuing a reject response
-    /// for each into the matching `InputQueue`.
+    /// for each guaranteed response request into the matching `InputQueue`. Best-effort
+    /// requests are simply dropped, their callbacks get timed out separately.
     ///
     /// Updating the correct input queues schedule after enqueuing a reject response into a
     /// previously empty queue also requires the full set of local canisters to decide whether
@@ -1020,12 +1072,18 @@ impl CanisterQueues {
         let mut timed_out_requests_count = 0;
         for (canister_id, (input_queue, output_queue)) in self.canister_queues.iter_mut() {
             for request in output_queue.time_out_requests(current_time) {
-                let response = generate_timeout_response(&request);
-
                 // Request was dropped, update stats.
-                let request = RequestOrResponse::Request(request);
-                self.memory_usage_stats -= MemoryUsageStats::stats_delta(QueueOp::Pop, &request);
-                self.output_queues_stats -= OutputQueuesStats::stats_delta(&request);
+                let msg = RequestOrResponse::Request(Arc::clone(&request));
+                self.memory_usage_stats -= MemoryUsageStats::stats_delta(QueueOp::Pop, &msg);
+                self.output_queues_stats -= OutputQueuesStats::stats_delta(&msg);
+                timed_out_requests_count += 1;
+
+                // The reject response for a best-effort request is only produced once
+                // its callback expires, so the reserved slot is retained until then.
+                if request.is_best_effort() {
+                    continue;
+                }
+                let response = generate_timeout_response(&request);
 
                 // Push response, update stats.
                 let iq_stats_delta = InputQueuesStats::stats_delta(QueueOp::Push, &response);
@@ -1042,8 +1100,6 @@ impl CanisterQueues {
                         self.remote_subnet_input_schedule.push_back(*canister_id);
                     }
                 }
-
-                timed_out_requests_count += 1;
             }
         }
 
@@ -1109,6 +1165,7 @@ fn generate_timeout_response(request: &Arc<Request>) -> RequestOrResponse {
             "Request timed out.",
             MR_SYNTHETIC_REJECT_MESSAGE_MAX_LEN,
         )),
+        deadline: request.deadline,
     }))
 }
 
@@ -1151,6 +1208,7 @@ impl From<&CanisterQueues> for pb_queues::CanisterQueues {
                 .iter()
                 .map(|canid| pb_types::CanisterId::from(*canid))
                 .collect(),
+            input_fragments: (&item.input_fragments).into(),
         }
     }
 }
@@ -1221,6 +1279,7 @@ impl TryFrom<pb_queues::CanisterQueues> for CanisterQueues {
             next_input_queue,
             local_subnet_input_schedule,
             remote_subnet_input_schedule,
+            input_fragments: InputFragments::try_from(item.input_fragments)?,
         })
     }
 }
@@ -1257,6 +1316,7 @@ impl InputQueuesStats {
         let response_count = match msg {
             RequestOrResponse::Response(_) => 1,
             RequestOrResponse::Request(_) => 0,
+            RequestOrResponse::Fragment(_) => unreachable!("{}", FRAGMENT_IN_QUEUE),
         };
         // Consume one reservation iff pushing a response.
         let reserved_slots = match (op, msg) {
@@ -1311,6 +1371,7 @@ impl OutputQueuesStats {
         let cycles_message = match msg {
             RequestOrResponse::Response(response) => response.refund,
             RequestOrResponse::Request(request) => request.payment,
+            RequestOrResponse::Fragment(_) => unreachable!("{}", FRAGMENT_IN_QUEUE),
         };
         OutputQueuesStats {
             message_count: 1,
@@ -1388,6 +1449,7 @@ impl MemoryUsageStats {
         match msg {
             RequestOrResponse::Request(req) => Self::request_stats_delta(op, req),
             RequestOrResponse::Response(rep) => Self::response_stats_delta(op, rep),
+            RequestOrResponse::Fragment(_) => unreachable!("{}", FRAGMENT_IN_QUEUE),
         }
     }
 
@@ -1469,21 +1531,27 @@ impl PartialEq for MemoryUsageStats {
 /// an input or output queue.
 ///
 /// Returns:
-///  * `Ok(())` if `msg` is a `Response`, as responses always return memory.
-///  * `Ok(())` if `msg` is a `Request` and `available_memory` is sufficient.
-///  * `Err(required_memory)` if `msg` is a `Request` and `required_memory >
-///    available_memory`.
+///  * `Ok(())` if `msg` is a `Response` or a fragment other than the first, as
+///    responses always return memory and the first fragment reserves memory
+///    for the complete message.
+///  * `Ok(())` if `msg` is a `Request` or the first fragment of a message and
+///    `available_memory` is sufficient. The first fragment of a `Response`
+///    requires memory too, as the response may be larger than its
+///    `MAX_RESPONSE_COUNT_BYTES` reservation.
+///  * `Err(required_memory)` if `msg` is a `Request` or the first fragment of
+///    a message and `required_memory > available_memory`.
 pub fn can_push(msg: &RequestOrResponse, available_memory: i64) -> Result<(), usize> {
-    match msg {
-        RequestOrResponse::Request(req) => {
-            let required = memory_required_to_push_request(req);
-            if required as i64 <= available_memory {
-                Ok(())
-            } else {
-                Err(required)
-            }
+    let required = match msg {
+        RequestOrResponse::Request(req) => memory_required_to_push_request(req),
+        RequestOrResponse::Fragment(fragment) if fragment.index == 0 => {
+            fragments::reserved_bytes(fragment)
         }
-        RequestOrResponse::Response(_) => Ok(()),
+        RequestOrResponse::Response(_) | RequestOrResponse::Fragment(_) => return Ok(()),
+    };
+    if required as i64 <= available_memory {
+        Ok(())
+    } else {
+        Err(required)
     }
 }
 
@@ -0,0 +1,182 @@
+use crate::StateError;
+#[cfg(test)]
+mod tests;
+
+use ic_protobuf::proxy::ProxyDecodeError;
+use ic_protobuf::state::queues::v1 as pb_queues;
+use ic_types::messages::{MessageFragment, RequestOrResponse};
+use ic_types::{CanisterId, CountBytes};
+use std::{
+    collections::BTreeMap,
+    convert::{From, TryFrom, TryInto},
+    sync::Arc,
+};
+
+/// Buffer of `MessageFragment`s of partially received inbound messages, keyed
+/// by sender.
+///
+/// Fragments of a message are always routed consecutively and in order, so at
+/// most one message per sender may be partially received at any given time.
+/// Once its last fragment is pushed, the message is reassembled and removed
+/// from the buffer.
+///
+/// Memory for the complete message is reserved upon its first fragment (see
+/// `reserved_bytes()`), so that the reassembled message is covered even if it
+/// is larger than any other reservation (e.g. a response larger than
+/// `MAX_RESPONSE_COUNT_BYTES`).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(super) struct InputFragments {
+    /// The fragments received so far, in order, from each sender.
+    fragments: BTreeMap<CanisterId, Vec<Arc<MessageFragment>>>,
+
+    /// Running total of the memory reserved for all partially received
+    /// messages.
+    size_bytes: usize,
+}
+
+/// Returns the memory to reserve for the complete message upon receiving its
+/// first fragment: an upper bound of the byte size of all its fragments, as
+/// no fragment is larger than the first one.
+pub(super) fn reserved_bytes(first: &MessageFragment) -> usize {
+    first.count_bytes().saturating_mul(first.count as usize)
+}
+
+/// Returns the memory reserved for the fragments buffered from one sender: the
+/// larger of the reservation made upon the first fragment and the byte size of
+/// the fragments buffered so far.
+fn memory_usage(fragments: &[Arc<MessageFragment>]) -> usize {
+    let buffered_bytes = fragments.iter().map(|f| f.count_bytes()).sum::<usize>();
+    fragments
+        .first()
+        .map_or(0, |first| reserved_bytes(first))
+        .max(buffered_bytes)
+}
+
+impl InputFragments {
+    /// Buffers the given fragment. If it is the last fragment of its message,
+    /// reassembles and returns the message.
+    ///
+    /// A fragment with index zero discards any fragments previously buffered
+    /// from the same sender (belonging to a message whose remaining fragments
+    /// were lost, e.g. rejected due to lack of memory).
+    ///
+    /// Returns `MissingMessageFragments` if the fragment does not directly
+    /// follow the previously buffered fragment from the same sender; and
+    /// `InvalidMessageFragments` if its `count` is zero, its index is out of
+    /// range or its `count` or header differ from those of the first buffered
+    /// fragment; or if the message cannot be reassembled. Any previously
+    /// buffered fragments from the same sender are also discarded, as their
+    /// message can no longer be reassembled.
+    pub(super) fn push(
+        &mut self,
+        fragment: Arc<MessageFragment>,
+    ) -> Result<Option<RequestOrResponse>, (StateError, Arc<MessageFragment>)> {
+        let sender = fragment.header.sender();
+        if fragment.index == 0 {
+            self.remove(&sender);
+        }
+
+        let buffered = self.fragments.entry(sender).or_default();
+        let expected_index = buffered.last().map_or(0, |last| last.index + 1);
+        let err = if fragment.index != expected_index {
+            Some(StateError::MissingMessageFragments {
+                sender,
+                expected_index,
+                index: fragment.index,
+            })
+        } else if fragment.count == 0 || fragment.index >= fragment.count {
+            Some(StateError::InvalidMessageFragments {
+                sender,
+                err: format!(
+                    "fragment index {} out of range 0..{}",
+                    fragment.index, fragment.count
+                ),
+            })
+        } else if let Some(first) = buffered
+            .first()
+            .filter(|first| first.count != fragment.count || first.header != fragment.header)
+        {
+            Some(StateError::InvalidMessageFragments {
+                sender,
+                err: format!(
+                    "fragment {} of {} does not match fragment 0 of {}",
+                    fragment.index, fragment.count, first.count
+                ),
+            })
+        } else {
+            None
+        };
+        if let Some(err) = err {
+            self.remove(&sender);
+            return Err((err, fragment));
+        }
+
+        self.size_bytes -= memory_usage(buffered);
+        let is_last = fragment.is_last();
+        buffered.push(Arc::clone(&fragment));
+        self.size_bytes += memory_usage(buffered);
+        if !is_last {
+            return Ok(None);
+        }
+
+        let fragments = self.remove(&sender);
+        MessageFragment::reassemble(fragments.iter().map(|f| f.as_ref()))
+            .map(Some)
+            .map_err(|err| {
+                (
+                    StateError::InvalidMessageFragments { sender, err },
+                    fragment,
+                )
+            })
+    }
+
+    /// Returns `true` if there are no buffered fragments.
+    pub(super) fn is_empty(&self) -> bool {
+        self.fragments.is_empty()
+    }
+
+    /// Returns the memory reserved for all partially received messages.
+    pub(super) fn size_bytes(&self) -> usize {
+        self.size_bytes
+    }
+
+    /// Removes and returns all fragments buffered from `sender`.
+    pub(super) fn remove(&mut self, sender: &CanisterId) -> Vec<Arc<MessageFragment>> {
+        let fragments = self.fragments.remove(sender).unwrap_or_default();
+        self.size_bytes -= memory_usage(&fragments);
+        fragments
+    }
+}
+
+impl From<&InputFragments> for Vec<pb_queues::MessageFragment> {
+    fn from(item: &InputFragments) -> Self {
+        item.fragments
+            .values()
+            .flatten()
+            .map(|fragment| fragment.as_ref().into())
+            .collect()
+    }
+}
+
+impl TryFrom<Vec<pb_queues::MessageFragment>> for InputFragments {
+    type Error = ProxyDecodeError;
+
+    fn try_from(item: Vec<pb_queues::MessageFragment>) -> Result<Self, Self::Error> {
+        let mut input_fragments = InputFragments::default();
+        for fragment in item {
+            let fragment: MessageFragment = fragment.try_into()?;
+            match input_fragments.push(Arc::new(fragment)) {
+                Ok(None) => {}
+                Ok(Some(_)) => {
+                    return Err(ProxyDecodeError::Other(
+                        "InputFragments: complete message in fragment buffer".to_string(),
+                    ))
+                }
+                Err((err, _)) => {
+                    return Err(ProxyDecodeError::Other(format!("InputFragments: {}", err)))
+                }
+            }
+        }
+        Ok(input_fragments)
+    }
+}
@@ -0,0 +1,200 @@
+use super::*;
+use assert_matches::assert_matches;
+use ic_test_utilities::types::{
+    ids::canister_test_id,
+    messages::{RequestBuilder, ResponseBuilder},
+};
+use ic_types::messages::Payload;
+
+fn request_fragments(
+    sender: u64,
+    payload_size: usize,
+    fragment_size: usize,
+) -> Vec<RequestOrResponse> {
+    RequestOrResponse::from(
+        RequestBuilder::default()
+            .sender(canister_test_id(sender))
+            .method_payload(vec![13; payload_size])
+            .build(),
+    )
+    .into_fragments(fragment_size)
+}
+
+fn as_fragment(msg: RequestOrResponse) -> Arc<MessageFragment> {
+    match msg {
+        RequestOrResponse::Fragment(fragment) => fragment,
+        msg => panic!("Expected a fragment, got {:?}", msg),
+    }
+}
+
+#[test]
+fn push_reassembles_request() {
+    let request: RequestOrResponse = RequestBuilder::default()
+        .method_payload(vec![13; 10])
+        .build()
+        .into();
+    let fragments = request.clone().into_fragments(3);
+    assert_eq!(4, fragments.len());
+
+    // Memory for all 4 fragments is reserved upon the first one.
+    let reserved = reserved_bytes(&as_fragment(fragments[0].clone()));
+    assert_eq!(4 * fragments[0].count_bytes(), reserved);
+
+    let mut input_fragments = InputFragments::default();
+    for fragment in fragments.iter().take(3).cloned().map(as_fragment) {
+        assert_eq!(Ok(None), input_fragments.push(fragment));
+        assert_eq!(reserved, input_fragments.size_bytes());
+    }
+
+    let last = as_fragment(fragments.last().unwrap().clone());
+    assert_eq!(Ok(Some(request)), input_fragments.push(last));
+    assert!(input_fragments.is_empty());
+    assert_eq!(0, input_fragments.size_bytes());
+}
+
+#[test]
+fn push_reassembles_response() {
+    let response: RequestOrResponse = ResponseBuilder::default()
+        .response_payload(Payload::Data(vec![17; 7]))
+        .build()
+        .into();
+
+    let mut input_fragments = InputFragments::default();
+    let mut result = None;
+    for fragment in response.clone().into_fragments(2) {
+        result = input_fragments.push(as_fragment(fragment)).unwrap();
+    }
+    assert_eq!(Some(response), result);
+    assert_eq!(InputFragments::default(), input_fragments);
+}
+
+#[test]
+fn push_interleaved_senders() {
+    let fragments_1 = request_fragments(1, 4, 2);
+    let fragments_2 = request_fragments(2, 4, 2);
+
+    let mut input_fragments = InputFragments::default();
+    for (fragment_1, fragment_2) in fragments_1.into_iter().zip(fragments_2.into_iter()) {
+        let is_last = as_fragment(fragment_1.clone()).is_last();
+        let msg_1 = input_fragments.push(as_fragment(fragment_1)).unwrap();
+        let msg_2 = input_fragments.push(as_fragment(fragment_2)).unwrap();
+        assert_eq!(is_last, msg_1.is_some());
+        assert_eq!(is_last, msg_2.is_some());
+    }
+    assert!(input_fragments.is_empty());
+}
+
+#[test]
+fn push_missing_fragment_fails_and_discards_buffered_fragments() {
+    let fragments = request_fragments(1, 6, 2);
+
+    let mut input_fragments = InputFragments::default();
+    input_fragments
+        .push(as_fragment(fragments[0].clone()))
+        .unwrap();
+
+    // Skipping fragment 1 fails and discards fragment 0.
+    let (err, _) = input_fragments
+        .push(as_fragment(fragments[2].clone()))
+        .unwrap_err();
+    assert_eq!(
+        StateError::MissingMessageFragments {
+            sender: canister_test_id(1),
+            expected_index: 1,
+            index: 2,
+        },
+        err
+    );
+    assert!(input_fragments.is_empty());
+    assert_eq!(0, input_fragments.size_bytes());
+}
+
+#[test]
+fn push_fragment_with_zero_count_fails() {
+    let fragment = as_fragment(request_fragments(1, 6, 2)[0].clone());
+    let fragment = Arc::new(MessageFragment {
+        count: 0,
+        ..fragment.as_ref().clone()
+    });
+
+    let mut input_fragments = InputFragments::default();
+    let (err, _) = input_fragments.push(fragment).unwrap_err();
+    assert_matches!(
+        err,
+        StateError::InvalidMessageFragments { sender, .. } if sender == canister_test_id(1)
+    );
+    assert!(input_fragments.is_empty());
+    assert_eq!(0, input_fragments.size_bytes());
+}
+
+#[test]
+fn push_fragment_with_different_count_fails_and_discards_buffered_fragments() {
+    let fragments = request_fragments(1, 6, 2);
+    let fragment_1 = as_fragment(fragments[1].clone());
+
+    let mut input_fragments = InputFragments::default();
+    input_fragments
+        .push(as_fragment(fragments[0].clone()))
+        .unwrap();
+
+    // Fragment 1 claims the message has only 2 fragments (instead of 3), so
+    // it would otherwise be the last one.
+    let (err, _) = input_fragments
+        .push(Arc::new(MessageFragment {
+            count: 2,
+            ..fragment_1.as_ref().clone()
+        }))
+        .unwrap_err();
+    assert_matches!(
+        err,
+        StateError::InvalidMessageFragments { sender, .. } if sender == canister_test_id(1)
+    );
+    assert!(input_fragments.is_empty());
+    assert_eq!(0, input_fragments.size_bytes());
+
+    // The remaining fragments of the original message are rejected too.
+    let (err, _) = input_fragments.push(fragment_1).unwrap_err();
+    assert_matches!(err, StateError::MissingMessageFragments { .. });
+    assert!(input_fragments.is_empty());
+}
+
+#[test]
+fn push_first_fragment_discards_stale_fragments() {
+    let stale = request_fragments(1, 6, 2);
+    let request: RequestOrResponse = RequestBuilder::default()
+        .sender(canister_test_id(1))
+        .method_payload(vec![42; 4])
+        .build()
+        .into();
+
+    let mut input_fragments = InputFragments::default();
+    input_fragments.push(as_fragment(stale[0].clone())).unwrap();
+
+    let mut result = None;
+    for fragment in request.clone().into_fragments(2) {
+        result = input_fragments.push(as_fragment(fragment)).unwrap();
+    }
+    assert_eq!(Some(request), result);
+    assert!(input_fragments.is_empty());
+    assert_eq!(0, input_fragments.size_bytes());
+}
+
+#[test]
+fn proto_round_trip() {
+    let fragments = request_fragments(1, 6, 2);
+
+    let mut input_fragments = InputFragments::default();
+    input_fragments
+        .push(as_fragment(fragments[0].clone()))
+        .unwrap();
+    input_fragments
+        .push(as_fragment(fragments[1].clone()))
+        .unwrap();
+
+    let proto: Vec<pb_queues::MessageFragment> = (&input_fragments).into();
+    assert_eq!(2, proto.len());
+    assert_eq!(
+        input_fragments,
+        InputFragments::try_from(proto).expect("failed to decode fragments")
+    );
+}
@@ -358,6 +358,10 @@ impl InputQueue {
                 .queue
                 .push_response(response)
                 .map_err(|(err, response)| (err, RequestOrResponse::Response(response))),
+            RequestOrResponse::Fragment(_) => Err((
+                StateError::InvariantBroken("Message fragments cannot be enqueued".to_string()),
+                msg,
+            )),
         }
     }
 
@@ -374,6 +374,7 @@ prop_compose! {
                     q.reserve_slot().unwrap();
                     q.push_response(response);
                 }
+                RequestOrResponse::Fragment(_) => unreachable!(),
             }
         }
         q.check_invariants();
@@ -463,7 +464,7 @@ proptest! {
                 Some(RequestOrResponse::Request(_)) => {
                     prop_assert_eq!(ref_q.pop(), timed_out_requests.pop_front());
                 }
-                None => unreachable!(),
+                Some(RequestOrResponse::Fragment(_)) | None => unreachable!(),
             }
         }
 
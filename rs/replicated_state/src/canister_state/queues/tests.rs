@@ -16,7 +16,7 @@ use ic_test_utilities::{
     },
 };
 use ic_types::{
-    messages::{CallbackId, CanisterMessage},
+    messages::{CallbackId, CanisterMessage, NO_DEADLINE},
     time::expiry_time_from_now,
 };
 use maplit::btreemap;
@@ -182,6 +182,98 @@ fn can_push_input_response_after_output_request() {
     queues.push_input_response().unwrap();
 }
 
+/// Request fragments are buffered until the last fragment is pushed; then the
+/// reassembled request is enqueued. Memory for the complete request is
+/// reserved upon the first fragment.
+#[test]
+fn push_input_request_fragments() {
+    let mut queues = CanisterQueuesFixture::new();
+    let request: RequestOrResponse = RequestBuilder::default()
+        .sender(queues.other)
+        .receiver(queues.this)
+        .method_payload(vec![13; 100])
+        .build()
+        .into();
+    let fragments = request.clone().into_fragments(40);
+    assert_eq!(3, fragments.len());
+
+    let reserved_bytes = 3 * fragments[0].count_bytes();
+    assert_eq!(Err(reserved_bytes), can_push(&fragments[0], 0));
+    assert_eq!(Ok(()), can_push(&fragments[1], 0));
+    for fragment in fragments.iter().take(2) {
+        queues
+            .queues
+            .push_input(fragment.clone(), InputQueueType::RemoteSubnet)
+            .unwrap();
+        assert!(!queues.queues.has_input());
+        assert_eq!(reserved_bytes, queues.queues.memory_usage());
+    }
+
+    queues
+        .queues
+        .push_input(fragments[2].clone(), InputQueueType::RemoteSubnet)
+        .unwrap();
+    assert_eq!(
+        memory_required_to_push_request(&RequestBuilder::default().build()),
+        queues.queues.memory_usage()
+    );
+    match (queues.pop_input(), request) {
+        (Some(CanisterMessage::Request(actual)), RequestOrResponse::Request(expected)) => {
+            assert_eq!(expected, actual)
+        }
+        (msg, _) => panic!("unexpected message popped: {:?}", msg),
+    }
+}
+
+/// The first fragment of a response requires memory for the complete response,
+/// as the latter may be larger than its `MAX_RESPONSE_COUNT_BYTES` reservation.
+#[test]
+fn can_push_first_response_fragment_requires_memory() {
+    let fragments = RequestOrResponse::from(
+        ResponseBuilder::default()
+            .response_payload(Payload::Data(vec![13; 100]))
+            .build(),
+    )
+    .into_fragments(40);
+    assert_eq!(3, fragments.len());
+
+    let reserved_bytes = 3 * fragments[0].count_bytes();
+    assert_eq!(Err(reserved_bytes), can_push(&fragments[0], 0));
+    assert_eq!(Ok(()), can_push(&fragments[0], reserved_bytes as i64));
+    assert_eq!(Ok(()), can_push(&fragments[1], 0));
+    assert_eq!(Ok(()), can_push(&fragments[2], 0));
+}
+
+/// An out-of-sequence fragment is rejected and the fragments buffered so far
+/// are dropped.
+#[test]
+fn push_input_out_of_sequence_fragment_fails() {
+    let mut queues = CanisterQueuesFixture::new();
+    let fragments = RequestOrResponse::from(
+        RequestBuilder::default()
+            .sender(queues.other)
+            .receiver(queues.this)
+            .method_payload(vec![13; 100])
+            .build(),
+    )
+    .into_fragments(40);
+
+    queues
+        .queues
+        .push_input(fragments[0].clone(), InputQueueType::RemoteSubnet)
+        .unwrap();
+    let (err, msg) = queues
+        .queues
+        .push_input(fragments[2].clone(), InputQueueType::RemoteSubnet)
+        .unwrap_err();
+    assert_matches!(err, StateError::MissingMessageFragments { .. });
+    assert_eq!(fragments[2], msg);
+    assert_eq!(0, queues.queues.memory_usage());
+
+    queues.queues.garbage_collect();
+    assert_eq!(CanisterQueues::default(), queues.queues);
+}
+
 /// Check `available_output_request_slots` doesn't count input requests and
 /// output reservations and responses.
 #[test]
@@ -820,6 +912,17 @@ fn encode_roundtrip() {
         .pop_canister_input(InputQueueType::RemoteSubnet)
         .unwrap();
     queues.push_ingress(IngressBuilder::default().receiver(this).build());
+    let fragment = RequestOrResponse::from(
+        RequestBuilder::default()
+            .sender(other)
+            .method_payload(vec![13; 10])
+            .build(),
+    )
+    .into_fragments(4)
+    .remove(0);
+    queues
+        .push_input(fragment, InputQueueType::RemoteSubnet)
+        .unwrap();
 
     let encoded: pb_queues::CanisterQueues = (&queues).into();
     let decoded = encoded.try_into().unwrap();
@@ -1810,6 +1913,7 @@ fn time_out_requests_pushes_correct_reject_responses() {
                     method_name: "No-Op".to_string(),
                     method_payload: vec![],
                     metadata: None,
+                    deadline: NO_DEADLINE,
                 }),
                 deadline,
             )
@@ -1865,7 +1969,8 @@ fn time_out_requests_pushes_correct_reject_responses() {
                     RejectCode::SysTransient,
                     "Request timed out.",
                     MR_SYNTHETIC_REJECT_MESSAGE_MAX_LEN
-                ))
+                )),
+                deadline: NO_DEADLINE,
             }),
             *reject_response,
         );
@@ -10,7 +10,8 @@ use crate::page_map::PageAllocatorFileDescriptor;
 use crate::{CanisterQueues, CanisterState, InputQueueType, PageMap, StateError};
 pub use call_context_manager::{CallContext, CallContextAction, CallContextManager, CallOrigin};
 use ic_base_types::NumSeconds;
-use ic_ic00_types::{CanisterChange, CanisterChangeDetails, CanisterChangeOrigin};
+use ic_error_types::RejectCode;
+use ic_ic00_types::{CanisterChange, CanisterChangeDetails, CanisterChangeOrigin, LogVisibility};
 use ic_logger::{error, ReplicaLogger};
 use ic_protobuf::{
     proxy::{try_from_option_field, ProxyDecodeError},
@@ -19,12 +20,14 @@ use ic_protobuf::{
 
 use ic_registry_subnet_type::SubnetType;
 use ic_types::{
+    canister_log::CanisterLog,
     messages::{
-        CanisterCall, CanisterMessage, CanisterMessageOrTask, CanisterTask, Ingress, RejectContext,
-        Request, RequestOrResponse, Response, StopCanisterContext,
+        CanisterCall, CanisterMessage, CanisterMessageOrTask, CanisterTask, Ingress, Payload,
+        RejectContext, Request, RequestOrResponse, Response, StopCanisterContext,
     },
     nominal_cycles::NominalCycles,
-    CanisterId, CanisterTimer, Cycles, MemoryAllocation, NumBytes, PrincipalId, Time,
+    CanisterId, CanisterTimer, CanisterTimers, CoarseTime, Cycles, MemoryAllocation, NumBytes,
+    PrincipalId, Time,
 };
 use lazy_static::lazy_static;
 use maplit::btreeset;
@@ -323,6 +326,10 @@ pub struct SystemState {
     /// Canister global timer.
     pub global_timer: CanisterTimer,
 
+    /// Named one-off timers set via `ic0.timer_set`, delivered through
+    /// `canister_global_timer` with the timer ID as argument.
+    pub timers: CanisterTimers,
+
     /// Canister version.
     pub canister_version: u64,
 
@@ -331,6 +338,36 @@ pub struct SystemState {
 
     /// Store of Wasm chunks to support installation of large Wasm modules.
     pub wasm_chunk_store: WasmChunkStore,
+
+    /// Total size of all the snapshots of this canister. The snapshots
+    /// themselves are kept in `ReplicatedState::canister_snapshots`.
+    pub snapshots_memory_usage: NumBytes,
+
+    /// The local ID to be used for the next snapshot of this canister.
+    pub next_snapshot_id: u64,
+
+    /// Who is allowed to fetch the canister log.
+    pub log_visibility: LogVisibility,
+
+    /// Log records produced by `ic0.debug_print` and traps.
+    pub canister_log: CanisterLog,
+
+    /// The user-specified soft limit on the size of the Wasm heap. Growing
+    /// the heap beyond it traps, except during `install_code` and
+    /// `canister_inspect_message`.
+    pub wasm_memory_limit: Option<NumBytes>,
+
+    /// The user-specified amount of free Wasm heap space below which the
+    /// `canister_on_low_wasm_memory` hook is executed.
+    pub wasm_memory_threshold: Option<NumBytes>,
+
+    /// Whether the `canister_on_low_wasm_memory` hook is due or has already
+    /// been executed since the heap last crossed `wasm_memory_threshold`.
+    pub on_low_wasm_memory_hook_status: OnLowWasmMemoryHookStatus,
+
+    /// Environment variables set through the canister settings and readable
+    /// by the canister through the `ic0.env_var_*` system calls.
+    pub environment_variables: BTreeMap<String, String>,
 }
 
 /// A wrapper around the different canister statuses.
@@ -430,6 +467,10 @@ pub enum ExecutionTask {
     /// The task exists only within an execution round, it never gets serialized.
     GlobalTimer,
 
+    /// Canister `canister_on_low_wasm_memory` hook task.
+    /// The task exists only within an execution round, it never gets serialized.
+    OnLowWasmMemory,
+
     // A paused execution task exists only within an epoch (between
     // checkpoints). It is never serialized, and it turns into `AbortedExecution`
     // before the checkpoint or when there are too many long-running executions.
@@ -466,11 +507,67 @@ pub enum ExecutionTask {
     },
 }
 
+/// Tracks the execution of the `canister_on_low_wasm_memory` hook, so that it
+/// runs once each time the free Wasm heap space drops below the canister's
+/// `wasm_memory_threshold`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OnLowWasmMemoryHookStatus {
+    /// The free Wasm heap space is above the threshold (or no threshold is
+    /// set).
+    #[default]
+    ConditionNotSatisfied,
+    /// The free Wasm heap space is below the threshold and the hook has not
+    /// been executed yet.
+    Ready,
+    /// The hook has been executed and won't run again until the free Wasm
+    /// heap space has been above the threshold in between.
+    Executed,
+}
+
+impl OnLowWasmMemoryHookStatus {
+    /// Updates the status given whether the free Wasm heap space is currently
+    /// below the threshold.
+    pub fn update(&mut self, is_condition_satisfied: bool) {
+        *self = match (*self, is_condition_satisfied) {
+            (_, false) => Self::ConditionNotSatisfied,
+            (Self::ConditionNotSatisfied, true) => Self::Ready,
+            (status @ (Self::Ready | Self::Executed), true) => status,
+        };
+    }
+
+    /// Returns true if the hook should be executed.
+    pub fn is_ready(&self) -> bool {
+        *self == Self::Ready
+    }
+}
+
+impl From<&OnLowWasmMemoryHookStatus> for pb::OnLowWasmMemoryHookStatus {
+    fn from(item: &OnLowWasmMemoryHookStatus) -> Self {
+        match item {
+            OnLowWasmMemoryHookStatus::ConditionNotSatisfied => Self::ConditionNotSatisfied,
+            OnLowWasmMemoryHookStatus::Ready => Self::Ready,
+            OnLowWasmMemoryHookStatus::Executed => Self::Executed,
+        }
+    }
+}
+
+impl From<pb::OnLowWasmMemoryHookStatus> for OnLowWasmMemoryHookStatus {
+    fn from(item: pb::OnLowWasmMemoryHookStatus) -> Self {
+        match item {
+            pb::OnLowWasmMemoryHookStatus::Unspecified
+            | pb::OnLowWasmMemoryHookStatus::ConditionNotSatisfied => Self::ConditionNotSatisfied,
+            pb::OnLowWasmMemoryHookStatus::Ready => Self::Ready,
+            pb::OnLowWasmMemoryHookStatus::Executed => Self::Executed,
+        }
+    }
+}
+
 impl From<&ExecutionTask> for pb::ExecutionTask {
     fn from(item: &ExecutionTask) -> Self {
         match item {
             ExecutionTask::Heartbeat
             | ExecutionTask::GlobalTimer
+            | ExecutionTask::OnLowWasmMemory
             | ExecutionTask::PausedExecution(_)
             | ExecutionTask::PausedInstallCode(_) => {
                 panic!("Attempt to serialize ephemeral task: {:?}.", item);
@@ -498,6 +595,9 @@ impl From<&ExecutionTask> for pb::ExecutionTask {
                     CanisterMessageOrTask::Task(CanisterTask::GlobalTimer) => {
                         PbInput::Task(PbCanisterTask::Timer as i32)
                     }
+                    CanisterMessageOrTask::Task(CanisterTask::OnLowWasmMemory) => {
+                        PbInput::Task(PbCanisterTask::OnLowWasmMemory as i32)
+                    }
                 };
                 Self {
                     task: Some(pb::execution_task::Task::AbortedExecution(
@@ -573,6 +673,7 @@ impl TryFrom<pb::ExecutionTask> for ExecutionTask {
                             }
                             PbCanisterTask::Heartbeat => CanisterTask::Heartbeat,
                             PbCanisterTask::Timer => CanisterTask::GlobalTimer,
+                            PbCanisterTask::OnLowWasmMemory => CanisterTask::OnLowWasmMemory,
                         };
                         CanisterMessageOrTask::Task(task)
                     }
@@ -699,9 +800,18 @@ impl SystemState {
             canister_metrics: CanisterMetrics::default(),
             task_queue: Default::default(),
             global_timer: CanisterTimer::Inactive,
+            timers: CanisterTimers::default(),
             canister_version: 0,
             canister_history: CanisterHistory::default(),
             wasm_chunk_store,
+            snapshots_memory_usage: NumBytes::from(0),
+            next_snapshot_id: 0,
+            log_visibility: LogVisibility::default(),
+            canister_log: CanisterLog::default(),
+            wasm_memory_limit: None,
+            wasm_memory_threshold: None,
+            on_low_wasm_memory_hook_status: OnLowWasmMemoryHookStatus::default(),
+            environment_variables: BTreeMap::new(),
         }
     }
 
@@ -741,10 +851,19 @@ impl SystemState {
         reserved_balance_limit: Option<Cycles>,
         task_queue: VecDeque<ExecutionTask>,
         global_timer: CanisterTimer,
+        timers: CanisterTimers,
         canister_version: u64,
         canister_history: CanisterHistory,
         wasm_chunk_store_data: PageMap,
         wasm_chunk_store_metadata: WasmChunkStoreMetadata,
+        snapshots_memory_usage: NumBytes,
+        next_snapshot_id: u64,
+        log_visibility: LogVisibility,
+        canister_log: CanisterLog,
+        wasm_memory_limit: Option<NumBytes>,
+        wasm_memory_threshold: Option<NumBytes>,
+        on_low_wasm_memory_hook_status: OnLowWasmMemoryHookStatus,
+        environment_variables: BTreeMap<String, String>,
     ) -> Self {
         Self {
             controllers,
@@ -761,12 +880,21 @@ impl SystemState {
             reserved_balance_limit,
             task_queue,
             global_timer,
+            timers,
             canister_version,
             canister_history,
             wasm_chunk_store: WasmChunkStore::from_checkpoint(
                 wasm_chunk_store_data,
                 wasm_chunk_store_metadata,
             ),
+            snapshots_memory_usage,
+            next_snapshot_id,
+            log_visibility,
+            canister_log,
+            wasm_memory_limit,
+            wasm_memory_threshold,
+            on_low_wasm_memory_hook_status,
+            environment_variables,
         }
     }
 
@@ -1010,8 +1138,13 @@ impl SystemState {
             "Expected `Request` to have been sent from canister ID {}, but instead got {}",
             self.canister_id, request.sender
         );
+        let callback_id = request.sender_reply_callback;
         self.queues
-            .reject_subnet_output_request(request, reject_context, subnet_ids)
+            .reject_subnet_output_request(request, reject_context, subnet_ids)?;
+        if let Some(call_context_manager) = self.call_context_manager_mut() {
+            call_context_manager.on_response_enqueued(callback_id);
+        }
+        Ok(())
     }
 
     /// Returns the number of output requests that can be pushed onto the queue
@@ -1052,6 +1185,9 @@ impl SystemState {
 
     /// Pushes a `RequestOrResponse` into the induction pool.
     ///
+    /// `MessageFragment`s are buffered until the last fragment of a message is
+    /// pushed, at which point the reassembled message is inducted.
+    ///
     /// If the message is a `Request`, reserves a slot in the corresponding
     /// output queue for the eventual response; and the maximum memory size and
     /// cycles cost for sending the `Response` back. If it is a `Response`,
@@ -1074,7 +1210,11 @@ impl SystemState {
     ///  * `OutOfMemory` if the necessary memory reservation is larger than subnet
     ///     available memory.
     ///  * `CanisterStopping` if the canister is stopping and inducting a
-    ///    `Request` was attempted.
+    ///    `Request` (or a fragment thereof) was attempted.
+    ///  * `MissingMessageFragments` if a `MessageFragment` does not directly
+    ///    follow the previously inducted fragment from the same sender.
+    ///  * `InvalidMessageFragments` if a `MessageFragment` is inconsistent with
+    ///    the previously inducted fragments from the same sender.
     ///  * `CanisterStopped` if the canister is stopped.
     ///  * `NonMatchingResponse` if the callback is not found or the respondent
     ///    does not match.
@@ -1099,8 +1239,8 @@ impl SystemState {
                 Err((StateError::CanisterStopped(self.canister_id()), msg))
             }
 
-            // Requests (only) are rejected while stopping.
-            (RequestOrResponse::Request(_), CanisterStatus::Stopping { .. }) => {
+            // Requests (and request fragments) only are rejected while stopping.
+            (_, CanisterStatus::Stopping { .. }) if msg.is_request() => {
                 Err((StateError::CanisterStopping(self.canister_id()), msg))
             }
 
@@ -1112,28 +1252,63 @@ impl SystemState {
                 },
             )
             | (
-                RequestOrResponse::Response(_),
+                _,
                 CanisterStatus::Stopping {
                     call_context_manager,
                     ..
                 },
             ) => {
-                if let RequestOrResponse::Response(response) = &msg {
-                    call_context_manager
-                        .validate_response(response)
-                        .map_err(|err| (err, msg.clone()))?;
-                }
+                let response_callback_id = match &msg {
+                    RequestOrResponse::Response(response) => {
+                        call_context_manager
+                            .validate_response(response)
+                            .map_err(|err| (err, msg.clone()))?;
+                        Some(response.originator_reply_callback)
+                    }
+                    RequestOrResponse::Request(_) => None,
+                    RequestOrResponse::Fragment(fragment) => match &fragment.header {
+                        // Every fragment of a response is validated against
+                        // the callback, but the response is only enqueued
+                        // (reassembled) upon the last fragment.
+                        RequestOrResponse::Response(response) => {
+                            call_context_manager
+                                .validate_response(response)
+                                .map_err(|err| (err, msg.clone()))?;
+                            fragment
+                                .is_last()
+                                .then_some(response.originator_reply_callback)
+                        }
+                        _ => None,
+                    },
+                };
                 push_input(
                     &mut self.queues,
                     msg,
                     subnet_available_memory,
                     own_subnet_type,
                     input_queue_type,
-                )
+                )?;
+                if let (Some(callback_id), Some(call_context_manager)) =
+                    (response_callback_id, self.call_context_manager_mut())
+                {
+                    call_context_manager.on_response_enqueued(callback_id);
+                }
+                Ok(())
             }
         }
     }
 
+    /// Discards any fragments of a partially received message from `sender`.
+    ///
+    /// Updates `subnet_available_memory` to reflect the released memory.
+    pub(crate) fn discard_input_fragments(
+        &mut self,
+        sender: &CanisterId,
+        subnet_available_memory: &mut i64,
+    ) {
+        discard_input_fragments(&mut self.queues, sender, subnet_available_memory)
+    }
+
     /// Pushes an ingress message into the induction pool.
     pub(crate) fn push_ingress(&mut self, msg: Ingress) {
         self.queues.push_ingress(msg)
@@ -1263,6 +1438,19 @@ impl SystemState {
                 return;
             }
 
+            // Best-effort responses to callbacks that already had a response enqueued
+            // (i.e. expired callbacks) are left to the stream builder to drop.
+            let best_effort_callback_id = match msg {
+                RequestOrResponse::Response(response) if response.is_best_effort() => {
+                    match self.call_context_manager() {
+                        Some(ccm) if ccm.validate_response(response).is_ok() => {}
+                        _ => return,
+                    }
+                    Some(response.originator_reply_callback)
+                }
+                _ => None,
+            };
+
             // Attempt inducting `msg`. May fail if the input queue is full.
             if self
                 .queues
@@ -1271,6 +1459,11 @@ impl SystemState {
             {
                 return;
             }
+            if let (Some(callback_id), Some(call_context_manager)) =
+                (best_effort_callback_id, self.call_context_manager_mut())
+            {
+                call_context_manager.on_response_enqueued(callback_id);
+            }
 
             // Adjust `subnet_available_memory` by `memory_usage_before - memory_usage_after`.
             // Defer the accounting to `CanisterQueues`, to avoid duplication or divergence.
@@ -1291,6 +1484,13 @@ impl SystemState {
         self.queues.has_expired_deadlines(current_time)
     }
 
+    /// Queries whether any best-effort callbacks have expired deadlines.
+    pub fn has_expired_callbacks(&self, current_time: Time) -> bool {
+        self.call_context_manager().map_or(false, |ccm| {
+            ccm.has_expired_callbacks(CoarseTime::floor(current_time))
+        })
+    }
+
     /// Times out requests in the `OutputQueues` of `self.queues`. Returns the number of requests
     /// that were timed out.
     ///
@@ -1305,6 +1505,67 @@ impl SystemState {
             .time_out_requests(current_time, own_canister_id, local_canisters)
     }
 
+    /// Enqueues a `SYS_UNKNOWN` reject response for every best-effort callback
+    /// whose deadline has expired and which has not yet had a response
+    /// enqueued. Any refund is lost, as the request may have been executed.
+    ///
+    /// Returns the number of callbacks that were timed out.
+    pub fn time_out_callbacks(
+        &mut self,
+        current_time: Time,
+        own_canister_id: &CanisterId,
+        local_canisters: &BTreeMap<CanisterId, CanisterState>,
+    ) -> u64 {
+        let canister_id = self.canister_id;
+        let (queues, call_context_manager) = match &mut self.status {
+            CanisterStatus::Running {
+                call_context_manager,
+            }
+            | CanisterStatus::Stopping {
+                call_context_manager,
+                ..
+            } => (&mut self.queues, call_context_manager),
+            CanisterStatus::Stopped => return 0,
+        };
+
+        let mut timed_out_callbacks_count = 0;
+        for callback_id in call_context_manager.expired_callbacks(CoarseTime::floor(current_time)) {
+            let callback = match call_context_manager.callback(&callback_id) {
+                Some(callback) => callback,
+                None => continue,
+            };
+            let respondent = match callback.respondent {
+                Some(respondent) => respondent,
+                None => continue,
+            };
+            let input_queue_type =
+                if &respondent == own_canister_id || local_canisters.contains_key(&respondent) {
+                    InputQueueType::LocalSubnet
+                } else {
+                    InputQueueType::RemoteSubnet
+                };
+            let response = RequestOrResponse::Response(Arc::new(Response {
+                originator: canister_id,
+                respondent,
+                originator_reply_callback: callback_id,
+                refund: Cycles::zero(),
+                response_payload: Payload::Reject(RejectContext::new(
+                    RejectCode::SysUnknown,
+                    "Call deadline has expired.",
+                )),
+                deadline: callback.deadline,
+            }));
+            // Consumes the slot reserved for the response when the request was
+            // enqueued, so the push is expected to succeed. Should it fail, the
+            // callback remains unexpired and the reject is retried next round.
+            if queues.push_input(response, input_queue_type).is_ok() {
+                call_context_manager.on_response_enqueued(callback_id);
+                timed_out_callbacks_count += 1;
+            }
+        }
+        timed_out_callbacks_count
+    }
+
     /// Re-partitions the local and remote input schedules of `self.queues`
     /// following a canister migration, based on the updated set of local canisters.
     ///
@@ -1489,6 +1750,18 @@ pub(crate) fn push_input(
     res
 }
 
+/// Discards any fragments of a partially received message from `sender`
+/// buffered in `queues`, adjusting `subnet_available_memory` accordingly.
+pub(crate) fn discard_input_fragments(
+    queues: &mut CanisterQueues,
+    sender: &CanisterId,
+    subnet_available_memory: &mut i64,
+) {
+    *subnet_available_memory += queues.memory_usage() as i64;
+    queues.discard_input_fragments(sender);
+    *subnet_available_memory -= queues.memory_usage() as i64;
+}
+
 pub mod testing {
     use super::*;
 
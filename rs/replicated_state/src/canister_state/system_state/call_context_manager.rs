@@ -10,13 +10,16 @@ use ic_protobuf::types::v1 as pb_types;
 use ic_types::NumInstructions;
 use ic_types::{
     ingress::WasmResult,
-    messages::{CallContextId, CallbackId, CanisterCall, CanisterCallOrTask, MessageId, Response},
+    messages::{
+        CallContextId, CallbackId, CanisterCall, CanisterCallOrTask, MessageId, Response,
+        NO_DEADLINE,
+    },
     methods::Callback,
-    user_id_into_protobuf, user_id_try_from_protobuf, CanisterId, Cycles, Funds, PrincipalId, Time,
-    UserId,
+    user_id_into_protobuf, user_id_try_from_protobuf, CanisterId, CoarseTime, Cycles, Funds,
+    PrincipalId, Time, UserId,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::{From, TryFrom, TryInto};
 use std::time::Duration;
 
@@ -226,12 +229,18 @@ pub struct CallContextManager {
     /// Maps call context to its responded status.
     call_contexts: BTreeMap<CallContextId, CallContext>,
     callbacks: BTreeMap<CallbackId, Callback>,
+    /// Best-effort callbacks that have not yet had a response enqueued, ordered
+    /// by deadline. Once a callback's deadline expires, it is removed from here
+    /// and a `SYS_UNKNOWN` reject response is enqueued on its behalf.
+    unexpired_callbacks: BTreeSet<(CoarseTime, CallbackId)>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CallOrigin {
     Ingress(UserId, MessageId),
-    CanisterUpdate(CanisterId, CallbackId),
+    /// The caller canister, its callback and, for best-effort calls, the call
+    /// deadline (`NO_DEADLINE` otherwise).
+    CanisterUpdate(CanisterId, CallbackId, CoarseTime),
     Query(UserId),
     CanisterQuery(CanisterId, CallbackId),
     /// System task is either a `Heartbeat` or a `GlobalTimer`.
@@ -243,7 +252,7 @@ impl CallOrigin {
     pub fn get_principal(&self) -> PrincipalId {
         match self {
             CallOrigin::Ingress(user_id, _) => user_id.get(),
-            CallOrigin::CanisterUpdate(canister_id, _) => canister_id.get(),
+            CallOrigin::CanisterUpdate(canister_id, _, _) => canister_id.get(),
             CallOrigin::Query(user_id) => user_id.get(),
             CallOrigin::CanisterQuery(canister_id, _) => canister_id.get(),
             CallOrigin::SystemTask => IC_00.get(),
@@ -258,10 +267,11 @@ impl From<&CallOrigin> for pb::call_context::CallOrigin {
                 user_id: Some(user_id_into_protobuf(*user_id)),
                 message_id: message_id.as_bytes().to_vec(),
             }),
-            CallOrigin::CanisterUpdate(canister_id, callback_id) => {
+            CallOrigin::CanisterUpdate(canister_id, callback_id, deadline) => {
                 Self::CanisterUpdate(pb::call_context::CanisterUpdateOrQuery {
                     canister_id: Some(pb_types::CanisterId::from(*canister_id)),
                     callback_id: callback_id.get(),
+                    deadline_seconds: deadline.as_secs_since_unix_epoch(),
                 })
             }
             CallOrigin::Query(user_id) => Self::Query(user_id_into_protobuf(*user_id)),
@@ -269,6 +279,7 @@ impl From<&CallOrigin> for pb::call_context::CallOrigin {
                 Self::CanisterQuery(pb::call_context::CanisterUpdateOrQuery {
                     canister_id: Some(pb_types::CanisterId::from(*canister_id)),
                     callback_id: callback_id.get(),
+                    deadline_seconds: NO_DEADLINE.as_secs_since_unix_epoch(),
                 })
             }
             CallOrigin::SystemTask => Self::SystemTask(pb::call_context::SystemTask {}),
@@ -294,10 +305,12 @@ impl TryFrom<pb::call_context::CallOrigin> for CallOrigin {
                 pb::call_context::CanisterUpdateOrQuery {
                     canister_id,
                     callback_id,
+                    deadline_seconds,
                 },
             ) => Self::CanisterUpdate(
                 try_from_option_field(canister_id, "CallOrigin::CanisterUpdate::canister_id")?,
                 callback_id.into(),
+                CoarseTime::from_secs_since_unix_epoch(deadline_seconds),
             ),
             pb::call_context::CallOrigin::Query(user_id) => {
                 Self::Query(user_id_try_from_protobuf(user_id)?)
@@ -306,6 +319,7 @@ impl TryFrom<pb::call_context::CallOrigin> for CallOrigin {
                 pb::call_context::CanisterUpdateOrQuery {
                     canister_id,
                     callback_id,
+                    ..
                 },
             ) => Self::CanisterQuery(
                 try_from_option_field(canister_id, "CallOrigin::CanisterQuery::canister_id")?,
@@ -399,6 +413,19 @@ impl CallContextManager {
                                 respondent: response.respondent,
                             })
                     }
+                    _ if callback.deadline != NO_DEADLINE
+                        && !self
+                            .unexpired_callbacks
+                            .contains(&(callback.deadline, response.originator_reply_callback)) =>
+                    {
+                        // A response (possibly a synthetic reject) was already enqueued.
+                        Err(StateError::NonMatchingResponse {
+                            err_str: "best-effort callback already has a response".to_string(),
+                            originator: response.originator,
+                            callback_id: response.originator_reply_callback,
+                            respondent: response.respondent,
+                        })
+                    }
                     _ => Ok(()),
                 }
             }
@@ -525,6 +552,10 @@ impl CallContextManager {
     pub fn register_callback(&mut self, callback: Callback) -> CallbackId {
         self.next_callback_id += 1;
         let callback_id = CallbackId::from(self.next_callback_id);
+        if callback.deadline != NO_DEADLINE {
+            self.unexpired_callbacks
+                .insert((callback.deadline, callback_id));
+        }
         self.callbacks.insert(callback_id, callback);
         callback_id
     }
@@ -537,7 +568,47 @@ impl CallContextManager {
     /// If we get a response for one of the outstanding calls, we unregister
     /// the callback and return it.
     pub fn unregister_callback(&mut self, callback_id: CallbackId) -> Option<Callback> {
-        self.callbacks.remove(&callback_id)
+        let callback = self.callbacks.remove(&callback_id)?;
+        self.unexpired_callbacks
+            .remove(&(callback.deadline, callback_id));
+        Some(callback)
+    }
+
+    /// Records that a response for the given best-effort callback was enqueued,
+    /// so that the callback no longer times out. No-op for guaranteed response
+    /// callbacks.
+    pub(crate) fn on_response_enqueued(&mut self, callback_id: CallbackId) {
+        if let Some(callback) = self.callbacks.get(&callback_id) {
+            self.unexpired_callbacks
+                .remove(&(callback.deadline, callback_id));
+        }
+    }
+
+    /// Returns the IDs of all best-effort callbacks whose deadline is at or
+    /// before `now` and that have not yet had a response enqueued.
+    ///
+    /// The callbacks remain tracked until `on_response_enqueued()` is called
+    /// for them, i.e. until their reject response was actually enqueued.
+    pub(crate) fn expired_callbacks(&self, now: CoarseTime) -> Vec<CallbackId> {
+        self.unexpired_callbacks
+            .iter()
+            .take_while(|(deadline, _)| *deadline <= now)
+            .map(|(_, callback_id)| *callback_id)
+            .collect()
+    }
+
+    /// Returns `true` if any best-effort callback has a deadline at or before
+    /// `now` and has not yet had a response enqueued.
+    pub fn has_expired_callbacks(&self, now: CoarseTime) -> bool {
+        self.unexpired_callbacks
+            .first()
+            .map_or(false, |(deadline, _)| *deadline <= now)
+    }
+
+    /// Returns the number of best-effort callbacks still waiting for either a
+    /// response or their deadline to expire.
+    pub fn unexpired_callbacks_count(&self) -> usize {
+        self.unexpired_callbacks.len()
     }
 
     /// Returns the call origin, which is either the message id of the ingress
@@ -600,9 +671,11 @@ impl CallContextManager {
 impl From<&CanisterCall> for CallOrigin {
     fn from(msg: &CanisterCall) -> Self {
         match msg {
-            CanisterCall::Request(request) => {
-                CallOrigin::CanisterUpdate(request.sender, request.sender_reply_callback)
-            }
+            CanisterCall::Request(request) => CallOrigin::CanisterUpdate(
+                request.sender,
+                request.sender_reply_callback,
+                request.deadline,
+            ),
             CanisterCall::Ingress(ingress) => {
                 CallOrigin::Ingress(ingress.source, ingress.message_id.clone())
             }
@@ -640,6 +713,11 @@ impl From<&CallContextManager> for pb::CallContextManager {
                     callback: Some(callback.into()),
                 })
                 .collect(),
+            unexpired_callbacks: item
+                .unexpired_callbacks
+                .iter()
+                .map(|(_, id)| id.get())
+                .collect(),
         }
     }
 }
@@ -670,11 +748,23 @@ impl TryFrom<pb::CallContextManager> for CallContextManager {
             );
         }
 
+        let mut unexpired_callbacks = BTreeSet::new();
+        for callback_id in value.unexpired_callbacks.into_iter().map(CallbackId::from) {
+            let callback = callbacks.get(&callback_id).ok_or_else(|| {
+                ProxyDecodeError::Other(format!(
+                    "CallContextManager::unexpired_callbacks: unknown callback {}",
+                    callback_id
+                ))
+            })?;
+            unexpired_callbacks.insert((callback.deadline, callback_id));
+        }
+
         Ok(Self {
             next_call_context_id: value.next_call_context_id,
             next_callback_id: value.next_callback_id,
             call_contexts,
             callbacks,
+            unexpired_callbacks,
         })
     }
 }
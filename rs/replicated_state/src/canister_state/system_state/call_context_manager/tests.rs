@@ -1,5 +1,7 @@
 use super::*;
-use ic_test_utilities::types::ids::canister_test_id;
+use assert_matches::assert_matches;
+use ic_test_utilities::types::ids::{canister_test_id, user_test_id};
+use ic_types::messages::Payload;
 use ic_types::methods::WasmClosure;
 
 #[test]
@@ -8,13 +10,13 @@ fn call_context_origin() {
     let id = canister_test_id(42);
     let cb_id = CallbackId::from(1);
     let cc_id = ccm.new_call_context(
-        CallOrigin::CanisterUpdate(id, cb_id),
+        CallOrigin::CanisterUpdate(id, cb_id, NO_DEADLINE),
         Cycles::new(10),
         Time::from_nanos_since_unix_epoch(0),
     );
     assert_eq!(
         ccm.call_contexts().get(&cc_id).unwrap().call_origin,
-        CallOrigin::CanisterUpdate(id, cb_id)
+        CallOrigin::CanisterUpdate(id, cb_id, NO_DEADLINE)
     );
 }
 
@@ -26,18 +28,18 @@ fn call_context_handling() {
 
     // On two incoming calls
     let call_context_id1 = call_context_manager.new_call_context(
-        CallOrigin::CanisterUpdate(canister_test_id(123), CallbackId::from(1)),
+        CallOrigin::CanisterUpdate(canister_test_id(123), CallbackId::from(1), NO_DEADLINE),
         Cycles::zero(),
         Time::from_nanos_since_unix_epoch(0),
     );
     let call_context_id2 = call_context_manager.new_call_context(
-        CallOrigin::CanisterUpdate(canister_test_id(123), CallbackId::from(2)),
+        CallOrigin::CanisterUpdate(canister_test_id(123), CallbackId::from(2), NO_DEADLINE),
         Cycles::zero(),
         Time::from_nanos_since_unix_epoch(0),
     );
 
     let call_context_id3 = call_context_manager.new_call_context(
-        CallOrigin::CanisterUpdate(canister_test_id(123), CallbackId::from(3)),
+        CallOrigin::CanisterUpdate(canister_test_id(123), CallbackId::from(3), NO_DEADLINE),
         Cycles::zero(),
         Time::from_nanos_since_unix_epoch(0),
     );
@@ -78,6 +80,7 @@ fn call_context_handling() {
         WasmClosure::new(0, 1),
         WasmClosure::new(2, 3),
         None,
+        NO_DEADLINE,
     ));
     let callback_id2 = call_context_manager.register_callback(Callback::new(
         call_context_id1,
@@ -89,6 +92,7 @@ fn call_context_handling() {
         WasmClosure::new(4, 5),
         WasmClosure::new(6, 7),
         None,
+        NO_DEADLINE,
     ));
 
     // There are 2 ougoing calls
@@ -105,6 +109,7 @@ fn call_context_handling() {
         WasmClosure::new(8, 9),
         WasmClosure::new(10, 11),
         None,
+        NO_DEADLINE,
     ));
     // There is 1 outgoing call
     assert_eq!(call_context_manager.outstanding_calls(call_context_id2), 1);
@@ -261,7 +266,7 @@ fn withdraw_cycles_fails_when_not_enough_available_cycles() {
     let id = canister_test_id(42);
     let cb_id = CallbackId::from(1);
     let cc_id = ccm.new_call_context(
-        CallOrigin::CanisterUpdate(id, cb_id),
+        CallOrigin::CanisterUpdate(id, cb_id, NO_DEADLINE),
         Cycles::new(30),
         Time::from_nanos_since_unix_epoch(0),
     );
@@ -283,7 +288,7 @@ fn withdraw_cycles_succeeds_when_enough_available_cycles() {
     let id = canister_test_id(42);
     let cb_id = CallbackId::from(1);
     let cc_id = ccm.new_call_context(
-        CallOrigin::CanisterUpdate(id, cb_id),
+        CallOrigin::CanisterUpdate(id, cb_id, NO_DEADLINE),
         Cycles::new(30),
         Time::from_nanos_since_unix_epoch(0),
     );
@@ -300,7 +305,7 @@ fn withdraw_cycles_succeeds_when_enough_available_cycles() {
 fn test_call_context_instructions_executed_is_updated() {
     let mut call_context_manager = CallContextManager::default();
     let call_context_id = call_context_manager.new_call_context(
-        CallOrigin::CanisterUpdate(canister_test_id(123), CallbackId::from(1)),
+        CallOrigin::CanisterUpdate(canister_test_id(123), CallbackId::from(1), NO_DEADLINE),
         Cycles::zero(),
         Time::from_nanos_since_unix_epoch(0),
     );
@@ -315,6 +320,7 @@ fn test_call_context_instructions_executed_is_updated() {
         WasmClosure::new(0, 1),
         WasmClosure::new(2, 3),
         None,
+        NO_DEADLINE,
     ));
 
     // Finish a successful execution with 1K instructions.
@@ -352,3 +358,146 @@ fn test_call_context_instructions_executed_is_updated() {
         (1_000 + 2_000).into()
     );
 }
+
+#[test]
+fn expire_best_effort_callbacks() {
+    let mut call_context_manager = CallContextManager::default();
+    let call_context_id = call_context_manager.new_call_context(
+        CallOrigin::CanisterUpdate(canister_test_id(123), CallbackId::from(1), NO_DEADLINE),
+        Cycles::zero(),
+        Time::from_nanos_since_unix_epoch(0),
+    );
+    let mut register_callback = |deadline| {
+        call_context_manager.register_callback(Callback::new(
+            call_context_id,
+            Some(canister_test_id(1)),
+            Some(canister_test_id(2)),
+            Cycles::zero(),
+            None,
+            None,
+            WasmClosure::new(0, 1),
+            WasmClosure::new(2, 3),
+            None,
+            deadline,
+        ))
+    };
+    let guaranteed_response_id = register_callback(NO_DEADLINE);
+    let deadline_10_id = register_callback(CoarseTime::from_secs_since_unix_epoch(10));
+    let deadline_20_id = register_callback(CoarseTime::from_secs_since_unix_epoch(20));
+    let deadline_30_id = register_callback(CoarseTime::from_secs_since_unix_epoch(30));
+    assert_eq!(3, call_context_manager.unexpired_callbacks_count());
+
+    // A response was enqueued for the callback with deadline 30.
+    call_context_manager.on_response_enqueued(deadline_30_id);
+    assert_eq!(2, call_context_manager.unexpired_callbacks_count());
+
+    // Nothing expires before the earliest deadline.
+    let time_9 = CoarseTime::from_secs_since_unix_epoch(9);
+    assert!(!call_context_manager.has_expired_callbacks(time_9));
+    assert!(call_context_manager.expired_callbacks(time_9).is_empty());
+
+    // The deadline is inclusive.
+    let time_20 = CoarseTime::from_secs_since_unix_epoch(20);
+    assert!(call_context_manager.has_expired_callbacks(time_20));
+    assert_eq!(
+        vec![deadline_10_id, deadline_20_id],
+        call_context_manager.expired_callbacks(time_20)
+    );
+
+    // Expired callbacks are tracked until their reject response is enqueued.
+    assert_eq!(2, call_context_manager.unexpired_callbacks_count());
+    call_context_manager.on_response_enqueued(deadline_10_id);
+    call_context_manager.on_response_enqueued(deadline_20_id);
+    assert_eq!(0, call_context_manager.unexpired_callbacks_count());
+    assert!(!call_context_manager
+        .has_expired_callbacks(CoarseTime::from_secs_since_unix_epoch(u32::MAX)));
+
+    // All callbacks are still registered.
+    for callback_id in [
+        guaranteed_response_id,
+        deadline_10_id,
+        deadline_20_id,
+        deadline_30_id,
+    ] {
+        assert!(call_context_manager.peek_callback(callback_id).is_some());
+    }
+}
+
+#[test]
+fn validate_response_for_expired_callback_fails() {
+    let mut call_context_manager = CallContextManager::default();
+    let call_context_id = call_context_manager.new_call_context(
+        CallOrigin::CanisterUpdate(canister_test_id(123), CallbackId::from(1), NO_DEADLINE),
+        Cycles::zero(),
+        Time::from_nanos_since_unix_epoch(0),
+    );
+    let deadline = CoarseTime::from_secs_since_unix_epoch(10);
+    let callback_id = call_context_manager.register_callback(Callback::new(
+        call_context_id,
+        Some(canister_test_id(1)),
+        Some(canister_test_id(2)),
+        Cycles::zero(),
+        None,
+        None,
+        WasmClosure::new(0, 1),
+        WasmClosure::new(2, 3),
+        None,
+        deadline,
+    ));
+    let response = Response {
+        originator: canister_test_id(1),
+        respondent: canister_test_id(2),
+        originator_reply_callback: callback_id,
+        refund: Cycles::zero(),
+        response_payload: Payload::Data(vec![]),
+        deadline,
+    };
+    assert_eq!(Ok(()), call_context_manager.validate_response(&response));
+
+    assert_eq!(
+        vec![callback_id],
+        call_context_manager.expired_callbacks(deadline)
+    );
+    // The callee's response is still valid until the reject is enqueued.
+    assert_eq!(Ok(()), call_context_manager.validate_response(&response));
+
+    call_context_manager.on_response_enqueued(callback_id);
+    assert_matches!(
+        call_context_manager.validate_response(&response),
+        Err(StateError::NonMatchingResponse { .. })
+    );
+}
+
+#[test]
+fn unexpired_callbacks_roundtrip_encode() {
+    let mut call_context_manager = CallContextManager::default();
+    let call_context_id = call_context_manager.new_call_context(
+        CallOrigin::Ingress(user_test_id(1), MessageId::from([0; 32])),
+        Cycles::zero(),
+        Time::from_nanos_since_unix_epoch(0),
+    );
+    for deadline in [10, 20] {
+        call_context_manager.register_callback(Callback::new(
+            call_context_id,
+            Some(canister_test_id(1)),
+            Some(canister_test_id(2)),
+            Cycles::zero(),
+            None,
+            None,
+            WasmClosure::new(0, 1),
+            WasmClosure::new(2, 3),
+            None,
+            CoarseTime::from_secs_since_unix_epoch(deadline),
+        ));
+    }
+    for callback_id in
+        call_context_manager.expired_callbacks(CoarseTime::from_secs_since_unix_epoch(10))
+    {
+        call_context_manager.on_response_enqueued(callback_id);
+    }
+
+    let pb_call_context_manager = pb::CallContextManager::from(&call_context_manager);
+    let round_trip = CallContextManager::try_from(pb_call_context_manager).unwrap();
+    assert_eq!(call_context_manager, round_trip);
+    assert_eq!(1, round_trip.unexpired_callbacks_count());
+}
@@ -26,7 +26,7 @@ use ic_test_utilities::types::{
 use ic_types::{
     messages::{
         CallContextId, CallbackId, CanisterCall, StopCanisterCallId, StopCanisterContext,
-        MAX_RESPONSE_COUNT_BYTES,
+        MAX_RESPONSE_COUNT_BYTES, NO_DEADLINE,
     },
     methods::{Callback, WasmClosure},
     nominal_cycles::NominalCycles,
@@ -95,7 +95,7 @@ impl CanisterStateFixture {
             .call_context_manager_mut()
             .unwrap()
             .new_call_context(
-                CallOrigin::CanisterUpdate(CANISTER_ID, CallbackId::from(1)),
+                CallOrigin::CanisterUpdate(CANISTER_ID, CallbackId::from(1), NO_DEADLINE),
                 Cycles::zero(),
                 Time::from_nanos_since_unix_epoch(0),
             );
@@ -113,6 +113,7 @@ impl CanisterStateFixture {
                 WasmClosure::new(0, 2),
                 WasmClosure::new(0, 2),
                 None,
+                NO_DEADLINE,
             ))
     }
 
@@ -318,6 +319,8 @@ fn system_subnet_remote_push_input_request_ignores_memory_reservation_and_execut
         Memory::new_for_testing(),
         vec![Global::I64(14)],
         WasmMetadata::default(),
+        false,
+        None,
     ));
     assert!(canister_state.memory_usage().get() > 0);
     let initial_memory_usage = canister_state.execution_memory_usage()
@@ -565,6 +568,7 @@ fn canister_state_callback_round_trip() {
         WasmClosure::new(0, 2),
         WasmClosure::new(0, 2),
         None,
+        NO_DEADLINE,
     );
 
     let pb_callback = pb::Callback::from(&callback);
@@ -574,6 +578,38 @@ fn canister_state_callback_round_trip() {
     assert_eq!(callback, round_trip);
 }
 
+#[test]
+fn is_low_on_wasm_memory_uses_wasm64_limit_for_wasm64_modules() {
+    let mut canister_state = CanisterStateFixture::new().canister_state;
+    let max_wasm64_memory_size = NumBytes::new(6 << 30);
+    let mut execution_state = ExecutionState::new(
+        Default::default(),
+        execution_state::WasmBinary::new(CanisterModule::new(vec![1, 2, 3])),
+        ExportedFunctions::new(Default::default()),
+        Memory::new_for_testing(),
+        Memory::new_for_testing(),
+        vec![],
+        WasmMetadata::default(),
+        false,
+        None,
+    );
+    // A heap of 3.5 GiB.
+    execution_state.wasm_memory.size = NumWasmPages::new(7 << 14);
+    canister_state.execution_state = Some(execution_state);
+    canister_state.system_state.wasm_memory_threshold = Some(NumBytes::new(1 << 30));
+
+    // Only 0.5 GiB left below the 4 GiB limit of 32-bit memories.
+    assert!(canister_state.is_low_on_wasm_memory(max_wasm64_memory_size));
+
+    // 2.5 GiB left below the limit of 64-bit memories.
+    canister_state.execution_state.as_mut().unwrap().is_wasm64 = true;
+    assert!(!canister_state.is_low_on_wasm_memory(max_wasm64_memory_size));
+
+    // An explicit `wasm_memory_limit` takes precedence.
+    canister_state.system_state.wasm_memory_limit = Some(NumBytes::new(4 << 30));
+    assert!(canister_state.is_low_on_wasm_memory(max_wasm64_memory_size));
+}
+
 #[test]
 fn execution_state_test_partial_eq() {
     let state_1 = ExecutionState::new(
@@ -584,6 +620,8 @@ fn execution_state_test_partial_eq() {
         Memory::new_for_testing(),
         vec![Global::I64(14)],
         WasmMetadata::default(),
+        false,
+        None,
     );
 
     assert_eq!(state_1, state_1.clone());
@@ -34,6 +34,7 @@ use ic_types::{
     ingress::{IngressState, IngressStatus},
     messages::{
         is_subnet_id, CanisterCall, MessageId, Payload, RejectContext, RequestOrResponse, Response,
+        MAX_FRAGMENTED_INTER_CANISTER_PAYLOAD_IN_BYTES, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES,
     },
     node_id_into_protobuf, node_id_try_from_option,
     nominal_cycles::NominalCycles,
@@ -220,6 +221,22 @@ impl NetworkTopology {
             .get(subnet_id)
             .map(|subnet_topology| subnet_topology.nodes.len())
     }
+
+    /// Returns the maximum payload size of inter-canister requests and
+    /// responses sent by canisters on the given subnet: the value set in the
+    /// subnet's registry record, clamped to the range between
+    /// `MAX_INTER_CANISTER_PAYLOAD_IN_BYTES` (the default) and
+    /// `MAX_FRAGMENTED_INTER_CANISTER_PAYLOAD_IN_BYTES`.
+    pub fn max_inter_canister_payload_in_bytes(&self, subnet_id: &SubnetId) -> NumBytes {
+        self.subnets
+            .get(subnet_id)
+            .and_then(|subnet_topology| subnet_topology.max_inter_canister_payload_in_bytes)
+            .unwrap_or(MAX_INTER_CANISTER_PAYLOAD_IN_BYTES)
+            .clamp(
+                MAX_INTER_CANISTER_PAYLOAD_IN_BYTES,
+                MAX_FRAGMENTED_INTER_CANISTER_PAYLOAD_IN_BYTES,
+            )
+    }
 }
 
 impl From<&NetworkTopology> for pb_metadata::NetworkTopology {
@@ -340,6 +357,10 @@ pub struct SubnetTopology {
     /// a backup. An additional NNS proposal will be needed to allow the subnet
     /// holding the key as backup to actually produce signatures.
     pub ecdsa_keys_held: BTreeSet<EcdsaKeyId>,
+    /// The maximum payload size of inter-canister requests and responses sent
+    /// by canisters on this subnet, as set in the subnet's registry record. See
+    /// `NetworkTopology::max_inter_canister_payload_in_bytes()`.
+    pub max_inter_canister_payload_in_bytes: Option<NumBytes>,
 }
 
 impl From<&SubnetTopology> for pb_metadata::SubnetTopology {
@@ -356,6 +377,9 @@ impl From<&SubnetTopology> for pb_metadata::SubnetTopology {
             subnet_type: i32::from(item.subnet_type),
             subnet_features: Some(pb_subnet::SubnetFeatures::from(item.subnet_features)),
             ecdsa_keys_held: item.ecdsa_keys_held.iter().map(|k| k.into()).collect(),
+            max_inter_canister_payload_in_bytes: item
+                .max_inter_canister_payload_in_bytes
+                .map(|bytes| bytes.get()),
         }
     }
 }
@@ -385,6 +409,9 @@ impl TryFrom<pb_metadata::SubnetTopology> for SubnetTopology {
                 .map(SubnetFeatures::from)
                 .unwrap_or_default(),
             ecdsa_keys_held,
+            max_inter_canister_payload_in_bytes: item
+                .max_inter_canister_payload_in_bytes
+                .map(NumBytes::from),
         })
     }
 }
@@ -1097,6 +1124,7 @@ impl SystemMetadata {
                         RejectCode::SysTransient,
                         format!("Canister {} migrated during a subnet split", canister_id),
                     )),
+                    deadline: request.deadline,
                 };
                 subnet_queues.push_output_response(response.into());
             }
@@ -1435,11 +1463,8 @@ impl Streams {
     /// Pushes the given message onto the stream for the given destination
     /// subnet.
     pub fn push(&mut self, destination: SubnetId, msg: RequestOrResponse) {
-        if let RequestOrResponse::Response(response) = &msg {
-            *self
-                .responses_size_bytes
-                .entry(response.respondent)
-                .or_default() += msg.count_bytes();
+        if let Some(respondent) = respondent(&msg) {
+            *self.responses_size_bytes.entry(respondent).or_default() += msg.count_bytes();
         }
         self.streams.entry(destination).or_default().push(msg);
         #[cfg(debug_assertions)]
@@ -1495,9 +1520,8 @@ impl Streams {
         let mut responses_size_bytes: BTreeMap<CanisterId, usize> = BTreeMap::new();
         for (_, stream) in streams.iter() {
             for (_, msg) in stream.messages().iter() {
-                if let RequestOrResponse::Response(response) = msg {
-                    *responses_size_bytes.entry(response.respondent).or_default() +=
-                        msg.count_bytes();
+                if let Some(respondent) = respondent(msg) {
+                    *responses_size_bytes.entry(respondent).or_default() += msg.count_bytes();
                 }
             }
         }
@@ -1566,11 +1590,8 @@ impl<'a> StreamHandle<'a> {
     /// Returns the byte size of the pushed message.
     pub fn push(&mut self, message: RequestOrResponse) -> usize {
         let size_bytes = message.count_bytes();
-        if let RequestOrResponse::Response(response) = &message {
-            *self
-                .responses_size_bytes
-                .entry(response.respondent)
-                .or_default() += size_bytes;
+        if let Some(respondent) = respondent(&message) {
+            *self.responses_size_bytes.entry(respondent).or_default() += size_bytes;
         }
         self.stream.push(message);
         size_bytes
@@ -1598,10 +1619,10 @@ impl<'a> StreamHandle<'a> {
             if index >= new_begin {
                 break;
             }
-            if let RequestOrResponse::Response(response) = &msg {
+            if let Some(respondent) = respondent(msg) {
                 let canister_responses_size_bytes = self
                     .responses_size_bytes
-                    .get_mut(&response.respondent)
+                    .get_mut(&respondent)
                     .expect("No `responses_size_bytes` entry for discarded response");
                 *canister_responses_size_bytes -= msg.count_bytes();
             }
@@ -1933,6 +1954,16 @@ impl IngressHistoryState {
 /// The number of snapshots retained in the `BlockmakerMetricsTimeSeries`.
 const BLOCKMAKER_METRICS_TIME_SERIES_NUM_SNAPSHOTS: usize = 60;
 
+/// Returns the respondent of `msg` if it is a response or a fragment of a
+/// response; `None` otherwise.
+fn respondent(msg: &RequestOrResponse) -> Option<CanisterId> {
+    match msg {
+        RequestOrResponse::Request(_) => None,
+        RequestOrResponse::Response(response) => Some(response.respondent),
+        RequestOrResponse::Fragment(fragment) => respondent(&fragment.header),
+    }
+}
+
 /// Converts `Time` to days since Unix epoch. This simply divides the timestamp by
 /// 24 hours.
 pub(crate) fn days_since_unix_epoch(time: Time) -> u64 {
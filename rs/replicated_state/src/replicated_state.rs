@@ -3,8 +3,11 @@ use super::{
     metadata_state::{IngressHistoryState, Stream, Streams, SystemMetadata},
 };
 use crate::{
+    canister_snapshots::CanisterSnapshots,
     canister_state::queues::CanisterQueuesLoopDetector,
-    canister_state::system_state::{push_input, CanisterOutputQueuesIterator},
+    canister_state::system_state::{
+        discard_input_fragments, push_input, CanisterOutputQueuesIterator,
+    },
     metadata_state::StreamMap,
     CanisterQueues,
 };
@@ -96,6 +99,19 @@ pub enum StateError {
 
     /// No corresponding request found when trying to push a response from the bitcoin adapter.
     BitcoinNonMatchingResponse { callback_id: u64 },
+
+    /// Message fragment enqueuing failed because it does not directly follow
+    /// the previously enqueued fragment from the same sender.
+    MissingMessageFragments {
+        sender: CanisterId,
+        expected_index: u32,
+        index: u32,
+    },
+
+    /// Message fragment enqueuing failed because the fragment is inconsistent
+    /// with the previously enqueued fragments from the same sender (e.g. a
+    /// different fragment count); or the fragments could not be reassembled.
+    InvalidMessageFragments { sender: CanisterId, err: String },
 }
 
 /// Circular iterator that consumes messages from all canisters' and the
@@ -196,7 +212,9 @@ pub trait PeekableOutputIterator: std::iter::Iterator<Item = (QueueId, RequestOr
     /// Permanently filters out from iteration the next queue (i.e. all messages
     /// with the same sender and receiver as the next). The messages are retained
     /// in the output queue.
-    fn exclude_queue(&mut self);
+    ///
+    /// Returns the number of messages left in the excluded queue.
+    fn exclude_queue(&mut self) -> usize;
 }
 
 impl PeekableOutputIterator for OutputIterator<'_> {
@@ -204,14 +222,17 @@ impl PeekableOutputIterator for OutputIterator<'_> {
         self.canister_iterators.front().and_then(|it| it.peek())
     }
 
-    fn exclude_queue(&mut self) {
+    fn exclude_queue(&mut self) -> usize {
+        let mut ignored = 0;
         if let Some(mut canister_iterator) = self.canister_iterators.pop_front() {
-            self.size -= canister_iterator.exclude_queue();
+            ignored = canister_iterator.exclude_queue();
+            self.size -= ignored;
             if !canister_iterator.is_empty() {
                 self.canister_iterators.push_front(canister_iterator);
             }
             debug_assert_eq!(Self::compute_size(&self.canister_iterators), self.size);
         }
+        ignored
     }
 }
 
@@ -227,6 +248,8 @@ pub const LABEL_VALUE_INVALID_RESPONSE: &str = "InvalidResponse";
 pub const LABEL_VALUE_INVALID_SUBNET_PAYLOAD: &str = "InvalidSubnetPayload";
 pub const LABEL_VALUE_OUT_OF_MEMORY: &str = "OutOfMemory";
 pub const LABEL_VALUE_BITCOIN_NON_MATCHING_RESPONSE: &str = "BitcoinNonMatchingResponse";
+pub const LABEL_VALUE_MISSING_MESSAGE_FRAGMENTS: &str = "MissingMessageFragments";
+pub const LABEL_VALUE_INVALID_MESSAGE_FRAGMENTS: &str = "InvalidMessageFragments";
 
 impl StateError {
     /// Returns a string representation of the `StateError` variant name to be
@@ -247,6 +270,8 @@ impl StateError {
             StateError::BitcoinNonMatchingResponse { .. } => {
                 LABEL_VALUE_BITCOIN_NON_MATCHING_RESPONSE
             }
+            StateError::MissingMessageFragments { .. } => LABEL_VALUE_MISSING_MESSAGE_FRAGMENTS,
+            StateError::InvalidMessageFragments { .. } => LABEL_VALUE_INVALID_MESSAGE_FRAGMENTS,
         }
     }
 }
@@ -305,6 +330,20 @@ impl std::fmt::Display for StateError {
                     callback_id
                 )
             }
+            StateError::MissingMessageFragments {
+                sender,
+                expected_index,
+                index,
+            } => write!(
+                f,
+                "Cannot enqueue message fragment {} from {}: expected fragment {}",
+                index, sender, expected_index
+            ),
+            StateError::InvalidMessageFragments { sender, err } => write!(
+                f,
+                "Cannot enqueue message fragments from {}: {}",
+                sender, err
+            ),
         }
     }
 }
@@ -321,6 +360,9 @@ impl From<&StateError> for ErrorCode {
             StateError::QueueFull { .. } => ErrorCode::CanisterQueueFull,
             StateError::IngressHistoryFull { .. } => ErrorCode::IngressHistoryFull,
             StateError::OutOfMemory { .. } => ErrorCode::CanisterOutOfMemory,
+            // Transient: the whole message may be retried.
+            StateError::MissingMessageFragments { .. }
+            | StateError::InvalidMessageFragments { .. } => ErrorCode::CanisterQueueFull,
 
             // These errors cannot happen when pushing a request or ingress:
             //
@@ -406,6 +448,10 @@ pub struct ReplicatedState {
     /// Temporary query stats received during the current epoch.
     /// Reset during the start of each epoch.
     pub epoch_query_stats: RawQueryStats,
+
+    /// Snapshots of canisters taken via `take_canister_snapshot`, indexed by
+    /// snapshot ID.
+    pub canister_snapshots: CanisterSnapshots,
 }
 
 impl ReplicatedState {
@@ -417,6 +463,7 @@ impl ReplicatedState {
             subnet_queues: CanisterQueues::default(),
             consensus_queue: Vec::new(),
             epoch_query_stats: RawQueryStats::default(),
+            canister_snapshots: CanisterSnapshots::default(),
         }
     }
 
@@ -426,6 +473,7 @@ impl ReplicatedState {
         metadata: SystemMetadata,
         subnet_queues: CanisterQueues,
         epoch_query_stats: RawQueryStats,
+        canister_snapshots: CanisterSnapshots,
     ) -> Self {
         let mut res = Self {
             canister_states,
@@ -433,6 +481,7 @@ impl ReplicatedState {
             subnet_queues,
             consensus_queue: Vec::new(),
             epoch_query_stats,
+            canister_snapshots,
         };
         res.update_stream_responses_size_bytes();
         res
@@ -593,7 +642,9 @@ impl ReplicatedState {
                 (
                     match canister.memory_allocation() {
                         MemoryAllocation::Reserved(bytes) => bytes,
-                        MemoryAllocation::BestEffort => canister.execution_memory_usage(),
+                        MemoryAllocation::BestEffort => {
+                            canister.execution_memory_usage() + canister.snapshots_memory_usage()
+                        }
                     },
                     canister.system_state.message_memory_usage(),
                     canister.wasm_custom_sections_memory_usage(),
@@ -691,6 +742,33 @@ impl ReplicatedState {
         }
     }
 
+    /// Discards any fragments of a partially received message from `sender` to
+    /// `receiver` (a canister or the subnet), e.g. after one of its fragments
+    /// failed induction or was dropped.
+    ///
+    /// Updates `subnet_available_memory` to reflect the released memory.
+    pub fn discard_input_fragments(
+        &mut self,
+        receiver: &CanisterId,
+        sender: &CanisterId,
+        subnet_available_memory: &mut i64,
+    ) {
+        match self.canister_states.get_mut(receiver) {
+            Some(receiver_canister) => receiver_canister
+                .system_state
+                .discard_input_fragments(sender, subnet_available_memory),
+            None => {
+                if receiver.get_ref() == self.metadata.own_subnet_id.get_ref() {
+                    discard_input_fragments(
+                        &mut self.subnet_queues,
+                        sender,
+                        subnet_available_memory,
+                    )
+                }
+            }
+        }
+    }
+
     /// Pushes an ingress message into the induction pool (canister or subnet
     /// ingress queue).
     pub fn push_ingress(&mut self, msg: Ingress) -> Result<(), StateError> {
@@ -850,6 +928,39 @@ impl ReplicatedState {
         timed_out_requests_count
     }
 
+    /// Times out all best-effort callbacks with expired deadlines (given the
+    /// state time), enqueuing a `SYS_UNKNOWN` reject response for each. Returns
+    /// the number of timed out callbacks.
+    ///
+    /// See `SystemState::time_out_callbacks` for further details.
+    pub fn time_out_callbacks(&mut self) -> u64 {
+        let current_time = self.metadata.time();
+        // Same remove-call-replace approach as in `time_out_requests()`.
+        let canister_ids_with_expired_callbacks = self
+            .canister_states
+            .iter()
+            .filter(|(_, canister_state)| {
+                canister_state
+                    .system_state
+                    .has_expired_callbacks(current_time)
+            })
+            .map(|(canister_id, _)| *canister_id)
+            .collect::<Vec<_>>();
+
+        let mut timed_out_callbacks_count = 0;
+        for canister_id in canister_ids_with_expired_callbacks {
+            let mut canister = self.canister_states.remove(&canister_id).unwrap();
+            timed_out_callbacks_count += canister.system_state.time_out_callbacks(
+                current_time,
+                &canister_id,
+                &self.canister_states,
+            );
+            self.canister_states.insert(canister_id, canister);
+        }
+
+        timed_out_callbacks_count
+    }
+
     /// Splits the replicated state as part of subnet splitting phase 1, retaining
     /// only the canisters of `subnet_id` (as determined by the provided routing
     /// table).
@@ -888,6 +999,7 @@ impl ReplicatedState {
             mut subnet_queues,
             consensus_queue,
             epoch_query_stats: _,
+            mut canister_snapshots,
         } = self;
 
         // Consensus queue is always empty at the end of the round.
@@ -899,6 +1011,10 @@ impl ReplicatedState {
         canister_states
             .retain(|canister_id, _| routing_table.route(canister_id.get()) == Some(subnet_id));
 
+        // Snapshots follow the canisters they belong to.
+        canister_snapshots
+            .retain_canisters(|canister_id| canister_states.contains_key(canister_id));
+
         // All subnet messages (ingress and canister) only remain on subnet A' because:
         //
         //  * Message Routing would drop a response from subnet B to a request it had
@@ -923,6 +1039,7 @@ impl ReplicatedState {
             subnet_queues,
             consensus_queue,
             epoch_query_stats: RawQueryStats::default(), // Don't preserve query stats during subnet splitting.
+            canister_snapshots,
         })
     }
 
@@ -945,6 +1062,7 @@ impl ReplicatedState {
             ref mut subnet_queues,
             consensus_queue: _,
             epoch_query_stats: _,
+            canister_snapshots: _,
         } = self;
 
         // Reset query stats after subnet split
@@ -1105,6 +1223,8 @@ pub mod testing {
             subnet_queues: Default::default(),
             consensus_queue: Default::default(),
             epoch_query_stats: Default::default(),
+            // Covered by `split()`, snapshots are retained along with their canisters.
+            canister_snapshots: Default::default(),
         };
     }
 }
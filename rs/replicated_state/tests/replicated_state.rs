@@ -1,3 +1,4 @@
+use assert_matches::assert_matches;
 use ic_base_types::{CanisterId, NumBytes, NumSeconds, PrincipalId, SubnetId};
 use ic_btc_interface::Network;
 use ic_btc_types_internal::{
@@ -16,6 +17,7 @@ use ic_replicated_state::replicated_state::testing::ReplicatedStateTesting;
 use ic_replicated_state::testing::{CanisterQueuesTesting, SystemStateTesting};
 use ic_replicated_state::{
     canister_state::execution_state::{CustomSection, CustomSectionType, WasmMetadata},
+    canister_state::system_state::CallOrigin,
     metadata_state::subnet_call_context_manager::{BitcoinGetSuccessorsContext, SubnetCallContext},
     replicated_state::{MemoryTaken, PeekableOutputIterator, ReplicatedStateMessageRouting},
     CanisterState, IngressHistoryState, ReplicatedState, SchedulerState, StateError, SystemState,
@@ -30,7 +32,8 @@ use ic_types::{
     messages::{
         CanisterMessage, Payload, Request, RequestOrResponse, Response, MAX_RESPONSE_COUNT_BYTES,
     },
-    CountBytes, Cycles, MemoryAllocation, Time,
+    methods::{Callback, WasmClosure},
+    CoarseTime, CountBytes, Cycles, MemoryAllocation, Time,
 };
 use maplit::btreemap;
 use proptest::prelude::*;
@@ -677,6 +680,139 @@ fn time_out_requests_updates_subnet_input_schedules_correctly() {
     );
 }
 
+#[test]
+fn time_out_callbacks_enqueues_sys_unknown_reject_response() {
+    let mut fixture = ReplicatedStateFixture::with_canisters(&[CANISTER_ID]);
+    let remote_canister_id = CanisterId::from_u64(123);
+    let deadline = CoarseTime::from_secs_since_unix_epoch(10);
+
+    // Register a best-effort callback and enqueue the matching request.
+    let call_context_manager = fixture
+        .state
+        .canister_state_mut(&CANISTER_ID)
+        .unwrap()
+        .system_state
+        .call_context_manager_mut()
+        .unwrap();
+    let call_context_id =
+        call_context_manager.new_call_context(CallOrigin::SystemTask, Cycles::zero(), mock_time());
+    let callback_id = call_context_manager.register_callback(Callback::new(
+        call_context_id,
+        Some(CANISTER_ID),
+        Some(remote_canister_id),
+        Cycles::zero(),
+        None,
+        None,
+        WasmClosure::new(0, 1),
+        WasmClosure::new(2, 3),
+        None,
+        deadline,
+    ));
+    let request = RequestBuilder::default()
+        .sender(CANISTER_ID)
+        .receiver(remote_canister_id)
+        .sender_reply_callback(callback_id)
+        .deadline(deadline)
+        .build();
+    fixture.push_output_request(request, mock_time()).unwrap();
+
+    // Nothing times out before the deadline.
+    fixture.state.metadata.batch_time =
+        Time::from_nanos_since_unix_epoch(Time::from(deadline).as_nanos_since_unix_epoch() - 1);
+    assert_eq!(0, fixture.state.time_out_requests());
+    assert_eq!(0, fixture.state.time_out_callbacks());
+
+    // The request is dropped without producing a reject response.
+    fixture.state.metadata.batch_time = Time::from(deadline);
+    assert_eq!(1, fixture.state.time_out_requests());
+    assert!(fixture.pop_input().is_none());
+
+    // The callback is timed out exactly once.
+    assert_eq!(1, fixture.state.time_out_callbacks());
+    assert_eq!(0, fixture.state.time_out_callbacks());
+    match fixture.pop_input() {
+        Some(CanisterMessage::Response(response)) => {
+            assert_eq!(callback_id, response.originator_reply_callback);
+            assert_eq!(remote_canister_id, response.respondent);
+            assert_eq!(deadline, response.deadline);
+            assert_matches!(
+                &response.response_payload,
+                Payload::Reject(context) if context.code() == RejectCode::SysUnknown
+            );
+        }
+        other => panic!("Expected a response, got {:?}", other),
+    }
+
+    // A late response from the callee can no longer be inducted.
+    let late_response = ResponseBuilder::default()
+        .respondent(remote_canister_id)
+        .originator(CANISTER_ID)
+        .originator_reply_callback(callback_id)
+        .deadline(deadline)
+        .build();
+    assert_matches!(
+        fixture.push_input(late_response.into()),
+        Err((StateError::NonMatchingResponse { .. }, _))
+    );
+}
+
+#[test]
+fn time_out_callbacks_retries_if_reject_cannot_be_enqueued() {
+    let mut fixture = ReplicatedStateFixture::with_canisters(&[CANISTER_ID]);
+    let remote_canister_id = CanisterId::from_u64(123);
+    let deadline = CoarseTime::from_secs_since_unix_epoch(10);
+
+    // Register a best-effort callback, but do not enqueue the matching request
+    // yet, so there is no reserved slot for the reject response.
+    let call_context_manager = fixture
+        .state
+        .canister_state_mut(&CANISTER_ID)
+        .unwrap()
+        .system_state
+        .call_context_manager_mut()
+        .unwrap();
+    let call_context_id =
+        call_context_manager.new_call_context(CallOrigin::SystemTask, Cycles::zero(), mock_time());
+    let callback_id = call_context_manager.register_callback(Callback::new(
+        call_context_id,
+        Some(CANISTER_ID),
+        Some(remote_canister_id),
+        Cycles::zero(),
+        None,
+        None,
+        WasmClosure::new(0, 1),
+        WasmClosure::new(2, 3),
+        None,
+        deadline,
+    ));
+
+    // The reject cannot be enqueued, so the callback is not timed out.
+    fixture.state.metadata.batch_time = Time::from(deadline);
+    assert_eq!(0, fixture.state.time_out_callbacks());
+    assert!(fixture.pop_input().is_none());
+
+    // Once a slot is reserved, the callback is timed out in a later round.
+    let request = RequestBuilder::default()
+        .sender(CANISTER_ID)
+        .receiver(remote_canister_id)
+        .sender_reply_callback(callback_id)
+        .deadline(deadline)
+        .build();
+    fixture.push_output_request(request, mock_time()).unwrap();
+    assert_eq!(1, fixture.state.time_out_callbacks());
+    assert_eq!(0, fixture.state.time_out_callbacks());
+    match fixture.pop_input() {
+        Some(CanisterMessage::Response(response)) => {
+            assert_eq!(callback_id, response.originator_reply_callback);
+            assert_matches!(
+                &response.response_payload,
+                Payload::Reject(context) if context.code() == RejectCode::SysUnknown
+            );
+        }
+        other => panic!("Expected a response, got {:?}", other),
+    }
+}
+
 #[test]
 fn split() {
     // We will be splitting subnet A into A' and B.
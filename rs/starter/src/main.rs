@@ -643,6 +643,7 @@ impl ValidatedConfig {
             embedders_config: EmbeddersConfig {
                 feature_flags: FeatureFlags {
                     rate_limiting_of_debug_prints: FlagStatus::Disabled,
+                    canister_backtrace: FlagStatus::Enabled,
                     ..FeatureFlags::default()
                 },
                 metering_type: if self.use_old_metering {
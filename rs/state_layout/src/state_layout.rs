@@ -3,6 +3,7 @@ use crate::utils::do_copy;
 
 use ic_base_types::{NumBytes, NumSeconds};
 use ic_config::flag_status::FlagStatus;
+use ic_ic00_types::LogVisibility;
 use ic_logger::{error, info, warn, ReplicaLogger};
 use ic_metrics::{buckets::decimal_buckets, MetricsRegistry};
 use ic_protobuf::{
@@ -18,12 +19,18 @@ use ic_replicated_state::{
         system_state::{wasm_chunk_store::WasmChunkStoreMetadata, CanisterHistory, CyclesUseCase},
     },
     CallContextManager, CanisterStatus, ExecutionTask, ExportedFunctions, Global, NumWasmPages,
+    OnLowWasmMemoryHookStatus, SnapshotId,
 };
 use ic_sys::mmap::ScopedMmap;
 use ic_types::{
-    batch::TotalQueryStats, nominal_cycles::NominalCycles, AccumulatedPriority, CanisterId,
-    ComputeAllocation, Cycles, ExecutionRound, Height, MemoryAllocation, NumInstructions,
-    PrincipalId,
+    batch::{
+        total_method_stats_into_protobuf, total_method_stats_try_from_protobuf, TotalMethodStats,
+        TotalQueryStats,
+    },
+    canister_log::CanisterLog,
+    nominal_cycles::NominalCycles,
+    AccumulatedPriority, CanisterId, CanisterTimers, ComputeAllocation, Cycles, ExecutionRound,
+    Height, MemoryAllocation, NumInstructions, PrincipalId, Time,
 };
 use ic_utils::fs::sync_path;
 use ic_utils::thread::parallel_map;
@@ -53,6 +60,8 @@ pub const SPLIT_MARKER_FILE: &str = "split_from.pbuf";
 pub const SUBNET_QUEUES_FILE: &str = "subnet_queues.pbuf";
 pub const SYSTEM_METADATA_FILE: &str = "system_metadata.pbuf";
 pub const STATS_FILE: &str = "stats.pbuf";
+pub const SNAPSHOTS_DIR: &str = "snapshots";
+pub const SNAPSHOT_FILE: &str = "snapshot.pbuf";
 
 /// `ReadOnly` is the access policy used for reading checkpoints. We
 /// don't want to ever modify persisted states.
@@ -125,6 +134,8 @@ pub struct ExecutionStateBits {
     pub metadata: WasmMetadata,
     pub binary_hash: Option<WasmHash>,
     pub next_scheduled_method: NextScheduledMethod,
+    pub is_wasm64: bool,
+    pub wasm_memory_max_pages: Option<u64>,
 }
 
 /// This struct contains bits of the `CanisterState` that are not already
@@ -156,11 +167,36 @@ pub struct CanisterStateBits {
     pub task_queue: Vec<ExecutionTask>,
     pub time_of_last_allocation_charge_nanos: u64,
     pub global_timer_nanos: Option<u64>,
+    pub timers: CanisterTimers,
     pub canister_version: u64,
     pub consumed_cycles_since_replica_started_by_use_cases: BTreeMap<CyclesUseCase, NominalCycles>,
     pub canister_history: CanisterHistory,
     pub wasm_chunk_store_metadata: WasmChunkStoreMetadata,
     pub total_query_stats: TotalQueryStats,
+    pub snapshots_memory_usage: NumBytes,
+    pub next_snapshot_id: u64,
+    pub log_visibility: LogVisibility,
+    pub canister_log: CanisterLog,
+    pub wasm_memory_limit: Option<NumBytes>,
+    pub wasm_memory_threshold: Option<NumBytes>,
+    pub on_low_wasm_memory_hook_status: OnLowWasmMemoryHookStatus,
+    pub environment_variables: BTreeMap<String, String>,
+    pub total_update_method_stats: BTreeMap<String, TotalMethodStats>,
+}
+
+/// This struct contains bits of a `CanisterSnapshot` that are not already
+/// covered by separate files (Wasm binary, memories and Wasm chunk store).
+#[derive(Debug)]
+pub struct CanisterSnapshotBits {
+    pub taken_at_timestamp: Time,
+    pub canister_version: u64,
+    pub certified_data: Vec<u8>,
+    pub global_timer_nanos: Option<u64>,
+    pub exported_globals: Vec<Global>,
+    pub wasm_memory_size: NumWasmPages,
+    pub stable_memory_size: NumWasmPages,
+    pub wasm_chunk_store_metadata: WasmChunkStoreMetadata,
+    pub binary_hash: Option<WasmHash>,
 }
 
 #[derive(Clone)]
@@ -240,6 +276,10 @@ struct CheckpointRefData {
 /// ├── diverged_state_markers
 /// │   └──<hex(round)>
 /// │
+/// ├── compilation_cache
+/// │   └──<hex(cache_version)>
+/// │      └── <hex(wasm_hash)>.bin
+/// │
 /// ├── tmp
 /// └── fs_tmp
 /// ```
@@ -400,6 +440,31 @@ impl TipHandler {
         }
         Ok(())
     }
+
+    /// Deletes canister snapshots from tip if they are not in ids.
+    pub fn filter_tip_snapshots(
+        &mut self,
+        height: Height,
+        ids: &BTreeSet<SnapshotId>,
+    ) -> Result<(), LayoutError> {
+        let tip = self.tip(height)?;
+        for canister_id in tip.canister_ids()? {
+            let canister_layout = tip.canister(&canister_id)?;
+            for id in canister_layout.snapshot_ids()? {
+                if !ids.contains(&id) {
+                    let snapshot_path = canister_layout.snapshot(&id)?.raw_path();
+                    std::fs::remove_dir_all(&snapshot_path).map_err(|err| {
+                        LayoutError::IoError {
+                            path: snapshot_path,
+                            message: "Cannot remove snapshot.".to_string(),
+                            io_err: err,
+                        }
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl StateLayout {
@@ -503,6 +568,12 @@ impl StateLayout {
         self.root.join("fs_tmp")
     }
 
+    /// Returns the path to the directory in which compiled Wasm modules are
+    /// persisted. Unlike `tmp`, this directory survives restarts of a node.
+    pub fn compilation_cache(&self) -> PathBuf {
+        self.root.join("compilation_cache")
+    }
+
     pub fn page_deltas(&self) -> PathBuf {
         self.root.join("page_deltas")
     }
@@ -1213,6 +1284,18 @@ fn parse_canister_id(hex: &str) -> Result<CanisterId, String> {
     ))
 }
 
+/// Helper for parsing the directory names under `snapshots`, which are the
+/// hex representation of the local part of a snapshot ID.
+fn parse_snapshot_id(canister_id: CanisterId, hex: &str) -> Result<SnapshotId, String> {
+    let local_id = u64::from_str_radix(hex, 16).map_err(|err| {
+        format!(
+            "failed to convert directory name {} into a snapshot ID: {}",
+            hex, err
+        )
+    })?;
+    Ok(SnapshotId::new(canister_id, local_id))
+}
+
 /// Parses the canister ID from a relative path, if it is the path of a canister
 /// state file (e.g. `canister_states/00000000000000010101/queues.pbuf`).
 /// Returns `None` if the path is not under `canister_states`; or if parsing
@@ -1473,6 +1556,123 @@ impl<Permissions: AccessPolicy> CanisterLayout<Permissions> {
         self.canister_root
             .join(format!("{:016x}_wasm_chunk_store.overlay", height.get()))
     }
+
+    /// IDs of all the snapshots of this canister that exist on disk.
+    pub fn snapshot_ids(&self) -> Result<Vec<SnapshotId>, LayoutError> {
+        let snapshots_dir = self.canister_root.join(SNAPSHOTS_DIR);
+        let canister_id = self
+            .canister_root
+            .file_name()
+            .and_then(|hex| parse_canister_id(hex.to_str()?).ok())
+            .ok_or_else(|| LayoutError::CorruptedLayout {
+                path: self.canister_root.clone(),
+                message: "failed to parse canister ID of a snapshot".to_string(),
+            })?;
+        collect_subdirs(snapshots_dir.as_path(), |hex| {
+            parse_snapshot_id(canister_id, hex)
+        })
+    }
+
+    /// Layout of the snapshot with the given ID.
+    pub fn snapshot(
+        &self,
+        snapshot_id: &SnapshotId,
+    ) -> Result<CanisterSnapshotLayout<Permissions>, LayoutError> {
+        CanisterSnapshotLayout::new(
+            self.canister_root
+                .join(SNAPSHOTS_DIR)
+                .join(format!("{:016x}", snapshot_id.local_id())),
+        )
+    }
+}
+
+/// Layout of a single canister snapshot, stored under the `snapshots`
+/// directory of the canister it belongs to.
+pub struct CanisterSnapshotLayout<Permissions: AccessPolicy> {
+    snapshot_root: PathBuf,
+    permissions_tag: PhantomData<Permissions>,
+}
+
+impl<Permissions: AccessPolicy> CanisterSnapshotLayout<Permissions> {
+    pub fn new(snapshot_root: PathBuf) -> Result<Self, LayoutError> {
+        Permissions::check_dir(&snapshot_root)?;
+        Ok(Self {
+            snapshot_root,
+            permissions_tag: PhantomData,
+        })
+    }
+
+    pub fn raw_path(&self) -> PathBuf {
+        self.snapshot_root.clone()
+    }
+
+    pub fn snapshot(
+        &self,
+    ) -> ProtoFileWith<pb_canister_state_bits::CanisterSnapshotBits, Permissions> {
+        self.snapshot_root.join(SNAPSHOT_FILE).into()
+    }
+
+    pub fn wasm(&self) -> WasmFile<Permissions> {
+        self.snapshot_root.join("software.wasm").into()
+    }
+
+    /// List all overlay files with a particular name ending, see
+    /// `CanisterLayout::overlays_impl`.
+    fn overlays_impl(&self, name_end: &str) -> Result<Vec<PathBuf>, LayoutError> {
+        CanisterLayout::<Permissions> {
+            canister_root: self.snapshot_root.clone(),
+            permissions_tag: PhantomData,
+        }
+        .overlays_impl(name_end)
+    }
+
+    /// Base file for the snapshotted wasm memory.
+    pub fn vmemory_0(&self) -> PathBuf {
+        self.snapshot_root.join("vmemory_0.bin")
+    }
+
+    /// List of existing overlay files for the snapshotted wasm memory.
+    pub fn vmemory_0_overlays(&self) -> Result<Vec<PathBuf>, LayoutError> {
+        self.overlays_impl("_vmemory_0.overlay")
+    }
+
+    /// Name of a (potentially new) overlay file for the snapshotted wasm memory written at `height`.
+    pub fn vmemory_0_overlay(&self, height: Height) -> PathBuf {
+        self.snapshot_root
+            .join(format!("{:016x}_vmemory_0.overlay", height.get()))
+    }
+
+    /// Base file for the snapshotted stable memory.
+    pub fn stable_memory_blob(&self) -> PathBuf {
+        self.snapshot_root.join("stable_memory.bin")
+    }
+
+    /// List of existing overlay files for the snapshotted stable memory.
+    pub fn stable_memory_overlays(&self) -> Result<Vec<PathBuf>, LayoutError> {
+        self.overlays_impl("_stable_memory.overlay")
+    }
+
+    /// Name of a (potentially new) overlay file for the snapshotted stable memory written at `height`.
+    pub fn stable_memory_overlay(&self, height: Height) -> PathBuf {
+        self.snapshot_root
+            .join(format!("{:016x}_stable_memory.overlay", height.get()))
+    }
+
+    /// Base file for the snapshotted wasm chunk store.
+    pub fn wasm_chunk_store(&self) -> PathBuf {
+        self.snapshot_root.join("wasm_chunk_store.bin")
+    }
+
+    /// List of existing overlay files for the snapshotted wasm chunk store.
+    pub fn wasm_chunk_store_overlays(&self) -> Result<Vec<PathBuf>, LayoutError> {
+        self.overlays_impl("_wasm_chunk_store.overlay")
+    }
+
+    /// Name of a (potentially new) overlay file for the snapshotted wasm chunk store written at `height`.
+    pub fn wasm_chunk_store_overlay(&self, height: Height) -> PathBuf {
+        self.snapshot_root
+            .join(format!("{:016x}_wasm_chunk_store.overlay", height.get()))
+    }
 }
 
 fn open_for_write(path: &Path) -> Result<std::fs::File, LayoutError> {
@@ -1766,6 +1966,42 @@ impl From<CanisterStateBits> for pb_canister_state_bits::CanisterStateBits {
             canister_history: Some((&item.canister_history).into()),
             wasm_chunk_store_metadata: Some((&item.wasm_chunk_store_metadata).into()),
             total_query_stats: Some((&item.total_query_stats).into()),
+            snapshots_memory_usage: item.snapshots_memory_usage.get(),
+            next_snapshot_id: item.next_snapshot_id,
+            log_visibility: pb_canister_state_bits::LogVisibility::from(&item.log_visibility)
+                .into(),
+            canister_log_records: item
+                .canister_log
+                .records()
+                .iter()
+                .map(|record| record.into())
+                .collect(),
+            next_canister_log_record_idx: item.canister_log.next_idx(),
+            wasm_memory_limit: item.wasm_memory_limit.map(|v| v.get()),
+            environment_variables: item
+                .environment_variables
+                .into_iter()
+                .map(|(name, value)| pb_canister_state_bits::EnvironmentVariable { name, value })
+                .collect(),
+            total_update_method_stats: total_method_stats_into_protobuf(
+                &item.total_update_method_stats,
+            ),
+            wasm_memory_threshold: item.wasm_memory_threshold.map(|v| v.get()),
+            on_low_wasm_memory_hook_status:
+                pb_canister_state_bits::OnLowWasmMemoryHookStatus::from(
+                    &item.on_low_wasm_memory_hook_status,
+                )
+                .into(),
+            timers: item
+                .timers
+                .iter()
+                .map(
+                    |(timer_id, deadline)| pb_canister_state_bits::CanisterTimerEntry {
+                        timer_id,
+                        deadline_nanos: deadline.as_nanos_since_unix_epoch(),
+                    },
+                )
+                .collect(),
         }
     }
 }
@@ -1858,6 +2094,16 @@ impl TryFrom<pb_canister_state_bits::CanisterStateBits> for CanisterStateBits {
             )?,
             task_queue,
             global_timer_nanos: value.global_timer_nanos,
+            timers: value
+                .timers
+                .into_iter()
+                .map(|entry| {
+                    (
+                        entry.timer_id,
+                        Time::from_nanos_since_unix_epoch(entry.deadline_nanos),
+                    )
+                })
+                .collect(),
             canister_version: value.canister_version,
             consumed_cycles_since_replica_started_by_use_cases: value
                 .consumed_cycles_since_replica_started_by_use_cases
@@ -1887,6 +2133,93 @@ impl TryFrom<pb_canister_state_bits::CanisterStateBits> for CanisterStateBits {
                 "CanisterStateBits::total_query_stats",
             )
             .unwrap_or_default(),
+            snapshots_memory_usage: NumBytes::from(value.snapshots_memory_usage),
+            next_snapshot_id: value.next_snapshot_id,
+            log_visibility: pb_canister_state_bits::LogVisibility::try_from(value.log_visibility)
+                .unwrap_or_default()
+                .into(),
+            canister_log: CanisterLog::new(
+                value.next_canister_log_record_idx,
+                value
+                    .canister_log_records
+                    .into_iter()
+                    .map(|record| record.into())
+                    .collect(),
+            ),
+            wasm_memory_limit: value.wasm_memory_limit.map(NumBytes::from),
+            wasm_memory_threshold: value.wasm_memory_threshold.map(NumBytes::from),
+            on_low_wasm_memory_hook_status:
+                pb_canister_state_bits::OnLowWasmMemoryHookStatus::try_from(
+                    value.on_low_wasm_memory_hook_status,
+                )
+                .unwrap_or_default()
+                .into(),
+            environment_variables: value
+                .environment_variables
+                .into_iter()
+                .map(|variable| (variable.name, variable.value))
+                .collect(),
+            total_update_method_stats: total_method_stats_try_from_protobuf(
+                value.total_update_method_stats,
+            )?,
+        })
+    }
+}
+
+impl From<CanisterSnapshotBits> for pb_canister_state_bits::CanisterSnapshotBits {
+    fn from(item: CanisterSnapshotBits) -> Self {
+        Self {
+            taken_at_timestamp: item.taken_at_timestamp.as_nanos_since_unix_epoch(),
+            canister_version: item.canister_version,
+            certified_data: item.certified_data,
+            global_timer_nanos: item.global_timer_nanos,
+            exported_globals: item
+                .exported_globals
+                .iter()
+                .map(|global| global.into())
+                .collect(),
+            wasm_memory_size: item.wasm_memory_size.get() as u64,
+            stable_memory_size: item.stable_memory_size.get() as u64,
+            wasm_chunk_store_metadata: Some((&item.wasm_chunk_store_metadata).into()),
+            binary_hash: item.binary_hash.as_ref().map(|h| h.to_vec()),
+        }
+    }
+}
+
+impl TryFrom<pb_canister_state_bits::CanisterSnapshotBits> for CanisterSnapshotBits {
+    type Error = ProxyDecodeError;
+
+    fn try_from(value: pb_canister_state_bits::CanisterSnapshotBits) -> Result<Self, Self::Error> {
+        let mut exported_globals = Vec::with_capacity(value.exported_globals.len());
+        for g in value.exported_globals.into_iter() {
+            exported_globals.push(g.try_into()?);
+        }
+        let binary_hash = match value.binary_hash {
+            Some(hash) => {
+                let hash: [u8; 32] =
+                    hash.try_into()
+                        .map_err(|e| ProxyDecodeError::ValueOutOfRange {
+                            typ: "BinaryHash",
+                            err: format!("Expected a 32-byte long module hash, got {:?}", e),
+                        })?;
+                Some(hash.into())
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            taken_at_timestamp: Time::from_nanos_since_unix_epoch(value.taken_at_timestamp),
+            canister_version: value.canister_version,
+            certified_data: value.certified_data,
+            global_timer_nanos: value.global_timer_nanos,
+            exported_globals,
+            wasm_memory_size: NumWasmPages::from(value.wasm_memory_size as usize),
+            stable_memory_size: NumWasmPages::from(value.stable_memory_size as usize),
+            wasm_chunk_store_metadata: try_from_option_field(
+                value.wasm_chunk_store_metadata,
+                "CanisterSnapshotBits::wasm_chunk_store_metadata",
+            )?,
+            binary_hash,
         })
     }
 }
@@ -1912,6 +2245,8 @@ impl From<&ExecutionStateBits> for pb_canister_state_bits::ExecutionStateBits {
                 pb_canister_state_bits::NextScheduledMethod::from(item.next_scheduled_method)
                     .into(),
             ),
+            is_wasm64: item.is_wasm64,
+            wasm_memory_max_pages: item.wasm_memory_max_pages,
         }
     }
 }
@@ -1951,6 +2286,8 @@ impl TryFrom<pb_canister_state_bits::ExecutionStateBits> for ExecutionStateBits
                     .into(),
                 None => NextScheduledMethod::default(),
             },
+            is_wasm64: value.is_wasm64,
+            wasm_memory_max_pages: value.wasm_memory_max_pages,
         })
     }
 }
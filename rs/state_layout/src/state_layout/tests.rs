@@ -1,7 +1,8 @@
 use super::*;
 
 use ic_ic00_types::{
-    CanisterChange, CanisterChangeDetails, CanisterChangeOrigin, CanisterInstallMode, IC_00,
+    CanisterChange, CanisterChangeDetails, CanisterChangeOrigin, CanisterInstallMode,
+    CanisterLogRecord, IC_00,
 };
 use ic_replicated_state::canister_state::system_state::CanisterHistory;
 use ic_replicated_state::metadata_state::subnet_call_context_manager::InstallCodeCallId;
@@ -48,11 +49,21 @@ fn default_canister_state_bits() -> CanisterStateBits {
         time_of_last_allocation_charge_nanos: mock_time().as_nanos_since_unix_epoch(),
         task_queue: vec![],
         global_timer_nanos: None,
+        timers: CanisterTimers::default(),
         canister_version: 0,
         consumed_cycles_since_replica_started_by_use_cases: BTreeMap::new(),
         canister_history: CanisterHistory::default(),
         wasm_chunk_store_metadata: WasmChunkStoreMetadata::default(),
         total_query_stats: TotalQueryStats::default(),
+        snapshots_memory_usage: NumBytes::from(0),
+        next_snapshot_id: 0,
+        log_visibility: LogVisibility::default(),
+        canister_log: CanisterLog::default(),
+        wasm_memory_limit: None,
+        wasm_memory_threshold: None,
+        on_low_wasm_memory_hook_status: OnLowWasmMemoryHookStatus::default(),
+        environment_variables: BTreeMap::new(),
+        total_update_method_stats: BTreeMap::new(),
     }
 }
 
@@ -92,6 +103,26 @@ fn test_encode_decode_empty_controllers() {
     assert_eq!(canister_state_bits.controllers, BTreeSet::new());
 }
 
+#[test]
+fn test_encode_decode_canister_timers() {
+    let timers: CanisterTimers = [
+        (1, Time::from_nanos_since_unix_epoch(20)),
+        (7, Time::from_nanos_since_unix_epoch(10)),
+    ]
+    .into_iter()
+    .collect();
+
+    let canister_state_bits = CanisterStateBits {
+        timers: timers.clone(),
+        ..default_canister_state_bits()
+    };
+
+    let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+    let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+
+    assert_eq!(canister_state_bits.timers, timers);
+}
+
 #[test]
 fn test_encode_decode_non_empty_controllers() {
     let mut controllers = BTreeSet::new();
@@ -180,6 +211,12 @@ fn test_encode_decode_non_empty_history() {
         CanisterChangeOrigin::from_canister(canister_test_id(123).get(), None),
         CanisterChangeDetails::controllers_change(vec![]),
     ));
+    canister_history.add_canister_change(CanisterChange::new(
+        555,
+        7,
+        CanisterChangeOrigin::from_user(user_test_id(42).get()),
+        CanisterChangeDetails::load_snapshot(3, vec![0, 1, 2, 3], 222),
+    ));
 
     // A canister state with non-empty history.
     let canister_state_bits = CanisterStateBits {
@@ -193,6 +230,33 @@ fn test_encode_decode_non_empty_history() {
     assert_eq!(canister_state_bits.canister_history, canister_history);
 }
 
+#[test]
+fn test_encode_decode_canister_log() {
+    let mut canister_log = CanisterLog::new_with_next_index(42);
+    canister_log.add_record(1_000, b"first".to_vec());
+    canister_log.add_record(2_000, b"second".to_vec());
+
+    let canister_state_bits = CanisterStateBits {
+        log_visibility: LogVisibility::Public,
+        canister_log: canister_log.clone(),
+        ..default_canister_state_bits()
+    };
+
+    let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+    let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+
+    assert_eq!(canister_state_bits.log_visibility, LogVisibility::Public);
+    assert_eq!(canister_state_bits.canister_log, canister_log);
+    assert_eq!(
+        canister_state_bits.canister_log.records().back(),
+        Some(&CanisterLogRecord {
+            idx: 43,
+            timestamp_nanos: 2_000,
+            content: b"second".to_vec(),
+        })
+    );
+}
+
 #[test]
 fn test_encode_decode_task_queue() {
     let ingress = Arc::new(IngressBuilder::new().method_name("test_ingress").build());
@@ -236,6 +300,99 @@ fn test_encode_decode_task_queue() {
     assert_eq!(canister_state_bits.task_queue, task_queue);
 }
 
+#[test]
+fn test_encode_decode_wasm_memory_limit() {
+    for wasm_memory_limit in [None, Some(NumBytes::new(0)), Some(NumBytes::new(1 << 32))] {
+        let canister_state_bits = CanisterStateBits {
+            wasm_memory_limit,
+            ..default_canister_state_bits()
+        };
+
+        let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+        let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+        assert_eq!(canister_state_bits.wasm_memory_limit, wasm_memory_limit);
+    }
+}
+
+#[test]
+fn test_encode_decode_on_low_wasm_memory_hook() {
+    for on_low_wasm_memory_hook_status in [
+        OnLowWasmMemoryHookStatus::ConditionNotSatisfied,
+        OnLowWasmMemoryHookStatus::Ready,
+        OnLowWasmMemoryHookStatus::Executed,
+    ] {
+        let wasm_memory_threshold = Some(NumBytes::new(1 << 20));
+        let canister_state_bits = CanisterStateBits {
+            wasm_memory_threshold,
+            on_low_wasm_memory_hook_status,
+            ..default_canister_state_bits()
+        };
+
+        let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+        let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+        assert_eq!(
+            canister_state_bits.wasm_memory_threshold,
+            wasm_memory_threshold
+        );
+        assert_eq!(
+            canister_state_bits.on_low_wasm_memory_hook_status,
+            on_low_wasm_memory_hook_status
+        );
+    }
+}
+
+#[test]
+fn test_encode_decode_environment_variables() {
+    let environment_variables = BTreeMap::from([
+        ("FEATURE_FLAG".to_string(), "enabled".to_string()),
+        (
+            "PEER".to_string(),
+            "rwlgt-iiaaa-aaaaa-aaaaa-cai".to_string(),
+        ),
+    ]);
+    let canister_state_bits = CanisterStateBits {
+        environment_variables: environment_variables.clone(),
+        ..default_canister_state_bits()
+    };
+
+    let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+    let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+    assert_eq!(
+        canister_state_bits.environment_variables,
+        environment_variables
+    );
+}
+
+#[test]
+fn test_encode_decode_method_stats() {
+    let method_stats = TotalMethodStats {
+        num_calls: 5,
+        num_errors: 1,
+        num_instructions: u64::MAX as u128 + 1,
+        ingress_payload_size: 100,
+        egress_payload_size: 200,
+    };
+    let total_query_stats = TotalQueryStats {
+        num_calls: 5,
+        method_stats: BTreeMap::from([("query".to_string(), method_stats.clone())]),
+        ..TotalQueryStats::default()
+    };
+    let total_update_method_stats = BTreeMap::from([("update".to_string(), method_stats)]);
+    let canister_state_bits = CanisterStateBits {
+        total_query_stats: total_query_stats.clone(),
+        total_update_method_stats: total_update_method_stats.clone(),
+        ..default_canister_state_bits()
+    };
+
+    let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+    let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+    assert_eq!(canister_state_bits.total_query_stats, total_query_stats);
+    assert_eq!(
+        canister_state_bits.total_update_method_stats,
+        total_update_method_stats
+    );
+}
+
 #[test]
 fn test_removal_when_last_dropped() {
     with_test_replica_logger(|log| {
@@ -353,6 +510,26 @@ fn test_canister_id_from_path() {
     );
 }
 
+#[test]
+fn test_snapshot_ids() {
+    let tempdir = tmpdir("state_layout");
+    let checkpoint_layout =
+        CheckpointLayout::<WriteOnly>::new_untracked(tempdir.path().to_path_buf(), Height::new(1))
+            .unwrap();
+    let canister_id = canister_test_id(1);
+    let canister_layout = checkpoint_layout.canister(&canister_id).unwrap();
+    assert_eq!(canister_layout.snapshot_ids().unwrap(), vec![]);
+
+    let snapshot_ids = vec![
+        SnapshotId::new(canister_id, 0),
+        SnapshotId::new(canister_id, 17),
+    ];
+    for snapshot_id in snapshot_ids.iter() {
+        canister_layout.snapshot(snapshot_id).unwrap();
+    }
+    assert_eq!(canister_layout.snapshot_ids().unwrap(), snapshot_ids);
+}
+
 // A strategy to create a randomly sampled and strictly monotonic sequence of `Height`.
 fn random_sorted_unique_heights(max_length: usize) -> impl Strategy<Value = Vec<Height>> {
     // Take a vector of length max_length, sort it and remove duplicate entries.
@@ -16,7 +16,7 @@ use ic_crypto_test_utils_keys::public_keys::valid_node_signing_public_key;
 use ic_crypto_tree_hash::{flatmap, Label, LabeledTree, LabeledTree::SubTree};
 use ic_cycles_account_manager::CyclesAccountManager;
 pub use ic_error_types::{ErrorCode, UserError};
-use ic_execution_environment::{ExecutionServices, IngressHistoryReaderImpl};
+use ic_execution_environment::{ExecutionServices, IngressHistoryReaderImpl, WasmProfiler};
 use ic_ic00_types::{
     self as ic00, CanisterIdRecord, CanisterStatusResultV2, InstallCodeArgs, Method, Payload,
 };
@@ -94,7 +94,7 @@ use ic_types::crypto::{
     CombinedThresholdSigOf, KeyPurpose, Signable, Signed,
 };
 use ic_types::malicious_flags::MaliciousFlags;
-use ic_types::messages::{CallbackId, Certificate, RejectContext, Response};
+use ic_types::messages::{CallbackId, Certificate, RejectContext, Response, NO_DEADLINE};
 use ic_types::signature::ThresholdSignature;
 use ic_types::time::GENESIS;
 use ic_types::xnet::CertifiedStreamSlice;
@@ -527,6 +527,7 @@ pub struct StateMachine {
     time: std::sync::atomic::AtomicU64,
     ecdsa_subnet_public_keys: BTreeMap<EcdsaKeyId, MasterEcdsaPublicKey>,
     replica_logger: ReplicaLogger,
+    wasm_profiler: Option<Arc<WasmProfiler>>,
 }
 
 impl Default for StateMachine {
@@ -566,6 +567,7 @@ pub struct StateMachineBuilder {
     features: SubnetFeatures,
     runtime: Option<Arc<Runtime>>,
     registry_data_provider: Arc<ProtoRegistryDataProvider>,
+    wasm_profiler: bool,
 }
 
 impl StateMachineBuilder {
@@ -596,6 +598,7 @@ impl StateMachineBuilder {
             },
             runtime: None,
             registry_data_provider: Arc::new(ProtoRegistryDataProvider::new()),
+            wasm_profiler: false,
         }
     }
 
@@ -732,6 +735,15 @@ impl StateMachineBuilder {
         }
     }
 
+    /// Profiles the executed canister code, see
+    /// [`StateMachine::wasm_profile_folded_stacks`].
+    pub fn with_wasm_profiler(self) -> Self {
+        Self {
+            wasm_profiler: true,
+            ..self
+        }
+    }
+
     pub fn build(self) -> StateMachine {
         let mut routing_table = self.routing_table;
         if routing_table.is_empty() {
@@ -768,6 +780,7 @@ impl StateMachineBuilder {
             }),
             registry_version,
             self.registry_data_provider,
+            self.wasm_profiler,
         )
     }
 
@@ -929,6 +942,7 @@ impl StateMachine {
                 originator_reply_callback: id,
                 refund: Cycles::zero(),
                 response_payload: MsgPayload::Data(reply.encode()),
+                deadline: NO_DEADLINE,
             });
         }
 
@@ -961,6 +975,7 @@ impl StateMachine {
         runtime: Arc<Runtime>,
         registry_version: RegistryVersion,
         registry_data_provider: Arc<ProtoRegistryDataProvider>,
+        wasm_profiler: bool,
     ) -> Self {
         let replica_logger = replica_logger();
 
@@ -989,6 +1004,13 @@ impl StateMachine {
             hypervisor_config.deterministic_time_slicing = FlagStatus::Disabled;
         }
 
+        if wasm_profiler {
+            hypervisor_config
+                .embedders_config
+                .feature_flags
+                .wasm_profiler = FlagStatus::Enabled;
+        }
+
         // We are not interested in ingress signature validation.
         let malicious_flags = MaliciousFlags {
             maliciously_disable_ingress_validation: true,
@@ -1032,6 +1054,7 @@ impl StateMachine {
                 Arc::clone(&cycles_account_manager),
                 Arc::clone(&state_manager) as Arc<_>,
                 Arc::clone(&state_manager.get_fd_factory()),
+                None,
             )
         });
 
@@ -1150,6 +1173,7 @@ impl StateMachine {
             time: std::sync::atomic::AtomicU64::new(time.as_nanos_since_unix_epoch()),
             ecdsa_subnet_public_keys,
             replica_logger,
+            wasm_profiler: execution_services.wasm_profiler,
         }
     }
 
@@ -1197,6 +1221,26 @@ impl StateMachine {
             .build()
     }
 
+    /// Returns the instructions executed by the canisters since the state
+    /// machine was built, attributed to their Wasm call stacks. The result is
+    /// in the folded stacks format that flamegraph tools like
+    /// `inferno-flamegraph` render.
+    ///
+    /// Panics if the state machine was not built with
+    /// [`StateMachineBuilder::with_wasm_profiler`].
+    pub fn wasm_profile_folded_stacks(&self) -> String {
+        self.wasm_profiler
+            .as_ref()
+            .expect("The state machine was not built with the Wasm profiler")
+            .folded_stacks()
+    }
+
+    /// Writes the result of [`Self::wasm_profile_folded_stacks`] to the given
+    /// file.
+    pub fn write_wasm_profile(&self, path: &Path) -> io::Result<()> {
+        std::fs::write(path, self.wasm_profile_folded_stacks())
+    }
+
     /// If the argument is true, the state machine will create an on-disk
     /// checkpoint for each new state it creates.
     ///
@@ -1372,6 +1416,7 @@ impl StateMachine {
                 originator_reply_callback: id,
                 refund: Cycles::zero(),
                 response_payload: MsgPayload::Data(reply.encode()),
+                deadline: NO_DEADLINE,
             });
         }
         self.execute_payload(payload);
@@ -2598,6 +2643,7 @@ impl PayloadBuilder {
             originator_reply_callback: id,
             refund: Cycles::zero(),
             response_payload: MsgPayload::Data(payload.encode()),
+            deadline: NO_DEADLINE,
         });
         self
     }
@@ -2614,6 +2660,7 @@ impl PayloadBuilder {
             originator_reply_callback: id,
             refund: Cycles::zero(),
             response_payload: MsgPayload::Reject(RejectContext::new(code, message)),
+            deadline: NO_DEADLINE,
         });
         self
     }
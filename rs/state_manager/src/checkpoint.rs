@@ -8,11 +8,15 @@ use ic_logger::error;
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::page_map::PageAllocatorFileDescriptor;
 use ic_replicated_state::{
-    canister_state::execution_state::WasmBinary, page_map::PageMap, CanisterMetrics, CanisterState,
-    ExecutionState, ReplicatedState, SchedulerState, SystemState,
+    canister_snapshots::ExecutionStateSnapshot, canister_state::execution_state::WasmBinary,
+    page_map::PageMap, CanisterMetrics, CanisterSnapshot, CanisterSnapshots, CanisterState,
+    ExecutionState, ReplicatedState, SchedulerState, SnapshotId, SystemState,
 };
 use ic_replicated_state::{CheckpointLoadingMetrics, Memory};
-use ic_state_layout::{CanisterLayout, CanisterStateBits, CheckpointLayout, ReadOnly, ReadPolicy};
+use ic_state_layout::{
+    CanisterLayout, CanisterSnapshotBits, CanisterSnapshotLayout, CanisterStateBits,
+    CheckpointLayout, ReadOnly, ReadPolicy,
+};
 use ic_types::batch::RawQueryStats;
 use ic_types::{CanisterTimer, Height, LongExecutionMode, Time};
 use ic_utils::thread::parallel_map;
@@ -75,6 +79,13 @@ pub(crate) fn make_checkpoint(
         })
         .unwrap();
 
+    tip_channel
+        .send(TipRequest::FilterTipSnapshots {
+            height,
+            ids: state.canister_snapshots.ids().copied().collect(),
+        })
+        .unwrap();
+
     let cp = {
         let _timer = metrics
             .make_checkpoint_step_duration
@@ -246,8 +257,35 @@ pub fn load_checkpoint<P: ReadPolicy + Send + Sync>(
         canister_states
     };
 
-    let state =
-        ReplicatedState::new_from_checkpoint(canister_states, metadata, subnet_queues, query_stats);
+    let canister_snapshots = {
+        let _timer = metrics
+            .load_checkpoint_step_duration
+            .with_label_values(&["canister_snapshots"])
+            .start_timer();
+
+        let mut canister_snapshots = BTreeMap::new();
+        for canister_id in canister_states.keys() {
+            let canister_layout = checkpoint_layout.canister(canister_id)?;
+            for snapshot_id in canister_layout.snapshot_ids()? {
+                let snapshot = load_snapshot(
+                    &canister_layout.snapshot(&snapshot_id)?,
+                    &snapshot_id,
+                    checkpoint_layout.height(),
+                    Arc::clone(&fd_factory),
+                )?;
+                canister_snapshots.insert(snapshot_id, snapshot);
+            }
+        }
+        CanisterSnapshots::new(canister_snapshots)
+    };
+
+    let state = ReplicatedState::new_from_checkpoint(
+        canister_states,
+        metadata,
+        subnet_queues,
+        query_stats,
+        canister_snapshots,
+    );
 
     Ok(state)
 }
@@ -344,6 +382,8 @@ pub fn load_canister_state<P: ReadPolicy>(
                 metadata: execution_state_bits.metadata,
                 last_executed_round: execution_state_bits.last_executed_round,
                 next_scheduled_method: execution_state_bits.next_scheduled_method,
+                is_wasm64: execution_state_bits.is_wasm64,
+                wasm_memory_max_pages: execution_state_bits.wasm_memory_max_pages,
             })
         }
         None => None,
@@ -398,10 +438,19 @@ pub fn load_canister_state<P: ReadPolicy>(
         canister_state_bits.reserved_balance_limit,
         canister_state_bits.task_queue.into_iter().collect(),
         CanisterTimer::from_nanos_since_unix_epoch(canister_state_bits.global_timer_nanos),
+        canister_state_bits.timers,
         canister_state_bits.canister_version,
         canister_state_bits.canister_history,
         wasm_chunk_store_data,
         canister_state_bits.wasm_chunk_store_metadata,
+        canister_state_bits.snapshots_memory_usage,
+        canister_state_bits.next_snapshot_id,
+        canister_state_bits.log_visibility,
+        canister_state_bits.canister_log,
+        canister_state_bits.wasm_memory_limit,
+        canister_state_bits.wasm_memory_threshold,
+        canister_state_bits.on_low_wasm_memory_hook_status,
+        canister_state_bits.environment_variables,
     );
 
     let canister_state = CanisterState {
@@ -421,6 +470,7 @@ pub fn load_canister_state<P: ReadPolicy>(
                 canister_state_bits.time_of_last_allocation_charge_nanos,
             ),
             total_query_stats: canister_state_bits.total_query_stats,
+            total_update_method_stats: canister_state_bits.total_update_method_stats,
         },
     };
 
@@ -429,6 +479,67 @@ pub fn load_canister_state<P: ReadPolicy>(
     Ok((canister_state, metrics))
 }
 
+/// Loads the canister snapshot stored under `snapshot_layout`.
+pub fn load_snapshot<P: ReadPolicy>(
+    snapshot_layout: &CanisterSnapshotLayout<P>,
+    snapshot_id: &SnapshotId,
+    height: Height,
+    fd_factory: Arc<dyn PageAllocatorFileDescriptor>,
+) -> Result<CanisterSnapshot, CheckpointError> {
+    let snapshot_bits: CanisterSnapshotBits = CanisterSnapshotBits::try_from(
+        snapshot_layout.snapshot().deserialize()?,
+    )
+    .map_err(|err| CheckpointError::ProtoError {
+        path: snapshot_layout.raw_path(),
+        field: format!("canister_snapshots[{}]::snapshot_bits", snapshot_id),
+        proto_err: err.to_string(),
+    })?;
+
+    let wasm_memory = Memory::new(
+        PageMap::open(
+            &snapshot_layout.vmemory_0(),
+            &snapshot_layout.vmemory_0_overlays()?,
+            height,
+            Arc::clone(&fd_factory),
+        )?,
+        snapshot_bits.wasm_memory_size,
+    );
+    let stable_memory = Memory::new(
+        PageMap::open(
+            &snapshot_layout.stable_memory_blob(),
+            &snapshot_layout.stable_memory_overlays()?,
+            height,
+            Arc::clone(&fd_factory),
+        )?,
+        snapshot_bits.stable_memory_size,
+    );
+    let chunk_store_data = PageMap::open(
+        &snapshot_layout.wasm_chunk_store(),
+        &snapshot_layout.wasm_chunk_store_overlays()?,
+        height,
+        Arc::clone(&fd_factory),
+    )?;
+    let wasm_binary = snapshot_layout
+        .wasm()
+        .deserialize(snapshot_bits.binary_hash)?;
+
+    Ok(CanisterSnapshot::new_from_checkpoint(
+        snapshot_id.canister_id(),
+        snapshot_bits.taken_at_timestamp,
+        snapshot_bits.canister_version,
+        snapshot_bits.certified_data,
+        CanisterTimer::from_nanos_since_unix_epoch(snapshot_bits.global_timer_nanos),
+        chunk_store_data,
+        snapshot_bits.wasm_chunk_store_metadata,
+        ExecutionStateSnapshot {
+            wasm_binary,
+            exported_globals: snapshot_bits.exported_globals,
+            wasm_memory,
+            stable_memory,
+        },
+    ))
+}
+
 fn load_canister_state_from_checkpoint<P: ReadPolicy>(
     checkpoint_layout: &CheckpointLayout<P>,
     canister_id: &CanisterId,
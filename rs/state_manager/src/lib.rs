@@ -42,9 +42,12 @@ use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::{
     canister_state::execution_state::SandboxMemory,
     page_map::{PersistenceError, StorageMetrics},
-    PageIndex, PageMap, ReplicatedState,
+    PageIndex, PageMap, ReplicatedState, SnapshotId,
+};
+use ic_state_layout::{
+    error::LayoutError, AccessPolicy, CanisterSnapshotLayout, CheckpointLayout, ReadOnly,
+    StateLayout,
 };
-use ic_state_layout::{error::LayoutError, AccessPolicy, CheckpointLayout, ReadOnly, StateLayout};
 use ic_types::{
     consensus::certification::Certification,
     crypto::CryptoHash,
@@ -1015,6 +1018,9 @@ pub enum PageMapType {
     WasmMemory(CanisterId),
     StableMemory(CanisterId),
     WasmChunkStore(CanisterId),
+    SnapshotWasmMemory(SnapshotId),
+    SnapshotStableMemory(SnapshotId),
+    SnapshotWasmChunkStore(SnapshotId),
 }
 
 impl PageMapType {
@@ -1028,6 +1034,11 @@ impl PageMapType {
                 result.push(Self::StableMemory(id.to_owned()));
             }
         }
+        for id in state.canister_snapshots.ids() {
+            result.push(Self::SnapshotWasmMemory(id.to_owned()));
+            result.push(Self::SnapshotStableMemory(id.to_owned()));
+            result.push(Self::SnapshotWasmChunkStore(id.to_owned()));
+        }
 
         result
     }
@@ -1041,6 +1052,13 @@ impl PageMapType {
             PageMapType::WasmMemory(id) => Ok(layout.canister(id)?.vmemory_0()),
             PageMapType::StableMemory(id) => Ok(layout.canister(id)?.stable_memory_blob()),
             PageMapType::WasmChunkStore(id) => Ok(layout.canister(id)?.wasm_chunk_store()),
+            PageMapType::SnapshotWasmMemory(id) => Ok(snapshot_layout(layout, id)?.vmemory_0()),
+            PageMapType::SnapshotStableMemory(id) => {
+                Ok(snapshot_layout(layout, id)?.stable_memory_blob())
+            }
+            PageMapType::SnapshotWasmChunkStore(id) => {
+                Ok(snapshot_layout(layout, id)?.wasm_chunk_store())
+            }
         }
     }
 
@@ -1059,6 +1077,15 @@ impl PageMapType {
             PageMapType::WasmChunkStore(id) => {
                 Ok(layout.canister(id)?.wasm_chunk_store_overlay(height))
             }
+            PageMapType::SnapshotWasmMemory(id) => {
+                Ok(snapshot_layout(layout, id)?.vmemory_0_overlay(height))
+            }
+            PageMapType::SnapshotStableMemory(id) => {
+                Ok(snapshot_layout(layout, id)?.stable_memory_overlay(height))
+            }
+            PageMapType::SnapshotWasmChunkStore(id) => {
+                Ok(snapshot_layout(layout, id)?.wasm_chunk_store_overlay(height))
+            }
         }
     }
 
@@ -1074,6 +1101,15 @@ impl PageMapType {
             PageMapType::WasmMemory(id) => layout.canister(id)?.vmemory_0_overlays(),
             PageMapType::StableMemory(id) => layout.canister(id)?.stable_memory_overlays(),
             PageMapType::WasmChunkStore(id) => layout.canister(id)?.wasm_chunk_store_overlays(),
+            PageMapType::SnapshotWasmMemory(id) => {
+                snapshot_layout(layout, id)?.vmemory_0_overlays()
+            }
+            PageMapType::SnapshotStableMemory(id) => {
+                snapshot_layout(layout, id)?.stable_memory_overlays()
+            }
+            PageMapType::SnapshotWasmChunkStore(id) => {
+                snapshot_layout(layout, id)?.wasm_chunk_store_overlays()
+            }
         }
     }
 
@@ -1093,6 +1129,18 @@ impl PageMapType {
             PageMapType::WasmChunkStore(id) => state
                 .canister_state(id)
                 .map(|can| can.system_state.wasm_chunk_store.page_map()),
+            PageMapType::SnapshotWasmMemory(id) => state
+                .canister_snapshots
+                .get(id)
+                .map(|snapshot| &snapshot.execution_snapshot().wasm_memory.page_map),
+            PageMapType::SnapshotStableMemory(id) => state
+                .canister_snapshots
+                .get(id)
+                .map(|snapshot| &snapshot.execution_snapshot().stable_memory.page_map),
+            PageMapType::SnapshotWasmChunkStore(id) => state
+                .canister_snapshots
+                .get(id)
+                .map(|snapshot| snapshot.chunk_store().page_map()),
         }
     }
 
@@ -1112,10 +1160,35 @@ impl PageMapType {
             PageMapType::WasmChunkStore(id) => state
                 .canister_state_mut(id)
                 .map(|can| can.system_state.wasm_chunk_store.page_map_mut()),
+            PageMapType::SnapshotWasmMemory(id) => state
+                .canister_snapshots
+                .get_mut(id)
+                .map(|snapshot| &mut snapshot.execution_snapshot_mut().wasm_memory.page_map),
+            PageMapType::SnapshotStableMemory(id) => state
+                .canister_snapshots
+                .get_mut(id)
+                .map(|snapshot| &mut snapshot.execution_snapshot_mut().stable_memory.page_map),
+            PageMapType::SnapshotWasmChunkStore(id) => state
+                .canister_snapshots
+                .get_mut(id)
+                .map(|snapshot| snapshot.chunk_store_mut().page_map_mut()),
         }
     }
 }
 
+/// Returns the layout of the snapshot with the given ID inside `layout`.
+fn snapshot_layout<Access>(
+    layout: &CheckpointLayout<Access>,
+    snapshot_id: &SnapshotId,
+) -> Result<CanisterSnapshotLayout<Access>, LayoutError>
+where
+    Access: AccessPolicy,
+{
+    layout
+        .canister(&snapshot_id.canister_id())?
+        .snapshot(snapshot_id)
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct DirtyPageMap {
     pub height: Height,
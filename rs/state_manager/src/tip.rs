@@ -14,12 +14,12 @@ use ic_protobuf::state::{
 use ic_replicated_state::page_map::{PersistDestination, StorageMetrics};
 #[allow(unused)]
 use ic_replicated_state::{
-    canister_state::execution_state::SandboxMemory, CanisterState, NumWasmPages, PageMap,
-    ReplicatedState,
+    canister_state::execution_state::SandboxMemory, CanisterSnapshot, CanisterState, NumWasmPages,
+    PageMap, ReplicatedState, SnapshotId,
 };
 use ic_state_layout::{
-    error::LayoutError, CanisterStateBits, CheckpointLayout, ExecutionStateBits, ReadOnly,
-    RwPolicy, StateLayout, TipHandler,
+    error::LayoutError, CanisterSnapshotBits, CanisterStateBits, CheckpointLayout,
+    ExecutionStateBits, ReadOnly, RwPolicy, StateLayout, TipHandler,
 };
 use ic_types::state_sync::{
     FILE_GROUP_CHUNK_ID_OFFSET, MANIFEST_CHUNK_ID_OFFSET, MAX_SUPPORTED_STATE_SYNC_VERSION,
@@ -76,6 +76,12 @@ pub(crate) enum TipRequest {
         height: Height,
         ids: BTreeSet<CanisterId>,
     },
+    /// Filter canister snapshots in tip. Remove ones not present in the set.
+    /// State: !Empty
+    FilterTipSnapshots {
+        height: Height,
+        ids: BTreeSet<SnapshotId>,
+    },
     /// Flush PageMaps's unflushed delta on disc.
     /// State: ReadyForPageDeltas(h) -> ReadyForPageDeltas(height), height >= h
     FlushPageMapDelta {
@@ -185,6 +191,21 @@ pub(crate) fn spawn_tip_thread(
                                     )
                                 });
                         }
+                        TipRequest::FilterTipSnapshots { height, ids } => {
+                            debug_assert_ne!(tip_state, TipState::Empty);
+
+                            let _timer = request_timer(&metrics, "filter_tip_snapshots");
+                            tip_handler
+                                .filter_tip_snapshots(height, &ids)
+                                .unwrap_or_else(|err| {
+                                    fatal!(
+                                        log,
+                                        "Failed to filter tip snapshots for height @{}: {}",
+                                        height,
+                                        err
+                                    )
+                                });
+                        }
                         TipRequest::TipToCheckpoint { height, sender } => {
                             debug_assert_eq!(tip_state, TipState::Serialized(height));
                             debug_assert!(have_latest_manifest);
@@ -459,6 +480,18 @@ fn serialize_to_tip(
         result?;
     }
 
+    let results = parallel_map(
+        thread_pool,
+        state.canister_snapshots.iter(),
+        |(snapshot_id, snapshot)| {
+            serialize_snapshot_to_tip(log, snapshot_id, snapshot, tip, metrics, lsmt_storage)
+        },
+    );
+
+    for result in results.into_iter() {
+        result?;
+    }
+
     Ok(())
 }
 
@@ -526,6 +559,8 @@ fn serialize_canister_to_tip(
                 metadata: execution_state.metadata.clone(),
                 binary_hash: Some(execution_state.wasm_binary.binary.module_hash().into()),
                 next_scheduled_method: execution_state.next_scheduled_method,
+                is_wasm64: execution_state.is_wasm64,
+                wasm_memory_max_pages: execution_state.wasm_memory_max_pages,
             })
         }
         None => {
@@ -611,6 +646,7 @@ fn serialize_canister_to_tip(
                 .system_state
                 .global_timer
                 .to_nanos_since_unix_epoch(),
+            timers: canister_state.system_state.timers.clone(),
             canister_version: canister_state.system_state.canister_version,
             consumed_cycles_since_replica_started_by_use_cases: canister_state
                 .system_state
@@ -624,6 +660,99 @@ fn serialize_canister_to_tip(
                 .metadata()
                 .clone(),
             total_query_stats: canister_state.scheduler_state.total_query_stats.clone(),
+            snapshots_memory_usage: canister_state.system_state.snapshots_memory_usage,
+            next_snapshot_id: canister_state.system_state.next_snapshot_id,
+            log_visibility: canister_state.system_state.log_visibility,
+            canister_log: canister_state.system_state.canister_log.clone(),
+            wasm_memory_limit: canister_state.system_state.wasm_memory_limit,
+            wasm_memory_threshold: canister_state.system_state.wasm_memory_threshold,
+            on_low_wasm_memory_hook_status: canister_state
+                .system_state
+                .on_low_wasm_memory_hook_status,
+            environment_variables: canister_state.system_state.environment_variables.clone(),
+            total_update_method_stats: canister_state
+                .scheduler_state
+                .total_update_method_stats
+                .clone(),
+        }
+        .into(),
+    )?;
+    Ok(())
+}
+
+fn serialize_snapshot_to_tip(
+    log: &ReplicaLogger,
+    snapshot_id: &SnapshotId,
+    snapshot: &CanisterSnapshot,
+    tip: &CheckpointLayout<RwPolicy<TipHandler>>,
+    metrics: &StorageMetrics,
+    lsmt_storage: FlagStatus,
+) -> Result<(), CheckpointError> {
+    let snapshot_layout = tip
+        .canister(&snapshot_id.canister_id())?
+        .snapshot(snapshot_id)?;
+    let execution_snapshot = snapshot.execution_snapshot();
+
+    let wasm_binary = &execution_snapshot.wasm_binary;
+    match wasm_binary.file() {
+        Some(path) => {
+            let wasm = snapshot_layout.wasm();
+            // The binary is either already in the snapshot directory, or it is still
+            // backed by the file of the canister it was taken from.
+            if !wasm.raw_path().exists() {
+                ic_state_layout::utils::do_copy(log, path, wasm.raw_path()).map_err(|io_err| {
+                    CheckpointError::IoError {
+                        path: path.to_path_buf(),
+                        message: "failed to copy Wasm file".to_string(),
+                        io_err: io_err.to_string(),
+                    }
+                })?;
+            }
+        }
+        None => {
+            snapshot_layout.wasm().serialize(wasm_binary)?;
+        }
+    }
+
+    let memory_dst = PersistDestination::new(
+        snapshot_layout.vmemory_0(),
+        snapshot_layout.vmemory_0_overlay(tip.height()),
+        lsmt_storage,
+    );
+    let stable_dst = PersistDestination::new(
+        snapshot_layout.stable_memory_blob(),
+        snapshot_layout.stable_memory_overlay(tip.height()),
+        lsmt_storage,
+    );
+    let wasm_chunk_store_dst = PersistDestination::new(
+        snapshot_layout.wasm_chunk_store(),
+        snapshot_layout.wasm_chunk_store_overlay(tip.height()),
+        lsmt_storage,
+    );
+    execution_snapshot
+        .wasm_memory
+        .page_map
+        .persist_delta(memory_dst, metrics)?;
+    execution_snapshot
+        .stable_memory
+        .page_map
+        .persist_delta(stable_dst, metrics)?;
+    snapshot
+        .chunk_store()
+        .page_map()
+        .persist_delta(wasm_chunk_store_dst, metrics)?;
+
+    snapshot_layout.snapshot().serialize(
+        CanisterSnapshotBits {
+            taken_at_timestamp: *snapshot.taken_at_timestamp(),
+            canister_version: snapshot.canister_version(),
+            certified_data: snapshot.certified_data().clone(),
+            global_timer_nanos: snapshot.global_timer().to_nanos_since_unix_epoch(),
+            exported_globals: execution_snapshot.exported_globals.clone(),
+            wasm_memory_size: execution_snapshot.wasm_memory.size,
+            stable_memory_size: execution_snapshot.stable_memory.size,
+            wasm_chunk_store_metadata: snapshot.chunk_store().metadata().clone(),
+            binary_hash: Some(wasm_binary.module_hash().into()),
         }
         .into(),
     )?;
@@ -193,6 +193,8 @@ mod tests {
                 metadata,
                 last_executed_round: ExecutionRound::from(0),
                 next_scheduled_method: NextScheduledMethod::default(),
+                is_wasm64: false,
+                wasm_memory_max_pages: None,
             };
             canister_state.execution_state = Some(execution_state);
 
@@ -341,6 +343,7 @@ mod tests {
             "80D4B528CC9E09C775273994261DD544D45EFFF90B655D90FC3A6E3F633ED718",
             "E1108326097AE9BF8212F333F4F46B9619B947CDF2A73F3223BBEBC6FC2033B6",
             "EEC0156BE3C97CE6D7E7FBE683FFB4641463648DB6AC6818DCF90114E6A9DA72",
+            "EEC0156BE3C97CE6D7E7FBE683FFB4641463648DB6AC6818DCF90114E6A9DA72",
         ];
 
         for certification_version in CertificationVersion::iter() {
@@ -525,6 +525,7 @@ fn query_stats_are_persisted() {
             num_instructions: 100000,
             ingress_payload_size: 100001,
             egress_payload_size: 100002,
+            method_stats: BTreeMap::new(),
         };
 
         let mut inner = BTreeMap::new();
@@ -3372,6 +3373,7 @@ fn certified_read_can_certify_node_public_keys_since_v12() {
                 subnet_type: SubnetType::System,
                 subnet_features: SubnetFeatures::default(),
                 ecdsa_keys_held: BTreeSet::new(),
+                max_inter_canister_payload_in_bytes: None,
             },
         );
 
@@ -4880,6 +4882,7 @@ fn query_stats_are_collected() {
                 num_instructions: INITIAL_VALUES,
                 ingress_payload_size: INITIAL_VALUES,
                 egress_payload_size: INITIAL_VALUES,
+                method_stats: BTreeMap::new(),
             },
         );
     }
@@ -4915,6 +4918,7 @@ fn query_stats_are_collected() {
                     num_instructions: 2,
                     ingress_payload_size: 3,
                     egress_payload_size: 4,
+                    method_stats: BTreeMap::new(),
                 },
             });
 
@@ -4928,6 +4932,7 @@ fn query_stats_are_collected() {
                     num_instructions: 2,
                     ingress_payload_size: 3,
                     egress_payload_size: 4,
+                    method_stats: BTreeMap::new(),
                 },
             });
 
@@ -4940,6 +4945,7 @@ fn query_stats_are_collected() {
                         num_instructions: 2,
                         ingress_payload_size: 3,
                         egress_payload_size: 4,
+                        method_stats: BTreeMap::new(),
                     },
                 });
             } else {
@@ -4951,6 +4957,7 @@ fn query_stats_are_collected() {
                         num_instructions: 2,
                         ingress_payload_size: 3,
                         egress_payload_size: 4,
+                        method_stats: BTreeMap::new(),
                     },
                 });
             }
@@ -1,8 +1,12 @@
 //! Command implementations.
+pub mod canister_diff;
+pub mod canister_info;
 pub mod cdiff;
 pub mod chash;
 pub mod convert_ids;
 pub mod decode;
+pub mod export_canister;
+pub mod import_canister;
 pub mod import_state;
 pub mod list;
 pub mod manifest;
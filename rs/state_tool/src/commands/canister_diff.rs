@@ -0,0 +1,189 @@
+//! Diffs the persisted states of a single canister across two checkpoints.
+
+use crate::commands::utils::{canister_layout, open_page_map};
+use ic_replicated_state::{PageIndex, PageMap};
+use ic_state_layout::{CanisterLayout, ReadOnly};
+use ic_types::CanisterId;
+use std::path::{Path, PathBuf};
+
+/// Returns the indices of all pages that differ between `a` and `b`.
+fn diff_page_maps(a: &PageMap, b: &PageMap) -> Vec<PageIndex> {
+    (0..a.num_host_pages().max(b.num_host_pages()))
+        .map(|i| PageIndex::new(i as u64))
+        .filter(|&index| a.get_page(index) != b.get_page(index))
+        .collect()
+}
+
+/// Formats a sorted list of page indices as a list of inclusive ranges, e.g.
+/// `0-3, 7, 10-12`.
+fn format_page_ranges(indices: &[PageIndex]) -> String {
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+    for index in indices.iter().map(|index| index.get()) {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == index => *end = index,
+            _ => ranges.push((index, index)),
+        }
+    }
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Reads the raw contents of the file at `path`, or `None` if it does not exist.
+fn read_optional_file(path: &Path) -> Result<Option<Vec<u8>>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    std::fs::read(path)
+        .map(Some)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))
+}
+
+/// Prints whether the given component of the canister state differs and
+/// returns `true` iff it does.
+fn report(component: &str, identical: bool) -> bool {
+    if identical {
+        println!("✓ {} identical", component);
+    } else {
+        println!("✗ {} differs", component);
+    }
+    !identical
+}
+
+/// Diffs the page map (Wasm heap, stable memory or Wasm chunk store) named
+/// `component`, opened from each layout via `open`, and prints the differing
+/// pages. Returns `true` iff there are any.
+fn diff_memory(
+    component: &str,
+    layout_a: &CanisterLayout<ReadOnly>,
+    layout_b: &CanisterLayout<ReadOnly>,
+    open: impl Fn(&CanisterLayout<ReadOnly>) -> Result<PageMap, String>,
+) -> Result<bool, String> {
+    let pages = diff_page_maps(&open(layout_a)?, &open(layout_b)?);
+    if pages.is_empty() {
+        return Ok(report(component, true));
+    }
+    report(component, false);
+    println!(
+        "    {} differing page(s): {}",
+        pages.len(),
+        format_page_ranges(&pages)
+    );
+    Ok(true)
+}
+
+/// `canister_diff` command entry point: compares the system state, queues,
+/// Wasm binary and memories of canister `canister_id` in the checkpoints at
+/// `path_a` and `path_b`, the latter at the page level.
+pub fn do_canister_diff(
+    path_a: PathBuf,
+    path_b: PathBuf,
+    canister_id: CanisterId,
+) -> Result<(), String> {
+    let layout_a = canister_layout(&path_a, &canister_id)?;
+    let layout_b = canister_layout(&path_b, &canister_id)?;
+
+    let mut differs = false;
+
+    let bits_a = layout_a
+        .canister()
+        .deserialize()
+        .map_err(|e| format!("{:?}", e))?;
+    let bits_b = layout_b
+        .canister()
+        .deserialize()
+        .map_err(|e| format!("{:?}", e))?;
+    differs |= report("system state", bits_a == bits_b);
+    if bits_a.certified_data != bits_b.certified_data {
+        println!(
+            "    certified data: {} -> {}",
+            hex::encode(&bits_a.certified_data),
+            hex::encode(&bits_b.certified_data)
+        );
+    }
+
+    let queues_a = layout_a
+        .queues()
+        .deserialize()
+        .map_err(|e| format!("{:?}", e))?;
+    let queues_b = layout_b
+        .queues()
+        .deserialize()
+        .map_err(|e| format!("{:?}", e))?;
+    differs |= report("queues", queues_a == queues_b);
+
+    differs |= report(
+        "Wasm binary",
+        read_optional_file(layout_a.wasm().raw_path())?
+            == read_optional_file(layout_b.wasm().raw_path())?,
+    );
+
+    differs |= diff_memory("Wasm heap", &layout_a, &layout_b, |layout| {
+        open_page_map(
+            &layout.vmemory_0(),
+            &layout.vmemory_0_overlays().map_err(|e| e.to_string())?,
+        )
+    })?;
+    differs |= diff_memory("stable memory", &layout_a, &layout_b, |layout| {
+        open_page_map(
+            &layout.stable_memory_blob(),
+            &layout.stable_memory_overlays().map_err(|e| e.to_string())?,
+        )
+    })?;
+    differs |= diff_memory("Wasm chunk store", &layout_a, &layout_b, |layout| {
+        open_page_map(
+            &layout.wasm_chunk_store(),
+            &layout
+                .wasm_chunk_store_overlays()
+                .map_err(|e| e.to_string())?,
+        )
+    })?;
+
+    if !differs {
+        println!(
+            "✓ Canister {} is identical in both checkpoints",
+            canister_id
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_page_maps, format_page_ranges};
+    use ic_replicated_state::{PageIndex, PageMap};
+    use ic_sys::PAGE_SIZE;
+
+    #[test]
+    fn diff_page_maps_reports_differing_pages() {
+        let mut a = PageMap::new_for_testing();
+        let mut b = PageMap::new_for_testing();
+        let page = [1u8; PAGE_SIZE];
+        let other_page = [2u8; PAGE_SIZE];
+        a.update(&[(PageIndex::new(0), &page), (PageIndex::new(1), &page)]);
+        b.update(&[
+            (PageIndex::new(0), &page),
+            (PageIndex::new(1), &other_page),
+            (PageIndex::new(2), &page),
+            (PageIndex::new(3), &page),
+            (PageIndex::new(5), &page),
+        ]);
+
+        let pages = diff_page_maps(&a, &b);
+        assert_eq!(
+            vec![1, 2, 3, 5],
+            pages.iter().map(|index| index.get()).collect::<Vec<_>>()
+        );
+        assert_eq!("1-3, 5", format_page_ranges(&pages));
+        assert!(diff_page_maps(&a, &a).is_empty());
+    }
+}
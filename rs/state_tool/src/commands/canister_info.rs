@@ -0,0 +1,58 @@
+//! Displays the persisted state of a single canister in a checkpoint.
+
+use crate::commands::utils::canister_layout;
+use ic_replicated_state::canister_state::CanisterQueues;
+use ic_state_layout::{CanisterLayout, CanisterStateBits, ReadOnly};
+use ic_types::CanisterId;
+use std::convert::TryFrom;
+use std::path::PathBuf;
+
+/// Loads the `CanisterStateBits` persisted under `layout`.
+pub(crate) fn load_canister_state_bits(
+    layout: &CanisterLayout<ReadOnly>,
+) -> Result<CanisterStateBits, String> {
+    let pb = layout
+        .canister()
+        .deserialize()
+        .map_err(|e| format!("{:?}", e))?;
+    CanisterStateBits::try_from(pb)
+        .map_err(|e| format!("failed to decode canister state bits: {}", e))
+}
+
+/// `canister_info` command entry point: prints the system state, queues,
+/// certified data and history of canister `canister_id` in the checkpoint at
+/// `path`.
+pub fn do_canister_info(path: PathBuf, canister_id: CanisterId) -> Result<(), String> {
+    let layout = canister_layout(&path, &canister_id)?;
+
+    let bits = load_canister_state_bits(&layout)?;
+    let queues = CanisterQueues::try_from(
+        layout
+            .queues()
+            .deserialize()
+            .map_err(|e| format!("{:?}", e))?,
+    )
+    .map_err(|e| format!("failed to decode canister queues: {}", e))?;
+
+    println!("CANISTER: {}", canister_id);
+    println!("\nCERTIFIED DATA: {}", hex::encode(&bits.certified_data));
+    if let Some(execution_state_bits) = &bits.execution_state_bits {
+        println!(
+            "WASM HASH: {}",
+            execution_state_bits
+                .binary_hash
+                .as_ref()
+                .map(|hash| hex::encode(hash.to_slice()))
+                .unwrap_or_else(|| "<unknown>".to_string())
+        );
+        println!("HEAP SIZE: {} Wasm pages", execution_state_bits.heap_size);
+        println!("STABLE MEMORY SIZE: {} Wasm pages", bits.stable_memory_size);
+    } else {
+        println!("NO EXECUTION STATE (empty canister)");
+    }
+    println!("\nCANISTER HISTORY: {:#?}", bits.canister_history);
+    println!("\nQUEUES: {:#?}", queues);
+    println!("\nSYSTEM STATE: {:#?}", bits);
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+//! Exports the memories of a single canister in a checkpoint as raw files.
+
+use crate::commands::{
+    canister_info::load_canister_state_bits,
+    utils::{canister_layout, open_page_map},
+};
+use ic_replicated_state::{
+    canister_state::{num_bytes_try_from, NumWasmPages},
+    page_map::PAGE_SIZE,
+    PageIndex, PageMap,
+};
+use ic_types::CanisterId;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the file that the Wasm heap is exported to.
+pub const HEAP_FILE: &str = "heap.bin";
+/// Name of the file that the stable memory is exported to.
+pub const STABLE_MEMORY_FILE: &str = "stable_memory.bin";
+/// Name of the file that the Wasm binary is exported to.
+pub const WASM_FILE: &str = "canister.wasm";
+
+/// Writes the first `size` Wasm pages of `page_map` into the file at `path`.
+fn write_memory(page_map: &PageMap, size: NumWasmPages, path: &Path) -> Result<(), String> {
+    let size_bytes = num_bytes_try_from(size)
+        .map_err(|e| format!("invalid memory size {}: {}", size, e))?
+        .get() as usize;
+
+    let file = File::create(path)
+        .map_err(|e| format!("failed to create file {}: {}", path.display(), e))?;
+    let mut writer = BufWriter::new(file);
+    for page in 0..size_bytes / PAGE_SIZE {
+        writer
+            .write_all(page_map.get_page(PageIndex::new(page as u64)))
+            .map_err(|e| format!("failed to write to {}: {}", path.display(), e))?;
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("failed to write to {}: {}", path.display(), e))
+}
+
+/// `export_canister` command entry point: dumps the Wasm heap, stable memory
+/// and Wasm binary of canister `canister_id` in the checkpoint at `path` as
+/// raw files under `output`.
+pub fn do_export_canister(
+    path: PathBuf,
+    canister_id: CanisterId,
+    output: PathBuf,
+) -> Result<(), String> {
+    let layout = canister_layout(&path, &canister_id)?;
+    let bits = load_canister_state_bits(&layout)?;
+    let execution_state_bits = bits
+        .execution_state_bits
+        .ok_or_else(|| format!("canister {} has no execution state", canister_id))?;
+
+    std::fs::create_dir_all(&output)
+        .map_err(|e| format!("failed to create directory {}: {}", output.display(), e))?;
+
+    let heap = open_page_map(
+        &layout.vmemory_0(),
+        &layout.vmemory_0_overlays().map_err(|e| e.to_string())?,
+    )?;
+    write_memory(
+        &heap,
+        execution_state_bits.heap_size,
+        &output.join(HEAP_FILE),
+    )?;
+
+    let stable_memory = open_page_map(
+        &layout.stable_memory_blob(),
+        &layout.stable_memory_overlays().map_err(|e| e.to_string())?,
+    )?;
+    write_memory(
+        &stable_memory,
+        bits.stable_memory_size,
+        &output.join(STABLE_MEMORY_FILE),
+    )?;
+
+    let wasm = layout.wasm();
+    std::fs::copy(wasm.raw_path(), output.join(WASM_FILE)).map_err(|e| {
+        format!(
+            "failed to copy Wasm binary {}: {}",
+            wasm.raw_path().display(),
+            e
+        )
+    })?;
+
+    println!(
+        "Exported canister {} to {}: heap {} Wasm pages, stable memory {} Wasm pages",
+        canister_id,
+        output.display(),
+        execution_state_bits.heap_size,
+        bits.stable_memory_size
+    );
+
+    Ok(())
+}
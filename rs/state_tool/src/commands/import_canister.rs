@@ -0,0 +1,340 @@
+//! Imports the state of a single canister from one checkpoint into another.
+
+use crate::commands::{import_state::copy_recursively, utils::canister_layout};
+use ic_replicated_state::page_map::TestPageAllocatorFileDescriptorImpl;
+use ic_state_layout::{CanisterLayout, CheckpointLayout, ReadOnly, RwPolicy};
+use ic_state_manager::{
+    checkpoint::load_canister_state,
+    manifest::{manifest_from_path, manifest_hash},
+};
+use ic_types::{
+    state_sync::{FileInfo, Manifest},
+    CanisterId, Height,
+};
+use ic_utils::fs::copy_file_sparse;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Copies the file at `src` (if it exists) into directory `dst_dir`, under the
+/// same file name.
+fn copy_file_into(src: &Path, dst_dir: &Path) -> Result<(), String> {
+    if !src.exists() {
+        return Ok(());
+    }
+    let file_name = src
+        .file_name()
+        .ok_or_else(|| format!("failed to get file name of path {}", src.display()))?;
+    let dst = dst_dir.join(file_name);
+    copy_file_sparse(src, &dst).map(|_| ()).map_err(|e| {
+        format!(
+            "Failed to copy {} -> {}: {}",
+            src.display(),
+            dst.display(),
+            e
+        )
+    })
+}
+
+/// Copies the system state, queues, Wasm binary, Wasm heap, stable memory and
+/// Wasm chunk store (base files and overlays) of the canister under `src` into
+/// `dst`.
+fn copy_canister(
+    src: &CanisterLayout<ReadOnly>,
+    dst: &CanisterLayout<RwPolicy<()>>,
+) -> Result<(), String> {
+    let dst_dir = dst.raw_path();
+    let mut files = vec![
+        src.canister().raw_path().to_path_buf(),
+        src.queues().raw_path().to_path_buf(),
+        src.wasm().raw_path().to_path_buf(),
+        src.vmemory_0(),
+        src.stable_memory_blob(),
+        src.wasm_chunk_store(),
+    ];
+    files.extend(src.vmemory_0_overlays().map_err(|e| e.to_string())?);
+    files.extend(src.stable_memory_overlays().map_err(|e| e.to_string())?);
+    files.extend(src.wasm_chunk_store_overlays().map_err(|e| e.to_string())?);
+
+    for file in files {
+        copy_file_into(&file, &dst_dir)?;
+    }
+    Ok(())
+}
+
+/// Checks that the manifest of the `output` checkpoint lists exactly the files
+/// of the `source` checkpoint under `canister_dir` (the imported canister's
+/// directory) and exactly the files of the `target` checkpoint everywhere else.
+///
+/// File hashes are only compared if `source` and `output` were computed using
+/// the same state sync version, file sizes always are.
+fn check_imported_files(
+    source: &Manifest,
+    target: &Manifest,
+    output: &Manifest,
+    canister_dir: &Path,
+) -> Result<(), String> {
+    let partition = |manifest: &Manifest| {
+        manifest
+            .file_table
+            .iter()
+            .partition::<Vec<&FileInfo>, _>(|file| file.relative_path.starts_with(canister_dir))
+    };
+    let (source_canister_files, _) = partition(source);
+    let (_, target_other_files) = partition(target);
+    let (output_canister_files, output_other_files) = partition(output);
+
+    if output_other_files != target_other_files {
+        return Err(format!(
+            "Files outside of {} differ from the target checkpoint",
+            canister_dir.display()
+        ));
+    }
+
+    let compare_hashes = source.version == output.version;
+    let same_file = |a: &&FileInfo, b: &&FileInfo| {
+        a.relative_path == b.relative_path
+            && a.size_bytes == b.size_bytes
+            && (!compare_hashes || a.hash == b.hash)
+    };
+    if output_canister_files.len() != source_canister_files.len()
+        || !output_canister_files
+            .iter()
+            .zip(source_canister_files.iter())
+            .all(|(a, b)| same_file(a, b))
+    {
+        return Err(format!(
+            "Files under {} differ from the source checkpoint",
+            canister_dir.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Computes the manifest of the checkpoint at `path`.
+fn compute_manifest(path: &Path) -> Result<Manifest, String> {
+    manifest_from_path(path).map_err(|e| {
+        format!(
+            "Failed to compute manifest of checkpoint at {}: {}",
+            path.display(),
+            e
+        )
+    })
+}
+
+/// `import_canister` command entry point: writes a copy of the checkpoint at
+/// `target` to `output`, with the state of canister `canister_id` taken from
+/// the checkpoint at `source` (replacing the canister, if `target` already
+/// hosts it). Canister snapshots are not imported.
+///
+/// Only the canister's own files are imported. Subnet-level metadata (e.g. the
+/// routing table, `canister_migrations` or the subnet's memory usage) is left
+/// as in `target`.
+///
+/// The manifest of the resulting checkpoint is computed and checked against
+/// the manifests of `source` and `target`, and its root hash printed.
+pub fn do_import_canister(
+    source: PathBuf,
+    target: PathBuf,
+    canister_id: CanisterId,
+    output: PathBuf,
+) -> Result<(), String> {
+    let src_layout = canister_layout(&source, &canister_id)?;
+    if output.exists() {
+        return Err(format!("Output path {} already exists", output.display()));
+    }
+
+    copy_recursively(&target, &output)?;
+
+    let cp_layout = CheckpointLayout::<RwPolicy<()>>::new_untracked(output.clone(), Height::new(0))
+        .map_err(|e| format!("Failed to create output checkpoint layout: {}", e))?;
+    let replaced = cp_layout
+        .canister_ids()
+        .map_err(|e| format!("Failed to list canisters: {}", e))?
+        .contains(&canister_id);
+    if replaced {
+        let canister_dir = cp_layout
+            .canister(&canister_id)
+            .map_err(|e| e.to_string())?
+            .raw_path();
+        std::fs::remove_dir_all(&canister_dir).map_err(|e| {
+            format!(
+                "Failed to remove directory {}: {}",
+                canister_dir.display(),
+                e
+            )
+        })?;
+    }
+    let dst_layout = cp_layout
+        .canister(&canister_id)
+        .map_err(|e| format!("Failed to create canister layout: {}", e))?;
+    copy_canister(&src_layout, &dst_layout)?;
+
+    // Make sure that the imported canister can be loaded.
+    load_canister_state(
+        &canister_layout(&output, &canister_id)?,
+        &canister_id,
+        Height::new(0),
+        Arc::new(TestPageAllocatorFileDescriptorImpl::new()),
+    )
+    .map_err(|e| format!("Failed to load imported canister {}: {}", canister_id, e))?;
+
+    // Make sure that exactly the canister's files were imported.
+    let manifest = compute_manifest(&output)?;
+    let canister_dir = dst_layout
+        .raw_path()
+        .strip_prefix(&output)
+        .map_err(|e| e.to_string())?
+        .to_path_buf();
+    check_imported_files(
+        &compute_manifest(&source)?,
+        &compute_manifest(&target)?,
+        &manifest,
+        &canister_dir,
+    )?;
+
+    println!(
+        "Successfully {} canister {} in checkpoint {}",
+        if replaced { "replaced" } else { "imported" },
+        canister_id,
+        output.display()
+    );
+    println!(
+        "NOTE: subnet-level metadata (routing table, canister_migrations, subnet memory usage) \
+         was not updated"
+    );
+    println!("ROOT HASH: {}", hex::encode(manifest_hash(&manifest)));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::do_import_canister;
+    use crate::commands::utils::canister_layout;
+    use ic_base_types::NumSeconds;
+    use ic_config::state_manager::Config;
+    use ic_interfaces_state_manager::{CertificationScope, StateManager};
+    use ic_logger::ReplicaLogger;
+    use ic_metrics::MetricsRegistry;
+    use ic_registry_subnet_type::SubnetType;
+    use ic_replicated_state::{page_map::TestPageAllocatorFileDescriptorImpl, CanisterState};
+    use ic_state_manager::{checkpoint::load_canister_state, StateManagerImpl};
+    use ic_test_utilities::{
+        consensus::fake::FakeVerifier,
+        state::new_canister_state,
+        types::ids::{canister_test_id, user_test_id, SUBNET_1},
+    };
+    use ic_test_utilities_logger::with_test_replica_logger;
+    use ic_types::{malicious_flags::MaliciousFlags, CanisterId, Cycles, Height};
+    use std::{
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
+
+    fn canister(canister_id: CanisterId, cycles: Cycles) -> CanisterState {
+        new_canister_state(
+            canister_id,
+            user_test_id(0).get(),
+            cycles,
+            NumSeconds::from(0),
+        )
+    }
+
+    /// Writes a checkpoint hosting `canisters` under the state root `root` and
+    /// returns its path.
+    fn write_checkpoint(root: &Path, canisters: Vec<CanisterState>, log: ReplicaLogger) -> PathBuf {
+        let state_manager = StateManagerImpl::new(
+            Arc::new(FakeVerifier::new()),
+            SUBNET_1,
+            SubnetType::Application,
+            log,
+            &MetricsRegistry::new(),
+            &Config::new(root.to_path_buf()),
+            None,
+            MaliciousFlags::default(),
+        );
+        let (_, mut state) = state_manager.take_tip();
+        for canister in canisters {
+            state.put_canister_state(canister);
+        }
+        let height = Height::new(1);
+        state_manager.commit_and_certify(state, height, CertificationScope::Full);
+        state_manager.flush_tip_channel();
+        state_manager
+            .state_layout()
+            .checkpoint(height)
+            .unwrap()
+            .raw_path()
+            .to_path_buf()
+    }
+
+    fn load_canister(checkpoint: &Path, canister_id: CanisterId) -> CanisterState {
+        load_canister_state(
+            &canister_layout(checkpoint, &canister_id).unwrap(),
+            &canister_id,
+            Height::new(0),
+            Arc::new(TestPageAllocatorFileDescriptorImpl::new()),
+        )
+        .unwrap()
+        .0
+    }
+
+    #[test]
+    fn import_canister_between_checkpoints() {
+        with_test_replica_logger(|log| {
+            let tmp = tempfile::tempdir().unwrap();
+            let imported = canister_test_id(1);
+            let replaced = canister_test_id(2);
+            let untouched = canister_test_id(3);
+
+            let source = write_checkpoint(
+                &tmp.path().join("source"),
+                vec![
+                    canister(imported, Cycles::new(1 << 40)),
+                    canister(replaced, Cycles::new(1 << 41)),
+                ],
+                log.clone(),
+            );
+            let target = write_checkpoint(
+                &tmp.path().join("target"),
+                vec![
+                    canister(replaced, Cycles::new(1 << 42)),
+                    canister(untouched, Cycles::new(1 << 43)),
+                ],
+                log,
+            );
+
+            // Import a canister not hosted by `target`.
+            let output = tmp.path().join("output");
+            do_import_canister(source.clone(), target.clone(), imported, output.clone()).unwrap();
+            assert_eq!(
+                load_canister(&source, imported),
+                load_canister(&output, imported)
+            );
+            assert_eq!(
+                load_canister(&target, replaced),
+                load_canister(&output, replaced)
+            );
+            assert_eq!(
+                load_canister(&target, untouched),
+                load_canister(&output, untouched)
+            );
+
+            // Replace a canister hosted by `target`.
+            let output = tmp.path().join("output_replaced");
+            do_import_canister(source.clone(), target.clone(), replaced, output.clone()).unwrap();
+            assert_eq!(
+                load_canister(&source, replaced),
+                load_canister(&output, replaced)
+            );
+            assert_eq!(
+                load_canister(&target, untouched),
+                load_canister(&output, untouched)
+            );
+            assert!(canister_layout(&output, &imported).is_err());
+
+            // The output path must not exist.
+            assert!(do_import_canister(source, target, imported, output).is_err());
+        });
+    }
+}
@@ -3,8 +3,11 @@
 use ic_config::{config_parser::ConfigSource, ConfigOptional};
 use ic_logger::replica_logger::no_op_logger;
 use ic_metrics::MetricsRegistry;
-use ic_state_layout::StateLayout;
-use std::path::PathBuf;
+use ic_replicated_state::page_map::{PageMap, TestPageAllocatorFileDescriptorImpl};
+use ic_state_layout::{CanisterLayout, CheckpointLayout, ReadOnly, StateLayout};
+use ic_types::{CanisterId, Height};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 /// Loads the location of the state root from the given `replica` configuration
 /// file.
@@ -25,3 +28,38 @@ pub fn locate_state_root(config_path: PathBuf) -> Result<StateLayout, String> {
 
     Ok(StateLayout::try_new(no_op_logger(), state_root, &MetricsRegistry::new()).unwrap())
 }
+
+/// Returns the layout of canister `canister_id` within the checkpoint rooted at
+/// `checkpoint_path`. Fails if the checkpoint does not contain the canister.
+pub fn canister_layout(
+    checkpoint_path: &Path,
+    canister_id: &CanisterId,
+) -> Result<CanisterLayout<ReadOnly>, String> {
+    let cp_layout =
+        CheckpointLayout::<ReadOnly>::new_untracked(checkpoint_path.to_path_buf(), Height::new(0))
+            .map_err(|e| format!("failed to create checkpoint layout: {}", e))?;
+    let canister_ids = cp_layout
+        .canister_ids()
+        .map_err(|e| format!("failed to list canisters: {}", e))?;
+    if !canister_ids.contains(canister_id) {
+        return Err(format!(
+            "canister {} not found in checkpoint {}",
+            canister_id,
+            checkpoint_path.display()
+        ));
+    }
+    cp_layout
+        .canister(canister_id)
+        .map_err(|e| format!("failed to create canister layout: {}", e))
+}
+
+/// Opens the `PageMap` persisted as the given base file and overlays.
+pub fn open_page_map(base_file: &Path, overlays: &[PathBuf]) -> Result<PageMap, String> {
+    PageMap::open(
+        base_file,
+        overlays,
+        Height::new(0),
+        Arc::new(TestPageAllocatorFileDescriptorImpl::new()),
+    )
+    .map_err(|e| format!("failed to open page map {}: {}", base_file.display(), e))
+}
@@ -2,13 +2,14 @@
 //!
 //! A command-line tool to manage Internet Computer replicated states (decode
 //! persisted state files, diff checkpoints, compute partial state hashes and
-//! checkpoint manifests, import state trees).
+//! checkpoint manifests, import state trees, inspect and import individual
+//! canisters).
 
 use clap::Parser;
 use ic_registry_routing_table::CanisterIdRange;
 use ic_registry_subnet_type::SubnetType;
 use ic_state_tool::commands;
-use ic_types::{PrincipalId, Time};
+use ic_types::{CanisterId, PrincipalId, Time};
 use std::path::PathBuf;
 
 /// Supported `state_tool` commands and their arguments.
@@ -19,6 +20,48 @@ enum Opt {
     #[clap(name = "cdiff")]
     CDiff { path_a: PathBuf, path_b: PathBuf },
 
+    /// Displays the system state, queues, certified data and history of a
+    /// canister in a checkpoint.
+    #[clap(name = "canister-info")]
+    CanisterInfo {
+        /// Path to a checkpoint.
+        #[clap(long = "state")]
+        path: PathBuf,
+
+        /// ID of the canister to display.
+        #[clap(long = "canister_id")]
+        canister_id: CanisterId,
+    },
+
+    /// Dumps the Wasm heap, stable memory and Wasm binary of a canister in a
+    /// checkpoint as raw files.
+    #[clap(name = "export-canister")]
+    ExportCanister {
+        /// Path to a checkpoint.
+        #[clap(long = "state")]
+        path: PathBuf,
+
+        /// ID of the canister to export.
+        #[clap(long = "canister_id")]
+        canister_id: CanisterId,
+
+        /// Directory to write the exported files to.
+        #[clap(long = "output")]
+        output: PathBuf,
+    },
+
+    /// Diffs the state of a canister between two checkpoints, memories at the
+    /// page level.
+    #[clap(name = "canister-diff")]
+    CanisterDiff {
+        path_a: PathBuf,
+        path_b: PathBuf,
+
+        /// ID of the canister to diff.
+        #[clap(long = "canister_id")]
+        canister_id: CanisterId,
+    },
+
     /// Computes partial state hash that is used for certification.
     #[clap(name = "chash")]
     CHash {
@@ -43,6 +86,27 @@ enum Opt {
         height: u64,
     },
 
+    /// Writes a copy of a checkpoint with the state of one canister imported
+    /// from another checkpoint.
+    #[clap(name = "import-canister")]
+    ImportCanister {
+        /// Path to the checkpoint to take the canister from.
+        #[clap(long = "source")]
+        source: PathBuf,
+
+        /// Path to the checkpoint to inject the canister into.
+        #[clap(long = "target")]
+        target: PathBuf,
+
+        /// ID of the canister to import.
+        #[clap(long = "canister_id")]
+        canister_id: CanisterId,
+
+        /// Path to write the resulting checkpoint to (must not exist).
+        #[clap(long = "output")]
+        output: PathBuf,
+    },
+
     /// Computes manifest of a checkpoint.
     #[clap(name = "manifest")]
     Manifest {
@@ -153,12 +217,31 @@ fn main() {
     let opt = Parser::parse();
     let result = match opt {
         Opt::CDiff { path_a, path_b } => commands::cdiff::do_diff(path_a, path_b),
+        Opt::CanisterInfo { path, canister_id } => {
+            commands::canister_info::do_canister_info(path, canister_id)
+        }
+        Opt::ExportCanister {
+            path,
+            canister_id,
+            output,
+        } => commands::export_canister::do_export_canister(path, canister_id, output),
+        Opt::CanisterDiff {
+            path_a,
+            path_b,
+            canister_id,
+        } => commands::canister_diff::do_canister_diff(path_a, path_b, canister_id),
         Opt::CHash { path } => commands::chash::do_hash(path),
         Opt::ImportState {
             state,
             config,
             height,
         } => commands::import_state::do_import(state, config, height),
+        Opt::ImportCanister {
+            source,
+            target,
+            canister_id,
+            output,
+        } => commands::import_canister::do_import_canister(source, target, canister_id, output),
         Opt::Manifest { path } => commands::manifest::do_compute_manifest(path),
         Opt::VerifyManifest { file } => commands::verify_manifest::do_verify_manifest(&file),
         Opt::ListStates { config } => commands::list::do_list(config),
@@ -5,11 +5,12 @@ pub mod sandbox_safe_system_state;
 mod stable_memory;
 
 use ic_base_types::PrincipalIdBlobParseError;
-use ic_config::flag_status::FlagStatus;
+use ic_config::{execution_environment::MAX_CANISTER_TIMERS, flag_status::FlagStatus};
 use ic_cycles_account_manager::ResourceSaturation;
 use ic_error_types::RejectCode;
+use ic_ic00_types::{EcdsaCurve, EcdsaKeyId};
 use ic_interfaces::execution_environment::{
-    ExecutionMode,
+    CanisterBacktrace, ExecutionMode, GrowableMemory,
     HypervisorError::{self, *},
     HypervisorResult, OutOfInstructionsHandler, PerformanceCounterType, StableGrowOutcome,
     StableMemoryApi, SubnetAvailableMemory, SystemApi,
@@ -23,10 +24,11 @@ use ic_replicated_state::{
 };
 use ic_sys::PageBytes;
 use ic_types::{
+    canister_log::{CanisterLog, MAX_ALLOWED_CANISTER_LOG_BUFFER_SIZE},
     ingress::WasmResult,
     messages::{CallContextId, RejectContext, Request, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES},
     methods::{SystemMethod, WasmClosure},
-    CanisterId, CanisterTimer, ComputeAllocation, Cycles, MemoryAllocation, NumBytes,
+    CanisterId, CanisterTimer, CoarseTime, ComputeAllocation, Cycles, MemoryAllocation, NumBytes,
     NumInstructions, NumPages, PrincipalId, SubnetId, Time, MAX_STABLE_MEMORY_IN_BYTES,
 };
 use ic_utils::deterministic_operations::deterministic_copy_from_slice;
@@ -41,7 +43,10 @@ use std::{
 
 pub const MULTIPLIER_MAX_SIZE_LOCAL_SUBNET: u64 = 5;
 const MAX_NON_REPLICATED_QUERY_REPLY_SIZE: NumBytes = NumBytes::new(3 << 20);
-const CERTIFIED_DATA_MAX_LENGTH: u32 = 32;
+const CERTIFIED_DATA_MAX_LENGTH: usize = 32;
+/// The maximum timeout of a best-effort call. Larger timeouts requested via
+/// `ic0.call_with_best_effort_response` are silently capped to this value.
+pub const MAX_CALL_TIMEOUT_SECONDS: u32 = 300;
 
 // Enables tracing of system calls for local debugging.
 const TRACE_SYSCALLS: bool = false;
@@ -52,6 +57,10 @@ const WASM_NATIVE_STABLE_MEMORY_ERROR: &str = "Stable memory cannot be accessed
 
 const MAX_32_BIT_STABLE_MEMORY_IN_PAGES: u64 = 64 * 1024; // 4GiB
 
+// This is an overestimation of table element size computed based on the
+// existing canister limits.
+const TABLE_ELEMENT_SIZE: u64 = 1024;
+
 // This macro is used in system calls for tracing.
 macro_rules! trace_syscall {
     ($self:ident, $name:ident, $result:expr $( , $args:expr )*) => {{
@@ -78,10 +87,10 @@ macro_rules! trace_syscall {
 
 // This helper is used in system calls for displaying a summary hash of a heap region.
 #[inline]
-fn summarize(heap: &[u8], start: u32, size: u32) -> u64 {
+fn summarize(heap: &[u8], start: usize, size: usize) -> u64 {
     if TRACE_SYSCALLS {
-        let start = (start as usize).min(heap.len());
-        let end = (start + (size as usize)).min(heap.len());
+        let start = start.min(heap.len());
+        let end = start.saturating_add(size).min(heap.len());
         // The actual hash function doesn't matter much as long as it is
         // cheap to compute and maps the input to u64 reasonably well.
         let mut sum = 0;
@@ -322,13 +331,18 @@ pub enum ApiType {
         message_accepted: bool,
     },
 
-    // For executing the `canister_heartbeat` or `canister_global_timer` methods
+    // For executing the `canister_heartbeat`, `canister_global_timer` or
+    // `canister_on_low_wasm_memory` methods
     SystemTask {
         caller: PrincipalId,
         /// System task to execute.
-        /// Only `canister_heartbeat` and `canister_global_timer` are allowed.
+        /// Only `canister_heartbeat`, `canister_global_timer` and
+        /// `canister_on_low_wasm_memory` are allowed.
         system_task: SystemMethod,
         time: Time,
+        /// The argument of the system task. Only `canister_global_timer`
+        /// receives one: the ID of the named timer that fired, if any.
+        incoming_payload: Vec<u8>,
         call_context_id: CallContextId,
         /// Optional outgoing request under construction. If `None` no outgoing
         /// request is currently under construction.
@@ -366,11 +380,13 @@ impl ApiType {
         caller: PrincipalId,
         system_task: SystemMethod,
         time: Time,
+        incoming_payload: Vec<u8>,
         call_context_id: CallContextId,
     ) -> Self {
         Self::SystemTask {
             caller,
             time,
+            incoming_payload,
             call_context_id,
             outgoing_request: None,
             system_task,
@@ -516,6 +532,38 @@ impl ApiType {
         }
     }
 
+    /// Raises the maximum size of the reply produced by an `Update`,
+    /// `ReplyCallback` or `RejectCallback` execution to `max_reply_size`.
+    ///
+    /// Used for replies to inter-canister calls, which may be fragmented and
+    /// are therefore not limited to `MAX_INTER_CANISTER_PAYLOAD_IN_BYTES`. A
+    /// no-op for all other API types.
+    pub fn with_max_reply_size(mut self, max_reply_size: NumBytes) -> Self {
+        match &mut self {
+            ApiType::Update {
+                max_reply_size: size,
+                ..
+            }
+            | ApiType::ReplyCallback {
+                max_reply_size: size,
+                ..
+            }
+            | ApiType::RejectCallback {
+                max_reply_size: size,
+                ..
+            } => *size = max_reply_size,
+            ApiType::Start { .. }
+            | ApiType::Init { .. }
+            | ApiType::SystemTask { .. }
+            | ApiType::ReplicatedQuery { .. }
+            | ApiType::NonReplicatedQuery { .. }
+            | ApiType::PreUpgrade { .. }
+            | ApiType::InspectMessage { .. }
+            | ApiType::Cleanup { .. } => {}
+        }
+        self
+    }
+
     /// Indicates whether state modifications are important for this API type or
     /// not.
     pub fn modification_tracking(&self) -> ModificationTracking {
@@ -550,7 +598,11 @@ impl ApiType {
             ApiType::SystemTask { system_task, .. } => match system_task {
                 SystemMethod::CanisterHeartbeat => "heartbeat",
                 SystemMethod::CanisterGlobalTimer => "global timer",
-                _ => panic!("Only `canister_heartbeat` and `canister_global_timer` are allowed."),
+                SystemMethod::CanisterOnLowWasmMemory => "on low wasm memory",
+                _ => panic!(
+                    "Only `canister_heartbeat`, `canister_global_timer` and \
+                    `canister_on_low_wasm_memory` are allowed."
+                ),
             },
             ApiType::Update { .. } => "update",
             ApiType::ReplicatedQuery { .. } => "replicated query",
@@ -833,6 +885,10 @@ pub struct SystemApiImpl {
     /// is initialized to 0 and updated after each out-of-instructions call that
     /// starts a new slice.
     instructions_executed_before_current_slice: i64,
+
+    /// The log records produced by the current execution. They are appended
+    /// to the canister log after the execution, regardless of its outcome.
+    canister_log: CanisterLog,
 }
 
 impl SystemApiImpl {
@@ -874,6 +930,39 @@ impl SystemApiImpl {
             log,
             current_slice_instruction_limit: i64::try_from(slice_limit).unwrap_or(i64::MAX),
             instructions_executed_before_current_slice: 0,
+            canister_log: CanisterLog::default(),
+        }
+    }
+
+    /// Returns the log records produced by the current execution, leaving an
+    /// empty log in their place.
+    pub fn take_canister_log(&mut self) -> CanisterLog {
+        std::mem::take(&mut self.canister_log)
+    }
+
+    /// Records the trap code and the backtrace of a Wasm trap in the canister
+    /// log.
+    pub fn save_trap_backtrace(&mut self, trap_code: &TrapCode, backtrace: &CanisterBacktrace) {
+        let content = format!("[TRAP]: {}\n{}", trap_code, backtrace).into_bytes();
+        let time = self.log_time();
+        self.canister_log
+            .add_record(time.as_nanos_since_unix_epoch(), content);
+    }
+
+    /// The time of the current execution used as the timestamp of log records.
+    fn log_time(&self) -> Time {
+        match &self.api_type {
+            ApiType::Start { time }
+            | ApiType::Init { time, .. }
+            | ApiType::SystemTask { time, .. }
+            | ApiType::Update { time, .. }
+            | ApiType::Cleanup { time, .. }
+            | ApiType::NonReplicatedQuery { time, .. }
+            | ApiType::ReplicatedQuery { time, .. }
+            | ApiType::PreUpgrade { time, .. }
+            | ApiType::ReplyCallback { time, .. }
+            | ApiType::RejectCallback { time, .. }
+            | ApiType::InspectMessage { time, .. } => *time,
         }
     }
 
@@ -1116,6 +1205,31 @@ impl SystemApiImpl {
         }
     }
 
+    /// Charges the canister for transmitting the bytes of a not yet sent reply
+    /// beyond `MAX_INTER_CANISTER_PAYLOAD_IN_BYTES`, which are not covered by
+    /// the caller's prepayment for response transmission.
+    ///
+    /// Only replies produced in replicated mode can be routed as XNet message
+    /// fragments, so nothing is charged in non-replicated mode.
+    fn charge_for_reply_transmission(&mut self) -> HypervisorResult<()> {
+        if self.execution_parameters.execution_mode == ExecutionMode::NonReplicated {
+            return Ok(());
+        }
+        let reply_size = match self.get_response_info() {
+            Some((data, _, ResponseStatus::NotRepliedYet)) => NumBytes::from(data.len() as u64),
+            _ => return Ok(()),
+        };
+        if reply_size <= MAX_INTER_CANISTER_PAYLOAD_IN_BYTES {
+            return Ok(());
+        }
+        self.sandbox_safe_system_state
+            .withdraw_cycles_for_reply_transmission(
+                self.memory_usage.current_usage,
+                self.memory_usage.current_message_usage,
+                reply_size,
+            )
+    }
+
     fn ic0_canister_cycle_balance_helper(&self, method_name: &str) -> HypervisorResult<Cycles> {
         match &self.api_type {
             ApiType::Start { .. } => Err(self.error_for(method_name)),
@@ -1324,6 +1438,90 @@ impl SystemApiImpl {
             panic!("{}", WASM_NATIVE_STABLE_MEMORY_ERROR)
         }
     }
+
+    /// Checks that a Wasm heap of `new_size` bytes is within the canister's
+    /// `wasm_memory_limit`. The limit is not enforced while installing or
+    /// upgrading code and in `canister_inspect_message`.
+    fn check_wasm_memory_limit(&self, new_size: NumBytes) -> HypervisorResult<()> {
+        let limit = match self.sandbox_safe_system_state.wasm_memory_limit() {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        match self.api_type {
+            ApiType::Start { .. }
+            | ApiType::Init { .. }
+            | ApiType::PreUpgrade { .. }
+            | ApiType::InspectMessage { .. } => Ok(()),
+            ApiType::Update { .. }
+            | ApiType::ReplicatedQuery { .. }
+            | ApiType::NonReplicatedQuery { .. }
+            | ApiType::ReplyCallback { .. }
+            | ApiType::RejectCallback { .. }
+            | ApiType::SystemTask { .. }
+            | ApiType::Cleanup { .. } => {
+                if new_size > limit {
+                    Err(HypervisorError::WasmMemoryLimitExceeded {
+                        bytes: new_size,
+                        limit,
+                    })
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    /// Returns the name of the environment variable at `index`, with variables
+    /// ordered by name.
+    fn env_var_name(&self, method_name: &str, index: usize) -> HypervisorResult<&String> {
+        let environment_variables = self.sandbox_safe_system_state.environment_variables();
+        environment_variables.keys().nth(index).ok_or_else(|| {
+            HypervisorError::ContractViolation(format!(
+                "{}: index {} out of bounds for {} environment variables",
+                method_name,
+                index,
+                environment_variables.len()
+            ))
+        })
+    }
+
+    /// Reads an environment variable name from heap[src..src+size].
+    fn env_var_name_from_heap<'a>(
+        &self,
+        method_name: &str,
+        src: usize,
+        size: usize,
+        heap: &'a [u8],
+    ) -> HypervisorResult<&'a str> {
+        let bytes = valid_subslice(method_name, src, size, heap)?;
+        std::str::from_utf8(bytes).map_err(|err| {
+            HypervisorError::ContractViolation(format!(
+                "{}: environment variable name is not valid UTF-8: {}",
+                method_name, err
+            ))
+        })
+    }
+
+    /// Returns the value of the environment variable whose name is in
+    /// heap[src..src+size].
+    fn env_var_value(
+        &self,
+        method_name: &str,
+        src: usize,
+        size: usize,
+        heap: &[u8],
+    ) -> HypervisorResult<&String> {
+        let name = self.env_var_name_from_heap(method_name, src, size, heap)?;
+        self.sandbox_safe_system_state
+            .environment_variables()
+            .get(name)
+            .ok_or_else(|| {
+                HypervisorError::ContractViolation(format!(
+                    "{}: environment variable {:?} does not exist",
+                    method_name, name
+                ))
+            })
+    }
 }
 
 impl SystemApi for SystemApiImpl {
@@ -1412,6 +1610,25 @@ impl SystemApi for SystemApiImpl {
         self.sandbox_safe_system_state.canister_id
     }
 
+    fn save_log_message(&mut self, is_trap: bool, src: usize, size: usize, heap: &[u8]) {
+        let time = self.log_time();
+        // Larger messages would be truncated by the log anyway.
+        let size = size.min(MAX_ALLOWED_CANISTER_LOG_BUFFER_SIZE);
+        let message = match valid_subslice("save_log_message", src, size, heap) {
+            Ok(bytes) => bytes,
+            // Logging must never fail, so an invalid memory range is recorded
+            // the same way as in `ic0_debug_print`.
+            Err(_) => b"(message out of memory bounds)",
+        };
+        let content = if is_trap {
+            [b"[TRAP]: ", message].concat()
+        } else {
+            message.to_vec()
+        };
+        self.canister_log
+            .add_record(time.as_nanos_since_unix_epoch(), content);
+    }
+
     fn ic0_msg_caller_size(&self) -> HypervisorResult<u32> {
         let result = self
             .get_msg_caller_id("ic0_msg_caller_size")
@@ -1422,9 +1639,9 @@ impl SystemApi for SystemApiImpl {
 
     fn ic0_msg_caller_copy(
         &self,
-        dst: u32,
-        offset: u32,
-        size: u32,
+        dst: usize,
+        offset: usize,
+        size: usize,
         heap: &mut [u8],
     ) -> HypervisorResult<()> {
         let result = match self.get_msg_caller_id("ic0_msg_caller_copy") {
@@ -1432,7 +1649,6 @@ impl SystemApi for SystemApiImpl {
                 let id_bytes = caller_id.as_slice();
                 valid_subslice("ic0.msg_caller_copy heap", dst, size, heap)?;
                 let slice = valid_subslice("ic0.msg_caller_copy id", offset, size, id_bytes)?;
-                let (dst, size) = (dst as usize, size as usize);
                 deterministic_copy_from_slice(&mut heap[dst..dst + size], slice);
                 Ok(())
             }
@@ -1452,12 +1668,12 @@ impl SystemApi for SystemApiImpl {
 
     fn ic0_msg_arg_data_size(&self) -> HypervisorResult<u32> {
         let result = match &self.api_type {
-            ApiType::Start { .. }
-            | ApiType::Cleanup { .. }
-            | ApiType::SystemTask { .. }
-            | ApiType::RejectCallback { .. }
-            | ApiType::PreUpgrade { .. } => Err(self.error_for("ic0_msg_arg_data_size")),
-            ApiType::Init {
+            ApiType::SystemTask {
+                system_task: SystemMethod::CanisterGlobalTimer,
+                incoming_payload,
+                ..
+            }
+            | ApiType::Init {
                 incoming_payload, ..
             }
             | ApiType::Update {
@@ -1475,6 +1691,11 @@ impl SystemApi for SystemApiImpl {
             | ApiType::NonReplicatedQuery {
                 incoming_payload, ..
             } => Ok(incoming_payload.len() as u32),
+            ApiType::Start { .. }
+            | ApiType::Cleanup { .. }
+            | ApiType::SystemTask { .. }
+            | ApiType::RejectCallback { .. }
+            | ApiType::PreUpgrade { .. } => Err(self.error_for("ic0_msg_arg_data_size")),
         };
         trace_syscall!(self, ic0_msg_arg_data_size, result);
         result
@@ -1482,18 +1703,18 @@ impl SystemApi for SystemApiImpl {
 
     fn ic0_msg_arg_data_copy(
         &self,
-        dst: u32,
-        offset: u32,
-        size: u32,
+        dst: usize,
+        offset: usize,
+        size: usize,
         heap: &mut [u8],
     ) -> HypervisorResult<()> {
         let result = match &self.api_type {
-            ApiType::Start { .. }
-            | ApiType::SystemTask { .. }
-            | ApiType::Cleanup { .. }
-            | ApiType::RejectCallback { .. }
-            | ApiType::PreUpgrade { .. } => Err(self.error_for("ic0_msg_arg_data_copy")),
-            ApiType::Init {
+            ApiType::SystemTask {
+                system_task: SystemMethod::CanisterGlobalTimer,
+                incoming_payload,
+                ..
+            }
+            | ApiType::Init {
                 incoming_payload, ..
             }
             | ApiType::Update {
@@ -1518,10 +1739,14 @@ impl SystemApi for SystemApiImpl {
                     size,
                     incoming_payload,
                 )?;
-                let (dst, size) = (dst as usize, size as usize);
                 deterministic_copy_from_slice(&mut heap[dst..dst + size], payload_subslice);
                 Ok(())
             }
+            ApiType::Start { .. }
+            | ApiType::SystemTask { .. }
+            | ApiType::Cleanup { .. }
+            | ApiType::RejectCallback { .. }
+            | ApiType::PreUpgrade { .. } => Err(self.error_for("ic0_msg_arg_data_copy")),
         };
         trace_syscall!(
             self,
@@ -1555,9 +1780,9 @@ impl SystemApi for SystemApiImpl {
 
     fn ic0_msg_method_name_copy(
         &self,
-        dst: u32,
-        offset: u32,
-        size: u32,
+        dst: usize,
+        offset: usize,
+        size: usize,
         heap: &mut [u8],
     ) -> HypervisorResult<()> {
         let result = match &self.api_type {
@@ -1579,7 +1804,6 @@ impl SystemApi for SystemApiImpl {
                     size,
                     method_name.as_bytes(),
                 )?;
-                let (dst, size) = (dst as usize, size as usize);
                 deterministic_copy_from_slice(&mut heap[dst..dst + size], payload_subslice);
                 Ok(())
             }
@@ -1626,35 +1850,39 @@ impl SystemApi for SystemApiImpl {
     }
 
     fn ic0_msg_reply(&mut self) -> HypervisorResult<()> {
-        let result = match self.get_response_info() {
-            None => Err(self.error_for("ic0_msg_reply")),
-            Some((data, _, status)) => match status {
-                ResponseStatus::NotRepliedYet => {
-                    *status = ResponseStatus::JustRepliedWith(Some(WasmResult::Reply(
-                        std::mem::take(data),
-                    )));
-                    Ok(())
-                }
-                ResponseStatus::AlreadyReplied | ResponseStatus::JustRepliedWith(_) => Err(
-                    ContractViolation("ic0.msg_reply: the call is already replied".to_string()),
-                ),
-            },
-        };
+        let result =
+            self.charge_for_reply_transmission()
+                .and_then(|()| match self.get_response_info() {
+                    None => Err(self.error_for("ic0_msg_reply")),
+                    Some((data, _, status)) => match status {
+                        ResponseStatus::NotRepliedYet => {
+                            *status = ResponseStatus::JustRepliedWith(Some(WasmResult::Reply(
+                                std::mem::take(data),
+                            )));
+                            Ok(())
+                        }
+                        ResponseStatus::AlreadyReplied | ResponseStatus::JustRepliedWith(_) => {
+                            Err(ContractViolation(
+                                "ic0.msg_reply: the call is already replied".to_string(),
+                            ))
+                        }
+                    },
+                });
         trace_syscall!(self, ic0_msg_reply, result);
         result
     }
 
     fn ic0_msg_reply_data_append(
         &mut self,
-        src: u32,
-        size: u32,
+        src: usize,
+        size: usize,
         heap: &[u8],
     ) -> HypervisorResult<()> {
         let result = match self.get_response_info() {
             None => Err(self.error_for("ic0_msg_reply_data_append")),
             Some((data, max_reply_size, response_status)) => match response_status {
                 ResponseStatus::NotRepliedYet => {
-                    let payload_size = (data.len() + size as usize) as u64;
+                    let payload_size = data.len().saturating_add(size) as u64;
                     if payload_size > max_reply_size.get() {
                         let string = format!(
                             "ic0.msg_reply_data_append: application payload size ({}) cannot be larger than {}",
@@ -1684,7 +1912,7 @@ impl SystemApi for SystemApiImpl {
         result
     }
 
-    fn ic0_msg_reject(&mut self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<()> {
+    fn ic0_msg_reject(&mut self, src: usize, size: usize, heap: &[u8]) -> HypervisorResult<()> {
         let result = match self.get_response_info() {
             None => Err(self.error_for("ic0_msg_reject")),
             Some((_, max_reply_size, response_status)) => match response_status {
@@ -1741,9 +1969,9 @@ impl SystemApi for SystemApiImpl {
 
     fn ic0_msg_reject_msg_copy(
         &self,
-        dst: u32,
-        offset: u32,
-        size: u32,
+        dst: usize,
+        offset: usize,
+        size: usize,
         heap: &mut [u8],
     ) -> HypervisorResult<()> {
         let result = {
@@ -1753,10 +1981,8 @@ impl SystemApi for SystemApiImpl {
             valid_subslice("ic0.msg_reject_msg_copy heap", dst, size, heap)?;
 
             let msg = reject_context.message();
-            let dst = dst as usize;
             let msg_bytes =
                 valid_subslice("ic0.msg_reject_msg_copy msg", offset, size, msg.as_bytes())?;
-            let size = size as usize;
             deterministic_copy_from_slice(&mut heap[dst..dst + size], msg_bytes);
             Ok(())
         };
@@ -1797,9 +2023,9 @@ impl SystemApi for SystemApiImpl {
 
     fn ic0_canister_self_copy(
         &mut self,
-        dst: u32,
-        offset: u32,
-        size: u32,
+        dst: usize,
+        offset: usize,
+        size: usize,
         heap: &mut [u8],
     ) -> HypervisorResult<()> {
         let result = match &self.api_type {
@@ -1818,7 +2044,6 @@ impl SystemApi for SystemApiImpl {
                 let canister_id = self.sandbox_safe_system_state.canister_id;
                 let id_bytes = canister_id.get_ref().as_slice();
                 let slice = valid_subslice("ic0.canister_self_copy id", offset, size, id_bytes)?;
-                let (dst, size) = (dst as usize, size as usize);
                 deterministic_copy_from_slice(&mut heap[dst..dst + size], slice);
                 Ok(())
             }
@@ -1835,12 +2060,184 @@ impl SystemApi for SystemApiImpl {
         result
     }
 
+    fn ic0_subnet_self_size(&self) -> HypervisorResult<usize> {
+        let result = Ok(self
+            .sandbox_safe_system_state
+            .subnet_id()
+            .get_ref()
+            .as_slice()
+            .len());
+        trace_syscall!(self, ic0_subnet_self_size, result);
+        result
+    }
+
+    fn ic0_subnet_self_copy(
+        &mut self,
+        dst: usize,
+        offset: usize,
+        size: usize,
+        heap: &mut [u8],
+    ) -> HypervisorResult<()> {
+        valid_subslice("ic0.subnet_self_copy heap", dst, size, heap)?;
+        let subnet_id = self.sandbox_safe_system_state.subnet_id();
+        let id_bytes = subnet_id.get_ref().as_slice();
+        let slice = valid_subslice("ic0.subnet_self_copy id", offset, size, id_bytes)?;
+        deterministic_copy_from_slice(&mut heap[dst..dst + size], slice);
+        let result = Ok(());
+        trace_syscall!(
+            self,
+            ic0_subnet_self_copy,
+            result,
+            dst,
+            offset,
+            size,
+            summarize(heap, dst, size)
+        );
+        result
+    }
+
+    fn ic0_in_replicated_execution(&self) -> HypervisorResult<i32> {
+        let execution_mode = match &self.api_type {
+            ApiType::Start { .. }
+            | ApiType::Init { .. }
+            | ApiType::SystemTask { .. }
+            | ApiType::Update { .. }
+            | ApiType::ReplicatedQuery { .. }
+            | ApiType::PreUpgrade { .. } => ExecutionMode::Replicated,
+            ApiType::NonReplicatedQuery { .. } | ApiType::InspectMessage { .. } => {
+                ExecutionMode::NonReplicated
+            }
+            ApiType::ReplyCallback { execution_mode, .. }
+            | ApiType::RejectCallback { execution_mode, .. } => execution_mode.clone(),
+            ApiType::Cleanup { .. } => self.execution_parameters.execution_mode.clone(),
+        };
+        let result = match execution_mode {
+            ExecutionMode::Replicated => Ok(1),
+            ExecutionMode::NonReplicated => Ok(0),
+        };
+        trace_syscall!(self, ic0_in_replicated_execution, result);
+        result
+    }
+
+    fn ic0_env_var_count(&self) -> HypervisorResult<usize> {
+        let result = Ok(self.sandbox_safe_system_state.environment_variables().len());
+        trace_syscall!(self, ic0_env_var_count, result);
+        result
+    }
+
+    fn ic0_env_var_name_size(&self, index: usize) -> HypervisorResult<usize> {
+        let result = self
+            .env_var_name("ic0_env_var_name_size", index)
+            .map(|name| name.len());
+        trace_syscall!(self, ic0_env_var_name_size, result, index);
+        result
+    }
+
+    fn ic0_env_var_name_copy(
+        &self,
+        index: usize,
+        dst: usize,
+        offset: usize,
+        size: usize,
+        heap: &mut [u8],
+    ) -> HypervisorResult<()> {
+        valid_subslice("ic0.env_var_name_copy heap", dst, size, heap)?;
+        let name = self.env_var_name("ic0_env_var_name_copy", index)?;
+        let slice = valid_subslice("ic0.env_var_name_copy name", offset, size, name.as_bytes())?;
+        deterministic_copy_from_slice(&mut heap[dst..dst + size], slice);
+        let result = Ok(());
+        trace_syscall!(
+            self,
+            ic0_env_var_name_copy,
+            result,
+            index,
+            dst,
+            offset,
+            size,
+            summarize(heap, dst, size)
+        );
+        result
+    }
+
+    fn ic0_env_var_name_exists(
+        &self,
+        name_src: usize,
+        name_size: usize,
+        heap: &[u8],
+    ) -> HypervisorResult<i32> {
+        let result = self
+            .env_var_name_from_heap("ic0_env_var_name_exists", name_src, name_size, heap)
+            .map(|name| {
+                self.sandbox_safe_system_state
+                    .environment_variables()
+                    .contains_key(name) as i32
+            });
+        trace_syscall!(
+            self,
+            ic0_env_var_name_exists,
+            result,
+            summarize(heap, name_src, name_size)
+        );
+        result
+    }
+
+    fn ic0_env_var_value_size(
+        &self,
+        name_src: usize,
+        name_size: usize,
+        heap: &[u8],
+    ) -> HypervisorResult<usize> {
+        let result = self
+            .env_var_value("ic0_env_var_value_size", name_src, name_size, heap)
+            .map(|value| value.len());
+        trace_syscall!(
+            self,
+            ic0_env_var_value_size,
+            result,
+            summarize(heap, name_src, name_size)
+        );
+        result
+    }
+
+    fn ic0_env_var_value_copy(
+        &self,
+        name_src: usize,
+        name_size: usize,
+        dst: usize,
+        offset: usize,
+        size: usize,
+        heap: &mut [u8],
+    ) -> HypervisorResult<()> {
+        valid_subslice("ic0.env_var_value_copy heap", dst, size, heap)?;
+        let value = self.env_var_value("ic0_env_var_value_copy", name_src, name_size, heap)?;
+        let slice = valid_subslice(
+            "ic0.env_var_value_copy value",
+            offset,
+            size,
+            value.as_bytes(),
+        )?;
+        deterministic_copy_from_slice(&mut heap[dst..dst + size], slice);
+        let result = Ok(());
+        trace_syscall!(
+            self,
+            ic0_env_var_value_copy,
+            result,
+            name_src,
+            name_size,
+            dst,
+            offset,
+            size,
+            summarize(heap, dst, size)
+        );
+        result
+    }
+
     fn ic0_call_new(
         &mut self,
-        callee_src: u32,
-        callee_size: u32,
-        name_src: u32,
-        name_len: u32,
+        callee_src: usize,
+        callee_size: usize,
+        name_src: usize,
+        name_len: usize,
         reply_fun: u32,
         reply_env: u32,
         reject_fun: u32,
@@ -1916,7 +2313,12 @@ impl SystemApi for SystemApiImpl {
         result
     }
 
-    fn ic0_call_data_append(&mut self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<()> {
+    fn ic0_call_data_append(
+        &mut self,
+        src: usize,
+        size: usize,
+        heap: &[u8],
+    ) -> HypervisorResult<()> {
         let result = match &mut self.api_type {
             ApiType::Start { .. }
             | ApiType::Init { .. }
@@ -2004,6 +2406,56 @@ impl SystemApi for SystemApiImpl {
         result
     }
 
+    fn ic0_call_with_best_effort_response(&mut self, timeout_seconds: u32) -> HypervisorResult<()> {
+        let result = match &mut self.api_type {
+            ApiType::Start { .. }
+            | ApiType::Init { .. }
+            | ApiType::ReplicatedQuery { .. }
+            | ApiType::NonReplicatedQuery { .. }
+            | ApiType::Cleanup { .. }
+            | ApiType::PreUpgrade { .. }
+            | ApiType::InspectMessage { .. } => {
+                Err(self.error_for("ic0_call_with_best_effort_response"))
+            }
+            ApiType::Update {
+                time,
+                outgoing_request,
+                ..
+            }
+            | ApiType::SystemTask {
+                time,
+                outgoing_request,
+                ..
+            }
+            | ApiType::ReplyCallback {
+                time,
+                outgoing_request,
+                ..
+            }
+            | ApiType::RejectCallback {
+                time,
+                outgoing_request,
+                ..
+            } => match outgoing_request {
+                None => Err(HypervisorError::ContractViolation(
+                    "ic0.call_with_best_effort_response called when no call is under construction."
+                        .to_string(),
+                )),
+                Some(request) => request.set_deadline(
+                    CoarseTime::floor(*time)
+                        .saturating_add_secs(timeout_seconds.min(MAX_CALL_TIMEOUT_SECONDS)),
+                ),
+            },
+        };
+        trace_syscall!(
+            self,
+            ic0_call_with_best_effort_response,
+            result,
+            timeout_seconds
+        );
+        result
+    }
+
     fn ic0_call_cycles_add(&mut self, amount: u64) -> HypervisorResult<()> {
         let result = self.ic0_call_cycles_add_helper("ic0_call_cycles_add", Cycles::from(amount));
         trace_syscall!(self, ic0_call_cycles_add, result, amount);
@@ -2143,7 +2595,7 @@ impl SystemApi for SystemApiImpl {
             dst,
             offset,
             size,
-            summarize(heap, dst, size)
+            summarize(heap, dst as usize, size as usize)
         );
         result
     }
@@ -2165,7 +2617,7 @@ impl SystemApi for SystemApiImpl {
             offset,
             src,
             size,
-            summarize(heap, src, size)
+            summarize(heap, src as usize, size as usize)
         );
         result
     }
@@ -2212,7 +2664,7 @@ impl SystemApi for SystemApiImpl {
             dst,
             offset,
             size,
-            summarize(heap, dst as u32, size as u32)
+            summarize(heap, dst as usize, size as usize)
         );
         result
     }
@@ -2245,7 +2697,7 @@ impl SystemApi for SystemApiImpl {
             offset,
             src,
             size,
-            summarize(heap, src as u32, size as u32)
+            summarize(heap, src as usize, size as usize)
         );
         result
     }
@@ -2309,6 +2761,44 @@ impl SystemApi for SystemApiImpl {
         result
     }
 
+    fn ic0_timer_set(&mut self, timer_id: u64, time: Time) -> HypervisorResult<Time> {
+        let result = match &self.api_type {
+            ApiType::Start { .. }
+            | ApiType::NonReplicatedQuery { .. }
+            | ApiType::ReplicatedQuery { .. }
+            | ApiType::InspectMessage { .. } => Err(self.error_for("ic0_timer_set")),
+            ApiType::Init { .. }
+            | ApiType::SystemTask { .. }
+            | ApiType::Update { .. }
+            | ApiType::Cleanup { .. }
+            | ApiType::PreUpgrade { .. }
+            | ApiType::ReplyCallback { .. }
+            | ApiType::RejectCallback { .. } => {
+                // Reply and reject callbacks can be executed in non-replicated mode
+                // iff from within a composite query call. Disallow in that case.
+                if self.execution_parameters.execution_mode == ExecutionMode::NonReplicated {
+                    return Err(self.error_for("ic0_timer_set"));
+                }
+
+                let timer = CanisterTimer::from_time(time);
+                let timers = self.sandbox_safe_system_state.timers();
+                if timer != CanisterTimer::Inactive
+                    && timers.get(timer_id) == CanisterTimer::Inactive
+                    && timers.len() >= MAX_CANISTER_TIMERS
+                {
+                    return Err(HypervisorError::ContractViolation(format!(
+                        "ic0.timer_set: the canister cannot have more than {} active timers.",
+                        MAX_CANISTER_TIMERS
+                    )));
+                }
+                let prev_timer = self.sandbox_safe_system_state.set_timer(timer_id, timer);
+                Ok(prev_timer.to_time())
+            }
+        };
+        trace_syscall!(self, ic0_timer_set, result, timer_id, time);
+        result
+    }
+
     fn ic0_performance_counter(
         &self,
         performance_counter_type: PerformanceCounterType,
@@ -2370,12 +2860,23 @@ impl SystemApi for SystemApiImpl {
         &mut self,
         native_memory_grow_res: i64,
         additional_elements: u64,
-        element_size: u64,
+        memory: GrowableMemory,
     ) -> HypervisorResult<()> {
         let result = {
             if native_memory_grow_res == -1 {
                 return Ok(());
             }
+            let element_size = match memory {
+                GrowableMemory::WasmMemory => WASM_PAGE_SIZE_IN_BYTES as u64,
+                GrowableMemory::Table => TABLE_ELEMENT_SIZE,
+            };
+            // The `wasm_memory_limit` applies to the Wasm heap only.
+            if memory == GrowableMemory::WasmMemory {
+                let new_size = (native_memory_grow_res as u64)
+                    .saturating_add(additional_elements)
+                    .saturating_mul(element_size);
+                self.check_wasm_memory_limit(NumBytes::new(new_size))?;
+            }
             let bytes = additional_elements
                 .checked_mul(element_size)
                 .map(NumBytes::new)
@@ -2405,7 +2906,7 @@ impl SystemApi for SystemApiImpl {
             result,
             native_memory_grow_res,
             additional_elements,
-            element_size
+            memory
         );
         result
     }
@@ -2419,9 +2920,10 @@ impl SystemApi for SystemApiImpl {
         let resulting_size = current_size.saturating_add(additional_pages);
         if let StableMemoryApi::Stable32 = stable_memory_api {
             if current_size > MAX_32_BIT_STABLE_MEMORY_IN_PAGES {
-                return Err(HypervisorError::Trapped(
-                    TrapCode::StableMemoryTooBigFor32Bit,
-                ));
+                return Err(HypervisorError::Trapped {
+                    trap_code: TrapCode::StableMemoryTooBigFor32Bit,
+                    backtrace: None,
+                });
             }
             if resulting_size > MAX_32_BIT_STABLE_MEMORY_IN_PAGES {
                 return Ok(StableGrowOutcome::Failure);
@@ -2462,7 +2964,10 @@ impl SystemApi for SystemApiImpl {
                 .ic0_canister_cycle_balance_helper("ic0_canister_cycle_balance")?
                 .into_parts();
             if high_amount != 0 {
-                return Err(HypervisorError::Trapped(CyclesAmountTooBigFor64Bit));
+                return Err(HypervisorError::Trapped {
+                    trap_code: CyclesAmountTooBigFor64Bit,
+                    backtrace: None,
+                });
             }
             Ok(low_amount)
         };
@@ -2470,7 +2975,7 @@ impl SystemApi for SystemApiImpl {
         result
     }
 
-    fn ic0_canister_cycle_balance128(&self, dst: u32, heap: &mut [u8]) -> HypervisorResult<()> {
+    fn ic0_canister_cycle_balance128(&self, dst: usize, heap: &mut [u8]) -> HypervisorResult<()> {
         let result = {
             let method_name = "ic0_canister_cycle_balance128";
             let cycles = self.ic0_canister_cycle_balance_helper(method_name)?;
@@ -2492,7 +2997,10 @@ impl SystemApi for SystemApiImpl {
                 .ic0_msg_cycles_available_helper("ic0_msg_cycles_available")?
                 .into_parts();
             if high_amount != 0 {
-                return Err(HypervisorError::Trapped(CyclesAmountTooBigFor64Bit));
+                return Err(HypervisorError::Trapped {
+                    trap_code: CyclesAmountTooBigFor64Bit,
+                    backtrace: None,
+                });
             }
             Ok(low_amount)
         };
@@ -2500,7 +3008,7 @@ impl SystemApi for SystemApiImpl {
         result
     }
 
-    fn ic0_msg_cycles_available128(&self, dst: u32, heap: &mut [u8]) -> HypervisorResult<()> {
+    fn ic0_msg_cycles_available128(&self, dst: usize, heap: &mut [u8]) -> HypervisorResult<()> {
         let result = {
             let method_name = "ic0_msg_cycles_available128";
             let cycles = self.ic0_msg_cycles_available_helper(method_name)?;
@@ -2517,7 +3025,10 @@ impl SystemApi for SystemApiImpl {
                 .ic0_msg_cycles_refunded_helper("ic0_msg_cycles_refunded")?
                 .into_parts();
             if high_amount != 0 {
-                return Err(HypervisorError::Trapped(CyclesAmountTooBigFor64Bit));
+                return Err(HypervisorError::Trapped {
+                    trap_code: CyclesAmountTooBigFor64Bit,
+                    backtrace: None,
+                });
             }
             Ok(low_amount)
         };
@@ -2525,7 +3036,7 @@ impl SystemApi for SystemApiImpl {
         result
     }
 
-    fn ic0_msg_cycles_refunded128(&self, dst: u32, heap: &mut [u8]) -> HypervisorResult<()> {
+    fn ic0_msg_cycles_refunded128(&self, dst: usize, heap: &mut [u8]) -> HypervisorResult<()> {
         let result = {
             let method_name = "ic0_msg_cycles_refunded128";
             let cycles = self.ic0_msg_cycles_refunded_helper(method_name)?;
@@ -2564,7 +3075,7 @@ impl SystemApi for SystemApiImpl {
     fn ic0_msg_cycles_accept128(
         &mut self,
         max_amount: Cycles,
-        dst: u32,
+        dst: usize,
         heap: &mut [u8],
     ) -> HypervisorResult<()> {
         let result = {
@@ -2629,9 +3140,9 @@ impl SystemApi for SystemApiImpl {
 
     fn ic0_data_certificate_copy(
         &self,
-        dst: u32,
-        offset: u32,
-        size: u32,
+        dst: usize,
+        offset: usize,
+        size: usize,
         heap: &mut [u8],
     ) -> HypervisorResult<()> {
         let result = match &self.api_type {
@@ -2651,8 +3162,6 @@ impl SystemApi for SystemApiImpl {
                 data_certificate, ..
             } => match data_certificate {
                 Some(data_certificate) => {
-                    let (dst, offset, size) = (dst as usize, offset as usize, size as usize);
-
                     let (upper_bound, overflow) = offset.overflowing_add(size);
                     if overflow || upper_bound > data_certificate.len() {
                         return Err(ContractViolation(format!(
@@ -2698,7 +3207,12 @@ impl SystemApi for SystemApiImpl {
         result
     }
 
-    fn ic0_certified_data_set(&mut self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<()> {
+    fn ic0_certified_data_set(
+        &mut self,
+        src: usize,
+        size: usize,
+        heap: &[u8],
+    ) -> HypervisorResult<()> {
         let result = match &mut self.api_type {
             ApiType::Start { .. }
             | ApiType::ReplicatedQuery { .. }
@@ -2725,7 +3239,6 @@ impl SystemApi for SystemApiImpl {
                     )));
                 }
 
-                let (src, size) = (src as usize, size as usize);
                 let (upper_bound, overflow) = src.overflowing_add(size);
                 if overflow || upper_bound > heap.len() {
                     return Err(ContractViolation(format!(
@@ -2800,8 +3313,8 @@ impl SystemApi for SystemApiImpl {
         result
     }
 
-    fn ic0_debug_print(&self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<()> {
-        const MAX_DEBUG_MESSAGE_SIZE: u32 = 32 * 1024;
+    fn ic0_debug_print(&self, src: usize, size: usize, heap: &[u8]) -> HypervisorResult<()> {
+        const MAX_DEBUG_MESSAGE_SIZE: usize = 32 * 1024;
         let size = size.min(MAX_DEBUG_MESSAGE_SIZE);
         let msg = match valid_subslice("ic0.debug_print", src, size, heap) {
             Ok(bytes) => String::from_utf8_lossy(bytes).to_string(),
@@ -2832,8 +3345,8 @@ impl SystemApi for SystemApiImpl {
         Ok(())
     }
 
-    fn ic0_trap(&self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<()> {
-        const MAX_ERROR_MESSAGE_SIZE: u32 = 16 * 1024;
+    fn ic0_trap(&self, src: usize, size: usize, heap: &[u8]) -> HypervisorResult<()> {
+        const MAX_ERROR_MESSAGE_SIZE: usize = 16 * 1024;
         let size = size.min(MAX_ERROR_MESSAGE_SIZE);
         let result = {
             let msg = valid_subslice("trap", src, size, heap)
@@ -2845,7 +3358,7 @@ impl SystemApi for SystemApiImpl {
         Err(result)
     }
 
-    fn ic0_is_controller(&self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<u32> {
+    fn ic0_is_controller(&self, src: usize, size: usize, heap: &[u8]) -> HypervisorResult<u32> {
         let result = match &self.api_type {
             ApiType::Start { .. }
             | ApiType::Init { .. }
@@ -2885,7 +3398,7 @@ impl SystemApi for SystemApiImpl {
     fn ic0_cycles_burn128(
         &mut self,
         amount: Cycles,
-        dst: u32,
+        dst: usize,
         heap: &mut [u8],
     ) -> HypervisorResult<()> {
         let method_name = "ic0_cycles_burn128";
@@ -2913,6 +3426,93 @@ impl SystemApi for SystemApiImpl {
         trace_syscall!(self, ic0_cycles_burn128, result, amount);
         result
     }
+
+    fn ic0_cost_call(
+        &self,
+        method_name_size: u64,
+        payload_size: u64,
+        dst: usize,
+        heap: &mut [u8],
+    ) -> HypervisorResult<()> {
+        let cost = self
+            .sandbox_safe_system_state
+            .cost_call(method_name_size, payload_size);
+        let result = copy_cycles_to_heap(cost, dst, heap, "ic0_cost_call");
+        trace_syscall!(
+            self,
+            ic0_cost_call,
+            result,
+            method_name_size,
+            payload_size,
+            cost
+        );
+        result
+    }
+
+    fn ic0_cost_create_canister(&self, dst: usize, heap: &mut [u8]) -> HypervisorResult<()> {
+        let cost = self.sandbox_safe_system_state.cost_create_canister();
+        let result = copy_cycles_to_heap(cost, dst, heap, "ic0_cost_create_canister");
+        trace_syscall!(self, ic0_cost_create_canister, result, cost);
+        result
+    }
+
+    fn ic0_cost_http_request(
+        &self,
+        request_size: u64,
+        max_res_bytes: u64,
+        dst: usize,
+        heap: &mut [u8],
+    ) -> HypervisorResult<()> {
+        let cost = self
+            .sandbox_safe_system_state
+            .cost_http_request(request_size, max_res_bytes);
+        let result = copy_cycles_to_heap(cost, dst, heap, "ic0_cost_http_request");
+        trace_syscall!(
+            self,
+            ic0_cost_http_request,
+            result,
+            request_size,
+            max_res_bytes,
+            cost
+        );
+        result
+    }
+
+    fn ic0_cost_sign_with_ecdsa(
+        &self,
+        src: usize,
+        size: usize,
+        curve: u32,
+        dst: usize,
+        heap: &mut [u8],
+    ) -> HypervisorResult<u32> {
+        let result = match curve {
+            0 => {
+                let name = valid_subslice("ic0.cost_sign_with_ecdsa", src, size, heap)
+                    .map(|bytes| String::from_utf8(bytes.to_vec()).ok());
+                match name {
+                    Ok(Some(name)) => {
+                        let key_id = EcdsaKeyId {
+                            curve: EcdsaCurve::Secp256k1,
+                            name,
+                        };
+                        match self.sandbox_safe_system_state.cost_sign_with_ecdsa(&key_id) {
+                            Some(cost) => {
+                                copy_cycles_to_heap(cost, dst, heap, "ic0_cost_sign_with_ecdsa")
+                                    .map(|()| 0)
+                            }
+                            None => Ok(2),
+                        }
+                    }
+                    Ok(None) => Ok(2),
+                    Err(err) => Err(err),
+                }
+            }
+            _ => Ok(1),
+        };
+        trace_syscall!(self, ic0_cost_sign_with_ecdsa, result, src, size, curve);
+        result
+    }
 }
 
 /// The default implementation of the `OutOfInstructionHandler` trait.
@@ -2927,7 +3527,7 @@ impl OutOfInstructionsHandler for DefaultOutOfInstructionsHandler {
 
 pub(crate) fn copy_cycles_to_heap(
     cycles: Cycles,
-    dst: u32,
+    dst: usize,
     heap: &mut [u8],
     method_name: &str,
 ) -> HypervisorResult<()> {
@@ -2936,7 +3536,6 @@ pub(crate) fn copy_cycles_to_heap(
     let size = bytes.len();
     assert_eq!(size, 16);
 
-    let dst = dst as usize;
     let (upper_bound, overflow) = dst.overflowing_add(size);
     if overflow || upper_bound > heap.len() {
         return Err(ContractViolation(format!(
@@ -2954,13 +3553,11 @@ pub(crate) fn copy_cycles_to_heap(
 
 pub(crate) fn valid_subslice<'a>(
     ctx: &str,
-    src: u32,
-    len: u32,
+    src: usize,
+    len: usize,
     slice: &'a [u8],
 ) -> HypervisorResult<&'a [u8]> {
-    let len = len as usize;
-    let src = src as usize;
-    if slice.len() < src + len {
+    if src.checked_add(len).map_or(true, |end| slice.len() < end) {
         return Err(ContractViolation(format!(
             "{}: src={} + length={} exceeds the slice size={}",
             ctx,
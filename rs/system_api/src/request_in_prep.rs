@@ -2,9 +2,9 @@ use crate::{sandbox_safe_system_state::SandboxSafeSystemState, valid_subslice};
 use ic_interfaces::execution_environment::{HypervisorError, HypervisorResult};
 use ic_logger::ReplicaLogger;
 use ic_types::{
-    messages::{CallContextId, Request},
+    messages::{CallContextId, Request, NO_DEADLINE},
     methods::{Callback, WasmClosure},
-    CanisterId, Cycles, NumBytes, PrincipalId,
+    CanisterId, CoarseTime, Cycles, NumBytes, PrincipalId,
 };
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
@@ -37,6 +37,9 @@ pub struct RequestInPrep {
     cycles: Cycles,
     method_name: String,
     method_payload: Vec<u8>,
+    /// Deadline of a best-effort call, set by `ic0.call_with_best_effort_response`;
+    /// `NO_DEADLINE` for guaranteed response calls.
+    deadline: CoarseTime,
     /// The maximum size of a message that will go to a canister on another
     /// subnet.
     max_size_remote_subnet: NumBytes,
@@ -53,10 +56,10 @@ impl RequestInPrep {
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         sender: CanisterId,
-        callee_src: u32,
-        callee_size: u32,
-        method_name_src: u32,
-        method_name_len: u32,
+        callee_src: usize,
+        callee_size: usize,
+        method_name_src: usize,
+        method_name_len: usize,
         heap: &[u8],
         on_reply: WasmClosure,
         on_reject: WasmClosure,
@@ -70,7 +73,7 @@ impl RequestInPrep {
             // the minimum of the limits.
 
             // method_name checked against sum of exported function names.
-            if method_name_len as usize > max_sum_exported_function_name_lengths {
+            if method_name_len > max_sum_exported_function_name_lengths {
                 return Err(HypervisorError::ContractViolation(format!(
                     "Size of method_name {} exceeds the allowed sum of exported function name lengths {}",
                     method_name_len, max_sum_exported_function_name_lengths
@@ -108,6 +111,7 @@ impl RequestInPrep {
             cycles: Cycles::zero(),
             method_name,
             method_payload: Vec::new(),
+            deadline: NO_DEADLINE,
             max_size_remote_subnet,
             multiplier_max_size_local_subnet,
         })
@@ -125,14 +129,26 @@ impl RequestInPrep {
         }
     }
 
+    pub(crate) fn set_deadline(&mut self, deadline: CoarseTime) -> HypervisorResult<()> {
+        if self.deadline != NO_DEADLINE {
+            Err(HypervisorError::ContractViolation(
+                "ic0.call_with_best_effort_response can be called at most once between `ic0.call_new` and `ic0.call_perform`"
+                    .to_string(),
+            ))
+        } else {
+            self.deadline = deadline;
+            Ok(())
+        }
+    }
+
     pub(crate) fn take_cycles(self) -> Cycles {
         self.cycles
     }
 
     pub(crate) fn extend_method_payload(
         &mut self,
-        src: u32,
-        size: u32,
+        src: usize,
+        size: usize,
         heap: &[u8],
     ) -> HypervisorResult<()> {
         let current_size = self.method_name.len() + self.method_payload.len();
@@ -143,7 +159,7 @@ impl RequestInPrep {
                 "Request to {}:{} has a payload size of {}, which exceeds the allowed local-subnet limit of {}",
                 self.callee,
                 self.method_name,
-                current_size + size as usize,
+                current_size + size,
                 max_size_local_subnet
             )))
         } else {
@@ -176,6 +192,7 @@ pub(crate) fn into_request(
         cycles,
         method_name,
         method_payload,
+        deadline,
         max_size_remote_subnet,
         multiplier_max_size_local_subnet,
     }: RequestInPrep,
@@ -213,6 +230,7 @@ pub(crate) fn into_request(
         on_reply,
         on_reject,
         on_cleanup,
+        deadline,
     ))?;
 
     let req = Request {
@@ -223,6 +241,7 @@ pub(crate) fn into_request(
         sender_reply_callback: callback_id,
         payment: cycles,
         metadata: None,
+        deadline,
     };
     // We cannot call `Request::payload_size_bytes()` before constructing the
     // request, so ensure our separate calculation matches the actual size.
@@ -8,8 +8,9 @@ use ic_cycles_account_manager::{
 };
 use ic_error_types::{ErrorCode, RejectCode, UserError};
 use ic_ic00_types::{
-    CreateCanisterArgs, InstallChunkedCodeArgs, InstallCodeArgsV2, Method as Ic00Method, Payload,
-    ProvisionalCreateCanisterWithCyclesArgs, UninstallCodeArgs, UpdateSettingsArgs, IC_00,
+    CreateCanisterArgs, EcdsaKeyId, InstallChunkedCodeArgs, InstallCodeArgsV2,
+    Method as Ic00Method, Payload, ProvisionalCreateCanisterWithCyclesArgs, UninstallCodeArgs,
+    UpdateSettingsArgs, IC_00,
 };
 use ic_interfaces::execution_environment::{HypervisorError, HypervisorResult};
 use ic_logger::{info, ReplicaLogger};
@@ -22,7 +23,8 @@ use ic_replicated_state::{
 use ic_types::{
     messages::{CallContextId, CallbackId, RejectContext, Request},
     methods::Callback,
-    CanisterTimer, ComputeAllocation, Cycles, MemoryAllocation, NumInstructions, NumPages, Time,
+    CanisterTimer, CanisterTimers, ComputeAllocation, Cycles, MemoryAllocation, NumInstructions,
+    NumPages, Time,
 };
 use ic_wasm_types::WasmEngineError;
 use serde::{Deserialize, Serialize};
@@ -69,6 +71,7 @@ pub struct SystemStateChanges {
     request_slots_used: BTreeMap<CanisterId, usize>,
     requests: Vec<Request>,
     pub(super) new_global_timer: Option<CanisterTimer>,
+    pub(super) new_timers: Option<CanisterTimers>,
 }
 
 impl Default for SystemStateChanges {
@@ -83,6 +86,7 @@ impl Default for SystemStateChanges {
             request_slots_used: BTreeMap::new(),
             requests: vec![],
             new_global_timer: None,
+            new_timers: None,
         }
     }
 }
@@ -246,7 +250,12 @@ impl SystemStateChanges {
             | Ok(Ic00Method::UploadChunk)
             | Ok(Ic00Method::StoredChunks)
             | Ok(Ic00Method::DeleteChunks)
-            | Ok(Ic00Method::ClearChunkStore) => Ok(None),
+            | Ok(Ic00Method::ClearChunkStore)
+            | Ok(Ic00Method::TakeCanisterSnapshot)
+            | Ok(Ic00Method::LoadCanisterSnapshot)
+            | Ok(Ic00Method::ListCanisterSnapshots)
+            | Ok(Ic00Method::DeleteCanisterSnapshot)
+            | Ok(Ic00Method::FetchCanisterLogs) => Ok(None),
             Err(_) => Err(UserError::new(
                 ErrorCode::CanisterMethodNotFound,
                 format!("Management canister has no method '{}'", msg.method_name),
@@ -305,7 +314,7 @@ impl SystemStateChanges {
                 })?;
                 if (*amount_taken).get() > LOG_CANISTER_OPERATION_CYCLES_THRESHOLD {
                     match call_context.call_origin() {
-                        CallOrigin::CanisterUpdate(origin_canister_id, _)
+                        CallOrigin::CanisterUpdate(origin_canister_id, _, _)
                         | CallOrigin::CanisterQuery(origin_canister_id, _) => info!(
                             logger,
                             "Canister {} accepted {} cycles from canister {}.",
@@ -425,7 +434,7 @@ impl SystemStateChanges {
 
         // Verify new certified data isn't too long and set it.
         if let Some(certified_data) = self.new_certified_data.as_ref() {
-            if certified_data.len() > CERTIFIED_DATA_MAX_LENGTH as usize {
+            if certified_data.len() > CERTIFIED_DATA_MAX_LENGTH {
                 return Err(Self::error("Certified data is too large"));
             }
             system_state.certified_data = certified_data.clone();
@@ -436,6 +445,11 @@ impl SystemStateChanges {
             system_state.global_timer = new_global_timer;
         }
 
+        // Update canister named timers
+        if let Some(new_timers) = self.new_timers.as_ref() {
+            system_state.timers = new_timers.clone();
+        }
+
         Ok(())
     }
 
@@ -540,8 +554,17 @@ pub struct SandboxSafeSystemState {
     ic00_available_request_slots: usize,
     ic00_aliases: BTreeSet<CanisterId>,
     global_timer: CanisterTimer,
+    timers: CanisterTimers,
     canister_version: u64,
     controllers: BTreeSet<PrincipalId>,
+    wasm_memory_limit: Option<NumBytes>,
+    /// ECDSA keys that signatures can be requested for, mapped to the size of
+    /// the subnet that `sign_with_ecdsa` requests for the key are routed to.
+    ecdsa_signing_subnet_sizes: BTreeMap<EcdsaKeyId, usize>,
+    /// Whether this canister is hosted on the NNS subnet, which is not charged
+    /// for threshold signatures.
+    is_nns_subnet: bool,
+    environment_variables: BTreeMap<String, String>,
 }
 
 impl SandboxSafeSystemState {
@@ -566,8 +589,13 @@ impl SandboxSafeSystemState {
         subnet_size: usize,
         dirty_page_overhead: NumInstructions,
         global_timer: CanisterTimer,
+        timers: CanisterTimers,
         canister_version: u64,
         controllers: BTreeSet<PrincipalId>,
+        wasm_memory_limit: Option<NumBytes>,
+        ecdsa_signing_subnet_sizes: BTreeMap<EcdsaKeyId, usize>,
+        is_nns_subnet: bool,
+        environment_variables: BTreeMap<String, String>,
     ) -> Self {
         Self {
             canister_id,
@@ -589,8 +617,13 @@ impl SandboxSafeSystemState {
             ic00_available_request_slots,
             ic00_aliases,
             global_timer,
+            timers,
             canister_version,
             controllers,
+            wasm_memory_limit,
+            ecdsa_signing_subnet_sizes,
+            is_nns_subnet,
+            environment_variables,
         }
     }
 
@@ -632,6 +665,8 @@ impl SandboxSafeSystemState {
         let subnet_size = network_topology
             .get_subnet_size(&cycles_account_manager.get_subnet_id())
             .unwrap_or(SMALL_APP_SUBNET_MAX_SIZE);
+        let is_nns_subnet =
+            network_topology.nns_subnet_id == cycles_account_manager.get_subnet_id();
 
         Self::new_internal(
             system_state.canister_id,
@@ -653,8 +688,23 @@ impl SandboxSafeSystemState {
             subnet_size,
             dirty_page_overhead,
             system_state.global_timer,
+            system_state.timers.clone(),
             system_state.canister_version,
             system_state.controllers.clone(),
+            system_state.wasm_memory_limit,
+            network_topology
+                .ecdsa_signing_subnets
+                .iter()
+                .filter_map(|(key_id, subnets)| {
+                    // Requests are routed to the first signing subnet.
+                    let subnet_size = network_topology
+                        .get_subnet_size(subnets.first()?)
+                        .unwrap_or(SMALL_APP_SUBNET_MAX_SIZE);
+                    Some((key_id.clone(), subnet_size))
+                })
+                .collect(),
+            is_nns_subnet,
+            system_state.environment_variables.clone(),
         )
     }
 
@@ -662,20 +712,45 @@ impl SandboxSafeSystemState {
         self.canister_id
     }
 
+    /// Returns the id of the subnet hosting the canister.
+    pub fn subnet_id(&self) -> SubnetId {
+        self.cycles_account_manager.get_subnet_id()
+    }
+
+    pub fn environment_variables(&self) -> &BTreeMap<String, String> {
+        &self.environment_variables
+    }
+
     pub fn global_timer(&self) -> CanisterTimer {
         self.global_timer
     }
 
+    pub fn timers(&self) -> &CanisterTimers {
+        &self.timers
+    }
+
     pub fn canister_version(&self) -> u64 {
         self.canister_version
     }
 
+    pub fn wasm_memory_limit(&self) -> Option<NumBytes> {
+        self.wasm_memory_limit
+    }
+
     pub fn set_global_timer(&mut self, timer: CanisterTimer) {
         // Update both sandbox global timer and the changes.
         self.system_state_changes.new_global_timer = Some(timer);
         self.global_timer = timer;
     }
 
+    /// Sets the named timer `timer_id` and returns its previous value.
+    pub fn set_timer(&mut self, timer_id: u64, timer: CanisterTimer) -> CanisterTimer {
+        // Update both sandbox timers and the changes.
+        let previous = self.timers.set(timer_id, timer);
+        self.system_state_changes.new_timers = Some(self.timers.clone());
+        previous
+    }
+
     pub fn changes(self) -> SystemStateChanges {
         self.system_state_changes
     }
@@ -855,6 +930,45 @@ impl SandboxSafeSystemState {
             .prepayment_for_response_transmission(self.subnet_size)
     }
 
+    /// Returns the cycles withdrawn when performing a call with the given
+    /// method name and argument sizes.
+    pub fn cost_call(&self, method_name_size: u64, payload_size: u64) -> Cycles {
+        self.cycles_account_manager.xnet_call_total_fee(
+            NumBytes::from(method_name_size.saturating_add(payload_size)),
+            self.subnet_size,
+        )
+    }
+
+    /// Returns the fee for creating a canister on this subnet.
+    pub fn cost_create_canister(&self) -> Cycles {
+        self.cycles_account_manager
+            .canister_creation_fee(self.subnet_size)
+    }
+
+    /// Returns the cycles to be attached to an `http_request` call with the
+    /// given request size and maximum response size.
+    pub fn cost_http_request(&self, request_size: u64, max_res_bytes: u64) -> Cycles {
+        self.cycles_account_manager.http_request_fee(
+            NumBytes::from(request_size),
+            Some(NumBytes::from(max_res_bytes)),
+            self.subnet_size,
+        )
+    }
+
+    /// Returns the cycles to be attached to a `sign_with_ecdsa` call using the
+    /// given key; or `None` if the key is not known. The fee is charged by,
+    /// and hence scaled to the size of, the signing subnet.
+    pub fn cost_sign_with_ecdsa(&self, key_id: &EcdsaKeyId) -> Option<Cycles> {
+        let signing_subnet_size = *self.ecdsa_signing_subnet_sizes.get(key_id)?;
+        if self.is_nns_subnet {
+            return Some(Cycles::zero());
+        }
+        Some(
+            self.cycles_account_manager
+                .ecdsa_signature_fee(signing_subnet_size),
+        )
+    }
+
     pub(super) fn withdraw_cycles_for_transfer(
         &mut self,
         canister_current_memory_usage: NumBytes,
@@ -881,6 +995,43 @@ impl SandboxSafeSystemState {
         result
     }
 
+    /// Withdraws the fee for transmitting the bytes of a reply of the given size
+    /// that are not covered by the caller's prepayment for response
+    /// transmission.
+    pub(super) fn withdraw_cycles_for_reply_transmission(
+        &mut self,
+        canister_current_memory_usage: NumBytes,
+        canister_current_message_memory_usage: NumBytes,
+        reply_size: NumBytes,
+    ) -> HypervisorResult<()> {
+        let fee = self
+            .cycles_account_manager
+            .response_transmission_fee_beyond_prepayment(reply_size, self.subnet_size);
+        if fee.is_zero() {
+            return Ok(());
+        }
+        let mut new_balance = self.cycles_balance();
+        self.cycles_account_manager
+            .withdraw_cycles_for_transfer(
+                self.canister_id,
+                self.freeze_threshold,
+                self.memory_allocation,
+                canister_current_memory_usage,
+                canister_current_message_memory_usage,
+                self.compute_allocation,
+                &mut new_balance,
+                fee,
+                self.subnet_size,
+                self.reserved_balance(),
+            )
+            .map_err(HypervisorError::InsufficientCyclesBalance)?;
+        self.update_balance_change_consuming(
+            new_balance,
+            &[(CyclesUseCase::RequestAndResponseTransmission, fee)],
+        );
+        Ok(())
+    }
+
     #[allow(clippy::result_large_err)]
     pub fn push_output_request(
         &mut self,
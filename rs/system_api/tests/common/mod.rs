@@ -22,7 +22,7 @@ use ic_test_utilities::{
     types::ids::{call_context_test_id, canister_test_id, subnet_test_id, user_test_id},
 };
 use ic_types::{
-    messages::{CallContextId, CallbackId, RejectContext},
+    messages::{CallContextId, CallbackId, RejectContext, NO_DEADLINE},
     methods::SystemMethod,
     ComputeAllocation, Cycles, MemoryAllocation, NumInstructions, PrincipalId, Time,
 };
@@ -92,6 +92,17 @@ impl ApiTypeBuilder {
             IC_00.get(),
             SystemMethod::CanisterHeartbeat,
             mock_time(),
+            vec![],
+            CallContextId::from(1),
+        )
+    }
+
+    pub fn build_global_timer_api(incoming_payload: Vec<u8>) -> ApiType {
+        ApiType::system_task(
+            IC_00.get(),
+            SystemMethod::CanisterGlobalTimer,
+            mock_time(),
+            incoming_payload,
             CallContextId::from(1),
         )
     }
@@ -128,19 +139,49 @@ pub fn get_system_api(
     system_state: &SystemState,
     cycles_account_manager: CyclesAccountManager,
 ) -> SystemApiImpl {
-    let sandbox_safe_system_state = SandboxSafeSystemState::new(
+    get_system_api_with_network_topology(
+        api_type,
         system_state,
         cycles_account_manager,
         &NetworkTopology::default(),
+    )
+}
+
+pub fn get_system_api_with_network_topology(
+    api_type: ApiType,
+    system_state: &SystemState,
+    cycles_account_manager: CyclesAccountManager,
+    network_topology: &NetworkTopology,
+) -> SystemApiImpl {
+    get_system_api_with_execution_parameters(
+        api_type,
+        system_state,
+        cycles_account_manager,
+        network_topology,
+        execution_parameters(),
+    )
+}
+
+pub fn get_system_api_with_execution_parameters(
+    api_type: ApiType,
+    system_state: &SystemState,
+    cycles_account_manager: CyclesAccountManager,
+    network_topology: &NetworkTopology,
+    execution_parameters: ExecutionParameters,
+) -> SystemApiImpl {
+    let sandbox_safe_system_state = SandboxSafeSystemState::new(
+        system_state,
+        cycles_account_manager,
+        network_topology,
         SchedulerConfig::application_subnet().dirty_page_overhead,
-        execution_parameters().compute_allocation,
+        execution_parameters.compute_allocation,
     );
     SystemApiImpl::new(
         api_type,
         sandbox_safe_system_state,
         CANISTER_CURRENT_MEMORY_USAGE,
         CANISTER_CURRENT_MESSAGE_MEMORY_USAGE,
-        execution_parameters(),
+        execution_parameters,
         SubnetAvailableMemory::new(
             SUBNET_MEMORY_CAPACITY,
             SUBNET_MEMORY_CAPACITY,
@@ -162,7 +203,7 @@ pub fn get_system_state() -> SystemState {
         .call_context_manager_mut()
         .unwrap()
         .new_call_context(
-            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5)),
+            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5), NO_DEADLINE),
             Cycles::new(50),
             Time::from_nanos_since_unix_epoch(0),
         );
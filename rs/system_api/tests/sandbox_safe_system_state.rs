@@ -1,8 +1,9 @@
+use assert_matches::assert_matches;
 use ic_base_types::{CanisterId, NumBytes, NumSeconds, SubnetId};
 use ic_config::subnet_config::SchedulerConfig;
 use ic_constants::SMALL_APP_SUBNET_MAX_SIZE;
 use ic_ic00_types::{CanisterIdRecord, CanisterSettingsArgs, Payload, UpdateSettingsArgs, IC_00};
-use ic_interfaces::execution_environment::SystemApi;
+use ic_interfaces::execution_environment::{HypervisorError, SystemApi};
 use ic_logger::replica_logger::no_op_logger;
 use ic_nns_constants::CYCLES_MINTING_CANISTER_ID;
 use ic_registry_routing_table::CanisterIdRange;
@@ -10,7 +11,7 @@ use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::canister_state::system_state::CyclesUseCase;
 use ic_replicated_state::testing::SystemStateTesting;
 use ic_replicated_state::{NetworkTopology, SystemState};
-use ic_system_api::sandbox_safe_system_state::SandboxSafeSystemState;
+use ic_system_api::{sandbox_safe_system_state::SandboxSafeSystemState, MAX_CALL_TIMEOUT_SECONDS};
 use ic_test_utilities::{
     cycles_account_manager::CyclesAccountManagerBuilder,
     mock_time,
@@ -22,8 +23,8 @@ use ic_test_utilities::{
 };
 use ic_types::nominal_cycles::NominalCycles;
 use ic_types::{
-    messages::{CanisterMessage, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES},
-    ComputeAllocation, Cycles, NumInstructions,
+    messages::{CanisterMessage, RequestOrResponse, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES},
+    CoarseTime, ComputeAllocation, Cycles, NumInstructions,
 };
 use prometheus::IntCounter;
 use std::collections::BTreeSet;
@@ -391,6 +392,97 @@ fn call_increases_cycles_consumed_metric() {
     );
 }
 
+#[test]
+fn best_effort_call_has_deadline() {
+    let mut system_state = SystemStateBuilder::default().build();
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+    let mut api = get_system_api(
+        ApiTypeBuilder::build_update_api(),
+        &system_state,
+        cycles_account_manager,
+    );
+
+    api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]).unwrap();
+    api.ic0_call_with_best_effort_response(10).unwrap();
+    // Can only be called once per call.
+    assert_matches!(
+        api.ic0_call_with_best_effort_response(10),
+        Err(HypervisorError::ContractViolation(_))
+    );
+    api.ic0_call_perform().unwrap();
+
+    let system_state_changes = api.into_system_state_changes();
+    system_state_changes
+        .apply_changes(
+            mock_time(),
+            &mut system_state,
+            &default_network_topology(),
+            subnet_test_id(1),
+            &no_op_logger(),
+        )
+        .unwrap();
+
+    // Deadline is `mock_time()` plus the 10 seconds timeout.
+    let expected_deadline = CoarseTime::floor(mock_time()).saturating_add_secs(10);
+    let callbacks = system_state.call_context_manager().unwrap().callbacks();
+    assert_eq!(1, callbacks.len());
+    assert_eq!(
+        expected_deadline,
+        callbacks.values().next().unwrap().deadline
+    );
+    let own_canister_id = system_state.canister_id;
+    match system_state.output_into_iter(own_canister_id).next() {
+        Some((_, RequestOrResponse::Request(request))) => {
+            assert_eq!(expected_deadline, request.deadline)
+        }
+        other => panic!("Expected a request, got {:?}", other),
+    }
+}
+
+#[test]
+fn best_effort_call_timeout_is_capped() {
+    let mut system_state = SystemStateBuilder::default().build();
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+    let mut api = get_system_api(
+        ApiTypeBuilder::build_update_api(),
+        &system_state,
+        cycles_account_manager,
+    );
+
+    // Not allowed without a call under construction.
+    assert_matches!(
+        api.ic0_call_with_best_effort_response(10),
+        Err(HypervisorError::ContractViolation(_))
+    );
+
+    api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]).unwrap();
+    api.ic0_call_with_best_effort_response(u32::MAX).unwrap();
+    api.ic0_call_perform().unwrap();
+
+    let system_state_changes = api.into_system_state_changes();
+    system_state_changes
+        .apply_changes(
+            mock_time(),
+            &mut system_state,
+            &default_network_topology(),
+            subnet_test_id(1),
+            &no_op_logger(),
+        )
+        .unwrap();
+
+    let callback_deadlines: Vec<_> = system_state
+        .call_context_manager()
+        .unwrap()
+        .callbacks()
+        .values()
+        .map(|callback| callback.deadline)
+        .collect();
+    assert_eq!(
+        vec![CoarseTime::floor(mock_time()).saturating_add_secs(MAX_CALL_TIMEOUT_SECONDS)],
+        callback_deadlines
+    );
+}
+
 /// Returns the system state after performing an inter-canister call
 /// from sender to recv with given method name and argument.
 /// The sender is assumed to be on subnet with given subnet ID
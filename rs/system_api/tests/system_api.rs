@@ -1,38 +1,42 @@
+use assert_matches::assert_matches;
 use ic_base_types::{NumSeconds, PrincipalIdBlobParseError};
 use ic_config::{
-    embedders::Config as EmbeddersConfig, flag_status::FlagStatus, subnet_config::SchedulerConfig,
+    embedders::Config as EmbeddersConfig, execution_environment::MAX_CANISTER_TIMERS,
+    flag_status::FlagStatus, subnet_config::SchedulerConfig,
 };
 use ic_constants::SMALL_APP_SUBNET_MAX_SIZE;
 use ic_error_types::RejectCode;
+use ic_ic00_types::{EcdsaCurve, EcdsaKeyId};
 use ic_interfaces::execution_environment::{
-    CanisterOutOfCyclesError, HypervisorError, HypervisorResult, PerformanceCounterType,
-    SubnetAvailableMemory, SystemApi, TrapCode,
+    CanisterOutOfCyclesError, ExecutionMode, GrowableMemory, HypervisorError, HypervisorResult,
+    PerformanceCounterType, SubnetAvailableMemory, SystemApi, TrapCode,
 };
 use ic_logger::replica_logger::no_op_logger;
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::{
-    testing::CanisterQueuesTesting, CallOrigin, Memory, NetworkTopology, SystemState,
+    testing::CanisterQueuesTesting, CallOrigin, Memory, NetworkTopology, SubnetTopology,
+    SystemState,
 };
 use ic_system_api::{
     sandbox_safe_system_state::SandboxSafeSystemState, ApiType, DefaultOutOfInstructionsHandler,
-    NonReplicatedQueryKind, SystemApiImpl,
+    ExecutionParameters, NonReplicatedQueryKind, SystemApiImpl,
 };
 use ic_test_utilities::{
     cycles_account_manager::CyclesAccountManagerBuilder,
     mock_time,
     state::SystemStateBuilder,
     types::{
-        ids::{call_context_test_id, canister_test_id, subnet_test_id, user_test_id},
+        ids::{call_context_test_id, canister_test_id, node_test_id, subnet_test_id, user_test_id},
         messages::RequestBuilder,
     },
 };
 use ic_types::{
-    messages::{CallContextId, CallbackId, RejectContext, MAX_RESPONSE_COUNT_BYTES},
+    messages::{CallContextId, CallbackId, RejectContext, MAX_RESPONSE_COUNT_BYTES, NO_DEADLINE},
     methods::{Callback, WasmClosure},
-    time, CanisterTimer, CountBytes, Cycles, NumInstructions, PrincipalId, Time,
+    time, CanisterTimer, CountBytes, Cycles, NumBytes, NumInstructions, PrincipalId, Time,
 };
 use std::{
-    collections::BTreeSet,
+    collections::{BTreeMap, BTreeSet},
     convert::{From, TryInto},
     panic::{catch_unwind, UnwindSafe},
     rc::Rc,
@@ -150,18 +154,24 @@ fn test_canister_init_support() {
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
+    assert_api_supported(api.ic0_subnet_self_size());
+    assert_api_supported(api.ic0_subnet_self_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_in_replicated_execution());
+    assert_api_supported(api.ic0_env_var_count());
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(10));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_not_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_time());
     assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_global_timer_set(time::UNIX_EPOCH));
+    assert_api_supported(api.ic0_timer_set(0, time::UNIX_EPOCH));
     assert_api_supported(
         api.ic0_performance_counter(PerformanceCounterType::Instructions(0.into())),
     );
@@ -181,6 +191,10 @@ fn test_canister_init_support() {
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_supported(api.ic0_cycles_burn128(Cycles::zero(), 0, &mut []));
+    assert_api_supported(api.ic0_cost_call(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_create_canister(0, &mut []));
+    assert_api_supported(api.ic0_cost_http_request(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_sign_with_ecdsa(0, 0, 0, 0, &mut []));
     check_stable_apis_support(api);
 }
 
@@ -207,18 +221,24 @@ fn test_canister_update_support() {
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
+    assert_api_supported(api.ic0_subnet_self_size());
+    assert_api_supported(api.ic0_subnet_self_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_in_replicated_execution());
+    assert_api_supported(api.ic0_env_var_count());
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(10));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_time());
     assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_global_timer_set(time::UNIX_EPOCH));
+    assert_api_supported(api.ic0_timer_set(0, time::UNIX_EPOCH));
     assert_api_supported(
         api.ic0_performance_counter(PerformanceCounterType::Instructions(0.into())),
     );
@@ -238,6 +258,10 @@ fn test_canister_update_support() {
     assert_api_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_supported(api.ic0_cycles_burn128(Cycles::zero(), 0, &mut []));
+    assert_api_supported(api.ic0_cost_call(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_create_canister(0, &mut []));
+    assert_api_supported(api.ic0_cost_http_request(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_sign_with_ecdsa(0, 0, 0, 0, &mut []));
     check_stable_apis_support(api);
 }
 
@@ -264,18 +288,24 @@ fn test_canister_replicated_query_support() {
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
+    assert_api_supported(api.ic0_subnet_self_size());
+    assert_api_supported(api.ic0_subnet_self_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_in_replicated_execution());
+    assert_api_supported(api.ic0_env_var_count());
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(10));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_not_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_time());
     assert_api_supported(api.ic0_canister_version());
     assert_api_not_supported(api.ic0_global_timer_set(time::UNIX_EPOCH));
+    assert_api_not_supported(api.ic0_timer_set(0, time::UNIX_EPOCH));
     assert_api_supported(
         api.ic0_performance_counter(PerformanceCounterType::Instructions(0.into())),
     );
@@ -295,6 +325,10 @@ fn test_canister_replicated_query_support() {
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_not_supported(api.ic0_cycles_burn128(Cycles::zero(), 0, &mut []));
+    assert_api_supported(api.ic0_cost_call(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_create_canister(0, &mut []));
+    assert_api_supported(api.ic0_cost_http_request(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_sign_with_ecdsa(0, 0, 0, 0, &mut []));
     check_stable_apis_support(api);
 }
 
@@ -321,18 +355,24 @@ fn test_canister_pure_query_support() {
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
+    assert_api_supported(api.ic0_subnet_self_size());
+    assert_api_supported(api.ic0_subnet_self_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_in_replicated_execution());
+    assert_api_supported(api.ic0_env_var_count());
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(10));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_not_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_time());
     assert_api_supported(api.ic0_canister_version());
     assert_api_not_supported(api.ic0_global_timer_set(time::UNIX_EPOCH));
+    assert_api_not_supported(api.ic0_timer_set(0, time::UNIX_EPOCH));
     assert_api_supported(
         api.ic0_performance_counter(PerformanceCounterType::Instructions(0.into())),
     );
@@ -352,6 +392,10 @@ fn test_canister_pure_query_support() {
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_not_supported(api.ic0_cycles_burn128(Cycles::zero(), 0, &mut []));
+    assert_api_supported(api.ic0_cost_call(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_create_canister(0, &mut []));
+    assert_api_supported(api.ic0_cost_http_request(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_sign_with_ecdsa(0, 0, 0, 0, &mut []));
     check_stable_apis_support(api);
 }
 
@@ -388,18 +432,24 @@ fn test_canister_stateful_query_support() {
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
+    assert_api_supported(api.ic0_subnet_self_size());
+    assert_api_supported(api.ic0_subnet_self_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_in_replicated_execution());
+    assert_api_supported(api.ic0_env_var_count());
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(10));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_time());
     assert_api_supported(api.ic0_canister_version());
     assert_api_not_supported(api.ic0_global_timer_set(time::UNIX_EPOCH));
+    assert_api_not_supported(api.ic0_timer_set(0, time::UNIX_EPOCH));
     assert_api_supported(
         api.ic0_performance_counter(PerformanceCounterType::Instructions(0.into())),
     );
@@ -419,6 +469,10 @@ fn test_canister_stateful_query_support() {
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_not_supported(api.ic0_cycles_burn128(Cycles::zero(), 0, &mut []));
+    assert_api_supported(api.ic0_cost_call(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_create_canister(0, &mut []));
+    assert_api_supported(api.ic0_cost_http_request(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_sign_with_ecdsa(0, 0, 0, 0, &mut []));
     check_stable_apis_support(api);
 }
 
@@ -445,18 +499,24 @@ fn test_reply_api_support_on_nns() {
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
+    assert_api_supported(api.ic0_subnet_self_size());
+    assert_api_supported(api.ic0_subnet_self_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_in_replicated_execution());
+    assert_api_supported(api.ic0_env_var_count());
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(10));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_time());
     assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_global_timer_set(time::UNIX_EPOCH));
+    assert_api_supported(api.ic0_timer_set(0, time::UNIX_EPOCH));
     assert_api_supported(
         api.ic0_performance_counter(PerformanceCounterType::Instructions(0.into())),
     );
@@ -476,6 +536,10 @@ fn test_reply_api_support_on_nns() {
     assert_api_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_supported(api.ic0_cycles_burn128(Cycles::zero(), 0, &mut []));
+    assert_api_supported(api.ic0_cost_call(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_create_canister(0, &mut []));
+    assert_api_supported(api.ic0_cost_http_request(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_sign_with_ecdsa(0, 0, 0, 0, &mut []));
     check_stable_apis_support(api);
 }
 
@@ -502,18 +566,24 @@ fn test_reply_api_support_non_nns() {
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
+    assert_api_supported(api.ic0_subnet_self_size());
+    assert_api_supported(api.ic0_subnet_self_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_in_replicated_execution());
+    assert_api_supported(api.ic0_env_var_count());
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(10));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_time());
     assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_global_timer_set(time::UNIX_EPOCH));
+    assert_api_supported(api.ic0_timer_set(0, time::UNIX_EPOCH));
     assert_api_supported(
         api.ic0_performance_counter(PerformanceCounterType::Instructions(0.into())),
     );
@@ -533,6 +603,10 @@ fn test_reply_api_support_non_nns() {
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_supported(api.ic0_cycles_burn128(Cycles::zero(), 0, &mut []));
+    assert_api_supported(api.ic0_cost_call(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_create_canister(0, &mut []));
+    assert_api_supported(api.ic0_cost_http_request(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_sign_with_ecdsa(0, 0, 0, 0, &mut []));
     check_stable_apis_support(api);
 }
 
@@ -560,18 +634,24 @@ fn test_reject_api_support_on_nns() {
     assert_api_supported(api.ic0_msg_reject_msg_size());
     assert_api_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
+    assert_api_supported(api.ic0_subnet_self_size());
+    assert_api_supported(api.ic0_subnet_self_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_in_replicated_execution());
+    assert_api_supported(api.ic0_env_var_count());
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(10));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_time());
     assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_global_timer_set(time::UNIX_EPOCH));
+    assert_api_supported(api.ic0_timer_set(0, time::UNIX_EPOCH));
     assert_api_supported(
         api.ic0_performance_counter(PerformanceCounterType::Instructions(0.into())),
     );
@@ -591,6 +671,10 @@ fn test_reject_api_support_on_nns() {
     assert_api_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_supported(api.ic0_cycles_burn128(Cycles::zero(), 0, &mut []));
+    assert_api_supported(api.ic0_cost_call(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_create_canister(0, &mut []));
+    assert_api_supported(api.ic0_cost_http_request(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_sign_with_ecdsa(0, 0, 0, 0, &mut []));
     check_stable_apis_support(api);
 }
 
@@ -618,18 +702,24 @@ fn test_reject_api_support_non_nns() {
     assert_api_supported(api.ic0_msg_reject_msg_size());
     assert_api_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
+    assert_api_supported(api.ic0_subnet_self_size());
+    assert_api_supported(api.ic0_subnet_self_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_in_replicated_execution());
+    assert_api_supported(api.ic0_env_var_count());
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(10));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_time());
     assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_global_timer_set(time::UNIX_EPOCH));
+    assert_api_supported(api.ic0_timer_set(0, time::UNIX_EPOCH));
     assert_api_supported(
         api.ic0_performance_counter(PerformanceCounterType::Instructions(0.into())),
     );
@@ -649,6 +739,10 @@ fn test_reject_api_support_non_nns() {
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_supported(api.ic0_cycles_burn128(Cycles::zero(), 0, &mut []));
+    assert_api_supported(api.ic0_cost_call(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_create_canister(0, &mut []));
+    assert_api_supported(api.ic0_cost_http_request(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_sign_with_ecdsa(0, 0, 0, 0, &mut []));
     check_stable_apis_support(api);
 }
 
@@ -675,18 +769,24 @@ fn test_pre_upgrade_support() {
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
+    assert_api_supported(api.ic0_subnet_self_size());
+    assert_api_supported(api.ic0_subnet_self_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_in_replicated_execution());
+    assert_api_supported(api.ic0_env_var_count());
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(10));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_not_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_time());
     assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_global_timer_set(time::UNIX_EPOCH));
+    assert_api_supported(api.ic0_timer_set(0, time::UNIX_EPOCH));
     assert_api_supported(
         api.ic0_performance_counter(PerformanceCounterType::Instructions(0.into())),
     );
@@ -706,6 +806,10 @@ fn test_pre_upgrade_support() {
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_supported(api.ic0_cycles_burn128(Cycles::zero(), 0, &mut []));
+    assert_api_supported(api.ic0_cost_call(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_create_canister(0, &mut []));
+    assert_api_supported(api.ic0_cost_http_request(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_sign_with_ecdsa(0, 0, 0, 0, &mut []));
     check_stable_apis_support(api);
 }
 
@@ -732,18 +836,24 @@ fn test_start_support() {
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_canister_self_size());
+    assert_api_supported(api.ic0_subnet_self_size());
+    assert_api_supported(api.ic0_subnet_self_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_in_replicated_execution());
+    assert_api_supported(api.ic0_env_var_count());
     assert_api_not_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(10));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_not_supported(api.ic0_call_perform());
     assert_api_not_supported(api.ic0_time());
     assert_api_not_supported(api.ic0_canister_version());
     assert_api_not_supported(api.ic0_global_timer_set(time::UNIX_EPOCH));
+    assert_api_not_supported(api.ic0_timer_set(0, time::UNIX_EPOCH));
     assert_api_supported(
         api.ic0_performance_counter(PerformanceCounterType::Instructions(0.into())),
     );
@@ -763,6 +873,10 @@ fn test_start_support() {
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_not_supported(api.ic0_cycles_burn128(Cycles::zero(), 0, &mut []));
+    assert_api_supported(api.ic0_cost_call(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_create_canister(0, &mut []));
+    assert_api_supported(api.ic0_cost_http_request(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_sign_with_ecdsa(0, 0, 0, 0, &mut []));
     check_stable_apis_support(api);
 }
 
@@ -793,18 +907,24 @@ fn test_cleanup_support() {
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
+    assert_api_supported(api.ic0_subnet_self_size());
+    assert_api_supported(api.ic0_subnet_self_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_in_replicated_execution());
+    assert_api_supported(api.ic0_env_var_count());
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(10));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_not_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_time());
     assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_global_timer_set(time::UNIX_EPOCH));
+    assert_api_supported(api.ic0_timer_set(0, time::UNIX_EPOCH));
     assert_api_supported(
         api.ic0_performance_counter(PerformanceCounterType::Instructions(0.into())),
     );
@@ -824,6 +944,10 @@ fn test_cleanup_support() {
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_supported(api.ic0_cycles_burn128(Cycles::zero(), 0, &mut []));
+    assert_api_supported(api.ic0_cost_call(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_create_canister(0, &mut []));
+    assert_api_supported(api.ic0_cost_http_request(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_sign_with_ecdsa(0, 0, 0, 0, &mut []));
     check_stable_apis_support(api);
 }
 
@@ -855,18 +979,24 @@ fn test_inspect_message_support() {
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
+    assert_api_supported(api.ic0_subnet_self_size());
+    assert_api_supported(api.ic0_subnet_self_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_in_replicated_execution());
+    assert_api_supported(api.ic0_env_var_count());
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_not_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_not_supported(api.ic0_call_with_best_effort_response(10));
     assert_api_not_supported(api.ic0_call_cycles_add(0));
     assert_api_not_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_not_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_time());
     assert_api_supported(api.ic0_canister_version());
     assert_api_not_supported(api.ic0_global_timer_set(time::UNIX_EPOCH));
+    assert_api_not_supported(api.ic0_timer_set(0, time::UNIX_EPOCH));
     assert_api_supported(
         api.ic0_performance_counter(PerformanceCounterType::Instructions(0.into())),
     );
@@ -886,6 +1016,10 @@ fn test_inspect_message_support() {
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_not_supported(api.ic0_cycles_burn128(Cycles::zero(), 0, &mut []));
+    assert_api_supported(api.ic0_cost_call(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_create_canister(0, &mut []));
+    assert_api_supported(api.ic0_cost_http_request(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_sign_with_ecdsa(0, 0, 0, 0, &mut []));
     check_stable_apis_support(api);
 }
 
@@ -913,18 +1047,24 @@ fn test_canister_system_task_support() {
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
+    assert_api_supported(api.ic0_subnet_self_size());
+    assert_api_supported(api.ic0_subnet_self_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_in_replicated_execution());
+    assert_api_supported(api.ic0_env_var_count());
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(10));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_time());
     assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_global_timer_set(time::UNIX_EPOCH));
+    assert_api_supported(api.ic0_timer_set(0, time::UNIX_EPOCH));
     assert_api_supported(
         api.ic0_performance_counter(PerformanceCounterType::Instructions(0.into())),
     );
@@ -944,6 +1084,10 @@ fn test_canister_system_task_support() {
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_supported(api.ic0_cycles_burn128(Cycles::zero(), 0, &mut []));
+    assert_api_supported(api.ic0_cost_call(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_create_canister(0, &mut []));
+    assert_api_supported(api.ic0_cost_http_request(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_sign_with_ecdsa(0, 0, 0, 0, &mut []));
     check_stable_apis_support(api);
 }
 
@@ -970,18 +1114,24 @@ fn test_canister_system_task_support_nns() {
     assert_api_not_supported(api.ic0_msg_reject_msg_size());
     assert_api_not_supported(api.ic0_msg_reject_msg_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_canister_self_size());
+    assert_api_supported(api.ic0_subnet_self_size());
+    assert_api_supported(api.ic0_subnet_self_copy(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_in_replicated_execution());
+    assert_api_supported(api.ic0_env_var_count());
     assert_api_supported(api.ic0_canister_self_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_debug_print(0, 0, &[]));
     assert_api_supported(api.ic0_trap(0, 0, &[]));
     assert_api_supported(api.ic0_call_new(0, 0, 0, 0, 0, 0, 0, 0, &[]));
     assert_api_supported(api.ic0_call_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_call_on_cleanup(0, 0));
+    assert_api_supported(api.ic0_call_with_best_effort_response(10));
     assert_api_supported(api.ic0_call_cycles_add(0));
     assert_api_supported(api.ic0_call_cycles_add128(Cycles::new(0)));
     assert_api_supported(api.ic0_call_perform());
     assert_api_supported(api.ic0_time());
     assert_api_supported(api.ic0_canister_version());
     assert_api_supported(api.ic0_global_timer_set(time::UNIX_EPOCH));
+    assert_api_supported(api.ic0_timer_set(0, time::UNIX_EPOCH));
     assert_api_supported(
         api.ic0_performance_counter(PerformanceCounterType::Instructions(0.into())),
     );
@@ -1001,6 +1151,10 @@ fn test_canister_system_task_support_nns() {
     assert_api_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
     assert_api_supported(api.ic0_cycles_burn128(Cycles::zero(), 0, &mut []));
+    assert_api_supported(api.ic0_cost_call(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_create_canister(0, &mut []));
+    assert_api_supported(api.ic0_cost_http_request(0, 0, 0, &mut []));
+    assert_api_supported(api.ic0_cost_sign_with_ecdsa(0, 0, 0, 0, &mut []));
     check_stable_apis_support(api);
 }
 
@@ -1151,7 +1305,7 @@ fn test_canister_balance() {
         .call_context_manager_mut()
         .unwrap()
         .new_call_context(
-            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5)),
+            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5), NO_DEADLINE),
             Cycles::new(50),
             Time::from_nanos_since_unix_epoch(0),
         );
@@ -1179,7 +1333,7 @@ fn test_canister_cycle_balance() {
         .call_context_manager_mut()
         .unwrap()
         .new_call_context(
-            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5)),
+            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5), NO_DEADLINE),
             Cycles::new(50),
             Time::from_nanos_since_unix_epoch(0),
         );
@@ -1193,9 +1347,10 @@ fn test_canister_cycle_balance() {
     // Check ic0_canister_cycle_balance.
     assert_eq!(
         api.ic0_canister_cycle_balance(),
-        Err(HypervisorError::Trapped(
-            TrapCode::CyclesAmountTooBigFor64Bit
-        ))
+        Err(HypervisorError::Trapped {
+            trap_code: TrapCode::CyclesAmountTooBigFor64Bit,
+            backtrace: None,
+        })
     );
 
     let mut heap = vec![0; 16];
@@ -1213,7 +1368,7 @@ fn test_msg_cycles_available_traps() {
         .call_context_manager_mut()
         .unwrap()
         .new_call_context(
-            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5)),
+            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5), NO_DEADLINE),
             available_cycles,
             Time::from_nanos_since_unix_epoch(0),
         );
@@ -1226,9 +1381,10 @@ fn test_msg_cycles_available_traps() {
 
     assert_eq!(
         api.ic0_msg_cycles_available(),
-        Err(HypervisorError::Trapped(
-            TrapCode::CyclesAmountTooBigFor64Bit
-        ))
+        Err(HypervisorError::Trapped {
+            trap_code: TrapCode::CyclesAmountTooBigFor64Bit,
+            backtrace: None,
+        })
     );
 
     let mut heap = vec![0; 16];
@@ -1250,9 +1406,10 @@ fn test_msg_cycles_refunded_traps() {
 
     assert_eq!(
         api.ic0_msg_cycles_refunded(),
-        Err(HypervisorError::Trapped(
-            TrapCode::CyclesAmountTooBigFor64Bit
-        ))
+        Err(HypervisorError::Trapped {
+            trap_code: TrapCode::CyclesAmountTooBigFor64Bit,
+            backtrace: None,
+        })
     );
 
     let mut heap = vec![0; 16];
@@ -1374,7 +1531,7 @@ fn msg_cycles_accept_all_cycles_in_call_context() {
         .call_context_manager_mut()
         .unwrap()
         .new_call_context(
-            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5)),
+            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5), NO_DEADLINE),
             Cycles::from(amount),
             Time::from_nanos_since_unix_epoch(0),
         );
@@ -1397,7 +1554,7 @@ fn msg_cycles_accept_all_cycles_in_call_context_when_more_asked() {
         .call_context_manager_mut()
         .unwrap()
         .new_call_context(
-            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5)),
+            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5), NO_DEADLINE),
             Cycles::new(40),
             Time::from_nanos_since_unix_epoch(0),
         );
@@ -1428,7 +1585,7 @@ fn call_perform_not_enough_cycles_resets_state() {
         .call_context_manager_mut()
         .unwrap()
         .new_call_context(
-            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5)),
+            CallOrigin::CanisterUpdate(canister_test_id(33), CallbackId::from(5), NO_DEADLINE),
             Cycles::new(40),
             Time::from_nanos_since_unix_epoch(0),
         );
@@ -1500,7 +1657,7 @@ fn update_available_memory_updates_subnet_available_memory() {
         no_op_logger(),
     );
 
-    api.update_available_memory(0, 1, wasm_page_size as u64)
+    api.update_available_memory(0, 1, GrowableMemory::WasmMemory)
         .unwrap();
     assert_eq!(api.get_allocated_bytes().get() as i64, wasm_page_size);
     assert_eq!(api.get_allocated_message_bytes().get() as i64, 0);
@@ -1509,7 +1666,7 @@ fn update_available_memory_updates_subnet_available_memory() {
         wasm_custom_sections_available_memory_before
     );
 
-    api.update_available_memory(0, 10, wasm_page_size as u64)
+    api.update_available_memory(0, 10, GrowableMemory::WasmMemory)
         .unwrap_err();
     assert_eq!(api.get_allocated_bytes().get() as i64, wasm_page_size);
     assert_eq!(api.get_allocated_message_bytes().get() as i64, 0);
@@ -1550,6 +1707,7 @@ fn push_output_request_respects_memory_limits() {
             WasmClosure::new(0, 0),
             WasmClosure::new(0, 0),
             None,
+            NO_DEADLINE,
         ))
         .unwrap();
     let mut api = SystemApiImpl::new(
@@ -1655,6 +1813,7 @@ fn push_output_request_oversized_request_memory_limits() {
             WasmClosure::new(0, 0),
             WasmClosure::new(0, 0),
             None,
+            NO_DEADLINE,
         ))
         .unwrap();
     let mut api = SystemApiImpl::new(
@@ -1770,6 +1929,94 @@ fn ic0_global_timer_set_is_propagated_from_sandbox() {
     );
 }
 
+#[test]
+fn ic0_timer_set_is_propagated_from_sandbox() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+    let mut system_state = SystemStateBuilder::default().build();
+    let mut api = get_system_api(
+        ApiTypeBuilder::build_update_api(),
+        &system_state,
+        cycles_account_manager,
+    );
+
+    assert_eq!(
+        api.ic0_timer_set(7, Time::from_nanos_since_unix_epoch(1))
+            .unwrap(),
+        time::UNIX_EPOCH
+    );
+    assert_eq!(
+        api.ic0_timer_set(7, Time::from_nanos_since_unix_epoch(2))
+            .unwrap(),
+        Time::from_nanos_since_unix_epoch(1)
+    );
+    assert_eq!(
+        api.ic0_timer_set(8, Time::from_nanos_since_unix_epoch(3))
+            .unwrap(),
+        time::UNIX_EPOCH
+    );
+    // Setting the time to zero cancels the timer.
+    assert_eq!(
+        api.ic0_timer_set(8, time::UNIX_EPOCH).unwrap(),
+        Time::from_nanos_since_unix_epoch(3)
+    );
+
+    // Propagate system state changes
+    assert!(system_state.timers.is_empty());
+    let system_state_changes = api.into_system_state_changes();
+    system_state_changes
+        .apply_changes(
+            mock_time(),
+            &mut system_state,
+            &default_network_topology(),
+            subnet_test_id(1),
+            &no_op_logger(),
+        )
+        .unwrap();
+    assert_eq!(
+        system_state.timers.iter().collect::<Vec<_>>(),
+        vec![(7, Time::from_nanos_since_unix_epoch(2))]
+    );
+}
+
+#[test]
+fn ic0_timer_set_fails_above_max_canister_timers() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+    let system_state = SystemStateBuilder::default().build();
+    let mut api = get_system_api(
+        ApiTypeBuilder::build_update_api(),
+        &system_state,
+        cycles_account_manager,
+    );
+
+    let deadline = Time::from_nanos_since_unix_epoch(1);
+    for timer_id in 0..MAX_CANISTER_TIMERS as u64 {
+        api.ic0_timer_set(timer_id, deadline).unwrap();
+    }
+    // Resetting an existing timer and cancelling a missing one are still allowed.
+    api.ic0_timer_set(0, deadline).unwrap();
+    api.ic0_timer_set(MAX_CANISTER_TIMERS as u64, time::UNIX_EPOCH)
+        .unwrap();
+    assert_matches!(
+        api.ic0_timer_set(MAX_CANISTER_TIMERS as u64, deadline),
+        Err(HypervisorError::ContractViolation(_))
+    );
+}
+
+#[test]
+fn ic0_msg_arg_data_is_supported_in_global_timer() {
+    let system_state = SystemStateBuilder::default().build();
+    let api = get_system_api(
+        ApiTypeBuilder::build_global_timer_api(7_u64.to_le_bytes().to_vec()),
+        &system_state,
+        CyclesAccountManagerBuilder::new().build(),
+    );
+
+    assert_eq!(api.ic0_msg_arg_data_size().unwrap(), 8);
+    let mut heap = vec![0; 8];
+    api.ic0_msg_arg_data_copy(0, 0, 8, &mut heap).unwrap();
+    assert_eq!(heap, 7_u64.to_le_bytes());
+}
+
 #[test]
 fn ic0_is_controller_test() {
     let mut system_state = SystemStateBuilder::default().build();
@@ -1784,7 +2031,7 @@ fn ic0_is_controller_test() {
     for i in 1..5 {
         let controller = user_test_id(i).get();
         assert_eq!(
-            api.ic0_is_controller(0, controller.as_slice().len() as u32, controller.as_slice())
+            api.ic0_is_controller(0, controller.as_slice().len(), controller.as_slice())
                 .unwrap(),
             (i <= 2) as u32
         );
@@ -1800,7 +2047,7 @@ fn ic0_is_controller_invalid_principal_id() {
     );
     let controller = [0u8; 70];
     assert!(matches!(
-        api.ic0_is_controller(0, controller.len() as u32, &controller),
+        api.ic0_is_controller(0, controller.len(), &controller),
         Err(HypervisorError::InvalidPrincipalId(
             PrincipalIdBlobParseError(..)
         ))
@@ -1839,3 +2086,318 @@ fn test_ic0_cycles_burn() {
     // There are no more cycles that can be burned.
     assert_eq!(Cycles::new(0), Cycles::from(&heap));
 }
+
+#[test]
+fn test_ic0_subnet_self() {
+    let subnet_id = subnet_test_id(7);
+    let mut api = get_system_api(
+        ApiTypeBuilder::build_update_api(),
+        &get_system_state(),
+        CyclesAccountManagerBuilder::new()
+            .with_subnet_id(subnet_id)
+            .build(),
+    );
+    // The little-endian test id, the subnet test id marker and the opaque
+    // principal class.
+    let id_bytes = [7, 0, 0, 0, 0, 0, 0, 0, 0xfc, 0x01];
+    assert_eq!(subnet_id.get_ref().as_slice(), &id_bytes);
+    assert_eq!(api.ic0_subnet_self_size(), Ok(id_bytes.len()));
+
+    let mut heap = vec![0xaa; 16];
+    api.ic0_subnet_self_copy(0, 0, id_bytes.len(), &mut heap)
+        .unwrap();
+    assert_eq!(&heap[..10], &id_bytes);
+    assert_eq!(&heap[10..], &[0xaa; 6]);
+
+    // Copies a sub-range of the id to an offset in the heap.
+    let mut heap = vec![0xaa; 16];
+    api.ic0_subnet_self_copy(4, 8, 2, &mut heap).unwrap();
+    assert_eq!(
+        heap,
+        vec![
+            0xaa, 0xaa, 0xaa, 0xaa, 0xfc, 0x01, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa,
+            0xaa, 0xaa
+        ]
+    );
+
+    // Out of bounds accesses fail without writing to the heap.
+    let mut heap = vec![0xaa; 16];
+    assert_matches!(
+        api.ic0_subnet_self_copy(0, 8, 3, &mut heap),
+        Err(HypervisorError::ContractViolation(_))
+    );
+    assert_matches!(
+        api.ic0_subnet_self_copy(10, 0, 10, &mut heap),
+        Err(HypervisorError::ContractViolation(_))
+    );
+    assert_eq!(heap, vec![0xaa; 16]);
+}
+
+#[test]
+fn test_ic0_in_replicated_execution() {
+    let in_replicated_execution = |api_type: ApiType, execution_mode: ExecutionMode| {
+        get_system_api_with_execution_parameters(
+            api_type,
+            &get_system_state(),
+            CyclesAccountManagerBuilder::new().build(),
+            &NetworkTopology::default(),
+            ExecutionParameters {
+                execution_mode,
+                ..execution_parameters()
+            },
+        )
+        .ic0_in_replicated_execution()
+        .unwrap()
+    };
+    let caller = user_test_id(1).get();
+    let reply_callback = |execution_mode| {
+        ApiType::reply_callback(
+            mock_time(),
+            caller,
+            vec![],
+            Cycles::zero(),
+            CallContextId::new(1),
+            false,
+            execution_mode,
+            0.into(),
+        )
+    };
+    let reject_callback = |execution_mode| {
+        ApiType::reject_callback(
+            mock_time(),
+            caller,
+            RejectContext::new(RejectCode::CanisterReject, "error"),
+            Cycles::zero(),
+            CallContextId::new(1),
+            false,
+            execution_mode,
+            0.into(),
+        )
+    };
+    let cleanup = ApiType::Cleanup {
+        caller,
+        time: mock_time(),
+        call_context_instructions_executed: 0.into(),
+    };
+
+    // Replicated execution.
+    for api_type in [
+        ApiType::start(mock_time()),
+        ApiType::init(mock_time(), vec![], caller),
+        ApiType::pre_upgrade(mock_time(), caller),
+        ApiTypeBuilder::build_update_api(),
+        ApiTypeBuilder::build_system_task_api(),
+        ApiType::replicated_query(mock_time(), vec![], caller, None),
+        reply_callback(ExecutionMode::Replicated),
+        reject_callback(ExecutionMode::Replicated),
+        cleanup.clone(),
+    ] {
+        assert_eq!(
+            in_replicated_execution(api_type.clone(), ExecutionMode::Replicated),
+            1,
+            "{}",
+            api_type.as_str()
+        );
+    }
+
+    // Non-replicated execution, including the callbacks and cleanup of
+    // composite queries.
+    for kind in [
+        NonReplicatedQueryKind::Pure,
+        NonReplicatedQueryKind::Stateful {
+            call_context_id: CallContextId::new(1),
+            outgoing_request: None,
+        },
+    ] {
+        let api_type = ApiType::non_replicated_query(
+            mock_time(),
+            caller,
+            subnet_test_id(1),
+            vec![],
+            Some(vec![1]),
+            kind,
+        );
+        assert_eq!(
+            in_replicated_execution(api_type, ExecutionMode::NonReplicated),
+            0
+        );
+    }
+    for api_type in [
+        ApiType::inspect_message(caller, "hello".to_string(), vec![], mock_time()),
+        reply_callback(ExecutionMode::NonReplicated),
+        reject_callback(ExecutionMode::NonReplicated),
+        cleanup,
+    ] {
+        assert_eq!(
+            in_replicated_execution(api_type.clone(), ExecutionMode::NonReplicated),
+            0,
+            "{}",
+            api_type.as_str()
+        );
+    }
+}
+
+#[test]
+fn test_ic0_env_vars() {
+    let mut system_state = get_system_state();
+    system_state.environment_variables = BTreeMap::from([
+        ("B_VAR".to_string(), "value_b".to_string()),
+        ("A_VAR".to_string(), "value_a".to_string()),
+    ]);
+    let api = get_system_api(
+        ApiTypeBuilder::build_update_api(),
+        &system_state,
+        CyclesAccountManagerBuilder::new().build(),
+    );
+    assert_eq!(api.ic0_env_var_count(), Ok(2));
+
+    // Variables are indexed in the order of their names.
+    let mut heap = vec![0; 16];
+    assert_eq!(api.ic0_env_var_name_size(0), Ok(5));
+    api.ic0_env_var_name_copy(0, 0, 0, 5, &mut heap).unwrap();
+    assert_eq!(&heap[0..5], b"A_VAR");
+    api.ic0_env_var_name_copy(1, 0, 2, 3, &mut heap).unwrap();
+    assert_eq!(&heap[0..3], b"VAR");
+    assert_matches!(
+        api.ic0_env_var_name_size(2),
+        Err(HypervisorError::ContractViolation(_))
+    );
+
+    let mut heap = vec![0; 32];
+    heap[0..5].copy_from_slice(b"B_VAR");
+    heap[5..10].copy_from_slice(b"C_VAR");
+    assert_eq!(api.ic0_env_var_name_exists(0, 5, &heap), Ok(1));
+    assert_eq!(api.ic0_env_var_name_exists(5, 5, &heap), Ok(0));
+    assert_eq!(api.ic0_env_var_value_size(0, 5, &heap), Ok(7));
+    api.ic0_env_var_value_copy(0, 5, 16, 0, 7, &mut heap)
+        .unwrap();
+    assert_eq!(&heap[16..23], b"value_b");
+    assert_matches!(
+        api.ic0_env_var_value_size(5, 5, &heap),
+        Err(HypervisorError::ContractViolation(_))
+    );
+    assert_matches!(
+        api.ic0_env_var_value_copy(0, 5, 16, 3, 7, &mut heap),
+        Err(HypervisorError::ContractViolation(_))
+    );
+
+    heap[0] = 0xff;
+    assert_matches!(
+        api.ic0_env_var_name_exists(0, 5, &heap),
+        Err(HypervisorError::ContractViolation(_))
+    );
+}
+
+#[test]
+fn test_ic0_cost_apis() {
+    let system_state = SystemStateBuilder::default().build();
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+    let key_id = EcdsaKeyId {
+        curve: EcdsaCurve::Secp256k1,
+        name: "key_1".to_string(),
+    };
+    let network_topology = NetworkTopology {
+        ecdsa_signing_subnets: BTreeMap::from([(key_id.clone(), vec![subnet_test_id(1)])]),
+        ..NetworkTopology::default()
+    };
+    let api = get_system_api_with_network_topology(
+        ApiTypeBuilder::build_update_api(),
+        &system_state,
+        cycles_account_manager,
+        &network_topology,
+    );
+    let subnet_size = SMALL_APP_SUBNET_MAX_SIZE;
+
+    let mut heap = vec![0; 16];
+    api.ic0_cost_call(4, 1000, 0, &mut heap).unwrap();
+    assert_eq!(
+        cycles_account_manager.xnet_call_total_fee(NumBytes::from(1004), subnet_size),
+        Cycles::from(&heap)
+    );
+
+    let mut heap = vec![0; 16];
+    api.ic0_cost_create_canister(0, &mut heap).unwrap();
+    assert_eq!(
+        cycles_account_manager.canister_creation_fee(subnet_size),
+        Cycles::from(&heap)
+    );
+
+    let mut heap = vec![0; 16];
+    api.ic0_cost_http_request(100, 2000, 0, &mut heap).unwrap();
+    assert_eq!(
+        cycles_account_manager.http_request_fee(
+            NumBytes::from(100),
+            Some(NumBytes::from(2000)),
+            subnet_size
+        ),
+        Cycles::from(&heap)
+    );
+
+    let mut heap = vec![0; 16];
+    heap.extend_from_slice(key_id.name.as_bytes());
+    let name_len = key_id.name.len();
+    assert_eq!(
+        api.ic0_cost_sign_with_ecdsa(16, name_len, 0, 0, &mut heap),
+        Ok(0)
+    );
+    assert_eq!(
+        cycles_account_manager.ecdsa_signature_fee(subnet_size),
+        Cycles::from(&heap[..16].to_vec())
+    );
+    // Invalid curve.
+    assert_eq!(
+        api.ic0_cost_sign_with_ecdsa(16, name_len, 1, 0, &mut heap),
+        Ok(1)
+    );
+    // Unknown key.
+    assert_eq!(
+        api.ic0_cost_sign_with_ecdsa(16, name_len - 1, 0, 0, &mut heap),
+        Ok(2)
+    );
+}
+
+#[test]
+fn test_ic0_cost_sign_with_ecdsa_uses_signing_subnet_size() {
+    let system_state = SystemStateBuilder::default().build();
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+    let key_id = EcdsaKeyId {
+        curve: EcdsaCurve::Secp256k1,
+        name: "key_1".to_string(),
+    };
+    let own_subnet_size = 4;
+    let signing_subnet_size = 34;
+    let subnet_topology = |size: u64| SubnetTopology {
+        nodes: (0..size).map(node_test_id).collect(),
+        ..SubnetTopology::default()
+    };
+    let network_topology = NetworkTopology {
+        subnets: BTreeMap::from([
+            (subnet_test_id(0), subnet_topology(own_subnet_size)),
+            (subnet_test_id(1), subnet_topology(signing_subnet_size)),
+        ]),
+        ecdsa_signing_subnets: BTreeMap::from([(key_id.clone(), vec![subnet_test_id(1)])]),
+        ..NetworkTopology::default()
+    };
+    let api = get_system_api_with_network_topology(
+        ApiTypeBuilder::build_update_api(),
+        &system_state,
+        cycles_account_manager,
+        &network_topology,
+    );
+
+    let mut heap = vec![0; 16];
+    heap.extend_from_slice(key_id.name.as_bytes());
+    assert_eq!(
+        api.ic0_cost_sign_with_ecdsa(16, key_id.name.len(), 0, 0, &mut heap),
+        Ok(0)
+    );
+    let fee = Cycles::from(&heap[..16].to_vec());
+    assert_eq!(
+        cycles_account_manager.ecdsa_signature_fee(signing_subnet_size as usize),
+        fee
+    );
+    assert_ne!(
+        cycles_account_manager.ecdsa_signature_fee(own_subnet_size as usize),
+        fee
+    );
+}
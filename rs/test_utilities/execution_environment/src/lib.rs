@@ -21,13 +21,13 @@ use ic_execution_environment::{
 };
 use ic_ic00_types::{
     CanisterIdRecord, CanisterInstallMode, CanisterInstallModeV2, CanisterSettingsArgs,
-    CanisterSettingsArgsBuilder, CanisterStatusType, EcdsaKeyId, EmptyBlob, InstallCodeArgs,
-    InstallCodeArgsV2, Method, Payload, ProvisionalCreateCanisterWithCyclesArgs, SkipPreUpgrade,
-    UpdateSettingsArgs,
+    CanisterSettingsArgsBuilder, CanisterStatusType, CanisterUpgradeOptions, EcdsaKeyId, EmptyBlob,
+    EnvironmentVariable, InstallCodeArgs, InstallCodeArgsV2, Method, Payload,
+    ProvisionalCreateCanisterWithCyclesArgs, UpdateSettingsArgs,
 };
 use ic_interfaces::execution_environment::{
-    ExecutionMode, IngressHistoryWriter, QueryHandler, RegistryExecutionSettings,
-    SubnetAvailableMemory,
+    CrossSubnetQueryClient, ExecutionMode, IngressHistoryWriter, QueryHandler,
+    RegistryExecutionSettings, SubnetAvailableMemory,
 };
 use ic_interfaces_state_manager::Labeled;
 use ic_logger::{replica_logger::no_op_logger, ReplicaLogger};
@@ -52,8 +52,9 @@ use ic_types::{
     crypto::{canister_threshold_sig::MasterEcdsaPublicKey, AlgorithmId},
     ingress::{IngressState, IngressStatus, WasmResult},
     messages::{
-        AnonymousQuery, CallbackId, CanisterCall, CanisterMessage, CanisterTask, MessageId,
-        RequestOrResponse, Response, UserQuery, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES,
+        AnonymousQuery, CallbackId, CanisterCall, CanisterMessage, CanisterTask, CrossSubnetQuery,
+        CrossSubnetQueryResponse, MessageId, RequestOrResponse, Response, UserQuery,
+        MAX_INTER_CANISTER_PAYLOAD_IN_BYTES,
     },
     CanisterId, Cycles, Height, NumInstructions, NumPages, QueryStatsEpoch, Time, UserId,
 };
@@ -97,6 +98,7 @@ pub fn generate_subnets(
                 subnet_type,
                 subnet_features: SubnetFeatures::default(),
                 ecdsa_keys_held: BTreeSet::new(),
+                max_inter_canister_payload_in_bytes: None,
             },
         );
     }
@@ -195,7 +197,7 @@ pub struct ExecutionTest {
 
     // The actual implementation.
     exec_env: ExecutionEnvironment,
-    query_handler: InternalHttpQueryHandler,
+    query_handler: Arc<InternalHttpQueryHandler>,
     cycles_account_manager: Arc<CyclesAccountManager>,
     metrics_registry: MetricsRegistry,
     ingress_history_writer: Arc<dyn IngressHistoryWriter<State = ReplicatedState>>,
@@ -263,6 +265,12 @@ impl ExecutionTest {
                 )
             }
             RequestOrResponse::Response(response) => response,
+            RequestOrResponse::Fragment(fragment) => {
+                panic!(
+                    "Expected the xnet message to be a Response, but got a Fragment: {:?}",
+                    fragment
+                )
+            }
         }
     }
 
@@ -536,7 +544,17 @@ impl ExecutionTest {
     ) -> Result<WasmResult, UserError> {
         let payload = UpdateSettingsArgs {
             canister_id: canister_id.into(),
-            settings: CanisterSettingsArgs::new(Some(controllers), None, None, None, None),
+            settings: CanisterSettingsArgs::new(
+                Some(controllers),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            ),
             sender_canister_version: None,
         }
         .encode();
@@ -560,6 +578,57 @@ impl ExecutionTest {
         self.subnet_message(Method::UpdateSettings, payload)
     }
 
+    /// Replaces the environment variables of the canister.
+    pub fn canister_update_environment_variables(
+        &mut self,
+        canister_id: CanisterId,
+        environment_variables: Vec<EnvironmentVariable>,
+    ) -> Result<WasmResult, UserError> {
+        let payload = UpdateSettingsArgs {
+            canister_id: canister_id.into(),
+            settings: CanisterSettingsArgsBuilder::new()
+                .with_environment_variables(environment_variables)
+                .build(),
+            sender_canister_version: None,
+        }
+        .encode();
+        self.subnet_message(Method::UpdateSettings, payload)
+    }
+
+    /// Updates the Wasm memory limit of the canister.
+    pub fn canister_update_wasm_memory_limit(
+        &mut self,
+        canister_id: CanisterId,
+        wasm_memory_limit: NumBytes,
+    ) -> Result<WasmResult, UserError> {
+        let payload = UpdateSettingsArgs {
+            canister_id: canister_id.into(),
+            settings: CanisterSettingsArgsBuilder::new()
+                .with_wasm_memory_limit(wasm_memory_limit.get())
+                .build(),
+            sender_canister_version: None,
+        }
+        .encode();
+        self.subnet_message(Method::UpdateSettings, payload)
+    }
+
+    /// Updates the Wasm memory threshold of the canister.
+    pub fn canister_update_wasm_memory_threshold(
+        &mut self,
+        canister_id: CanisterId,
+        wasm_memory_threshold: NumBytes,
+    ) -> Result<WasmResult, UserError> {
+        let payload = UpdateSettingsArgs {
+            canister_id: canister_id.into(),
+            settings: CanisterSettingsArgsBuilder::new()
+                .with_wasm_memory_threshold(wasm_memory_threshold.get())
+                .build(),
+            sender_canister_version: None,
+        }
+        .encode();
+        self.subnet_message(Method::UpdateSettings, payload)
+    }
+
     /// Sends an `install_code` message to the IC management canister.
     /// Consider using higher-level helpers like `canister_from_wat()`.
     pub fn install_code(&mut self, args: InstallCodeArgs) -> Result<WasmResult, UserError> {
@@ -769,15 +838,15 @@ impl ExecutionTest {
     }
 
     /// Upgrades the given canister with the given Wasm binary,
-    /// in the mode specified by value of 'skip_pre_upgrade' field.
+    /// using the given upgrade options.
     pub fn upgrade_canister_v2(
         &mut self,
         canister_id: CanisterId,
         wasm_binary: Vec<u8>,
-        skip_pre_upgrade: Option<SkipPreUpgrade>,
+        upgrade_options: Option<CanisterUpgradeOptions>,
     ) -> Result<(), UserError> {
         let args = InstallCodeArgsV2::new(
-            CanisterInstallModeV2::Upgrade(skip_pre_upgrade),
+            CanisterInstallModeV2::Upgrade(upgrade_options),
             canister_id,
             wasm_binary,
             vec![],
@@ -958,6 +1027,12 @@ impl ExecutionTest {
                     .task_queue
                     .push_front(ExecutionTask::GlobalTimer);
             }
+            CanisterTask::OnLowWasmMemory => {
+                canister
+                    .system_state
+                    .task_queue
+                    .push_front(ExecutionTask::OnLowWasmMemory);
+            }
         }
         let result = execute_canister(
             &self.exec_env,
@@ -1469,7 +1544,7 @@ impl ExecutionTest {
     /// downcast to the concrete type of the query handler and be able to
     /// access private fields in query handler related tests.
     pub fn query_handler(&self) -> &dyn std::any::Any {
-        &self.query_handler
+        self.query_handler.as_ref()
     }
 
     /// Returns a mutable reference to the query handler of this test.
@@ -1478,7 +1553,28 @@ impl ExecutionTest {
     /// downcast to the concrete type of the query handler and be able to
     /// access private fields in query handler related tests.
     pub fn query_handler_mut(&mut self) -> &mut dyn std::any::Any {
-        &mut self.query_handler
+        Arc::get_mut(&mut self.query_handler)
+            .expect("The query handler is shared with a cross-subnet query executor")
+    }
+
+    /// Returns a function that executes the query calls that composite
+    /// queries on other subnets make to canisters on this subnet, on a snapshot
+    /// of the current state, like the `XNetEndpoint` of this subnet would.
+    ///
+    /// Useful for simulating query call graphs that span multiple subnets.
+    pub fn cross_subnet_query_executor(
+        &self,
+    ) -> Box<dyn Fn(CrossSubnetQuery) -> CrossSubnetQueryResponse + Send + Sync> {
+        // A weak reference avoids a reference cycle if the executor ends up
+        // in the `CrossSubnetQueryClient` of this query handler.
+        let query_handler = Arc::downgrade(&self.query_handler);
+        let state = Arc::new(self.state().clone());
+        Box::new(move |query| {
+            query_handler
+                .upgrade()
+                .expect("The query handler was dropped")
+                .cross_subnet_query(query, Labeled::new(Height::from(0), Arc::clone(&state)))
+        })
     }
 
     pub fn checkpoint_canister_memories(&mut self) {
@@ -1533,7 +1629,9 @@ impl ExecutionTest {
     }
 
     pub fn query_stats_set_epoch_for_testing(&mut self, epoch: QueryStatsEpoch) {
-        self.query_handler.query_stats_set_epoch_for_testing(epoch);
+        Arc::get_mut(&mut self.query_handler)
+            .expect("The query handler is shared with a cross-subnet query executor")
+            .query_stats_set_epoch_for_testing(epoch);
     }
 }
 
@@ -1562,6 +1660,7 @@ pub struct ExecutionTestBuilder {
     resource_saturation_scaling: usize,
     heap_delta_rate_limit: NumBytes,
     upload_wasm_chunk_instructions: NumInstructions,
+    cross_subnet_query_client: Option<Arc<dyn CrossSubnetQueryClient>>,
 }
 
 impl Default for ExecutionTestBuilder {
@@ -1603,6 +1702,7 @@ impl Default for ExecutionTestBuilder {
             resource_saturation_scaling: 1,
             heap_delta_rate_limit: scheduler_config.heap_delta_rate_limit,
             upload_wasm_chunk_instructions: scheduler_config.upload_wasm_chunk_instructions,
+            cross_subnet_query_client: None,
         }
     }
 }
@@ -1779,6 +1879,11 @@ impl ExecutionTestBuilder {
         self
     }
 
+    pub fn with_query_deterministic_time_slicing(mut self) -> Self {
+        self.execution_config.query_deterministic_time_slicing = FlagStatus::Enabled;
+        self
+    }
+
     pub fn with_canister_sandboxing_disabled(mut self) -> Self {
         self.execution_config.canister_sandboxing_flag = FlagStatus::Disabled;
         self
@@ -1799,6 +1904,17 @@ impl ExecutionTestBuilder {
         self
     }
 
+    /// Enables cross-subnet composite queries, forwarding query calls to
+    /// canisters on other subnets to the given client.
+    pub fn with_cross_subnet_query_client(
+        mut self,
+        client: Arc<dyn CrossSubnetQueryClient>,
+    ) -> Self {
+        self.execution_config.cross_subnet_composite_queries = FlagStatus::Enabled;
+        self.cross_subnet_query_client = Some(client);
+        self
+    }
+
     pub fn with_query_stats(mut self) -> Self {
         self.execution_config.query_stats_aggregation = FlagStatus::Enabled;
         self
@@ -1879,6 +1995,40 @@ impl ExecutionTestBuilder {
         self
     }
 
+    pub fn with_snapshots(mut self, status: FlagStatus) -> Self {
+        self.execution_config.canister_snapshots = status;
+        self
+    }
+
+    pub fn with_canister_logging(mut self, status: FlagStatus) -> Self {
+        self.execution_config
+            .embedders_config
+            .feature_flags
+            .canister_logging = status;
+        self
+    }
+
+    pub fn with_canister_backtrace(mut self, status: FlagStatus) -> Self {
+        self.execution_config
+            .embedders_config
+            .feature_flags
+            .canister_backtrace = status;
+        self
+    }
+
+    pub fn with_wasm_profiler(mut self) -> Self {
+        self.execution_config
+            .embedders_config
+            .feature_flags
+            .wasm_profiler = FlagStatus::Enabled;
+        self
+    }
+
+    pub fn with_wasm64(mut self) -> Self {
+        self.execution_config.embedders_config.feature_flags.wasm64 = FlagStatus::Enabled;
+        self
+    }
+
     pub fn with_non_native_stable(mut self) -> Self {
         self.execution_config
             .embedders_config
@@ -2041,7 +2191,7 @@ impl ExecutionTestBuilder {
         );
         let (query_stats_collector, _) = init_query_stats(self.log.clone());
 
-        let query_handler = InternalHttpQueryHandler::new(
+        let query_handler = Arc::new(InternalHttpQueryHandler::new(
             self.log.clone(),
             hypervisor,
             self.subnet_type,
@@ -2050,7 +2200,8 @@ impl ExecutionTestBuilder {
             self.instruction_limit_without_dts,
             Arc::clone(&cycles_account_manager),
             query_stats_collector,
-        );
+            self.cross_subnet_query_client,
+        ));
         ExecutionTest {
             state: Some(state),
             message_id: 0,
@@ -31,7 +31,10 @@ use ic_replicated_state::{
 };
 use ic_types::methods::{Callback, WasmClosure};
 use ic_types::time::UNIX_EPOCH;
-use ic_types::{batch::RawQueryStats, messages::CallbackId};
+use ic_types::{
+    batch::RawQueryStats,
+    messages::{CallbackId, NO_DEADLINE},
+};
 use ic_types::{
     messages::{Ingress, Request, RequestOrResponse},
     nominal_cycles::NominalCycles,
@@ -515,6 +518,8 @@ impl Default for ExecutionStateBuilder {
                 metadata: wasm_metadata,
                 last_executed_round: ExecutionRound::from(0),
                 next_scheduled_method: NextScheduledMethod::default(),
+                is_wasm64: false,
+                wasm_memory_max_pages: None,
             },
         }
     }
@@ -738,7 +743,7 @@ pub fn register_callback(
         .call_context_manager_mut()
         .unwrap();
     let call_context_id = call_context_manager.new_call_context(
-        CallOrigin::CanisterUpdate(originator, callback_id),
+        CallOrigin::CanisterUpdate(originator, callback_id, NO_DEADLINE),
         Cycles::zero(),
         Time::from_nanos_since_unix_epoch(0),
     );
@@ -753,6 +758,7 @@ pub fn register_callback(
         WasmClosure::new(0, 2),
         WasmClosure::new(0, 2),
         None,
+        NO_DEADLINE,
     ));
 }
 
@@ -17,7 +17,7 @@ use ic_types::{
     consensus::certification::Certification,
     crypto::threshold_sig::ni_dkg::{NiDkgId, NiDkgTag, NiDkgTargetSubnet},
     crypto::{CryptoHash, CryptoHashOf},
-    messages::{Request, RequestOrResponse, Response},
+    messages::{MessageFragment, Request, RequestOrResponse, Response},
     xnet::{CertifiedStreamSlice, StreamHeader, StreamIndex, StreamIndexedQueue, StreamSlice},
     CryptoHashOfPartialState, CryptoHashOfState, Height, RegistryVersion, SubnetId,
 };
@@ -323,6 +323,12 @@ impl StateReader for FakeStateManager {
 enum SerializableRequestOrResponse {
     Request(Request),
     Response(Response),
+    Fragment {
+        header: Box<SerializableRequestOrResponse>,
+        index: u32,
+        count: u32,
+        payload: Vec<u8>,
+    },
 }
 
 impl From<&RequestOrResponse> for SerializableRequestOrResponse {
@@ -334,6 +340,12 @@ impl From<&RequestOrResponse> for SerializableRequestOrResponse {
             RequestOrResponse::Response(rep) => {
                 SerializableRequestOrResponse::Response((**rep).clone())
             }
+            RequestOrResponse::Fragment(fragment) => SerializableRequestOrResponse::Fragment {
+                header: Box::new((&fragment.header).into()),
+                index: fragment.index,
+                count: fragment.count,
+                payload: fragment.payload.clone(),
+            },
         }
     }
 }
@@ -347,6 +359,17 @@ impl From<SerializableRequestOrResponse> for RequestOrResponse {
             SerializableRequestOrResponse::Response(rep) => {
                 RequestOrResponse::Response(Arc::new(rep))
             }
+            SerializableRequestOrResponse::Fragment {
+                header,
+                index,
+                count,
+                payload,
+            } => RequestOrResponse::Fragment(Arc::new(MessageFragment {
+                header: (*header).into(),
+                index,
+                count,
+                payload,
+            })),
         }
     }
 }
@@ -143,6 +143,7 @@ pub(crate) fn get_updatesubnetpayload_with_keys(
         subnet_type: None,
         is_halted: None,
         halt_at_cup_height: None,
+        max_inter_canister_payload_in_bytes: None,
         max_instructions_per_message: None,
         max_instructions_per_round: None,
         max_instructions_per_install_code: None,
@@ -19,7 +19,9 @@ use ic_protobuf::registry::subnet::v1::{InitialIDkgDealings, InitialNiDkgTranscr
 use ic_protobuf::state::canister_state_bits::v1::{self as pb_canister_state_bits};
 use ic_protobuf::types::v1::CanisterInstallModeV2 as CanisterInstallModeV2Proto;
 use ic_protobuf::types::v1::{
-    CanisterInstallMode as CanisterInstallModeProto, CanisterUpgradeOptions,
+    CanisterInstallMode as CanisterInstallModeProto,
+    CanisterUpgradeOptions as CanisterUpgradeOptionsProto,
+    WasmMemoryPersistence as WasmMemoryPersistenceProto,
 };
 use ic_protobuf::{proxy::ProxyDecodeError, registry::crypto::v1 as pb_registry_crypto};
 use num_traits::cast::ToPrimitive;
@@ -87,6 +89,14 @@ pub enum Method {
     StoredChunks,
     DeleteChunks,
     ClearChunkStore,
+
+    // Support for canister snapshots.
+    TakeCanisterSnapshot,
+    LoadCanisterSnapshot,
+    ListCanisterSnapshots,
+    DeleteCanisterSnapshot,
+
+    FetchCanisterLogs,
 }
 
 fn candid_error_to_user_error(err: candid::Error) -> UserError {
@@ -252,6 +262,53 @@ impl CanisterControllersChangeRecord {
     }
 }
 
+/// `CandidType` for `CanisterEnvironmentVariablesChangeRecord`
+/// ```text
+/// record {
+///   environment_variables_hash : blob;
+/// }
+/// ```
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CanisterEnvironmentVariablesChangeRecord {
+    environment_variables_hash: [u8; 32],
+}
+
+impl CanisterEnvironmentVariablesChangeRecord {
+    pub fn environment_variables_hash(&self) -> [u8; 32] {
+        self.environment_variables_hash
+    }
+}
+
+/// `CandidType` for `CanisterLoadSnapshotRecord`
+/// ```text
+/// record {
+///   canister_version : nat64;
+///   snapshot_id : blob;
+///   taken_at_timestamp : nat64;
+/// }
+/// ```
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CanisterLoadSnapshotRecord {
+    canister_version: u64,
+    #[serde(with = "serde_bytes")]
+    snapshot_id: Vec<u8>,
+    taken_at_timestamp: u64,
+}
+
+impl CanisterLoadSnapshotRecord {
+    pub fn canister_version(&self) -> u64 {
+        self.canister_version
+    }
+
+    pub fn snapshot_id(&self) -> &[u8] {
+        &self.snapshot_id
+    }
+
+    pub fn taken_at_timestamp(&self) -> u64 {
+        self.taken_at_timestamp
+    }
+}
+
 /// `CandidType` for `CanisterChangeDetails`
 /// ```text
 /// variant {
@@ -266,6 +323,14 @@ impl CanisterControllersChangeRecord {
 ///   controllers_change : record {
 ///     controllers : vec principal;
 ///   };
+///   environment_variables_change : record {
+///     environment_variables_hash : blob;
+///   };
+///   load_snapshot : record {
+///     canister_version : nat64;
+///     snapshot_id : blob;
+///     taken_at_timestamp : nat64;
+///   };
 /// }
 /// ```
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -278,6 +343,10 @@ pub enum CanisterChangeDetails {
     CanisterCodeDeployment(CanisterCodeDeploymentRecord),
     #[serde(rename = "controllers_change")]
     CanisterControllersChange(CanisterControllersChangeRecord),
+    #[serde(rename = "environment_variables_change")]
+    CanisterEnvironmentVariablesChange(CanisterEnvironmentVariablesChangeRecord),
+    #[serde(rename = "load_snapshot")]
+    CanisterLoadSnapshot(CanisterLoadSnapshotRecord),
 }
 
 impl CanisterChangeDetails {
@@ -300,9 +369,32 @@ impl CanisterChangeDetails {
             controllers,
         })
     }
+
+    pub fn environment_variables_change(
+        environment_variables_hash: [u8; 32],
+    ) -> CanisterChangeDetails {
+        CanisterChangeDetails::CanisterEnvironmentVariablesChange(
+            CanisterEnvironmentVariablesChangeRecord {
+                environment_variables_hash,
+            },
+        )
+    }
+
+    pub fn load_snapshot(
+        canister_version: u64,
+        snapshot_id: Vec<u8>,
+        taken_at_timestamp: u64,
+    ) -> CanisterChangeDetails {
+        CanisterChangeDetails::CanisterLoadSnapshot(CanisterLoadSnapshotRecord {
+            canister_version,
+            snapshot_id,
+            taken_at_timestamp,
+        })
+    }
 }
 
-/// Every canister change (canister creation, code uninstallation, code deployment, or controllers change) consists of
+/// Every canister change (canister creation, code uninstallation, code deployment, controllers change,
+/// environment variables change, or snapshot load) consists of
 ///
 /// 1. the system timestamp (in nanoseconds since Unix Epoch) at which the change was performed,
 /// 2. the canister version after performing the change,
@@ -318,6 +410,11 @@ impl CanisterChangeDetails {
 ///
 /// Controllers changes are described by the full new set of the canister controllers after the change.
 ///
+/// Environment variables changes are described by the SHA-256 hash of the new environment variables.
+///
+/// Snapshot loads are described by the ID of the loaded snapshot and the canister version and
+/// timestamp at which that snapshot was taken.
+///
 /// `CandidType` for `CanisterChange`
 /// ```text
 /// record {
@@ -350,22 +447,30 @@ impl CanisterChange {
         }
     }
 
+    pub fn details(&self) -> &CanisterChangeDetails {
+        &self.details
+    }
+
     /// Returns the number of bytes to represent a canister change in memory.
     /// The vector of controllers in `CanisterCreation` and `CanisterControllersChange`
-    /// is counted separately because the controllers are stored on heap
-    /// and thus not accounted for in `size_of::<CanisterChange>()`.
+    /// and the snapshot ID in `CanisterLoadSnapshot` are counted separately because
+    /// they are stored on heap and thus not accounted for in `size_of::<CanisterChange>()`.
     pub fn count_bytes(&self) -> NumBytes {
-        let controllers_memory_size = match &self.details {
+        let heap_memory_size = match &self.details {
             CanisterChangeDetails::CanisterCreation(canister_creation) => {
                 std::mem::size_of_val(canister_creation.controllers())
             }
             CanisterChangeDetails::CanisterControllersChange(canister_controllers_change) => {
                 std::mem::size_of_val(canister_controllers_change.controllers())
             }
+            CanisterChangeDetails::CanisterLoadSnapshot(canister_load_snapshot) => {
+                std::mem::size_of_val(canister_load_snapshot.snapshot_id())
+            }
             CanisterChangeDetails::CanisterCodeDeployment(_)
-            | CanisterChangeDetails::CanisterCodeUninstall => 0,
+            | CanisterChangeDetails::CanisterCodeUninstall
+            | CanisterChangeDetails::CanisterEnvironmentVariablesChange(_) => 0,
         };
-        NumBytes::from((size_of::<CanisterChange>() + controllers_memory_size) as u64)
+        NumBytes::from((size_of::<CanisterChange>() + heap_memory_size) as u64)
     }
 }
 
@@ -535,6 +640,24 @@ impl From<&CanisterChangeDetails> for pb_canister_state_bits::canister_change::C
                     },
                 )
             }
+            CanisterChangeDetails::CanisterEnvironmentVariablesChange(
+                canister_environment_variables_change,
+            ) => pb_canister_state_bits::canister_change::ChangeDetails::CanisterEnvironmentVariablesChange(
+                pb_canister_state_bits::CanisterEnvironmentVariablesChange {
+                    environment_variables_hash: canister_environment_variables_change
+                        .environment_variables_hash
+                        .to_vec(),
+                },
+            ),
+            CanisterChangeDetails::CanisterLoadSnapshot(canister_load_snapshot) => {
+                pb_canister_state_bits::canister_change::ChangeDetails::CanisterLoadSnapshot(
+                    pb_canister_state_bits::CanisterLoadSnapshot {
+                        canister_version: canister_load_snapshot.canister_version,
+                        snapshot_id: canister_load_snapshot.snapshot_id.clone(),
+                        taken_at_timestamp: canister_load_snapshot.taken_at_timestamp,
+                    },
+                )
+            }
         }
     }
 }
@@ -578,6 +701,18 @@ impl TryFrom<pb_canister_state_bits::canister_change::ChangeDetails> for Caniste
                     .map(TryInto::try_into)
                     .collect::<Result<Vec<PrincipalId>, _>>()?,
             )),
+            pb_canister_state_bits::canister_change::ChangeDetails::CanisterEnvironmentVariablesChange(
+                canister_environment_variables_change,
+            ) => Ok(CanisterChangeDetails::environment_variables_change(
+                try_decode_hash(canister_environment_variables_change.environment_variables_hash)?,
+            )),
+            pb_canister_state_bits::canister_change::ChangeDetails::CanisterLoadSnapshot(
+                canister_load_snapshot,
+            ) => Ok(CanisterChangeDetails::load_snapshot(
+                canister_load_snapshot.canister_version,
+                canister_load_snapshot.snapshot_id,
+                canister_load_snapshot.taken_at_timestamp,
+            )),
         }
     }
 }
@@ -645,6 +780,10 @@ impl Payload<'_> for UninstallCodeArgs {}
 ///     memory_allocation: nat;
 ///     freezing_threshold: nat;
 ///     reserved_cycles_limit: nat;
+///     log_visibility: log_visibility;
+///     wasm_memory_limit: opt nat;
+///     wasm_memory_threshold: opt nat;
+///     environment_variables: vec environment_variable;
 /// })`
 #[derive(CandidType, Clone, Deserialize, Debug, Eq, PartialEq)]
 pub struct DefiniteCanisterSettingsArgs {
@@ -654,9 +793,14 @@ pub struct DefiniteCanisterSettingsArgs {
     memory_allocation: candid::Nat,
     freezing_threshold: candid::Nat,
     reserved_cycles_limit: candid::Nat,
+    log_visibility: LogVisibility,
+    wasm_memory_limit: Option<candid::Nat>,
+    wasm_memory_threshold: Option<candid::Nat>,
+    environment_variables: Vec<EnvironmentVariable>,
 }
 
 impl DefiniteCanisterSettingsArgs {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         controller: PrincipalId,
         controllers: Vec<PrincipalId>,
@@ -664,6 +808,10 @@ impl DefiniteCanisterSettingsArgs {
         memory_allocation: Option<u64>,
         freezing_threshold: u64,
         reserved_cycles_limit: Option<u128>,
+        log_visibility: LogVisibility,
+        wasm_memory_limit: Option<u64>,
+        wasm_memory_threshold: Option<u64>,
+        environment_variables: Vec<EnvironmentVariable>,
     ) -> Self {
         let memory_allocation = candid::Nat::from(memory_allocation.unwrap_or(0));
         let reserved_cycles_limit = candid::Nat::from(reserved_cycles_limit.unwrap_or(0));
@@ -674,6 +822,10 @@ impl DefiniteCanisterSettingsArgs {
             memory_allocation,
             freezing_threshold: candid::Nat::from(freezing_threshold),
             reserved_cycles_limit,
+            log_visibility,
+            wasm_memory_limit: wasm_memory_limit.map(candid::Nat::from),
+            wasm_memory_threshold: wasm_memory_threshold.map(candid::Nat::from),
+            environment_variables,
         }
     }
 
@@ -684,6 +836,22 @@ impl DefiniteCanisterSettingsArgs {
     pub fn reserved_cycles_limit(&self) -> candid::Nat {
         self.reserved_cycles_limit.clone()
     }
+
+    pub fn log_visibility(&self) -> LogVisibility {
+        self.log_visibility
+    }
+
+    pub fn wasm_memory_limit(&self) -> Option<candid::Nat> {
+        self.wasm_memory_limit.clone()
+    }
+
+    pub fn wasm_memory_threshold(&self) -> Option<candid::Nat> {
+        self.wasm_memory_threshold.clone()
+    }
+
+    pub fn environment_variables(&self) -> &[EnvironmentVariable] {
+        &self.environment_variables
+    }
 }
 
 impl Payload<'_> for DefiniteCanisterSettingsArgs {}
@@ -752,6 +920,86 @@ pub struct QueryStats {
     response_payload_bytes_total: candid::Nat,
 }
 
+/// The kind of a canister method in `CanisterMethodStats`.
+#[derive(CandidType, Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+pub enum CanisterMethodKind {
+    #[serde(rename = "update")]
+    Update,
+    #[serde(rename = "query")]
+    Query,
+}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     method_name: text;
+///     kind: variant { update; query };
+///     num_calls_total: nat;
+///     num_errors_total: nat;
+///     num_instructions_total: nat;
+///     request_payload_bytes_total: nat;
+///     response_payload_bytes_total: nat;
+/// })`
+#[derive(CandidType, Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct CanisterMethodStats {
+    method_name: String,
+    kind: CanisterMethodKind,
+    num_calls_total: candid::Nat,
+    num_errors_total: candid::Nat,
+    num_instructions_total: candid::Nat,
+    request_payload_bytes_total: candid::Nat,
+    response_payload_bytes_total: candid::Nat,
+}
+
+impl CanisterMethodStats {
+    pub fn new(
+        method_name: String,
+        kind: CanisterMethodKind,
+        num_calls: u128,
+        num_errors: u128,
+        num_instructions: u128,
+        request_payload_bytes: u128,
+        response_payload_bytes: u128,
+    ) -> Self {
+        Self {
+            method_name,
+            kind,
+            num_calls_total: candid::Nat::from(num_calls),
+            num_errors_total: candid::Nat::from(num_errors),
+            num_instructions_total: candid::Nat::from(num_instructions),
+            request_payload_bytes_total: candid::Nat::from(request_payload_bytes),
+            response_payload_bytes_total: candid::Nat::from(response_payload_bytes),
+        }
+    }
+
+    pub fn method_name(&self) -> &str {
+        &self.method_name
+    }
+
+    pub fn kind(&self) -> CanisterMethodKind {
+        self.kind
+    }
+
+    pub fn num_calls(&self) -> u128 {
+        self.num_calls_total.0.to_u128().unwrap()
+    }
+
+    pub fn num_errors(&self) -> u128 {
+        self.num_errors_total.0.to_u128().unwrap()
+    }
+
+    pub fn num_instructions(&self) -> u128 {
+        self.num_instructions_total.0.to_u128().unwrap()
+    }
+
+    pub fn request_payload_bytes(&self) -> u128 {
+        self.request_payload_bytes_total.0.to_u128().unwrap()
+    }
+
+    pub fn response_payload_bytes(&self) -> u128 {
+        self.response_payload_bytes_total.0.to_u128().unwrap()
+    }
+}
+
 /// Struct used for encoding/decoding
 /// `(record {
 ///     status : variant { running; stopping; stopped };
@@ -768,7 +1016,8 @@ pub struct QueryStats {
 ///         num_instructions: nat;
 ///         ingress_payload_size: nat;
 ///         egress_payload_size: nat;
-///     }
+///     };
+///     method_stats: vec canister_method_stats;
 /// })`
 #[derive(CandidType, Debug, Deserialize, Eq, PartialEq)]
 pub struct CanisterStatusResultV2 {
@@ -784,6 +1033,7 @@ pub struct CanisterStatusResultV2 {
     idle_cycles_burned_per_day: candid::Nat,
     reserved_cycles: candid::Nat,
     query_stats: QueryStats,
+    method_stats: Vec<CanisterMethodStats>,
 }
 
 impl CanisterStatusResultV2 {
@@ -799,12 +1049,17 @@ impl CanisterStatusResultV2 {
         memory_allocation: Option<u64>,
         freezing_threshold: u64,
         reserved_cycles_limit: Option<u128>,
+        log_visibility: LogVisibility,
+        wasm_memory_limit: Option<u64>,
+        wasm_memory_threshold: Option<u64>,
+        environment_variables: Vec<EnvironmentVariable>,
         idle_cycles_burned_per_day: u128,
         reserved_cycles: u128,
         query_num_calls: u128,
         query_num_instructions: u128,
         query_ingress_payload_size: u128,
         query_egress_payload_size: u128,
+        method_stats: Vec<CanisterMethodStats>,
     ) -> Self {
         Self {
             status,
@@ -822,6 +1077,10 @@ impl CanisterStatusResultV2 {
                 memory_allocation,
                 freezing_threshold,
                 reserved_cycles_limit,
+                log_visibility,
+                wasm_memory_limit,
+                wasm_memory_threshold,
+                environment_variables,
             ),
             freezing_threshold: candid::Nat::from(freezing_threshold),
             idle_cycles_burned_per_day: candid::Nat::from(idle_cycles_burned_per_day),
@@ -832,6 +1091,7 @@ impl CanisterStatusResultV2 {
                 request_payload_bytes_total: candid::Nat::from(query_ingress_payload_size),
                 response_payload_bytes_total: candid::Nat::from(query_egress_payload_size),
             },
+            method_stats,
         }
     }
 
@@ -879,6 +1139,28 @@ impl CanisterStatusResultV2 {
         self.reserved_cycles.0.to_u128().unwrap()
     }
 
+    pub fn wasm_memory_limit(&self) -> Option<u64> {
+        self.settings
+            .wasm_memory_limit
+            .as_ref()
+            .map(|limit| limit.0.to_u64().unwrap())
+    }
+
+    pub fn wasm_memory_threshold(&self) -> Option<u64> {
+        self.settings
+            .wasm_memory_threshold
+            .as_ref()
+            .map(|threshold| threshold.0.to_u64().unwrap())
+    }
+
+    pub fn environment_variables(&self) -> &[EnvironmentVariable] {
+        self.settings.environment_variables()
+    }
+
+    pub fn method_stats(&self) -> &[CanisterMethodStats] {
+        &self.method_stats
+    }
+
     pub fn settings(&self) -> DefiniteCanisterSettingsArgs {
         self.settings.clone()
     }
@@ -940,13 +1222,88 @@ impl CanisterInstallMode {
     }
 }
 
+/// Specifies what happens to the Wasm memory (heap) of a canister on upgrade.
+#[derive(
+    Clone, Debug, Deserialize, PartialEq, Serialize, Eq, EnumString, Hash, CandidType, Copy, Default,
+)]
+pub enum WasmMemoryPersistence {
+    /// Keep the Wasm memory of the old module, so that it is available to the
+    /// new module. Requires the new module to declare a compatible memory.
+    #[serde(rename = "keep")]
+    #[strum(serialize = "keep")]
+    Keep,
+    /// Replace the Wasm memory with the initial memory of the new module.
+    #[serde(rename = "replace")]
+    #[strum(serialize = "replace")]
+    #[default]
+    Replace,
+}
+
+impl From<&WasmMemoryPersistence> for WasmMemoryPersistenceProto {
+    fn from(item: &WasmMemoryPersistence) -> Self {
+        match item {
+            WasmMemoryPersistence::Keep => WasmMemoryPersistenceProto::Keep,
+            WasmMemoryPersistence::Replace => WasmMemoryPersistenceProto::Replace,
+        }
+    }
+}
+
+impl From<&WasmMemoryPersistence> for i32 {
+    fn from(item: &WasmMemoryPersistence) -> Self {
+        let proto: WasmMemoryPersistenceProto = item.into();
+        proto.into()
+    }
+}
+
+impl TryFrom<i32> for WasmMemoryPersistence {
+    type Error = CanisterInstallModeError;
+
+    fn try_from(item: i32) -> Result<Self, Self::Error> {
+        match WasmMemoryPersistenceProto::try_from(item).ok() {
+            Some(WasmMemoryPersistenceProto::Keep) => Ok(WasmMemoryPersistence::Keep),
+            Some(WasmMemoryPersistenceProto::Replace) => Ok(WasmMemoryPersistence::Replace),
+            Some(WasmMemoryPersistenceProto::Unspecified) | None => {
+                Err(CanisterInstallModeError(item.to_string()))
+            }
+        }
+    }
+}
+
+/// The options of a canister upgrade.
+///
+/// ```text
+/// record {
+///     skip_pre_upgrade: opt bool;
+///     wasm_memory_persistence : opt variant {
+///         keep;
+///         replace;
+///     };
+/// }
+/// ```
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize, Eq, Hash, CandidType, Copy, Default)]
-pub struct SkipPreUpgrade(pub Option<bool>);
+pub struct CanisterUpgradeOptions {
+    /// Determines whether the pre-upgrade hook is executed before the upgrade.
+    pub skip_pre_upgrade: Option<bool>,
+    /// Determines whether the Wasm memory is kept or replaced on upgrade.
+    pub wasm_memory_persistence: Option<WasmMemoryPersistence>,
+}
+
+impl CanisterUpgradeOptions {
+    /// Returns true if the `canister_pre_upgrade` hook should not be executed.
+    pub fn skips_pre_upgrade(&self) -> bool {
+        self.skip_pre_upgrade == Some(true)
+    }
+
+    /// Returns true if the Wasm memory of the old module should be kept.
+    pub fn keeps_wasm_memory(&self) -> bool {
+        self.wasm_memory_persistence == Some(WasmMemoryPersistence::Keep)
+    }
+}
 
 /// The mode with which a canister is installed.
 ///
 /// This second version of the mode allows someone to specify the
-/// optional `SkipPreUpgrade` parameter in case of an upgrade
+/// optional `CanisterUpgradeOptions` parameter in case of an upgrade
 #[derive(
     Clone, Debug, Deserialize, PartialEq, Serialize, Eq, EnumString, Hash, CandidType, Copy, Default,
 )]
@@ -963,18 +1320,51 @@ pub enum CanisterInstallModeV2 {
     /// Upgrade an existing canister.
     #[serde(rename = "upgrade")]
     #[strum(serialize = "upgrade")]
-    Upgrade(Option<SkipPreUpgrade>),
+    Upgrade(Option<CanisterUpgradeOptions>),
 }
 
 impl CanisterInstallModeV2 {
     pub fn iter() -> Iter<'static, CanisterInstallModeV2> {
-        static MODES: [CanisterInstallModeV2; 6] = [
+        static MODES: [CanisterInstallModeV2; 12] = [
             CanisterInstallModeV2::Install,
             CanisterInstallModeV2::Reinstall,
             CanisterInstallModeV2::Upgrade(None),
-            CanisterInstallModeV2::Upgrade(Some(SkipPreUpgrade(None))),
-            CanisterInstallModeV2::Upgrade(Some(SkipPreUpgrade(Some(false)))),
-            CanisterInstallModeV2::Upgrade(Some(SkipPreUpgrade(Some(true)))),
+            CanisterInstallModeV2::Upgrade(Some(CanisterUpgradeOptions {
+                skip_pre_upgrade: None,
+                wasm_memory_persistence: None,
+            })),
+            CanisterInstallModeV2::Upgrade(Some(CanisterUpgradeOptions {
+                skip_pre_upgrade: None,
+                wasm_memory_persistence: Some(WasmMemoryPersistence::Keep),
+            })),
+            CanisterInstallModeV2::Upgrade(Some(CanisterUpgradeOptions {
+                skip_pre_upgrade: None,
+                wasm_memory_persistence: Some(WasmMemoryPersistence::Replace),
+            })),
+            CanisterInstallModeV2::Upgrade(Some(CanisterUpgradeOptions {
+                skip_pre_upgrade: Some(false),
+                wasm_memory_persistence: None,
+            })),
+            CanisterInstallModeV2::Upgrade(Some(CanisterUpgradeOptions {
+                skip_pre_upgrade: Some(false),
+                wasm_memory_persistence: Some(WasmMemoryPersistence::Keep),
+            })),
+            CanisterInstallModeV2::Upgrade(Some(CanisterUpgradeOptions {
+                skip_pre_upgrade: Some(false),
+                wasm_memory_persistence: Some(WasmMemoryPersistence::Replace),
+            })),
+            CanisterInstallModeV2::Upgrade(Some(CanisterUpgradeOptions {
+                skip_pre_upgrade: Some(true),
+                wasm_memory_persistence: None,
+            })),
+            CanisterInstallModeV2::Upgrade(Some(CanisterUpgradeOptions {
+                skip_pre_upgrade: Some(true),
+                wasm_memory_persistence: Some(WasmMemoryPersistence::Keep),
+            })),
+            CanisterInstallModeV2::Upgrade(Some(CanisterUpgradeOptions {
+                skip_pre_upgrade: Some(true),
+                wasm_memory_persistence: Some(WasmMemoryPersistence::Replace),
+            })),
         ];
         MODES.iter()
     }
@@ -1050,9 +1440,15 @@ impl TryFrom<CanisterInstallModeV2Proto> for CanisterInstallModeV2 {
 
             ic_protobuf::types::v1::canister_install_mode_v2::CanisterInstallModeV2::Mode2(
                 upgrade_mode,
-            ) => Ok(CanisterInstallModeV2::Upgrade(Some(SkipPreUpgrade(
-                upgrade_mode.skip_pre_upgrade,
-            )))),
+            ) => Ok(CanisterInstallModeV2::Upgrade(Some(
+                CanisterUpgradeOptions {
+                    skip_pre_upgrade: upgrade_mode.skip_pre_upgrade,
+                    wasm_memory_persistence: upgrade_mode
+                        .wasm_memory_persistence
+                        .map(WasmMemoryPersistence::try_from)
+                        .transpose()?,
+                },
+            ))),
         }
     }
 }
@@ -1096,10 +1492,14 @@ impl From<&CanisterInstallModeV2> for CanisterInstallModeV2Proto {
                         CanisterInstallModeProto::Upgrade.into(),
                     )
                 }
-                CanisterInstallModeV2::Upgrade(Some(skip_pre_upgrade)) => {
+                CanisterInstallModeV2::Upgrade(Some(upgrade_options)) => {
                     ic_protobuf::types::v1::canister_install_mode_v2::CanisterInstallModeV2::Mode2(
-                        CanisterUpgradeOptions {
-                            skip_pre_upgrade: skip_pre_upgrade.0,
+                        CanisterUpgradeOptionsProto {
+                            skip_pre_upgrade: upgrade_options.skip_pre_upgrade,
+                            wasm_memory_persistence: upgrade_options
+                                .wasm_memory_persistence
+                                .as_ref()
+                                .map(i32::from),
                         },
                     )
                 }
@@ -1428,6 +1828,10 @@ fn verify_max_bounded_controllers_length() {
 ///     memory_allocation: opt nat;
 ///     freezing_threshold: opt nat;
 ///     reserved_cycles_limit: opt nat;
+///     log_visibility: opt log_visibility;
+///     wasm_memory_limit: opt nat;
+///     wasm_memory_threshold: opt nat;
+///     environment_variables: opt vec environment_variable;
 /// })`
 #[derive(Default, Clone, CandidType, Deserialize, Debug, PartialEq, Eq)]
 pub struct CanisterSettingsArgs {
@@ -1438,17 +1842,26 @@ pub struct CanisterSettingsArgs {
     pub memory_allocation: Option<candid::Nat>,
     pub freezing_threshold: Option<candid::Nat>,
     pub reserved_cycles_limit: Option<candid::Nat>,
+    pub log_visibility: Option<LogVisibility>,
+    pub wasm_memory_limit: Option<candid::Nat>,
+    pub wasm_memory_threshold: Option<candid::Nat>,
+    pub environment_variables: Option<Vec<EnvironmentVariable>>,
 }
 
 impl Payload<'_> for CanisterSettingsArgs {}
 
 impl CanisterSettingsArgs {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         controllers: Option<Vec<PrincipalId>>,
         compute_allocation: Option<u64>,
         memory_allocation: Option<u64>,
         freezing_threshold: Option<u64>,
         reserved_cycles_limit: Option<u128>,
+        log_visibility: Option<LogVisibility>,
+        wasm_memory_limit: Option<u64>,
+        wasm_memory_threshold: Option<u64>,
+        environment_variables: Option<Vec<EnvironmentVariable>>,
     ) -> Self {
         Self {
             controller: None,
@@ -1457,6 +1870,10 @@ impl CanisterSettingsArgs {
             memory_allocation: memory_allocation.map(candid::Nat::from),
             freezing_threshold: freezing_threshold.map(candid::Nat::from),
             reserved_cycles_limit: reserved_cycles_limit.map(candid::Nat::from),
+            log_visibility,
+            wasm_memory_limit: wasm_memory_limit.map(candid::Nat::from),
+            wasm_memory_threshold: wasm_memory_threshold.map(candid::Nat::from),
+            environment_variables,
         }
     }
 
@@ -1473,6 +1890,10 @@ pub struct CanisterSettingsArgsBuilder {
     memory_allocation: Option<candid::Nat>,
     freezing_threshold: Option<candid::Nat>,
     reserved_cycles_limit: Option<candid::Nat>,
+    log_visibility: Option<LogVisibility>,
+    wasm_memory_limit: Option<candid::Nat>,
+    wasm_memory_threshold: Option<candid::Nat>,
+    environment_variables: Option<Vec<EnvironmentVariable>>,
 }
 
 #[allow(dead_code)]
@@ -1489,6 +1910,10 @@ impl CanisterSettingsArgsBuilder {
             memory_allocation: self.memory_allocation,
             freezing_threshold: self.freezing_threshold,
             reserved_cycles_limit: self.reserved_cycles_limit,
+            log_visibility: self.log_visibility,
+            wasm_memory_limit: self.wasm_memory_limit,
+            wasm_memory_threshold: self.wasm_memory_threshold,
+            environment_variables: self.environment_variables,
         }
     }
 
@@ -1556,6 +1981,41 @@ impl CanisterSettingsArgsBuilder {
             ..self
         }
     }
+
+    /// Sets who is allowed to read the canister log.
+    pub fn with_log_visibility(self, log_visibility: LogVisibility) -> Self {
+        Self {
+            log_visibility: Some(log_visibility),
+            ..self
+        }
+    }
+
+    /// Sets the Wasm memory limit in bytes.
+    pub fn with_wasm_memory_limit(self, wasm_memory_limit: u64) -> Self {
+        Self {
+            wasm_memory_limit: Some(candid::Nat::from(wasm_memory_limit)),
+            ..self
+        }
+    }
+
+    /// Sets the Wasm memory threshold in bytes.
+    pub fn with_wasm_memory_threshold(self, wasm_memory_threshold: u64) -> Self {
+        Self {
+            wasm_memory_threshold: Some(candid::Nat::from(wasm_memory_threshold)),
+            ..self
+        }
+    }
+
+    /// Sets the environment variables, replacing any existing ones.
+    pub fn with_environment_variables(
+        self,
+        environment_variables: Vec<EnvironmentVariable>,
+    ) -> Self {
+        Self {
+            environment_variables: Some(environment_variables),
+            ..self
+        }
+    }
 }
 
 /// Struct used for encoding/decoding
@@ -2366,3 +2826,331 @@ impl StoredChunksArgs {
 pub struct StoredChunksReply(pub Vec<serde_bytes::ByteBuf>);
 
 impl Payload<'_> for StoredChunksReply {}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     canister_id: principal;
+///     replace_snapshot: opt blob;
+/// })`
+#[derive(Clone, CandidType, Deserialize, Debug, PartialEq, Eq)]
+pub struct TakeCanisterSnapshotArgs {
+    pub canister_id: PrincipalId,
+    pub replace_snapshot: Option<serde_bytes::ByteBuf>,
+}
+
+impl Payload<'_> for TakeCanisterSnapshotArgs {}
+
+impl TakeCanisterSnapshotArgs {
+    pub fn new(canister_id: CanisterId, replace_snapshot: Option<Vec<u8>>) -> Self {
+        Self {
+            canister_id: canister_id.get(),
+            replace_snapshot: replace_snapshot.map(serde_bytes::ByteBuf::from),
+        }
+    }
+
+    pub fn get_canister_id(&self) -> CanisterId {
+        CanisterId::unchecked_from_principal(self.canister_id)
+    }
+
+    pub fn replace_snapshot(&self) -> Option<Vec<u8>> {
+        self.replace_snapshot.as_ref().map(|id| id.to_vec())
+    }
+}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     canister_id: principal;
+///     snapshot_id: blob;
+///     sender_canister_version: opt nat64;
+/// })`
+#[derive(Clone, CandidType, Deserialize, Debug, PartialEq, Eq)]
+pub struct LoadCanisterSnapshotArgs {
+    pub canister_id: PrincipalId,
+    #[serde(with = "serde_bytes")]
+    pub snapshot_id: Vec<u8>,
+    pub sender_canister_version: Option<u64>,
+}
+
+impl Payload<'_> for LoadCanisterSnapshotArgs {}
+
+impl LoadCanisterSnapshotArgs {
+    pub fn new(
+        canister_id: CanisterId,
+        snapshot_id: Vec<u8>,
+        sender_canister_version: Option<u64>,
+    ) -> Self {
+        Self {
+            canister_id: canister_id.get(),
+            snapshot_id,
+            sender_canister_version,
+        }
+    }
+
+    pub fn get_canister_id(&self) -> CanisterId {
+        CanisterId::unchecked_from_principal(self.canister_id)
+    }
+
+    pub fn snapshot_id(&self) -> &[u8] {
+        &self.snapshot_id
+    }
+
+    pub fn get_sender_canister_version(&self) -> Option<u64> {
+        self.sender_canister_version
+    }
+}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     canister_id: principal;
+/// })`
+#[derive(Clone, CandidType, Deserialize, Debug, PartialEq, Eq)]
+pub struct ListCanisterSnapshotArgs {
+    pub canister_id: PrincipalId,
+}
+
+impl Payload<'_> for ListCanisterSnapshotArgs {}
+
+impl ListCanisterSnapshotArgs {
+    pub fn new(canister_id: CanisterId) -> Self {
+        Self {
+            canister_id: canister_id.get(),
+        }
+    }
+
+    pub fn get_canister_id(&self) -> CanisterId {
+        CanisterId::unchecked_from_principal(self.canister_id)
+    }
+}
+
+/// Struct used for encoding/decoding
+/// `(record {
+///     canister_id: principal;
+///     snapshot_id: blob;
+/// })`
+#[derive(Clone, CandidType, Deserialize, Debug, PartialEq, Eq)]
+pub struct DeleteCanisterSnapshotArgs {
+    pub canister_id: PrincipalId,
+    #[serde(with = "serde_bytes")]
+    pub snapshot_id: Vec<u8>,
+}
+
+impl Payload<'_> for DeleteCanisterSnapshotArgs {}
+
+impl DeleteCanisterSnapshotArgs {
+    pub fn new(canister_id: CanisterId, snapshot_id: Vec<u8>) -> Self {
+        Self {
+            canister_id: canister_id.get(),
+            snapshot_id,
+        }
+    }
+
+    pub fn get_canister_id(&self) -> CanisterId {
+        CanisterId::unchecked_from_principal(self.canister_id)
+    }
+
+    pub fn snapshot_id(&self) -> &[u8] {
+        &self.snapshot_id
+    }
+}
+
+/// Struct to be returned when taking or listing canister snapshots.
+/// `(record {
+///      id: blob;
+///      taken_at_timestamp: nat64;
+///      total_size: nat64;
+/// })`
+#[derive(Clone, CandidType, Deserialize, Debug, PartialEq, Eq)]
+pub struct CanisterSnapshotResponse {
+    #[serde(with = "serde_bytes")]
+    pub id: Vec<u8>,
+    pub taken_at_timestamp: u64,
+    pub total_size: u64,
+}
+
+impl Payload<'_> for CanisterSnapshotResponse {}
+
+impl CanisterSnapshotResponse {
+    pub fn new(id: Vec<u8>, taken_at_timestamp: u64, total_size: NumBytes) -> Self {
+        Self {
+            id,
+            taken_at_timestamp,
+            total_size: total_size.get(),
+        }
+    }
+
+    pub fn snapshot_id(&self) -> &[u8] {
+        &self.id
+    }
+
+    pub fn total_size(&self) -> u64 {
+        self.total_size
+    }
+
+    pub fn taken_at_timestamp(&self) -> u64 {
+        self.taken_at_timestamp
+    }
+}
+
+/// Struct to be returned when listing canister snapshots.
+/// `(vec record {
+///      id: blob;
+///      taken_at_timestamp: nat64;
+///      total_size: nat64;
+/// })`
+#[derive(Clone, CandidType, Deserialize, Debug, PartialEq, Eq)]
+pub struct ListCanisterSnapshotsReply(pub Vec<CanisterSnapshotResponse>);
+
+impl Payload<'_> for ListCanisterSnapshotsReply {}
+
+/// Log visibility for a canister.
+/// ```text
+/// variant {
+///    controllers;
+///    public;
+/// }
+/// ```
+#[derive(Default, Clone, Copy, CandidType, Deserialize, Debug, PartialEq, Eq, Hash, Serialize)]
+pub enum LogVisibility {
+    /// Only the controllers of the canister can read its log.
+    #[default]
+    #[serde(rename = "controllers")]
+    Controllers,
+    /// Anyone can read the canister log.
+    #[serde(rename = "public")]
+    Public,
+}
+
+impl Payload<'_> for LogVisibility {}
+
+impl From<&LogVisibility> for pb_canister_state_bits::LogVisibility {
+    fn from(item: &LogVisibility) -> Self {
+        match item {
+            LogVisibility::Controllers => pb_canister_state_bits::LogVisibility::Controllers,
+            LogVisibility::Public => pb_canister_state_bits::LogVisibility::Public,
+        }
+    }
+}
+
+impl From<pb_canister_state_bits::LogVisibility> for LogVisibility {
+    fn from(item: pb_canister_state_bits::LogVisibility) -> Self {
+        match item {
+            pb_canister_state_bits::LogVisibility::Unspecified
+            | pb_canister_state_bits::LogVisibility::Controllers => Self::Controllers,
+            pb_canister_state_bits::LogVisibility::Public => Self::Public,
+        }
+    }
+}
+
+/// An environment variable of a canister.
+/// ```text
+/// record {
+///     name: text;
+///     value: text;
+/// }
+/// ```
+#[derive(Clone, CandidType, Deserialize, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct EnvironmentVariable {
+    pub name: String,
+    pub value: String,
+}
+
+impl EnvironmentVariable {
+    pub fn new(name: String, value: String) -> Self {
+        Self { name, value }
+    }
+}
+
+impl From<&EnvironmentVariable> for pb_canister_state_bits::EnvironmentVariable {
+    fn from(item: &EnvironmentVariable) -> Self {
+        Self {
+            name: item.name.clone(),
+            value: item.value.clone(),
+        }
+    }
+}
+
+impl From<pb_canister_state_bits::EnvironmentVariable> for EnvironmentVariable {
+    fn from(item: pb_canister_state_bits::EnvironmentVariable) -> Self {
+        Self {
+            name: item.name,
+            value: item.value,
+        }
+    }
+}
+
+/// A single record of a canister log.
+/// `(record {
+///     idx: nat64;
+///     timestamp_nanos: nat64;
+///     content: blob;
+/// })`
+#[derive(Clone, CandidType, Deserialize, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct CanisterLogRecord {
+    pub idx: u64,
+    pub timestamp_nanos: u64,
+    #[serde(with = "serde_bytes")]
+    pub content: Vec<u8>,
+}
+
+impl Payload<'_> for CanisterLogRecord {}
+
+impl CanisterLogRecord {
+    /// Returns the number of bytes taken by the record in the canister log.
+    pub fn data_size(&self) -> usize {
+        2 * size_of::<u64>() + self.content.len()
+    }
+}
+
+impl From<&CanisterLogRecord> for pb_canister_state_bits::CanisterLogRecord {
+    fn from(item: &CanisterLogRecord) -> Self {
+        Self {
+            idx: item.idx,
+            timestamp_nanos: item.timestamp_nanos,
+            content: item.content.clone(),
+        }
+    }
+}
+
+impl From<pb_canister_state_bits::CanisterLogRecord> for CanisterLogRecord {
+    fn from(item: pb_canister_state_bits::CanisterLogRecord) -> Self {
+        Self {
+            idx: item.idx,
+            timestamp_nanos: item.timestamp_nanos,
+            content: item.content,
+        }
+    }
+}
+
+/// Argument type of `fetch_canister_logs`.
+/// `(record {
+///     canister_id: principal;
+/// })`
+#[derive(Clone, CandidType, Deserialize, Debug, PartialEq, Eq)]
+pub struct FetchCanisterLogsRequest {
+    pub canister_id: PrincipalId,
+}
+
+impl Payload<'_> for FetchCanisterLogsRequest {}
+
+impl FetchCanisterLogsRequest {
+    pub fn new(canister_id: CanisterId) -> Self {
+        Self {
+            canister_id: canister_id.get(),
+        }
+    }
+
+    pub fn get_canister_id(&self) -> CanisterId {
+        CanisterId::unchecked_from_principal(self.canister_id)
+    }
+}
+
+/// Return type of `fetch_canister_logs`.
+/// `(record {
+///     canister_log_records: vec canister_log_record;
+/// })`
+#[derive(Clone, CandidType, Deserialize, Debug, PartialEq, Eq, Default)]
+pub struct FetchCanisterLogsResponse {
+    pub canister_log_records: Vec<CanisterLogRecord>,
+}
+
+impl Payload<'_> for FetchCanisterLogsResponse {}
@@ -10,8 +10,9 @@ mod xnet;
 pub use self::{
     canister_http::{CanisterHttpPayload, MAX_CANISTER_HTTP_PAYLOAD_SIZE},
     execution_environment::{
-        CanisterQueryStats, LocalQueryStats, QueryStats, QueryStatsPayload, RawQueryStats,
-        TotalQueryStats,
+        total_method_stats_into_protobuf, total_method_stats_try_from_protobuf, CanisterQueryStats,
+        LocalQueryStats, MethodStats, QueryStats, QueryStatsPayload, RawQueryStats,
+        TotalMethodStats, TotalQueryStats,
     },
     ingress::{IngressPayload, IngressPayloadError},
     self_validating::{SelfValidatingPayload, MAX_BITCOIN_PAYLOAD_IN_BYTES},
@@ -20,13 +20,19 @@
 //! by taking for each [`CanisterId`] the median of the statistics reported by each node.
 //! The aggregated statistics are then added to the [`TotalQueryStats`], from where they can
 //! be accessed by canisters.
+//!
+//! Alongside the per-canister totals, [`QueryStats`] carry a [`MethodStats`] breakdown by
+//! method name, which takes the same path and is aggregated the same way.
 
 use crate::{node_id_into_protobuf, node_id_try_from_option, QueryStatsEpoch};
 use ic_base_types::{CanisterId, NodeId, NumBytes};
 use ic_protobuf::{
     proxy::{try_from_option_field, ProxyDecodeError},
     state::{
-        canister_state_bits::v1::{TotalQueryStats as TotalQueryStatsProto, Unsigned128},
+        canister_state_bits::v1::{
+            TotalMethodStats as TotalMethodStatsProto, TotalQueryStats as TotalQueryStatsProto,
+            Unsigned128,
+        },
         stats::v1::{QueryStats as QueryStatsProto, QueryStatsInner},
     },
     types::v1::{self as pb},
@@ -40,7 +46,57 @@ pub struct QueryStats {
     pub num_instructions: u64, // Want u128, but not supported in protobuf
     pub ingress_payload_size: u64,
     pub egress_payload_size: u64,
+    /// Breakdown of the above by method name.
+    pub method_stats: BTreeMap<String, MethodStats>,
+}
+
+/// Statistics of the calls to a single method of a canister.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MethodStats {
+    pub num_calls: u32,
+    /// Number of calls that trapped or were rejected by the canister.
+    pub num_errors: u32,
+    pub num_instructions: u64,
+    pub ingress_payload_size: u64,
+    pub egress_payload_size: u64,
+}
+
+fn method_stats_into_protobuf(
+    method_stats: &BTreeMap<String, MethodStats>,
+) -> Vec<pb::MethodQueryStats> {
+    method_stats
+        .iter()
+        .map(|(method_name, stats)| pb::MethodQueryStats {
+            method_name: method_name.clone(),
+            num_calls: stats.num_calls,
+            num_errors: stats.num_errors,
+            num_instructions: stats.num_instructions,
+            ingress_payload_size: stats.ingress_payload_size,
+            egress_payload_size: stats.egress_payload_size,
+        })
+        .collect()
 }
+
+fn method_stats_from_protobuf(
+    method_stats: &[pb::MethodQueryStats],
+) -> BTreeMap<String, MethodStats> {
+    method_stats
+        .iter()
+        .map(|stats| {
+            (
+                stats.method_name.clone(),
+                MethodStats {
+                    num_calls: stats.num_calls,
+                    num_errors: stats.num_errors,
+                    num_instructions: stats.num_instructions,
+                    ingress_payload_size: stats.ingress_payload_size,
+                    egress_payload_size: stats.egress_payload_size,
+                },
+            )
+        })
+        .collect()
+}
+
 /// Total number of query stats collected since creation of the canister.
 ///
 /// This is a separate struct since values contained in here are accumulated
@@ -57,6 +113,38 @@ pub struct TotalQueryStats {
     pub num_instructions: u128,
     pub ingress_payload_size: u128,
     pub egress_payload_size: u128,
+    /// Breakdown of the above by method name.
+    pub method_stats: BTreeMap<String, TotalMethodStats>,
+}
+
+/// Total statistics of the calls to a single method of a canister.
+///
+/// Used both for the aggregated query stats and for the stats of update
+/// calls, which are accounted for directly during (replicated) execution.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TotalMethodStats {
+    pub num_calls: u128,
+    pub num_errors: u128,
+    pub num_instructions: u128,
+    pub ingress_payload_size: u128,
+    pub egress_payload_size: u128,
+}
+
+impl TotalMethodStats {
+    /// Accounts for a single call of the method.
+    pub fn record_call(
+        &mut self,
+        num_instructions: u64,
+        ingress_payload_size: u64,
+        egress_payload_size: u64,
+        is_error: bool,
+    ) {
+        self.num_calls += 1;
+        self.num_errors += is_error as u128;
+        self.num_instructions += num_instructions as u128;
+        self.ingress_payload_size += ingress_payload_size as u128;
+        self.egress_payload_size += egress_payload_size as u128;
+    }
 }
 
 fn get_u128_from_protobuf(proto: Option<Unsigned128>) -> Result<u128, ProxyDecodeError> {
@@ -87,6 +175,7 @@ impl TryFrom<TotalQueryStatsProto> for TotalQueryStats {
             num_instructions: get_u128_from_protobuf(value.num_instructions)?,
             ingress_payload_size: get_u128_from_protobuf(value.ingress_payload_size)?,
             egress_payload_size: get_u128_from_protobuf(value.egress_payload_size)?,
+            method_stats: total_method_stats_try_from_protobuf(value.method_stats)?,
         })
     }
 }
@@ -98,10 +187,50 @@ impl From<&TotalQueryStats> for TotalQueryStatsProto {
             num_instructions: Some(get_protobuf_for_u128(value.num_instructions)),
             ingress_payload_size: Some(get_protobuf_for_u128(value.ingress_payload_size)),
             egress_payload_size: Some(get_protobuf_for_u128(value.egress_payload_size)),
+            method_stats: total_method_stats_into_protobuf(&value.method_stats),
         }
     }
 }
 
+/// Converts per-method stats into their protobuf representation, ordered by
+/// method name.
+pub fn total_method_stats_into_protobuf(
+    method_stats: &BTreeMap<String, TotalMethodStats>,
+) -> Vec<TotalMethodStatsProto> {
+    method_stats
+        .iter()
+        .map(|(method_name, stats)| TotalMethodStatsProto {
+            method_name: method_name.clone(),
+            num_calls: Some(get_protobuf_for_u128(stats.num_calls)),
+            num_errors: Some(get_protobuf_for_u128(stats.num_errors)),
+            num_instructions: Some(get_protobuf_for_u128(stats.num_instructions)),
+            ingress_payload_size: Some(get_protobuf_for_u128(stats.ingress_payload_size)),
+            egress_payload_size: Some(get_protobuf_for_u128(stats.egress_payload_size)),
+        })
+        .collect()
+}
+
+/// Decodes per-method stats from their protobuf representation.
+pub fn total_method_stats_try_from_protobuf(
+    method_stats: Vec<TotalMethodStatsProto>,
+) -> Result<BTreeMap<String, TotalMethodStats>, ProxyDecodeError> {
+    method_stats
+        .into_iter()
+        .map(|stats| {
+            Ok((
+                stats.method_name,
+                TotalMethodStats {
+                    num_calls: get_u128_from_protobuf(stats.num_calls)?,
+                    num_errors: get_u128_from_protobuf(stats.num_errors)?,
+                    num_instructions: get_u128_from_protobuf(stats.num_instructions)?,
+                    ingress_payload_size: get_u128_from_protobuf(stats.ingress_payload_size)?,
+                    egress_payload_size: get_u128_from_protobuf(stats.egress_payload_size)?,
+                },
+            ))
+        })
+        .collect()
+}
+
 /// QueryStats with the epoch at which they where collected.
 ///
 /// [`LocalQueryStats`] are sent from execution to consensus for
@@ -138,6 +267,7 @@ impl RawQueryStats {
                     num_instructions: stats.num_instructions,
                     ingress_payload_size: stats.ingress_payload_size,
                     egress_payload_size: stats.egress_payload_size,
+                    method_stats: method_stats_into_protobuf(&stats.method_stats),
                 });
             }
         }
@@ -167,6 +297,7 @@ impl TryFrom<QueryStatsProto> for RawQueryStats {
                         num_instructions: entry.num_instructions,
                         ingress_payload_size: entry.ingress_payload_size,
                         egress_payload_size: entry.egress_payload_size,
+                        method_stats: method_stats_from_protobuf(&entry.method_stats),
                     },
                 );
             }
@@ -285,6 +416,7 @@ impl From<&CanisterQueryStats> for pb::CanisterQueryStats {
             num_instructions: entry.stats.num_instructions,
             ingress_payload_size: entry.stats.ingress_payload_size,
             egress_payload_size: entry.stats.egress_payload_size,
+            method_stats: method_stats_into_protobuf(&entry.stats.method_stats),
         }
     }
 }
@@ -303,6 +435,7 @@ impl TryFrom<&pb::CanisterQueryStats> for CanisterQueryStats {
                 num_instructions: entry.num_instructions,
                 ingress_payload_size: entry.ingress_payload_size,
                 egress_payload_size: entry.egress_payload_size,
+                method_stats: method_stats_from_protobuf(&entry.method_stats),
             },
         })
     }
@@ -373,6 +506,20 @@ mod tests {
             num_instructions: rng.gen(),
             ingress_payload_size: rng.gen(),
             egress_payload_size: rng.gen(),
+            method_stats: (0..rng.gen_range(0..3))
+                .map(|idx| {
+                    (
+                        format!("method_{}", idx),
+                        MethodStats {
+                            num_calls: rng.gen(),
+                            num_errors: rng.gen(),
+                            num_instructions: rng.gen(),
+                            ingress_payload_size: rng.gen(),
+                            egress_payload_size: rng.gen(),
+                        },
+                    )
+                })
+                .collect(),
         }
     }
 }
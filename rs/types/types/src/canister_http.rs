@@ -625,7 +625,7 @@ impl CountBytes for CanisterHttpResponseProof {
 
 #[cfg(test)]
 mod tests {
-    use crate::{time::UNIX_EPOCH, Cycles};
+    use crate::{messages::NO_DEADLINE, time::UNIX_EPOCH, Cycles};
 
     use super::*;
 
@@ -652,6 +652,7 @@ mod tests {
                 method_name: "tansform".to_string(),
                 method_payload: Vec::new(),
                 metadata: None,
+                deadline: NO_DEADLINE,
             },
             time: UNIX_EPOCH,
         };
@@ -693,6 +694,7 @@ mod tests {
                 method_name: "tansform".to_string(),
                 method_payload: Vec::new(),
                 metadata: None,
+                deadline: NO_DEADLINE,
             },
             time: UNIX_EPOCH,
         };
@@ -0,0 +1,159 @@
+//! Bounded buffer of canister log records.
+use ic_ic00_types::CanisterLogRecord;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// The maximum total size in bytes of the records kept in a canister log.
+/// Once the limit is exceeded, the oldest records are dropped.
+pub const MAX_ALLOWED_CANISTER_LOG_BUFFER_SIZE: usize = 4 * 1024;
+
+/// The log of a canister, i.e. the messages recorded by `ic0.debug_print` and
+/// traps, together with the index to be assigned to the next record.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CanisterLog {
+    next_idx: u64,
+    records: VecDeque<CanisterLogRecord>,
+    used_space: usize,
+}
+
+impl CanisterLog {
+    /// Creates a log from the given records, e.g. when loading a checkpoint.
+    pub fn new(next_idx: u64, records: Vec<CanisterLogRecord>) -> Self {
+        let mut log = Self {
+            next_idx,
+            records: VecDeque::with_capacity(records.len()),
+            used_space: 0,
+        };
+        for record in records {
+            log.push_back(record);
+        }
+        log
+    }
+
+    /// Creates an empty log whose first record will get the given index.
+    pub fn new_with_next_index(next_idx: u64) -> Self {
+        Self {
+            next_idx,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the index to be assigned to the next record.
+    pub fn next_idx(&self) -> u64 {
+        self.next_idx
+    }
+
+    /// Returns the records currently kept in the log, oldest first.
+    pub fn records(&self) -> &VecDeque<CanisterLogRecord> {
+        &self.records
+    }
+
+    /// Returns the number of bytes taken by the records in the log.
+    pub fn used_space(&self) -> usize {
+        self.used_space
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Appends a new record with the given content to the log. Contents that
+    /// do not fit into the log buffer are truncated.
+    pub fn add_record(&mut self, timestamp_nanos: u64, content: Vec<u8>) {
+        let mut content = content;
+        // Each record also stores its index and timestamp.
+        content.truncate(MAX_ALLOWED_CANISTER_LOG_BUFFER_SIZE - 2 * std::mem::size_of::<u64>());
+        let record = CanisterLogRecord {
+            idx: self.next_idx,
+            timestamp_nanos,
+            content,
+        };
+        self.next_idx += 1;
+        self.push_back(record);
+    }
+
+    /// Moves all the records of `delta` to the end of this log, re-indexing
+    /// them so that they follow the records already in the log.
+    pub fn append_delta_log(&mut self, delta: &mut CanisterLog) {
+        for record in std::mem::take(&mut delta.records) {
+            self.add_record(record.timestamp_nanos, record.content);
+        }
+        delta.used_space = 0;
+    }
+
+    fn push_back(&mut self, record: CanisterLogRecord) {
+        self.used_space += record.data_size();
+        self.records.push_back(record);
+        while self.used_space > MAX_ALLOWED_CANISTER_LOG_BUFFER_SIZE {
+            match self.records.pop_front() {
+                Some(record) => self.used_space -= record.data_size(),
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_get_consecutive_indices() {
+        let mut log = CanisterLog::new_with_next_index(7);
+        log.add_record(100, b"a".to_vec());
+        log.add_record(200, b"b".to_vec());
+
+        let indices: Vec<_> = log.records().iter().map(|r| r.idx).collect();
+        assert_eq!(indices, vec![7, 8]);
+        assert_eq!(log.next_idx(), 9);
+    }
+
+    #[test]
+    fn oldest_records_are_dropped_when_buffer_is_full() {
+        let mut log = CanisterLog::default();
+        let content = vec![0; MAX_ALLOWED_CANISTER_LOG_BUFFER_SIZE / 4];
+        for i in 0..10 {
+            log.add_record(i, content.clone());
+        }
+
+        assert!(log.used_space() <= MAX_ALLOWED_CANISTER_LOG_BUFFER_SIZE);
+        assert_eq!(log.records().back().unwrap().idx, 9);
+        assert_eq!(log.records().len(), 3);
+    }
+
+    #[test]
+    fn oversized_record_is_truncated() {
+        let mut log = CanisterLog::default();
+        log.add_record(0, vec![1; 2 * MAX_ALLOWED_CANISTER_LOG_BUFFER_SIZE]);
+
+        assert_eq!(log.records().len(), 1);
+        assert_eq!(log.used_space(), MAX_ALLOWED_CANISTER_LOG_BUFFER_SIZE);
+    }
+
+    #[test]
+    fn append_delta_log_reindexes_records() {
+        let mut log = CanisterLog::new_with_next_index(3);
+        log.add_record(0, b"first".to_vec());
+        let mut delta = CanisterLog::default();
+        delta.add_record(1, b"second".to_vec());
+        delta.add_record(2, b"third".to_vec());
+
+        log.append_delta_log(&mut delta);
+
+        assert!(delta.is_empty());
+        assert_eq!(delta.used_space(), 0);
+        let records: Vec<_> = log
+            .records()
+            .iter()
+            .map(|r| (r.idx, r.content.clone()))
+            .collect();
+        assert_eq!(
+            records,
+            vec![
+                (3, b"first".to_vec()),
+                (4, b"second".to_vec()),
+                (5, b"third".to_vec())
+            ]
+        );
+    }
+}
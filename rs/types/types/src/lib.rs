@@ -67,6 +67,7 @@ pub mod artifact;
 pub mod artifact_kind;
 pub mod batch;
 pub mod canister_http;
+pub mod canister_log;
 pub mod chunkable;
 pub mod consensus;
 pub mod crypto;
@@ -93,7 +94,7 @@ pub mod xnet;
 pub mod exhaustive;
 
 pub use crate::replica_version::ReplicaVersion;
-pub use crate::time::Time;
+pub use crate::time::{CoarseTime, Time};
 pub use funds::*;
 pub use ic_base_types::{
     subnet_id_into_protobuf, subnet_id_try_from_protobuf, CanisterId, CanisterIdBlobParseError,
@@ -105,6 +106,7 @@ use ic_protobuf::proxy::{try_from_option_field, ProxyDecodeError};
 use ic_protobuf::types::v1 as pb;
 use phantom_newtype::{AmountOf, Id};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::TryFrom;
 use std::fmt;
 use std::sync::Arc;
@@ -373,6 +375,142 @@ impl CanisterTimer {
     }
 }
 
+/// The named one-off timers of a canister, in addition to the global timer.
+///
+/// Each timer is identified by a canister-chosen ID. The timers are indexed by
+/// deadline, so that the next one to fire is found without a linear scan.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CanisterTimers {
+    /// The deadline of each active timer, by timer ID.
+    deadlines: BTreeMap<u64, Time>,
+    /// The active timers ordered by deadline and timer ID.
+    queue: BTreeSet<(Time, u64)>,
+}
+
+impl CanisterTimers {
+    /// Returns the timer with the given ID.
+    pub fn get(&self, timer_id: u64) -> CanisterTimer {
+        match self.deadlines.get(&timer_id) {
+            Some(time) => CanisterTimer::Active(*time),
+            None => CanisterTimer::Inactive,
+        }
+    }
+
+    /// Sets the timer with the given ID, replacing its previous deadline.
+    /// Setting an inactive timer cancels it. Returns the previous timer.
+    pub fn set(&mut self, timer_id: u64, timer: CanisterTimer) -> CanisterTimer {
+        let previous = match self.deadlines.remove(&timer_id) {
+            Some(time) => {
+                self.queue.remove(&(time, timer_id));
+                CanisterTimer::Active(time)
+            }
+            None => CanisterTimer::Inactive,
+        };
+        if let CanisterTimer::Active(time) = timer {
+            self.deadlines.insert(timer_id, time);
+            self.queue.insert((time, timer_id));
+        }
+        previous
+    }
+
+    /// Returns the number of active timers.
+    pub fn len(&self) -> usize {
+        self.deadlines.len()
+    }
+
+    /// Returns true if there are no active timers.
+    pub fn is_empty(&self) -> bool {
+        self.deadlines.is_empty()
+    }
+
+    /// Returns the earliest deadline of all active timers.
+    pub fn next_deadline(&self) -> Option<Time> {
+        self.queue.first().map(|(time, _)| *time)
+    }
+
+    /// Returns true if at least one timer has reached its deadline.
+    pub fn has_reached_deadline(&self, now: Time) -> bool {
+        self.next_deadline().map_or(false, |time| now >= time)
+    }
+
+    /// Removes the timer with the earliest deadline if it has reached the
+    /// deadline and returns its ID.
+    pub fn pop_reached_deadline(&mut self, now: Time) -> Option<u64> {
+        let (time, timer_id) = *self.queue.first()?;
+        if now < time {
+            return None;
+        }
+        self.queue.remove(&(time, timer_id));
+        self.deadlines.remove(&timer_id);
+        Some(timer_id)
+    }
+
+    /// Cancels all timers.
+    pub fn clear(&mut self) {
+        self.deadlines.clear();
+        self.queue.clear();
+    }
+
+    /// Returns the active timers as `(timer_id, deadline)` pairs ordered by
+    /// timer ID.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, Time)> + '_ {
+        self.deadlines
+            .iter()
+            .map(|(timer_id, time)| (*timer_id, *time))
+    }
+}
+
+impl FromIterator<(u64, Time)> for CanisterTimers {
+    fn from_iter<I: IntoIterator<Item = (u64, Time)>>(iter: I) -> Self {
+        let mut timers = Self::default();
+        for (timer_id, time) in iter {
+            timers.set(timer_id, CanisterTimer::from_time(time));
+        }
+        timers
+    }
+}
+
+#[test]
+fn canister_timers_fire_in_deadline_order() {
+    let mut timers = CanisterTimers::default();
+    let time = Time::from_nanos_since_unix_epoch;
+    assert_eq!(
+        timers.set(7, CanisterTimer::Active(time(30))),
+        CanisterTimer::Inactive
+    );
+    timers.set(3, CanisterTimer::Active(time(20)));
+    timers.set(5, CanisterTimer::Active(time(10)));
+    assert_eq!(
+        timers.set(5, CanisterTimer::Active(time(20))),
+        CanisterTimer::Active(time(10))
+    );
+    assert_eq!(timers.len(), 3);
+    assert_eq!(timers.next_deadline(), Some(time(20)));
+
+    assert!(!timers.has_reached_deadline(time(19)));
+    assert_eq!(timers.pop_reached_deadline(time(19)), None);
+    assert_eq!(timers.pop_reached_deadline(time(25)), Some(3));
+    assert_eq!(timers.pop_reached_deadline(time(25)), Some(5));
+    assert_eq!(timers.pop_reached_deadline(time(25)), None);
+    assert_eq!(timers.get(7), CanisterTimer::Active(time(30)));
+}
+
+#[test]
+fn canister_timers_can_be_cancelled() {
+    let time = Time::from_nanos_since_unix_epoch;
+    let mut timers: CanisterTimers = vec![(1, time(10)), (2, time(20))].into_iter().collect();
+    assert_eq!(
+        timers.set(1, CanisterTimer::Inactive),
+        CanisterTimer::Active(time(10))
+    );
+    assert_eq!(timers.get(1), CanisterTimer::Inactive);
+    assert_eq!(timers.next_deadline(), Some(time(20)));
+    assert_eq!(timers.iter().collect::<Vec<_>>(), vec![(2, time(20))]);
+    timers.clear();
+    assert!(timers.is_empty());
+    assert!(!timers.has_reached_deadline(time(100)));
+}
+
 /// Represents scheduling strategy for Canisters with long execution in progress.
 /// All long execution start in the Opportunistic mode, and then the scheduler
 /// prioritizes top `long_execution_cores` some of them. This is to enforce FIFO
@@ -17,7 +17,9 @@ pub use self::http::{
     SignedDelegation,
 };
 pub use crate::methods::SystemMethod;
-use crate::{user_id_into_protobuf, user_id_try_from_protobuf, Cycles, Funds, NumBytes, UserId};
+use crate::{
+    user_id_into_protobuf, user_id_try_from_protobuf, CoarseTime, Cycles, Funds, NumBytes, UserId,
+};
 pub use blob::Blob;
 use ic_base_types::{CanisterId, PrincipalId};
 use ic_ic00_types::CanisterChangeOrigin;
@@ -29,12 +31,15 @@ pub use ingress_messages::{
     SignedIngressContent,
 };
 pub use inter_canister::{
-    CallContextId, CallbackId, Payload, RejectContext, Request, RequestMetadata, RequestOrResponse,
-    Response, MAX_REJECT_MESSAGE_LEN_BYTES,
+    CallContextId, CallbackId, MessageFragment, Payload, RejectContext, Request, RequestMetadata,
+    RequestOrResponse, Response, MAX_REJECT_MESSAGE_LEN_BYTES, NO_DEADLINE,
 };
 pub use message_id::{MessageId, MessageIdError, EXPECTED_MESSAGE_ID_LENGTH};
 use phantom_newtype::Id;
-pub use query::{AnonymousQuery, AnonymousQueryResponse, AnonymousQueryResponseReply, UserQuery};
+pub use query::{
+    AnonymousQuery, AnonymousQueryResponse, AnonymousQueryResponseReply, CrossSubnetQuery,
+    CrossSubnetQueryResponse, UserQuery,
+};
 pub use read_state::ReadState;
 use serde::{Deserialize, Serialize};
 use std::fmt::{self, Debug, Display, Formatter};
@@ -58,6 +63,16 @@ pub const MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64: u64 = 2 * 1024 * 1024; // 2 M
 pub const MAX_INTER_CANISTER_PAYLOAD_IN_BYTES: NumBytes =
     NumBytes::new(MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64); // 2 MiB
 
+/// Hard upper bound on the payload size of an inter-canister request or
+/// response that is routed to a remote subnet as a sequence of
+/// `MessageFragment`s, each of at most [MAX_INTER_CANISTER_PAYLOAD_IN_BYTES].
+///
+/// The actual limit is configurable per subnet (see
+/// `SubnetRecord::max_inter_canister_payload_in_bytes`), but may not exceed
+/// this value.
+pub const MAX_FRAGMENTED_INTER_CANISTER_PAYLOAD_IN_BYTES: NumBytes =
+    NumBytes::new(5 * MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64); // 10 MiB
+
 /// The maximum size of an inter-canister request or response that the IC can
 /// support.
 ///
@@ -112,6 +127,9 @@ pub enum StopCanisterContext {
         /// here so that they can be returned to the caller in the eventual
         /// reply.
         cycles: Cycles,
+        /// The deadline of the request to stop the canister, if it was a
+        /// best-effort call; `NO_DEADLINE` otherwise.
+        deadline: CoarseTime,
     },
 }
 
@@ -148,6 +166,7 @@ impl From<(CanisterCall, StopCanisterCallId)> for StopCanisterContext {
                 reply_callback: req.sender_reply_callback,
                 call_id: Some(call_id),
                 cycles: Arc::make_mut(&mut req).payment.take(),
+                deadline: req.deadline,
             },
             CanisterCall::Ingress(ingress) => StopCanisterContext::Ingress {
                 sender: ingress.source,
@@ -179,6 +198,7 @@ impl From<&StopCanisterContext> for pb::StopCanisterContext {
                 reply_callback,
                 call_id,
                 cycles,
+                deadline,
             } => Self {
                 context: Some(pb::stop_canister_context::Context::Canister(
                     pb::stop_canister_context::Canister {
@@ -187,6 +207,7 @@ impl From<&StopCanisterContext> for pb::StopCanisterContext {
                         call_id: call_id.map(|id| id.get()),
                         funds: Some((&Funds::new(*cycles)).into()),
                         cycles: Some((*cycles).into()),
+                        deadline_seconds: deadline.as_secs_since_unix_epoch(),
                     },
                 )),
             },
@@ -220,6 +241,7 @@ impl TryFrom<pb::StopCanisterContext> for StopCanisterContext {
                         call_id,
                         funds,
                         cycles,
+                        deadline_seconds,
                     },
                 ) => {
                     // To maintain backwards compatibility we fall back to reading from `funds` if
@@ -246,6 +268,7 @@ impl TryFrom<pb::StopCanisterContext> for StopCanisterContext {
                         reply_callback: CallbackId::from(reply_callback),
                         call_id: call_id.map(StopCanisterCallId::from),
                         cycles,
+                        deadline: CoarseTime::from_secs_since_unix_epoch(deadline_seconds),
                     }
                 }
             };
@@ -413,11 +436,13 @@ impl TryFrom<CanisterMessage> for CanisterCall {
 }
 
 /// A canister task can be thought of as a special system message that the IC
-/// sends to the canister to execute its heartbeat or the global timer method.
+/// sends to the canister to execute its heartbeat, the global timer or the
+/// low Wasm memory hook method.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum CanisterTask {
     Heartbeat,
     GlobalTimer,
+    OnLowWasmMemory,
 }
 
 impl From<CanisterTask> for SystemMethod {
@@ -425,6 +450,7 @@ impl From<CanisterTask> for SystemMethod {
         match task {
             CanisterTask::Heartbeat => SystemMethod::CanisterHeartbeat,
             CanisterTask::GlobalTimer => SystemMethod::CanisterGlobalTimer,
+            CanisterTask::OnLowWasmMemory => SystemMethod::CanisterOnLowWasmMemory,
         }
     }
 }
@@ -434,6 +460,7 @@ impl Display for CanisterTask {
         match self {
             Self::Heartbeat => write!(f, "Heartbeat task"),
             Self::GlobalTimer => write!(f, "Global timer task"),
+            Self::OnLowWasmMemory => write!(f, "On low Wasm memory task"),
         }
     }
 }
@@ -709,6 +736,7 @@ mod tests {
                 method_name: "method".into(),
                 method_payload: vec![0_u8, 1_u8, 2_u8, 3_u8, 4_u8, 5_u8],
                 metadata,
+                deadline: NO_DEADLINE,
             };
             let bytes = bincode::serialize(&request).unwrap();
             let request1 = bincode::deserialize::<Request>(&bytes);
@@ -724,6 +752,7 @@ mod tests {
             originator_reply_callback: CallbackId::from(100),
             refund: Cycles::from(100_000_000_u128),
             response_payload: Payload::Data(vec![0_u8, 1_u8, 2_u8, 3_u8, 4_u8, 5_u8]),
+            deadline: NO_DEADLINE,
         };
         let bytes = bincode::serialize(&response).unwrap();
         let response1 = bincode::deserialize::<Response>(&bytes);
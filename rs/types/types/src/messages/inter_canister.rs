@@ -1,11 +1,14 @@
-use crate::{ingress::WasmResult, CanisterId, CountBytes, Cycles, Funds, NumBytes, Time};
+use crate::{
+    ingress::WasmResult, CanisterId, CoarseTime, CountBytes, Cycles, Funds, NumBytes, Time,
+};
 use ic_error_types::{RejectCode, TryFromError, UserError};
 #[cfg(test)]
 use ic_exhaustive_derive::ExhaustiveSet;
 use ic_ic00_types::{
-    CanisterIdRecord, CanisterInfoRequest, ClearChunkStoreArgs, InstallChunkedCodeArgs,
-    InstallCodeArgsV2, Method, Payload as _, ProvisionalTopUpCanisterArgs, StoredChunksArgs,
-    UpdateSettingsArgs, UploadChunkArgs,
+    CanisterIdRecord, CanisterInfoRequest, ClearChunkStoreArgs, DeleteCanisterSnapshotArgs,
+    FetchCanisterLogsRequest, InstallChunkedCodeArgs, InstallCodeArgsV2, ListCanisterSnapshotArgs,
+    LoadCanisterSnapshotArgs, Method, Payload as _, ProvisionalTopUpCanisterArgs, StoredChunksArgs,
+    TakeCanisterSnapshotArgs, UpdateSettingsArgs, UploadChunkArgs,
 };
 use ic_protobuf::{
     proxy::{try_from_option_field, ProxyDecodeError},
@@ -37,6 +40,9 @@ pub enum CallContextIdTag {}
 /// Identifies an incoming call.
 pub type CallContextId = Id<CallContextIdTag, u64>;
 
+/// The `deadline` of guaranteed response requests and responses.
+pub const NO_DEADLINE: CoarseTime = CoarseTime::from_secs_since_unix_epoch(0);
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct RequestMetadata {
     /// Indicates how many steps down the call tree a request is, starting at 0.
@@ -67,6 +73,10 @@ pub struct Request {
     #[serde(with = "serde_bytes")]
     pub method_payload: Vec<u8>,
     pub metadata: Option<RequestMetadata>,
+    /// If non-zero, this is a best-effort call: the caller stops waiting for a
+    /// response at this time and the request may be dropped if it cannot be
+    /// delivered before it.
+    pub deadline: CoarseTime,
 }
 
 impl Request {
@@ -75,6 +85,11 @@ impl Request {
         self.sender
     }
 
+    /// Returns `true` if this is the request of a best-effort call.
+    pub fn is_best_effort(&self) -> bool {
+        self.deadline != NO_DEADLINE
+    }
+
     /// Takes the payment out of this `Request`.
     pub fn take_cycles(&mut self) -> Cycles {
         self.payment.take()
@@ -143,6 +158,36 @@ impl Request {
                 Ok(record) => Some(record.get_canister_id()),
                 Err(_) => None,
             },
+            Ok(Method::TakeCanisterSnapshot) => {
+                match TakeCanisterSnapshotArgs::decode(&self.method_payload) {
+                    Ok(record) => Some(record.get_canister_id()),
+                    Err(_) => None,
+                }
+            }
+            Ok(Method::LoadCanisterSnapshot) => {
+                match LoadCanisterSnapshotArgs::decode(&self.method_payload) {
+                    Ok(record) => Some(record.get_canister_id()),
+                    Err(_) => None,
+                }
+            }
+            Ok(Method::ListCanisterSnapshots) => {
+                match ListCanisterSnapshotArgs::decode(&self.method_payload) {
+                    Ok(record) => Some(record.get_canister_id()),
+                    Err(_) => None,
+                }
+            }
+            Ok(Method::DeleteCanisterSnapshot) => {
+                match DeleteCanisterSnapshotArgs::decode(&self.method_payload) {
+                    Ok(record) => Some(record.get_canister_id()),
+                    Err(_) => None,
+                }
+            }
+            Ok(Method::FetchCanisterLogs) => {
+                match FetchCanisterLogsRequest::decode(&self.method_payload) {
+                    Ok(record) => Some(record.get_canister_id()),
+                    Err(_) => None,
+                }
+            }
             Ok(Method::DeleteChunks) => None,
             Ok(Method::CreateCanister)
             | Ok(Method::SetupInitialDKG)
@@ -190,7 +235,8 @@ impl std::fmt::Debug for Request {
             "method_payload: [{}], ",
             truncate_and_format(&self.method_payload, 1024)
         )?;
-        write!(f, "metadata: {:?} }}", self.metadata)?;
+        write!(f, "metadata: {:?}, ", self.metadata)?;
+        write!(f, "deadline: {:?} }}", self.deadline)?;
         Ok(())
     }
 }
@@ -340,9 +386,17 @@ pub struct Response {
     pub originator_reply_callback: CallbackId,
     pub refund: Cycles,
     pub response_payload: Payload,
+    /// The deadline of the originating request; `NO_DEADLINE` for guaranteed
+    /// response calls.
+    pub deadline: CoarseTime,
 }
 
 impl Response {
+    /// Returns `true` if this is the response to a best-effort call.
+    pub fn is_best_effort(&self) -> bool {
+        self.deadline != NO_DEADLINE
+    }
+
     /// Returns the size in bytes of this `Response`'s payload.
     pub fn payload_size_bytes(&self) -> NumBytes {
         self.response_payload.size_bytes()
@@ -357,6 +411,9 @@ impl Response {
 pub enum RequestOrResponse {
     Request(Arc<Request>),
     Response(Arc<Response>),
+    /// A fragment of a request or response whose payload is too large to be
+    /// routed to a remote subnet as a single message.
+    Fragment(Arc<MessageFragment>),
 }
 
 impl RequestOrResponse {
@@ -364,6 +421,7 @@ impl RequestOrResponse {
         match self {
             RequestOrResponse::Request(req) => req.receiver,
             RequestOrResponse::Response(resp) => resp.originator,
+            RequestOrResponse::Fragment(fragment) => fragment.header.receiver(),
         }
     }
 
@@ -371,6 +429,7 @@ impl RequestOrResponse {
         match self {
             RequestOrResponse::Request(req) => req.sender,
             RequestOrResponse::Response(resp) => resp.respondent,
+            RequestOrResponse::Fragment(fragment) => fragment.header.sender(),
         }
     }
 
@@ -379,19 +438,199 @@ impl RequestOrResponse {
     ///
     /// This is the "payload size" based on which cycle costs are calculated;
     /// and is (generally) limited to `MAX_INTER_CANISTER_PAYLOAD_IN_BYTES`.
+    /// For a fragment, this is the size of the fragment's slice of the payload
+    /// plus the size of the (payload-less) header.
     pub fn payload_size_bytes(&self) -> NumBytes {
         match self {
             RequestOrResponse::Request(req) => req.payload_size_bytes(),
             RequestOrResponse::Response(resp) => resp.response_payload.size_bytes(),
+            RequestOrResponse::Fragment(fragment) => {
+                fragment.header.payload_size_bytes() + NumBytes::from(fragment.payload.len() as u64)
+            }
+        }
+    }
+
+    /// Returns the deadline of this message; `NO_DEADLINE` for guaranteed
+    /// response messages.
+    pub fn deadline(&self) -> CoarseTime {
+        match self {
+            RequestOrResponse::Request(req) => req.deadline,
+            RequestOrResponse::Response(resp) => resp.deadline,
+            RequestOrResponse::Fragment(fragment) => fragment.header.deadline(),
         }
     }
 
     /// Returns the amount of cycles contained in this message.
+    ///
+    /// The cycles attached to a fragmented message are attributed to its last
+    /// fragment, i.e. the one upon which the message is reassembled.
     pub fn cycles(&self) -> Cycles {
         match self {
             RequestOrResponse::Request(req) => req.payment,
             RequestOrResponse::Response(resp) => resp.refund,
+            RequestOrResponse::Fragment(fragment) if fragment.is_last() => fragment.header.cycles(),
+            RequestOrResponse::Fragment(_) => Cycles::zero(),
+        }
+    }
+
+    /// Returns `true` if this is a request or a fragment of a request.
+    pub fn is_request(&self) -> bool {
+        match self {
+            RequestOrResponse::Request(_) => true,
+            RequestOrResponse::Response(_) => false,
+            RequestOrResponse::Fragment(fragment) => fragment.header.is_request(),
+        }
+    }
+
+    /// Splits this message into fragments whose payloads are at most
+    /// `max_fragment_payload_size` bytes each.
+    ///
+    /// Only requests and `Payload::Data` responses with payloads larger than
+    /// `max_fragment_payload_size` are split; any other message is returned
+    /// as is, as the single element of the returned `Vec`.
+    pub fn into_fragments(self, max_fragment_payload_size: usize) -> Vec<RequestOrResponse> {
+        assert!(max_fragment_payload_size > 0);
+
+        let (header, payload) = match &self {
+            RequestOrResponse::Request(req)
+                if req.method_payload.len() > max_fragment_payload_size =>
+            {
+                let mut header = req.as_ref().clone();
+                let payload = std::mem::take(&mut header.method_payload);
+                (RequestOrResponse::from(header), payload)
+            }
+            RequestOrResponse::Response(resp) => match &resp.response_payload {
+                Payload::Data(data) if data.len() > max_fragment_payload_size => {
+                    let mut header = resp.as_ref().clone();
+                    header.response_payload = Payload::Data(vec![]);
+                    (RequestOrResponse::from(header), data.clone())
+                }
+                _ => return vec![self],
+            },
+            _ => return vec![self],
+        };
+
+        let chunks = payload.chunks(max_fragment_payload_size);
+        let count = chunks.len() as u32;
+        chunks
+            .enumerate()
+            .map(|(index, chunk)| {
+                RequestOrResponse::Fragment(Arc::new(MessageFragment {
+                    header: header.clone(),
+                    index: index as u32,
+                    count,
+                    payload: chunk.to_vec(),
+                }))
+            })
+            .collect()
+    }
+}
+
+/// A fragment of an inter-canister `Request` or `Response` whose payload is
+/// too large to be routed to a remote subnet as a single stream message.
+///
+/// Every fragment carries a copy of the fragmented message with an empty
+/// payload (the `header`), so that it can be routed, accounted for and, if
+/// necessary, rejected on its own. The payload is split across fragments with
+/// indices `0..count`, which are always routed consecutively and in order.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct MessageFragment {
+    /// The fragmented `Request` or `Response`, with an empty payload.
+    pub header: RequestOrResponse,
+    /// The index of this fragment, in the `0..count` range.
+    pub index: u32,
+    /// The number of fragments the message was split into.
+    pub count: u32,
+    /// This fragment's slice of the message payload.
+    pub payload: Vec<u8>,
+}
+
+impl MessageFragment {
+    /// Returns `true` if this is the last fragment of the message.
+    pub fn is_last(&self) -> bool {
+        self.index + 1 == self.count
+    }
+
+    /// Reassembles a message from all of its fragments, in order.
+    ///
+    /// Returns an error if `fragments` is not the complete, ordered sequence of
+    /// fragments of a single message.
+    pub fn reassemble<'a>(
+        fragments: impl IntoIterator<Item = &'a MessageFragment>,
+    ) -> Result<RequestOrResponse, String> {
+        let mut fragments = fragments.into_iter();
+        let first = fragments
+            .next()
+            .ok_or_else(|| "No fragments to reassemble".to_string())?;
+        if first.index != 0 {
+            return Err(format!("Missing fragment 0 of {}", first.count));
+        }
+
+        let mut payload = first.payload.clone();
+        let mut last = first;
+        for fragment in fragments {
+            if fragment.header != first.header || fragment.count != first.count {
+                return Err(format!(
+                    "Fragment {} does not belong to the same message as fragment 0",
+                    fragment.index
+                ));
+            }
+            if fragment.index != last.index + 1 {
+                return Err(format!(
+                    "Expected fragment {}, got fragment {}",
+                    last.index + 1,
+                    fragment.index
+                ));
+            }
+            payload.extend_from_slice(&fragment.payload);
+            last = fragment;
+        }
+        if !last.is_last() {
+            return Err(format!(
+                "Missing fragments {}..{}",
+                last.index + 1,
+                last.count
+            ));
         }
+
+        match &first.header {
+            RequestOrResponse::Request(req) => Ok(Request {
+                method_payload: payload,
+                ..req.as_ref().clone()
+            }
+            .into()),
+            RequestOrResponse::Response(resp) => Ok(Response {
+                response_payload: Payload::Data(payload),
+                ..resp.as_ref().clone()
+            }
+            .into()),
+            RequestOrResponse::Fragment(_) => Err("Fragment of a fragment".to_string()),
+        }
+    }
+}
+
+impl std::fmt::Debug for MessageFragment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{{ header: {:?}, ", self.header)?;
+        write!(f, "index: {:?}, ", self.index)?;
+        write!(f, "count: {:?}, ", self.count)?;
+        write!(
+            f,
+            "payload: [{}] }}",
+            truncate_and_format(&self.payload, 1024)
+        )?;
+        Ok(())
+    }
+}
+
+/// Convenience `CountBytes` implementation that returns the same value as
+/// `RequestOrResponse::Fragment(self).count_bytes()`.
+impl CountBytes for MessageFragment {
+    fn count_bytes(&self) -> usize {
+        size_of::<RequestOrResponse>()
+            + size_of::<MessageFragment>()
+            + self.header.count_bytes()
+            + self.payload.len()
     }
 }
 
@@ -425,6 +664,7 @@ impl CountBytes for RequestOrResponse {
         match self {
             RequestOrResponse::Request(req) => req.count_bytes(),
             RequestOrResponse::Response(resp) => resp.count_bytes(),
+            RequestOrResponse::Fragment(fragment) => fragment.count_bytes(),
         }
     }
 }
@@ -466,6 +706,7 @@ impl From<&Request> for pb_queues::Request {
             method_payload: req.method_payload.clone(),
             cycles_payment: Some((req.payment).into()),
             metadata: req.metadata.as_ref().map(From::from),
+            deadline_seconds: req.deadline.as_secs_since_unix_epoch(),
         }
     }
 }
@@ -506,6 +747,7 @@ impl TryFrom<pb_queues::Request> for Request {
             method_name: req.method_name,
             method_payload: req.method_payload,
             metadata: req.metadata.map(From::from),
+            deadline: CoarseTime::from_secs_since_unix_epoch(req.deadline_seconds),
         })
     }
 }
@@ -548,6 +790,7 @@ impl From<&Response> for pb_queues::Response {
             refund: Some((&Funds::new(rep.refund)).into()),
             response_payload: Some(p),
             cycles_refund: Some((rep.refund).into()),
+            deadline_seconds: rep.deadline.as_secs_since_unix_epoch(),
         }
     }
 }
@@ -578,6 +821,7 @@ impl TryFrom<pb_queues::Response> for Response {
             originator_reply_callback: rep.originator_reply_callback.into(),
             refund,
             response_payload,
+            deadline: CoarseTime::from_secs_since_unix_epoch(rep.deadline_seconds),
         })
     }
 }
@@ -595,6 +839,11 @@ impl From<&RequestOrResponse> for pb_queues::RequestOrResponse {
                     rep.as_ref().into(),
                 )),
             },
+            RequestOrResponse::Fragment(fragment) => pb_queues::RequestOrResponse {
+                r: Some(pb_queues::request_or_response::R::Fragment(Box::new(
+                    fragment.as_ref().into(),
+                ))),
+            },
         }
     }
 }
@@ -613,6 +862,48 @@ impl TryFrom<pb_queues::RequestOrResponse> for RequestOrResponse {
             pb_queues::request_or_response::R::Response(r) => {
                 Ok(RequestOrResponse::Response(Arc::new(r.try_into()?)))
             }
+            pb_queues::request_or_response::R::Fragment(f) => {
+                Ok(RequestOrResponse::Fragment(Arc::new((*f).try_into()?)))
+            }
         }
     }
 }
+
+impl From<&MessageFragment> for pb_queues::MessageFragment {
+    fn from(fragment: &MessageFragment) -> Self {
+        Self {
+            header: Some(Box::new((&fragment.header).into())),
+            index: fragment.index,
+            count: fragment.count,
+            payload: fragment.payload.clone(),
+        }
+    }
+}
+
+impl TryFrom<pb_queues::MessageFragment> for MessageFragment {
+    type Error = ProxyDecodeError;
+
+    fn try_from(fragment: pb_queues::MessageFragment) -> Result<Self, Self::Error> {
+        let header: RequestOrResponse = try_from_option_field(
+            fragment.header.map(|header| *header),
+            "MessageFragment::header",
+        )?;
+        if let RequestOrResponse::Fragment(_) = header {
+            return Err(ProxyDecodeError::Other(
+                "MessageFragment::header must be a Request or Response".to_string(),
+            ));
+        }
+        if fragment.index >= fragment.count {
+            return Err(ProxyDecodeError::Other(format!(
+                "MessageFragment::index {} out of range 0..{}",
+                fragment.index, fragment.count
+            )));
+        }
+        Ok(Self {
+            header,
+            index: fragment.index,
+            count: fragment.count,
+            payload: fragment.payload,
+        })
+    }
+}
@@ -2,11 +2,11 @@ use crate::messages::Blob;
 use crate::{
     messages::{
         http::{representation_independent_hash_call_or_query, CallOrQuery},
-        HasCanisterId, HttpRequestError, HttpUserQuery, MessageId,
+        HasCanisterId, HttpRequestError, HttpUserQuery, MessageId, Payload,
     },
     CanisterId, PrincipalId, UserId,
 };
-use ic_error_types::RejectCode;
+use ic_error_types::{RejectCode, UserError};
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 
@@ -93,6 +93,37 @@ pub struct AnonymousQueryResponseReply {
     pub arg: Blob,
 }
 
+/// A query call made by a canister, as part of a composite query, to a
+/// canister hosted on another subnet. It is forwarded by a replica of the
+/// caller's subnet to a replica of the callee's subnet, together with the
+/// budget that is left for the rest of the query call graph.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct CrossSubnetQuery {
+    pub sender: CanisterId,
+    pub receiver: CanisterId,
+    pub method_name: String,
+    #[serde(with = "serde_bytes")]
+    pub method_payload: Vec<u8>,
+    /// The number of nested query calls that are still allowed, including
+    /// this one.
+    pub max_call_graph_depth: u64,
+    /// The number of instructions that the sub-graph may execute in total.
+    pub max_instructions: u64,
+    /// The walltime that the sub-graph may take, in milliseconds.
+    pub max_walltime_millis: u64,
+}
+
+/// The result of executing a `CrossSubnetQuery`.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct CrossSubnetQueryResponse {
+    /// The response to the caller, or the error that aborts the whole query
+    /// call graph (e.g. because one of its limits was exceeded).
+    pub result: Result<Payload, UserError>,
+    /// The number of instructions executed by the sub-graph, which count
+    /// towards the instruction limit of the caller's query call graph.
+    pub instructions_executed: u64,
+}
+
 #[cfg(test)]
 mod test {
     use super::super::{Blob, HttpUserQuery};
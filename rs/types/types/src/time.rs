@@ -202,6 +202,42 @@ impl Time {
     }
 }
 
+/// Time since UNIX_EPOCH, in seconds. Used for message deadlines, where a
+/// coarse granularity is sufficient and a compact encoding is desirable.
+#[derive(
+    Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Serialize, Deserialize,
+)]
+#[cfg_attr(test, derive(Arbitrary, ExhaustiveSet))]
+pub struct CoarseTime(u32);
+
+impl CoarseTime {
+    pub const fn from_secs_since_unix_epoch(secs: u32) -> Self {
+        CoarseTime(secs)
+    }
+
+    /// Number of seconds since UNIX EPOCH
+    pub fn as_secs_since_unix_epoch(self) -> u32 {
+        self.0
+    }
+
+    /// Rounds the given `Time` down to the closest `CoarseTime`, saturating at
+    /// `u32::MAX` seconds.
+    pub fn floor(time: Time) -> Self {
+        CoarseTime(time.as_secs_since_unix_epoch().min(u32::MAX as u64) as u32)
+    }
+
+    /// Saturating addition of a number of seconds.
+    pub fn saturating_add_secs(self, secs: u32) -> Self {
+        CoarseTime(self.0.saturating_add(secs))
+    }
+}
+
+impl From<CoarseTime> for Time {
+    fn from(t: CoarseTime) -> Self {
+        Time(t.0 as u64 * NANOS_PER_SEC)
+    }
+}
+
 #[derive(Error, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum TimeInstantiationError {
     #[error("Time cannot be instantiated as it would overflow: {0}")]
@@ -38,6 +38,8 @@ pub enum WasmValidationError {
     InvalidCustomSection(String),
     /// Module contains an invalid global section
     InvalidGlobalSection(String),
+    /// Module contains an invalid memory section
+    InvalidMemorySection(String),
     /// Module contains too many globals.
     TooManyGlobals { defined: usize, allowed: usize },
     /// Module contains too many functions.
@@ -92,6 +94,9 @@ impl std::fmt::Display for WasmValidationError {
             Self::InvalidGlobalSection(err) => {
                 write!(f, "Wasm module has an invalid global section. {}", err)
             }
+            Self::InvalidMemorySection(err) => {
+                write!(f, "Wasm module has an invalid memory section. {}", err)
+            }
             Self::TooManyGlobals { defined, allowed } => write!(
                 f,
                 "Wasm module defined {} globals which exceeds the maximum number allowed {}.",
@@ -24,19 +24,10 @@ pub(super) mod parser_to_internal {
             wasmparser::DataKind::Active {
                 memory_index,
                 offset_expr,
-            } => {
-                let ops: Vec<_> = offset_expr
-                    .get_operators_reader()
-                    .into_iter()
-                    .collect::<Result<_, _>>()?;
-                match ops.as_slice() {
-                    [_, wasmparser::Operator::End] => crate::DataSegmentKind::Active {
-                        memory_index,
-                        offset_expr: ops[0].clone(),
-                    },
-                    _ => return Err(crate::Error::InvalidConstExpr),
-                }
-            }
+            } => crate::DataSegmentKind::Active {
+                memory_index,
+                offset_expr: const_expr(offset_expr)?,
+            },
         })
     }
 
@@ -287,7 +278,7 @@ pub(super) mod internal_to_encoder {
                 memory_index,
                 offset_expr,
             } => {
-                *temp_const_expr = const_expr(&[offset_expr, wasmparser::Operator::End])?;
+                *temp_const_expr = const_expr(&offset_expr)?;
                 wasm_encoder::DataSegmentMode::Active {
                     memory_index,
                     offset: temp_const_expr,
@@ -57,8 +57,9 @@ pub enum DataSegmentKind<'a> {
     Active {
         /// The memory index for the data segment.
         memory_index: u32,
-        /// The initialization operator for the data segment.
-        offset_expr: Operator<'a>,
+        /// The constant expression computing the offset of the data segment,
+        /// including the terminating `End` operator.
+        offset_expr: Vec<Operator<'a>>,
     },
 }
 
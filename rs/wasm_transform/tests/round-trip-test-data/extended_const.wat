@@ -0,0 +1,10 @@
+(module
+  (global $g1 (import "foo" "bar") i32)
+  (global $g2 i32 (i32.add (global.get $g1) (i32.const 5)))
+  (global $g3 i64 (i64.mul (i64.sub (i64.const 100) (i64.const 4)) (i64.const 2)))
+  (memory 1)
+  (table 4 funcref)
+  (func $f)
+  (elem (i32.add (i32.const 1) (i32.const 2)) $f)
+  (data (i32.mul (i32.const 4) (i32.const 4)) "abcd")
+)
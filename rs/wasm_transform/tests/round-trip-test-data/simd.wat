@@ -0,0 +1,21 @@
+(module
+  (memory 1)
+  (func (param i32) (result v128)
+	local.get 0
+	v128.load offset=16
+	v128.const i32x4 1 2 3 4
+	i32x4.add
+	f32x4.splat (f32.const 1.5)
+	f32x4.mul
+	i8x16.shuffle 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 (v128.const i64x2 0 0)
+  )
+  (func (param i32 v128)
+	local.get 0
+	local.get 1
+	v128.store64_lane align=8 1
+	local.get 0
+	local.get 1
+	i64x2.extract_lane 0
+	i64.store
+  )
+)
@@ -0,0 +1,22 @@
+(module
+  (type $t (func (param i64) (result i64)))
+  (table 1 funcref)
+  (elem (i32.const 0) $f)
+  (func $f (type $t)
+	local.get 0
+	i64.eqz
+	if (result i64)
+	  i64.const 0
+	else
+	  local.get 0
+	  i64.const 1
+	  i64.sub
+	  return_call $f
+	end
+  )
+  (func (param i64) (result i64)
+	local.get 0
+	i32.const 0
+	return_call_indirect (type $t)
+  )
+)
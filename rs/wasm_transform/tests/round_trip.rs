@@ -38,6 +38,9 @@ mod round_trip {
         table_init,
         globals,
         exports,
-        start
+        start,
+        simd,
+        tail_call,
+        extended_const
     );
 }
@@ -3,16 +3,23 @@ mod config_tests;
 #[cfg(test)]
 mod tests;
 
-use hyper::{Body, Request, Response, StatusCode};
-use ic_crypto_tls_interfaces::TlsHandshake;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use ic_crypto_tls_interfaces::{AuthenticatedPeer, TlsHandshake};
+use ic_interfaces::execution_environment::CrossSubnetQueryService;
 use ic_interfaces_certified_stream_store::{CertifiedStreamStore, EncodeStreamError};
 use ic_interfaces_registry::RegistryClient;
 use ic_logger::{debug, info, warn, ReplicaLogger};
 use ic_metrics::{buckets::decimal_buckets, MetricsRegistry, Timer};
 use ic_protobuf::messaging::xnet::v1 as pb;
 use ic_protobuf::proxy::ProtoProxy;
-use ic_registry_client_helpers::node::NodeRegistry;
-use ic_types::{xnet::StreamIndex, NodeId, PrincipalId, SubnetId};
+use ic_registry_client_helpers::{
+    node::NodeRegistry, routing_table::RoutingTableRegistry, subnet::SubnetRegistry,
+};
+use ic_types::{
+    messages::{CrossSubnetQuery, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64},
+    xnet::StreamIndex,
+    CanisterId, NodeId, PrincipalId, SubnetId,
+};
 use prometheus::{Histogram, HistogramVec};
 use serde::Serialize;
 use std::convert::Infallible;
@@ -24,6 +31,7 @@ use tokio::{
     runtime,
     sync::{oneshot, Notify},
 };
+use tower::ServiceExt;
 use url::Url;
 
 pub struct XNetEndpointMetrics {
@@ -41,12 +49,18 @@ const METRIC_SLICE_PAYLOAD_SIZE: &str = "xnet_endpoint_slice_payload_size_bytes"
 const METRIC_RESPONSE_SIZE: &str = "xnet_endpoint_response_size_bytes";
 
 const RESOURCE_ERROR: &str = "error";
+const RESOURCE_QUERY: &str = "query";
 const RESOURCE_STREAM: &str = "stream";
 const RESOURCE_STREAMS: &str = "streams";
 const RESOURCE_UNKNOWN: &str = "unknown";
 
 const XNET_ENDPOINT_NUM_WORKER_THREADS: usize = 4;
 
+/// Maximum size of a `/api/v1/query` request body: the maximum inter-canister
+/// payload plus headroom for the method name and the CBOR envelope.
+const MAX_QUERY_REQUEST_SIZE_BYTES: usize =
+    MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64 as usize + 64 * 1024;
+
 impl XNetEndpointMetrics {
     pub fn new(metrics_registry: &MetricsRegistry) -> Self {
         Self {
@@ -103,6 +117,12 @@ enum WorkerMessage {
 ///   - Returns a stream slice for the given `SubnetId` with up to `msg_limit`
 ///     messages beginning at `msg_begin`, witness beginning at `witness_begin`
 ///     (`msg_begin` if missing), of up to `byte_limit` bytes.
+/// * `POST /api/v1/query`
+///   - Executes a CBOR encoded `CrossSubnetQuery` made by a composite query on
+///     another subnet and returns the CBOR encoded `CrossSubnetQueryResponse`.
+///     Served directly by the query execution service, not by the stream
+///     handler threads. Only accepted from nodes of the subnet hosting the
+///     calling canister.
 pub struct XNetEndpoint {
     server_address: SocketAddr,
     handler_thread_pool: threadpool::ThreadPool,
@@ -135,12 +155,14 @@ impl Drop for XNetEndpoint {
 
 const API_URL_STREAMS: &str = "/api/v1/streams";
 const API_URL_STREAM_PREFIX: &str = "/api/v1/stream/";
+const API_URL_QUERY: &str = "/api/v1/query";
 
 impl XNetEndpoint {
     /// Creates and starts an `XNetEndpoint` to publish XNet `Streams`.
     pub fn new(
         runtime_handle: runtime::Handle,
         certified_stream_store: Arc<dyn CertifiedStreamStore>,
+        cross_subnet_query_service: CrossSubnetQueryService,
         tls: Arc<dyn TlsHandshake + Send + Sync>,
         registry_client: Arc<dyn RegistryClient + Send + Sync>,
         config: XNetEndpointConfig,
@@ -174,6 +196,8 @@ impl XNetEndpoint {
             struct Context {
                 log: ReplicaLogger,
                 request_sender: crossbeam_channel::Sender<WorkerMessage>,
+                cross_subnet_query_service: CrossSubnetQueryService,
+                registry_client: Arc<dyn RegistryClient + Send + Sync>,
                 metrics: Arc<XNetEndpointMetrics>,
             }
 
@@ -181,6 +205,8 @@ impl XNetEndpoint {
                 log: log.clone(),
                 metrics: Arc::clone(&metrics),
                 request_sender: request_sender.clone(),
+                cross_subnet_query_service,
+                registry_client: Arc::clone(&registry_client),
             };
 
             fn ok<T>(t: T) -> Result<T, Infallible> {
@@ -189,6 +215,9 @@ impl XNetEndpoint {
 
             move |tls_conn: &TlsConnection| {
                 let ctx = ctx.clone();
+                // The TLS handshake may still be in progress, so the peer is
+                // looked up when a request is served.
+                let peer_handle = tls_conn.peer_handle();
                 debug!(
                     ctx.log,
                     "Serving XNet streams to peer {:?}",
@@ -200,9 +229,25 @@ impl XNetEndpoint {
                     ok(service_fn({
                         move |request: Request<Body>| {
                             let ctx = ctx.clone();
+                            let peer = peer_handle.get().cloned();
 
                             async move {
                                 let _ = &ctx;
+                                // Queries are executed by the query execution service, which has
+                                // its own scheduler, so they bypass the stream handler threads.
+                                if request.method() == Method::POST
+                                    && request.uri().path() == API_URL_QUERY
+                                {
+                                    return ok(handle_query(
+                                        request,
+                                        peer,
+                                        ctx.registry_client.as_ref(),
+                                        ctx.cross_subnet_query_service.clone(),
+                                        &ctx.metrics,
+                                    )
+                                    .await);
+                                }
+
                                 let (response_sender, response_receiver) = oneshot::channel();
                                 let task = WorkerMessage::HandleRequest {
                                     request,
@@ -434,6 +479,99 @@ fn route_request(
     response
 }
 
+/// Executes a query call forwarded by a composite query on another subnet,
+/// after checking that `peer` is a node of the subnet hosting the caller.
+async fn handle_query(
+    request: Request<Body>,
+    peer: Option<AuthenticatedPeer>,
+    registry_client: &dyn RegistryClient,
+    cross_subnet_query_service: CrossSubnetQueryService,
+    metrics: &XNetEndpointMetrics,
+) -> Response<Body> {
+    let timer = Timer::start();
+    let response = match read_body(request.into_body(), MAX_QUERY_REQUEST_SIZE_BYTES).await {
+        Ok(body) => match serde_cbor::from_slice::<CrossSubnetQuery>(&body) {
+            Ok(query) => {
+                match authenticate_query_sender(peer.as_ref(), query.sender, registry_client) {
+                    Ok(()) => {
+                        let response = cross_subnet_query_service
+                            .oneshot(query)
+                            .await
+                            .unwrap_or_else(|err| match err {});
+                        observe_response_size(|| cbor_response(&response), RESOURCE_QUERY, metrics)
+                    }
+                    Err(response) => response,
+                }
+            }
+            Err(e) => bad_request(format!("Invalid query: {}", e)),
+        },
+        Err(response) => response,
+    };
+    metrics
+        .request_duration
+        .with_label_values(&[RESOURCE_QUERY, response.status().as_str()])
+        .observe(timer.elapsed());
+
+    response
+}
+
+/// Checks that `peer` is a node of the subnet that the routing table assigns
+/// `sender` to, i.e. that the query call was forwarded by a replica of the
+/// caller's subnet. Produces an error response otherwise.
+fn authenticate_query_sender(
+    peer: Option<&AuthenticatedPeer>,
+    sender: CanisterId,
+    registry_client: &dyn RegistryClient,
+) -> Result<(), Response<Body>> {
+    let node_id = match peer {
+        Some(AuthenticatedPeer::Node(node_id)) => *node_id,
+        None => return Err(forbidden("Query calls require an authenticated peer")),
+    };
+
+    let version = registry_client.get_latest_version();
+    let sender_subnet_id = registry_client
+        .get_routing_table(version)
+        .map_err(|e| service_unavailable(format!("Failed to read the routing table: {}", e)))?
+        .and_then(|routing_table| routing_table.route(sender.get()))
+        .ok_or_else(|| forbidden(format!("Sender {} is not hosted on any subnet", sender)))?;
+    let sender_subnet_nodes = registry_client
+        .get_node_ids_on_subnet(sender_subnet_id, version)
+        .map_err(|e| {
+            service_unavailable(format!(
+                "Failed to read the nodes of subnet {}: {}",
+                sender_subnet_id, e
+            ))
+        })?
+        .unwrap_or_default();
+    if !sender_subnet_nodes.contains(&node_id) {
+        return Err(forbidden(format!(
+            "Node {} is not a member of subnet {} hosting sender {}",
+            node_id, sender_subnet_id, sender
+        )));
+    }
+    Ok(())
+}
+
+/// Reads the request body, producing an error response if reading fails or the
+/// body is larger than `limit` bytes.
+async fn read_body(mut body: Body, limit: usize) -> Result<Vec<u8>, Response<Body>> {
+    use hyper::body::HttpBody;
+
+    let mut buf = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk =
+            chunk.map_err(|e| bad_request(format!("Failed to read request body: {}", e)))?;
+        if buf.len() + chunk.len() > limit {
+            return Err(payload_too_large(format!(
+                "Request body exceeds {} bytes",
+                limit
+            )));
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(buf)
+}
+
 /// Returns a list of all subnets with available streams.
 fn handle_streams(
     certified_stream_store: &dyn CertifiedStreamStore,
@@ -511,6 +649,19 @@ pub(crate) fn json_response<R: Serialize>(r: &R) -> (Response<Body>, usize) {
     (response, size_bytes)
 }
 
+/// Serializes the response as CBOR.
+pub(crate) fn cbor_response<R: Serialize>(r: &R) -> (Response<Body>, usize) {
+    let buf = serde_cbor::to_vec(r).expect("Could not serialize response");
+    let size_bytes = buf.len();
+
+    let response = Response::builder()
+        .header("Content-Type", "application/cbor")
+        .body(buf.into())
+        .unwrap();
+
+    (response, size_bytes)
+}
+
 /// Serializes the response as Protobuf.
 pub(crate) fn proto_response<R, M>(r: R) -> (Response<Body>, usize)
 where
@@ -547,6 +698,14 @@ fn bad_request<T: Into<Body>>(msg: T) -> Response<Body> {
         .unwrap()
 }
 
+/// Produces a 403 Forbidden response with the given content.
+fn forbidden<T: Into<Body>>(msg: T) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .body(msg.into())
+        .unwrap()
+}
+
 /// Produces a 404 Not Found response with the given content.
 fn not_found<T: Into<Body>>(msg: T) -> Response<Body> {
     Response::builder()
@@ -555,6 +714,14 @@ fn not_found<T: Into<Body>>(msg: T) -> Response<Body> {
         .unwrap()
 }
 
+/// Produces a 413 Payload Too Large response with the given content.
+fn payload_too_large<T: Into<Body>>(msg: T) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::PAYLOAD_TOO_LARGE)
+        .body(msg.into())
+        .unwrap()
+}
+
 /// Produces a 416 Range Not Satisfiable response with the given content.
 fn range_not_satisfiable<T: Into<Body>>(msg: T) -> Response<Body> {
     Response::builder()
@@ -563,6 +730,14 @@ fn range_not_satisfiable<T: Into<Body>>(msg: T) -> Response<Body> {
         .unwrap()
 }
 
+/// Produces a 503 Service Unavailable response with the given content.
+fn service_unavailable<T: Into<Body>>(msg: T) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .body(msg.into())
+        .unwrap()
+}
+
 /// The socket address for `XNetEndpoint` to listen on.
 #[derive(Debug, PartialEq, Eq)]
 pub struct XNetEndpointConfig {
@@ -2,13 +2,19 @@ use super::*;
 use bytes::Bytes;
 use ic_interfaces_registry_mocks::MockRegistryClient;
 use ic_interfaces_state_manager::{CertificationScope, StateManager};
-use ic_protobuf::{messaging::xnet::v1 as pb, proxy::ProtoProxy};
+use ic_protobuf::{
+    messaging::xnet::v1 as pb,
+    proxy::ProtoProxy,
+    registry::{routing_table::v1 as pb_routing_table, subnet::v1::SubnetRecord},
+};
+use ic_registry_keys::{make_routing_table_record_key, make_subnet_record_key};
+use ic_registry_routing_table::{CanisterIdRange, RoutingTable};
 use ic_replicated_state::{testing::ReplicatedStateTesting, ReplicatedState, Stream};
 use ic_test_utilities::{
     crypto::fake_tls_handshake::FakeTlsHandshake,
     state_manager::FakeStateManager,
     types::{
-        ids::{canister_test_id, SUBNET_6, SUBNET_7},
+        ids::{canister_test_id, node_test_id, SUBNET_5, SUBNET_6, SUBNET_7},
         messages::RequestBuilder,
     },
 };
@@ -16,7 +22,11 @@ use ic_test_utilities_logger::with_test_replica_logger;
 use ic_test_utilities_metrics::{
     fetch_histogram_stats, fetch_histogram_vec_count, metric_vec, HistogramStats, MetricVec,
 };
-use ic_types::{messages::CallbackId, xnet::StreamIndexedQueue, Height, SubnetId};
+use ic_types::{
+    messages::{CallbackId, CrossSubnetQueryResponse, Payload},
+    xnet::StreamIndexedQueue,
+    Height, RegistryVersion, SubnetId,
+};
 use maplit::btreemap;
 use std::sync::Barrier;
 use url::Url;
@@ -24,6 +34,8 @@ use url::Url;
 const SRC_CANISTER: u64 = 2;
 const DST_CANISTER: u64 = 3;
 const CALLBACK_ID: u64 = 4;
+const SRC_NODE: u64 = 5;
+const SRC_SUBNET: SubnetId = SUBNET_5;
 const DST_SUBNET: SubnetId = SUBNET_6;
 const UNKNOWN_SUBNET: SubnetId = SUBNET_7;
 
@@ -35,6 +47,7 @@ pub(crate) struct EndpointTestFixture {
     pub registry_client: Arc<MockRegistryClient>,
     pub metrics: MetricsRegistry,
     pub tls_handshake: Arc<dyn TlsHandshake + Send + Sync>,
+    pub cross_subnet_query_service: CrossSubnetQueryService,
 }
 
 impl EndpointTestFixture {
@@ -69,6 +82,7 @@ impl Default for EndpointTestFixture {
             state_manager: Arc::new(FakeStateManager::new()),
             registry_client: Arc::new(MockRegistryClient::new()),
             tls_handshake: Arc::new(FakeTlsHandshake::new()),
+            cross_subnet_query_service: echo_query_service(),
         }
     }
 }
@@ -85,6 +99,7 @@ fn query_streams() {
         let xnet_endpoint = XNetEndpoint::new(
             rt.handle().clone(),
             fixture.state_manager.clone(),
+            fixture.cross_subnet_query_service.clone(),
             fixture.tls_handshake.clone(),
             fixture.registry_client.clone(),
             Default::default(),
@@ -120,6 +135,7 @@ fn query_stream() {
         let xnet_endpoint = XNetEndpoint::new(
             rt.handle().clone(),
             fixture.state_manager.clone(),
+            fixture.cross_subnet_query_service.clone(),
             fixture.tls_handshake.clone(),
             fixture.registry_client.clone(),
             Default::default(),
@@ -184,6 +200,7 @@ fn query_stream_parallel() {
         let xnet_endpoint = XNetEndpoint::new(
             endpoint_rt.handle().clone(),
             fixture.state_manager.clone(),
+            fixture.cross_subnet_query_service.clone(),
             fixture.tls_handshake.clone(),
             fixture.registry_client.clone(),
             Default::default(),
@@ -555,6 +572,208 @@ async fn handle_bad_api_path() {
 }
 
 /// Commits a `ReplicatedState` containing a single stream for DST_SUBNET.
+/// Tests that a `CrossSubnetQuery` is decoded, handed to the query service and
+/// the response is returned CBOR encoded.
+#[tokio::test]
+async fn handle_cross_subnet_query() {
+    let fixture = EndpointTestFixture::default();
+    let query = cross_subnet_query_for_testing();
+
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(API_URL_QUERY)
+        .body(Body::from(serde_cbor::to_vec(&query).unwrap()))
+        .unwrap();
+    let response = handle_query(
+        request,
+        Some(AuthenticatedPeer::Node(node_test_id(SRC_NODE))),
+        &registry_with_sender_subnet(),
+        fixture.cross_subnet_query_service.clone(),
+        &XNetEndpointMetrics::new(&fixture.metrics),
+    )
+    .await;
+    let (parsed_status, body) = parse_response(response).await;
+
+    assert_eq!(200, parsed_status);
+    assert_eq!(
+        CrossSubnetQueryResponse {
+            result: Ok(Payload::Data(query.method_payload.clone())),
+            instructions_executed: 1_000,
+        },
+        serde_cbor::from_slice(&body).unwrap()
+    );
+    assert_eq!(
+        metric_vec(&[(&[("resource", "query"), ("status", "200")], 1)]),
+        fixture.request_counts()
+    );
+    assert_eq!(
+        metric_vec(&[(&[("resource", "query")], 1)]),
+        fixture.response_size_counts()
+    );
+}
+
+/// Tests that malformed and oversized query requests are rejected.
+#[tokio::test]
+async fn handle_cross_subnet_query_bad_request() {
+    let fixture = EndpointTestFixture::default();
+    let metrics = XNetEndpointMetrics::new(&fixture.metrics);
+
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(API_URL_QUERY)
+        .body(Body::from(vec![0xff, 0x00]))
+        .unwrap();
+    let response = handle_query(
+        request,
+        Some(AuthenticatedPeer::Node(node_test_id(SRC_NODE))),
+        &registry_with_sender_subnet(),
+        fixture.cross_subnet_query_service.clone(),
+        &metrics,
+    )
+    .await;
+    assert_eq!(400, parse_response(response).await.0);
+
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(API_URL_QUERY)
+        .body(Body::from(vec![0; MAX_QUERY_REQUEST_SIZE_BYTES + 1]))
+        .unwrap();
+    let response = handle_query(
+        request,
+        Some(AuthenticatedPeer::Node(node_test_id(SRC_NODE))),
+        &registry_with_sender_subnet(),
+        fixture.cross_subnet_query_service.clone(),
+        &metrics,
+    )
+    .await;
+    assert_eq!(413, parse_response(response).await.0);
+
+    assert_eq!(
+        metric_vec(&[
+            (&[("resource", "query"), ("status", "400")], 1),
+            (&[("resource", "query"), ("status", "413")], 1)
+        ]),
+        fixture.request_counts()
+    );
+}
+
+/// Tests that query calls are only accepted from nodes of the subnet hosting
+/// the sender.
+#[tokio::test]
+async fn handle_cross_subnet_query_from_unauthorized_peer() {
+    let fixture = EndpointTestFixture::default();
+    let metrics = XNetEndpointMetrics::new(&fixture.metrics);
+    let registry = registry_with_sender_subnet();
+
+    let query_request = |query: &CrossSubnetQuery| {
+        Request::builder()
+            .method(Method::POST)
+            .uri(API_URL_QUERY)
+            .body(Body::from(serde_cbor::to_vec(query).unwrap()))
+            .unwrap()
+    };
+
+    // No authenticated peer.
+    let query = cross_subnet_query_for_testing();
+    let response = handle_query(
+        query_request(&query),
+        None,
+        &registry,
+        fixture.cross_subnet_query_service.clone(),
+        &metrics,
+    )
+    .await;
+    assert_eq!(403, parse_response(response).await.0);
+
+    // A node that is not a member of the sender's subnet.
+    let response = handle_query(
+        query_request(&query),
+        Some(AuthenticatedPeer::Node(node_test_id(SRC_NODE + 1))),
+        &registry,
+        fixture.cross_subnet_query_service.clone(),
+        &metrics,
+    )
+    .await;
+    assert_eq!(403, parse_response(response).await.0);
+
+    // A sender that is not hosted on the peer's subnet.
+    let query = CrossSubnetQuery {
+        sender: canister_test_id(DST_CANISTER),
+        ..cross_subnet_query_for_testing()
+    };
+    let response = handle_query(
+        query_request(&query),
+        Some(AuthenticatedPeer::Node(node_test_id(SRC_NODE))),
+        &registry,
+        fixture.cross_subnet_query_service.clone(),
+        &metrics,
+    )
+    .await;
+    assert_eq!(403, parse_response(response).await.0);
+
+    assert_eq!(
+        metric_vec(&[(&[("resource", "query"), ("status", "403")], 3)]),
+        fixture.request_counts()
+    );
+}
+
+/// Returns a registry that routes `SRC_CANISTER` (and only it) to
+/// `SRC_SUBNET`, whose only member is `SRC_NODE`.
+fn registry_with_sender_subnet() -> MockRegistryClient {
+    use prost::Message;
+
+    let routing_table = RoutingTable::try_from(btreemap! {
+        CanisterIdRange {
+            start: canister_test_id(SRC_CANISTER),
+            end: canister_test_id(SRC_CANISTER),
+        } => SRC_SUBNET,
+    })
+    .unwrap();
+    let routing_table = pb_routing_table::RoutingTable::from(&routing_table).encode_to_vec();
+    let subnet_record = SubnetRecord {
+        membership: vec![node_test_id(SRC_NODE).get().into_vec()],
+        ..Default::default()
+    }
+    .encode_to_vec();
+
+    let mut registry = MockRegistryClient::new();
+    registry
+        .expect_get_latest_version()
+        .return_const(RegistryVersion::from(1));
+    registry.expect_get_value().returning(move |key, _| {
+        if key == make_routing_table_record_key() {
+            Ok(Some(routing_table.clone()))
+        } else if key == make_subnet_record_key(SRC_SUBNET) {
+            Ok(Some(subnet_record.clone()))
+        } else {
+            Ok(None)
+        }
+    });
+    registry
+}
+
+/// A query service that replies with the query's payload.
+fn echo_query_service() -> CrossSubnetQueryService {
+    tower::util::BoxCloneService::new(tower::service_fn(|query: CrossSubnetQuery| async move {
+        Ok(CrossSubnetQueryResponse {
+            result: Ok(Payload::Data(query.method_payload)),
+            instructions_executed: 1_000,
+        })
+    }))
+}
+
+fn cross_subnet_query_for_testing() -> CrossSubnetQuery {
+    CrossSubnetQuery {
+        sender: canister_test_id(SRC_CANISTER),
+        receiver: canister_test_id(DST_CANISTER),
+        method_name: "query".to_string(),
+        method_payload: vec![1, 2, 3],
+        max_call_graph_depth: 5,
+        max_instructions: 1_000_000,
+        max_walltime_millis: 1_000,
+    }
+}
+
 fn put_replicated_state_for_testing(
     h: Height,
     state_manager: &dyn StateManager<State = ReplicatedState>,
@@ -17,7 +17,7 @@ use std::convert::TryFrom;
 use std::future::Future;
 use std::net::SocketAddr;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::task::{Context, Poll};
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio::net::{TcpListener, TcpStream};
@@ -75,20 +75,65 @@ fn io_err(e: impl std::error::Error + Send + Sync + 'static) -> std::io::Error {
     std::io::Error::new(std::io::ErrorKind::Other, e)
 }
 
+/// A handle to the identity of the peer of a `TlsConnection`, which is set
+/// once the TLS handshake completes successfully.
+///
+/// Unlike `TlsConnection::peer()`, the handle can be retrieved before the
+/// handshake completes (e.g. when a Hyper service is created for the
+/// connection) and queried later on (e.g. when a request is served).
+#[derive(Clone, Debug, Default)]
+pub struct PeerHandle(Arc<OnceLock<AuthenticatedPeer>>);
+
+impl PeerHandle {
+    fn new(peer: AuthenticatedPeer) -> Self {
+        let handle = Self::default();
+        handle.set(peer);
+        handle
+    }
+
+    fn set(&self, peer: AuthenticatedPeer) {
+        // The handshake completes at most once.
+        let _ = self.0.set(peer);
+    }
+
+    /// Returns the identity of the connected peer if the TLS handshake
+    /// completed successfully.
+    pub fn get(&self) -> Option<&AuthenticatedPeer> {
+        self.0.get()
+    }
+}
+
 /// A TLS connection.
-pub struct TlsConnection(ConnectionState);
+pub struct TlsConnection {
+    state: ConnectionState,
+    peer_handle: PeerHandle,
+}
 
 impl TlsConnection {
+    fn new(state: ConnectionState) -> Self {
+        let peer_handle = match &state {
+            ConnectionState::Ready { peer, .. } => PeerHandle::new(peer.clone()),
+            _ => PeerHandle::default(),
+        };
+        Self { state, peer_handle }
+    }
+
     /// Returns the identity of the connected peer if the TLS
     /// handshake completed successfully. Returns None if the handshake is not
     /// completed yet or failed.
     pub fn peer(&self) -> Option<&AuthenticatedPeer> {
-        match &self.0 {
+        match &self.state {
             ConnectionState::Ready { peer, .. } => Some(peer),
             _ => None,
         }
     }
 
+    /// Returns a handle to the identity of the connected peer, which is set
+    /// once the TLS handshake completes successfully.
+    pub fn peer_handle(&self) -> PeerHandle {
+        self.peer_handle.clone()
+    }
+
     /// If the handshake is completed, applies `f` to the TlsStream.
     /// Otherwise, tries to make the progress with the handshake first.
     fn after_handshake<F, R>(
@@ -99,23 +144,24 @@ impl TlsConnection {
     where
         F: FnOnce(Pin<&mut Box<dyn TlsStream>>, &mut Context<'_>) -> Poll<std::io::Result<R>>,
     {
-        match &mut self.0 {
+        match &mut self.state {
             ConnectionState::Handshake(fut) => match Future::poll(Pin::new(fut), cx) {
                 Poll::Ready(Ok((stream, peer))) => {
+                    self.peer_handle.set(peer.clone());
                     // We have to switch the state before we call the
                     // callback, because the callback might call back
                     // into TlsConnection and cause another poll on
                     // the `fut` future, which is not allowed for
                     // futures that returned `Ready`.
-                    self.0 = ConnectionState::Ready { stream, peer };
-                    if let ConnectionState::Ready { ref mut stream, .. } = self.0 {
+                    self.state = ConnectionState::Ready { stream, peer };
+                    if let ConnectionState::Ready { ref mut stream, .. } = self.state {
                         f(Pin::new(stream), cx)
                     } else {
                         unreachable!()
                     }
                 }
                 Poll::Ready(Err(tls_err)) => {
-                    self.0 = ConnectionState::Failed(tls_err.clone());
+                    self.state = ConnectionState::Failed(tls_err.clone());
                     Poll::Ready(Err(io_err(tls_err)))
                 }
                 Poll::Pending => Poll::Pending,
@@ -135,7 +181,7 @@ impl AsyncRead for TlsConnection {
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<std::io::Result<()>> {
-        match &mut self.0 {
+        match &mut self.state {
             ConnectionState::Unencrypted(ref mut tcp_stream) => {
                 Pin::new(tcp_stream).poll_read(cx, buf)
             }
@@ -150,7 +196,7 @@ impl AsyncWrite for TlsConnection {
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<std::io::Result<usize>> {
-        match &mut self.0 {
+        match &mut self.state {
             ConnectionState::Unencrypted(ref mut tcp_stream) => {
                 Pin::new(tcp_stream).poll_write(cx, buf)
             }
@@ -159,14 +205,14 @@ impl AsyncWrite for TlsConnection {
     }
 
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
-        match &mut self.0 {
+        match &mut self.state {
             ConnectionState::Unencrypted(ref mut tcp_stream) => Pin::new(tcp_stream).poll_flush(cx),
             _ => self.after_handshake(cx, |stream, cx| stream.poll_flush(cx)),
         }
     }
 
     fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
-        match &mut self.0 {
+        match &mut self.state {
             ConnectionState::Unencrypted(ref mut tcp_stream) => {
                 Pin::new(tcp_stream).poll_shutdown(cx)
             }
@@ -177,7 +223,7 @@ impl AsyncWrite for TlsConnection {
 
 impl Connection for TlsConnection {
     fn connected(&self) -> Connected {
-        match &self.0 {
+        match &self.state {
             ConnectionState::Ready { .. } => Connected::new(),
             ConnectionState::Unencrypted(tcp_stream) => tcp_stream.connected(),
             ConnectionState::Failed(_) | ConnectionState::Handshake(_) => {
@@ -206,7 +252,7 @@ impl Accept for TlsAccept {
         Pin::new(&mut self.inner).poll_accept(cx).map(|opt_res| {
             opt_res.map(|res| match res {
                 Ok(conn) => match self.connection_type {
-                    ConnectionType::Raw => Ok(TlsConnection(ConnectionState::Unencrypted(
+                    ConnectionType::Raw => Ok(TlsConnection::new(ConnectionState::Unencrypted(
                         conn.into_inner(),
                     ))),
                     ConnectionType::Tls => {
@@ -220,7 +266,9 @@ impl Accept for TlsAccept {
                             )
                             .await
                         };
-                        Ok(TlsConnection(ConnectionState::Handshake(Box::pin(future))))
+                        Ok(TlsConnection::new(ConnectionState::Handshake(Box::pin(
+                            future,
+                        ))))
                     }
                 },
                 Err(err) => Err(Box::new(err) as Box<_>),
@@ -374,7 +422,9 @@ impl Service<Uri> for TlsConnector {
         let future = async move {
             let tcp_stream = connecting.await.map_err(box_err)?;
             match connection_type {
-                ConnectionType::Raw => Ok(TlsConnection(ConnectionState::Unencrypted(tcp_stream))),
+                ConnectionType::Raw => {
+                    Ok(TlsConnection::new(ConnectionState::Unencrypted(tcp_stream)))
+                }
                 ConnectionType::Tls => {
                     let tls_stream = tls
                         .perform_tls_client_handshake(
@@ -384,7 +434,7 @@ impl Service<Uri> for TlsConnector {
                         )
                         .await
                         .map_err(box_err)?;
-                    Ok(TlsConnection(ConnectionState::Ready {
+                    Ok(TlsConnection::new(ConnectionState::Ready {
                         stream: tls_stream,
                         peer: AuthenticatedPeer::Node(xnet_auth.node_id),
                     }))
@@ -1,5 +1,6 @@
 pub mod certified_slice_pool;
 mod proximity;
+mod query_client;
 
 #[cfg(test)]
 mod impl_tests;
@@ -48,6 +49,7 @@ use ic_xnet_hyper::{ExecuteOnRuntime, TlsConnector};
 use ic_xnet_uri::XNetAuthority;
 use prometheus::{Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge};
 pub use proximity::{GenRangeFn, ProximityMap};
+pub use query_client::XNetQueryClient;
 use rand::{rngs::StdRng, thread_rng, Rng};
 use std::{
     collections::{BTreeMap, VecDeque},
@@ -0,0 +1,342 @@
+//! Client for forwarding query calls made by composite queries to canisters
+//! hosted on other subnets, via the `/api/v1/query` `XNetEndpoint` API.
+
+use crate::Error;
+use hyper::{client::Client, header, Body, Method, Request, StatusCode, Uri};
+use ic_async_utils::receive_body_without_timeout;
+use ic_crypto_tls_interfaces::TlsHandshake;
+use ic_error_types::{ErrorCode, UserError};
+use ic_interfaces::execution_environment::CrossSubnetQueryClient;
+use ic_interfaces_registry::RegistryClient;
+use ic_logger::{warn, ReplicaLogger};
+use ic_metrics::{buckets::decimal_buckets, MetricsRegistry};
+use ic_registry_client_helpers::{node::NodeRegistry, subnet::SubnetRegistry};
+use ic_types::{
+    messages::{
+        CrossSubnetQuery, CrossSubnetQueryResponse, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64,
+    },
+    NodeId, RegistryVersion, SubnetId,
+};
+use ic_xnet_hyper::{ExecuteOnRuntime, TlsConnector};
+use ic_xnet_uri::XNetAuthority;
+use prometheus::HistogramVec;
+use rand::{seq::SliceRandom, thread_rng};
+use std::{
+    net::SocketAddr,
+    sync::{mpsc, Arc},
+    time::{Duration, Instant},
+};
+use tokio::{runtime, sync::Semaphore, task::JoinSet};
+
+const METRIC_QUERY_DURATION: &str = "xnet_query_client_request_duration_seconds";
+
+const LABEL_STATUS: &str = "status";
+const STATUS_SUCCESS: &str = "success";
+const STATUS_ERROR: &str = "error";
+const STATUS_TIMEOUT: &str = "timeout";
+const STATUS_REJECTED: &str = "rejected";
+
+/// Maximum size of a `CrossSubnetQueryResponse`: the maximum inter-canister
+/// payload plus headroom for an error message and the CBOR envelope.
+const MAX_RESPONSE_BODY_SIZE_BYTES: usize =
+    MAX_INTER_CANISTER_PAYLOAD_IN_BYTES_U64 as usize + 64 * 1024;
+
+/// Forwards `CrossSubnetQueries` to `f + 1` randomly picked nodes of the
+/// subnet hosting the callee and only accepts a response that all of them
+/// agree on, so that a single faulty node cannot forge the response.
+///
+/// The requests are driven by the given runtime; the calling query execution
+/// thread waits for them without entering the runtime. To keep query execution
+/// threads available for local queries and for the queries of other subnets,
+/// at most `max_concurrent_queries` cross-subnet query calls may be in flight
+/// at any time and further ones are rejected.
+pub struct XNetQueryClient {
+    /// An HTTP client to be used for querying.
+    http_client: Client<TlsConnector, Body>,
+
+    /// Runtime that drives the HTTP client.
+    runtime_handle: runtime::Handle,
+
+    registry: Arc<dyn RegistryClient>,
+
+    /// Bounds the number of cross-subnet query calls in flight.
+    in_flight: Semaphore,
+
+    /// Request duration, by outcome.
+    request_duration: HistogramVec,
+
+    log: ReplicaLogger,
+}
+
+impl XNetQueryClient {
+    pub fn new(
+        registry: Arc<dyn RegistryClient>,
+        tls: Arc<dyn TlsHandshake + Send + Sync>,
+        runtime_handle: runtime::Handle,
+        max_concurrent_queries: usize,
+        metrics_registry: &MetricsRegistry,
+        log: ReplicaLogger,
+    ) -> Self {
+        let http_client: Client<TlsConnector, _> = Client::builder()
+            .pool_idle_timeout(Some(Duration::from_secs(600)))
+            .executor(ExecuteOnRuntime(runtime_handle.clone()))
+            .build(
+                #[cfg(not(test))]
+                TlsConnector::new(tls),
+                #[cfg(test)]
+                TlsConnector::new_for_tests(tls),
+            );
+
+        let request_duration = metrics_registry.histogram_vec(
+            METRIC_QUERY_DURATION,
+            "The time it took to forward a query call to another subnet, by outcome.",
+            // 1ms - 50s
+            decimal_buckets(-3, 1),
+            &[LABEL_STATUS],
+        );
+        for status in &[
+            STATUS_SUCCESS,
+            STATUS_ERROR,
+            STATUS_TIMEOUT,
+            STATUS_REJECTED,
+        ] {
+            request_duration.with_label_values(&[status]);
+        }
+
+        Self {
+            http_client,
+            runtime_handle,
+            registry,
+            in_flight: Semaphore::new(max_concurrent_queries),
+            request_duration,
+            log,
+        }
+    }
+
+    /// Returns the `/api/v1/query` `XNetEndpoint` URLs of `f + 1` random nodes
+    /// on `subnet_id`, where `f` is the number of faulty nodes that the subnet
+    /// tolerates.
+    fn query_urls(&self, subnet_id: SubnetId) -> Result<Vec<Uri>, Error> {
+        let version = self.registry.get_latest_version();
+        let nodes = self
+            .registry
+            .get_node_ids_on_subnet(subnet_id, version)
+            .map_err(|e| Error::RegistryGetSubnetInfoFailed(subnet_id, e))?
+            .filter(|nodes| !nodes.is_empty())
+            .ok_or(Error::MissingSubnet(subnet_id))?;
+        let max_faulty_nodes = (nodes.len() - 1) / 3;
+
+        nodes
+            .choose_multiple(&mut thread_rng(), max_faulty_nodes + 1)
+            .map(|node| self.query_url(*node, version))
+            .collect()
+    }
+
+    /// Returns the `/api/v1/query` `XNetEndpoint` URL of `node`.
+    fn query_url(&self, node: NodeId, version: RegistryVersion) -> Result<Uri, Error> {
+        let xnet_endpoint = self
+            .registry
+            .get_node_record(node, version)
+            .map_err(|e| Error::RegistryGetNodeInfoFailed(node, e))?
+            .and_then(|node_record| node_record.xnet)
+            .ok_or(Error::MissingXNetEndpoint(node))?;
+        let address = SocketAddr::new(
+            xnet_endpoint.ip_addr.parse().map_err(|_| {
+                Error::InvalidXNetEndpoint(node, format!("bad ip addr {}", xnet_endpoint.ip_addr))
+            })?,
+            u16::try_from(xnet_endpoint.port).map_err(|_| {
+                Error::InvalidXNetEndpoint(node, format!("bad port {}", xnet_endpoint.port))
+            })?,
+        );
+        let authority = XNetAuthority {
+            node_id: node,
+            registry_version: version,
+            address,
+        };
+
+        let url = format!("http://{}/api/v1/query", authority);
+        url.parse::<Uri>()
+            .map_err(|e| Error::InvalidXNetEndpoint(node, format!("bad url {}: {}", url, e)))
+    }
+}
+
+/// Sends `query` to `url` and decodes the response.
+async fn send(
+    http_client: Client<TlsConnector, Body>,
+    url: Uri,
+    query: CrossSubnetQuery,
+) -> Result<CrossSubnetQueryResponse, String> {
+    let body = serde_cbor::to_vec(&query).map_err(|e| e.to_string())?;
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(url)
+        .header(header::CONTENT_TYPE, "application/cbor")
+        .body(Body::from(body))
+        .map_err(|e| e.to_string())?;
+
+    let response = http_client
+        .request(request)
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = response.status();
+    let bytes =
+        receive_body_without_timeout(response.into_body(), MAX_RESPONSE_BODY_SIZE_BYTES.into())
+            .await
+            .map_err(|e| e.to_string())?;
+    if status != StatusCode::OK {
+        return Err(format!(
+            "{}: {}",
+            status,
+            String::from_utf8_lossy(bytes.as_ref())
+        ));
+    }
+    serde_cbor::from_slice(bytes.as_ref()).map_err(|e| e.to_string())
+}
+
+/// Returns the response that all replicas agree on, or an error if any of them
+/// failed or they returned different results.
+///
+/// Replicas may execute the query at slightly different heights, so only the
+/// results are compared and the largest number of executed instructions is
+/// charged.
+fn agreed_response(
+    responses: Vec<Result<CrossSubnetQueryResponse, String>>,
+) -> Result<CrossSubnetQueryResponse, String> {
+    let mut agreed: Option<CrossSubnetQueryResponse> = None;
+    for response in responses {
+        let response = response?;
+        agreed = match agreed {
+            None => Some(response),
+            Some(agreed) if agreed.result == response.result => Some(CrossSubnetQueryResponse {
+                result: agreed.result,
+                instructions_executed: agreed
+                    .instructions_executed
+                    .max(response.instructions_executed),
+            }),
+            Some(_) => return Err("replicas returned different responses".to_string()),
+        };
+    }
+    agreed.ok_or_else(|| "no replica was queried".to_string())
+}
+
+impl CrossSubnetQueryClient for XNetQueryClient {
+    /// Forwards `query` to `f + 1` nodes of `subnet_id`, waiting until their
+    /// responses arrive or the query's walltime budget is exhausted.
+    ///
+    /// Rejects the query call right away if `max_concurrent_queries` calls are
+    /// already in flight.
+    fn query(
+        &self,
+        subnet_id: SubnetId,
+        query: CrossSubnetQuery,
+    ) -> Result<CrossSubnetQueryResponse, UserError> {
+        let start = Instant::now();
+        let _permit = match self.in_flight.try_acquire() {
+            Ok(permit) => permit,
+            Err(_) => {
+                self.request_duration
+                    .with_label_values(&[STATUS_REJECTED])
+                    .observe(start.elapsed().as_secs_f64());
+                return Err(UserError::new(
+                    ErrorCode::QueryCallGraphInternal,
+                    format!(
+                        "Too many query calls to other subnets in flight, cannot forward \
+                         the query call to {} on subnet {}",
+                        query.receiver, subnet_id
+                    ),
+                ));
+            }
+        };
+
+        let urls = self.query_urls(subnet_id).map_err(|e| {
+            UserError::new(
+                ErrorCode::QueryCallGraphInternal,
+                format!("Failed to locate subnet {}: {}", subnet_id, e),
+            )
+        })?;
+
+        // The requests are driven by the runtime and the responses are handed
+        // back over a channel, so that this thread never blocks the runtime.
+        let timeout = Duration::from_millis(query.max_walltime_millis);
+        let (response_sender, response_receiver) = mpsc::sync_channel(1);
+        let http_client = self.http_client.clone();
+        let receiver = query.receiver;
+        self.runtime_handle.spawn(async move {
+            let mut requests = JoinSet::new();
+            for url in urls {
+                requests.spawn(send(http_client.clone(), url, query.clone()));
+            }
+            // On timeout, dropping the `JoinSet` aborts the pending requests.
+            let responses = tokio::time::timeout(timeout, async move {
+                let mut responses = Vec::with_capacity(requests.len());
+                while let Some(response) = requests.join_next().await {
+                    responses.push(response.unwrap_or_else(|e| Err(e.to_string())));
+                }
+                responses
+            })
+            .await;
+            let _ = response_sender.send(responses.map(agreed_response));
+        });
+        let result = response_receiver
+            .recv()
+            .expect("The cross-subnet query task was dropped before sending the response.");
+
+        let (status, result) = match result {
+            Ok(Ok(response)) => (STATUS_SUCCESS, Ok(response)),
+            Ok(Err(err)) => {
+                warn!(
+                    self.log,
+                    "Failed to forward query call to {} on subnet {}: {}", receiver, subnet_id, err
+                );
+                (
+                    STATUS_ERROR,
+                    Err(UserError::new(
+                        ErrorCode::QueryCallGraphInternal,
+                        format!(
+                            "Failed to forward query call to {} on subnet {}: {}",
+                            receiver, subnet_id, err
+                        ),
+                    )),
+                )
+            }
+            Err(_) => (
+                STATUS_TIMEOUT,
+                Err(UserError::new(
+                    ErrorCode::QueryTimeLimitExceeded,
+                    format!(
+                        "Query call to {} on subnet {} timed out",
+                        receiver, subnet_id
+                    ),
+                )),
+            ),
+        };
+        self.request_duration
+            .with_label_values(&[status])
+            .observe(start.elapsed().as_secs_f64());
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_types::messages::Payload;
+
+    fn response(data: u8, instructions_executed: u64) -> CrossSubnetQueryResponse {
+        CrossSubnetQueryResponse {
+            result: Ok(Payload::Data(vec![data])),
+            instructions_executed,
+        }
+    }
+
+    #[test]
+    fn agreed_response_requires_identical_results() {
+        assert_eq!(
+            agreed_response(vec![Ok(response(1, 10)), Ok(response(1, 30))]),
+            Ok(response(1, 30))
+        );
+        assert!(agreed_response(vec![Ok(response(1, 10)), Ok(response(2, 10))]).is_err());
+        assert!(agreed_response(vec![Ok(response(1, 10)), Err("down".to_string())]).is_err());
+        assert!(agreed_response(vec![]).is_err());
+    }
+}